@@ -0,0 +1,34 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.keytrans;
+
+import static org.signal.libsignal.internal.FilterExceptions.filterExceptions;
+
+import org.signal.libsignal.internal.Native;
+import org.signal.libsignal.internal.NativeHandleGuard;
+
+/**
+ * An E.164 to look up in key transparency, along with the account's unidentified access key.
+ *
+ * <p>The unidentified access key provides the same guess protection for the E.164 as the ACI
+ * identity key does for the ACI.
+ */
+public class E164SearchKey extends NativeHandleGuard.SimpleOwner {
+  /**
+   * @param e164 string representation of the E.164, starting with '+'.
+   * @param unidentifiedAccessKey the account's unidentified access key. Optional.
+   * @throws IllegalArgumentException if {@code e164} is not a valid E.164, or if {@code
+   *     unidentifiedAccessKey} is not 16 bytes long.
+   */
+  public E164SearchKey(final String e164, final byte[] unidentifiedAccessKey) {
+    super(filterExceptions(() -> Native.E164SearchKey_New(e164, unidentifiedAccessKey)));
+  }
+
+  @Override
+  protected void release(long nativeHandle) {
+    Native.E164SearchKey_Destroy(nativeHandle);
+  }
+}
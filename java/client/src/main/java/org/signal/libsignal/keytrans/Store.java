@@ -32,6 +32,19 @@ public interface Store {
 
   void setAccountData(ServiceId.Aci aci, byte[] data);
 
+  /**
+   * The key rotations applied so far, as last passed to {@link #setKeyRotations}.
+   *
+   * <p>The default implementation stores nothing, so any rotation is forgotten once the {@link
+   * org.signal.libsignal.net.KeyTransparencyClient} operation that applied it completes. Override
+   * both methods to persist them.
+   */
+  default Optional<byte[]> getKeyRotations() {
+    return Optional.empty();
+  }
+
+  default void setKeyRotations(byte[] keyRotations) {}
+
   default void applyUpdates(ServiceId.Aci aci, SearchResult searchResult) {
     searchResult.updateStore(aci, this);
   }
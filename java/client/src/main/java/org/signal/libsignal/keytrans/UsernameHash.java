@@ -0,0 +1,27 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.keytrans;
+
+import static org.signal.libsignal.internal.FilterExceptions.filterExceptions;
+
+import org.signal.libsignal.internal.Native;
+import org.signal.libsignal.internal.NativeHandleGuard;
+
+/** A username hash to look up in key transparency, checked to be the right length. */
+public class UsernameHash extends NativeHandleGuard.SimpleOwner {
+  /**
+   * @param hash the hash of the username, as computed by {@code Username.getHash()}.
+   * @throws IllegalArgumentException if {@code hash} is not 32 bytes long.
+   */
+  public UsernameHash(final byte[] hash) {
+    super(filterExceptions(() -> Native.KeyTransUsernameHash_New(hash)));
+  }
+
+  @Override
+  protected void release(long nativeHandle) {
+    Native.KeyTransUsernameHash_Destroy(nativeHandle);
+  }
+}
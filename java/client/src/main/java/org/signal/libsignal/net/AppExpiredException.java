@@ -5,9 +5,30 @@
 
 package org.signal.libsignal.net;
 
+import java.util.Optional;
+
 /** Indicates that the local application is too old, and was rejected by the server. */
 public class AppExpiredException extends ChatServiceException {
+  private final String minimumVersion;
+  private final String upgradeUrl;
+
   public AppExpiredException(String message) {
+    this(message, null, null);
+  }
+
+  public AppExpiredException(String message, String minimumVersion, String upgradeUrl) {
     super(message);
+    this.minimumVersion = minimumVersion;
+    this.upgradeUrl = upgradeUrl;
+  }
+
+  /** The oldest app version the server still accepts, if it said. */
+  public Optional<String> getMinimumVersion() {
+    return Optional.ofNullable(minimumVersion);
+  }
+
+  /** An {@code https} URL to get a newer version of the app from, if the server said. */
+  public Optional<String> getUpgradeUrl() {
+    return Optional.ofNullable(upgradeUrl);
   }
 }
@@ -47,6 +47,7 @@ public class AuthenticatedChatConnection extends ChatConnection {
                             username,
                             password,
                             receiveStories)
+                        .withCancellationHandler(tokioAsyncContext::cancel)
                         .thenApply(
                             nativeHandle ->
                                 new AuthenticatedChatConnection(
@@ -175,9 +176,9 @@ public class AuthenticatedChatConnection extends ChatConnection {
       long nativeAsyncContextHandle,
       long nativeChatConnectionHandle,
       long nativeRequestHandle,
-      int timeoutMillis) {
+      long deadlineMillis) {
     return Native.AuthenticatedChatConnection_send(
-        nativeAsyncContextHandle, nativeChatConnectionHandle, nativeRequestHandle, timeoutMillis);
+        nativeAsyncContextHandle, nativeChatConnectionHandle, nativeRequestHandle, deadlineMillis);
   }
 
   @Override
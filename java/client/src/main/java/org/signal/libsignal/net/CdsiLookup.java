@@ -50,6 +50,7 @@ class CdsiLookup implements NativeHandleGuard.Owner {
               username,
               password,
               nativeRequest.getHandle())
+          .withCancellationHandler(network.getAsyncContext()::cancel)
           .thenApply((Long nativeHandle) -> new CdsiLookup(nativeHandle, network));
     }
   }
@@ -58,6 +59,7 @@ class CdsiLookup implements NativeHandleGuard.Owner {
     try (NativeHandleGuard asyncRuntime = new NativeHandleGuard(this.network.getAsyncContext());
         NativeHandleGuard self = new NativeHandleGuard(this)) {
       return Native.CdsiLookup_complete(asyncRuntime.nativeHandle(), self.nativeHandle())
+          .withCancellationHandler(this.network.getAsyncContext()::cancel)
           .thenApply(response -> (CdsiLookupResponse) response);
     }
   }
@@ -74,6 +74,18 @@ public abstract class ChatConnection extends NativeHandleGuard.SimpleOwner {
       chat.chatListener.onReceivedAlerts(chat, alerts);
     }
 
+    public void onUnrecognizedRequest(String path, byte[] body, long sendAckHandle) {
+      ChatConnection chat = this.chat.get();
+      if (chat == null) return;
+      if (chat.chatListener == null) return;
+
+      chat.chatListener.onUnrecognizedRequest(
+          chat,
+          path,
+          body,
+          new ChatConnectionListener.ServerMessageAck(chat.tokioAsyncContext, sendAckHandle));
+    }
+
     public void onConnectionInterrupted(Throwable disconnectReason) {
       ChatConnection chat = this.chat.get();
       if (chat == null) return;
@@ -136,7 +148,8 @@ public abstract class ChatConnection extends NativeHandleGuard.SimpleOwner {
               asyncContextHandle.nativeHandle(),
               chatConnectionHandle.nativeHandle(),
               requestHandle.nativeHandle(),
-              req.timeoutMillis)
+              System.currentTimeMillis() + req.timeoutMillis)
+          .withCancellationHandler(tokioAsyncContext::cancel)
           .thenApply(o -> (Response) o);
     }
   }
@@ -150,7 +163,7 @@ public abstract class ChatConnection extends NativeHandleGuard.SimpleOwner {
       long nativeAsyncContextHandle,
       long nativeChatConnectionHandle,
       long nativeRequestHandle,
-      int timeoutMillis);
+      long deadlineMillis);
 
   protected abstract void startWrapper(
       long nativeChatConnectionHandle, BridgeChatListener listener);
@@ -40,6 +40,23 @@ public interface ChatConnectionListener {
    */
   default void onReceivedAlerts(ChatConnection chat, String[] alerts) {}
 
+  /**
+   * Called when the server sends a request that doesn't correspond to any of the other callbacks.
+   *
+   * <p>{@param body} is empty if the request didn't have one. Respond to the server with {@param
+   * sendAck}'s {@code sendStatus} method.
+   *
+   * <p>The default implementation responds with 404 Not Found.
+   */
+  default void onUnrecognizedRequest(
+      ChatConnection chat, String path, byte[] body, ServerMessageAck sendAck) {
+    try {
+      sendAck.sendStatus(404);
+    } catch (ChatServiceException e) {
+      // The connection is already gone, so there's no one left to respond to.
+    }
+  }
+
   /**
    * Called when the client gets disconnected from the server.
    *
@@ -82,5 +99,20 @@ public interface ChatConnectionListener {
             guardedRunChecked(Native::ServerMessageAck_Send);
           });
     }
+
+    /**
+     * Responds to a server request from {@link #onUnrecognizedRequest} with an HTTP status code.
+     *
+     * @throws ChatServiceInactiveException if the connection is already terminated.
+     * @throws ChatServiceException if the send fails for another reason.
+     */
+    public void sendStatus(int status) throws ChatServiceInactiveException, ChatServiceException {
+      FilterExceptions.filterExceptions(
+          ChatServiceInactiveException.class,
+          ChatServiceException.class,
+          () -> {
+            guardedRunChecked(ack -> Native.ServerMessageAck_SendStatus(ack, status));
+          });
+    }
   }
 }
@@ -0,0 +1,33 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.net;
+
+import java.util.OptionalInt;
+
+/**
+ * Indicates that the chat connection was closed by the server or the network.
+ *
+ * <p>Unlike an idle timeout, which is reported as {@link ChatServiceInactiveException}, this might
+ * point to a problem with the server or the network.
+ */
+public class ConnectionLostException extends ChatServiceException {
+  private final Integer closeCode;
+
+  public ConnectionLostException(String message) {
+    super(message);
+    this.closeCode = null;
+  }
+
+  public ConnectionLostException(String message, int closeCode) {
+    super(message);
+    this.closeCode = closeCode;
+  }
+
+  /** The code from the server's close frame, if the server sent one. */
+  public OptionalInt getCloseCode() {
+    return closeCode == null ? OptionalInt.empty() : OptionalInt.of(closeCode);
+  }
+}
@@ -5,12 +5,16 @@
 
 package org.signal.libsignal.net;
 
+import static org.signal.libsignal.internal.FilterExceptions.filterExceptions;
+
 import java.util.Optional;
 import org.signal.libsignal.internal.CompletableFuture;
 import org.signal.libsignal.internal.Native;
 import org.signal.libsignal.internal.NativeHandleGuard;
+import org.signal.libsignal.keytrans.E164SearchKey;
 import org.signal.libsignal.keytrans.SearchResult;
 import org.signal.libsignal.keytrans.Store;
+import org.signal.libsignal.keytrans.UsernameHash;
 import org.signal.libsignal.protocol.IdentityKey;
 import org.signal.libsignal.protocol.ServiceId;
 
@@ -30,6 +34,9 @@ import org.signal.libsignal.protocol.ServiceId;
  * </ul>
  */
 public class KeyTransparencyClient {
+  /** How long a single key transparency operation, including all its requests, may take. */
+  private static final long OPERATION_TIMEOUT_MILLIS = 30_000;
+
   private final TokioAsyncContext tokioAsyncContext;
   private final UnauthenticatedChatConnection chatConnection;
   private final Network.Environment environment;
@@ -59,6 +66,8 @@ public class KeyTransparencyClient {
    * <ul>
    *   <li>{@link ChatServiceException} for errors related to communication with the server.
    *       Depending on the severity, the search can be retried.
+   *   <li>{@link RetryLaterException} if the server is rate limiting requests and said how
+   *       long to wait.
    *   <li>{@link KeyTransparencyException} for errors related to key transparency logic. Retrying
    *       the search without changing any of the arguments (including the state of the store) is
    *       unlikely to yield a different result.
@@ -69,9 +78,8 @@ public class KeyTransparencyClient {
    *     transparency is used to request this information, passing it in the request lets chat
    *     server know that it is not a random guess and that the caller of this API has communicated
    *     with the ACI.
-   * @param e164 string representation of an E.164 number associated with the account. Optional.
-   * @param unidentifiedAccessKey provides the same guess protections as the ACI identity key. This
-   *     parameter has the same optionality as the E.164 parameter.
+   * @param e164 E.164 number associated with the account, with its unidentified access key.
+   *     Optional.
    * @param usernameHash hash of the username associated with the account. Optional.
    * @param store local persistent storage for key transparency-related data, such as the latest
    *     tree heads and account monitoring data. It will be queried for data before performing the
@@ -85,23 +93,21 @@ public class KeyTransparencyClient {
   public CompletableFuture<SearchResult> search(
       /* @NotNull */ final ServiceId.Aci aci,
       /* @NotNull */ final IdentityKey aciIdentityKey,
-      final String e164,
-      final byte[] unidentifiedAccessKey,
-      final byte[] usernameHash,
+      final E164SearchKey e164,
+      final UsernameHash usernameHash,
       final Store store) {
     Optional<byte[]> lastDistinguishedTreeHead = store.getLastDistinguishedTreeHead();
     if (lastDistinguishedTreeHead.isEmpty()) {
       return this.updateDistinguished(store)
-          .thenCompose(
-              (ignored) ->
-                  this.search(
-                      aci, aciIdentityKey, e164, unidentifiedAccessKey, usernameHash, store));
+          .thenCompose((ignored) -> this.search(aci, aciIdentityKey, e164, usernameHash, store));
     }
     // Decoding of the last distinguished tree head happens "eagerly" before making any network
     // requests.
     // It may result in an IllegalArgumentException.
     try (NativeHandleGuard tokioContextGuard = this.tokioAsyncContext.guard();
-        NativeHandleGuard identityKeyGuard = aciIdentityKey.getPublicKey().guard()) {
+        NativeHandleGuard identityKeyGuard = aciIdentityKey.getPublicKey().guard();
+        NativeHandleGuard e164Guard = new NativeHandleGuard(e164);
+        NativeHandleGuard usernameHashGuard = new NativeHandleGuard(usernameHash)) {
       NativeHandleGuard chatConnectionGuard = new NativeHandleGuard(chatConnection);
       return Native.KeyTransparency_Search(
               tokioContextGuard.nativeHandle(),
@@ -109,11 +115,13 @@ public class KeyTransparencyClient {
               chatConnectionGuard.nativeHandle(),
               aci.toServiceIdFixedWidthBinary(),
               identityKeyGuard.nativeHandle(),
-              e164,
-              unidentifiedAccessKey,
-              usernameHash,
+              e164Guard.nativeHandle(),
+              usernameHashGuard.nativeHandle(),
               store.getAccountData(aci).orElse(null),
-              lastDistinguishedTreeHead.get())
+              lastDistinguishedTreeHead.get(),
+              store.getKeyRotations().orElse(null),
+              operationDeadline())
+          .withCancellationHandler(this.tokioAsyncContext::cancel)
           .thenApply(
               (handle) -> {
                 SearchResult result = new SearchResult(handle);
@@ -134,6 +142,8 @@ public class KeyTransparencyClient {
    * <ul>
    *   <li>{@link ChatServiceException} for errors related to communication with the server.
    *       Depending on the severity, the request can be retried.
+   *   <li>{@link RetryLaterException} if the server is rate limiting requests and said how
+   *       long to wait.
    *   <li>{@link KeyTransparencyException} for the errors related to key transparency logic.
    *       Retrying the search without changing any of the arguments (including the state of the
    *       store) is unlikely to produce a different result.
@@ -157,7 +167,10 @@ public class KeyTransparencyClient {
               tokioContextGuard.nativeHandle(),
               this.environment.value,
               chatConnectionGuard.nativeHandle(),
-              lastDistinguished)
+              lastDistinguished,
+              store.getKeyRotations().orElse(null),
+              operationDeadline())
+          .withCancellationHandler(this.tokioAsyncContext::cancel)
           .thenApply(
               bytes -> {
                 store.setLastDistinguishedTreeHead(bytes);
@@ -188,9 +201,13 @@ public class KeyTransparencyClient {
    * <ul>
    *   <li>{@link ChatServiceException} for errors related to communication with the server.
    *       Depending on the severity, the search can be retried.
+   *   <li>{@link RetryLaterException} if the server is rate limiting requests and said how
+   *       long to wait.
    *   <li>{@link KeyTransparencyException} for errors related to key transparency logic. Retrying
    *       the search without changing any of the arguments (including the state of the store) is
    *       unlikely to yield a different result.
+   *   <li>{@link IllegalArgumentException} if the arguments don't match the state in the store,
+   *       for example if the store has no data for the account yet.
    * </ul>
    *
    * @param aci the ACI of the account to be searched for. Required.
@@ -198,9 +215,8 @@ public class KeyTransparencyClient {
    *     transparency is used to request this information, passing it in the request lets chat
    *     server know that it is not a random guess and that the caller of this API has communicated
    *     with the ACI.
-   * @param e164 string representation of an E.164 number associated with the account. Optional.
-   * @param unidentifiedAccessKey provides the same guess protections as the ACI identity key. This
-   *     parameter has the same optionality as the E.164 parameter.
+   * @param e164 E.164 number associated with the account, with its unidentified access key.
+   *     Optional.
    * @param usernameHash hash of the username associated with the account. Optional.
    * @param store local persistent storage for key transparency-related data, such as the latest
    *     tree heads and account monitoring data. It will be queried for data before performing the
@@ -214,20 +230,18 @@ public class KeyTransparencyClient {
   public CompletableFuture<Void> monitor(
       /* @NotNull */ final ServiceId.Aci aci,
       /* @NotNull */ final IdentityKey aciIdentityKey,
-      final String e164,
-      final byte[] unidentifiedAccessKey,
-      final byte[] usernameHash,
+      final E164SearchKey e164,
+      final UsernameHash usernameHash,
       final Store store) {
     Optional<byte[]> lastDistinguishedTreeHead = store.getLastDistinguishedTreeHead();
     if (lastDistinguishedTreeHead.isEmpty()) {
       return this.updateDistinguished(store)
-          .thenCompose(
-              (ignored) ->
-                  this.monitor(
-                      aci, aciIdentityKey, e164, unidentifiedAccessKey, usernameHash, store));
+          .thenCompose((ignored) -> this.monitor(aci, aciIdentityKey, e164, usernameHash, store));
     }
     try (NativeHandleGuard tokioContextGuard = this.tokioAsyncContext.guard();
         NativeHandleGuard identityKeyGuard = aciIdentityKey.getPublicKey().guard();
+        NativeHandleGuard e164Guard = new NativeHandleGuard(e164);
+        NativeHandleGuard usernameHashGuard = new NativeHandleGuard(usernameHash);
         NativeHandleGuard chatConnectionGuard = new NativeHandleGuard(chatConnection)) {
       return Native.KeyTransparency_Monitor(
               tokioContextGuard.nativeHandle(),
@@ -235,13 +249,15 @@ public class KeyTransparencyClient {
               chatConnectionGuard.nativeHandle(),
               aci.toServiceIdFixedWidthBinary(),
               identityKeyGuard.nativeHandle(),
-              e164,
-              unidentifiedAccessKey,
-              usernameHash,
+              e164Guard.nativeHandle(),
+              usernameHashGuard.nativeHandle(),
               // Technically this is a required parameter, but passing null
               // to generate the error on the Rust side.
               store.getAccountData(aci).orElse(null),
-              lastDistinguishedTreeHead.get())
+              lastDistinguishedTreeHead.get(),
+              store.getKeyRotations().orElse(null),
+              operationDeadline())
+          .withCancellationHandler(this.tokioAsyncContext::cancel)
           .thenApply(
               (updatedAccountData) -> {
                 store.setAccountData(aci, updatedAccountData);
@@ -249,4 +265,123 @@ public class KeyTransparencyClient {
               });
     }
   }
+
+  /**
+   * Fetch the key transparency service's latest key rotation and, if it hasn't been applied yet,
+   * verify and apply it.
+   *
+   * <p>Rotations are signed by the key being replaced, so this must be called before the old key
+   * stops being used; the rotations in the store are installed by every other operation.
+   *
+   * <p>This is an asynchronous operation; all the exceptions occurring during communication with
+   * the server will be wrapped in {@link java.util.concurrent.ExecutionException}.
+   *
+   * <p>Possible exceptions include:
+   *
+   * <ul>
+   *   <li>{@link ChatServiceException} for errors related to communication with the server.
+   *       Depending on the severity, the request can be retried.
+   *   <li>{@link RetryLaterException} if the server is rate limiting requests and said how
+   *       long to wait.
+   *   <li>{@link KeyTransparencyException} if the rotation is rejected, for example because its
+   *       signature doesn't verify or it has expired.
+   * </ul>
+   *
+   * @param store local persistent storage for key transparency related data. Its key rotations are
+   *     updated if a new one is applied.
+   * @return an instance of {@link CompletableFuture} that completes with {@code true} if a new
+   *     rotation was applied.
+   * @throws IllegalArgumentException if the store contains corrupted data.
+   */
+  public CompletableFuture<Boolean> updateKeyRotations(final Store store) {
+    try (NativeHandleGuard tokioContextGuard = this.tokioAsyncContext.guard();
+        NativeHandleGuard chatConnectionGuard = new NativeHandleGuard(chatConnection)) {
+      return Native.KeyTransparency_UpdateKeyRotations(
+              tokioContextGuard.nativeHandle(),
+              this.environment.value,
+              chatConnectionGuard.nativeHandle(),
+              store.getKeyRotations().orElse(null),
+              operationDeadline())
+          .withCancellationHandler(this.tokioAsyncContext::cancel)
+          .thenApply(
+              (updated) -> {
+                if (updated == null) {
+                  return false;
+                }
+                store.setKeyRotations(updated);
+                return true;
+              });
+    }
+  }
+
+  /**
+   * Verify and apply a key rotation document received out-of-band, such as in a push message.
+   *
+   * @param document a serialized {@code SignedKeyRotation}.
+   * @param store local persistent storage for key transparency related data. Its key rotations are
+   *     updated if the rotation is applied.
+   * @throws KeyTransparencyException if the rotation is rejected.
+   * @throws IllegalArgumentException if the document or the store's data is corrupted.
+   */
+  public void applyKeyRotation(final byte[] document, final Store store)
+      throws KeyTransparencyException {
+    byte[] updated =
+        filterExceptions(
+            KeyTransparencyException.class,
+            () ->
+                Native.KeyTransparency_ApplyKeyRotation(
+                    this.environment.value, store.getKeyRotations().orElse(null), document));
+    store.setKeyRotations(updated);
+  }
+
+  /**
+   * Packs the key transparency state kept for an account, so that it can be moved to another device
+   * during device transfer.
+   *
+   * <p>The result carries a checksum, so {@link #importState} rejects an export that was cut short
+   * or corrupted instead of saving it.
+   *
+   * @param aci the account whose state is exported.
+   * @param store local persistent storage for key transparency related data.
+   * @return the exported state, or empty if the store has no account data for {@code aci} or no
+   *     distinguished tree head.
+   * @throws IllegalArgumentException if the store contains corrupted data.
+   */
+  public static Optional<byte[]> exportState(final ServiceId.Aci aci, final Store store) {
+    final Optional<byte[]> accountData = store.getAccountData(aci);
+    final Optional<byte[]> lastDistinguishedTreeHead = store.getLastDistinguishedTreeHead();
+    if (!accountData.isPresent() || !lastDistinguishedTreeHead.isPresent()) {
+      return Optional.empty();
+    }
+    return Optional.of(
+        filterExceptions(
+            () ->
+                Native.KeyTransparency_ExportState(
+                    accountData.get(), lastDistinguishedTreeHead.get())));
+  }
+
+  /**
+   * Saves key transparency state produced by {@link #exportState} on another device.
+   *
+   * @param aci the account the state was exported for.
+   * @param exported the result of {@link #exportState}.
+   * @param store local persistent storage for key transparency related data. The account data for
+   *     {@code aci} and the last distinguished tree head are replaced.
+   * @throws IllegalArgumentException if {@code exported} is truncated, corrupted, or from an
+   *     unsupported version. Nothing is saved in that case.
+   */
+  public static void importState(final ServiceId.Aci aci, final byte[] exported, final Store store) {
+    final long state = filterExceptions(() -> Native.KeyTransparency_ImportState(exported));
+    try {
+      store.setAccountData(aci, Native.ImportedKeyTransState_GetAccountData(state));
+      store.setLastDistinguishedTreeHead(
+          Native.ImportedKeyTransState_GetLastDistinguishedTreeHead(state));
+    } finally {
+      Native.ImportedKeyTransState_Destroy(state);
+    }
+  }
+
+  private static long operationDeadline() {
+    return System.currentTimeMillis() + OPERATION_TIMEOUT_MILLIS;
+  }
 }
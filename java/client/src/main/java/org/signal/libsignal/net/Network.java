@@ -8,7 +8,14 @@ package org.signal.libsignal.net;
 import static org.signal.libsignal.internal.FilterExceptions.filterExceptions;
 
 import java.io.IOException;
+import java.net.InetAddress;
+import java.time.Duration;
+import java.util.Collections;
+import java.util.List;
+import java.util.Map;
+import java.util.Optional;
 import java.util.concurrent.ExecutionException;
+import java.util.stream.Collectors;
 import java.util.function.Consumer;
 import org.signal.libsignal.internal.CompletableFuture;
 import org.signal.libsignal.internal.Native;
@@ -28,6 +35,40 @@ public class Network {
     }
   }
 
+  /** The kind of network the device is using. See {@link #setNetworkType(NetworkType)}. */
+  public enum NetworkType {
+    UNKNOWN(0),
+    WIFI(1),
+    CELLULAR(2),
+    VPN(3);
+
+    private final int value;
+
+    NetworkType(int value) {
+      this.value = value;
+    }
+  }
+
+  /** A Signal service. See {@link #setEndpointIpHints}. */
+  public enum ServiceKind {
+    CHAT(0),
+    CDSI(1),
+    SVR(2);
+
+    private final int value;
+
+    ServiceKind(int value) {
+      this.value = value;
+    }
+  }
+
+  /** How responsive the chat connection is. See {@link #chatConnectionQuality()}. */
+  public enum ConnectionQuality {
+    GOOD,
+    OK,
+    BAD
+  }
+
   /**
    * The "scheme" for Signal TLS proxies. See {@link #setProxy(String, String, Integer, String,
    * String)}.
@@ -63,7 +104,32 @@ public class Network {
    */
   public void setProxy(String scheme, String host, Integer port, String username, String password)
       throws IOException {
-    this.connectionManager.setProxy(scheme, host, port, username, password);
+    this.connectionManager.setProxy(
+        scheme, host, port, username, password, Collections.emptyMap());
+  }
+
+  /**
+   * Sets the proxy host to be used for all new connections (until overridden), sending extra
+   * headers with each HTTP CONNECT request.
+   *
+   * <p>Behaves like {@link #setProxy(String, String, Integer, String, String)}, but HTTP proxies
+   * will also be sent {@code connectHeaders}, such as a bearer token in {@code
+   * Proxy-Authorization} or a header identifying the tenant.
+   *
+   * @throws IOException if the parameters are invalid as described for the other overload, if the
+   *     scheme is not {@code http} or {@code https} and {@code connectHeaders} is not empty, or if
+   *     the headers are invalid (e.g. {@code Host}, or {@code Proxy-Authorization} along with a
+   *     username).
+   */
+  public void setProxy(
+      String scheme,
+      String host,
+      Integer port,
+      String username,
+      String password,
+      Map<String, String> connectHeaders)
+      throws IOException {
+    this.connectionManager.setProxy(scheme, host, port, username, password, connectHeaders);
   }
 
   /**
@@ -90,7 +156,8 @@ public class Network {
       username = host.substring(0, atIndex);
       host = host.substring(atIndex + 1);
     }
-    this.connectionManager.setProxy(SIGNAL_TLS_PROXY_SCHEME, host, port, username, null);
+    this.connectionManager.setProxy(
+        SIGNAL_TLS_PROXY_SCHEME, host, port, username, null, Collections.emptyMap());
   }
 
   /**
@@ -131,6 +198,68 @@ public class Network {
     this.connectionManager.setCensorshipCircumventionEnabled(enabled);
   }
 
+  /**
+   * Tells libsignal what kind of network the device is using.
+   *
+   * <p>This is used to adjust timeouts and route ordering for <em>new</em> connections. On a VPN,
+   * domain-fronted routes are not tried when a direct route is available. It is not itself a
+   * network change; call {@link #onNetworkChange()} as well if the network changed.
+   */
+  public void setNetworkType(NetworkType networkType) {
+    this.connectionManager.setNetworkType(networkType);
+  }
+
+  /**
+   * Adds {@code addresses} as candidates when connecting to {@code service} for the next {@code
+   * ttl}.
+   *
+   * <p>This is for addresses the app learned about out-of-band, e.g. during a censorship event.
+   * They're tried after the addresses found by resolving the service's hostname, and are used on
+   * their own if resolution fails. Replaces any hints previously set for {@code service}. The TTL
+   * is capped at one week.
+   */
+  public void setEndpointIpHints(ServiceKind service, List<InetAddress> addresses, Duration ttl) {
+    this.connectionManager.setEndpointIpHints(service, addresses, ttl);
+  }
+
+  /** Discards any addresses set by {@link #setEndpointIpHints} for {@code service}. */
+  public void clearEndpointIpHints(ServiceKind service) {
+    this.connectionManager.clearEndpointIpHints(service);
+  }
+
+  /**
+   * How responsive the authenticated chat connection is.
+   *
+   * <p>Based on the round-trip times of the connection's handshake and keepalive pings, so checking
+   * this doesn't send anything. Empty if there's no connected authenticated chat connection.
+   */
+  public Optional<ConnectionQuality> chatConnectionQuality() {
+    return this.connectionManager.chatConnectionQuality();
+  }
+
+  /**
+   * Sets where {@link #chatConnectionQuality()} draws the lines between levels.
+   *
+   * <p>Round trips faster than {@code goodBelow} are good, and ones slower than {@code badAbove}
+   * are bad.
+   *
+   * @throws IllegalArgumentException if {@code goodBelow} is longer than {@code badAbove}
+   */
+  public void setConnectionQualityThresholds(Duration goodBelow, Duration badAbove) {
+    this.connectionManager.setConnectionQualityThresholds(goodBelow, badAbove);
+  }
+
+  /**
+   * The most recent connection attempts, oldest first, as a JSON array suitable for attaching to a
+   * bug report.
+   *
+   * <p>Each event only has a timestamp, a duration, and coarse categories for the service, route,
+   * and outcome, so the export never contains addresses or anything else that identifies the user.
+   */
+  public String exportRecentNetworkEvents() {
+    return connectionManager.guardedMap(Native::ConnectionManager_export_recent_network_events);
+  }
+
   /**
    * Notifies libsignal that the network has changed.
    *
@@ -140,6 +269,16 @@ public class Network {
     connectionManager.guardedRun(Native::ConnectionManager_on_network_change);
   }
 
+  /**
+   * Like {@link #onNetworkChange()}, but takes effect even if the network was reported as changed
+   * very recently.
+   *
+   * <p>Meant for explicit user actions, like a "retry now" button.
+   */
+  public void forceNetworkChange() {
+    connectionManager.guardedRun(Native::ConnectionManager_force_network_change);
+  }
+
   public CompletableFuture<CdsiLookupResponse> cdsiLookup(
       String username, String password, CdsiLookupRequest request, Consumer<byte[]> tokenConsumer)
       throws IOException, InterruptedException, ExecutionException {
@@ -258,8 +397,17 @@ public class Network {
     }
 
     private void setProxy(
-        String scheme, String host, Integer port, String username, String password)
+        String scheme,
+        String host,
+        Integer port,
+        String username,
+        String password,
+        Map<String, String> connectHeaders)
         throws IOException {
+      final String encodedConnectHeaders =
+          connectHeaders.entrySet().stream()
+              .map(header -> header.getKey() + ": " + header.getValue() + "\n")
+              .collect(Collectors.joining());
       long rawProxyConfig;
       try {
         rawProxyConfig =
@@ -273,7 +421,8 @@ public class Network {
                         // to pass that manually.
                         port != null ? port : Integer.MIN_VALUE,
                         username,
-                        password));
+                        password,
+                        encodedConnectHeaders));
       } catch (IOException | RuntimeException | Error e) {
         setInvalidProxy();
         throw e;
@@ -302,6 +451,54 @@ public class Network {
       guardedRun(h -> Native.ConnectionManager_set_censorship_circumvention_enabled(h, enabled));
     }
 
+    private void setNetworkType(NetworkType networkType) {
+      guardedRun(h -> Native.ConnectionManager_set_network_type(h, networkType.value));
+    }
+
+    private void setEndpointIpHints(
+        ServiceKind service, List<InetAddress> addresses, Duration ttl) {
+      final String joined =
+          addresses.stream().map(InetAddress::getHostAddress).collect(Collectors.joining(","));
+      final int ttlSeconds = (int) Math.min(ttl.getSeconds(), Integer.MAX_VALUE);
+      guardedRun(
+          h ->
+              filterExceptions(
+                  () ->
+                      Native.ConnectionManager_set_endpoint_ip_hints(
+                          h, service.value, joined, ttlSeconds)));
+    }
+
+    private void clearEndpointIpHints(ServiceKind service) {
+      guardedRun(h -> Native.ConnectionManager_clear_endpoint_ip_hints(h, service.value));
+    }
+
+    private Optional<ConnectionQuality> chatConnectionQuality() {
+      final int quality = guardedMap(Native::ConnectionManager_chat_connection_quality);
+      switch (quality) {
+        case 0:
+          return Optional.empty();
+        case 1:
+          return Optional.of(ConnectionQuality.GOOD);
+        case 2:
+          return Optional.of(ConnectionQuality.OK);
+        case 3:
+          return Optional.of(ConnectionQuality.BAD);
+        default:
+          throw new IllegalStateException("unknown connection quality " + quality);
+      }
+    }
+
+    private void setConnectionQualityThresholds(Duration goodBelow, Duration badAbove) {
+      final int goodBelowMillis = (int) Math.min(goodBelow.toMillis(), Integer.MAX_VALUE);
+      final int badAboveMillis = (int) Math.min(badAbove.toMillis(), Integer.MAX_VALUE);
+      guardedRun(
+          h ->
+              filterExceptions(
+                  () ->
+                      Native.ConnectionManager_set_connection_quality_thresholds(
+                          h, goodBelowMillis, badAboveMillis)));
+    }
+
     @Override
     protected void release(final long nativeHandle) {
       Native.ConnectionManager_Destroy(nativeHandle);
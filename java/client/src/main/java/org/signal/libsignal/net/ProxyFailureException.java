@@ -0,0 +1,16 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.net;
+
+/**
+ * Indicates that a connection could not be made through the configured proxy, because the proxy
+ * was unreachable, rejected the connection, or did not accept the provided credentials.
+ */
+public class ProxyFailureException extends ChatServiceException {
+  public ProxyFailureException(String message) {
+    super(message);
+  }
+}
@@ -0,0 +1,30 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.net;
+
+import java.util.Arrays;
+import java.util.Collections;
+import java.util.List;
+
+/**
+ * Indicates that the server requires a challenge to be completed before accepting more requests.
+ *
+ * <p>The app should complete one of the challenge {@link #options} and submit the solution along
+ * with {@link #token} before trying again.
+ */
+public class RateLimitChallengeException extends Exception {
+  /** The token identifying this challenge. */
+  public final String token;
+
+  /** The kinds of challenge that may be completed, e.g. {@code "recaptcha"}. */
+  public final List<String> options;
+
+  public RateLimitChallengeException(String token, String[] options) {
+    super("Rate limit challenge required, options: " + Arrays.toString(options));
+    this.token = token;
+    this.options = Collections.unmodifiableList(Arrays.asList(options));
+  }
+}
@@ -6,17 +6,31 @@
 package org.signal.libsignal.net;
 
 import java.time.Duration;
+import java.time.Instant;
 
 public class RetryLaterException extends Exception {
   /** The amount of time to wait before retrying. */
   public final Duration duration;
 
+  /**
+   * The earliest time at which to retry.
+   *
+   * <p>When the delay was provided by the chat server, this is measured from when the server's
+   * response was received, so it doesn't get stale while the exception is being handled.
+   */
+  public final Instant retryAt;
+
   public RetryLaterException(long retryAfterSeconds) {
-    this(Duration.ofSeconds(retryAfterSeconds));
+    this(Duration.ofSeconds(retryAfterSeconds), Instant.now().plusSeconds(retryAfterSeconds));
+  }
+
+  public RetryLaterException(long retryAfterSeconds, long retryAtEpochMillis) {
+    this(Duration.ofSeconds(retryAfterSeconds), Instant.ofEpochMilli(retryAtEpochMillis));
   }
 
-  private RetryLaterException(Duration duration) {
+  private RetryLaterException(Duration duration, Instant retryAt) {
     super("Retry after " + duration.getSeconds() + " seconds");
     this.duration = duration;
+    this.retryAt = retryAt;
   }
 }
@@ -19,6 +19,11 @@ class TokioAsyncContext extends NativeHandleGuard.SimpleOwner {
     return (CompletableFuture<Class<Object>>) Native.AsyncLoadClass(this, className);
   }
 
+  /** Stops the native work for a future that was started on this context, if it's still running. */
+  void cancel(long cancellationId) {
+    guardedRun(nativeHandle -> Native.TokioAsyncContext_cancel(nativeHandle, cancellationId));
+  }
+
   @Override
   protected void release(final long nativeHandle) {
     Native.TokioAsyncContext_Destroy(nativeHandle);
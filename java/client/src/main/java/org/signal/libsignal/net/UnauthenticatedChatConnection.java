@@ -41,6 +41,7 @@ public class UnauthenticatedChatConnection extends ChatConnection {
                 connectionManagerHandle ->
                     Native.UnauthenticatedChatConnection_connect(
                             asyncContextHandle, connectionManagerHandle)
+                        .withCancellationHandler(tokioAsyncContext::cancel)
                         .thenApply(
                             nativeHandle ->
                                 new UnauthenticatedChatConnection(
@@ -80,9 +81,9 @@ public class UnauthenticatedChatConnection extends ChatConnection {
       long nativeAsyncContextHandle,
       long nativeChatConnectionHandle,
       long nativeRequestHandle,
-      int timeoutMillis) {
+      long deadlineMillis) {
     return Native.UnauthenticatedChatConnection_send(
-        nativeAsyncContextHandle, nativeChatConnectionHandle, nativeRequestHandle, timeoutMillis);
+        nativeAsyncContextHandle, nativeChatConnectionHandle, nativeRequestHandle, deadlineMillis);
   }
 
   @Override
@@ -12,6 +12,7 @@ public class TestStore implements Store {
 
   public HashMap<ServiceId.Aci, Deque<byte[]>> storage = new HashMap<>();
   public byte[] lastDistinguishedTreeHead;
+  public byte[] keyRotations;
 
   @Override
   public Optional<byte[]> getLastDistinguishedTreeHead() {
@@ -34,4 +35,14 @@ public class TestStore implements Store {
     Deque<byte[]> deque = this.storage.computeIfAbsent(aci, key -> new ArrayDeque<>());
     deque.addLast(data);
   }
+
+  @Override
+  public Optional<byte[]> getKeyRotations() {
+    return Optional.ofNullable(keyRotations);
+  }
+
+  @Override
+  public void setKeyRotations(byte[] keyRotations) {
+    this.keyRotations = keyRotations;
+  }
 }
@@ -10,7 +10,11 @@ import static org.junit.Assert.*;
 import java.io.IOException;
 import java.nio.charset.StandardCharsets;
 import java.time.Duration;
+import java.time.Instant;
+import java.util.List;
 import java.util.Map;
+import java.util.Optional;
+import java.util.OptionalInt;
 import java.util.concurrent.CountDownLatch;
 import java.util.concurrent.ExecutionException;
 import java.util.concurrent.TimeUnit;
@@ -44,6 +48,12 @@ public class ChatServiceTest {
           "content-type", "application/octet-stream",
           "forwarded", "1.1.1.1");
 
+  // Responses only carry allowlisted headers, so "forwarded" is dropped.
+  private static final Map<String, String> EXPECTED_RESPONSE_HEADERS =
+      Map.of(
+          "content-type", "application/octet-stream",
+          "retry-after", "30");
+
   @Test
   public void testConvertResponse() throws Exception {
     // empty body
@@ -52,20 +62,24 @@ public class ChatServiceTest {
     assertEquals(EXPECTED_STATUS, response1.status());
     assertEquals(EXPECTED_MESSAGE, response1.message());
     assertArrayEquals(new byte[0], response1.body());
-    assertEquals(EXPECTED_HEADERS, response1.headers());
+    assertEquals(EXPECTED_RESPONSE_HEADERS, response1.headers());
 
     final ChatConnection.Response response2 =
         (ChatConnection.Response) NativeTesting.TESTING_ChatResponseConvert(true);
     assertEquals(EXPECTED_STATUS, response2.status());
     assertEquals(EXPECTED_MESSAGE, response2.message());
     assertArrayEquals(EXPECTED_CONTENT, response2.body());
-    assertEquals(EXPECTED_HEADERS, response2.headers());
+    assertEquals(EXPECTED_RESPONSE_HEADERS, response2.headers());
   }
 
   @Test
   public void chatConnectErrorConvert() {
-    assertChatConnectErrorIs("AppExpired", AppExpiredException.class);
+    AppExpiredException appExpired =
+        assertChatConnectErrorIs("AppExpired", AppExpiredException.class);
+    assertEquals(Optional.of("7.40.0"), appExpired.getMinimumVersion());
+    assertEquals(Optional.of("https://signal.org/install"), appExpired.getUpgradeUrl());
     assertChatConnectErrorIs("DeviceDeregistered", DeviceDeregisteredException.class);
+    assertChatConnectErrorIs("ProxyAuthFailed", ProxyFailureException.class);
 
     assertChatConnectErrorIs("WebSocketConnectionFailed", ChatServiceException.class);
     assertChatConnectErrorIs("Timeout", ChatServiceException.class);
@@ -74,11 +88,23 @@ public class ChatServiceTest {
     RetryLaterException retryLater =
         assertChatConnectErrorIs("RetryAfter42Seconds", RetryLaterException.class);
     assertEquals(retryLater.duration, Duration.ofSeconds(42));
+    assertTrue(retryLater.retryAt.isAfter(Instant.now().plusSeconds(40)));
+    RateLimitChallengeException challenge =
+        assertChatConnectErrorIs("RateLimitChallenge", RateLimitChallengeException.class);
+    assertEquals("fake token", challenge.token);
+    assertEquals(List.of("recaptcha"), challenge.options);
   }
 
   @Test
   public void chatSendErrorConvert() {
     assertChatSendErrorIs("Disconnected", ChatServiceInactiveException.class);
+    assertChatSendErrorIs("ConnectionIdleTimeout", ChatServiceInactiveException.class);
+    ConnectionLostException lost =
+        assertChatSendErrorIs("ConnectionLost", ConnectionLostException.class);
+    assertEquals(OptionalInt.of(4001), lost.getCloseCode());
+    ConnectionLostException lostWithoutCode =
+        assertChatSendErrorIs("ConnectionLostWithoutCloseCode", ConnectionLostException.class);
+    assertEquals(OptionalInt.empty(), lostWithoutCode.getCloseCode());
 
     assertChatSendErrorIs("WebSocketConnectionReset", ChatServiceException.class);
     assertChatSendErrorIs("IncomingDataInvalid", ChatServiceException.class);
@@ -296,6 +322,14 @@ public class ChatServiceTest {
     check.accept(() -> net.setProxy("signalfoundation.org", -1));
 
     check.accept(() -> net.setProxy("socks+shoes", "signalfoundation.org", null, null, null));
+    check.accept(
+        () ->
+            net.setProxy(
+                "socks5", "signalfoundation.org", null, null, null, Map.of("X-Tenant", "tenant")));
+    check.accept(
+        () ->
+            net.setProxy(
+                "http", "signalfoundation.org", null, null, null, Map.of("Host", "example.com")));
 
     check.accept(
         () -> {
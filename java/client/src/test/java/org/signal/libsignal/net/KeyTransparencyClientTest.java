@@ -23,8 +23,10 @@ import org.junit.runners.Parameterized;
 import org.junit.runners.Parameterized.Parameter;
 import org.junit.runners.Parameterized.Parameters;
 import org.signal.libsignal.internal.CompletableFuture;
+import org.signal.libsignal.keytrans.E164SearchKey;
 import org.signal.libsignal.keytrans.SearchResult;
 import org.signal.libsignal.keytrans.TestStore;
+import org.signal.libsignal.keytrans.UsernameHash;
 import org.signal.libsignal.protocol.util.Hex;
 import org.signal.libsignal.util.TestEnvironment;
 
@@ -69,9 +71,8 @@ public class KeyTransparencyClientTest {
             .search(
                 TEST_ACI,
                 TEST_ACI_IDENTITY_KEY,
-                TEST_E164,
-                TEST_UNIDENTIFIED_ACCESS_KEY,
-                TEST_USERNAME_HASH,
+                new E164SearchKey(TEST_E164, TEST_UNIDENTIFIED_ACCESS_KEY),
+                new UsernameHash(TEST_USERNAME_HASH),
                 store)
             .get();
 
@@ -113,9 +114,8 @@ public class KeyTransparencyClientTest {
             .search(
                 TEST_ACI,
                 TEST_ACI_IDENTITY_KEY,
-                TEST_E164,
-                TEST_UNIDENTIFIED_ACCESS_KEY,
-                TEST_USERNAME_HASH,
+                new E164SearchKey(TEST_E164, TEST_UNIDENTIFIED_ACCESS_KEY),
+                new UsernameHash(TEST_USERNAME_HASH),
                 store)
             .get();
 
@@ -128,9 +128,8 @@ public class KeyTransparencyClientTest {
         .monitor(
             TEST_ACI,
             TEST_ACI_IDENTITY_KEY,
-            TEST_E164,
-            TEST_UNIDENTIFIED_ACCESS_KEY,
-            TEST_USERNAME_HASH,
+            new E164SearchKey(TEST_E164, TEST_UNIDENTIFIED_ACCESS_KEY),
+            new UsernameHash(TEST_USERNAME_HASH),
             store)
         .get();
     // Another entry in the account history after a successful monitor request
@@ -147,20 +146,20 @@ public class KeyTransparencyClientTest {
     TestStore store = new TestStore();
 
     // Call to monitor before any data has been persisted in the store.
-    // Distinguished tree will be requested from the server, but it will fail
-    // due to account data missing.
-    try {
-      ktClient
-          .monitor(
-              TEST_ACI,
-              TEST_ACI_IDENTITY_KEY,
-              TEST_E164,
-              TEST_UNIDENTIFIED_ACCESS_KEY,
-              TEST_USERNAME_HASH,
-              store)
-          .get();
-    } catch (ExecutionException e) {
-      assertTrue(e.getCause() instanceof KeyTransparencyException);
-    }
+    // Distinguished tree will be requested from the server, but monitoring
+    // will be rejected because there is no account data to monitor.
+    ExecutionException e =
+        assertThrows(
+            ExecutionException.class,
+            () ->
+                ktClient
+                    .monitor(
+                        TEST_ACI,
+                        TEST_ACI_IDENTITY_KEY,
+                        new E164SearchKey(TEST_E164, TEST_UNIDENTIFIED_ACCESS_KEY),
+                        new UsernameHash(TEST_USERNAME_HASH),
+                        store)
+                    .get());
+    assertTrue(e.getCause() instanceof IllegalArgumentException);
   }
 }
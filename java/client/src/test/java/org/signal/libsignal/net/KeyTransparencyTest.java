@@ -7,12 +7,16 @@ package org.signal.libsignal.net;
 
 import static org.junit.Assert.*;
 
+import java.time.Duration;
+import java.util.Arrays;
 import java.util.Optional;
 import java.util.UUID;
 import org.junit.Test;
 import org.signal.libsignal.internal.NativeTesting;
+import org.signal.libsignal.keytrans.E164SearchKey;
 import org.signal.libsignal.keytrans.SearchResult;
 import org.signal.libsignal.keytrans.TestStore;
+import org.signal.libsignal.keytrans.UsernameHash;
 import org.signal.libsignal.protocol.IdentityKey;
 import org.signal.libsignal.protocol.InvalidKeyException;
 import org.signal.libsignal.protocol.ServiceId;
@@ -28,6 +32,10 @@ public class KeyTransparencyTest {
           "d237a4b83b463ca7da58d4a16bf6a3ba104506eb412b235eb603ea10f467c655");
   static final byte[] TEST_UNIDENTIFIED_ACCESS_KEY =
       Hex.fromStringCondensedAssert("c6f7c258c24d69538ea553b4a943c8d9");
+  // A StoredTreeHead of size 42.
+  static final byte[] TEST_DISTINGUISHED_TREE_HEAD =
+      Hex.fromStringCondensedAssert(
+          "0a0c082a10b2af9d141a0301020312202a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a");
 
   static {
     try {
@@ -51,4 +59,84 @@ public class KeyTransparencyTest {
 
     assertTrue(store.getAccountData(TEST_ACI).isPresent());
   }
+
+  @Test
+  public void stateRoundTripsThroughExport() throws Exception {
+    SearchResult result = new SearchResult(NativeTesting.TESTING_ChatSearchResult());
+    TestStore store = new TestStore();
+    store.applyUpdates(TEST_ACI, result);
+    store.setLastDistinguishedTreeHead(TEST_DISTINGUISHED_TREE_HEAD);
+
+    byte[] exported = KeyTransparencyClient.exportState(TEST_ACI, store).get();
+    TestStore newStore = new TestStore();
+    KeyTransparencyClient.importState(TEST_ACI, exported, newStore);
+    assertArrayEquals(
+        store.getAccountData(TEST_ACI).get(), newStore.getAccountData(TEST_ACI).get());
+    assertArrayEquals(
+        TEST_DISTINGUISHED_TREE_HEAD, newStore.getLastDistinguishedTreeHead().get());
+
+    byte[] truncated = Arrays.copyOf(exported, exported.length - 1);
+    TestStore emptyStore = new TestStore();
+    assertThrows(
+        IllegalArgumentException.class,
+        () -> KeyTransparencyClient.importState(TEST_ACI, truncated, emptyStore));
+    assertFalse(emptyStore.getAccountData(TEST_ACI).isPresent());
+  }
+
+  @Test
+  public void exportNeedsAccountDataAndDistinguishedTreeHead() {
+    TestStore store = new TestStore();
+    store.setLastDistinguishedTreeHead(TEST_DISTINGUISHED_TREE_HEAD);
+    assertFalse(KeyTransparencyClient.exportState(TEST_ACI, store).isPresent());
+  }
+
+  @Test
+  public void searchKeysAreValidated() {
+    new UsernameHash(TEST_USERNAME_HASH);
+    assertThrows(IllegalArgumentException.class, () -> new UsernameHash(new byte[31]));
+
+    new E164SearchKey(TEST_E164, TEST_UNIDENTIFIED_ACCESS_KEY);
+    new E164SearchKey(TEST_E164, null);
+    assertThrows(
+        IllegalArgumentException.class, () -> new E164SearchKey(TEST_E164, new byte[15]));
+    assertThrows(
+        IllegalArgumentException.class,
+        () -> new E164SearchKey("+1234567890123456", TEST_UNIDENTIFIED_ACCESS_KEY));
+  }
+
+  @Test
+  public void errorConversion() {
+    assertKeyTransErrorIs(
+        "BadArguments",
+        IllegalArgumentException.class,
+        "Invalid arguments: no stored account data to monitor");
+    assertKeyTransErrorIs(
+        "DecodingFailed",
+        IllegalArgumentException.class,
+        "Invalid arguments: Invalid protobuf: failed to decode Protobuf message: fake reason");
+    assertKeyTransErrorIs(
+        "InvalidResponse",
+        KeyTransparencyException.class,
+        "Internal error: Invalid response: fake reason");
+    // Chat connection details are not passed on.
+    assertKeyTransErrorIs("ChatSendDisconnected", ChatServiceException.class, "Network error");
+
+    RetryLaterException retryLater =
+        assertKeyTransErrorIs(
+            "RetryAfter42Seconds", RetryLaterException.class, "Retry after 42 seconds");
+    assertEquals(Duration.ofSeconds(42), retryLater.duration);
+    assertKeyTransErrorIs(
+        "RateLimitedWithoutRetryAfter", ChatServiceException.class, "Rate limited");
+  }
+
+  private static <E extends Exception> E assertKeyTransErrorIs(
+      String errorDescription, Class<E> expectedErrorType, String expectedMessage) {
+    E e =
+        assertThrows(
+            "for " + errorDescription,
+            expectedErrorType,
+            () -> NativeTesting.TESTING_KeyTransErrorConvert(errorDescription));
+    assertEquals(expectedMessage, e.getMessage());
+    return e;
+  }
 }
@@ -5,6 +5,8 @@
 
 package org.signal.libsignal.net;
 
+import static org.junit.Assert.assertEquals;
+
 import org.junit.Test;
 
 public class NetworkTest {
@@ -15,5 +17,12 @@ public class NetworkTest {
     // There's no feedback from this, we're just making sure it doesn't normally crash or throw.
     var net = new Network(Network.Environment.STAGING, USER_AGENT);
     net.onNetworkChange();
+    net.forceNetworkChange();
+  }
+
+  @Test
+  public void noRecentNetworkEventsBeforeConnecting() {
+    var net = new Network(Network.Environment.STAGING, USER_AGENT);
+    assertEquals("[]", net.exportRecentNetworkEvents());
   }
 }
@@ -7,10 +7,13 @@ package org.signal.libsignal.net;
 
 import static org.junit.Assert.*;
 
+import java.util.concurrent.CancellationException;
 import java.util.concurrent.ExecutionException;
 import java.util.concurrent.Future;
 import java.util.concurrent.TimeUnit;
 import org.junit.Test;
+import org.signal.libsignal.internal.CompletableFuture;
+import org.signal.libsignal.internal.NativeTesting;
 
 public class TokioAsyncContextTest {
   @Test
@@ -35,6 +38,34 @@ public class TokioAsyncContextTest {
     assertClassNotFound(context, "org.signal.libsignal.ClassThatDoesNotExist10");
   }
 
+  @Test
+  public void cancellation() throws Exception {
+    TokioAsyncContext context = new TokioAsyncContext();
+    @SuppressWarnings("unchecked")
+    CompletableFuture<Void> future =
+        context
+            .guardedMap(NativeTesting::TESTING_OnlyCompletesByCancellation)
+            .withCancellationHandler(context::cancel);
+    assertFalse(future.isDone());
+
+    assertTrue(future.cancel(true));
+    assertThrows(CancellationException.class, () -> future.get(10, TimeUnit.SECONDS));
+    // Cancelling again, after the native side has also finished, is harmless.
+    assertFalse(future.cancel(true));
+  }
+
+  @Test
+  public void cancellationAfterCompletionIsNoOp() throws Exception {
+    TokioAsyncContext context = new TokioAsyncContext();
+    CompletableFuture<Class<Object>> future =
+        context
+            .loadClassAsync("org.signal.libsignal.net.NetworkException")
+            .withCancellationHandler(context::cancel);
+    future.get();
+    assertFalse(future.cancel(true));
+    assertFalse(future.isCancelled());
+  }
+
   /** Assert that the class with the given name can be loaded on a Tokio worker thread. */
   private static void assertCanLoadClass(TokioAsyncContext context, String className)
       throws ExecutionException, InterruptedException {
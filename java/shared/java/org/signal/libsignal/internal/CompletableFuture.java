@@ -15,6 +15,7 @@ import java.util.concurrent.TimeoutException;
 import java.util.function.BiConsumer;
 import java.util.function.Consumer;
 import java.util.function.Function;
+import java.util.function.LongConsumer;
 
 /** A stripped-down, Android-21-compatible version of java.util.concurrent.CompletableFuture. */
 public class CompletableFuture<T> implements Future<T> {
@@ -22,21 +23,58 @@ public class CompletableFuture<T> implements Future<T> {
   private T result;
   private Throwable exception;
   private List<ThenApplyCompleter<T>> consumers;
+  private long cancellationId;
+  private Runnable cancellationHandler;
 
   @CalledFromNative
   public CompletableFuture() {
     this.consumers = new ArrayList<>();
   }
 
+  /**
+   * Completes this future with a {@link CancellationException}, and asks whatever would have
+   * completed it to stop working.
+   *
+   * <p>Futures returned from native code only stop their work once given a {@link
+   * #withCancellationHandler}. Cancelling a future that has already completed does nothing and
+   * returns {@code false}.
+   */
   @Override
-  public synchronized boolean cancel(boolean mayInterruptIfRunning) {
-    // We do not currently support cancellation.
-    return false;
+  public boolean cancel(boolean mayInterruptIfRunning) {
+    Runnable handler;
+    synchronized (this) {
+      if (!completeExceptionally(new CancellationException())) return false;
+      handler = this.cancellationHandler;
+    }
+    // Run the handler outside the lock, since it may call into native code.
+    if (handler != null) {
+      handler.run();
+    }
+    return true;
   }
 
   @Override
   public synchronized boolean isCancelled() {
-    return false;
+    return exception instanceof CancellationException;
+  }
+
+  @CalledFromNative
+  private synchronized void setCancellationId(long cancellationId) {
+    this.cancellationId = cancellationId;
+  }
+
+  /**
+   * Arranges for {@link #cancel} to call {@code handler} with the ID native code assigned to this
+   * future's work, and returns this future.
+   *
+   * <p>Has no effect if native code did not make this future cancellable.
+   */
+  public synchronized CompletableFuture<T> withCancellationHandler(LongConsumer handler) {
+    long id = this.cancellationId;
+    if (id != 0) {
+      this.cancellationHandler = () -> handler.accept(id);
+    }
+    return this;
   }
 
   @Override
@@ -85,6 +123,7 @@ public class CompletableFuture<T> implements Future<T> {
       throws CancellationException, ExecutionException, InterruptedException {
     while (!completed) wait();
 
+    if (exception instanceof CancellationException) throw (CancellationException) exception;
     if (exception != null) throw new ExecutionException(exception);
 
     return result;
@@ -150,6 +189,14 @@ public class CompletableFuture<T> implements Future<T> {
             future.completeExceptionally(e);
             return;
           }
+          boolean alreadyCancelled;
+          synchronized (future) {
+            alreadyCancelled = future.isCancelled();
+            future.cancellationHandler = () -> output.cancel(false);
+          }
+          if (alreadyCancelled) {
+            output.cancel(false);
+          }
           output.addCompleter(
               new ThenApplyCompleter<>(future::complete, future::completeExceptionally));
         },
@@ -194,6 +241,8 @@ public class CompletableFuture<T> implements Future<T> {
       BiConsumer<CompletableFuture<U>, T> complete,
       BiConsumer<CompletableFuture<U>, Throwable> completeExceptionally) {
     CompletableFuture<U> future = new CompletableFuture<>();
+    // Cancelling the chained future cancels this one too, so the work stops wherever it is.
+    future.cancellationHandler = () -> this.cancel(false);
     ThenApplyCompleter<T> completer =
         new ThenApplyCompleter<T>(
             (T value) -> complete.accept(future, value),
@@ -16,6 +16,7 @@ import org.signal.libsignal.protocol.state.KyberPreKeyStore;
 import org.signal.libsignal.protocol.groups.state.SenderKeyStore;
 import org.signal.libsignal.protocol.logging.Log;
 import org.signal.libsignal.protocol.logging.SignalProtocolLogger;
+import org.signal.libsignal.net.internal.BridgeChatConnectListener;
 import org.signal.libsignal.net.internal.BridgeChatListener;
 
 import java.io.File;
@@ -170,10 +171,13 @@ public final class Native {
 
   public static native void AuthenticatedChatConnection_Destroy(long handle);
   public static native CompletableFuture<Long> AuthenticatedChatConnection_connect(long asyncRuntime, long connectionManager, String username, String password, boolean receiveStories);
+  public static native CompletableFuture<Long> AuthenticatedChatConnection_connect_with_listener(long asyncRuntime, long connectionManager, String username, String password, boolean receiveStories, BridgeChatConnectListener progressListener);
   public static native CompletableFuture AuthenticatedChatConnection_disconnect(long asyncRuntime, long chat);
   public static native void AuthenticatedChatConnection_init_listener(long chat, BridgeChatListener listener);
   public static native CompletableFuture<Void> AuthenticatedChatConnection_preconnect(long asyncRuntime, long connectionManager);
-  public static native CompletableFuture<Object> AuthenticatedChatConnection_send(long asyncRuntime, long chat, long httpRequest, int timeoutMillis);
+  public static native CompletableFuture<Void> AuthenticatedChatConnection_run_auto_reconnect(long asyncRuntime, long chat, long connectionManager, String username, String password, boolean receiveStories);
+  public static native CompletableFuture<Object> AuthenticatedChatConnection_send(long asyncRuntime, long chat, long httpRequest, long deadline);
+  public static native void AuthenticatedChatConnection_stop_auto_reconnect(long chat);
 
   public static native void BackupAuthCredentialPresentation_CheckValidContents(byte[] presentationBytes) throws Exception;
   public static native byte[] BackupAuthCredentialPresentation_GetBackupId(byte[] presentationBytes);
@@ -230,18 +234,45 @@ public final class Native {
   public static native CompletableFuture<Object> CdsiLookup_complete(long asyncRuntime, long lookup);
   public static native CompletableFuture<Long> CdsiLookup_new(long asyncRuntime, long connectionManager, String username, String password, long request);
   public static native CompletableFuture<Long> CdsiLookup_new_routes(long asyncRuntime, long connectionManager, String username, String password, long request);
+  public static native CompletableFuture<Object> CdsiLookup_nextBatch(long asyncRuntime, long lookup, int maxEntries);
   public static native byte[] CdsiLookup_token(long lookup);
 
+  public static native void ChatConnectionStateWatcher_Destroy(long handle);
+  public static native long ChatConnectionStateWatcher_current(long watcher);
+  public static native long ChatConnectionStateWatcher_new(long connectionManager);
+  public static native CompletableFuture<Long> ChatConnectionStateWatcher_next(long asyncRuntime, long watcher);
+
+  public static native void ChatConnectionState_Destroy(long handle);
+  public static native int ChatConnectionState_attempt(long state);
+  public static native long ChatConnectionState_connected_since(long state);
+  public static native int ChatConnectionState_disconnect_reason(long state);
+  public static native int ChatConnectionState_kind(long state);
+
+  public static native void ConnectStateResetSummary_Destroy(long handle);
+  public static native int ConnectStateResetSummary_dns_lookups_flushed(long summary);
+  public static native boolean ConnectStateResetSummary_outage_cleared(long summary);
+  public static native int ConnectStateResetSummary_route_failures_cleared(long summary);
+
   public static native void ConnectionManager_Destroy(long handle);
+  public static native int ConnectionManager_chat_connection_quality(long connectionManager);
+  public static native void ConnectionManager_clear_endpoint_ip_hints(long connectionManager, int service);
   public static native void ConnectionManager_clear_proxy(long connectionManager);
+  public static native String ConnectionManager_export_recent_network_events(long connectionManager);
+  public static native void ConnectionManager_force_network_change(long connectionManager);
+  public static native long ConnectionManager_last_connect_state_reset(long connectionManager);
   public static native long ConnectionManager_new(int environment, String userAgent);
   public static native void ConnectionManager_on_network_change(long connectionManager);
+  public static native long ConnectionManager_reset_connect_state(long connectionManager);
   public static native void ConnectionManager_set_censorship_circumvention_enabled(long connectionManager, boolean enabled);
+  public static native void ConnectionManager_set_connection_quality_thresholds(long connectionManager, int goodBelowMillis, int badAboveMillis) throws Exception;
+  public static native void ConnectionManager_set_endpoint_ip_hints(long connectionManager, int service, String addresses, int ttlSeconds) throws Exception;
   public static native void ConnectionManager_set_invalid_proxy(long connectionManager);
+  public static native void ConnectionManager_set_local_bind_address(long connectionManager, String address) throws Exception;
+  public static native void ConnectionManager_set_network_type(long connectionManager, int networkType);
   public static native void ConnectionManager_set_proxy(long connectionManager, long proxy);
 
   public static native void ConnectionProxyConfig_Destroy(long handle);
-  public static native long ConnectionProxyConfig_new(String scheme, String host, int port, String username, String password) throws Exception;
+  public static native long ConnectionProxyConfig_new(String scheme, String host, int port, String username, String password, String connectHeaders) throws Exception;
 
   public static native void CreateCallLinkCredentialPresentation_CheckValidContents(byte[] presentationBytes) throws Exception;
   public static native void CreateCallLinkCredentialPresentation_Verify(byte[] presentationBytes, byte[] roomId, long now, byte[] serverParamsBytes, byte[] callLinkParamsBytes) throws Exception;
@@ -287,6 +318,9 @@ public final class Native {
   public static native byte[] DeviceTransfer_GenerateCertificate(byte[] privateKey, String name, int daysToExpire) throws Exception;
   public static native byte[] DeviceTransfer_GeneratePrivateKey();
 
+  public static native void E164SearchKey_Destroy(long handle);
+  public static native long E164SearchKey_New(String e164, byte[] unidentifiedAccessKey) throws Exception;
+
   public static native byte[] ECPrivateKey_Agree(long privateKey, long publicKey) throws Exception;
   public static native long ECPrivateKey_Deserialize(byte[] data) throws Exception;
   public static native void ECPrivateKey_Destroy(long handle);
@@ -377,17 +411,28 @@ public final class Native {
 
   public static native boolean IdentityKey_VerifyAlternateIdentity(long publicKey, long otherIdentity, byte[] signature) throws Exception;
 
+  public static native void ImportedKeyTransState_Destroy(long handle);
+  public static native byte[] ImportedKeyTransState_GetAccountData(long state);
+  public static native byte[] ImportedKeyTransState_GetLastDistinguishedTreeHead(long state);
+
   public static native int IncrementalMac_CalculateChunkSize(int dataSize);
   public static native void IncrementalMac_Destroy(long handle);
   public static native byte[] IncrementalMac_Finalize(long mac);
   public static native long IncrementalMac_Initialize(byte[] key, int chunkSize);
   public static native byte[] IncrementalMac_Update(long mac, byte[] bytes, int offset, int length);
 
+  public static native void KeyTransUsernameHash_Destroy(long handle);
+  public static native long KeyTransUsernameHash_New(byte[] hash) throws Exception;
+
   public static native byte[] KeyTransparency_AciSearchKey(byte[] aci);
-  public static native CompletableFuture<byte[]> KeyTransparency_Distinguished(long asyncRuntime, int environment, long chatConnection, byte[] lastDistinguishedTreeHead);
+  public static native byte[] KeyTransparency_ApplyKeyRotation(int environment, byte[] keyRotations, byte[] document) throws Exception;
+  public static native CompletableFuture<byte[]> KeyTransparency_Distinguished(long asyncRuntime, int environment, long chatConnection, byte[] lastDistinguishedTreeHead, byte[] keyRotations, long deadline);
   public static native byte[] KeyTransparency_E164SearchKey(String e164);
-  public static native CompletableFuture<byte[]> KeyTransparency_Monitor(long asyncRuntime, int environment, long chatConnection, byte[] aci, long aciIdentityKey, String e164, byte[] unidentifiedAccessKey, byte[] usernameHash, byte[] accountData, byte[] lastDistinguishedTreeHead);
-  public static native CompletableFuture<Long> KeyTransparency_Search(long asyncRuntime, int environment, long chatConnection, byte[] aci, long aciIdentityKey, String e164, byte[] unidentifiedAccessKey, byte[] usernameHash, byte[] accountData, byte[] lastDistinguishedTreeHead);
+  public static native byte[] KeyTransparency_ExportState(byte[] accountData, byte[] lastDistinguishedTreeHead) throws Exception;
+  public static native long KeyTransparency_ImportState(byte[] bytes) throws Exception;
+  public static native CompletableFuture<byte[]> KeyTransparency_Monitor(long asyncRuntime, int environment, long chatConnection, byte[] aci, long aciIdentityKey, long e164SearchKey, long usernameHash, byte[] accountData, byte[] lastDistinguishedTreeHead, byte[] keyRotations, long deadline);
+  public static native CompletableFuture<Long> KeyTransparency_Search(long asyncRuntime, int environment, long chatConnection, byte[] aci, long aciIdentityKey, long e164SearchKey, long usernameHash, byte[] accountData, byte[] lastDistinguishedTreeHead, byte[] keyRotations, long deadline);
+  public static native CompletableFuture<byte[]> KeyTransparency_UpdateKeyRotations(long asyncRuntime, int environment, long chatConnection, byte[] keyRotations, long deadline);
   public static native byte[] KeyTransparency_UsernameHashSearchKey(byte[] hash);
 
   public static native void KyberKeyPair_Destroy(long handle);
@@ -422,6 +467,7 @@ public final class Native {
   public static native void LookupRequest_addAciAndAccessKey(long request, byte[] aci, byte[] accessKey) throws Exception;
   public static native void LookupRequest_addE164(long request, String e164);
   public static native void LookupRequest_addPreviousE164(long request, String e164);
+  public static native void LookupRequest_addRemovedE164(long request, String e164);
   public static native long LookupRequest_new();
   public static native void LookupRequest_setToken(long request, byte[] token);
 
@@ -608,6 +654,7 @@ public final class Native {
 
   public static native void ServerMessageAck_Destroy(long handle);
   public static native void ServerMessageAck_Send(long ack) throws Exception;
+  public static native void ServerMessageAck_SendStatus(long ack, int status) throws Exception;
 
   public static native byte[] ServerPublicParams_CreateAuthCredentialWithPniPresentationDeterministic(long serverPublicParams, byte[] randomness, byte[] groupSecretParams, byte[] authCredentialWithPniBytes);
   public static native byte[] ServerPublicParams_CreateExpiringProfileKeyCredentialPresentationDeterministic(long serverPublicParams, byte[] randomness, byte[] groupSecretParams, byte[] profileKeyCredential);
@@ -702,10 +749,11 @@ public final class Native {
 
   public static native void UnauthenticatedChatConnection_Destroy(long handle);
   public static native CompletableFuture<Long> UnauthenticatedChatConnection_connect(long asyncRuntime, long connectionManager);
+  public static native CompletableFuture<Long> UnauthenticatedChatConnection_connect_with_listener(long asyncRuntime, long connectionManager, BridgeChatConnectListener progressListener);
   public static native CompletableFuture UnauthenticatedChatConnection_disconnect(long asyncRuntime, long chat);
   public static native long UnauthenticatedChatConnection_info(long chat);
   public static native void UnauthenticatedChatConnection_init_listener(long chat, BridgeChatListener listener);
-  public static native CompletableFuture<Object> UnauthenticatedChatConnection_send(long asyncRuntime, long chat, long httpRequest, int timeoutMillis);
+  public static native CompletableFuture<Object> UnauthenticatedChatConnection_send(long asyncRuntime, long chat, long httpRequest, long deadline);
 
   public static native long UnidentifiedSenderMessageContent_Deserialize(byte[] data) throws Exception;
   public static native void UnidentifiedSenderMessageContent_Destroy(long handle);
@@ -0,0 +1,22 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.net.internal;
+
+import org.signal.libsignal.internal.CalledFromNative;
+
+/**
+ * A helper interface that receives the stages of establishing a chat connection from the Rust side
+ * of the bridge.
+ */
+@CalledFromNative
+public interface BridgeChatConnectListener {
+  /**
+   * Called the first time any connection attempt reaches each stage: 0 when a route's hostname was
+   * resolved, 1 when a transport was established, and 2 when a websocket opened. If connecting
+   * fails, the last stage reported is the furthest any attempt got.
+   */
+  void onConnectProgress(int stage, long atMillis);
+}
@@ -20,6 +20,8 @@ public interface BridgeChatListener {
 
   void onReceivedAlerts(String[] alerts);
 
+  void onUnrecognizedRequest(String path, byte[] body, long sendAckHandle);
+
   // disconnectReason should always be a ChatServiceError, but it is converted to a Throwable
   //   just to be easily passed across the bridge.
   void onConnectionInterrupted(Throwable disconnectReason);
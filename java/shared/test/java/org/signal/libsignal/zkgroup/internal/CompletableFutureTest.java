@@ -7,6 +7,7 @@ package org.signal.libsignal.internal;
 
 import static org.junit.Assert.*;
 
+import java.util.concurrent.CancellationException;
 import java.util.concurrent.ExecutionException;
 import java.util.concurrent.TimeUnit;
 import java.util.concurrent.TimeoutException;
@@ -56,6 +57,35 @@ public class CompletableFutureTest {
     assertEquals(42, (int) future.get(1, TimeUnit.MILLISECONDS));
   }
 
+  @Test
+  public void testCancel() throws Exception {
+    CompletableFuture<Integer> future = new CompletableFuture<>();
+    assertTrue(future.cancel(true));
+    assertTrue(future.isDone());
+    assertTrue(future.isCancelled());
+    assertThrows(CancellationException.class, () -> future.get());
+    assertFalse(future.complete(42));
+    assertFalse(future.cancel(true));
+  }
+
+  @Test
+  public void testCancelAfterCompletion() throws Exception {
+    CompletableFuture<Integer> future = new CompletableFuture<>();
+    future.complete(42);
+    assertFalse(future.cancel(true));
+    assertFalse(future.isCancelled());
+    assertEquals(42, (int) future.get());
+  }
+
+  @Test
+  public void testCancelChained() throws Exception {
+    CompletableFuture<Integer> future = new CompletableFuture<>();
+    CompletableFuture<Integer> chained = future.thenApply(x -> x + 1);
+    assertTrue(chained.cancel(true));
+    assertTrue(chained.isCancelled());
+    assertTrue(future.isCancelled());
+  }
+
   @Test
   public void testFailure() throws Exception {
     CompletableFuture<Integer> future = new CompletableFuture<>();
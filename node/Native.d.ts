@@ -116,6 +116,11 @@ type InputStream = {
 
 type SyncInputStream = Buffer;
 
+type ChatConnectListener = {
+  // Stages are numbered as in the Rust bridged_connect_stage.
+  _connect_progress(stage: number, at: number): void;
+};
+
 type ChatListener = {
   _incoming_message(
     envelope: Buffer,
@@ -124,6 +129,11 @@ type ChatListener = {
   ): void;
   _queue_empty(): void;
   _received_alerts(alerts: string[]): void;
+  _received_unrecognized_request(
+    path: string,
+    body: Buffer,
+    ack: ServerMessageAck
+  ): void;
   _connection_interrupted(
     // A LibSignalError or null, but not naming the type to avoid circular import dependencies.
     reason: Error | null
@@ -165,11 +175,14 @@ export function AuthCredentialPresentation_GetUuidCiphertext(presentationBytes:
 export function AuthCredentialWithPniResponse_CheckValidContents(bytes: Buffer): void;
 export function AuthCredentialWithPni_CheckValidContents(bytes: Buffer): void;
 export function AuthenticatedChatConnection_connect(asyncRuntime: Wrapper<TokioAsyncContext>, connectionManager: Wrapper<ConnectionManager>, username: string, password: string, receiveStories: boolean): CancellablePromise<AuthenticatedChatConnection>;
+export function AuthenticatedChatConnection_connect_with_listener(asyncRuntime: Wrapper<TokioAsyncContext>, connectionManager: Wrapper<ConnectionManager>, username: string, password: string, receiveStories: boolean, progressListener: ChatConnectListener): CancellablePromise<AuthenticatedChatConnection>;
 export function AuthenticatedChatConnection_disconnect(asyncRuntime: Wrapper<TokioAsyncContext>, chat: Wrapper<AuthenticatedChatConnection>): CancellablePromise<void>;
 export function AuthenticatedChatConnection_info(chat: Wrapper<AuthenticatedChatConnection>): ChatConnectionInfo;
 export function AuthenticatedChatConnection_init_listener(chat: Wrapper<AuthenticatedChatConnection>, listener: ChatListener): void;
 export function AuthenticatedChatConnection_preconnect(asyncRuntime: Wrapper<TokioAsyncContext>, connectionManager: Wrapper<ConnectionManager>): CancellablePromise<void>;
-export function AuthenticatedChatConnection_send(asyncRuntime: Wrapper<TokioAsyncContext>, chat: Wrapper<AuthenticatedChatConnection>, httpRequest: Wrapper<HttpRequest>, timeoutMillis: number): CancellablePromise<ChatResponse>;
+export function AuthenticatedChatConnection_run_auto_reconnect(asyncRuntime: Wrapper<TokioAsyncContext>, chat: Wrapper<AuthenticatedChatConnection>, connectionManager: Wrapper<ConnectionManager>, username: string, password: string, receiveStories: boolean): CancellablePromise<void>;
+export function AuthenticatedChatConnection_send(asyncRuntime: Wrapper<TokioAsyncContext>, chat: Wrapper<AuthenticatedChatConnection>, httpRequest: Wrapper<HttpRequest>, deadline: Timestamp): CancellablePromise<ChatResponse>;
+export function AuthenticatedChatConnection_stop_auto_reconnect(chat: Wrapper<AuthenticatedChatConnection>): void;
 export function BackupAuthCredentialPresentation_CheckValidContents(presentationBytes: Buffer): void;
 export function BackupAuthCredentialPresentation_GetBackupId(presentationBytes: Buffer): Buffer;
 export function BackupAuthCredentialPresentation_GetBackupLevel(presentationBytes: Buffer): number;
@@ -210,24 +223,45 @@ export function Cds2ClientState_New(mrenclave: Buffer, attestationMsg: Buffer, c
 export function CdsiLookup_complete(asyncRuntime: Wrapper<TokioAsyncContext>, lookup: Wrapper<CdsiLookup>): CancellablePromise<LookupResponse>;
 export function CdsiLookup_new(asyncRuntime: Wrapper<TokioAsyncContext>, connectionManager: Wrapper<ConnectionManager>, username: string, password: string, request: Wrapper<LookupRequest>): CancellablePromise<CdsiLookup>;
 export function CdsiLookup_new_routes(asyncRuntime: Wrapper<TokioAsyncContext>, connectionManager: Wrapper<ConnectionManager>, username: string, password: string, request: Wrapper<LookupRequest>): CancellablePromise<CdsiLookup>;
+export function CdsiLookup_nextBatch(asyncRuntime: Wrapper<TokioAsyncContext>, lookup: Wrapper<CdsiLookup>, maxEntries: number): CancellablePromise<LookupResponse>;
 export function CdsiLookup_token(lookup: Wrapper<CdsiLookup>): Buffer;
 export function ChatConnectionInfo_description(connectionInfo: Wrapper<ChatConnectionInfo>): string;
 export function ChatConnectionInfo_ip_version(connectionInfo: Wrapper<ChatConnectionInfo>): number;
 export function ChatConnectionInfo_local_port(connectionInfo: Wrapper<ChatConnectionInfo>): number;
+export function ChatConnectionStateWatcher_current(watcher: Wrapper<ChatConnectionStateWatcher>): ChatConnectionState;
+export function ChatConnectionStateWatcher_new(connectionManager: Wrapper<ConnectionManager>): ChatConnectionStateWatcher;
+export function ChatConnectionStateWatcher_next(asyncRuntime: Wrapper<TokioAsyncContext>, watcher: Wrapper<ChatConnectionStateWatcher>): CancellablePromise<ChatConnectionState>;
+export function ChatConnectionState_attempt(state: Wrapper<ChatConnectionState>): number;
+export function ChatConnectionState_connected_since(state: Wrapper<ChatConnectionState>): Timestamp;
+export function ChatConnectionState_disconnect_reason(state: Wrapper<ChatConnectionState>): number;
+export function ChatConnectionState_kind(state: Wrapper<ChatConnectionState>): number;
 export function CiphertextMessage_FromPlaintextContent(m: Wrapper<PlaintextContent>): CiphertextMessage;
 export function CiphertextMessage_Serialize(obj: Wrapper<CiphertextMessage>): Buffer;
 export function CiphertextMessage_Type(msg: Wrapper<CiphertextMessage>): number;
 export function ComparableBackup_GetComparableString(backup: Wrapper<ComparableBackup>): string;
 export function ComparableBackup_GetUnknownFields(backup: Wrapper<ComparableBackup>): string[];
 export function ComparableBackup_ReadUnencrypted(stream: InputStream, len: bigint, purpose: number): Promise<ComparableBackup>;
+export function ConnectStateResetSummary_dns_lookups_flushed(summary: Wrapper<ConnectStateResetSummary>): number;
+export function ConnectStateResetSummary_outage_cleared(summary: Wrapper<ConnectStateResetSummary>): boolean;
+export function ConnectStateResetSummary_route_failures_cleared(summary: Wrapper<ConnectStateResetSummary>): number;
+export function ConnectionManager_chat_connection_quality(connectionManager: Wrapper<ConnectionManager>): number;
+export function ConnectionManager_clear_endpoint_ip_hints(connectionManager: Wrapper<ConnectionManager>, service: number): void;
 export function ConnectionManager_clear_proxy(connectionManager: Wrapper<ConnectionManager>): void;
+export function ConnectionManager_export_recent_network_events(connectionManager: Wrapper<ConnectionManager>): string;
+export function ConnectionManager_force_network_change(connectionManager: Wrapper<ConnectionManager>): void;
+export function ConnectionManager_last_connect_state_reset(connectionManager: Wrapper<ConnectionManager>): ConnectStateResetSummary | null;
 export function ConnectionManager_new(environment: number, userAgent: string): ConnectionManager;
 export function ConnectionManager_on_network_change(connectionManager: Wrapper<ConnectionManager>): void;
+export function ConnectionManager_reset_connect_state(connectionManager: Wrapper<ConnectionManager>): ConnectStateResetSummary;
 export function ConnectionManager_set_censorship_circumvention_enabled(connectionManager: Wrapper<ConnectionManager>, enabled: boolean): void;
+export function ConnectionManager_set_connection_quality_thresholds(connectionManager: Wrapper<ConnectionManager>, goodBelowMillis: number, badAboveMillis: number): void;
+export function ConnectionManager_set_endpoint_ip_hints(connectionManager: Wrapper<ConnectionManager>, service: number, addresses: string, ttlSeconds: number): void;
 export function ConnectionManager_set_invalid_proxy(connectionManager: Wrapper<ConnectionManager>): void;
 export function ConnectionManager_set_ipv6_enabled(connectionManager: Wrapper<ConnectionManager>, ipv6Enabled: boolean): void;
+export function ConnectionManager_set_local_bind_address(connectionManager: Wrapper<ConnectionManager>, address: string | null): void;
+export function ConnectionManager_set_network_type(connectionManager: Wrapper<ConnectionManager>, networkType: number): void;
 export function ConnectionManager_set_proxy(connectionManager: Wrapper<ConnectionManager>, proxy: Wrapper<ConnectionProxyConfig>): void;
-export function ConnectionProxyConfig_new(scheme: string, host: string, port: number, username: string | null, password: string | null): ConnectionProxyConfig;
+export function ConnectionProxyConfig_new(scheme: string, host: string, port: number, username: string | null, password: string | null, connectHeaders: string): ConnectionProxyConfig;
 export function CreateCallLinkCredentialPresentation_CheckValidContents(presentationBytes: Buffer): void;
 export function CreateCallLinkCredentialPresentation_Verify(presentationBytes: Buffer, roomId: Buffer, now: Timestamp, serverParamsBytes: Buffer, callLinkParamsBytes: Buffer): void;
 export function CreateCallLinkCredentialRequestContext_CheckValidContents(contextBytes: Buffer): void;
@@ -302,10 +336,14 @@ export function IdentityKeyPair_Deserialize(buffer: Buffer): {publicKey:PublicKe
 export function IdentityKeyPair_Serialize(publicKey: Wrapper<PublicKey>, privateKey: Wrapper<PrivateKey>): Buffer;
 export function IdentityKeyPair_SignAlternateIdentity(publicKey: Wrapper<PublicKey>, privateKey: Wrapper<PrivateKey>, otherIdentity: Wrapper<PublicKey>): Buffer;
 export function IdentityKey_VerifyAlternateIdentity(publicKey: Wrapper<PublicKey>, otherIdentity: Wrapper<PublicKey>, signature: Buffer): boolean;
+export function ImportedKeyTransState_GetAccountData(state: Wrapper<ImportedKeyTransState>): Buffer;
+export function ImportedKeyTransState_GetLastDistinguishedTreeHead(state: Wrapper<ImportedKeyTransState>): Buffer;
 export function IncrementalMac_CalculateChunkSize(dataSize: number): number;
 export function IncrementalMac_Finalize(mac: Wrapper<IncrementalMac>): Buffer;
 export function IncrementalMac_Initialize(key: Buffer, chunkSize: number): IncrementalMac;
 export function IncrementalMac_Update(mac: Wrapper<IncrementalMac>, bytes: Buffer, offset: number, length: number): Buffer;
+export function KeyTransparency_ExportState(accountData: Buffer, lastDistinguishedTreeHead: Buffer): Buffer;
+export function KeyTransparency_ImportState(bytes: Buffer): ImportedKeyTransState;
 export function KyberKeyPair_Generate(): KyberKeyPair;
 export function KyberKeyPair_GetPublicKey(keyPair: Wrapper<KyberKeyPair>): KyberPublicKey;
 export function KyberKeyPair_GetSecretKey(keyPair: Wrapper<KyberKeyPair>): KyberSecretKey;
@@ -326,6 +364,7 @@ export function KyberSecretKey_Serialize(obj: Wrapper<KyberSecretKey>): Buffer;
 export function LookupRequest_addAciAndAccessKey(request: Wrapper<LookupRequest>, aci: Buffer, accessKey: Buffer): void;
 export function LookupRequest_addE164(request: Wrapper<LookupRequest>, e164: string): void;
 export function LookupRequest_addPreviousE164(request: Wrapper<LookupRequest>, e164: string): void;
+export function LookupRequest_addRemovedE164(request: Wrapper<LookupRequest>, e164: string): void;
 export function LookupRequest_new(): LookupRequest;
 export function LookupRequest_setToken(request: Wrapper<LookupRequest>, token: Buffer): void;
 export function MessageBackupKey_FromAccountEntropyPool(accountEntropy: AccountEntropyPool, aci: Buffer): MessageBackupKey;
@@ -576,10 +615,11 @@ export function TESTING_TestingHandleType_getValue(handle: Wrapper<TestingHandle
 export function TokioAsyncContext_cancel(context: Wrapper<TokioAsyncContext>, rawCancellationId: bigint): void;
 export function TokioAsyncContext_new(): TokioAsyncContext;
 export function UnauthenticatedChatConnection_connect(asyncRuntime: Wrapper<TokioAsyncContext>, connectionManager: Wrapper<ConnectionManager>): CancellablePromise<UnauthenticatedChatConnection>;
+export function UnauthenticatedChatConnection_connect_with_listener(asyncRuntime: Wrapper<TokioAsyncContext>, connectionManager: Wrapper<ConnectionManager>, progressListener: ChatConnectListener): CancellablePromise<UnauthenticatedChatConnection>;
 export function UnauthenticatedChatConnection_disconnect(asyncRuntime: Wrapper<TokioAsyncContext>, chat: Wrapper<UnauthenticatedChatConnection>): CancellablePromise<void>;
 export function UnauthenticatedChatConnection_info(chat: Wrapper<UnauthenticatedChatConnection>): ChatConnectionInfo;
 export function UnauthenticatedChatConnection_init_listener(chat: Wrapper<UnauthenticatedChatConnection>, listener: ChatListener): void;
-export function UnauthenticatedChatConnection_send(asyncRuntime: Wrapper<TokioAsyncContext>, chat: Wrapper<UnauthenticatedChatConnection>, httpRequest: Wrapper<HttpRequest>, timeoutMillis: number): CancellablePromise<ChatResponse>;
+export function UnauthenticatedChatConnection_send(asyncRuntime: Wrapper<TokioAsyncContext>, chat: Wrapper<UnauthenticatedChatConnection>, httpRequest: Wrapper<HttpRequest>, deadline: Timestamp): CancellablePromise<ChatResponse>;
 export function UnidentifiedSenderMessageContent_Deserialize(data: Buffer): UnidentifiedSenderMessageContent;
 export function UnidentifiedSenderMessageContent_GetContentHint(m: Wrapper<UnidentifiedSenderMessageContent>): number;
 export function UnidentifiedSenderMessageContent_GetContents(obj: Wrapper<UnidentifiedSenderMessageContent>): Buffer;
@@ -606,9 +646,12 @@ interface Aes256GcmSiv { readonly __type: unique symbol; }
 interface AuthenticatedChatConnection { readonly __type: unique symbol; }
 interface CdsiLookup { readonly __type: unique symbol; }
 interface ChatConnectionInfo { readonly __type: unique symbol; }
+interface ChatConnectionState { readonly __type: unique symbol; }
+interface ChatConnectionStateWatcher { readonly __type: unique symbol; }
 interface CiphertextMessage { readonly __type: unique symbol; }
 interface ComparableBackup { readonly __type: unique symbol; }
 interface ComparableBackup { readonly __type: unique symbol; }
+interface ConnectStateResetSummary { readonly __type: unique symbol; }
 interface ConnectionManager { readonly __type: unique symbol; }
 interface ConnectionProxyConfig { readonly __type: unique symbol; }
 interface DecryptionErrorMessage { readonly __type: unique symbol; }
@@ -623,6 +666,7 @@ interface GroupPublicParams { readonly __type: unique symbol; }
 interface GroupSecretParams { readonly __type: unique symbol; }
 interface HsmEnclaveClient { readonly __type: unique symbol; }
 interface HttpRequest { readonly __type: unique symbol; }
+interface ImportedKeyTransState { readonly __type: unique symbol; }
 interface IncrementalMac { readonly __type: unique symbol; }
 interface KyberKeyPair { readonly __type: unique symbol; }
 interface KyberPreKeyRecord { readonly __type: unique symbol; }
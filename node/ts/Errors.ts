@@ -42,14 +42,17 @@ export enum ErrorCode {
   InvalidUsernameLinkEncryptedData,
 
   RateLimitedError,
+  RateLimitChallengeError,
 
   SvrDataMissing,
   SvrRequestFailed,
   SvrRestoreFailed,
 
   ChatServiceInactive,
+  ConnectionLost,
   AppExpired,
   DeviceDelinked,
+  ProxyFailure,
 
   BackupValidation,
 
@@ -238,20 +241,49 @@ export type UnsupportedMediaInputError = LibSignalErrorCommon & {
 export type RateLimitedError = LibSignalErrorBase & {
   code: ErrorCode.RateLimitedError;
   readonly retryAfterSecs: number;
+  /**
+   * When to retry, in milliseconds since the Unix epoch.
+   *
+   * Only present for errors from the chat server, where it is measured from
+   * when the server's response was received and so doesn't get stale.
+   */
+  readonly retryAtMillis?: number;
+};
+
+export type RateLimitChallengeError = LibSignalErrorBase & {
+  code: ErrorCode.RateLimitChallengeError;
+  /** Identifies the challenge; submit it along with the solution. */
+  readonly token: string;
+  /** The kinds of challenge that may be completed, e.g. `"recaptcha"`. */
+  readonly options: ReadonlyArray<string>;
 };
 
 export type ChatServiceInactive = LibSignalErrorBase & {
   code: ErrorCode.ChatServiceInactive;
 };
 
+export type ConnectionLostError = LibSignalErrorBase & {
+  code: ErrorCode.ConnectionLost;
+  /** The code from the server's close frame, if the server sent one. */
+  readonly closeCode?: number;
+};
+
 export type AppExpiredError = LibSignalErrorBase & {
   code: ErrorCode.AppExpired;
+  /** The oldest app version the server still accepts, if it said. */
+  readonly minimumVersion?: string;
+  /** An `https` URL to get a newer version of the app from, if the server said. */
+  readonly upgradeUrl?: string;
 };
 
 export type DeviceDelinkedError = LibSignalErrorBase & {
   code: ErrorCode.DeviceDelinked;
 };
 
+export type ProxyFailureError = LibSignalErrorBase & {
+  code: ErrorCode.ProxyFailure;
+};
+
 export type SvrDataMissingError = LibSignalErrorBase & {
   code: ErrorCode.SvrDataMissing;
 };
@@ -307,8 +339,11 @@ export type LibSignalError =
   | SvrRequestFailedError
   | UnsupportedMediaInputError
   | ChatServiceInactive
+  | ConnectionLostError
   | AppExpiredError
   | DeviceDelinkedError
+  | ProxyFailureError
   | RateLimitedError
+  | RateLimitChallengeError
   | BackupValidationError
   | CancellationError;
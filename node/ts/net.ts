@@ -16,6 +16,7 @@ import {
 } from './net/Chat';
 export * from './net/CDSI';
 export * from './net/Chat';
+export * from './net/KeyTransparency';
 
 // This must match the libsignal-bridge Rust enum of the same name.
 export enum Environment {
@@ -23,6 +24,28 @@ export enum Environment {
   Production = 1,
 }
 
+/** The kind of network the device is using. See {@link Net#setNetworkType}. */
+export enum NetworkType {
+  Unknown = 0,
+  Wifi = 1,
+  Cellular = 2,
+  Vpn = 3,
+}
+
+/** A Signal service. See {@link Net#setEndpointIpHints}. */
+export enum ServiceKind {
+  Chat = 0,
+  Cdsi = 1,
+  Svr = 2,
+}
+
+/** How responsive the chat connection is. See {@link Net#chatConnectionQuality}. */
+export enum ConnectionQuality {
+  Good = 1,
+  Ok = 2,
+  Bad = 3,
+}
+
 export type ServiceAuth = {
   username: string;
   password: string;
@@ -95,6 +118,11 @@ export type ProxyOptions = {
   port?: number;
   username?: string;
   password?: string;
+  /**
+   * Extra headers for HTTP proxies' CONNECT requests, such as a bearer token in
+   * `Proxy-Authorization` or a header identifying the tenant.
+   */
+  connectHeaders?: Readonly<Record<string, string>>;
 };
 
 /** The "scheme" for Signal TLS proxies. See {@link Net.setProxy()}. */
@@ -218,6 +246,95 @@ export class Net {
     );
   }
 
+  /**
+   * Tells libsignal what kind of network the device is using.
+   *
+   * This is used to adjust timeouts and route ordering for *new* connections. On a VPN,
+   * domain-fronted routes are not tried when a direct route is available. It is not itself a
+   * network change; call {@link #onNetworkChange} as well if the network changed.
+   */
+  public setNetworkType(networkType: NetworkType): void {
+    Native.ConnectionManager_set_network_type(
+      this._connectionManager,
+      networkType
+    );
+  }
+
+  /**
+   * Adds `addresses` as candidates when connecting to `service` for the next `ttlSeconds`.
+   *
+   * This is for addresses the app learned about out-of-band, e.g. during a censorship event.
+   * They're tried after the addresses found by resolving the service's hostname, and are used on
+   * their own if resolution fails. Replaces any hints previously set for `service`. The TTL is
+   * capped at one week.
+   *
+   * Throws if any of the addresses isn't a valid IPv4 or IPv6 address.
+   */
+  public setEndpointIpHints(
+    service: ServiceKind,
+    addresses: ReadonlyArray<string>,
+    ttlSeconds: number
+  ): void {
+    Native.ConnectionManager_set_endpoint_ip_hints(
+      this._connectionManager,
+      service,
+      addresses.join(','),
+      ttlSeconds
+    );
+  }
+
+  /** Discards any addresses set by {@link #setEndpointIpHints} for `service`. */
+  public clearEndpointIpHints(service: ServiceKind): void {
+    Native.ConnectionManager_clear_endpoint_ip_hints(
+      this._connectionManager,
+      service
+    );
+  }
+
+  /**
+   * How responsive the authenticated chat connection is.
+   *
+   * Based on the round-trip times of the connection's handshake and keepalive pings, so checking
+   * this doesn't send anything. Returns `null` if there's no connected authenticated chat
+   * connection.
+   */
+  public chatConnectionQuality(): ConnectionQuality | null {
+    const quality = Native.ConnectionManager_chat_connection_quality(
+      this._connectionManager
+    );
+    return quality === 0 ? null : (quality as ConnectionQuality);
+  }
+
+  /**
+   * Sets where {@link #chatConnectionQuality} draws the lines between levels.
+   *
+   * Round trips faster than `goodBelowMillis` are good, and ones slower than `badAboveMillis` are
+   * bad. Throws if `goodBelowMillis` is greater than `badAboveMillis`.
+   */
+  public setConnectionQualityThresholds(
+    goodBelowMillis: number,
+    badAboveMillis: number
+  ): void {
+    Native.ConnectionManager_set_connection_quality_thresholds(
+      this._connectionManager,
+      goodBelowMillis,
+      badAboveMillis
+    );
+  }
+
+  /**
+   * The most recent connection attempts, oldest first, as a JSON array suitable for attaching to a
+   * bug report.
+   *
+   * Each event only has a timestamp, a duration, and coarse categories for the service, route, and
+   * outcome, so the export never contains addresses or anything else that identifies the user.
+   */
+  public exportRecentNetworkEvents(): string {
+    return Native.ConnectionManager_export_recent_network_events(
+      this._connectionManager
+    );
+  }
+
   /**
    * Sets the proxy host to be used for all new connections (until overridden).
    *
@@ -259,7 +376,8 @@ export class Net {
         username,
       };
     }
-    const { scheme, host, port, username, password } = hostOrOptions;
+    const { scheme, host, port, username, password, connectHeaders } =
+      hostOrOptions;
     try {
       const proxyConfig = newNativeHandle(
         Native.ConnectionProxyConfig_new(
@@ -268,7 +386,10 @@ export class Net {
           // i32::MIN represents "no port provided"; we don't expect anyone to pass that manually.
           port ?? -0x8000_0000,
           username ?? null,
-          password ?? null
+          password ?? null,
+          Object.entries(connectHeaders ?? {})
+            .map(([name, value]) => `${name}: ${value}\n`)
+            .join('')
         )
       );
       Native.ConnectionManager_set_proxy(this._connectionManager, proxyConfig);
@@ -370,6 +491,16 @@ export class Net {
     Native.ConnectionManager_on_network_change(this._connectionManager);
   }
 
+  /**
+   * Like {@link #onNetworkChange}, but takes effect even if the network was
+   * reported as changed very recently.
+   *
+   * Meant for explicit user actions, like a "retry now" button.
+   */
+  forceNetworkChange(): void {
+    Native.ConnectionManager_force_network_change(this._connectionManager);
+  }
+
   async cdsiLookup(
     auth: Readonly<ServiceAuth>,
     options: ReadonlyDeep<CDSRequestOptionsType>
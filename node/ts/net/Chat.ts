@@ -38,6 +38,21 @@ export interface ConnectionEventsListener {
    * will be provided.
    */
   onConnectionInterrupted(cause: LibSignalError | null): void;
+
+  /**
+   * Called when the server sends a request that doesn't correspond to any of
+   * the other callbacks.
+   *
+   * `body` is empty if the request didn't have one. Respond to the server with
+   * `ack`'s `send` method.
+   *
+   * If not implemented, requests are responded to with 404 Not Found.
+   */
+  onUnrecognizedRequest?(
+    path: string,
+    body: Buffer,
+    ack: ChatServerMessageAck
+  ): void;
 }
 
 export interface ChatServiceListener extends ConnectionEventsListener {
@@ -178,7 +193,8 @@ export class UnauthenticatedChatConnection implements ChatConnection {
         this.asyncContext,
         this.chatService,
         buildHttpRequest(chatRequest),
-        chatRequest.timeoutMillis ?? DEFAULT_CHAT_REQUEST_TIMEOUT_MILLIS
+        Date.now() +
+          (chatRequest.timeoutMillis ?? DEFAULT_CHAT_REQUEST_TIMEOUT_MILLIS)
       )
     );
   }
@@ -283,7 +299,8 @@ export class AuthenticatedChatConnection implements ChatConnection {
         this.asyncContext,
         this.chatService,
         buildHttpRequest(chatRequest),
-        chatRequest.timeoutMillis ?? DEFAULT_CHAT_REQUEST_TIMEOUT_MILLIS
+        Date.now() +
+          (chatRequest.timeoutMillis ?? DEFAULT_CHAT_REQUEST_TIMEOUT_MILLIS)
       )
     );
   }
@@ -340,6 +357,27 @@ class WeakListenerWrapper implements Native.ChatListener {
   _received_alerts(alerts: string[]): void {
     this.listener.deref()?._received_alerts(alerts);
   }
+  _received_unrecognized_request(
+    path: string,
+    body: Buffer,
+    ack: ServerMessageAck
+  ): void {
+    this.listener.deref()?._received_unrecognized_request(path, body, ack);
+  }
+}
+
+function receivedUnrecognizedRequest(
+  listener: ConnectionEventsListener,
+  path: string,
+  body: Buffer,
+  ack: ServerMessageAck
+): void {
+  const chatAck = new ChatServerMessageAck(ack);
+  if (listener.onUnrecognizedRequest) {
+    listener.onUnrecognizedRequest(path, body, chatAck);
+  } else {
+    chatAck.send(404);
+  }
 }
 
 function makeNativeChatListener(
@@ -365,6 +403,13 @@ function makeNativeChatListener(
       _received_alerts(alerts: string[]): void {
         listener.onReceivedAlerts?.(alerts);
       },
+      _received_unrecognized_request(
+        path: string,
+        body: Buffer,
+        ack: ServerMessageAck
+      ): void {
+        receivedUnrecognizedRequest(listener, path, body, ack);
+      },
       _connection_interrupted(cause: Error | null): void {
         listener.onConnectionInterrupted(cause as LibSignalError | null);
       },
@@ -385,6 +430,13 @@ function makeNativeChatListener(
     _received_alerts(_alerts: string[]): void {
       throw new Error('Event not supported on unauthenticated connection');
     },
+    _received_unrecognized_request(
+      path: string,
+      body: Buffer,
+      ack: ServerMessageAck
+    ): void {
+      receivedUnrecognizedRequest(listener, path, body, ack);
+    },
     _connection_interrupted(cause: LibSignalError | null): void {
       listener.onConnectionInterrupted(cause);
     },
@@ -0,0 +1,56 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+import * as Native from '../../Native';
+import { Buffer } from 'node:buffer';
+import { newNativeHandle } from '../net';
+
+/**
+ * The key transparency state kept for one account, in the serialized forms an
+ * app persists.
+ */
+export type KeyTransparencyState = {
+  /** A serialized `StoredAccountData` message. */
+  accountData: Buffer;
+  /** A serialized `StoredTreeHead` message. */
+  lastDistinguishedTreeHead: Buffer;
+};
+
+/**
+ * Packs an account's key transparency state, so that it can be moved to
+ * another device during device transfer.
+ *
+ * The result carries a checksum, so {@link importKeyTransparencyState} rejects
+ * an export that was cut short or corrupted.
+ *
+ * @throws {LibSignalError} if either part of the state is corrupted.
+ */
+export function exportKeyTransparencyState({
+  accountData,
+  lastDistinguishedTreeHead,
+}: Readonly<KeyTransparencyState>): Buffer {
+  return Native.KeyTransparency_ExportState(
+    accountData,
+    lastDistinguishedTreeHead
+  );
+}
+
+/**
+ * Unpacks state produced by {@link exportKeyTransparencyState} on another
+ * device.
+ *
+ * @throws {LibSignalError} if `exported` is truncated, corrupted, or from an
+ * unsupported version.
+ */
+export function importKeyTransparencyState(
+  exported: Buffer
+): KeyTransparencyState {
+  const state = newNativeHandle(Native.KeyTransparency_ImportState(exported));
+  return {
+    accountData: Native.ImportedKeyTransState_GetAccountData(state),
+    lastDistinguishedTreeHead:
+      Native.ImportedKeyTransState_GetLastDistinguishedTreeHead(state),
+  };
+}
@@ -17,6 +17,7 @@ import {
   ChatServerMessageAck,
   ChatServiceListener,
   Environment,
+  importKeyTransparencyState,
   Net,
   newNativeHandle,
   SIGNAL_TLS_PROXY_SCHEME,
@@ -45,14 +46,31 @@ describe('Net class', () => {
       userAgent: userAgent,
     });
     net.onNetworkChange();
+    net.forceNetworkChange();
+  });
+
+  it('has no recent network events before connecting', () => {
+    const net = new Net({
+      env: Environment.Production,
+      userAgent: userAgent,
+    });
+    expect(net.exportRecentNetworkEvents()).to.equal('[]');
   });
 });
 
 describe('chat service api', () => {
   it('converts connect errors to native', () => {
     const cases: Array<[string, ErrorCode | object]> = [
-      ['AppExpired', ErrorCode.AppExpired],
+      [
+        'AppExpired',
+        {
+          code: ErrorCode.AppExpired,
+          minimumVersion: '7.40.0',
+          upgradeUrl: 'https://signal.org/install',
+        },
+      ],
       ['DeviceDeregistered', ErrorCode.DeviceDelinked],
+      ['ProxyAuthFailed', ErrorCode.ProxyFailure],
 
       ['WebSocketConnectionFailed', ErrorCode.IoError],
       ['Timeout', ErrorCode.IoError],
@@ -79,6 +97,9 @@ describe('chat service api', () => {
   it('converts send errors to native', () => {
     const cases: Array<[string, ErrorCode | object]> = [
       ['Disconnected', ErrorCode.ChatServiceInactive],
+      ['ConnectionIdleTimeout', ErrorCode.ChatServiceInactive],
+      ['ConnectionLost', { code: ErrorCode.ConnectionLost, closeCode: 4001 }],
+      ['ConnectionLostWithoutCloseCode', ErrorCode.ConnectionLost],
 
       ['WebSocketConnectionReset', ErrorCode.IoError],
       ['IncomingDataInvalid', ErrorCode.IoError],
@@ -98,9 +119,10 @@ describe('chat service api', () => {
 
   it('converts Response object to native', () => {
     const status = 200;
+    // Responses only carry allowlisted headers, so 'forwarded' is dropped.
     const headers: ReadonlyArray<[string, string]> = [
       ['content-type', 'application/octet-stream'],
-      ['forwarded', '1.1.1.1'],
+      ['retry-after', '30'],
     ];
     const expectedWithContent: ChatResponse = {
       status: status,
@@ -203,6 +225,20 @@ describe('chat service api', () => {
     check(() =>
       net.setProxy({ scheme: 'socks+shoes', host: 'signalfoundation.org' })
     );
+    check(() =>
+      net.setProxy({
+        scheme: 'socks5',
+        host: 'signalfoundation.org',
+        connectHeaders: { 'X-Tenant': 'tenant' },
+      })
+    );
+    check(() =>
+      net.setProxy({
+        scheme: 'http',
+        host: 'signalfoundation.org',
+        connectHeaders: { Host: 'example.com' },
+      })
+    );
 
     check(() => net.setProxyFromUrl('not a url'));
     check(() => net.setProxyFromUrl('socks+shoes://signalfoundation.org'));
@@ -520,6 +556,37 @@ describe('chat service api', () => {
       await check(EMPTY_QUEUE, listener.onQueueEmpty, []);
     });
 
+    it('unrecognized requests are passed to the listener', async () => {
+      const listener = {
+        onIncomingMessage: sinon.stub(),
+        onQueueEmpty: sinon.stub(),
+        onUnrecognizedRequest: sinon.stub(),
+        onConnectionInterrupted: sinon.stub(),
+      };
+      const received = new CompletablePromise();
+      listener.onUnrecognizedRequest.callsFake(() => received.complete());
+
+      const tokio = new TokioAsyncContext(Native.TokioAsyncContext_new());
+      const [_chat, fakeRemote] = AuthenticatedChatConnection.fakeConnect(
+        tokio,
+        listener
+      );
+      Native.TESTING_FakeChatRemoteEnd_SendRawServerRequest(
+        fakeRemote,
+        INVALID_MESSAGE
+      );
+      await received.done();
+
+      expect(listener.onUnrecognizedRequest).to.have.been.calledOnceWith(
+        '/invalid',
+        Buffer.of(),
+        sinon.match.instanceOf(ChatServerMessageAck)
+      );
+      const ack = listener.onUnrecognizedRequest.firstCall
+        .args[2] as ChatServerMessageAck;
+      ack.send(204);
+    });
+
     it('messages arrive in order', async () => {
       const listener: ChatServiceListener = {
         onIncomingMessage(
@@ -789,6 +856,11 @@ describe('cdsi lookup', () => {
           'invalid response received from the server',
         ],
         ['RetryAfter42Seconds', ErrorCode.RateLimitedError, 'retry later'],
+        [
+          'RateLimitChallenge',
+          ErrorCode.RateLimitChallengeError,
+          'rate limit challenge with options ["recaptcha"]',
+        ],
         [
           'InvalidToken',
           ErrorCode.CdsiInvalidToken,
@@ -829,3 +901,12 @@ describe('cdsi lookup', () => {
     });
   });
 });
+
+describe('key transparency state', () => {
+  it('rejects a truncated export', () => {
+    expect(() => importKeyTransparencyState(Buffer.alloc(0))).throws(
+      LibSignalErrorBase,
+      'truncated'
+    );
+  });
+});
@@ -135,6 +135,36 @@ pub unsafe extern "C" fn signal_error_get_retry_after_seconds(
     })
 }
 
+/// Gets the wall-clock time (in milliseconds since the Unix epoch) at which an
+/// operation that was rate-limited by the server can be retried.
+///
+/// Unlike [`signal_error_get_retry_after_seconds`], this doesn't become stale
+/// as time passes before the error is handled.
+#[no_mangle]
+pub unsafe extern "C" fn signal_error_get_retry_at_timestamp(
+    err: *const SignalFfiError,
+    out: *mut u64,
+) -> *mut SignalFfiError {
+    let err = AssertUnwindSafe(err);
+    run_ffi_safe(|| {
+        let err = err.as_ref().ok_or(NullPointerError)?;
+        let value = err.provide_retry_at().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "cannot get retry_at from error ({})",
+                err
+            ))
+        })?;
+        let millis = value
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        write_result_to(
+            out,
+            Timestamp::from_epoch_millis(millis.try_into().unwrap_or(u64::MAX)),
+        )
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn signal_error_get_tries_remaining(
     err: *const SignalFfiError,
@@ -174,6 +204,99 @@ pub unsafe extern "C" fn signal_error_get_unknown_fields(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn signal_error_get_rate_limit_challenge_token(
+    err: *const SignalFfiError,
+    out: *mut *const c_char,
+) -> *mut SignalFfiError {
+    let err = AssertUnwindSafe(err);
+    run_ffi_safe(|| {
+        let err = err.as_ref().ok_or(NullPointerError)?;
+        let value = err.provide_rate_limit_challenge().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "cannot get rate_limit_challenge from error ({})",
+                err
+            ))
+        })?;
+        write_result_to(out, value.token)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn signal_error_get_rate_limit_challenge_options(
+    err: *const SignalFfiError,
+    out: *mut StringArray,
+) -> *mut SignalFfiError {
+    let err = AssertUnwindSafe(err);
+    run_ffi_safe(|| {
+        let err = err.as_ref().ok_or(NullPointerError)?;
+        let value = err.provide_rate_limit_challenge().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "cannot get rate_limit_challenge from error ({})",
+                err
+            ))
+        })?;
+        write_result_to(out, value.options.into_boxed_slice())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn signal_error_get_app_expired_minimum_version(
+    err: *const SignalFfiError,
+    out: *mut *const c_char,
+) -> *mut SignalFfiError {
+    let err = AssertUnwindSafe(err);
+    run_ffi_safe(|| {
+        let err = err.as_ref().ok_or(NullPointerError)?;
+        let value = err.provide_app_expired_minimum_version().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "cannot get minimum_version from error ({})",
+                err
+            ))
+        })?;
+        write_result_to(out, value)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn signal_error_get_app_expired_upgrade_url(
+    err: *const SignalFfiError,
+    out: *mut *const c_char,
+) -> *mut SignalFfiError {
+    let err = AssertUnwindSafe(err);
+    run_ffi_safe(|| {
+        let err = err.as_ref().ok_or(NullPointerError)?;
+        let value = err.provide_app_expired_upgrade_url().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "cannot get upgrade_url from error ({})",
+                err
+            ))
+        })?;
+        write_result_to(out, value)
+    })
+}
+
+/// Writes the code from the server's close frame, or 0 if the connection ended without one.
+///
+/// 0 is never a valid close code, so it can't be confused with a real one.
+#[no_mangle]
+pub unsafe extern "C" fn signal_error_get_close_code(
+    err: *const SignalFfiError,
+    out: *mut u16,
+) -> *mut SignalFfiError {
+    let err = AssertUnwindSafe(err);
+    run_ffi_safe(|| {
+        let err = err.as_ref().ok_or(NullPointerError)?;
+        let value = err.provide_close_code().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "cannot get close_code from error ({})",
+                err
+            ))
+        })?;
+        write_result_to(out, value.unwrap_or(0))
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn signal_error_free(err: *mut SignalFfiError) {
     if !err.is_null() {
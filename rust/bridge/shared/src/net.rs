@@ -3,15 +3,23 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::net::IpAddr;
 use std::num::NonZeroU16;
 
 use base64::prelude::{Engine, BASE64_STANDARD};
 use libsignal_bridge_macros::bridge_fn;
+use libsignal_bridge_types::net::ConnectStateResetSummary;
 pub use libsignal_bridge_types::net::{ConnectionManager, Environment, TokioAsyncContext};
 use libsignal_net::auth::Auth;
 use libsignal_net::chat::ConnectionInfo;
+use libsignal_net::connect_state::NetworkType;
+use libsignal_net::connection_quality::{ConnectionQuality, ConnectionQualityThresholds};
 use libsignal_net::infra::errors::LogSafeDisplay;
-use libsignal_net::infra::route::ConnectionProxyConfig;
+use libsignal_net::infra::route::{
+    ConnectionProxyConfig, HttpProxyConnectHeaders, InvalidProxyConnectHeader,
+};
+use libsignal_net::metrics::ServiceKind;
+use libsignal_protocol::SignalProtocolError;
 
 use crate::support::*;
 use crate::*;
@@ -25,6 +33,8 @@ bridge_handle_fns!(ConnectionInfo, clone = false, jni = false);
 
 bridge_handle_fns!(ConnectionProxyConfig);
 
+/// `connect_headers` holds extra headers for HTTP proxies' CONNECT requests, one
+/// `Name: value` per line.
 #[bridge_fn]
 fn ConnectionProxyConfig_new(
     mut scheme: String,
@@ -32,6 +42,7 @@ fn ConnectionProxyConfig_new(
     port: i32,
     username: Option<String>,
     password: Option<String>,
+    connect_headers: String,
 ) -> Result<ConnectionProxyConfig, std::io::Error> {
     // We take port as an i32 because Java 'short' is signed and thus can't represent all port
     // numbers, and we want too-large port numbers to be handled the same way as 0. However, we
@@ -66,11 +77,29 @@ fn ConnectionProxyConfig_new(
         (Some(username), password) => Some((username, password.unwrap_or_default())),
     };
 
+    let connect_headers = connect_headers
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (name, value) = line.split_once(':').ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "CONNECT headers must be formatted as 'Name: value'",
+                )
+            })?;
+            Ok((name, value.trim()))
+        })
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    let connect_headers = HttpProxyConnectHeaders::new(connect_headers).map_err(|e| {
+        static_assertions::assert_impl_all!(InvalidProxyConnectHeader: LogSafeDisplay);
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
+    })?;
+
     // We allow clients to pass in upper or mixed-case schemes, but convert to
     // lowercase for ease of matching.
     scheme.make_ascii_lowercase();
 
-    ConnectionProxyConfig::from_parts(&scheme, &host, port, auth).map_err(|e| {
+    ConnectionProxyConfig::from_parts(&scheme, &host, port, auth, connect_headers).map_err(|e| {
         use libsignal_net::infra::route::ProxyFromPartsError;
         static_assertions::assert_impl_all!(ProxyFromPartsError: LogSafeDisplay);
         match e {
@@ -79,7 +108,9 @@ fn ConnectionProxyConfig_new(
             }
             ProxyFromPartsError::MissingHost
             | ProxyFromPartsError::SchemeDoesNotSupportUsernames(_)
-            | ProxyFromPartsError::SchemeDoesNotSupportPasswords(_) => {
+            | ProxyFromPartsError::SchemeDoesNotSupportPasswords(_)
+            | ProxyFromPartsError::SchemeDoesNotSupportConnectHeaders(_)
+            | ProxyFromPartsError::InvalidConnectHeader(_) => {
                 std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
             }
         }
@@ -119,6 +150,64 @@ fn ConnectionManager_set_ipv6_enabled(connection_manager: &ConnectionManager, ip
     connection_manager.set_ipv6_enabled(ipv6_enabled)
 }
 
+#[bridge_fn]
+fn ConnectionManager_set_local_bind_address(
+    connection_manager: &ConnectionManager,
+    address: Option<String>,
+) -> Result<(), std::io::Error> {
+    let address = address
+        .map(|address| {
+            address.parse::<IpAddr>().map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid IP address")
+            })
+        })
+        .transpose()?;
+    connection_manager
+        .set_local_bind_address(address)
+        .map_err(|e| {
+            static_assertions::assert_impl_all!(
+                libsignal_net::infra::tcp_ssl::InvalidLocalBindAddress: LogSafeDisplay
+            );
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
+        })
+}
+
+/// Sets the IP addresses to try for `service` in addition to the ones from DNS.
+///
+/// `addresses` is a comma-separated list; an empty list clears the hints.
+#[bridge_fn]
+fn ConnectionManager_set_endpoint_ip_hints(
+    connection_manager: &ConnectionManager,
+    service: AsType<ServiceKind, u8>,
+    addresses: String,
+    ttl_seconds: u32,
+) -> Result<(), std::io::Error> {
+    let addresses = addresses
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address.parse::<IpAddr>().map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid IP address")
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    connection_manager.set_endpoint_ip_hints(
+        service.into_inner(),
+        addresses,
+        std::time::Duration::from_secs(ttl_seconds.into()),
+    );
+    Ok(())
+}
+
+#[bridge_fn]
+fn ConnectionManager_clear_endpoint_ip_hints(
+    connection_manager: &ConnectionManager,
+    service: AsType<ServiceKind, u8>,
+) {
+    connection_manager.clear_endpoint_ip_hints(service.into_inner())
+}
+
 #[bridge_fn]
 fn ConnectionManager_set_censorship_circumvention_enabled(
     connection_manager: &ConnectionManager,
@@ -127,11 +216,93 @@ fn ConnectionManager_set_censorship_circumvention_enabled(
     connection_manager.set_censorship_circumvention_enabled(enabled)
 }
 
+#[bridge_fn]
+fn ConnectionManager_set_network_type(
+    connection_manager: &ConnectionManager,
+    network_type: AsType<NetworkType, u8>,
+) {
+    connection_manager.set_network_type(network_type.into_inner())
+}
+
 #[bridge_fn]
 fn ConnectionManager_on_network_change(connection_manager: &ConnectionManager) {
     connection_manager.on_network_change(std::time::Instant::now())
 }
 
+#[bridge_fn]
+fn ConnectionManager_force_network_change(connection_manager: &ConnectionManager) {
+    connection_manager.force_network_change(std::time::Instant::now())
+}
+
+/// Returns 0 if there's no connected authenticated chat connection (or it hasn't been measured
+/// yet), 1 for good, 2 for ok, and 3 for bad.
+#[bridge_fn]
+fn ConnectionManager_chat_connection_quality(connection_manager: &ConnectionManager) -> u8 {
+    match connection_manager.chat_connection_quality() {
+        None => 0,
+        Some(ConnectionQuality::Good) => 1,
+        Some(ConnectionQuality::Ok) => 2,
+        Some(ConnectionQuality::Bad) => 3,
+    }
+}
+
+#[bridge_fn]
+fn ConnectionManager_set_connection_quality_thresholds(
+    connection_manager: &ConnectionManager,
+    good_below_millis: u32,
+    bad_above_millis: u32,
+) -> Result<(), SignalProtocolError> {
+    connection_manager
+        .set_connection_quality_thresholds(ConnectionQualityThresholds {
+            good_below: std::time::Duration::from_millis(good_below_millis.into()),
+            bad_above: std::time::Duration::from_millis(bad_above_millis.into()),
+        })
+        .map_err(|e| SignalProtocolError::InvalidArgument(e.to_string()))
+}
+
+bridge_handle_fns!(ConnectStateResetSummary, clone = false);
+
+#[bridge_fn]
+fn ConnectionManager_reset_connect_state(
+    connection_manager: &ConnectionManager,
+) -> ConnectStateResetSummary {
+    connection_manager.reset_connect_state(std::time::Instant::now())
+}
+
+#[bridge_fn]
+fn ConnectionManager_last_connect_state_reset(
+    connection_manager: &ConnectionManager,
+) -> Option<ConnectStateResetSummary> {
+    connection_manager.last_connect_state_reset()
+}
+
+/// Returns the most recent connection attempts as a JSON array, for attaching to bug reports.
+#[bridge_fn]
+fn ConnectionManager_export_recent_network_events(
+    connection_manager: &ConnectionManager,
+) -> String {
+    connection_manager.export_recent_network_events()
+}
+
+#[bridge_fn]
+fn ConnectStateResetSummary_route_failures_cleared(summary: &ConnectStateResetSummary) -> u32 {
+    summary
+        .connect_state
+        .route_failures_cleared
+        .try_into()
+        .unwrap_or(u32::MAX)
+}
+
+#[bridge_fn]
+fn ConnectStateResetSummary_outage_cleared(summary: &ConnectStateResetSummary) -> bool {
+    summary.connect_state.outage_cleared
+}
+
+#[bridge_fn]
+fn ConnectStateResetSummary_dns_lookups_flushed(summary: &ConnectStateResetSummary) -> u32 {
+    summary.dns_lookups_flushed.try_into().unwrap_or(u32::MAX)
+}
+
 #[bridge_fn]
 fn CreateOTP(username: String, secret: &[u8]) -> String {
     Auth::otp(&username, secret, std::time::SystemTime::now())
@@ -160,4 +331,115 @@ mod test {
         ConnectionProxyConfig_new(scheme.to_owned(), "host".to_owned(), 80, None, None)
             .expect("valid")
     }
+
+    /// Summarizes `proxy` as the scheme it corresponds to and its port.
+    fn scheme_and_port(proxy: &ConnectionProxyConfig) -> (&'static str, u16) {
+        use libsignal_net::infra::tcp_ssl::proxy::socks::Protocol;
+        match proxy {
+            ConnectionProxyConfig::Tls(proxy) => ("org.signal.tls", proxy.proxy_port.get()),
+            ConnectionProxyConfig::Tcp(proxy) => ("tcp", proxy.proxy_port.get()),
+            ConnectionProxyConfig::Http(proxy) => (
+                if proxy.proxy_tls.is_some() {
+                    "https"
+                } else {
+                    "http"
+                },
+                proxy.proxy_port.get(),
+            ),
+            ConnectionProxyConfig::Socks(proxy) => (
+                match (&proxy.protocol, proxy.resolve_hostname_locally) {
+                    (Protocol::Socks4 { .. }, true) => "socks4",
+                    (Protocol::Socks4 { .. }, false) => "socks4a",
+                    (Protocol::Socks5 { .. }, true) => "socks5",
+                    (Protocol::Socks5 { .. }, false) => "socks5h",
+                },
+                proxy.proxy_port.get(),
+            ),
+        }
+    }
+
+    #[test_case("org.signal.tls", i32::MIN, None => ("org.signal.tls", 443))]
+    #[test_case("org.signal.tls", 8443, None => ("org.signal.tls", 8443))]
+    #[test_case("org.signal.tls", i32::MIN, Some("UNENCRYPTED_FOR_TESTING") => ("tcp", 80))]
+    #[test_case("http", i32::MIN, None => ("http", 80))]
+    #[test_case("http", 8080, Some("user") => ("http", 8080))]
+    #[test_case("https", i32::MIN, None => ("https", 443))]
+    #[test_case("https", 8443, Some("user") => ("https", 8443))]
+    #[test_case("socks4", i32::MIN, Some("user") => ("socks4", 1080))]
+    #[test_case("socks4a", 9050, None => ("socks4a", 9050))]
+    #[test_case("socks", i32::MIN, None => ("socks5", 1080))]
+    #[test_case("socks5", i32::MIN, Some("user") => ("socks5", 1080))]
+    #[test_case("socks5h", 9050, None => ("socks5h", 9050))]
+    fn connection_proxy_config_supports_scheme(
+        scheme: &str,
+        port: i32,
+        username: Option<&str>,
+    ) -> (&'static str, u16) {
+        let proxy = ConnectionProxyConfig_new(
+            scheme.to_owned(),
+            "proxy.example".to_owned(),
+            port,
+            username.map(ToOwned::to_owned),
+            None,
+        )
+        .expect("valid");
+        scheme_and_port(&proxy)
+    }
+
+    #[test_case("ftp", "host", 21, None, None => std::io::ErrorKind::Unsupported; "unsupported scheme")]
+    #[test_case("http", "", 80, None, None => std::io::ErrorKind::InvalidInput; "missing host")]
+    #[test_case("http", "host", 0, None, None => std::io::ErrorKind::InvalidInput; "zero port")]
+    #[test_case("http", "host", 65536, None, None => std::io::ErrorKind::InvalidInput; "port too large")]
+    #[test_case("http", "host", -1, None, None => std::io::ErrorKind::InvalidInput; "negative port")]
+    #[test_case("http", "host", 80, None, Some("pass") => std::io::ErrorKind::InvalidInput; "password without username")]
+    #[test_case("socks4", "host", 1080, Some("user"), Some("pass") => std::io::ErrorKind::InvalidInput; "socks4 password")]
+    #[test_case("org.signal.tls", "host", 443, Some("user"), None => std::io::ErrorKind::InvalidInput; "signal proxy username")]
+    fn connection_proxy_config_rejects_invalid_parts(
+        scheme: &str,
+        host: &str,
+        port: i32,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) -> std::io::ErrorKind {
+        ConnectionProxyConfig_new(
+            scheme.to_owned(),
+            host.to_owned(),
+            port,
+            username.map(ToOwned::to_owned),
+            password.map(ToOwned::to_owned),
+        )
+        .map(|_| ())
+        .expect_err("invalid")
+        .kind()
+    }
+
+    #[test]
+    fn endpoint_ip_hints_are_parsed_from_a_list() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        ConnectionManager_set_endpoint_ip_hints(
+            &cm,
+            ServiceKind::Chat.into(),
+            "192.0.2.1, 3fff::1".to_owned(),
+            60,
+        )
+        .expect("valid");
+        assert_eq!(cm.endpoint_ip_hint_count(ServiceKind::Chat), 2);
+
+        let err = ConnectionManager_set_endpoint_ip_hints(
+            &cm,
+            ServiceKind::Chat.into(),
+            "192.0.2.1,signal.org".to_owned(),
+            60,
+        )
+        .expect_err("invalid");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            cm.endpoint_ip_hint_count(ServiceKind::Chat),
+            2,
+            "unchanged on error"
+        );
+
+        ConnectionManager_clear_endpoint_ip_hints(&cm, ServiceKind::Chat.into());
+        assert_eq!(cm.endpoint_ip_hint_count(ServiceKind::Chat), 0);
+    }
 }
@@ -4,6 +4,7 @@
 //
 
 use std::convert::TryInto as _;
+use std::num::NonZeroUsize;
 
 use libsignal_bridge_macros::{bridge_fn, bridge_io};
 use libsignal_bridge_types::net::cdsi::{CdsiLookup, LookupRequest};
@@ -33,6 +34,19 @@ fn LookupRequest_addPreviousE164(request: &LookupRequest, e164: E164) {
     request.lock().prev_e164s.push(e164)
 }
 
+#[bridge_fn]
+fn LookupRequest_addRemovedE164(request: &LookupRequest, e164: E164) {
+    request.lock().discard_e164s.push(e164)
+}
+
+/// Continues the lookup that returned `token`, rather than starting over.
+///
+/// The request should list the numbers that lookup covered with
+/// `LookupRequest_addPreviousE164`, along with any numbers to add
+/// (`LookupRequest_addE164`) or remove (`LookupRequest_addRemovedE164`). If the
+/// server has expired the token, the lookup fails with
+/// [`cdsi::LookupError::InvalidToken`], and the app should retry without one.
+/// Either way, the new lookup's `CdsiLookup_token` replaces `token`.
 #[bridge_fn]
 fn LookupRequest_setToken(request: &LookupRequest, token: &[u8]) {
     request.lock().token = token.into();
@@ -97,3 +111,14 @@ async fn CdsiLookup_complete(lookup: &CdsiLookup) -> Result<LookupResponse, cdsi
         .collect()
         .await
 }
+
+/// Returns up to `max_entries` more results; an empty list means the lookup is
+/// complete.
+#[bridge_io(TokioAsyncContext)]
+async fn CdsiLookup_nextBatch(
+    lookup: &CdsiLookup,
+    max_entries: u32,
+) -> Result<LookupResponse, cdsi::LookupError> {
+    let max_entries = NonZeroUsize::new(max_entries as usize).unwrap_or(NonZeroUsize::MIN);
+    lookup.next_batch(max_entries).await
+}
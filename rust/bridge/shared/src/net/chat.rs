@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use std::time::Duration;
+use std::time::SystemTime;
 
 use http::uri::InvalidUri;
 use http::{HeaderName, HeaderValue, StatusCode};
@@ -12,7 +12,10 @@ use libsignal_bridge_types::net::chat::*;
 use libsignal_bridge_types::net::{ConnectionManager, TokioAsyncContext};
 use libsignal_bridge_types::support::AsType;
 use libsignal_net::auth::Auth;
+use libsignal_net::chat::retry::SUGGESTED_RECONNECT_BACKOFF;
+use libsignal_net::chat::state::{ConnectionState, DisconnectReason};
 use libsignal_net::chat::{self, ConnectError, Response as ChatResponse, SendError};
+use libsignal_protocol::Timestamp;
 
 use crate::support::*;
 use crate::*;
@@ -78,6 +81,22 @@ async fn UnauthenticatedChatConnection_connect(
     UnauthenticatedChatConnection::connect(connection_manager).await
 }
 
+/// Reports each stage of the connection to `progress_listener`; see
+/// [`UnauthenticatedChatConnection::connect_with_listener`].
+///
+/// Stages are numbered as in [`bridged_connect_stage`].
+#[bridge_io(TokioAsyncContext)]
+async fn UnauthenticatedChatConnection_connect_with_listener(
+    connection_manager: &ConnectionManager,
+    progress_listener: Box<dyn ChatConnectListener>,
+) -> Result<UnauthenticatedChatConnection, ConnectError> {
+    UnauthenticatedChatConnection::connect_with_listener(
+        connection_manager,
+        progress_listener.into_progress_callback(),
+    )
+    .await
+}
+
 #[bridge_fn]
 fn UnauthenticatedChatConnection_init_listener(
     chat: &UnauthenticatedChatConnection,
@@ -90,17 +109,21 @@ fn UnauthenticatedChatConnection_init_listener(
 async fn UnauthenticatedChatConnection_send(
     chat: &UnauthenticatedChatConnection,
     http_request: &HttpRequest,
-    timeout_millis: u32,
+    deadline: Timestamp,
 ) -> Result<ChatResponse, SendError> {
+    // Check this first, so that time spent waiting to run counts against the deadline.
+    let timeout = chat::timeout_until(deadline.into(), SystemTime::now())?;
     let headers = http_request.headers.lock().expect("not poisoned").clone();
     let request = chat::Request {
         method: http_request.method.clone(),
         path: http_request.path.clone(),
         headers,
         body: http_request.body.clone(),
+        priority: Default::default(),
+        correlation_id: None,
+        gzip_body: false,
     };
-    chat.send(request, Duration::from_millis(timeout_millis.into()))
-        .await
+    chat.send(request, timeout).await
 }
 
 #[bridge_io(TokioAsyncContext)]
@@ -135,6 +158,25 @@ async fn AuthenticatedChatConnection_connect(
     .await
 }
 
+/// Like `UnauthenticatedChatConnection_connect_with_listener`, for an
+/// authenticated connection.
+#[bridge_io(TokioAsyncContext)]
+async fn AuthenticatedChatConnection_connect_with_listener(
+    connection_manager: &ConnectionManager,
+    username: String,
+    password: String,
+    receive_stories: bool,
+    progress_listener: Box<dyn ChatConnectListener>,
+) -> Result<AuthenticatedChatConnection, ConnectError> {
+    AuthenticatedChatConnection::connect_with_listener(
+        connection_manager,
+        Auth { username, password },
+        receive_stories,
+        progress_listener.into_progress_callback(),
+    )
+    .await
+}
+
 #[bridge_fn]
 fn AuthenticatedChatConnection_init_listener(
     chat: &AuthenticatedChatConnection,
@@ -147,17 +189,21 @@ fn AuthenticatedChatConnection_init_listener(
 async fn AuthenticatedChatConnection_send(
     chat: &AuthenticatedChatConnection,
     http_request: &HttpRequest,
-    timeout_millis: u32,
+    deadline: Timestamp,
 ) -> Result<ChatResponse, SendError> {
+    // Check this first, so that time spent waiting to run counts against the deadline.
+    let timeout = chat::timeout_until(deadline.into(), SystemTime::now())?;
     let headers = http_request.headers.lock().expect("not poisoned").clone();
     let request = chat::Request {
         method: http_request.method.clone(),
         path: http_request.path.clone(),
         headers,
         body: http_request.body.clone(),
+        priority: Default::default(),
+        correlation_id: None,
+        gzip_body: false,
     };
-    chat.send(request, Duration::from_millis(timeout_millis.into()))
-        .await
+    chat.send(request, timeout).await
 }
 
 #[bridge_io(TokioAsyncContext)]
@@ -165,11 +211,113 @@ async fn AuthenticatedChatConnection_disconnect(chat: &AuthenticatedChatConnecti
     chat.disconnect().await
 }
 
+/// Reconnects whenever the connection is lost, until stopped; see
+/// [`AuthenticatedChatConnection::run_auto_reconnect`].
+///
+/// `username`, `password`, and `receive_stories` must match the ones the
+/// connection was made with.
+#[bridge_io(TokioAsyncContext)]
+async fn AuthenticatedChatConnection_run_auto_reconnect(
+    chat: &AuthenticatedChatConnection,
+    connection_manager: &ConnectionManager,
+    username: String,
+    password: String,
+    receive_stories: bool,
+) -> Result<(), ConnectError> {
+    chat.run_auto_reconnect(
+        connection_manager,
+        Auth { username, password },
+        receive_stories,
+        SUGGESTED_RECONNECT_BACKOFF,
+    )
+    .await
+}
+
+#[bridge_fn]
+fn AuthenticatedChatConnection_stop_auto_reconnect(chat: &AuthenticatedChatConnection) {
+    chat.stop_auto_reconnect()
+}
+
 #[bridge_fn(jni = false)]
 fn AuthenticatedChatConnection_info(chat: &AuthenticatedChatConnection) -> ChatConnectionInfo {
     chat.info()
 }
 
+bridge_handle_fns!(ChatConnectionStateWatcher, clone = false);
+bridge_handle_fns!(ChatConnectionState, clone = false);
+
+#[bridge_fn]
+fn ChatConnectionStateWatcher_new(
+    connection_manager: &ConnectionManager,
+) -> ChatConnectionStateWatcher {
+    ChatConnectionStateWatcher::new(connection_manager)
+}
+
+#[bridge_fn]
+fn ChatConnectionStateWatcher_current(watcher: &ChatConnectionStateWatcher) -> ChatConnectionState {
+    watcher.current()
+}
+
+#[bridge_io(TokioAsyncContext)]
+async fn ChatConnectionStateWatcher_next(
+    watcher: &ChatConnectionStateWatcher,
+) -> ChatConnectionState {
+    watcher.next().await
+}
+
+/// Returns 0 for disconnected, 1 for connecting, and 2 for connected.
+#[bridge_fn]
+fn ChatConnectionState_kind(state: &ChatConnectionState) -> u8 {
+    match state {
+        ConnectionState::Disconnected(_) => 0,
+        ConnectionState::Connecting { .. } => 1,
+        ConnectionState::Connected { .. } => 2,
+    }
+}
+
+/// Returns 0 if the state is not "connecting".
+#[bridge_fn]
+fn ChatConnectionState_attempt(state: &ChatConnectionState) -> u32 {
+    match state {
+        ConnectionState::Connecting { attempt } => attempt.get(),
+        ConnectionState::Disconnected(_) | ConnectionState::Connected { .. } => 0,
+    }
+}
+
+/// Returns 0 if the state is not "connected".
+#[bridge_fn]
+fn ChatConnectionState_connected_since(state: &ChatConnectionState) -> Timestamp {
+    let since = match state {
+        ConnectionState::Connected { since } => *since,
+        ConnectionState::Disconnected(_) | ConnectionState::Connecting { .. } => {
+            return Timestamp::from_epoch_millis(0)
+        }
+    };
+    Timestamp::from_epoch_millis(
+        since
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+            .try_into()
+            .expect("within a u64"),
+    )
+}
+
+/// Returns 0 for "not started", 1 for a local disconnect, 2 for a failed connection attempt, and
+/// 3 for a lost connection. Returns 0 if the state is not "disconnected".
+#[bridge_fn]
+fn ChatConnectionState_disconnect_reason(state: &ChatConnectionState) -> u8 {
+    match state {
+        ConnectionState::Disconnected(reason) => match reason {
+            DisconnectReason::NotStarted => 0,
+            DisconnectReason::LocalDisconnect => 1,
+            DisconnectReason::ConnectFailed(_) => 2,
+            DisconnectReason::ConnectionLost => 3,
+        },
+        ConnectionState::Connecting { .. } | ConnectionState::Connected { .. } => 0,
+    }
+}
+
 bridge_handle_fns!(ServerMessageAck, clone = false);
 
 #[bridge_fn(node = false)]
@@ -178,7 +326,7 @@ fn ServerMessageAck_Send(ack: &ServerMessageAck) -> Result<(), SendError> {
     sender(StatusCode::OK)
 }
 
-#[bridge_fn(jni = false, ffi = false)]
+#[bridge_fn]
 fn ServerMessageAck_SendStatus(
     ack: &ServerMessageAck,
     status: AsType<HttpStatus, u16>,
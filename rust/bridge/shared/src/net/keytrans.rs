@@ -3,26 +3,65 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::sync::LazyLock;
 use std::time::SystemTime;
 
 use itertools::Itertools;
 use libsignal_bridge_macros::{bridge_fn, bridge_io};
+use libsignal_bridge_types::keytrans::{ImportedKeyTransState, KeyTransUsernameHash};
 use libsignal_bridge_types::net::chat::UnauthenticatedChatConnection;
 pub use libsignal_bridge_types::net::{Environment, TokioAsyncContext};
 use libsignal_bridge_types::support::AsType;
 use libsignal_core::{Aci, E164};
 use libsignal_keytrans::{
-    AccountData, KeyTransparency, LocalStateUpdate, StoredAccountData, StoredTreeHead,
+    AccountData, KeyRotations, KeyTransparency, PublicConfig, SignedKeyRotation, StoredAccountData,
+    StoredKeyRotations, StoredTreeHead,
 };
 use libsignal_net::keytrans::{
-    monitor_and_search, Error, Kt, KtApi as _, MaybePartial, SearchKey, SearchResult, UsernameHash,
+    monitor_and_search, BadArgumentsReason, Config, E164SearchKey, Error, Kt, KtApi as _,
+    MaybePartial, MonitorResult, SearchKey, SearchResult, UsernameHash,
 };
-use libsignal_protocol::PublicKey;
+use libsignal_protocol::{PublicKey, SignalProtocolError, Timestamp};
 use prost::{DecodeError, Message};
 
 use crate::support::*;
 use crate::*;
 
+/// The key transparency config for `environment`, cloned from one kept for the
+/// life of the process.
+///
+/// Clones share a [request limit](Config::with_max_concurrent_requests), so
+/// this is what bounds the requests in flight across every operation.
+fn config_for(environment: Environment) -> Config {
+    static STAGING: LazyLock<Config> =
+        LazyLock::new(|| Config::for_env(&Environment::Staging.env()));
+    static PROD: LazyLock<Config> = LazyLock::new(|| Config::for_env(&Environment::Prod.env()));
+    match environment {
+        Environment::Staging => STAGING.clone(),
+        Environment::Prod => PROD.clone(),
+    }
+}
+
+/// Decodes the key rotations persisted by the app, a serialized
+/// [`StoredKeyRotations`]. None have been applied if there are none stored.
+fn decode_key_rotations(key_rotations: Option<&[u8]>) -> Result<KeyRotations, Error> {
+    let Some(bytes) = key_rotations else {
+        return Ok(KeyRotations::default());
+    };
+    KeyRotations::try_from(StoredKeyRotations::decode(bytes)?)
+        .map_err(|_| BadArgumentsReason::InvalidKeyRotations.into())
+}
+
+/// The public config for `environment`, with the keys in `rotations`
+/// installed.
+fn public_config_for(environment: Environment, rotations: &KeyRotations) -> PublicConfig {
+    environment
+        .env()
+        .keytrans_config
+        .expect("keytrans config must be set")
+        .load(rotations)
+}
+
 #[bridge_fn(node = false, ffi = false)]
 fn KeyTransparency_AciSearchKey(aci: Aci) -> Vec<u8> {
     aci.as_search_key()
@@ -38,6 +77,29 @@ fn KeyTransparency_UsernameHashSearchKey(hash: &[u8]) -> Vec<u8> {
     UsernameHash::from_slice(hash).as_search_key()
 }
 
+bridge_handle_fns!(
+    KeyTransUsernameHash,
+    clone = false,
+    ffi = false,
+    node = false
+);
+
+#[bridge_fn(node = false, ffi = false)]
+fn KeyTransUsernameHash_New(hash: &[u8]) -> Result<KeyTransUsernameHash, Error> {
+    let hash = UsernameHash::try_from_slice(hash)?;
+    Ok(KeyTransUsernameHash(UsernameHash::new(hash.into_vec())))
+}
+
+bridge_handle_fns!(E164SearchKey, clone = false, ffi = false, node = false);
+
+#[bridge_fn(node = false, ffi = false)]
+fn E164SearchKey_New(
+    e164: E164,
+    unidentified_access_key: Option<&[u8]>,
+) -> Result<E164SearchKey, Error> {
+    E164SearchKey::new(e164, unidentified_access_key)
+}
+
 bridge_handle_fns!(SearchResult, clone = false, ffi = false, node = false);
 
 #[bridge_fn(node = false, ffi = false)]
@@ -70,6 +132,59 @@ fn SearchResult_GetAccountData(res: &SearchResult) -> Vec<u8> {
     res.account_data.encode_to_vec()
 }
 
+/// Export and import only fail on bad input, so they report it as an invalid
+/// argument, which every bridge can throw, rather than as a key transparency
+/// error, which only the Java bridge can.
+fn invalid_state(err: Error) -> SignalProtocolError {
+    SignalProtocolError::InvalidArgument(err.to_string())
+}
+
+fn export_state(
+    account_data: &[u8],
+    last_distinguished_tree_head: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let account_data = AccountData::try_from(StoredAccountData::decode(account_data)?)?;
+    let last_distinguished_tree_head =
+        StoredTreeHead::decode_last_tree_head(last_distinguished_tree_head)?
+            .ok_or(BadArgumentsReason::MissingDistinguishedTreeHead)?;
+    Ok(libsignal_keytrans::export_state(
+        account_data,
+        last_distinguished_tree_head,
+    ))
+}
+
+#[bridge_fn]
+fn KeyTransparency_ExportState(
+    account_data: &[u8],
+    last_distinguished_tree_head: &[u8],
+) -> Result<Vec<u8>, SignalProtocolError> {
+    export_state(account_data, last_distinguished_tree_head).map_err(invalid_state)
+}
+
+#[bridge_fn]
+fn KeyTransparency_ImportState(bytes: &[u8]) -> Result<ImportedKeyTransState, SignalProtocolError> {
+    let (account_data, last_distinguished_tree_head) =
+        libsignal_keytrans::import_state(bytes).map_err(|e| invalid_state(e.into()))?;
+    Ok(ImportedKeyTransState {
+        account_data: StoredAccountData::from(account_data).encode_to_vec(),
+        last_distinguished_tree_head: StoredTreeHead::encode_last_tree_head(
+            last_distinguished_tree_head,
+        ),
+    })
+}
+
+bridge_handle_fns!(ImportedKeyTransState, clone = false);
+
+#[bridge_fn]
+fn ImportedKeyTransState_GetAccountData(state: &ImportedKeyTransState) -> Vec<u8> {
+    state.account_data.clone()
+}
+
+#[bridge_fn]
+fn ImportedKeyTransState_GetLastDistinguishedTreeHead(state: &ImportedKeyTransState) -> Vec<u8> {
+    state.last_distinguished_tree_head.clone()
+}
+
 #[cfg(feature = "jni")]
 fn try_decode<B, T>(bytes: B) -> Result<T, DecodeError>
 where
@@ -87,27 +202,26 @@ async fn KeyTransparency_Search(
     chatConnection: &UnauthenticatedChatConnection,
     aci: Aci,
     aci_identity_key: &PublicKey,
-    e164: Option<E164>,
-    unidentified_access_key: Option<Box<[u8]>>,
-    username_hash: Option<Box<[u8]>>,
+    e164_search_key: Option<&E164SearchKey>,
+    username_hash: Option<&KeyTransUsernameHash>,
     account_data: Option<Box<[u8]>>,
     last_distinguished_tree_head: Box<[u8]>,
+    key_rotations: Option<Box<[u8]>>,
+    deadline: Timestamp,
 ) -> Result<SearchResult, Error> {
     let chat = chatConnection;
-    let username_hash = username_hash.map(UsernameHash::from);
-    let config = environment
-        .into_inner()
-        .env()
-        .keytrans_config
-        .expect("keytrans config must be set")
-        .into();
-    let kt = Kt {
-        inner: KeyTransparency { config },
+    let e164_search_key = e164_search_key.cloned();
+    let username_hash = username_hash.map(|KeyTransUsernameHash(hash)| hash.clone());
+    let environment = environment.into_inner();
+    let config = public_config_for(
+        environment,
+        &decode_key_rotations(key_rotations.as_deref())?,
+    );
+    let kt = Kt::new(
+        KeyTransparency { config },
         chat,
-        config: Default::default(),
-    };
-
-    let e164_pair = make_e164_pair(e164, unidentified_access_key)?;
+        config_for(environment).with_deadline(deadline.into()),
+    );
 
     let account_data = account_data
         .map(|bytes| {
@@ -116,9 +230,9 @@ async fn KeyTransparency_Search(
         })
         .transpose()?;
 
-    let last_distinguished_tree_head = try_decode(last_distinguished_tree_head)
-        .map(|stored: StoredTreeHead| stored.into_last_tree_head())?
-        .ok_or(Error::InvalidRequest("last distinguished tree is required"))?;
+    let last_distinguished_tree_head =
+        StoredTreeHead::decode_last_tree_head(&last_distinguished_tree_head)?
+            .ok_or(BadArgumentsReason::MissingDistinguishedTreeHead)?;
 
     let MaybePartial {
         inner: result,
@@ -127,7 +241,7 @@ async fn KeyTransparency_Search(
         .search(
             &aci,
             aci_identity_key,
-            e164_pair,
+            e164_search_key,
             username_hash,
             account_data,
             &last_distinguished_tree_head,
@@ -152,19 +266,21 @@ async fn KeyTransparency_Monitor(
     chatConnection: &UnauthenticatedChatConnection,
     aci: Aci,
     aci_identity_key: &PublicKey,
-    e164: Option<E164>,
-    unidentified_access_key: Option<Box<[u8]>>,
-    username_hash: Option<Box<[u8]>>,
+    e164_search_key: Option<&E164SearchKey>,
+    username_hash: Option<&KeyTransUsernameHash>,
     // Bridging this as optional even though it is required because it is
     // simpler to produce an error once here than on all platforms.
     account_data: Option<Box<[u8]>>,
     last_distinguished_tree_head: Box<[u8]>,
+    key_rotations: Option<Box<[u8]>>,
+    deadline: Timestamp,
 ) -> Result<Vec<u8>, Error> {
     let chat = chatConnection;
-    let username_hash = username_hash.map(UsernameHash::from);
+    let e164_search_key = e164_search_key.cloned();
+    let username_hash = username_hash.map(|KeyTransUsernameHash(hash)| hash.clone());
 
     let Some(account_data) = account_data else {
-        return Err(Error::InvalidRequest("account data not found in store"));
+        return Err(BadArgumentsReason::MissingAccountData.into());
     };
 
     let account_data = {
@@ -172,31 +288,36 @@ async fn KeyTransparency_Monitor(
         AccountData::try_from(stored).map_err(Error::from)?
     };
 
-    let last_distinguished_tree_head = try_decode(last_distinguished_tree_head)
-        .map(|stored: StoredTreeHead| stored.into_last_tree_head())?
-        .ok_or(Error::InvalidRequest("last distinguished tree is required"))?;
+    let last_distinguished_tree_head =
+        StoredTreeHead::decode_last_tree_head(&last_distinguished_tree_head)?
+            .ok_or(BadArgumentsReason::MissingDistinguishedTreeHead)?;
 
-    let config = environment
-        .into_inner()
-        .env()
-        .keytrans_config
-        .expect("keytrans config must be set")
-        .into();
-    let kt = Kt {
-        inner: KeyTransparency { config },
+    let environment = environment.into_inner();
+    let config = public_config_for(
+        environment,
+        &decode_key_rotations(key_rotations.as_deref())?,
+    );
+    let kt = Kt::new(
+        KeyTransparency { config },
         chat,
-        config: Default::default(),
-    };
+        config_for(environment).with_deadline(deadline.into()),
+    );
 
-    let e164_pair = make_e164_pair(e164, unidentified_access_key)?;
-    let MaybePartial {
-        inner: updated_account_data,
-        missing_fields,
+    let MonitorResult {
+        account_data:
+            MaybePartial {
+                inner: updated_account_data,
+                missing_fields,
+            },
+        skipped,
+        consistency,
+        proof_metrics,
+        dropped_legs,
     } = monitor_and_search(
         &kt,
         &aci,
         aci_identity_key,
-        e164_pair,
+        e164_search_key,
         username_hash,
         account_data,
         &last_distinguished_tree_head,
@@ -210,6 +331,22 @@ async fn KeyTransparency_Monitor(
         )));
     }
 
+    // Only the account data goes back to the app, so log the rest of the
+    // result where it can still be found.
+    if !dropped_legs.is_empty() {
+        log::info!(
+            "keytrans monitor: server stopped monitoring {}",
+            dropped_legs.iter().join(", ")
+        );
+    }
+    match (consistency, proof_metrics) {
+        (Some(consistency), Some(proof_metrics)) => {
+            log::debug!("keytrans monitor: verified; {consistency:?}, {proof_metrics:?}")
+        }
+        _ if skipped => log::debug!("keytrans monitor: skipped, stored data is recent"),
+        _ => log::debug!("keytrans monitor: response was already verified"),
+    }
+
     Ok(StoredAccountData::from(updated_account_data).encode_to_vec())
 }
 
@@ -219,47 +356,70 @@ async fn KeyTransparency_Distinguished(
     environment: AsType<Environment, u8>,
     chatConnection: &UnauthenticatedChatConnection,
     last_distinguished_tree_head: Option<Box<[u8]>>,
+    key_rotations: Option<Box<[u8]>>,
+    deadline: Timestamp,
 ) -> Result<Vec<u8>, Error> {
     let chat = chatConnection;
-    let config = environment
-        .into_inner()
-        .env()
-        .keytrans_config
-        .expect("keytrans config must be set")
-        .into();
-    let kt = Kt {
-        inner: KeyTransparency { config },
+    let environment = environment.into_inner();
+    let config = public_config_for(
+        environment,
+        &decode_key_rotations(key_rotations.as_deref())?,
+    );
+    let kt = Kt::new(
+        KeyTransparency { config },
         chat,
-        config: Default::default(),
-    };
+        config_for(environment).with_deadline(deadline.into()),
+    );
 
     let known_distinguished = last_distinguished_tree_head
-        .map(try_decode)
+        .map(|bytes| StoredTreeHead::decode_last_tree_head(&bytes))
         .transpose()?
-        .and_then(|stored: StoredTreeHead| stored.into_last_tree_head());
-    let LocalStateUpdate {
-        tree_head,
-        tree_root,
-        monitoring_data: _,
-    } = kt.distinguished(known_distinguished).await?;
-    let updated_distinguished = StoredTreeHead::from((tree_head, tree_root));
-    let serialized = updated_distinguished.encode_to_vec();
-    Ok(serialized)
+        .flatten();
+    let updated_distinguished = kt
+        .distinguished(known_distinguished)
+        .await?
+        .state_update
+        .into_stored();
+    Ok(updated_distinguished.encode_to_vec())
 }
 
-#[cfg(feature = "jni")]
-fn make_e164_pair(
-    e164: Option<E164>,
-    unidentified_access_key: Option<Box<[u8]>>,
-) -> Result<Option<(E164, Vec<u8>)>, Error> {
-    match (e164, unidentified_access_key) {
-        (None, None) => Ok(None),
-        (Some(e164), Some(uak)) => Ok(Some((e164, uak.into_vec()))),
-        (None, Some(_uak)) => Err(Error::InvalidRequest(
-            "Unidentified access key without an E164",
-        )),
-        (Some(_e164), None) => Err(Error::InvalidRequest(
-            "E164 without unidentified access key",
-        )),
-    }
+/// Fetches the log's latest key rotation and applies it on top of
+/// `key_rotations`.
+///
+/// Returns the updated rotations to persist, or `None` if nothing changed.
+#[bridge_io(TokioAsyncContext, node = false, ffi = false)]
+async fn KeyTransparency_UpdateKeyRotations(
+    // TODO: it is currently possible to pass an env that does not match chat
+    environment: AsType<Environment, u8>,
+    chatConnection: &UnauthenticatedChatConnection,
+    key_rotations: Option<Box<[u8]>>,
+    deadline: Timestamp,
+) -> Result<Option<Vec<u8>>, Error> {
+    let chat = chatConnection;
+    let environment = environment.into_inner();
+    let mut rotations = decode_key_rotations(key_rotations.as_deref())?;
+    let config = public_config_for(environment, &rotations);
+    let mut kt = Kt::new(
+        KeyTransparency { config },
+        chat,
+        config_for(environment).with_deadline(deadline.into()),
+    );
+
+    let rotated = kt.update_key_rotations(&mut rotations).await?;
+    Ok(rotated.map(|_| StoredKeyRotations::from(rotations).encode_to_vec()))
+}
+
+/// Applies a [`SignedKeyRotation`] received out-of-band on top of
+/// `key_rotations`, returning the updated rotations to persist.
+#[bridge_fn(node = false, ffi = false)]
+fn KeyTransparency_ApplyKeyRotation(
+    environment: AsType<Environment, u8>,
+    key_rotations: Option<&[u8]>,
+    document: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut rotations = decode_key_rotations(key_rotations)?;
+    let mut config = public_config_for(environment.into_inner(), &rotations);
+    let document = SignedKeyRotation::decode(document)?;
+    rotations.apply(&mut config, &document, SystemTime::now())?;
+    Ok(StoredKeyRotations::from(rotations).encode_to_vec())
 }
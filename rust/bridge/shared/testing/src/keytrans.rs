@@ -8,8 +8,12 @@ use std::time::SystemTime;
 use hex_literal::hex;
 use libsignal_bridge_macros::*;
 use libsignal_core::Aci;
-use libsignal_keytrans::{StoredAccountData, StoredMonitoringData, StoredTreeHead, TreeHead};
-use libsignal_net::keytrans::SearchResult;
+use libsignal_keytrans::{
+    ConsistencyCheck, StoredAccountData, StoredMonitoringData, StoredTreeHead, TreeHead,
+    TreeHeadConsistency,
+};
+use libsignal_net::infra::errors::RetryDelay;
+use libsignal_net::keytrans::{BadArgumentsReason, Error, SearchResult};
 use libsignal_protocol::IdentityKey;
 use uuid::Uuid;
 
@@ -38,6 +42,7 @@ fn TESTING_ChatSearchResult() -> SearchResult {
             pos: byte.into(),
             ptrs: Default::default(),
             owned: false,
+            search_key_hash: vec![],
         }
     }
     SearchResult {
@@ -52,5 +57,60 @@ fn TESTING_ChatSearchResult() -> SearchResult {
             username_hash: Some(make_monitoring_data(2)),
             last_tree_head,
         },
+        proof_metrics: Default::default(),
+        auditor_lag: None,
+        auditor_timestamp: None,
+        e164_binding_age: None,
+        username_binding_age: None,
+        username_change: None,
+        consistency: TreeHeadConsistency {
+            last: ConsistencyCheck::Proven,
+            distinguished: ConsistencyCheck::Proven,
+        },
+    }
+}
+
+#[cfg(feature = "jni")]
+#[derive(Copy, Clone, strum::EnumString)]
+enum TestingKeyTransError {
+    BadArguments,
+    DecodingFailed,
+    InvalidResponse,
+    ChatSendDisconnected,
+    RetryAfter42Seconds,
+    RateLimitedWithoutRetryAfter,
+}
+
+#[cfg(feature = "jni")]
+impl TryFrom<String> for TestingKeyTransError {
+    type Error = <Self as std::str::FromStr>::Err;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        std::str::FromStr::from_str(&value)
     }
 }
+
+/// Return an error matching the requested description.
+#[bridge_fn(node = false, ffi = false)]
+fn TESTING_KeyTransErrorConvert(
+    // The stringly-typed API makes the call sites more self-explanatory.
+    error_description: AsType<TestingKeyTransError, String>,
+) -> Result<(), Error> {
+    Err(match error_description.into_inner() {
+        TestingKeyTransError::BadArguments => {
+            Error::BadArguments(BadArgumentsReason::MissingAccountData)
+        }
+        TestingKeyTransError::DecodingFailed => {
+            Error::DecodingFailed(prost::DecodeError::new("fake reason"))
+        }
+        TestingKeyTransError::InvalidResponse => Error::InvalidResponse("fake reason".into()),
+        TestingKeyTransError::ChatSendDisconnected => {
+            Error::ChatSendError(libsignal_net::chat::SendError::Disconnected)
+        }
+        TestingKeyTransError::RetryAfter42Seconds => Error::RateLimited {
+            retry_after: Some(RetryDelay::from_secs(42)),
+        },
+        TestingKeyTransError::RateLimitedWithoutRetryAfter => {
+            Error::RateLimited { retry_after: None }
+        }
+    })
+}
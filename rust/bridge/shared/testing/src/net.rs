@@ -12,6 +12,7 @@ use libsignal_core::E164;
 use libsignal_net::cdsi::{CdsiProtocolError, LookupError, LookupResponse, LookupResponseEntry};
 use libsignal_net::infra::errors::RetryLater;
 use libsignal_net::infra::ws2::attested::AttestedProtocolError;
+use libsignal_net::ws::RateLimitChallenge;
 use libsignal_protocol::{Aci, Pni};
 use nonzero_ext::nonzero;
 use uuid::Uuid;
@@ -93,6 +94,7 @@ make_error_testing_enum! {
         AttestationError => AttestationDataError,
         InvalidResponse => InvalidResponse,
         RateLimited => RetryAfter42Seconds,
+        RateLimitChallenge => RateLimitChallenge,
         InvalidToken => InvalidToken,
         InvalidArgument => InvalidArgument,
         ParseError => Parse,
@@ -125,6 +127,12 @@ fn TESTING_CdsiLookupErrorConvert(
         TestingCdsiLookupError::RetryAfter42Seconds => LookupError::RateLimited(RetryLater {
             retry_after_seconds: 42,
         }),
+        TestingCdsiLookupError::RateLimitChallenge => {
+            LookupError::RateLimitChallenge(RateLimitChallenge {
+                token: "fake token".to_owned(),
+                options: vec!["recaptcha".to_owned()],
+            })
+        }
         TestingCdsiLookupError::InvalidToken => LookupError::InvalidToken,
         TestingCdsiLookupError::InvalidArgument => LookupError::InvalidArgument {
             server_reason: "fake reason".into(),
@@ -8,8 +8,11 @@ use libsignal_bridge_macros::*;
 use libsignal_bridge_types::net::chat::{AuthenticatedChatConnection, ChatListener, HttpRequest};
 use libsignal_bridge_types::net::TokioAsyncContext;
 use libsignal_net::chat::fake::FakeChatRemote;
-use libsignal_net::chat::{ConnectError, RequestProto, Response as ChatResponse, SendError};
-use libsignal_net::infra::errors::RetryLater;
+use libsignal_net::chat::{
+    ConnectError, FailurePhase, RequestProto, Response as ChatResponse, SendError,
+};
+use libsignal_net::infra::errors::{ProxyFailureKind, RetryLater};
+use libsignal_net::ws::RateLimitChallenge;
 
 use crate::net::make_error_testing_enum;
 use crate::*;
@@ -140,7 +143,7 @@ fn TESTING_FakeChatSentRequest_RequestId(request: &FakeChatSentRequest) -> u64 {
 #[bridge_fn]
 fn TESTING_ChatResponseConvert(body_present: bool) -> ChatResponse {
     let body = match body_present {
-        true => Some(b"content".to_vec().into_boxed_slice()),
+        true => Some(b"content".as_slice().into()),
         false => None,
     };
     let mut headers = HeaderMap::new();
@@ -148,6 +151,8 @@ fn TESTING_ChatResponseConvert(body_present: bool) -> ChatResponse {
         http::header::CONTENT_TYPE,
         HeaderValue::from_static("application/octet-stream"),
     );
+    headers.append(http::header::RETRY_AFTER, HeaderValue::from_static("30"));
+    // Not allowlisted, so it should never reach the app.
     headers.append(http::header::FORWARDED, HeaderValue::from_static("1.1.1.1"));
     ChatResponse {
         status: StatusCode::OK,
@@ -197,7 +202,9 @@ make_error_testing_enum! {
         Timeout => Timeout,
         AllAttemptsFailed => AllAttemptsFailed,
         InvalidConnectionConfiguration => InvalidConnectionConfiguration,
+        ProxyFailure => ProxyAuthFailed,
         RetryLater => RetryAfter42Seconds,
+        RateLimitChallenge => RateLimitChallenge,
     }
 }
 
@@ -212,16 +219,35 @@ fn TESTING_ChatConnectErrorConvert(
                 libsignal_net::infra::errors::TransportConnectError::TcpConnectionFailed,
             ))
         }
-        TestingChatConnectError::AppExpired => ConnectError::AppExpired,
+        TestingChatConnectError::AppExpired => ConnectError::AppExpired {
+            minimum_version: Some("7.40.0".to_owned()),
+            upgrade_url: Some("https://signal.org/install".to_owned()),
+        },
         TestingChatConnectError::DeviceDeregistered => ConnectError::DeviceDeregistered,
-        TestingChatConnectError::Timeout => ConnectError::Timeout,
-        TestingChatConnectError::AllAttemptsFailed => ConnectError::AllAttemptsFailed,
+        TestingChatConnectError::Timeout => ConnectError::Timeout {
+            furthest_phase: FailurePhase::Transport,
+        },
+        TestingChatConnectError::AllAttemptsFailed => ConnectError::AllAttemptsFailed {
+            furthest_phase: FailurePhase::Transport,
+        },
         TestingChatConnectError::InvalidConnectionConfiguration => {
             ConnectError::InvalidConnectionConfiguration
         }
-        TestingChatConnectError::RetryAfter42Seconds => ConnectError::RetryLater(RetryLater {
-            retry_after_seconds: 42,
-        }),
+        TestingChatConnectError::ProxyAuthFailed => {
+            ConnectError::ProxyFailure(ProxyFailureKind::AuthFailed)
+        }
+        TestingChatConnectError::RetryAfter42Seconds => ConnectError::RetryLater {
+            retry_later: RetryLater {
+                retry_after_seconds: 42,
+            },
+            received_at: tokio::time::Instant::now(),
+        },
+        TestingChatConnectError::RateLimitChallenge => {
+            ConnectError::RateLimitChallenge(RateLimitChallenge {
+                token: "fake token".to_owned(),
+                options: vec!["recaptcha".to_owned()],
+            })
+        }
     })
 }
 
@@ -229,9 +255,13 @@ make_error_testing_enum! {
     enum TestingChatSendError for SendError {
         RequestTimedOut => RequestTimedOut,
         Disconnected => Disconnected,
+        ConnectionIdleTimeout => ConnectionIdleTimeout,
+        ConnectionLost => ConnectionLost,
         WebSocket => WebSocketConnectionReset,
         IncomingDataInvalid => IncomingDataInvalid,
         RequestHasInvalidHeader => RequestHasInvalidHeader,
+        ;
+        ConnectionLostWithoutCloseCode,
     }
 }
 
@@ -241,8 +271,17 @@ fn TESTING_ChatSendErrorConvert(
     error_description: AsType<TestingChatSendError, String>,
 ) -> Result<(), SendError> {
     Err(match error_description.into_inner() {
-        TestingChatSendError::RequestTimedOut => SendError::RequestTimedOut,
+        TestingChatSendError::RequestTimedOut => SendError::RequestTimedOut {
+            correlation_id: None,
+        },
         TestingChatSendError::Disconnected => SendError::Disconnected,
+        TestingChatSendError::ConnectionIdleTimeout => SendError::ConnectionIdleTimeout,
+        TestingChatSendError::ConnectionLost => SendError::ConnectionLost {
+            close_code: Some(4001),
+        },
+        TestingChatSendError::ConnectionLostWithoutCloseCode => {
+            SendError::ConnectionLost { close_code: None }
+        }
         TestingChatSendError::WebSocketConnectionReset => {
             SendError::WebSocket(libsignal_net::infra::ws::WebSocketServiceError::Io(
                 std::io::ErrorKind::ConnectionReset.into(),
@@ -3,13 +3,16 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use std::ffi::{c_uchar, c_void};
+use std::ffi::{c_char, c_uchar, c_void};
 use std::panic::UnwindSafe;
 
 use libsignal_net::chat::server_requests::DisconnectCause;
+use libsignal_net::connect_state::ConnectStage;
 
 use super::*;
-use crate::net::chat::{ChatListener, ServerMessageAck};
+use crate::net::chat::{
+    bridged_connect_stage, ChatConnectListener, ChatListener, ServerMessageAck,
+};
 
 type ReceivedIncomingMessage = extern "C" fn(
     ctx: *mut c_void,
@@ -19,6 +22,12 @@ type ReceivedIncomingMessage = extern "C" fn(
 );
 type ReceivedQueueEmpty = extern "C" fn(ctx: *mut c_void);
 type ReceivedAlerts = extern "C" fn(ctx: *mut c_void, alerts: StringArray);
+type ReceivedUnrecognizedRequest = extern "C" fn(
+    ctx: *mut c_void,
+    path: *const c_char,
+    body: OwnedBufferOf<c_uchar>,
+    ack: *mut ServerMessageAck,
+);
 type ConnectionInterrupted = extern "C" fn(ctx: *mut c_void, error: *mut SignalFfiError);
 type DestroyChatListener = extern "C" fn(ctx: *mut c_void);
 
@@ -41,6 +50,7 @@ pub struct FfiChatListenerStruct {
     received_incoming_message: ReceivedIncomingMessage,
     received_queue_empty: ReceivedQueueEmpty,
     received_alerts: ReceivedAlerts,
+    received_unrecognized_request: ReceivedUnrecognizedRequest,
     connection_interrupted: ConnectionInterrupted,
     destroy: DestroyChatListener,
 }
@@ -62,6 +72,7 @@ impl FfiChatListenerStruct {
             received_incoming_message,
             received_queue_empty,
             received_alerts,
+            received_unrecognized_request,
             connection_interrupted,
             destroy,
         } = *self;
@@ -70,6 +81,7 @@ impl FfiChatListenerStruct {
             received_incoming_message,
             received_queue_empty,
             received_alerts,
+            received_unrecognized_request,
             connection_interrupted,
             destroy,
         }))
@@ -121,6 +133,32 @@ impl ChatListener for ChatListenerStruct {
         )
     }
 
+    fn received_unrecognized_request(
+        &mut self,
+        path: String,
+        body: Option<Vec<u8>>,
+        ack: ServerMessageAck,
+    ) {
+        let path = match path.convert_into() {
+            Ok(path) => path,
+            Err(e) => {
+                log::warn!("rejecting server request with unbridgeable path: {e}");
+                ack.send_status(http::StatusCode::NOT_FOUND);
+                return;
+            }
+        };
+        (self.0.received_unrecognized_request)(
+            self.0.ctx,
+            path,
+            body.unwrap_or_default()
+                .convert_into()
+                .expect("Vec<u8> conversion is infallible"),
+            ack.convert_into()
+                .expect("bridge_as_handle conversion is infallible")
+                .into_inner(),
+        )
+    }
+
     fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
         let error = match disconnect_cause {
             DisconnectCause::LocalDisconnect => None,
@@ -132,3 +170,65 @@ impl ChatListener for ChatListenerStruct {
         )
     }
 }
+
+type ReportConnectProgress = extern "C" fn(ctx: *mut c_void, stage: u8, at_millis: u64);
+type DestroyChatConnectListener = extern "C" fn(ctx: *mut c_void);
+
+/// Callbacks for [`ChatConnectListener`].
+///
+/// Stages are numbered as in [`bridged_connect_stage`].
+///
+/// # Safety
+///
+/// This type contains raw pointers. Code that constructs an instance of this type must ensure
+/// memory safety assuming that
+/// - the `connect_progress` function pointer field is called with `ctx` as an argument;
+/// - the `destroy` function pointer field is called with `ctx` as an argument;
+/// - no function pointer fields are called after `destroy` is called.
+#[repr(C)]
+pub struct FfiChatConnectListenerStruct {
+    ctx: *mut c_void,
+    connect_progress: ReportConnectProgress,
+    destroy: DestroyChatConnectListener,
+}
+
+impl FfiChatConnectListenerStruct {
+    /// Turns `self` into a type-erased [`ChatConnectListener`].
+    ///
+    /// Takes ownership of the memory behind [`FfiChatConnectListenerStruct::ctx`].
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that this method is called at most once on an
+    /// `FfiChatConnectListenerStruct`.
+    pub(crate) unsafe fn make_listener(&self) -> Box<dyn ChatConnectListener> {
+        let FfiChatConnectListenerStruct {
+            ctx,
+            connect_progress,
+            destroy,
+        } = *self;
+        Box::new(ChatConnectListenerStruct(FfiChatConnectListenerStruct {
+            ctx,
+            connect_progress,
+            destroy,
+        }))
+    }
+}
+
+// SAFETY: Progress is reported from whichever thread the connection attempt runs on. It's up to
+// the creator of the C struct to make sure `ctx` is appropriate for this.
+unsafe impl Send for FfiChatConnectListenerStruct {}
+
+struct ChatConnectListenerStruct(FfiChatConnectListenerStruct);
+
+impl Drop for ChatConnectListenerStruct {
+    fn drop(&mut self) {
+        (self.0.destroy)(self.0.ctx);
+    }
+}
+
+impl ChatConnectListener for ChatConnectListenerStruct {
+    fn connect_progress(&mut self, stage: ConnectStage, at: Timestamp) {
+        (self.0.connect_progress)(self.0.ctx, bridged_connect_stage(stage), at.epoch_millis())
+    }
+}
@@ -15,7 +15,7 @@ use uuid::Uuid;
 
 use super::*;
 use crate::io::{InputStream, SyncInputStream};
-use crate::net::chat::ChatListener;
+use crate::net::chat::{bridged_response_headers, ChatConnectListener, ChatListener};
 use crate::support::{extend_lifetime, AsType, FixedLengthBincodeSerializable, Serialized};
 
 /// Converts arguments from their FFI form to their Rust form.
@@ -433,6 +433,24 @@ impl<'a> ArgTypeInfo<'a> for Option<Box<dyn ChatListener>> {
     }
 }
 
+impl<'a> ArgTypeInfo<'a> for Box<dyn ChatConnectListener> {
+    type ArgType = crate::ffi::ConstPointer<FfiChatConnectListenerStruct>;
+    type StoredType = Option<Box<dyn ChatConnectListener>>;
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn borrow(foreign: Self::ArgType) -> SignalFfiResult<Self::StoredType> {
+        Ok(Some(unsafe {
+            foreign
+                .into_inner()
+                .as_ref()
+                .ok_or(NullPointerError)?
+                .make_listener()
+        }))
+    }
+    fn load_from(stored: &'a mut Self::StoredType) -> Self {
+        stored.take().expect("not previously taken")
+    }
+}
+
 impl<T: ResultTypeInfo, E> ResultTypeInfo for Result<T, E>
 where
     E: FfiError,
@@ -721,24 +739,20 @@ impl ResultTypeInfo for libsignal_net::chat::Response {
             headers,
         } = self;
 
-        let header_strings: Vec<*const c_char> = headers
-            .iter()
-            .map(|(k, v)| {
-                // We only support string values for now (see chat_websocket.proto).
-                format!(
-                    "{}:{}",
-                    k,
-                    v.to_str().expect("Chat never produces non-string headers")
-                )
-                .convert_into()
-            })
+        let header_strings: Vec<*const c_char> = bridged_response_headers(&headers)
+            .into_iter()
+            .map(|(name, value)| format!("{name}:{value}").convert_into())
             .collect::<SignalFfiResult<_>>()?;
 
         Ok(FfiChatResponse {
             status: status.as_u16(),
             message: message.unwrap_or_default().convert_into()?,
             headers: OwnedBufferOf::from(header_strings.into_boxed_slice()),
-            body: body.unwrap_or_default().convert_into()?,
+            body: body
+                .map(Vec::from)
+                .unwrap_or_default()
+                .into_boxed_slice()
+                .convert_into()?,
         })
     }
 }
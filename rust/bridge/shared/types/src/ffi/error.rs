@@ -5,12 +5,14 @@
 
 use std::fmt;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::time::SystemTime;
 
 use attest::enclave::Error as EnclaveError;
 use attest::hsm_enclave::Error as HsmEnclaveError;
 use device_transfer::Error as DeviceTransferError;
 use libsignal_account_keys::Error as PinError;
 use libsignal_net::infra::errors::RetryLater;
+use libsignal_net::ws::RateLimitChallenge;
 use libsignal_protocol::*;
 use signal_crypto::Error as SignalCryptoError;
 use usernames::{UsernameError, UsernameLinkError};
@@ -93,6 +95,8 @@ pub enum SignalErrorCode {
     ConnectionFailed = 148,
     ChatServiceInactive = 149,
     RequestTimedOut = 150,
+    RateLimitChallenge = 151,
+    ConnectionLost = 152,
 
     SvrDataMissing = 160,
     SvrRestoreFailed = 161,
@@ -100,6 +104,7 @@ pub enum SignalErrorCode {
 
     AppExpired = 170,
     DeviceDeregistered = 171,
+    ProxyFailure = 172,
 
     BackupValidation = 180,
 }
@@ -129,12 +134,27 @@ pub trait FfiError: UpcastAsAny + fmt::Debug + Send + 'static {
     fn provide_retry_after_seconds(&self) -> Result<u32, WrongErrorKind> {
         Err(WrongErrorKind)
     }
+    fn provide_retry_at(&self) -> Result<SystemTime, WrongErrorKind> {
+        Err(WrongErrorKind)
+    }
     fn provide_tries_remaining(&self) -> Result<u32, WrongErrorKind> {
         Err(WrongErrorKind)
     }
     fn provide_unknown_fields(&self) -> Result<Vec<String>, WrongErrorKind> {
         Err(WrongErrorKind)
     }
+    fn provide_rate_limit_challenge(&self) -> Result<RateLimitChallenge, WrongErrorKind> {
+        Err(WrongErrorKind)
+    }
+    fn provide_app_expired_minimum_version(&self) -> Result<Option<String>, WrongErrorKind> {
+        Err(WrongErrorKind)
+    }
+    fn provide_app_expired_upgrade_url(&self) -> Result<Option<String>, WrongErrorKind> {
+        Err(WrongErrorKind)
+    }
+    fn provide_close_code(&self) -> Result<Option<u16>, WrongErrorKind> {
+        Err(WrongErrorKind)
+    }
 }
 
 /// The top-level error type (opaquely) returned to C clients when something goes wrong.
@@ -445,6 +465,7 @@ impl FfiError for libsignal_net::cdsi::LookupError {
             Self::RateLimited(RetryLater {
                 retry_after_seconds,
             }) => format!("Rate limited; try again after {retry_after_seconds}s"),
+            Self::RateLimitChallenge(challenge) => format!("Rate limited; {challenge}"),
             Self::InvalidToken => "CDSI request token was invalid".to_owned(),
             Self::ConnectTransport(e) => format!("IO error: {e}"),
             Self::WebSocket(e) => format!("WebSocket error: {e}"),
@@ -462,6 +483,7 @@ impl FfiError for libsignal_net::cdsi::LookupError {
             | Self::Server { .. } => SignalErrorCode::NetworkProtocol,
             Self::AttestationError(e) => e.code(),
             Self::RateLimited { .. } => SignalErrorCode::RateLimited,
+            Self::RateLimitChallenge(_) => SignalErrorCode::RateLimitChallenge,
             Self::InvalidToken => SignalErrorCode::CdsiInvalidToken,
             Self::ConnectTransport(_) => SignalErrorCode::IoError,
             Self::WebSocket(_) => SignalErrorCode::WebSocket,
@@ -478,21 +500,33 @@ impl FfiError for libsignal_net::cdsi::LookupError {
             _ => Err(WrongErrorKind),
         }
     }
+
+    fn provide_rate_limit_challenge(&self) -> Result<RateLimitChallenge, WrongErrorKind> {
+        match self {
+            Self::RateLimitChallenge(challenge) => Ok(challenge.clone()),
+            _ => Err(WrongErrorKind),
+        }
+    }
 }
 
 impl FfiError for libsignal_net::chat::ConnectError {
     fn describe(&self) -> String {
         match self {
             Self::WebSocket(e) => format!("WebSocket error: {e}"),
-            Self::AllAttemptsFailed | Self::InvalidConnectionConfiguration => {
+            Self::AllAttemptsFailed { .. } | Self::InvalidConnectionConfiguration => {
                 "Connection failed".to_owned()
             }
-            Self::Timeout => "Connect timed out".to_owned(),
-            Self::AppExpired => "App expired".to_owned(),
+            Self::Timeout { .. } => "Connect timed out".to_owned(),
+            Self::ProxyFailure(kind) => format!("Proxy connection failed: {kind}"),
+            Self::AppExpired { .. } => "App expired".to_owned(),
             Self::DeviceDeregistered => "Device deregistered or delinked".to_owned(),
-            Self::RetryLater(RetryLater {
-                retry_after_seconds,
-            }) => format!("Rate limited; try again after {retry_after_seconds}s"),
+            Self::RetryLater {
+                retry_later: RetryLater {
+                    retry_after_seconds,
+                },
+                received_at: _,
+            } => format!("Rate limited; try again after {retry_after_seconds}s"),
+            Self::RateLimitChallenge(challenge) => format!("Rate limited; {challenge}"),
         }
     }
 
@@ -502,17 +536,45 @@ impl FfiError for libsignal_net::chat::ConnectError {
             Self::AllAttemptsFailed { .. } | Self::InvalidConnectionConfiguration => {
                 SignalErrorCode::ConnectionFailed
             }
-            Self::Timeout => SignalErrorCode::ConnectionTimedOut,
-            Self::AppExpired => SignalErrorCode::AppExpired,
+            Self::Timeout { .. } => SignalErrorCode::ConnectionTimedOut,
+            Self::ProxyFailure(_) => SignalErrorCode::ProxyFailure,
+            Self::AppExpired { .. } => SignalErrorCode::AppExpired,
             Self::DeviceDeregistered => SignalErrorCode::DeviceDeregistered,
             Self::RetryLater { .. } => SignalErrorCode::RateLimited,
+            Self::RateLimitChallenge(_) => SignalErrorCode::RateLimitChallenge,
         }
     }
     fn provide_retry_after_seconds(&self) -> Result<u32, WrongErrorKind> {
         match self {
-            Self::RetryLater(RetryLater {
-                retry_after_seconds,
-            }) => Ok(*retry_after_seconds),
+            Self::RetryLater {
+                retry_later: RetryLater {
+                    retry_after_seconds,
+                },
+                received_at: _,
+            } => Ok(*retry_after_seconds),
+            _ => Err(WrongErrorKind),
+        }
+    }
+    fn provide_retry_at(&self) -> Result<SystemTime, WrongErrorKind> {
+        self.retry_at_system_time().ok_or(WrongErrorKind)
+    }
+    fn provide_rate_limit_challenge(&self) -> Result<RateLimitChallenge, WrongErrorKind> {
+        match self {
+            Self::RateLimitChallenge(challenge) => Ok(challenge.clone()),
+            _ => Err(WrongErrorKind),
+        }
+    }
+    fn provide_app_expired_minimum_version(&self) -> Result<Option<String>, WrongErrorKind> {
+        match self {
+            Self::AppExpired {
+                minimum_version, ..
+            } => Ok(minimum_version.clone()),
+            _ => Err(WrongErrorKind),
+        }
+    }
+    fn provide_app_expired_upgrade_url(&self) -> Result<Option<String>, WrongErrorKind> {
+        match self {
+            Self::AppExpired { upgrade_url, .. } => Ok(upgrade_url.clone()),
             _ => Err(WrongErrorKind),
         }
     }
@@ -526,8 +588,16 @@ impl FfiError for libsignal_net::chat::SendError {
             Self::RequestHasInvalidHeader => {
                 format!("internal error: {self}")
             }
-            Self::RequestTimedOut => "Request timed out".to_string(),
+            Self::RequestTimedOut {
+                correlation_id: None,
+            } => "Request timed out".to_string(),
+            Self::RequestTimedOut {
+                correlation_id: Some(id),
+            } => format!("Request timed out (correlation id: {id})"),
             Self::Disconnected => "Chat service disconnected".to_owned(),
+            Self::ConnectionIdleTimeout | Self::ConnectionLost { .. } => {
+                format!("Chat service disconnected: {self}")
+            }
         }
     }
 
@@ -536,13 +606,23 @@ impl FfiError for libsignal_net::chat::SendError {
             Self::WebSocket(_) => SignalErrorCode::WebSocket,
             Self::IncomingDataInvalid => SignalErrorCode::NetworkProtocol,
             Self::RequestHasInvalidHeader => SignalErrorCode::InternalError,
-            Self::RequestTimedOut => SignalErrorCode::RequestTimedOut,
-            Self::Disconnected => SignalErrorCode::ChatServiceInactive,
+            Self::RequestTimedOut { .. } => SignalErrorCode::RequestTimedOut,
+            // Idle timeouts are routine; the app only needs to reconnect.
+            Self::Disconnected | Self::ConnectionIdleTimeout => {
+                SignalErrorCode::ChatServiceInactive
+            }
+            Self::ConnectionLost { .. } => SignalErrorCode::ConnectionLost,
         }
     }
     fn provide_retry_after_seconds(&self) -> Result<u32, WrongErrorKind> {
         Err(WrongErrorKind)
     }
+    fn provide_close_code(&self) -> Result<Option<u16>, WrongErrorKind> {
+        match self {
+            Self::ConnectionLost { close_code } => Ok(*close_code),
+            _ => Err(WrongErrorKind),
+        }
+    }
 }
 
 impl FfiError for http::uri::InvalidUri {
@@ -4,9 +4,12 @@
 //
 
 use libsignal_net::chat::server_requests::DisconnectCause;
+use libsignal_net::connect_state::ConnectStage;
 
 use super::*;
-use crate::net::chat::{ChatListener, ServerMessageAck};
+use crate::net::chat::{
+    bridged_connect_stage, ChatConnectListener, ChatListener, ServerMessageAck,
+};
 
 pub type JavaBridgeChatListener<'a> = JObject<'a>;
 
@@ -99,6 +102,30 @@ impl ChatListener for JniChatListener {
         });
     }
 
+    fn received_unrecognized_request(
+        &mut self,
+        path: String,
+        body: Option<Vec<u8>>,
+        ack: ServerMessageAck,
+    ) {
+        let listener = &self.listener;
+        self.attach_and_log_on_error("unrecognized request", move |env| {
+            let path = path.convert_into(env)?;
+            let body = body.unwrap_or_default().convert_into(env)?;
+            let ack_handle = ack.convert_into(env)?;
+            call_method_checked(
+                env,
+                listener,
+                "onUnrecognizedRequest",
+                jni_args!((
+                    path => java.lang.String,
+                    body => [byte],
+                    ack_handle => long,
+                ) -> void),
+            )
+        });
+    }
+
     fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
         let listener = &self.listener;
         self.attach_and_log_on_error("connection interrupted", move |env| {
@@ -133,3 +160,43 @@ impl ChatListener for JniChatListener {
         });
     }
 }
+
+pub type JavaBridgeChatConnectListener<'a> = JObject<'a>;
+
+pub struct JniBridgeChatConnectListener(JniChatListener);
+
+impl JniBridgeChatConnectListener {
+    pub fn new(env: &mut JNIEnv<'_>, listener: &JObject) -> Result<Self, BridgeLayerError> {
+        check_jobject_type(
+            env,
+            listener,
+            ClassName("org.signal.libsignal.net.internal.BridgeChatConnectListener"),
+        )?;
+        Ok(Self(JniChatListener {
+            vm: env.get_java_vm().expect("can get VM"),
+            listener: env.new_global_ref(listener).expect("can get env"),
+        }))
+    }
+
+    pub(crate) fn into_listener(self) -> Box<dyn ChatConnectListener> {
+        let Self(listener) = self;
+        Box::new(listener)
+    }
+}
+
+impl ChatConnectListener for JniChatListener {
+    fn connect_progress(&mut self, stage: ConnectStage, at: Timestamp) {
+        let listener = &self.listener;
+        self.attach_and_log_on_error("connect progress", move |env| {
+            call_method_checked(
+                env,
+                listener,
+                "onConnectProgress",
+                jni_args!((
+                    bridged_connect_stage(stage).into() => int,
+                    at.epoch_millis() as i64 => long,
+                ) -> void),
+            )
+        });
+    }
+}
@@ -17,7 +17,7 @@ use paste::paste;
 use super::*;
 use crate::io::{InputStream, SyncInputStream};
 use crate::message_backup::MessageBackupValidationOutcome;
-use crate::net::chat::ChatListener;
+use crate::net::chat::{bridged_response_headers, ChatConnectListener, ChatListener};
 use crate::support::{Array, AsType, FixedLengthBincodeSerializable, Serialized};
 
 /// Converts arguments from their JNI form to their Rust form.
@@ -560,6 +560,27 @@ impl<'storage, 'param: 'storage, 'context: 'param> ArgTypeInfo<'storage, 'param,
     }
 }
 
+impl<'storage, 'param: 'storage, 'context: 'param> ArgTypeInfo<'storage, 'param, 'context>
+    for Box<dyn ChatConnectListener>
+{
+    type ArgType = JObject<'context>;
+    type StoredType = Option<JniBridgeChatConnectListener>;
+    fn borrow(
+        env: &mut JNIEnv<'context>,
+        store: &'param Self::ArgType,
+    ) -> Result<Self::StoredType, BridgeLayerError> {
+        if store.is_null() {
+            return Err(BridgeLayerError::NullPointer(Some(
+                "BridgeChatConnectListener",
+            )));
+        }
+        Ok(Some(JniBridgeChatConnectListener::new(env, store)?))
+    }
+    fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+        stored.take().expect("not previously taken").into_listener()
+    }
+}
+
 /// A translation from a Java interface where the implementing class wraps the Rust handle.
 impl<'a> SimpleArgTypeInfo<'a> for CiphertextMessageRef<'a> {
     type ArgType = JavaCiphertextMessage<'a>;
@@ -1230,12 +1251,12 @@ impl<'a> ResultTypeInfo<'a> for libsignal_net::chat::Response {
         let headers_map = new_instance(env, ClassName("java.util.HashMap"), jni_args!(() -> void))?;
         let headers_jmap =
             JMap::from_env(env, &headers_map).check_exceptions(env, "Response::convert_into")?;
-        for (name, value) in headers.iter() {
+        for (name, value) in bridged_response_headers(&headers) {
             let name_str = env
-                .new_string(name.as_str())
+                .new_string(name)
                 .check_exceptions(env, "Response::convert_into")?;
             let value_str = env
-                .new_string(value.to_str().expect("valid header value"))
+                .new_string(value)
                 .check_exceptions(env, "Response::convert_into")?;
             headers_jmap
                 .put(env, &name_str, &value_str)
@@ -1443,6 +1464,9 @@ macro_rules! jni_arg_type {
     (Box<dyn ChatListener >) =>{
         jni::JavaBridgeChatListener<'local>
     };
+    (Box<dyn ChatConnectListener >) =>{
+        jni::JavaBridgeChatConnectListener<'local>
+    };
     (&mut [u8]) => {
         ::jni::objects::JByteArray<'local>
     };
@@ -13,6 +13,7 @@ use jni::{JNIEnv, JavaVM};
 use libsignal_account_keys::Error as PinError;
 use libsignal_net::cdsi::CdsiProtocolError;
 use libsignal_net::infra::ws::WebSocketServiceError;
+use libsignal_net::keytrans::Error as KeyTransNetError;
 use libsignal_protocol::*;
 use signal_crypto::Error as SignalCryptoError;
 use usernames::{UsernameError, UsernameLinkError};
@@ -54,7 +55,7 @@ pub enum SignalJniError {
         exception_class: ClassName<'static>,
     },
     #[from(skip)]
-    KeyTransparency(libsignal_net::keytrans::Error),
+    KeyTransparency(crate::keytrans::KeyTransError),
 }
 
 /// Subset of errors that can happen in the bridge layer.
@@ -69,9 +70,14 @@ pub enum BridgeLayerError {
     UnexpectedJniResultType(&'static str, &'static str),
     NullPointer(Option<&'static str>),
     IntegerOverflow(String),
-    IncorrectArrayLength { expected: usize, actual: usize },
+    IncorrectArrayLength {
+        expected: usize,
+        actual: usize,
+    },
     CallbackException(&'static str, ThrownException),
     UnexpectedPanic(std::boxed::Box<dyn std::any::Any + std::marker::Send>),
+    /// The Java future for an async operation was cancelled before it completed.
+    Cancelled,
 }
 
 impl fmt::Display for SignalJniError {
@@ -138,6 +144,7 @@ impl fmt::Display for BridgeLayerError {
             Self::UnexpectedPanic(e) => {
                 write!(f, "unexpected panic: {}", describe_panic(e))
             }
+            Self::Cancelled => write!(f, "operation was cancelled"),
         }
     }
 }
@@ -183,6 +190,7 @@ impl From<libsignal_net::cdsi::LookupError> for SignalJniError {
                 CdsiError::NoTokenInResponse
             }
             LookupError::RateLimited(retry_later) => CdsiError::RateLimited(retry_later),
+            LookupError::RateLimitChallenge(challenge) => CdsiError::RateLimitChallenge(challenge),
             LookupError::ParseError => CdsiError::ParseError,
             LookupError::InvalidToken => CdsiError::InvalidToken,
             LookupError::Server { reason } => CdsiError::Server { reason },
@@ -192,14 +200,9 @@ impl From<libsignal_net::cdsi::LookupError> for SignalJniError {
 
 impl From<KeyTransNetError> for SignalJniError {
     fn from(err: KeyTransNetError) -> Self {
-        match err {
-            KeyTransNetError::ChatSendError(e) => SignalJniError::ChatSend(e),
-            KeyTransNetError::RequestFailed(_)
-            | KeyTransNetError::VerificationFailed(_)
-            | KeyTransNetError::InvalidResponse(_)
-            | KeyTransNetError::InvalidRequest(_)
-            | KeyTransNetError::DecodingFailed(_) => SignalJniError::KeyTransparency(err),
-        }
+        // Sorted by what the app can do about it, so that chat connection
+        // details aren't passed on.
+        SignalJniError::KeyTransparency(err.into())
     }
 }
 
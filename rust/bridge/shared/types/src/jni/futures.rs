@@ -8,7 +8,7 @@ use std::future::Future;
 use futures_util::{FutureExt, TryFutureExt};
 
 use super::*;
-use crate::support::{AsyncRuntime, ResultReporter};
+use crate::support::{AsyncRuntime, CancellationId, ResultReporter};
 
 /// Used to complete a Java CompletableFuture from any thread.
 pub struct FutureCompleter<T> {
@@ -169,7 +169,17 @@ where
         jni_args!(() -> void),
     )?;
     let completer = FutureCompleter::new(env, &java_future)?;
-    runtime.run_future(future, completer);
+    let cancellation_id = runtime.run_future(future, completer);
+    if cancellation_id != CancellationId::NotSupported {
+        // If the future has already completed, this is harmless; cancelling it will do nothing.
+        let raw_cancellation_id = u64::from(cancellation_id) as jlong;
+        call_method_checked(
+            env,
+            &java_future,
+            "setCancellationId",
+            jni_args!((raw_cancellation_id => long) -> void),
+        )?;
+    }
     Ok(java_future.into())
 }
 
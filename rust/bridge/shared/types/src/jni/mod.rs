@@ -5,6 +5,7 @@
 use std::error::Error;
 use std::fmt::Display;
 use std::marker::PhantomData;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use attest::enclave::Error as EnclaveError;
 use attest::hsm_enclave::Error as HsmEnclaveError;
@@ -20,11 +21,12 @@ use libsignal_account_keys::Error as PinError;
 use libsignal_net::chat::{ConnectError as ChatConnectError, SendError as ChatSendError};
 use libsignal_net::infra::errors::RetryLater;
 use libsignal_net::infra::ws::WebSocketServiceError;
-use libsignal_net::keytrans::Error as KeyTransNetError;
+use libsignal_net::ws::RateLimitChallenge;
 use libsignal_protocol::*;
 use signal_crypto::Error as SignalCryptoError;
 use usernames::{UsernameError, UsernameLinkError};
 
+use crate::keytrans::KeyTransError;
 use crate::net::cdsi::CdsiError;
 
 #[macro_use]
@@ -300,7 +302,33 @@ impl<'env> ConsumableException<'env> {
             SignalJniError::Cdsi(CdsiError::RateLimited(RetryLater {
                 retry_after_seconds,
             })) => {
-                let throwable = retry_later_exception(env, retry_after_seconds);
+                let throwable = retry_later_exception(env, retry_after_seconds, None);
+
+                return ConsumableException {
+                    throwable,
+                    error: error.into(),
+                };
+            }
+
+            SignalJniError::KeyTransparency(KeyTransError::RateLimited {
+                retry_after: Some(retry_after),
+            }) => {
+                let retry_after_seconds = retry_after
+                    .as_duration()
+                    .as_secs()
+                    .try_into()
+                    .unwrap_or(u32::MAX);
+                let throwable = retry_later_exception(env, retry_after_seconds, None);
+
+                return ConsumableException {
+                    throwable,
+                    error: error.into(),
+                };
+            }
+
+            SignalJniError::Cdsi(CdsiError::RateLimitChallenge(ref challenge))
+            | SignalJniError::ChatConnect(ChatConnectError::RateLimitChallenge(ref challenge)) => {
+                let throwable = rate_limit_challenge_exception(env, challenge);
 
                 return ConsumableException {
                     throwable,
@@ -357,6 +385,11 @@ impl<'env> ConsumableException<'env> {
                 (ClassName("java.lang.NullPointerException"), error)
             }
 
+            SignalJniError::Bridge(BridgeLayerError::Cancelled) => (
+                ClassName("java.util.concurrent.CancellationException"),
+                error,
+            ),
+
             SignalJniError::Protocol(SignalProtocolError::InvalidState(_, _)) => {
                 (ClassName("java.lang.IllegalStateException"), error)
             }
@@ -368,7 +401,7 @@ impl<'env> ConsumableException<'env> {
             | SignalJniError::Bridge(BridgeLayerError::BadArgument(_))
             | SignalJniError::Bridge(BridgeLayerError::IntegerOverflow(_))
             | SignalJniError::Bridge(BridgeLayerError::IncorrectArrayLength { .. })
-            | SignalJniError::KeyTransparency(KeyTransNetError::DecodingFailed(_)) => {
+            | SignalJniError::KeyTransparency(KeyTransError::BadArguments(_)) => {
                 (ClassName("java.lang.IllegalArgumentException"), error)
             }
 
@@ -618,23 +651,48 @@ impl<'env> ConsumableException<'env> {
 
             SignalJniError::ChatConnect(ref chat) => {
                 let class = match chat {
-                    ChatConnectError::RetryLater(RetryLater {
-                        retry_after_seconds,
-                    }) => {
+                    ChatConnectError::RetryLater {
+                        retry_later:
+                            RetryLater {
+                                retry_after_seconds,
+                            },
+                        received_at: _,
+                    } => {
                         return ConsumableException {
-                            throwable: retry_later_exception(env, *retry_after_seconds),
+                            throwable: retry_later_exception(
+                                env,
+                                *retry_after_seconds,
+                                chat.retry_at_system_time(),
+                            ),
                             error: error.into(),
                         }
                     }
-                    ChatConnectError::AppExpired => {
-                        ClassName("org.signal.libsignal.net.AppExpiredException")
+                    ChatConnectError::AppExpired {
+                        minimum_version,
+                        upgrade_url,
+                    } => {
+                        return ConsumableException {
+                            throwable: app_expired_exception(
+                                env,
+                                chat.to_string(),
+                                minimum_version.as_deref(),
+                                upgrade_url.as_deref(),
+                            ),
+                            error: error.into(),
+                        }
                     }
                     ChatConnectError::DeviceDeregistered => {
                         ClassName("org.signal.libsignal.net.DeviceDeregisteredException")
                     }
+                    ChatConnectError::ProxyFailure(_) => {
+                        ClassName("org.signal.libsignal.net.ProxyFailureException")
+                    }
+                    ChatConnectError::RateLimitChallenge(_) => {
+                        unreachable!("should have been handled separately")
+                    }
                     ChatConnectError::WebSocket(_)
-                    | ChatConnectError::Timeout
-                    | ChatConnectError::AllAttemptsFailed
+                    | ChatConnectError::Timeout { .. }
+                    | ChatConnectError::AllAttemptsFailed { .. }
                     | ChatConnectError::InvalidConnectionConfiguration => {
                         ClassName("org.signal.libsignal.net.ChatServiceException")
                     }
@@ -644,13 +702,24 @@ impl<'env> ConsumableException<'env> {
 
             SignalJniError::ChatSend(ref chat) => {
                 let class = match chat {
-                    ChatSendError::Disconnected => {
+                    // Idle timeouts are routine; the app only needs to reconnect.
+                    ChatSendError::Disconnected | ChatSendError::ConnectionIdleTimeout => {
                         ClassName("org.signal.libsignal.net.ChatServiceInactiveException")
                     }
+                    ChatSendError::ConnectionLost { close_code } => {
+                        return ConsumableException {
+                            throwable: connection_lost_exception(
+                                env,
+                                chat.to_string(),
+                                *close_code,
+                            ),
+                            error: error.into(),
+                        }
+                    }
                     ChatSendError::WebSocket(_)
                     | ChatSendError::IncomingDataInvalid
                     | ChatSendError::RequestHasInvalidHeader
-                    | ChatSendError::RequestTimedOut => {
+                    | ChatSendError::RequestTimedOut { .. } => {
                         ClassName("org.signal.libsignal.net.ChatServiceException")
                     }
                 };
@@ -659,14 +728,20 @@ impl<'env> ConsumableException<'env> {
 
             SignalJniError::KeyTransparency(ref inner) => {
                 let class = match inner {
-                    KeyTransNetError::DecodingFailed(_) => {
+                    KeyTransError::BadArguments(_)
+                    | KeyTransError::RateLimited {
+                        retry_after: Some(_),
+                    } => {
                         unreachable!("should have been handled separately")
                     }
-                    KeyTransNetError::ChatSendError(_)
-                    | KeyTransNetError::RequestFailed(_)
-                    | KeyTransNetError::VerificationFailed(_)
-                    | KeyTransNetError::InvalidResponse(_)
-                    | KeyTransNetError::InvalidRequest(_) => {
+                    KeyTransError::NetworkError { .. }
+                    | KeyTransError::RateLimited { retry_after: None } => {
+                        ClassName("org.signal.libsignal.net.ChatServiceException")
+                    }
+                    KeyTransError::VerificationFailed(_)
+                    | KeyTransError::NotFound
+                    | KeyTransError::StaleState(_)
+                    | KeyTransError::InternalError(_) => {
                         ClassName("org.signal.libsignal.net.KeyTransparencyException")
                     }
                 };
@@ -690,14 +765,95 @@ impl<'env> ConsumableException<'env> {
     }
 }
 
+fn rate_limit_challenge_exception<'env>(
+    env: &mut JNIEnv<'env>,
+    challenge: &RateLimitChallenge,
+) -> Result<JThrowable<'env>, BridgeLayerError> {
+    let RateLimitChallenge { token, options } = challenge;
+    let token = token.clone().convert_into(env)?;
+    let options = options.clone().into_boxed_slice().convert_into(env)?;
+    new_instance(
+        env,
+        ClassName("org.signal.libsignal.net.RateLimitChallengeException"),
+        jni_args!((token => java.lang.String, options => [java.lang.String]) -> void),
+    )
+    .map(Into::into)
+}
+
+fn app_expired_exception<'env>(
+    env: &mut JNIEnv<'env>,
+    message: String,
+    minimum_version: Option<&str>,
+    upgrade_url: Option<&str>,
+) -> Result<JThrowable<'env>, BridgeLayerError> {
+    let message = message.convert_into(env)?;
+    let minimum_version = minimum_version.convert_into(env)?;
+    let upgrade_url = upgrade_url.convert_into(env)?;
+    new_instance(
+        env,
+        ClassName("org.signal.libsignal.net.AppExpiredException"),
+        jni_args!((
+            message => java.lang.String,
+            minimum_version => java.lang.String,
+            upgrade_url => java.lang.String,
+        ) -> void),
+    )
+    .map(Into::into)
+}
+
+fn connection_lost_exception<'env>(
+    env: &mut JNIEnv<'env>,
+    message: String,
+    close_code: Option<u16>,
+) -> Result<JThrowable<'env>, BridgeLayerError> {
+    const CLASS_NAME: ClassName = ClassName("org.signal.libsignal.net.ConnectionLostException");
+    let message = message.convert_into(env)?;
+    let Some(close_code) = close_code else {
+        return new_instance(
+            env,
+            CLASS_NAME,
+            jni_args!((message => java.lang.String) -> void),
+        )
+        .map(Into::into);
+    };
+    new_instance(
+        env,
+        CLASS_NAME,
+        jni_args!((
+            message => java.lang.String,
+            close_code.into() => int,
+        ) -> void),
+    )
+    .map(Into::into)
+}
+
 fn retry_later_exception<'env>(
     env: &mut JNIEnv<'env>,
     retry_after_seconds: u32,
+    retry_at: Option<SystemTime>,
 ) -> Result<JThrowable<'env>, BridgeLayerError> {
+    const CLASS_NAME: ClassName = ClassName("org.signal.libsignal.net.RetryLaterException");
+    let Some(retry_at) = retry_at else {
+        return new_instance(
+            env,
+            CLASS_NAME,
+            jni_args!((retry_after_seconds.into() => long) -> void),
+        )
+        .map(Into::into);
+    };
+    let retry_at_millis = retry_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .try_into()
+        .unwrap_or(i64::MAX);
     new_instance(
         env,
-        ClassName("org.signal.libsignal.net.RetryLaterException"),
-        jni_args!((retry_after_seconds.into() => long) -> void),
+        CLASS_NAME,
+        jni_args!((
+            retry_after_seconds.into() => long,
+            retry_at_millis => long,
+        ) -> void),
     )
     .map(Into::into)
 }
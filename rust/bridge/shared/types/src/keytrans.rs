@@ -1,18 +1,41 @@
-use std::time::Duration;
-
-use futures_util::future::BoxFuture;
 //
 // Copyright 2024 Signal Messenger, LLC.
 // SPDX-License-Identifier: AGPL-3.0-only
 //
+
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use http::StatusCode;
 use libsignal_net::chat;
-use libsignal_net::keytrans::{SearchResult, UnauthenticatedChat};
+use libsignal_net::infra::errors::RetryDelay;
+use libsignal_net::keytrans::{
+    AuthenticatedChat, BadArgumentsReason, E164SearchKey, Error as KeyTransNetError, SearchResult,
+    UnauthenticatedChat, UsernameHash,
+};
 
 use crate::net::chat::BridgeChatConnection as _;
 use crate::*;
 
 bridge_as_handle!(SearchResult, ffi = false, node = false);
 
+/// A username hash that has been checked to be the right length to search for.
+pub struct KeyTransUsernameHash(pub UsernameHash<'static>);
+
+bridge_as_handle!(KeyTransUsernameHash, ffi = false, node = false);
+bridge_as_handle!(E164SearchKey, ffi = false, node = false);
+
+/// Key transparency state unpacked from an export, in the same serialized forms
+/// the app persists.
+pub struct ImportedKeyTransState {
+    /// A `StoredAccountData` message.
+    pub account_data: Vec<u8>,
+    /// A `StoredTreeHead` message.
+    pub last_distinguished_tree_head: Vec<u8>,
+}
+
+bridge_as_handle!(ImportedKeyTransState);
+
 impl UnauthenticatedChat for crate::net::chat::UnauthenticatedChatConnection {
     fn send_unauthenticated(
         &self,
@@ -22,3 +45,174 @@ impl UnauthenticatedChat for crate::net::chat::UnauthenticatedChatConnection {
         Box::pin(self.send(request, timeout))
     }
 }
+
+impl AuthenticatedChat for crate::net::chat::AuthenticatedChatConnection {
+    fn send_authenticated(
+        &self,
+        request: chat::Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, Result<chat::Response, chat::SendError>> {
+        Box::pin(self.send(request, timeout))
+    }
+}
+
+/// What the apps are told about a failed key transparency operation.
+///
+/// Every [`KeyTransNetError`] is sorted into one of these by what the app can
+/// do about it. Chat connection failures in particular are reduced to whether
+/// retrying might help, so that websocket details don't reach the apps as
+/// error messages.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum KeyTransError {
+    /// Verification failed: {0}
+    VerificationFailed(String),
+    /// Network error
+    NetworkError {
+        /// Whether the same request might succeed if sent again.
+        retryable: bool,
+    },
+    /// Rate limited
+    RateLimited {
+        /// How long the server asked to wait, if it said.
+        retry_after: Option<RetryDelay>,
+    },
+    /// Not found
+    NotFound,
+    /// Key transparency state is too old to check against: {0}
+    StaleState(String),
+    /// Invalid arguments: {0}
+    BadArguments(String),
+    /// Internal error: {0}
+    InternalError(String),
+}
+
+impl From<KeyTransNetError> for KeyTransError {
+    fn from(err: KeyTransNetError) -> Self {
+        match err {
+            KeyTransNetError::ChatSendError(e) => Self::from(e),
+            KeyTransNetError::RequestFailed(status) => Self::from(status),
+            KeyTransNetError::RateLimited { retry_after } => Self::RateLimited { retry_after },
+            KeyTransNetError::VerificationFailed(_)
+            | KeyTransNetError::LegVerificationFailed { .. }
+            | KeyTransNetError::PinnedKeyMismatch { .. }
+            | KeyTransNetError::KeyRotationRejected(_) => Self::VerificationFailed(err.to_string()),
+            KeyTransNetError::TreeHeadOutOfRange { .. }
+            | KeyTransNetError::StaleView { .. }
+            | KeyTransNetError::AuditorTooFarBehind { .. } => Self::StaleState(err.to_string()),
+            KeyTransNetError::BadArguments(reason) => Self::from(reason),
+            KeyTransNetError::DecodingFailed(_)
+            | KeyTransNetError::ImportFailed(_)
+            | KeyTransNetError::UnsupportedSearchKey(_) => Self::BadArguments(err.to_string()),
+            // The server was reached; a timeout past that point is as likely to
+            // recur as the slow response that caused it.
+            KeyTransNetError::DeadlineExceeded { .. } => Self::NetworkError { retryable: true },
+            KeyTransNetError::InvalidResponse(_)
+            | KeyTransNetError::Internal(_)
+            | KeyTransNetError::StateLoadFailed(_)
+            | KeyTransNetError::StatePersistFailed(_) => Self::InternalError(err.to_string()),
+        }
+    }
+}
+
+impl From<chat::SendError> for KeyTransError {
+    fn from(err: chat::SendError) -> Self {
+        match err {
+            chat::SendError::RequestTimedOut { .. }
+            | chat::SendError::Disconnected
+            | chat::SendError::ConnectionIdleTimeout
+            | chat::SendError::ConnectionLost { .. }
+            | chat::SendError::WebSocket(_) => Self::NetworkError { retryable: true },
+            chat::SendError::IncomingDataInvalid => Self::NetworkError { retryable: false },
+            chat::SendError::RequestHasInvalidHeader => Self::InternalError(err.to_string()),
+        }
+    }
+}
+
+impl From<StatusCode> for KeyTransError {
+    fn from(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited { retry_after: None },
+            StatusCode::REQUEST_TIMEOUT => Self::NetworkError { retryable: true },
+            status if status.is_server_error() => Self::NetworkError { retryable: true },
+            status => Self::InternalError(format!("unexpected status {status}")),
+        }
+    }
+}
+
+impl From<BadArgumentsReason> for KeyTransError {
+    fn from(reason: BadArgumentsReason) -> Self {
+        Self::BadArguments(reason.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use assert_matches::assert_matches;
+    use libsignal_core::Aci;
+    use libsignal_net::keytrans::{AccountDataField, OperationPhase, SearchKeyKind};
+    use test_case::test_case;
+
+    use super::*;
+
+    fn verification_error() -> libsignal_keytrans::Error {
+        libsignal_keytrans::Error::VerificationFailed("bad proof".to_owned())
+    }
+
+    // One case per source variant, so that adding a variant without deciding
+    // where it goes shows up here as well as in the match.
+    #[test_case(KeyTransNetError::ChatSendError(chat::SendError::Disconnected) => matches KeyTransError::NetworkError { retryable: true }; "chat send")]
+    #[test_case(KeyTransNetError::RequestFailed(StatusCode::NOT_FOUND) => matches KeyTransError::NotFound; "request failed")]
+    #[test_case(KeyTransNetError::RateLimited { retry_after: Some(RetryDelay::from_secs(30)) } => matches KeyTransError::RateLimited { retry_after: Some(delay) } if delay == RetryDelay::from_secs(30); "rate limited")]
+    #[test_case(KeyTransNetError::VerificationFailed(verification_error()) => matches KeyTransError::VerificationFailed(_); "verification failed")]
+    #[test_case(KeyTransNetError::LegVerificationFailed { leg: SearchKeyKind::E164, source: verification_error() } => matches KeyTransError::VerificationFailed(_); "leg verification failed")]
+    #[test_case(KeyTransNetError::InvalidResponse("no tree head".to_owned()) => matches KeyTransError::InternalError(_); "invalid response")]
+    #[test_case(KeyTransNetError::BadArguments(BadArgumentsReason::E164OutOfRange) => matches KeyTransError::BadArguments(_); "bad arguments")]
+    #[test_case(KeyTransNetError::Internal("oops") => matches KeyTransError::InternalError(_); "internal")]
+    #[test_case(KeyTransNetError::DecodingFailed(prost::DecodeError::new("truncated")) => matches KeyTransError::BadArguments(_); "decoding failed")]
+    #[test_case(KeyTransNetError::TreeHeadOutOfRange { timestamp: SystemTime::UNIX_EPOCH, now: SystemTime::UNIX_EPOCH } => matches KeyTransError::StaleState(_); "tree head out of range")]
+    #[test_case(KeyTransNetError::StaleView { search_size: 1, distinguished_size: 2 } => matches KeyTransError::StaleState(_); "stale view")]
+    #[test_case(KeyTransNetError::AuditorTooFarBehind { lag: 2, max_lag: 1 } => matches KeyTransError::StaleState(_); "auditor too far behind")]
+    #[test_case(KeyTransNetError::PinnedKeyMismatch { aci: Aci::from(uuid::Uuid::nil()) } => matches KeyTransError::VerificationFailed(_); "pinned key mismatch")]
+    #[test_case(KeyTransNetError::UnsupportedSearchKey(AccountDataField::E164) => matches KeyTransError::BadArguments(_); "unsupported search key")]
+    #[test_case(KeyTransNetError::ImportFailed(libsignal_keytrans::ImportError::Truncated) => matches KeyTransError::BadArguments(_); "import failed")]
+    #[test_case(KeyTransNetError::KeyRotationRejected(libsignal_keytrans::KeyRotationError::UnknownKey) => matches KeyTransError::VerificationFailed(_); "key rotation rejected")]
+    #[test_case(KeyTransNetError::DeadlineExceeded { phase: OperationPhase::Request } => matches KeyTransError::NetworkError { retryable: true }; "deadline exceeded")]
+    #[test_case(KeyTransNetError::StateLoadFailed("disk full".into()) => matches KeyTransError::InternalError(_); "state load failed")]
+    #[test_case(KeyTransNetError::StatePersistFailed("disk full".into()) => matches KeyTransError::InternalError(_); "state persist failed")]
+    fn every_error_is_mapped(err: KeyTransNetError) -> KeyTransError {
+        KeyTransError::from(err)
+    }
+
+    #[test_case(chat::SendError::RequestTimedOut { correlation_id: None } => matches KeyTransError::NetworkError { retryable: true }; "timed out")]
+    #[test_case(chat::SendError::Disconnected => matches KeyTransError::NetworkError { retryable: true }; "disconnected")]
+    #[test_case(chat::SendError::ConnectionIdleTimeout => matches KeyTransError::NetworkError { retryable: true }; "idle timeout")]
+    #[test_case(chat::SendError::ConnectionLost { close_code: Some(1011) } => matches KeyTransError::NetworkError { retryable: true }; "connection lost")]
+    #[test_case(chat::SendError::IncomingDataInvalid => matches KeyTransError::NetworkError { retryable: false }; "incoming data invalid")]
+    #[test_case(chat::SendError::RequestHasInvalidHeader => matches KeyTransError::InternalError(_); "invalid header")]
+    fn chat_errors_are_reduced_to_retryability(err: chat::SendError) -> KeyTransError {
+        KeyTransError::from(KeyTransNetError::ChatSendError(err))
+    }
+
+    #[test_case(StatusCode::NOT_FOUND => matches KeyTransError::NotFound; "not found")]
+    #[test_case(StatusCode::TOO_MANY_REQUESTS => matches KeyTransError::RateLimited { retry_after: None }; "too many requests")]
+    #[test_case(StatusCode::REQUEST_TIMEOUT => matches KeyTransError::NetworkError { retryable: true }; "request timeout")]
+    #[test_case(StatusCode::SERVICE_UNAVAILABLE => matches KeyTransError::NetworkError { retryable: true }; "service unavailable")]
+    #[test_case(StatusCode::FORBIDDEN => matches KeyTransError::InternalError(_); "forbidden")]
+    fn status_codes_are_classified(status: StatusCode) -> KeyTransError {
+        KeyTransError::from(KeyTransNetError::RequestFailed(status))
+    }
+
+    #[test]
+    fn chat_error_details_are_not_passed_on() {
+        let err = KeyTransError::from(KeyTransNetError::ChatSendError(
+            chat::SendError::ConnectionLost {
+                close_code: Some(1011),
+            },
+        ));
+        assert_matches!(err, KeyTransError::NetworkError { .. });
+        assert_eq!(err.to_string(), "Network error");
+    }
+}
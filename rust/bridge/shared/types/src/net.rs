@@ -3,23 +3,43 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::panic::RefUnwindSafe;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
+use libsignal_net::chat::state::{ConnectionState, ConnectionStateMachine};
+use libsignal_net::chat::RequestPathPrefix;
 use libsignal_net::connect_state::{
-    ConnectState, DefaultConnectorFactory, PreconnectingFactory, SUGGESTED_CONNECT_CONFIG,
+    ConnectConfigOverrides, ConnectState, ConnectStateReset, DefaultConnectorFactory,
+    InvalidConnectConfig, NetworkType, PreconnectingFactory, SUGGESTED_CONNECT_CONFIG,
     SUGGESTED_TLS_PRECONNECT_LIFETIME,
 };
-use libsignal_net::enclave::{Cdsi, EnclaveEndpoint, EnclaveEndpointConnection, EnclaveKind};
+use libsignal_net::connection_quality::{
+    ConnectionQuality, ConnectionQualityThresholds, InvalidConnectionQualityThresholds,
+    RoundTripEstimator,
+};
+use libsignal_net::data_usage::{DataUsage, DataUsageSnapshot};
+use libsignal_net::enclave::{
+    AttestedMeasurement, Cdsi, EnclaveEndpoint, EnclaveEndpointConnection, EnclaveKind,
+};
 use libsignal_net::env::{add_user_agent_header, Env, UserAgent};
-use libsignal_net::infra::connection_manager::MultiRouteConnectionManager;
+use libsignal_net::infra::connection_manager::{MultiRouteConnectionManager, RouteCooldowns};
 use libsignal_net::infra::dns::DnsResolver;
 use libsignal_net::infra::route::ConnectionProxyConfig;
-use libsignal_net::infra::tcp_ssl::{InvalidProxyConfig, TcpSslConnector};
+use libsignal_net::infra::tcp_ssl::{
+    check_local_bind_address, InvalidLocalBindAddress, InvalidProxyConfig, TcpSslConnector,
+    TlsHandshakeCounts,
+};
 use libsignal_net::infra::timeouts::ONE_ROUTE_CONNECTION_TIMEOUT;
-use libsignal_net::infra::utils::ObservableEvent;
+use libsignal_net::infra::utils::{
+    EventSubscription, ObservableEvent, ObservableEventWithPayload, SingleFlight,
+};
 use libsignal_net::infra::{EnableDomainFronting, EndpointConnection};
+use libsignal_net::metrics::ServiceKind;
+use libsignal_net::network_events::NetworkEventLog;
+use libsignal_net::server_time::ServerTimeEstimator;
 
 use crate::*;
 
@@ -46,10 +66,20 @@ impl Environment {
     }
 }
 
+/// The connection parameters for each service, as of the most recent censorship circumvention and
+/// network type settings.
+///
+/// Each service's connection is shared separately, so that changing a setting only rebuilds the
+/// connections that depend on it, and so that copying the whole set is cheap.
+#[derive(Clone)]
 struct EndpointConnections {
-    chat: EndpointConnection<MultiRouteConnectionManager>,
-    cdsi: EnclaveEndpointConnection<Cdsi, MultiRouteConnectionManager>,
+    chat: Arc<EndpointConnection<MultiRouteConnectionManager>>,
+    cdsi: Arc<EnclaveEndpointConnection<Cdsi, MultiRouteConnectionManager>>,
     enable_fronting: EnableDomainFronting,
+    /// The network type [`Self::cdsi`] was built for.
+    network_type: NetworkType,
+    /// Incremented whenever any of the connections is rebuilt.
+    version: u64,
 }
 
 impl EndpointConnections {
@@ -57,7 +87,8 @@ impl EndpointConnections {
         env: &Env<'static>,
         user_agent: &UserAgent,
         use_fallbacks: bool,
-        network_change_event: &ObservableEvent,
+        network_type: NetworkType,
+        route_cooldowns: &RouteCooldowns,
     ) -> Self {
         log::info!(
             "Creating endpoint connections (fallbacks {}) for {} and others",
@@ -67,30 +98,99 @@ impl EndpointConnections {
             // testing. (Or the person running this isn't Signal.)
             env.chat_domain_config.connect.hostname
         );
-        let chat = libsignal_net::chat::endpoint_connection(
-            &env.chat_domain_config.connect,
-            user_agent,
-            use_fallbacks,
-            network_change_event,
-        );
-        let cdsi =
-            Self::endpoint_connection(&env.cdsi, user_agent, use_fallbacks, network_change_event);
         Self {
+            chat: Self::chat_connection(env, user_agent, use_fallbacks, route_cooldowns),
+            cdsi: Self::endpoint_connection(
+                &env.cdsi,
+                user_agent,
+                use_fallbacks,
+                network_type,
+                route_cooldowns,
+            )
+            .into(),
+            enable_fronting: Self::enable_fronting(use_fallbacks),
+            network_type,
+            version: 0,
+        }
+    }
+
+    /// Returns a copy updated for new settings, or `None` if the settings haven't changed.
+    ///
+    /// Connections that don't depend on the changed settings are shared with `self`.
+    fn rebuild(
+        &self,
+        env: &Env<'static>,
+        user_agent: &UserAgent,
+        use_fallbacks: bool,
+        network_type: NetworkType,
+        route_cooldowns: &RouteCooldowns,
+    ) -> Option<Self> {
+        let fallbacks_changed = use_fallbacks != self.use_fallbacks();
+        if !fallbacks_changed && network_type == self.network_type {
+            return None;
+        }
+        log::info!(
+            "Rebuilding endpoint connections (fallbacks {}, network type {network_type:?})",
+            if use_fallbacks { "enabled" } else { "disabled" },
+        );
+        // Chat's connection doesn't depend on the network type.
+        let chat = if fallbacks_changed {
+            Self::chat_connection(env, user_agent, use_fallbacks, route_cooldowns)
+        } else {
+            self.chat.clone()
+        };
+        Some(Self {
             chat,
-            cdsi,
-            enable_fronting: if use_fallbacks {
-                EnableDomainFronting::OneDomainPerProxy
-            } else {
-                EnableDomainFronting::No
-            },
+            cdsi: Self::endpoint_connection(
+                &env.cdsi,
+                user_agent,
+                use_fallbacks,
+                network_type,
+                route_cooldowns,
+            )
+            .into(),
+            enable_fronting: Self::enable_fronting(use_fallbacks),
+            network_type,
+            version: self.version + 1,
+        })
+    }
+
+    fn use_fallbacks(&self) -> bool {
+        match self.enable_fronting {
+            EnableDomainFronting::No => false,
+            EnableDomainFronting::OneDomainPerProxy | EnableDomainFronting::AllDomains => true,
         }
     }
 
+    fn enable_fronting(use_fallbacks: bool) -> EnableDomainFronting {
+        if use_fallbacks {
+            EnableDomainFronting::OneDomainPerProxy
+        } else {
+            EnableDomainFronting::No
+        }
+    }
+
+    fn chat_connection(
+        env: &Env<'static>,
+        user_agent: &UserAgent,
+        use_fallbacks: bool,
+        route_cooldowns: &RouteCooldowns,
+    ) -> Arc<EndpointConnection<MultiRouteConnectionManager>> {
+        libsignal_net::chat::endpoint_connection(
+            &env.chat_domain_config.connect,
+            user_agent,
+            use_fallbacks,
+            route_cooldowns,
+        )
+        .into()
+    }
+
     fn endpoint_connection<E: EnclaveKind>(
         endpoint: &EnclaveEndpoint<'static, E>,
         user_agent: &UserAgent,
         include_fallback: bool,
-        network_change_event: &ObservableEvent,
+        network_type: NetworkType,
+        route_cooldowns: &RouteCooldowns,
     ) -> EnclaveEndpointConnection<E, MultiRouteConnectionManager> {
         let params = if include_fallback {
             endpoint
@@ -104,23 +204,84 @@ impl EndpointConnections {
         EnclaveEndpointConnection::new_multi(
             endpoint,
             params,
-            ONE_ROUTE_CONNECTION_TIMEOUT,
-            network_change_event,
+            network_type.scale_connect_timeout(ONE_ROUTE_CONNECTION_TIMEOUT),
+            route_cooldowns,
         )
     }
 }
 
+/// Something that happened to a [`ConnectionManager`] that may affect existing or future
+/// connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetEvent {
+    /// The device's network changed (after debouncing).
+    NetworkChanged,
+    /// The proxy configuration was set, cleared, or marked invalid.
+    ProxyChanged,
+    /// The set of routes used for new connections was rebuilt, e.g. because censorship
+    /// circumvention was toggled.
+    EndpointsRebuilt,
+}
+
+/// What [`ConnectionManager::reset_connect_state`] cleared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectStateResetSummary {
+    /// When the reset happened.
+    pub at: Instant,
+    /// What was discarded from the [`ConnectState`].
+    pub connect_state: ConnectStateReset,
+    /// The number of DNS lookups that were in progress and will be started over.
+    pub dns_lookups_flushed: usize,
+}
+
+/// Collapses bursts of network change notifications into a single change.
+struct NetworkChangeDebounce {
+    /// When the most recent change that wasn't debounced happened.
+    most_recent: Instant,
+    /// Changes within this long of [`Self::most_recent`] are ignored.
+    window: Duration,
+}
+
 pub struct ConnectionManager {
     env: Env<'static>,
     user_agent: UserAgent,
     dns_resolver: DnsResolver,
     connect: ::tokio::sync::RwLock<ConnectState<PreconnectingFactory>>,
     // We could split this up to a separate mutex on each kind of connection,
-    // but we don't hold it for very long anyway (just enough to clone the Arc).
-    endpoints: std::sync::Mutex<Arc<EndpointConnections>>,
+    // but we don't hold it for very long anyway (just enough to clone an Arc).
+    endpoints: std::sync::Mutex<EndpointConnections>,
     transport_connector: std::sync::Mutex<TcpSslConnector>,
-    most_recent_network_change: std::sync::Mutex<Instant>,
+    network_change_debounce: std::sync::Mutex<NetworkChangeDebounce>,
+    /// Kept for internal consumers that only care that the network changed; see also
+    /// [`Self::net_events`].
     network_change_event: ObservableEvent,
+    /// Shared by every version of [`Self::endpoints`], so that rebuilding them doesn't forget
+    /// which routes are failing.
+    route_cooldowns: RouteCooldowns,
+    net_events: ObservableEventWithPayload<NetEvent>,
+    /// The state of the most recent authenticated chat connection made through this manager.
+    chat_state: Arc<ConnectionStateMachine>,
+    /// Updated from the timestamps on chat responses.
+    server_time: Arc<ServerTimeEstimator>,
+    /// Belongs to the most recent authenticated chat connection, if one has been made.
+    chat_round_trip: std::sync::Mutex<Option<Arc<RoundTripEstimator>>>,
+    /// See [`Self::set_connection_quality_thresholds`].
+    connection_quality_thresholds: std::sync::Mutex<ConnectionQualityThresholds>,
+    /// Applied to chat connections made after it's set.
+    request_path_prefix: std::sync::Mutex<Option<RequestPathPrefix>>,
+    /// Lets concurrent chat connection attempts share a single connection.
+    chat_connects: SingleFlight<chat::ChatConnectKey, chat::SharedChatConnectResult>,
+    /// Shared with [`Self::connect`], which hands it to every connection.
+    data_usage: Arc<DataUsage>,
+    /// Shared with [`Self::connect`], which records every connection attempt in it.
+    network_events: Arc<NetworkEventLog>,
+    /// The enclave measurement of the most recent successful attestation for each service.
+    attested_measurements: std::sync::Mutex<HashMap<ServiceKind, AttestedMeasurement>>,
+    /// Set by [`Self::reset_connect_state`].
+    last_connect_state_reset: std::sync::Mutex<Option<ConnectStateResetSummary>>,
+    /// See [`Self::set_fake_chat_server`].
+    #[cfg(feature = "test-util")]
+    fake_chat_server: std::sync::Mutex<Option<libsignal_net::chat::fake::server::FakeChatServer>>,
 }
 
 impl RefUnwindSafe for ConnectionManager {}
@@ -137,42 +298,185 @@ impl ConnectionManager {
 
         let dns_resolver =
             DnsResolver::new_with_static_fallback(env.static_fallback(), &network_change_event);
-        let transport_connector =
-            std::sync::Mutex::new(TcpSslConnector::new_direct(dns_resolver.clone()));
-        let endpoints = std::sync::Mutex::new(
-            EndpointConnections::new(&env, &user_agent, false, &network_change_event).into(),
+        let transport_connector = TcpSslConnector::new_direct(dns_resolver.clone());
+        let route_cooldowns = RouteCooldowns::new(&network_change_event);
+        let endpoints = std::sync::Mutex::new(EndpointConnections::new(
+            &env,
+            &user_agent,
+            false,
+            NetworkType::default(),
+            &route_cooldowns,
+        ));
+        let mut connect = ConnectState::new_with_transport_connector(
+            SUGGESTED_CONNECT_CONFIG,
+            PreconnectingFactory::new(
+                DefaultConnectorFactory::default(),
+                SUGGESTED_TLS_PRECONNECT_LIFETIME,
+            ),
         );
+        // Connections made through `connect` resume the same TLS sessions as
+        // those made through `transport_connector`, which clears them when the
+        // proxy changes.
+        connect
+            .get_mut()
+            .set_tls_session_cache(Some(transport_connector.tls_session_cache().clone()));
+        let data_usage = connect.get_mut().data_usage.clone();
+        let network_events = connect.get_mut().network_events.clone();
         Self {
             env,
             endpoints,
             user_agent,
-            connect: ConnectState::new_with_transport_connector(
-                SUGGESTED_CONNECT_CONFIG,
-                PreconnectingFactory::new(
-                    DefaultConnectorFactory,
-                    SUGGESTED_TLS_PRECONNECT_LIFETIME,
-                ),
-            ),
+            connect,
             dns_resolver,
-            transport_connector,
-            most_recent_network_change: Instant::now().into(),
+            transport_connector: transport_connector.into(),
+            network_change_debounce: NetworkChangeDebounce {
+                most_recent: Instant::now(),
+                window: Self::NETWORK_CHANGE_DEBOUNCE,
+            }
+            .into(),
             network_change_event,
+            route_cooldowns,
+            net_events: Default::default(),
+            chat_state: Default::default(),
+            server_time: Default::default(),
+            chat_round_trip: Default::default(),
+            connection_quality_thresholds: Default::default(),
+            request_path_prefix: Default::default(),
+            chat_connects: Default::default(),
+            data_usage,
+            network_events,
+            attested_measurements: Default::default(),
+            last_connect_state_reset: Default::default(),
+            #[cfg(feature = "test-util")]
+            fake_chat_server: Default::default(),
+        }
+    }
+
+    /// Calls `callback` with each [`NetEvent`] from now on, until the returned subscription is
+    /// dropped.
+    ///
+    /// Callbacks are run synchronously by whichever thread caused the event, so they should be
+    /// quick; see [`ObservableEventWithPayload::subscribe`].
+    pub fn subscribe_to_net_events(
+        &self,
+        callback: Box<dyn FnMut(&NetEvent) + Send>,
+    ) -> EventSubscription {
+        self.net_events.subscribe(callback)
+    }
+
+    /// Adds `prefix` to the path of every request sent over chat connections made from now on.
+    ///
+    /// Existing connections are unaffected.
+    pub fn set_request_path_prefix(&self, prefix: Option<RequestPathPrefix>) {
+        *self.request_path_prefix.lock().expect("not poisoned") = prefix;
+    }
+
+    /// The number of bytes sent and received for each service since this manager was created or
+    /// [`Self::reset_data_usage`] was last called.
+    ///
+    /// Connections made through this manager, including those since closed, all count toward the
+    /// totals. See [`DataUsage`] for exactly what's counted.
+    pub fn data_usage(&self) -> DataUsageSnapshot {
+        self.data_usage.snapshot()
+    }
+
+    pub fn reset_data_usage(&self) {
+        self.data_usage.reset()
+    }
+
+    /// The most recent connection attempts made through this manager, as a JSON array suitable
+    /// for attaching to a bug report.
+    ///
+    /// Only timestamps, durations, and coarse categories are included; see [`NetworkEventLog`].
+    pub fn export_recent_network_events(&self) -> String {
+        self.network_events.export_json()
+    }
+
+    /// How many TLS handshakes for connections made through this manager resumed an earlier
+    /// session, and how many were full handshakes.
+    ///
+    /// Saved sessions are forgotten when the network or the proxy changes.
+    pub fn tls_handshake_counts(&self) -> TlsHandshakeCounts {
+        self.transport_connector
+            .lock()
+            .expect("not poisoned")
+            .tls_session_cache()
+            .handshake_counts()
+    }
+
+    /// The enclave measurement that the most recent successful connection to `service` was
+    /// attested against, if there has been one.
+    ///
+    /// Expected measurements change as enclaves are upgraded, so this records which one was
+    /// actually in use.
+    pub fn last_attested_measurement(&self, service: ServiceKind) -> Option<AttestedMeasurement> {
+        self.attested_measurements
+            .lock()
+            .expect("not poisoned")
+            .get(&service)
+            .cloned()
+    }
+
+    fn record_attestation(&self, service: ServiceKind, measurement: &AttestedMeasurement) {
+        _ = self
+            .attested_measurements
+            .lock()
+            .expect("not poisoned")
+            .insert(service, measurement.clone());
+    }
+
+    /// Adds `addrs` as candidates when connecting to `service` for the next `ttl`.
+    ///
+    /// This is for addresses the app learned about out-of-band, e.g. during a censorship event.
+    /// They're tried after the addresses found by resolving the service's hostname, and are used
+    /// on their own if resolution fails. Replaces any hints previously set for `service`.
+    pub fn set_endpoint_ip_hints(&self, service: ServiceKind, addrs: Vec<IpAddr>, ttl: Duration) {
+        log::info!("setting {} IP hint(s) for {service:?}", addrs.len());
+        self.dns_resolver
+            .set_ip_hints(self.endpoint_hostname(service), addrs, ttl);
+    }
+
+    pub fn clear_endpoint_ip_hints(&self, service: ServiceKind) {
+        self.dns_resolver
+            .clear_ip_hints(self.endpoint_hostname(service));
+    }
+
+    /// The number of unexpired addresses set by [`Self::set_endpoint_ip_hints`] for `service`.
+    pub fn endpoint_ip_hint_count(&self, service: ServiceKind) -> usize {
+        self.dns_resolver
+            .ip_hint_count(self.endpoint_hostname(service))
+    }
+
+    fn endpoint_hostname(&self, service: ServiceKind) -> &'static str {
+        match service {
+            ServiceKind::Chat => self.env.chat_domain_config.connect.hostname,
+            ServiceKind::Cdsi => self.env.cdsi.domain_config.connect.hostname,
+            ServiceKind::Svr => self.env.svr2.domain_config.connect.hostname,
         }
     }
 
     pub fn set_proxy(&self, proxy: ConnectionProxyConfig) {
-        let mut guard = self.transport_connector.lock().expect("not poisoned");
-        guard.set_proxy(proxy);
+        self.transport_connector
+            .lock()
+            .expect("not poisoned")
+            .set_proxy(proxy);
+        self.net_events.fire(&NetEvent::ProxyChanged);
     }
 
     pub fn set_invalid_proxy(&self) {
-        let mut guard = self.transport_connector.lock().expect("not poisoned");
-        guard.set_invalid();
+        self.transport_connector
+            .lock()
+            .expect("not poisoned")
+            .set_invalid();
+        self.net_events.fire(&NetEvent::ProxyChanged);
     }
 
     pub fn clear_proxy(&self) {
-        let mut guard = self.transport_connector.lock().expect("not poisoned");
-        guard.clear_proxy();
+        self.transport_connector
+            .lock()
+            .expect("not poisoned")
+            .clear_proxy();
+        self.net_events.fire(&NetEvent::ProxyChanged);
     }
 
     pub fn is_using_proxy(&self) -> Result<bool, InvalidProxyConfig> {
@@ -186,54 +490,322 @@ impl ConnectionManager {
         self.connect.blocking_write().route_resolver.allow_ipv6 = ipv6_enabled;
     }
 
+    /// Binds the sockets of connections made from now on to `address`, for devices with more than
+    /// one network interface (say, to keep traffic off a VPN). `None` lets the OS pick again.
+    ///
+    /// Fails without changing anything if sockets can't be bound to `address` right now. If that
+    /// changes later, connection attempts fail with
+    /// [`TransportConnectError::LocalBindFailed`](libsignal_net::infra::errors::TransportConnectError::LocalBindFailed).
+    pub fn set_local_bind_address(
+        &self,
+        address: Option<IpAddr>,
+    ) -> Result<(), InvalidLocalBindAddress> {
+        if let Some(address) = address {
+            check_local_bind_address(address)?;
+        }
+        log::info!(
+            "ConnectionManager: {} local bind address",
+            if address.is_some() {
+                "setting"
+            } else {
+                "clearing"
+            }
+        );
+        self.transport_connector
+            .lock()
+            .expect("not poisoned")
+            .set_local_bind_address(address);
+        self.connect
+            .blocking_write()
+            .set_local_bind_address(address);
+        self.dns_resolver.set_local_bind_address(address);
+        Ok(())
+    }
+
+    /// Changes how subsequent connections are attempted.
+    ///
+    /// Start from [`ConnectConfigOverrides::default()`] to only change some settings.
+    pub fn set_connect_config(
+        &self,
+        overrides: ConnectConfigOverrides,
+    ) -> Result<(), InvalidConnectConfig> {
+        log::info!("ConnectionManager: setting connect config {overrides:?}");
+        self.connect
+            .blocking_write()
+            .set_config_overrides(overrides)
+    }
+
+    /// Replaces the randomness used to pick between routes with `rng`.
+    ///
+    /// See [`ConnectState::set_route_randomness_for_testing`].
+    #[cfg(feature = "test-util")]
+    pub fn set_route_randomness_for_testing(&self, rng: impl rand::RngCore + Send + 'static) {
+        self.connect
+            .blocking_write()
+            .set_route_randomness_for_testing(rng)
+    }
+
+    /// Makes the routes picked by `injector` fail in the given way instead of being attempted.
+    ///
+    /// See [`ConnectState::set_route_fault_injector`].
+    #[cfg(feature = "test-util")]
+    pub fn set_route_fault_injector(
+        &self,
+        injector: Option<libsignal_net::connect_state::RouteFaultInjector>,
+    ) {
+        self.connect
+            .blocking_write()
+            .set_route_fault_injector(injector)
+    }
+
+    /// Has chat connections made from now on talk to `server` instead of going over the network.
+    ///
+    /// Connecting always succeeds immediately, without any progress being reported, and the
+    /// connections' states aren't tracked. Authenticated connections don't reconnect to the fake
+    /// server once lost.
+    #[cfg(feature = "test-util")]
+    pub fn set_fake_chat_server(
+        &self,
+        server: Option<libsignal_net::chat::fake::server::FakeChatServer>,
+    ) {
+        *self.fake_chat_server.lock().expect("not poisoned") = server;
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn fake_chat_server(
+        &self,
+    ) -> Option<libsignal_net::chat::fake::server::FakeChatServer> {
+        self.fake_chat_server.lock().expect("not poisoned").clone()
+    }
+
     /// Resets the endpoint connections to include or exclude censorship circumvention routes.
     ///
     /// This is not itself a network change event; existing working connections are expected to
     /// continue to work, and existing failing connections will continue to fail.
     pub fn set_censorship_circumvention_enabled(&self, enabled: bool) {
-        let new_endpoints = EndpointConnections::new(
+        // Held until the endpoints are updated, so a concurrent change to the network type can't
+        // be overwritten.
+        let connect_guard = self.connect.blocking_read();
+        let rebuilt = self.rebuild_endpoints(enabled, connect_guard.network_type);
+        drop(connect_guard);
+        if rebuilt {
+            self.net_events.fire(&NetEvent::EndpointsRebuilt);
+        }
+    }
+
+    /// Records the kind of network the device is using, which is used to adjust connect timeouts
+    /// and route ordering for future connections.
+    ///
+    /// See [`NetworkType`] for the specific adjustments.
+    ///
+    /// Like [`Self::set_censorship_circumvention_enabled`], this is not itself a network change
+    /// event; call [`Self::on_network_change`] separately if the network actually changed.
+    pub fn set_network_type(&self, network_type: NetworkType) {
+        let mut connect_guard = self.connect.blocking_write();
+        if connect_guard.network_type == network_type {
+            return;
+        }
+        log::info!("ConnectionManager: network type is now {network_type:?}");
+        connect_guard.network_type = network_type;
+
+        let use_fallbacks = self.endpoints.lock().expect("not poisoned").use_fallbacks();
+        let rebuilt = self.rebuild_endpoints(use_fallbacks, network_type);
+        drop(connect_guard);
+        if rebuilt {
+            self.net_events.fire(&NetEvent::EndpointsRebuilt);
+        }
+    }
+
+    /// Identifies the current set of endpoint connections.
+    ///
+    /// Changes whenever [`Self::set_censorship_circumvention_enabled`] or
+    /// [`Self::set_network_type`] rebuilds any of them, so that anything holding on to an older
+    /// connection can tell it should fetch a fresh one.
+    pub fn endpoints_version(&self) -> u64 {
+        self.endpoints.lock().expect("not poisoned").version
+    }
+
+    /// Updates the endpoint connections for new settings, returning whether anything changed.
+    ///
+    /// The caller must hold [`Self::connect`] so that `network_type` stays current.
+    fn rebuild_endpoints(&self, use_fallbacks: bool, network_type: NetworkType) -> bool {
+        let mut endpoints_guard = self.endpoints.lock().expect("not poisoned");
+        let Some(rebuilt) = endpoints_guard.rebuild(
             &self.env,
             &self.user_agent,
-            enabled,
-            &self.network_change_event,
-        );
-        *self.endpoints.lock().expect("not poisoned") = Arc::new(new_endpoints);
+            use_fallbacks,
+            network_type,
+            &self.route_cooldowns,
+        ) else {
+            return false;
+        };
+        *endpoints_guard = rebuilt;
+        true
     }
 
-    const NETWORK_CHANGE_DEBOUNCE: Duration = Duration::from_secs(1);
+    /// The current time according to the chat server, as best we can tell.
+    ///
+    /// This is the local time adjusted by the offset observed in chat responses, or just the local
+    /// time if no responses have been received yet.
+    pub fn server_time_estimate(&self) -> SystemTime {
+        self.server_time.estimate(SystemTime::now())
+    }
+
+    /// How responsive the authenticated chat connection is, or `None` if it isn't connected.
+    ///
+    /// This is based on the round-trip times of the connection's handshake and keepalive pings,
+    /// so it doesn't cost any extra network traffic.
+    pub fn chat_connection_quality(&self) -> Option<ConnectionQuality> {
+        if !matches!(self.chat_state.current(), ConnectionState::Connected { .. }) {
+            return None;
+        }
+        let round_trip = self.chat_round_trip.lock().expect("not poisoned").clone()?;
+        let thresholds = *self
+            .connection_quality_thresholds
+            .lock()
+            .expect("not poisoned");
+        round_trip.quality(&thresholds)
+    }
+
+    /// Sets where [`Self::chat_connection_quality`] draws the lines between levels.
+    pub fn set_connection_quality_thresholds(
+        &self,
+        thresholds: ConnectionQualityThresholds,
+    ) -> Result<(), InvalidConnectionQualityThresholds> {
+        *self
+            .connection_quality_thresholds
+            .lock()
+            .expect("not poisoned") = thresholds.validate()?;
+        Ok(())
+    }
+
+    /// Makes [`Self::chat_connection_quality`] report on the connection that `round_trip`
+    /// belongs to.
+    pub(crate) fn set_chat_round_trip_estimator(&self, round_trip: Arc<RoundTripEstimator>) {
+        *self.chat_round_trip.lock().expect("not poisoned") = Some(round_trip);
+    }
+
+    /// The default for [`Self::set_network_change_debounce`].
+    pub const NETWORK_CHANGE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+    /// Sets how long after a network change further changes are ignored.
+    ///
+    /// Mobile OSes tend to report connectivity changes in bursts, and handling every one of them
+    /// (clearing DNS caches, resetting routes) just delays reconnecting.
+    pub fn set_network_change_debounce(&self, window: Duration) {
+        self.network_change_debounce
+            .lock()
+            .expect("not poisoned")
+            .window = window;
+    }
 
     pub fn on_network_change(&self, now: Instant) {
         {
-            let mut most_recent_change_guard = self
-                .most_recent_network_change
-                .lock()
-                .expect("not poisoned");
-            if now.saturating_duration_since(*most_recent_change_guard)
-                < Self::NETWORK_CHANGE_DEBOUNCE
-            {
+            let mut debounce_guard = self.network_change_debounce.lock().expect("not poisoned");
+            if now.saturating_duration_since(debounce_guard.most_recent) < debounce_guard.window {
                 log::info!("ConnectionManager: on_network_change (debounced)");
                 return;
             }
-            *most_recent_change_guard = now;
+            debounce_guard.most_recent = now;
         }
         log::info!("ConnectionManager: on_network_change");
+        self.handle_network_change(now);
+    }
+
+    /// Like [`Self::on_network_change`], but never debounced.
+    ///
+    /// For when the user explicitly asks to retry, and in tests.
+    pub fn force_network_change(&self, now: Instant) {
+        {
+            let mut debounce_guard = self.network_change_debounce.lock().expect("not poisoned");
+            debounce_guard.most_recent = debounce_guard.most_recent.max(now);
+        }
+        log::info!("ConnectionManager: force_network_change");
+        self.handle_network_change(now);
+    }
+
+    /// Forgets everything learned about connecting on this network, for when the user asks to
+    /// start over.
+    ///
+    /// This goes further than [`Self::force_network_change`]: connect settings from
+    /// [`Self::set_connect_config`] are reverted to the suggested defaults, route failures and
+    /// any detected outage are forgotten, and DNS lookups in progress are abandoned. Proxy, IPv6,
+    /// censorship circumvention, and network type settings are kept.
+    ///
+    /// Connections already being made finish with the state they started with, but their outcomes
+    /// aren't recorded.
+    pub fn reset_connect_state(&self, now: Instant) -> ConnectStateResetSummary {
+        {
+            let mut debounce_guard = self.network_change_debounce.lock().expect("not poisoned");
+            debounce_guard.most_recent = debounce_guard.most_recent.max(now);
+        }
+        let dns_lookups_flushed = self.dns_resolver.flush();
+        let connect_state = self
+            .connect
+            .blocking_write()
+            .reset(SUGGESTED_CONNECT_CONFIG, now.into());
+        let summary = ConnectStateResetSummary {
+            at: now,
+            connect_state,
+            dns_lookups_flushed,
+        };
+        log::info!("ConnectionManager: reset connect state: {summary:?}");
+        *self.last_connect_state_reset.lock().expect("not poisoned") = Some(summary);
+
         self.network_change_event.fire();
+        self.net_events.fire(&NetEvent::NetworkChanged);
+        summary
+    }
+
+    /// What the most recent call to [`Self::reset_connect_state`] cleared, if there has been one.
+    pub fn last_connect_state_reset(&self) -> Option<ConnectStateResetSummary> {
+        *self.last_connect_state_reset.lock().expect("not poisoned")
+    }
+
+    fn handle_network_change(&self, now: Instant) {
+        self.network_change_event.fire();
+        self.transport_connector
+            .lock()
+            .expect("not poisoned")
+            .tls_session_cache()
+            .clear();
         self.connect.blocking_write().network_changed(now.into());
+        self.net_events.fire(&NetEvent::NetworkChanged);
     }
 }
 
 bridge_as_handle!(ConnectionManager);
 bridge_as_handle!(ConnectionProxyConfig);
+bridge_as_handle!(ConnectStateResetSummary);
 
 #[cfg(test)]
 mod test {
     use ::tokio; // otherwise ambiguous with the tokio submodule
     use assert_matches::assert_matches;
-    use libsignal_net::chat::ConnectError;
+    use libsignal_net::auth::Auth;
+    use libsignal_net::chat::fake::server::{CannedResponse, FakeChatServer};
+    use libsignal_net::chat::retry::SUGGESTED_RECONNECT_BACKOFF;
+    use libsignal_net::chat::server_requests::DisconnectCause;
+    use libsignal_net::chat::state::{ConnectionState, DisconnectReason};
+    use libsignal_net::chat::{ConnectError, FailurePhase};
+    use libsignal_net::connect_state::{InjectedFault, RouteInfo};
+    use libsignal_net::env::{PROXY_CONFIG_F_STAGING, PROXY_CONFIG_G};
+    use libsignal_net::infra::connection_manager::{
+        ConnectionAttemptOutcome, ConnectionManager as _, ErrorClass, ErrorClassifier,
+    };
+    use libsignal_net::infra::dns::lookup_result::LookupResult;
+    use libsignal_net::infra::errors::LogSafeDisplay;
+    use libsignal_net::infra::route::ConnectionRacing;
+    use libsignal_net::infra::ws::TransferTotals;
+    use libsignal_net::infra::{DnsSource, RouteType};
+    use libsignal_protocol::Timestamp;
     use test_case::test_case;
 
     use super::*;
-    use crate::net::chat::UnauthenticatedChatConnection;
+    use crate::net::chat::{
+        AuthenticatedChatConnection, BridgeChatConnection as _, ChatConnectionStateWatcher,
+        ChatListener, ServerMessageAck, UnauthenticatedChatConnection,
+    };
 
     #[test_case(Environment::Staging; "staging")]
     #[test_case(Environment::Prod; "prod")]
@@ -241,17 +813,227 @@ mod test {
         let _ = ConnectionManager::new(env, "test-user-agent");
     }
 
+    #[test]
+    fn endpoint_ip_hints_are_per_service() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_endpoint_ip_hints(
+            ServiceKind::Chat,
+            vec![
+                IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2)),
+            ],
+            Duration::from_secs(60),
+        );
+        assert_eq!(cm.endpoint_ip_hint_count(ServiceKind::Chat), 2);
+        assert_eq!(cm.endpoint_ip_hint_count(ServiceKind::Cdsi), 0);
+
+        cm.clear_endpoint_ip_hints(ServiceKind::Chat);
+        assert_eq!(cm.endpoint_ip_hint_count(ServiceKind::Chat), 0);
+    }
+
     // Normally we would write this test in the app languages, but it depends on timeouts.
     // Using a paused tokio runtime auto-advances time when there's no other work to be done.
     #[tokio::test(start_paused = true)]
     async fn cannot_connect_through_invalid_proxy() {
         let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
         cm.set_invalid_proxy();
+        let start = tokio::time::Instant::now();
         let err = UnauthenticatedChatConnection::connect(&cm)
             .await
             .map(|_| ())
             .expect_err("should fail to connect");
         assert_matches!(err, ConnectError::InvalidConnectionConfiguration);
+        // No connection attempts were made, so nothing waited on a timeout.
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cannot_look_up_through_invalid_proxy() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_invalid_proxy();
+        let auth = Auth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        let start = tokio::time::Instant::now();
+
+        let err = crate::net::cdsi::CdsiLookup::new(&cm, auth.clone(), Default::default())
+            .await
+            .map(|_| ())
+            .expect_err("should fail to connect");
+        assert_matches!(
+            err,
+            libsignal_net::cdsi::LookupError::ConnectTransport(
+                libsignal_net::infra::errors::TransportConnectError::InvalidConfiguration
+            )
+        );
+
+        let err = crate::net::cdsi::CdsiLookup::new_routes(&cm, auth, Default::default())
+            .await
+            .map(|_| ())
+            .expect_err("should fail to connect");
+        assert_matches!(
+            err,
+            libsignal_net::cdsi::LookupError::ConnectTransport(
+                libsignal_net::infra::errors::TransportConnectError::InvalidConfiguration
+            )
+        );
+
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_chat_connect_is_reported_to_state_watchers() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_invalid_proxy();
+        let watcher = ChatConnectionStateWatcher::new(&cm);
+        assert_eq!(
+            watcher.current(),
+            ConnectionState::Disconnected(DisconnectReason::NotStarted)
+        );
+
+        let auth = Auth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        let err = AuthenticatedChatConnection::connect(&cm, auth, false)
+            .await
+            .map(|_| ())
+            .expect_err("should fail to connect");
+        assert_matches!(err, ConnectError::InvalidConnectionConfiguration);
+
+        // The watcher only sees the latest state, not the intermediate "connecting" one.
+        assert_eq!(
+            watcher.next().await,
+            ConnectionState::Disconnected(DisconnectReason::ConnectFailed(
+                FailurePhase::BeforeTransport
+            ))
+        );
+        assert_eq!(
+            cm.chat_state.current(),
+            ConnectionState::Disconnected(DisconnectReason::ConnectFailed(
+                FailurePhase::BeforeTransport
+            ))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_chat_connect_resets_state() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        let auth = Auth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        // Poll the connect attempt once, then drop it.
+        tokio::time::timeout(
+            Duration::ZERO,
+            AuthenticatedChatConnection::connect(&cm, auth, false),
+        )
+        .await
+        .map(|_| ())
+        .expect_err("should not finish connecting");
+
+        assert_eq!(
+            cm.chat_state.current(),
+            ConnectionState::Disconnected(DisconnectReason::LocalDisconnect)
+        );
+    }
+
+    #[test]
+    fn chat_connection_quality_requires_connection() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        let round_trip = Arc::new(RoundTripEstimator::new());
+        round_trip.record_sample(Duration::from_millis(100));
+        cm.set_chat_round_trip_estimator(round_trip);
+        assert_eq!(cm.chat_connection_quality(), None);
+
+        let generation = cm.chat_state.connecting().connected(SystemTime::now());
+        assert_eq!(cm.chat_connection_quality(), Some(ConnectionQuality::Good));
+
+        cm.set_connection_quality_thresholds(ConnectionQualityThresholds {
+            good_below: Duration::from_millis(50),
+            bad_above: Duration::from_millis(80),
+        })
+        .expect("valid");
+        assert_eq!(cm.chat_connection_quality(), Some(ConnectionQuality::Bad));
+
+        // A newer connection starts with its own estimate.
+        let reconnected = Arc::new(RoundTripEstimator::new());
+        reconnected.record_sample(Duration::from_millis(10));
+        cm.set_chat_round_trip_estimator(reconnected);
+        assert_eq!(cm.chat_connection_quality(), Some(ConnectionQuality::Good));
+
+        assert_matches!(
+            cm.set_connection_quality_thresholds(ConnectionQualityThresholds {
+                good_below: Duration::from_millis(80),
+                bad_above: Duration::from_millis(50),
+            }),
+            Err(InvalidConnectionQualityThresholds)
+        );
+
+        cm.chat_state
+            .disconnected(generation, DisconnectReason::LocalDisconnect);
+        assert_eq!(cm.chat_connection_quality(), None);
+    }
+
+    #[test_case("org.signal.tls"; "signal TLS proxy")]
+    #[test_case("http"; "HTTP")]
+    #[test_case("https"; "HTTPS")]
+    #[test_case("socks4a"; "SOCKS4a")]
+    #[test_case("socks5h"; "SOCKS5h")]
+    fn set_proxy_is_passed_to_transport_connector(scheme: &str) {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        assert_matches!(cm.is_using_proxy(), Ok(false));
+
+        let proxy = ConnectionProxyConfig::from_parts(
+            scheme,
+            "proxy.example",
+            None,
+            None,
+            Default::default(),
+        )
+        .expect("valid");
+        let expected = format!("{proxy:?}");
+        cm.set_proxy(proxy);
+
+        assert_matches!(cm.is_using_proxy(), Ok(true));
+        assert_matches!(
+            cm.transport_connector.lock().expect("not poisoned").proxy(),
+            Ok(Some(proxy)) if format!("{proxy:?}") == expected
+        );
+
+        cm.clear_proxy();
+        assert_matches!(cm.is_using_proxy(), Ok(false));
+    }
+
+    #[test]
+    fn set_local_bind_address_is_checked_and_passed_to_transport_connector() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        let local_bind_address = |cm: &ConnectionManager| {
+            cm.transport_connector
+                .lock()
+                .expect("not poisoned")
+                .local_bind_address()
+        };
+
+        let loopback = IpAddr::from([127, 0, 0, 1]);
+        cm.set_local_bind_address(Some(loopback))
+            .expect("loopback is always available");
+        assert_eq!(local_bind_address(&cm), Some(loopback));
+
+        assert_matches!(
+            cm.set_local_bind_address(Some(IpAddr::from([224, 0, 0, 1]))),
+            Err(InvalidLocalBindAddress::NotUnicast)
+        );
+        assert_matches!(
+            cm.set_local_bind_address(Some(IpAddr::from([0, 0, 0, 0]))),
+            Err(InvalidLocalBindAddress::NotUnicast)
+        );
+        // A rejected address leaves the previous one in place.
+        assert_eq!(local_bind_address(&cm), Some(loopback));
+
+        cm.set_local_bind_address(None).expect("can always clear");
+        assert_eq!(local_bind_address(&cm), None);
     }
 
     #[test]
@@ -290,4 +1072,509 @@ mod test {
         cm.on_network_change(start + ConnectionManager::NETWORK_CHANGE_DEBOUNCE * 4);
         assert_eq!(3, fire_count.load(std::sync::atomic::Ordering::SeqCst));
     }
+
+    #[test]
+    fn network_change_burst_is_coalesced() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_network_change_debounce(Duration::from_secs(2));
+
+        let fire_count = Arc::new(std::sync::atomic::AtomicU8::new(0));
+        let _subscription = {
+            let fire_count = fire_count.clone();
+            cm.network_change_event.subscribe(Box::new(move || {
+                _ = fire_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }))
+        };
+
+        // cellular -> none -> wifi, all within a second.
+        let start = Instant::now() + Duration::from_secs(60);
+        for offset_ms in [0, 300, 900] {
+            cm.on_network_change(start + Duration::from_millis(offset_ms));
+        }
+        assert_eq!(1, fire_count.load(std::sync::atomic::Ordering::SeqCst));
+
+        // Past the default window but still inside the configured one.
+        cm.on_network_change(start + Duration::from_millis(1500));
+        assert_eq!(1, fire_count.load(std::sync::atomic::Ordering::SeqCst));
+
+        cm.force_network_change(start + Duration::from_millis(1600));
+        assert_eq!(2, fire_count.load(std::sync::atomic::Ordering::SeqCst));
+
+        // Forcing a change restarts the window.
+        cm.on_network_change(start + Duration::from_millis(3000));
+        assert_eq!(2, fire_count.load(std::sync::atomic::Ordering::SeqCst));
+        cm.on_network_change(start + Duration::from_millis(3600));
+        assert_eq!(3, fire_count.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn set_connect_config_applies_to_connect_state() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_connect_config(ConnectConfigOverrides {
+            max_concurrent_attempts: 1,
+            ..Default::default()
+        })
+        .expect("valid");
+        assert_eq!(
+            cm.connect
+                .blocking_read()
+                .connection_racing
+                .max_concurrent_attempts
+                .get(),
+            1
+        );
+
+        assert_matches!(
+            cm.set_connect_config(ConnectConfigOverrides {
+                connect_timeout: Duration::ZERO,
+                ..Default::default()
+            }),
+            Err(InvalidConnectConfig::ZeroConnectTimeout)
+        );
+    }
+
+    #[test]
+    fn reset_connect_state_reverts_config_and_reports_network_change() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_ipv6_enabled(false);
+        cm.set_connect_config(ConnectConfigOverrides {
+            max_concurrent_attempts: 1,
+            ..Default::default()
+        })
+        .expect("valid");
+        assert_eq!(cm.last_connect_state_reset(), None);
+
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let _subscription = {
+            let events = events.clone();
+            cm.subscribe_to_net_events(Box::new(move |event| {
+                events.lock().expect("not poisoned").push(*event);
+            }))
+        };
+
+        let now = Instant::now();
+        let summary = cm.reset_connect_state(now);
+        assert_eq!(
+            summary,
+            ConnectStateResetSummary {
+                at: now,
+                connect_state: ConnectStateReset::default(),
+                dns_lookups_flushed: 0,
+            }
+        );
+        assert_eq!(cm.last_connect_state_reset(), Some(summary));
+        assert_eq!(
+            *events.lock().expect("not poisoned"),
+            [NetEvent::NetworkChanged]
+        );
+
+        let connect = cm.connect.blocking_read();
+        assert_eq!(
+            connect.connection_racing.max_concurrent_attempts,
+            ConnectionRacing::default().max_concurrent_attempts
+        );
+        // IPv6 settings are kept, since the DNS resolver and transport connector share them.
+        assert!(!connect.route_resolver.allow_ipv6);
+    }
+
+    #[test]
+    fn net_events_are_published() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let _subscription = {
+            let events = events.clone();
+            cm.subscribe_to_net_events(Box::new(move |event| {
+                events.lock().expect("not poisoned").push(*event);
+            }))
+        };
+
+        cm.set_invalid_proxy();
+        cm.clear_proxy();
+        cm.set_censorship_circumvention_enabled(true);
+        let start = Instant::now() + ConnectionManager::NETWORK_CHANGE_DEBOUNCE * 10;
+        cm.on_network_change(start);
+        // Debounced, so not published.
+        cm.on_network_change(start);
+
+        assert_eq!(
+            *events.lock().expect("not poisoned"),
+            [
+                NetEvent::ProxyChanged,
+                NetEvent::ProxyChanged,
+                NetEvent::EndpointsRebuilt,
+                NetEvent::NetworkChanged,
+            ]
+        );
+    }
+
+    #[test]
+    fn endpoints_are_only_rebuilt_as_needed() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        let current = || cm.endpoints.lock().expect("not poisoned").clone();
+        let original = current();
+
+        // Only CDSI's timeouts depend on the network type.
+        cm.set_network_type(NetworkType::Cellular);
+        let after_network_type = current();
+        assert!(Arc::ptr_eq(&original.chat, &after_network_type.chat));
+        assert!(!Arc::ptr_eq(&original.cdsi, &after_network_type.cdsi));
+        assert_eq!(cm.endpoints_version(), original.version + 1);
+
+        // Nothing changed, so nothing is rebuilt.
+        cm.set_censorship_circumvention_enabled(false);
+        let unchanged = current();
+        assert!(Arc::ptr_eq(&after_network_type.chat, &unchanged.chat));
+        assert!(Arc::ptr_eq(&after_network_type.cdsi, &unchanged.cdsi));
+        assert_eq!(cm.endpoints_version(), after_network_type.version);
+
+        // Every service's routes depend on whether fallbacks are enabled.
+        cm.set_censorship_circumvention_enabled(true);
+        let with_fallbacks = current();
+        assert!(!Arc::ptr_eq(&unchanged.chat, &with_fallbacks.chat));
+        assert!(!Arc::ptr_eq(&unchanged.cdsi, &with_fallbacks.cdsi));
+        assert_eq!(cm.endpoints_version(), unchanged.version + 1);
+        assert_eq!(with_fallbacks.network_type, NetworkType::Cellular);
+    }
+
+    /// Fails a connection attempt, fatally or not.
+    #[derive(Debug)]
+    struct RouteFailed {
+        fatal: bool,
+    }
+
+    impl std::fmt::Display for RouteFailed {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "route failed (fatal: {})", self.fatal)
+        }
+    }
+
+    impl LogSafeDisplay for RouteFailed {}
+
+    impl ErrorClassifier for RouteFailed {
+        fn classify(&self) -> ErrorClass {
+            if self.fatal {
+                ErrorClass::Fatal
+            } else {
+                ErrorClass::Intermittent
+            }
+        }
+    }
+
+    #[test]
+    fn route_cooldowns_survive_endpoint_rebuilds() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("can build runtime");
+        // Fails every chat route, returning the outcome and the routes that were tried.
+        let connect_chat = |fatal: bool| {
+            let chat = cm.endpoints.lock().expect("not poisoned").chat.clone();
+            let attempted = std::sync::Mutex::new(vec![]);
+            let outcome = runtime.block_on(chat.manager.connect_or_wait(|params| {
+                attempted
+                    .lock()
+                    .expect("not poisoned")
+                    .push(params.route_type);
+                std::future::ready(Err::<(), _>(RouteFailed { fatal }))
+            }));
+            (outcome, attempted.into_inner().expect("not poisoned"))
+        };
+
+        // Without fallbacks, the direct route is the only one, and it's retried until it's put in
+        // cooldown.
+        let (outcome, attempted) = connect_chat(false);
+        assert_matches!(outcome, ConnectionAttemptOutcome::WaitUntil(_));
+        assert!(attempted.iter().all(|route| *route == RouteType::Direct));
+
+        // The rebuilt routes skip the direct route while it's still in cooldown.
+        cm.set_censorship_circumvention_enabled(true);
+        let (outcome, attempted) = connect_chat(true);
+        assert_matches!(
+            outcome,
+            ConnectionAttemptOutcome::Attempted(Err(RouteFailed { fatal: true }))
+        );
+        assert!(!attempted.is_empty());
+        assert!(!attempted.contains(&RouteType::Direct), "{attempted:?}");
+
+        // A network change still ends the cooldown.
+        cm.force_network_change(Instant::now());
+        let (_outcome, attempted) = connect_chat(true);
+        assert_eq!(attempted, [RouteType::Direct]);
+    }
+
+    #[test]
+    fn chat_falls_back_to_fronted_routes_when_direct_fails() {
+        let mut cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        // Every chat hostname, direct and fronted, resolves without a lookup, so the only thing
+        // that decides which routes are tried is the fault injector.
+        let hostnames = [PROXY_CONFIG_F_STAGING, PROXY_CONFIG_G]
+            .iter()
+            .flat_map(|config| config.hostnames())
+            .copied()
+            .chain([cm.env.chat_domain_config.connect.hostname])
+            .map(|hostname| {
+                let addr = std::net::Ipv4Addr::new(192, 0, 2, 1);
+                (
+                    hostname,
+                    LookupResult::new(DnsSource::Static, vec![addr], vec![]),
+                )
+            })
+            .collect();
+        cm.dns_resolver = DnsResolver::new_from_static_map(hostnames);
+        cm.set_censorship_circumvention_enabled(true);
+
+        // The setters take the connect state's lock synchronously, so they have to be called
+        // before entering the runtime.
+        let attempted_kinds = Arc::new(std::sync::Mutex::new(vec![]));
+        cm.set_route_fault_injector(Some(Arc::new({
+            let attempted_kinds = attempted_kinds.clone();
+            move |info: &RouteInfo| {
+                let kind = info.route_kind();
+                attempted_kinds.lock().expect("not poisoned").push(kind);
+                Some(match kind {
+                    "direct" => InjectedFault::TcpRefused,
+                    _ => InjectedFault::TlsFailed,
+                })
+            }
+        })));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .expect("can build runtime");
+        let err = runtime
+            .block_on(UnauthenticatedChatConnection::connect(&cm))
+            .map(|_| ())
+            .expect_err("every route fails");
+        assert_matches!(err, ConnectError::AllAttemptsFailed { .. });
+
+        let attempted_kinds = attempted_kinds.lock().expect("not poisoned");
+        let first_fronted = attempted_kinds
+            .iter()
+            .position(|kind| *kind == "domain_fronted")
+            .expect("tried a fronted route");
+        assert_ne!(first_fronted, 0, "{attempted_kinds:?}");
+        assert!(
+            attempted_kinds[..first_fronted]
+                .iter()
+                .all(|kind| *kind == "direct"),
+            "{attempted_kinds:?}"
+        );
+        assert!(
+            attempted_kinds[first_fronted..]
+                .iter()
+                .all(|kind| *kind == "domain_fronted"),
+            "{attempted_kinds:?}"
+        );
+    }
+
+    #[test]
+    fn set_network_type_is_not_a_network_change() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+
+        let fire_count = Arc::new(std::sync::atomic::AtomicU8::new(0));
+        let _subscription = {
+            let fire_count = fire_count.clone();
+            cm.network_change_event.subscribe(Box::new(move || {
+                _ = fire_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }))
+        };
+
+        cm.set_network_type(NetworkType::Cellular);
+        assert_eq!(
+            cm.connect.blocking_read().network_type,
+            NetworkType::Cellular
+        );
+        assert_eq!(0, fire_count.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    /// Records each interruption and state change it's told about.
+    #[derive(Default)]
+    struct InterruptionRecorder {
+        interruptions: Arc<std::sync::Mutex<Vec<&'static str>>>,
+        states: Arc<std::sync::Mutex<Vec<ConnectionState>>>,
+    }
+
+    impl ChatListener for InterruptionRecorder {
+        fn received_incoming_message(
+            &mut self,
+            _envelope: Vec<u8>,
+            _timestamp: Timestamp,
+            _ack: ServerMessageAck,
+        ) {
+        }
+        fn received_queue_empty(&mut self) {}
+        fn received_alerts(&mut self, _alerts: Vec<String>) {}
+        fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
+            self.interruptions
+                .lock()
+                .expect("not poisoned")
+                .push(match disconnect_cause {
+                    DisconnectCause::LocalDisconnect => "local",
+                    DisconnectCause::Error(_) => "error",
+                })
+        }
+        fn connection_state_changed(&mut self, state: ConnectionState) {
+            self.states.lock().expect("not poisoned").push(state)
+        }
+    }
+
+    #[tokio::test]
+    async fn chat_connects_to_fake_server() {
+        let server = FakeChatServer::new();
+        server.respond(
+            "/v1/greeting",
+            CannedResponse::status(http::StatusCode::NO_CONTENT),
+        );
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_fake_chat_server(Some(server.clone()));
+
+        let chat = UnauthenticatedChatConnection::connect(&cm)
+            .await
+            .expect("connected");
+        let chat = tokio::task::spawn_blocking(move || {
+            chat.init_listener(Box::new(InterruptionRecorder::default()));
+            chat
+        })
+        .await
+        .expect("no panic");
+
+        let request = libsignal_net::chat::Request::builder()
+            .path("/v1/greeting")
+            .expect("valid path")
+            .build()
+            .expect("valid request");
+        let response = chat
+            .send(request, Duration::from_secs(5))
+            .await
+            .expect("response");
+        assert_eq!(response.status, http::StatusCode::NO_CONTENT);
+        assert_matches!(&server.received_requests()[..], [request] => {
+            assert_eq!(request.path(), "/v1/greeting");
+        });
+    }
+
+    #[tokio::test]
+    async fn data_usage_counts_chat_traffic() {
+        let server = FakeChatServer::new();
+        server.respond(
+            "/v1/greeting",
+            CannedResponse::status(http::StatusCode::NO_CONTENT),
+        );
+        server.respond(
+            "/v1/key-transparency/distinguished",
+            CannedResponse::status(http::StatusCode::OK).with_body(b"tree head".as_slice()),
+        );
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_fake_chat_server(Some(server));
+
+        let chat = UnauthenticatedChatConnection::connect(&cm)
+            .await
+            .expect("connected");
+        let chat = tokio::task::spawn_blocking(move || {
+            chat.init_listener(Box::new(InterruptionRecorder::default()));
+            chat
+        })
+        .await
+        .expect("no panic");
+
+        for path in ["/v1/greeting", "/v1/key-transparency/distinguished"] {
+            let request = libsignal_net::chat::Request::builder()
+                .path(path)
+                .expect("valid path")
+                .build()
+                .expect("valid request");
+            chat.send(request, Duration::from_secs(5))
+                .await
+                .expect("response");
+        }
+
+        let usage = cm.data_usage();
+        assert_eq!(
+            usage.key_transparency,
+            TransferTotals {
+                sent: 0,
+                received: b"tree head".len() as u64,
+            }
+        );
+        // Chat's totals include the whole websocket messages for both
+        // requests, key transparency included.
+        assert!(usage.chat.sent > 0);
+        assert!(usage.chat.received > usage.key_transparency.received);
+        assert_eq!(usage.cdsi, TransferTotals::default());
+
+        cm.reset_data_usage();
+        assert_eq!(cm.data_usage(), DataUsageSnapshot::default());
+    }
+
+    fn fake_auth() -> Auth {
+        Auth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn auto_reconnect_gives_up_on_fatal_connect_error() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        cm.set_invalid_proxy();
+        let listener = InterruptionRecorder::default();
+        let interruptions = listener.interruptions.clone();
+        let states = listener.states.clone();
+        let (chat, remote) = AuthenticatedChatConnection::new_fake(
+            tokio::runtime::Handle::current(),
+            Box::new(listener),
+            [],
+        );
+
+        let (result, ()) = tokio::join!(
+            chat.run_auto_reconnect(&cm, fake_auth(), false, SUGGESTED_RECONNECT_BACKOFF),
+            async { drop(remote) },
+        );
+        assert_matches!(result, Err(ConnectError::InvalidConnectionConfiguration));
+        // The lost connection is only reported once auto-reconnect gives up.
+        assert_eq!(*interruptions.lock().expect("not poisoned"), ["error"]);
+        assert_matches!(
+            cm.chat_state.current(),
+            ConnectionState::Disconnected(DisconnectReason::ConnectFailed(_))
+        );
+        // Meanwhile, the listener saw the failed attempt.
+        assert_matches!(
+            states.lock().expect("not poisoned").last(),
+            Some(ConnectionState::Disconnected(
+                DisconnectReason::ConnectFailed(_)
+            ))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn auto_reconnect_can_be_stopped() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        let listener = InterruptionRecorder::default();
+        let interruptions = listener.interruptions.clone();
+        let (chat, remote) = AuthenticatedChatConnection::new_fake(
+            tokio::runtime::Handle::current(),
+            Box::new(listener),
+            [],
+        );
+
+        let (result, ()) = tokio::join!(
+            chat.run_auto_reconnect(&cm, fake_auth(), false, SUGGESTED_RECONNECT_BACKOFF),
+            async { chat.stop_auto_reconnect() },
+        );
+        assert_matches!(result, Ok(()));
+
+        // Stopping is permanent.
+        chat.run_auto_reconnect(&cm, fake_auth(), false, SUGGESTED_RECONNECT_BACKOFF)
+            .await
+            .expect("does nothing");
+
+        // With auto-reconnect off, losing the connection is reported right away.
+        drop(remote);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(*interruptions.lock().expect("not poisoned"), ["error"]);
+    }
 }
@@ -3,13 +3,19 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::num::NonZeroUsize;
+
 use http::HeaderName;
 use libsignal_net::auth::Auth;
-use libsignal_net::cdsi::{self, CdsiConnection, ClientResponseCollector, Token};
+use libsignal_net::cdsi::{
+    self, CdsiConnection, ClientResponseCollector, LookupResponseBatches, Token,
+};
 use libsignal_net::infra::errors::RetryLater;
 use libsignal_net::infra::route::{DirectOrProxyProvider, RouteProviderExt};
 use libsignal_net::infra::tcp_ssl::InvalidProxyConfig;
 use libsignal_net::infra::AsHttpHeader as _;
+use libsignal_net::metrics::ServiceKind;
+use libsignal_net::ws::RateLimitChallenge;
 
 use crate::net::ConnectionManager;
 use crate::*;
@@ -26,6 +32,8 @@ pub enum CdsiError {
     InvalidResponse,
     /// Retry later
     RateLimited(RetryLater),
+    /// Rate limit challenge required
+    RateLimitChallenge(RateLimitChallenge),
     /// Failed to parse the response from the server
     ParseError,
     /// Request token was invalid
@@ -47,9 +55,17 @@ impl LookupRequest {
 
 bridge_as_handle!(LookupRequest);
 
+fn invalid_proxy_error(InvalidProxyConfig: InvalidProxyConfig) -> cdsi::LookupError {
+    cdsi::LookupError::ConnectTransport(
+        libsignal_net::infra::errors::TransportConnectError::InvalidConfiguration,
+    )
+}
+
 pub struct CdsiLookup {
     pub token: Token,
     remaining: std::sync::Mutex<Option<ClientResponseCollector>>,
+    /// Set once results start being read with [`Self::next_batch`].
+    batches: tokio::sync::Mutex<Option<LookupResponseBatches>>,
 }
 
 impl CdsiLookup {
@@ -63,17 +79,28 @@ impl CdsiLookup {
             .lock()
             .expect("not poisoned")
             .clone();
-        let endpoints = connection_manager
+        // Fail fast instead of letting every connection attempt fail the same way.
+        transport_connector.proxy().map_err(invalid_proxy_error)?;
+        let endpoint = connection_manager
             .endpoints
             .lock()
             .expect("not poisoned")
+            .cdsi
             .clone();
-        let connected = CdsiConnection::connect(&endpoints.cdsi, transport_connector, auth).await?;
+        let connected = CdsiConnection::connect_recording_events(
+            &endpoint,
+            transport_connector,
+            auth,
+            &connection_manager.network_events,
+        )
+        .await?;
+        connection_manager.record_attestation(ServiceKind::Cdsi, connected.attested_measurement());
         let (token, remaining_response) = connected.send_request(request).await?;
 
         Ok(CdsiLookup {
             token,
             remaining: std::sync::Mutex::new(Some(remaining_response)),
+            batches: Default::default(),
         })
     }
 
@@ -95,11 +122,7 @@ impl CdsiLookup {
         let proxy_config: Option<libsignal_net::infra::route::ConnectionProxyConfig> =
             (&*transport_connector.lock().expect("not poisoned"))
                 .try_into()
-                .map_err(|InvalidProxyConfig| {
-                    cdsi::LookupError::ConnectTransport(
-                        libsignal_net::infra::errors::TransportConnectError::InvalidConfiguration,
-                    )
-                })?;
+                .map_err(invalid_proxy_error)?;
 
         let (ws_config, enable_domain_fronting) = {
             let guard = endpoints.lock().expect("not poisoned");
@@ -129,17 +152,41 @@ impl CdsiLookup {
             auth,
         )
         .await?;
+        connection_manager.record_attestation(ServiceKind::Cdsi, connected.attested_measurement());
         let (token, remaining_response) = connected.send_request(request).await?;
 
         Ok(CdsiLookup {
             token,
             remaining: std::sync::Mutex::new(Some(remaining_response)),
+            batches: Default::default(),
         })
     }
 
     pub fn take_remaining(&self) -> Option<ClientResponseCollector> {
         self.remaining.lock().expect("not poisoned").take()
     }
+
+    /// Reads up to `max_entries` more results, as an alternative to collecting
+    /// them all at once with [`Self::take_remaining`].
+    ///
+    /// An empty batch marks the end of the results; only then is
+    /// `debug_permits_used` final.
+    pub async fn next_batch(
+        &self,
+        max_entries: NonZeroUsize,
+    ) -> Result<cdsi::LookupResponse, cdsi::LookupError> {
+        let mut batches = self.batches.lock().await;
+        let batches = batches.get_or_insert_with(|| {
+            self.take_remaining()
+                .expect("not completed yet")
+                .into_batches()
+        });
+        let records = batches.next_batch(max_entries).await?;
+        Ok(cdsi::LookupResponse {
+            records,
+            debug_permits_used: batches.debug_permits_used(),
+        })
+    }
 }
 
 bridge_as_handle!(CdsiLookup);
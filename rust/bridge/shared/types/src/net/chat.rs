@@ -4,32 +4,43 @@
 //
 
 use std::future::Future;
+use std::ops::Deref;
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 use atomic_take::AtomicTake;
-use futures_util::FutureExt as _;
+use futures_util::{FutureExt as _, TryFutureExt as _};
 use http::status::InvalidStatusCode;
 use http::uri::{InvalidUri, PathAndQuery};
 use http::{HeaderMap, HeaderName, HeaderValue};
 use libsignal_net::auth::Auth;
 use libsignal_net::chat::fake::FakeChatRemote;
+use libsignal_net::chat::retry::ReconnectBackoff;
 use libsignal_net::chat::server_requests::DisconnectCause;
+use libsignal_net::chat::state::{
+    ConnectionGeneration, ConnectionState, ConnectionStateMachine, ConnectionStateReceiver,
+    DisconnectReason,
+};
 use libsignal_net::chat::{
     self, ChatConnection, ConnectError, ConnectionInfo, DebugInfo as ChatServiceDebugInfo, Request,
     Response as ChatResponse, SendError,
 };
+use libsignal_net::connect_state::{ConnectProgress, ConnectStage};
 use libsignal_net::infra::route::{
     ConnectionProxyConfig, DirectOrProxyProvider, RouteProvider, RouteProviderExt,
     UnresolvedHttpsServiceRoute,
 };
 use libsignal_net::infra::tcp_ssl::InvalidProxyConfig;
+use libsignal_net::infra::utils::Flight;
 use libsignal_net::infra::EnableDomainFronting;
+use libsignal_net::server_time::ServerTimeEstimator;
 use libsignal_protocol::Timestamp;
 use static_assertions::assert_impl_all;
 
-use crate::net::ConnectionManager;
+use crate::net::{ConnectionManager, NetEvent};
 use crate::*;
 
 pub type ChatConnectionInfo = ConnectionInfo;
@@ -41,7 +52,9 @@ pub struct UnauthenticatedChatConnection {
     ///
     /// See [`AuthenticatedChatConnection::inner`] for rationale around lack of
     /// reader/writer contention.
-    inner: tokio::sync::RwLock<MaybeChatConnection>,
+    inner: ChatConnectionHandle,
+    /// Updated with the server's timestamp from every response.
+    server_time: Arc<ServerTimeEstimator>,
 }
 bridge_as_handle!(UnauthenticatedChatConnection);
 impl UnwindSafe for UnauthenticatedChatConnection {}
@@ -55,35 +68,181 @@ pub struct AuthenticatedChatConnection {
     /// `ChatConnection`. The lock will only be held in writer mode once, when
     /// finishing construction, and after that will be held in read mode, so
     /// there won't be any contention.
-    inner: tokio::sync::RwLock<MaybeChatConnection>,
+    ///
+    /// It's shared with any other handles whose connection attempts were
+    /// joined with this one's; see [`establish_or_join_chat_connection`].
+    inner: ChatConnectionHandle,
+    /// Where to report changes in the connection's state, if anywhere.
+    state: Option<Arc<ConnectionStateMachine>>,
+    /// Updated with the server's timestamp from every response.
+    server_time: Arc<ServerTimeEstimator>,
 }
 bridge_as_handle!(AuthenticatedChatConnection);
 impl UnwindSafe for AuthenticatedChatConnection {}
 impl RefUnwindSafe for AuthenticatedChatConnection {}
 
-enum MaybeChatConnection {
+pub(super) enum MaybeChatConnection {
     Running(ChatConnection),
     WaitingForListener(
         tokio::runtime::Handle,
         tokio::sync::Mutex<chat::PendingChatConnection>,
     ),
+    /// Will connect to the server set by [`ConnectionManager::set_fake_chat_server`] once there's
+    /// a listener.
+    #[cfg(feature = "test-util")]
+    WaitingForFakeListener(
+        tokio::runtime::Handle,
+        libsignal_net::chat::fake::server::FakeChatServer,
+        Arc<libsignal_net::data_usage::DataUsage>,
+    ),
     TemporarilyEvicted,
 }
 
 assert_impl_all!(MaybeChatConnection: Send, Sync);
 
+/// A chat connection, shared by every handle whose connection attempts were
+/// joined.
+pub(super) struct SharedChatConnection {
+    connection: tokio::sync::RwLock<MaybeChatConnection>,
+    /// The generation the connection's state is reported under, if it's
+    /// reported anywhere.
+    ///
+    /// Replaced along with the connection when auto-reconnect swaps in a new
+    /// one.
+    generation: std::sync::Mutex<Option<ConnectionGeneration>>,
+    /// How many [`ChatConnectionHandle`]s still hold on to the connection.
+    handles: AtomicUsize,
+    /// Coordinates [`AuthenticatedChatConnection::run_auto_reconnect`] with
+    /// the connection's listener.
+    ///
+    /// Kept with the connection rather than any one handle, since only one
+    /// handle sets the listener but any of them can start auto-reconnect.
+    auto_reconnect: Arc<AutoReconnect>,
+}
+
+impl SharedChatConnection {
+    fn new(connection: MaybeChatConnection, generation: Option<ConnectionGeneration>) -> Self {
+        Self {
+            connection: connection.into(),
+            generation: generation.into(),
+            handles: AtomicUsize::new(0),
+            auto_reconnect: Default::default(),
+        }
+    }
+
+    fn generation(&self) -> Option<ConnectionGeneration> {
+        *self.generation.lock().expect("not poisoned")
+    }
+}
+
+type SharedMaybeChatConnection = Arc<SharedChatConnection>;
+
+/// One handle's hold on a [`SharedChatConnection`].
+///
+/// The connection is only disconnected once every handle has let go of it,
+/// by disconnecting or by being dropped.
+pub(super) struct ChatConnectionHandle {
+    shared: SharedMaybeChatConnection,
+    released: AtomicBool,
+}
+
+impl ChatConnectionHandle {
+    fn new(shared: SharedMaybeChatConnection) -> Self {
+        shared.handles.fetch_add(1, Ordering::SeqCst);
+        Self {
+            shared,
+            released: AtomicBool::new(false),
+        }
+    }
+
+    /// Lets go of the connection, if this handle hasn't already.
+    ///
+    /// Returns `true` if this was the last handle holding on to it.
+    fn release(&self) -> bool {
+        if self.released.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        self.shared.handles.fetch_sub(1, Ordering::SeqCst) == 1
+    }
+}
+
+impl Deref for ChatConnectionHandle {
+    type Target = SharedChatConnection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.shared
+    }
+}
+
+impl Drop for ChatConnectionHandle {
+    fn drop(&mut self) {
+        // Dropping the last reference to the connection closes it anyway.
+        let _ = self.release();
+    }
+}
+
+/// Identifies chat connection attempts that can share a connection: either
+/// unauthenticated (`None`), or authenticated with the same credentials and
+/// story setting.
+pub(super) type ChatConnectKey = Option<ChatConnectCredentials>;
+
+/// The part of [`ChatConnectKey`] for authenticated connections.
+///
+/// The password is included so that an attempt with stale credentials never
+/// hands out a connection made with fresh ones, or the other way around.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(super) struct ChatConnectCredentials {
+    username: String,
+    password: String,
+    receive_stories: bool,
+}
+
+/// The outcome of a chat connection attempt, as handed to callers that joined
+/// it.
+pub(super) type SharedChatConnectResult = Result<SharedMaybeChatConnection, Arc<ConnectError>>;
+
 impl UnauthenticatedChatConnection {
     pub async fn connect(connection_manager: &ConnectionManager) -> Result<Self, ConnectError> {
-        let inner = establish_chat_connection("unauthenticated", connection_manager, None).await?;
+        Self::connect_with_listener(connection_manager, |_| {}).await
+    }
+
+    /// Like [`UnauthenticatedChatConnection::connect`], but reports the stages
+    /// of establishing the connection to `progress_listener`.
+    ///
+    /// Each stage is reported as soon as any route reaches it, so a failed
+    /// connection still reports how far it got. Nothing is reported if this
+    /// joins an attempt that was already in progress.
+    pub async fn connect_with_listener(
+        connection_manager: &ConnectionManager,
+        progress_listener: impl FnMut(ConnectProgress) + Send,
+    ) -> Result<Self, ConnectError> {
+        let inner = establish_or_join_chat_connection(
+            "unauthenticated",
+            connection_manager,
+            None,
+            establish_chat_connection(
+                "unauthenticated",
+                connection_manager,
+                None,
+                progress_listener,
+            )
+            .map_ok(|pending| (pending, None)),
+        )
+        .await?;
         log::info!("connected unauthenticated chat");
         Ok(Self {
-            inner: MaybeChatConnection::WaitingForListener(
-                tokio::runtime::Handle::current(),
-                inner.into(),
-            )
-            .into(),
+            inner: ChatConnectionHandle::new(inner),
+            server_time: connection_manager.server_time.clone(),
         })
     }
+
+    /// The estimate of the server's clock this connection contributes to.
+    ///
+    /// Not used to check responses unless a caller opts in with
+    /// [`BoundedServerClock`](libsignal_net::server_time::BoundedServerClock).
+    pub fn server_time(&self) -> &Arc<ServerTimeEstimator> {
+        &self.server_time
+    }
 }
 impl AuthenticatedChatConnection {
     pub async fn connect(
@@ -91,24 +250,236 @@ impl AuthenticatedChatConnection {
         auth: Auth,
         receive_stories: bool,
     ) -> Result<Self, ConnectError> {
-        let inner = establish_chat_connection(
-            "authenticated",
+        Self::connect_with_listener(connection_manager, auth, receive_stories, |_| {}).await
+    }
+
+    /// Like [`AuthenticatedChatConnection::connect`], but reports the stages of
+    /// establishing the connection to `progress_listener`, as
+    /// [`UnauthenticatedChatConnection::connect_with_listener`] does.
+    ///
+    /// The server checks the credentials during the websocket upgrade, so
+    /// [`ConnectStage::WebSocketOpened`] means they were accepted.
+    pub async fn connect_with_listener(
+        connection_manager: &ConnectionManager,
+        auth: Auth,
+        receive_stories: bool,
+        progress_listener: impl FnMut(ConnectProgress) + Send,
+    ) -> Result<Self, ConnectError> {
+        let key = Some(ChatConnectCredentials {
+            username: auth.username.clone(),
+            password: auth.password.clone(),
+            receive_stories,
+        });
+        let establish = establish_authenticated_chat_connection(
             connection_manager,
-            Some(chat::AuthenticatedChatHeaders {
-                auth,
-                receive_stories: receive_stories.into(),
-            }),
+            auth,
+            receive_stories,
+            progress_listener,
         )
-        .await?;
+        .map_ok(|(pending, generation)| (pending, Some(generation)));
+        let inner =
+            establish_or_join_chat_connection("authenticated", connection_manager, key, establish)
+                .await?;
         Ok(Self {
-            inner: MaybeChatConnection::WaitingForListener(
-                tokio::runtime::Handle::current(),
-                inner.into(),
-            )
-            .into(),
+            inner: ChatConnectionHandle::new(inner),
+            state: Some(connection_manager.chat_state.clone()),
+            server_time: connection_manager.server_time.clone(),
         })
     }
 
+    /// Keeps this connection up by reconnecting whenever it's lost, until
+    /// auto-reconnect is turned off.
+    ///
+    /// Each new connection uses the same `auth` and `receive_stories` as the
+    /// original and is swapped in behind this handle, delivering events to the
+    /// same listener. Failed attempts are spaced out according to `backoff`, or
+    /// longer if the server asked for that with [`ConnectError::RetryLater`] or
+    /// if `connection_manager` has detected that the network is down. A
+    /// [network change](ConnectionManager::on_network_change) triggers the next
+    /// attempt immediately.
+    ///
+    /// While this is running, lost connections aren't reported to the
+    /// listener's [`ChatListener::connection_interrupted`]; instead, each
+    /// transition of `connection_manager`'s [`ConnectionState`] is passed to
+    /// [`ChatListener::connection_state_changed`]. The listener must already
+    /// have been set.
+    ///
+    /// Auto-reconnect belongs to the connection, not this handle: if other
+    /// handles share the connection (see [`establish_or_join_chat_connection`]),
+    /// the replacement connection is shared with them too, and only one call
+    /// for the connection runs at a time; any others return `Ok` right away.
+    ///
+    /// Returns `Ok` once [`Self::stop_auto_reconnect`] is called or the
+    /// connection is [disconnected](BridgeChatConnection::disconnect) by its
+    /// last handle, and returns the error if reconnecting fails in a way
+    /// retrying won't fix ([`ConnectError::AppExpired`],
+    /// [`ConnectError::DeviceDeregistered`], or
+    /// [`ConnectError::InvalidConnectionConfiguration`]). Either way, if the
+    /// connection is down at that point, the listener is then told how it was
+    /// lost.
+    pub async fn run_auto_reconnect(
+        &self,
+        connection_manager: &ConnectionManager,
+        auth: Auth,
+        receive_stories: bool,
+        backoff: ReconnectBackoff,
+    ) -> Result<(), ConnectError> {
+        let auto_reconnect = &self.inner.auto_reconnect;
+        if auto_reconnect.stopped.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if auto_reconnect.active.swap(true, Ordering::SeqCst) {
+            log::info!("auto-reconnect is already running for this connection");
+            return Ok(());
+        }
+        let mut states = connection_manager.chat_state.subscribe();
+        let forward_state_changes = async {
+            while states.changed().await.is_ok() {
+                let state = states.borrow_and_update().clone();
+                auto_reconnect.report_state(state);
+            }
+            std::future::pending::<std::convert::Infallible>().await
+        };
+        let result = tokio::select! {
+            result = self.reconnect_until_stopped(
+                connection_manager,
+                auth,
+                receive_stories,
+                backoff,
+            ) => result,
+            never = forward_state_changes => match never {},
+        };
+        // Don't miss the state the last attempt left behind.
+        if states.has_changed().unwrap_or(false) {
+            let state = states.borrow_and_update().clone();
+            auto_reconnect.report_state(state);
+        }
+        auto_reconnect.active.store(false, Ordering::SeqCst);
+        auto_reconnect.report_pending_interruption();
+        result
+    }
+
+    /// Turns off auto-reconnect for good, for this connection.
+    ///
+    /// Any call to [`Self::run_auto_reconnect`] in progress stops and later
+    /// calls do nothing, including calls through other handles sharing the
+    /// connection. Lost connections are reported to the listener as usual.
+    ///
+    /// This is the off switch for explicit logout.
+    pub fn stop_auto_reconnect(&self) {
+        self.inner.auto_reconnect.stop()
+    }
+
+    async fn reconnect_until_stopped(
+        &self,
+        connection_manager: &ConnectionManager,
+        auth: Auth,
+        receive_stories: bool,
+        backoff: ReconnectBackoff,
+    ) -> Result<(), ConnectError> {
+        let auto_reconnect = &self.inner.auto_reconnect;
+        let network_changed = Arc::new(tokio::sync::Notify::new());
+        let _subscription = connection_manager.subscribe_to_net_events(Box::new({
+            let network_changed = network_changed.clone();
+            move |event| {
+                if matches!(event, NetEvent::NetworkChanged) {
+                    network_changed.notify_one()
+                }
+            }
+        }));
+
+        while auto_reconnect.wait_for_lost_connection().await {
+            let mut failed_attempts = 0;
+            let mut retry_at = None;
+            loop {
+                let now = tokio::time::Instant::now();
+                let outage_ends_at = connection_manager.connect.read().await.outage_ends_at(now);
+                let wait_until = [
+                    Some(now + backoff.delay_after(failed_attempts, &mut rand::thread_rng())),
+                    retry_at,
+                    outage_ends_at,
+                ]
+                .into_iter()
+                .flatten()
+                .max()
+                .expect("backoff is always present");
+
+                tokio::select! {
+                    () = tokio::time::sleep_until(wait_until) => {}
+                    () = network_changed.notified() => {
+                        log::info!("network changed; reconnecting authenticated chat now");
+                    }
+                    () = auto_reconnect.wake.notified() => {}
+                }
+                if auto_reconnect.stopped.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+
+                match self
+                    .reconnect(connection_manager, auth.clone(), receive_stories)
+                    .await
+                {
+                    Ok(()) => break,
+                    Err(
+                        e @ (ConnectError::AppExpired { .. }
+                        | ConnectError::DeviceDeregistered
+                        | ConnectError::InvalidConnectionConfiguration),
+                    ) => {
+                        log::warn!("giving up on reconnecting authenticated chat: {e}");
+                        return Err(e);
+                    }
+                    Err(e) => {
+                        failed_attempts += 1;
+                        retry_at = e.retry_at();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes a new connection and swaps it in for the lost one.
+    async fn reconnect(
+        &self,
+        connection_manager: &ConnectionManager,
+        auth: Auth,
+        receive_stories: bool,
+    ) -> Result<(), ConnectError> {
+        let (mut pending, generation) = establish_authenticated_chat_connection(
+            connection_manager,
+            auth,
+            receive_stories,
+            |_| {},
+        )
+        .await?;
+        if self.inner.auto_reconnect.stopped.load(Ordering::SeqCst) {
+            pending.disconnect().await;
+            if let Some(state) = &self.state {
+                state.disconnected(generation, DisconnectReason::LocalDisconnect);
+            }
+            return Ok(());
+        }
+
+        let listener = self
+            .inner
+            .auto_reconnect
+            .listener_for_new_connection()
+            .expect("listener was set before the connection was lost");
+        let listener = report_state_changes(listener, self.state.as_ref(), Some(generation));
+        let connection = ChatConnection::finish_connect(
+            tokio::runtime::Handle::current(),
+            pending,
+            listener.into_event_listener(),
+        );
+        let mut guard = self.inner.connection.write().await;
+        *guard = MaybeChatConnection::Running(connection);
+        *self.inner.generation.lock().expect("not poisoned") = Some(generation);
+        drop(guard);
+        self.inner.auto_reconnect.connection_restored();
+        log::info!("reconnected authenticated chat");
+        Ok(())
+    }
+
     pub async fn preconnect(connection_manager: &ConnectionManager) -> Result<(), ConnectError> {
         let enable_domain_fronting = connection_manager
             .endpoints
@@ -125,7 +496,9 @@ impl AuthenticatedChatConnection {
             &connection_manager.dns_resolver,
             "preconnect".into(),
         )
-        .await?;
+        .await
+        // Preconnecting stops once the transport is established.
+        .map_err(|e| ConnectError::from_route_error(e, chat::FailurePhase::Transport))?;
         Ok(())
     }
 
@@ -134,27 +507,86 @@ impl AuthenticatedChatConnection {
         listener: Box<dyn ChatListener>,
         alerts: impl IntoIterator<Item = &'a str>,
     ) -> (Self, FakeChatRemote) {
+        let auto_reconnect = Arc::<AutoReconnect>::default();
+        let listener = auto_reconnect.install_listener(listener);
         let (inner, remote) =
             ChatConnection::new_fake(tokio_runtime, listener.into_event_listener(), alerts);
         (
             Self {
-                inner: MaybeChatConnection::Running(inner).into(),
+                inner: ChatConnectionHandle::new(Arc::new(SharedChatConnection {
+                    auto_reconnect,
+                    ..SharedChatConnection::new(MaybeChatConnection::Running(inner), None)
+                })),
+                state: None,
+                server_time: Default::default(),
             },
             remote,
         )
     }
 }
 
-impl AsRef<tokio::sync::RwLock<MaybeChatConnection>> for AuthenticatedChatConnection {
-    fn as_ref(&self) -> &tokio::sync::RwLock<MaybeChatConnection> {
+impl AsRef<SharedChatConnection> for AuthenticatedChatConnection {
+    fn as_ref(&self) -> &SharedChatConnection {
         &self.inner
     }
 }
 
-impl AsRef<tokio::sync::RwLock<MaybeChatConnection>> for UnauthenticatedChatConnection {
-    fn as_ref(&self) -> &tokio::sync::RwLock<MaybeChatConnection> {
+impl AsRef<SharedChatConnection> for UnauthenticatedChatConnection {
+    fn as_ref(&self) -> &SharedChatConnection {
+        &self.inner
+    }
+}
+
+/// State shared between a connection and the [`ConnectionManager`] that made it.
+trait SharedConnectionState {
+    /// Where to report changes in the connection's state, if anywhere.
+    fn connection_state(&self) -> Option<&Arc<ConnectionStateMachine>>;
+
+    /// The estimate of the server's clock to update from responses.
+    fn server_time_estimator(&self) -> &ServerTimeEstimator;
+
+    /// This handle's hold on the connection.
+    fn handle(&self) -> &ChatConnectionHandle;
+
+    /// The auto-reconnect state for the connection, if it supports
+    /// auto-reconnect.
+    fn auto_reconnect(&self) -> Option<&Arc<AutoReconnect>>;
+}
+
+impl SharedConnectionState for AuthenticatedChatConnection {
+    fn connection_state(&self) -> Option<&Arc<ConnectionStateMachine>> {
+        self.state.as_ref()
+    }
+
+    fn server_time_estimator(&self) -> &ServerTimeEstimator {
+        &self.server_time
+    }
+
+    fn handle(&self) -> &ChatConnectionHandle {
         &self.inner
     }
+
+    fn auto_reconnect(&self) -> Option<&Arc<AutoReconnect>> {
+        Some(&self.inner.auto_reconnect)
+    }
+}
+
+impl SharedConnectionState for UnauthenticatedChatConnection {
+    fn connection_state(&self) -> Option<&Arc<ConnectionStateMachine>> {
+        None
+    }
+
+    fn server_time_estimator(&self) -> &ServerTimeEstimator {
+        &self.server_time
+    }
+
+    fn handle(&self) -> &ChatConnectionHandle {
+        &self.inner
+    }
+
+    fn auto_reconnect(&self) -> Option<&Arc<AutoReconnect>> {
+        None
+    }
 }
 
 pub trait BridgeChatConnection {
@@ -171,34 +603,68 @@ pub trait BridgeChatConnection {
     fn info(&self) -> ConnectionInfo;
 }
 
-impl<C: AsRef<tokio::sync::RwLock<MaybeChatConnection>> + Sync> BridgeChatConnection for C {
+impl<C: AsRef<SharedChatConnection> + SharedConnectionState + Sync> BridgeChatConnection for C {
     fn init_listener(&self, listener: Box<dyn ChatListener>) {
-        init_listener(&mut self.as_ref().blocking_write(), listener)
+        let mut guard = self.as_ref().connection.blocking_write();
+        // Events like incoming messages can only be delivered once, so only
+        // one of the handles sharing a connection gets to listen to it.
+        assert!(
+            !matches!(*guard, MaybeChatConnection::Running(_)),
+            "listener already set (possibly by another handle for the same connection)"
+        );
+        let listener = match self.auto_reconnect() {
+            Some(auto_reconnect) => auto_reconnect.install_listener(listener),
+            None => listener,
+        };
+        let listener = report_state_changes(
+            listener,
+            self.connection_state(),
+            self.as_ref().generation(),
+        );
+        init_listener(&mut guard, listener)
     }
 
     async fn send(&self, message: Request, timeout: Duration) -> Result<ChatResponse, SendError> {
-        let guard = self.as_ref().read().await;
+        let guard = self.as_ref().connection.read().await;
         let MaybeChatConnection::Running(inner) = &*guard else {
             panic!("listener was not set")
         };
-        inner.send(message, timeout).await
+        let response = inner.send(message, timeout).await?;
+        self.server_time_estimator()
+            .record_response(&response.headers, SystemTime::now());
+        Ok(response)
     }
 
     async fn disconnect(&self) {
-        let guard = self.as_ref().read().await;
+        if !self.handle().release() {
+            // Any auto-reconnect keeps running for the handles that remain.
+            log::info!("chat connection is still in use by another handle; not disconnecting");
+            return;
+        }
+        if let Some(auto_reconnect) = self.auto_reconnect() {
+            auto_reconnect.stop();
+        }
+        let guard = self.as_ref().connection.read().await;
         match &*guard {
             MaybeChatConnection::Running(chat_connection) => chat_connection.disconnect().await,
             MaybeChatConnection::WaitingForListener(_handle, pending_chat_mutex) => {
                 pending_chat_mutex.lock().await.disconnect().await
             }
+            #[cfg(feature = "test-util")]
+            MaybeChatConnection::WaitingForFakeListener(..) => {}
             MaybeChatConnection::TemporarilyEvicted => {
                 unreachable!("unobservable state");
             }
         }
+        if let (Some(state), Some(generation)) =
+            (self.connection_state(), self.as_ref().generation())
+        {
+            state.disconnected(generation, DisconnectReason::LocalDisconnect);
+        }
     }
 
     fn info(&self) -> ConnectionInfo {
-        let guard = self.as_ref().blocking_read();
+        let guard = self.as_ref().connection.blocking_read();
         let connection_info = match &*guard {
             MaybeChatConnection::Running(chat_connection) => {
                 chat_connection.connection_info().clone()
@@ -206,6 +672,14 @@ impl<C: AsRef<tokio::sync::RwLock<MaybeChatConnection>> + Sync> BridgeChatConnec
             MaybeChatConnection::WaitingForListener(_, pending_chat_connection) => {
                 pending_chat_connection.blocking_lock().connection_info()
             }
+            #[cfg(feature = "test-util")]
+            MaybeChatConnection::WaitingForFakeListener(..) => ConnectionInfo {
+                route_info: libsignal_net::connect_state::RouteInfo::fake(),
+                transport_info: libsignal_net::infra::TransportInfo {
+                    ip_version: libsignal_net::infra::IpType::V4,
+                    local_port: 0,
+                },
+            },
             MaybeChatConnection::TemporarilyEvicted => unreachable!("unobservable state"),
         };
 
@@ -223,6 +697,14 @@ fn init_listener(connection: &mut MaybeChatConnection, listener: Box<dyn ChatLis
             MaybeChatConnection::WaitingForListener(tokio_runtime, pending_chat_connection) => {
                 (tokio_runtime, pending_chat_connection)
             }
+            #[cfg(feature = "test-util")]
+            MaybeChatConnection::WaitingForFakeListener(tokio_runtime, server, data_usage) => {
+                let _entered = tokio_runtime.enter();
+                *connection = MaybeChatConnection::Running(
+                    server.connect_with_data_usage(listener.into_event_listener(), data_usage),
+                );
+                return;
+            }
             MaybeChatConnection::TemporarilyEvicted => panic!("should be a temporary state"),
         };
 
@@ -233,10 +715,160 @@ fn init_listener(connection: &mut MaybeChatConnection, listener: Box<dyn ChatLis
     ))
 }
 
+/// Wraps `listener` in a [`StateReportingListener`] if there's a `state` to
+/// report to.
+fn report_state_changes(
+    listener: Box<dyn ChatListener>,
+    state: Option<&Arc<ConnectionStateMachine>>,
+    generation: Option<ConnectionGeneration>,
+) -> Box<dyn ChatListener> {
+    match (state, generation) {
+        (Some(state), Some(generation)) => Box::new(StateReportingListener {
+            inner: listener,
+            state: state.clone(),
+            generation,
+        }),
+        _ => listener,
+    }
+}
+
+/// Runs `establish`, unless a connection attempt with the same `key` is already
+/// in progress, in which case its result is shared instead.
+///
+/// A successful connection is shared by every handle that joined it. Only one
+/// of them can set a listener, which receives all the connection's events; the
+/// others can only send requests. The connection stays up until every handle
+/// has disconnected or been dropped. A failure is reported to each caller once,
+/// and isn't remembered for later attempts.
+async fn establish_or_join_chat_connection(
+    auth_type: &'static str,
+    connection_manager: &ConnectionManager,
+    key: ChatConnectKey,
+    establish: impl Future<
+        Output = Result<(chat::PendingChatConnection, Option<ConnectionGeneration>), ConnectError>,
+    >,
+) -> Result<SharedMaybeChatConnection, ConnectError> {
+    #[cfg(feature = "test-util")]
+    if let Some(server) = connection_manager.fake_chat_server() {
+        log::info!("connecting {auth_type} chat to a fake server");
+        return Ok(Arc::new(SharedChatConnection::new(
+            MaybeChatConnection::WaitingForFakeListener(
+                tokio::runtime::Handle::current(),
+                server,
+                connection_manager.data_usage.clone(),
+            ),
+            None,
+        )));
+    }
+
+    let flight = connection_manager
+        .chat_connects
+        .run(
+            key,
+            || async move {
+                let (pending, generation) = establish.await?;
+                Ok(Arc::new(SharedChatConnection::new(
+                    MaybeChatConnection::WaitingForListener(
+                        tokio::runtime::Handle::current(),
+                        pending.into(),
+                    ),
+                    generation,
+                )))
+            },
+            |result: &Result<SharedMaybeChatConnection, ConnectError>| match result {
+                Ok(connection) => Ok(connection.clone()),
+                Err(e) => Err(Arc::new(copy_connect_error(e))),
+            },
+        )
+        .await;
+    match flight {
+        Flight::Led(result) => result,
+        Flight::Joined(result) => {
+            log::info!("joined in-progress {auth_type} chat connection attempt");
+            result.map_err(|e| copy_connect_error(&e))
+        }
+    }
+}
+
+/// Produces an equivalent [`ConnectError`] for callers that joined the attempt
+/// that failed.
+///
+/// See [`WebSocketConnectError::duplicate`](libsignal_net::infra::ws::WebSocketConnectError::duplicate)
+/// for how websocket errors are copied.
+fn copy_connect_error(error: &ConnectError) -> ConnectError {
+    match error {
+        ConnectError::Timeout { furthest_phase } => ConnectError::Timeout {
+            furthest_phase: *furthest_phase,
+        },
+        ConnectError::AllAttemptsFailed { furthest_phase } => ConnectError::AllAttemptsFailed {
+            furthest_phase: *furthest_phase,
+        },
+        ConnectError::InvalidConnectionConfiguration => {
+            ConnectError::InvalidConnectionConfiguration
+        }
+        ConnectError::ProxyFailure(kind) => ConnectError::ProxyFailure(*kind),
+        ConnectError::WebSocket(e) => ConnectError::WebSocket(e.duplicate()),
+        ConnectError::RetryLater {
+            retry_later,
+            received_at,
+        } => ConnectError::RetryLater {
+            retry_later: *retry_later,
+            received_at: *received_at,
+        },
+        ConnectError::AppExpired {
+            minimum_version,
+            upgrade_url,
+        } => ConnectError::AppExpired {
+            minimum_version: minimum_version.clone(),
+            upgrade_url: upgrade_url.clone(),
+        },
+        ConnectError::DeviceDeregistered => ConnectError::DeviceDeregistered,
+        ConnectError::RateLimitChallenge(challenge) => {
+            ConnectError::RateLimitChallenge(challenge.clone())
+        }
+    }
+}
+
+/// Establishes an authenticated chat connection, reporting progress to the
+/// [`ConnectionManager`]'s chat state.
+///
+/// Returns the generation to report the connection's state under. If the
+/// returned future is dropped before it completes, the chat state is reset.
+async fn establish_authenticated_chat_connection(
+    connection_manager: &ConnectionManager,
+    auth: Auth,
+    receive_stories: bool,
+    on_progress: impl FnMut(ConnectProgress) + Send,
+) -> Result<(chat::PendingChatConnection, ConnectionGeneration), ConnectError> {
+    let attempt = connection_manager.chat_state.connecting();
+    let result = establish_chat_connection(
+        "authenticated",
+        connection_manager,
+        Some(chat::AuthenticatedChatHeaders {
+            auth,
+            receive_stories: receive_stories.into(),
+        }),
+        on_progress,
+    )
+    .await;
+    match result {
+        Ok(pending) => {
+            connection_manager
+                .set_chat_round_trip_estimator(pending.round_trip_estimator().clone());
+            Ok((pending, attempt.connected(SystemTime::now())))
+        }
+        Err(e) => {
+            attempt.failed(e.failure_phase());
+            Err(e)
+        }
+    }
+}
+
 async fn establish_chat_connection(
     auth_type: &'static str,
     connection_manager: &ConnectionManager,
     auth: Option<chat::AuthenticatedChatHeaders>,
+    on_progress: impl FnMut(ConnectProgress) + Send,
 ) -> Result<chat::PendingChatConnection, ConnectError> {
     let ConnectionManager {
         env,
@@ -244,8 +876,10 @@ async fn establish_chat_connection(
         connect,
         user_agent,
         endpoints,
+        request_path_prefix,
         ..
     } = connection_manager;
+    let request_path_prefix = request_path_prefix.lock().expect("not poisoned").clone();
 
     let (ws_config, enable_domain_fronting) = {
         let endpoints_guard = endpoints.lock().expect("not poisoned");
@@ -266,7 +900,7 @@ async fn establish_chat_connection(
 
     log::info!("connecting {auth_type} chat");
 
-    ChatConnection::start_connect_with(
+    ChatConnection::start_connect_with_progress(
         connect,
         dns_resolver,
         route_provider,
@@ -281,12 +915,14 @@ async fn establish_chat_connection(
         },
         auth,
         auth_type,
+        on_progress,
     )
     .inspect(|r| match r {
         Ok(_) => log::info!("successfully connected {auth_type} chat"),
         Err(e) => log::warn!("failed to connect {auth_type} chat: {e}"),
     })
     .await
+    .map(|pending| pending.with_request_path_prefix(request_path_prefix))
 }
 
 fn make_route_provider(
@@ -324,6 +960,34 @@ pub struct ResponseAndDebugInfo {
     pub debug_info: ChatServiceDebugInfo,
 }
 
+/// The response headers apps may see; any others are dropped when a [`ChatResponse`] is passed
+/// across the bridge.
+static BRIDGED_RESPONSE_HEADERS: [HeaderName; 3] = [
+    http::header::CONTENT_TYPE,
+    http::header::RETRY_AFTER,
+    HeaderName::from_static(libsignal_net::env::TIMESTAMP_HEADER_NAME),
+];
+
+/// Header values longer than this are dropped rather than passed across the bridge.
+const MAX_BRIDGED_HEADER_VALUE_LEN: usize = 256;
+
+/// The headers from a [`ChatResponse`] to pass across the bridge, as name/value pairs.
+///
+/// Only headers in [`BRIDGED_RESPONSE_HEADERS`] are included, each at most once (the first value
+/// wins). Values that are too long or aren't printable ASCII are dropped.
+pub fn bridged_response_headers(headers: &HeaderMap) -> Vec<(&'static str, &str)> {
+    BRIDGED_RESPONSE_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(name)?;
+            if value.len() > MAX_BRIDGED_HEADER_VALUE_LEN {
+                return None;
+            }
+            Some((name.as_str(), value.to_str().ok()?))
+        })
+        .collect()
+}
+
 bridge_as_handle!(HttpRequest);
 
 /// Newtype wrapper for implementing [`TryFrom`]`
@@ -369,6 +1033,44 @@ impl HttpRequest {
     }
 }
 
+/// Receives the stages of establishing a chat connection, for
+/// [`UnauthenticatedChatConnection::connect_with_listener`] and
+/// [`AuthenticatedChatConnection::connect_with_listener`].
+pub trait ChatConnectListener: Send {
+    fn connect_progress(&mut self, stage: ConnectStage, at: Timestamp);
+}
+
+/// The number used to report `stage` to the app languages.
+pub fn bridged_connect_stage(stage: ConnectStage) -> u8 {
+    match stage {
+        ConnectStage::DnsResolved => 0,
+        ConnectStage::TransportEstablished => 1,
+        ConnectStage::WebSocketOpened => 2,
+    }
+}
+
+impl dyn ChatConnectListener {
+    /// Adapts `self` to the progress callback taken by the `connect_with_listener` methods.
+    pub fn into_progress_callback(mut self: Box<Self>) -> impl FnMut(ConnectProgress) + Send {
+        move |ConnectProgress { stage, at }| {
+            // Translate the monotonic time to wall-clock time by how long ago it was.
+            let at = SystemTime::now()
+                .checked_sub(at.elapsed())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            self.connect_progress(
+                stage,
+                Timestamp::from_epoch_millis(
+                    at.duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                        .try_into()
+                        .unwrap_or(u64::MAX),
+                ),
+            )
+        }
+    }
+}
+
 /// A trait of callbacks for different kinds of [`chat::server_requests::ServerEvent`].
 ///
 /// Done as multiple functions so we can adjust the types to be more suitable for bridging.
@@ -382,6 +1084,33 @@ pub trait ChatListener: Send {
     fn received_queue_empty(&mut self);
     fn received_alerts(&mut self, alerts: Vec<String>);
     fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause);
+
+    /// Called with each change in the state of the connection while
+    /// [auto-reconnect](AuthenticatedChatConnection::run_auto_reconnect) is
+    /// running, in place of [`Self::connection_interrupted`] for connections it
+    /// replaces.
+    ///
+    /// Ignored by default; the same transitions can be followed with a
+    /// [`ChatConnectionStateWatcher`].
+    fn connection_state_changed(&mut self, state: ConnectionState) {
+        let _ = state;
+    }
+
+    /// Called for a server-initiated request that doesn't correspond to any of
+    /// the other callbacks.
+    ///
+    /// The listener is responsible for responding using `ack`. By default,
+    /// requests are rejected with 404 Not Found.
+    fn received_unrecognized_request(
+        &mut self,
+        path: String,
+        body: Option<Vec<u8>>,
+        ack: ServerMessageAck,
+    ) {
+        let _ = body;
+        log::info!("ignoring unrecognized server request for {path}");
+        ack.send_status(http::StatusCode::NOT_FOUND);
+    }
 }
 
 impl dyn ChatListener {
@@ -401,6 +1130,11 @@ impl dyn ChatListener {
             ),
             chat::server_requests::ServerEvent::QueueEmpty => self.received_queue_empty(),
             chat::server_requests::ServerEvent::Alerts(alerts) => self.received_alerts(alerts),
+            chat::server_requests::ServerEvent::UnrecognizedRequest {
+                path,
+                body,
+                send_ack,
+            } => self.received_unrecognized_request(path, body, ServerMessageAck::new(send_ack)),
             chat::server_requests::ServerEvent::Stopped(error) => {
                 self.connection_interrupted(error)
             }
@@ -421,6 +1155,270 @@ impl dyn ChatListener {
     }
 }
 
+/// Forwards to another [`ChatListener`], reporting disconnects to a [`ConnectionStateMachine`].
+struct StateReportingListener {
+    inner: Box<dyn ChatListener>,
+    state: Arc<ConnectionStateMachine>,
+    generation: ConnectionGeneration,
+}
+
+impl ChatListener for StateReportingListener {
+    fn received_incoming_message(
+        &mut self,
+        envelope: Vec<u8>,
+        timestamp: Timestamp,
+        ack: ServerMessageAck,
+    ) {
+        self.inner
+            .received_incoming_message(envelope, timestamp, ack)
+    }
+
+    fn received_queue_empty(&mut self) {
+        self.inner.received_queue_empty()
+    }
+
+    fn received_alerts(&mut self, alerts: Vec<String>) {
+        self.inner.received_alerts(alerts)
+    }
+
+    fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
+        self.state.disconnected(
+            self.generation,
+            match &disconnect_cause {
+                DisconnectCause::LocalDisconnect => DisconnectReason::LocalDisconnect,
+                DisconnectCause::Error(_) => DisconnectReason::ConnectionLost,
+            },
+        );
+        self.inner.connection_interrupted(disconnect_cause)
+    }
+
+    fn connection_state_changed(&mut self, state: ConnectionState) {
+        self.inner.connection_state_changed(state)
+    }
+
+    fn received_unrecognized_request(
+        &mut self,
+        path: String,
+        body: Option<Vec<u8>>,
+        ack: ServerMessageAck,
+    ) {
+        self.inner.received_unrecognized_request(path, body, ack)
+    }
+}
+
+/// A listener shared by every connection made by auto-reconnect.
+type SharedChatListener = Arc<std::sync::Mutex<Box<dyn ChatListener>>>;
+
+/// State shared between [`AuthenticatedChatConnection::run_auto_reconnect`]
+/// and the listeners of the connections it manages.
+#[derive(Default)]
+struct AutoReconnect {
+    /// Set while [`AuthenticatedChatConnection::run_auto_reconnect`] is
+    /// running.
+    active: AtomicBool,
+    /// The off switch; once set, never cleared.
+    stopped: AtomicBool,
+    /// Set when a connection is lost while auto-reconnect is active, until a
+    /// reconnect attempt is started.
+    lost: AtomicBool,
+    /// Notified whenever `lost` or `stopped` is set.
+    wake: tokio::sync::Notify,
+    /// Why the connection went down, if it hasn't come back and the app hasn't
+    /// been told.
+    pending_interruption: std::sync::Mutex<Option<DisconnectCause>>,
+    /// The app's listener, once set.
+    listener: std::sync::Mutex<Option<SharedChatListener>>,
+}
+
+impl AutoReconnect {
+    /// Keeps `listener` for future connections, and returns a listener for the
+    /// current one.
+    fn install_listener(
+        self: &Arc<Self>,
+        listener: Box<dyn ChatListener>,
+    ) -> Box<dyn ChatListener> {
+        let shared = Arc::new(std::sync::Mutex::new(listener));
+        *self.listener.lock().expect("not poisoned") = Some(shared.clone());
+        Box::new(ReconnectingListener {
+            inner: shared,
+            auto_reconnect: self.clone(),
+        })
+    }
+
+    fn listener_for_new_connection(self: &Arc<Self>) -> Option<Box<dyn ChatListener>> {
+        let shared = self.listener.lock().expect("not poisoned").clone()?;
+        Some(Box::new(ReconnectingListener {
+            inner: shared,
+            auto_reconnect: self.clone(),
+        }))
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+
+    /// Returns `true` if the connection was lost and should be replaced,
+    /// or `false` if auto-reconnect was stopped first.
+    async fn wait_for_lost_connection(&self) -> bool {
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return false;
+            }
+            if self.lost.swap(false, Ordering::SeqCst) {
+                return true;
+            }
+            self.wake.notified().await;
+        }
+    }
+
+    /// Takes over reporting a lost connection, if auto-reconnect is active.
+    ///
+    /// Otherwise gives `disconnect_cause` back.
+    fn connection_lost(&self, disconnect_cause: DisconnectCause) -> Option<DisconnectCause> {
+        if !self.active.load(Ordering::SeqCst) || self.stopped.load(Ordering::SeqCst) {
+            return Some(disconnect_cause);
+        }
+        log::info!("authenticated chat connection lost; will reconnect");
+        *self.pending_interruption.lock().expect("not poisoned") = Some(disconnect_cause);
+        self.lost.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+        None
+    }
+
+    fn connection_restored(&self) {
+        *self.pending_interruption.lock().expect("not poisoned") = None;
+    }
+
+    /// Passes a state transition on to the app's listener, if it's been set.
+    fn report_state(&self, state: ConnectionState) {
+        if let Some(listener) = &*self.listener.lock().expect("not poisoned") {
+            listener
+                .lock()
+                .expect("not poisoned")
+                .connection_state_changed(state)
+        }
+    }
+
+    /// Tells the app's listener about a lost connection that auto-reconnect
+    /// didn't end up replacing.
+    fn report_pending_interruption(&self) {
+        let Some(disconnect_cause) = self
+            .pending_interruption
+            .lock()
+            .expect("not poisoned")
+            .take()
+        else {
+            return;
+        };
+        if let Some(listener) = &*self.listener.lock().expect("not poisoned") {
+            listener
+                .lock()
+                .expect("not poisoned")
+                .connection_interrupted(disconnect_cause)
+        }
+    }
+}
+
+/// Forwards to the app's [`ChatListener`], except for lost connections that
+/// [`AutoReconnect`] is going to replace.
+struct ReconnectingListener {
+    inner: SharedChatListener,
+    auto_reconnect: Arc<AutoReconnect>,
+}
+
+impl ReconnectingListener {
+    fn inner(&self) -> std::sync::MutexGuard<'_, Box<dyn ChatListener>> {
+        self.inner.lock().expect("not poisoned")
+    }
+}
+
+impl ChatListener for ReconnectingListener {
+    fn received_incoming_message(
+        &mut self,
+        envelope: Vec<u8>,
+        timestamp: Timestamp,
+        ack: ServerMessageAck,
+    ) {
+        self.inner()
+            .received_incoming_message(envelope, timestamp, ack)
+    }
+
+    fn received_queue_empty(&mut self) {
+        self.inner().received_queue_empty()
+    }
+
+    fn received_alerts(&mut self, alerts: Vec<String>) {
+        self.inner().received_alerts(alerts)
+    }
+
+    fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
+        let disconnect_cause = match disconnect_cause {
+            DisconnectCause::LocalDisconnect => Some(disconnect_cause),
+            DisconnectCause::Error(_) => self.auto_reconnect.connection_lost(disconnect_cause),
+        };
+        if let Some(disconnect_cause) = disconnect_cause {
+            self.inner().connection_interrupted(disconnect_cause)
+        }
+    }
+
+    fn connection_state_changed(&mut self, state: ConnectionState) {
+        self.inner().connection_state_changed(state)
+    }
+
+    fn received_unrecognized_request(
+        &mut self,
+        path: String,
+        body: Option<Vec<u8>>,
+        ack: ServerMessageAck,
+    ) {
+        self.inner().received_unrecognized_request(path, body, ack)
+    }
+}
+
+pub type ChatConnectionState = ConnectionState;
+
+bridge_as_handle!(ChatConnectionState);
+
+/// Lets app code follow the [`ConnectionState`] of a [`ConnectionManager`]'s authenticated chat
+/// connection.
+pub struct ChatConnectionStateWatcher {
+    // Holding the state machine keeps the channel open even if the ConnectionManager goes away.
+    state: Arc<ConnectionStateMachine>,
+    receiver: tokio::sync::Mutex<ConnectionStateReceiver>,
+}
+
+bridge_as_handle!(ChatConnectionStateWatcher);
+impl UnwindSafe for ChatConnectionStateWatcher {}
+impl RefUnwindSafe for ChatConnectionStateWatcher {}
+
+impl ChatConnectionStateWatcher {
+    pub fn new(connection_manager: &ConnectionManager) -> Self {
+        let state = connection_manager.chat_state.clone();
+        let receiver = state.subscribe().into();
+        Self { state, receiver }
+    }
+
+    /// Returns the current state without waiting.
+    pub fn current(&self) -> ConnectionState {
+        self.state.current()
+    }
+
+    /// Waits until the state is different from the last one returned by this method, then returns
+    /// the latest state.
+    ///
+    /// If several changes happen in between calls, only the most recent one is returned.
+    pub async fn next(&self) -> ConnectionState {
+        let mut receiver = self.receiver.lock().await;
+        receiver
+            .changed()
+            .await
+            .expect("sender is kept alive by self.state");
+        let state = receiver.borrow_and_update().clone();
+        state
+    }
+}
+
 /// Wraps a named type and a single-use guard around [`chat::server_requests::ResponseEnvelopeSender`].
 pub struct ServerMessageAck {
     inner: AtomicTake<chat::server_requests::ResponseEnvelopeSender>,
@@ -436,6 +1434,18 @@ impl ServerMessageAck {
     pub fn take(&self) -> Option<chat::server_requests::ResponseEnvelopeSender> {
         self.inner.take()
     }
+
+    /// Responds with `status`, logging rather than returning any failure.
+    ///
+    /// Does nothing if the ack has already been sent.
+    pub fn send_status(&self, status: http::StatusCode) {
+        let Some(sender) = self.take() else {
+            return;
+        };
+        if let Err(e) = sender(status) {
+            log::warn!("failed to respond to server request: {e}");
+        }
+    }
 }
 
 bridge_as_handle!(ServerMessageAck);
@@ -444,3 +1454,230 @@ bridge_as_handle!(ServerMessageAck);
 // makes it `!RefUnwindSafe`. We're putting that back; because we only manipulate the `AtomicTake`
 // using its atomic operations, it can never be in an invalid state.
 impl std::panic::RefUnwindSafe for ServerMessageAck {}
+
+#[cfg(test)]
+mod test {
+    use ::tokio; // otherwise ambiguous with the tokio submodule
+    use assert_matches::assert_matches;
+    use libsignal_net::chat::fake::server::{CannedResponse, FakeChatServer};
+    use libsignal_net::chat::retry::SUGGESTED_RECONNECT_BACKOFF;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::net::Environment;
+
+    struct IgnoringListener;
+
+    impl ChatListener for IgnoringListener {
+        fn received_incoming_message(
+            &mut self,
+            _envelope: Vec<u8>,
+            _timestamp: Timestamp,
+            _ack: ServerMessageAck,
+        ) {
+        }
+        fn received_queue_empty(&mut self) {}
+        fn received_alerts(&mut self, _alerts: Vec<String>) {}
+        fn connection_interrupted(&mut self, _disconnect_cause: DisconnectCause) {}
+    }
+
+    /// Responds to the request [`unrecognized_server_requests_are_answered`]
+    /// sends with a fixed status, and to anything else with 400 Bad Request.
+    struct RespondingListener(http::StatusCode);
+
+    impl ChatListener for RespondingListener {
+        fn received_incoming_message(
+            &mut self,
+            _envelope: Vec<u8>,
+            _timestamp: Timestamp,
+            _ack: ServerMessageAck,
+        ) {
+        }
+        fn received_queue_empty(&mut self) {}
+        fn received_alerts(&mut self, _alerts: Vec<String>) {}
+        fn connection_interrupted(&mut self, _disconnect_cause: DisconnectCause) {}
+        fn received_unrecognized_request(
+            &mut self,
+            path: String,
+            body: Option<Vec<u8>>,
+            ack: ServerMessageAck,
+        ) {
+            if path == "/v1/custom" && body.as_deref() == Some(b"body") {
+                ack.send_status(self.0)
+            } else {
+                ack.send_status(http::StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+
+    fn greeting() -> Request {
+        Request::builder()
+            .path("/v1/greeting")
+            .expect("valid path")
+            .build()
+            .expect("valid request")
+    }
+
+    #[tokio::test]
+    async fn shared_connection_stays_up_until_every_handle_disconnects() {
+        let server = FakeChatServer::new();
+        server.respond(
+            "/v1/greeting",
+            CannedResponse::status(http::StatusCode::NO_CONTENT),
+        );
+        let shared = Arc::new(SharedChatConnection::new(
+            MaybeChatConnection::WaitingForFakeListener(
+                tokio::runtime::Handle::current(),
+                server,
+                Default::default(),
+            ),
+            None,
+        ));
+        let [first, second] = [(); 2].map(|()| UnauthenticatedChatConnection {
+            inner: ChatConnectionHandle::new(shared.clone()),
+            server_time: Default::default(),
+        });
+        drop(shared);
+
+        let (first, second) = tokio::task::spawn_blocking(move || {
+            first.init_listener(Box::new(IgnoringListener));
+            let second_listener = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                second.init_listener(Box::new(IgnoringListener))
+            }));
+            assert!(second_listener.is_err(), "only one handle can listen");
+            (first, second)
+        })
+        .await
+        .expect("no unexpected panic");
+
+        first.disconnect().await;
+        // Disconnecting again doesn't count twice.
+        first.disconnect().await;
+        let response = second
+            .send(greeting(), Duration::from_secs(5))
+            .await
+            .expect("still connected");
+        assert_eq!(response.status, http::StatusCode::NO_CONTENT);
+
+        second.disconnect().await;
+        assert_matches!(
+            second.send(greeting(), Duration::from_secs(5)).await,
+            Err(SendError::Disconnected)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn auto_reconnect_belongs_to_the_shared_connection() {
+        let cm = ConnectionManager::new(Environment::Staging, "test-user-agent");
+        let (first, _remote) = AuthenticatedChatConnection::new_fake(
+            tokio::runtime::Handle::current(),
+            Box::new(IgnoringListener),
+            [],
+        );
+        let second = AuthenticatedChatConnection {
+            inner: ChatConnectionHandle::new(first.inner.shared.clone()),
+            state: None,
+            server_time: Default::default(),
+        };
+        let auth = || Auth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+
+        let (result, ()) = tokio::join!(
+            first.run_auto_reconnect(&cm, auth(), false, SUGGESTED_RECONNECT_BACKOFF),
+            async {
+                // Already running for the connection, through the first handle.
+                second
+                    .run_auto_reconnect(&cm, auth(), false, SUGGESTED_RECONNECT_BACKOFF)
+                    .await
+                    .expect("returns right away");
+
+                second.disconnect().await;
+                assert!(
+                    !first.inner.auto_reconnect.stopped.load(Ordering::SeqCst),
+                    "still needed by the first handle"
+                );
+                first.disconnect().await;
+            },
+        );
+        assert_matches!(result, Ok(()));
+        assert!(second.inner.auto_reconnect.stopped.load(Ordering::SeqCst));
+    }
+
+    #[test_case(Box::new(IgnoringListener) => http::StatusCode::NOT_FOUND; "rejected by default")]
+    #[test_case(Box::new(RespondingListener(http::StatusCode::NO_CONTENT)) => http::StatusCode::NO_CONTENT; "answered by the listener")]
+    #[tokio::test]
+    async fn unrecognized_server_requests_are_answered(
+        listener: Box<dyn ChatListener>,
+    ) -> http::StatusCode {
+        let (_chat, remote) =
+            AuthenticatedChatConnection::new_fake(tokio::runtime::Handle::current(), listener, []);
+
+        remote
+            .send_request(chat::RequestProto {
+                verb: Some("PUT".to_owned()),
+                path: Some("/v1/custom".to_owned()),
+                body: Some(b"body".to_vec()),
+                headers: vec![],
+                id: Some(7),
+            })
+            .expect("still connected");
+        let response = remote
+            .receive_response()
+            .await
+            .expect("valid response")
+            .expect("still connected");
+
+        assert_eq!(response.id, Some(7));
+        let status = response.status.expect("has a status");
+        http::StatusCode::from_u16(status.try_into().expect("fits")).expect("valid status")
+    }
+
+    #[test]
+    fn bridged_response_headers_are_allowlisted() {
+        let headers = HeaderMap::from_iter([
+            (
+                http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            ),
+            (
+                HeaderName::from_static("x-signal-timestamp"),
+                HeaderValue::from_static("1700000000000"),
+            ),
+            (http::header::RETRY_AFTER, HeaderValue::from_static("30")),
+            (
+                http::header::SET_COOKIE,
+                HeaderValue::from_static("session=secret"),
+            ),
+            (
+                HeaderName::from_static("x-unknown"),
+                HeaderValue::from_static("value"),
+            ),
+        ]);
+
+        assert_eq!(
+            bridged_response_headers(&headers),
+            [
+                ("content-type", "application/json"),
+                ("retry-after", "30"),
+                ("x-signal-timestamp", "1700000000000"),
+            ]
+        );
+    }
+
+    #[test_case(HeaderValue::from_str(&"1".repeat(MAX_BRIDGED_HEADER_VALUE_LEN + 1)).expect("valid"); "too long")]
+    #[test_case(HeaderValue::from_bytes(b"caf\xc3\xa9").expect("valid"); "not ASCII")]
+    fn unusable_header_values_are_dropped(value: HeaderValue) {
+        let headers = HeaderMap::from_iter([(http::header::RETRY_AFTER, value)]);
+        assert!(bridged_response_headers(&headers).is_empty());
+    }
+
+    #[test]
+    fn only_the_first_value_is_bridged() {
+        let mut headers = HeaderMap::new();
+        headers.append(http::header::RETRY_AFTER, HeaderValue::from_static("1"));
+        headers.append(http::header::RETRY_AFTER, HeaderValue::from_static("2"));
+        assert_eq!(bridged_response_headers(&headers), [("retry-after", "1")]);
+    }
+}
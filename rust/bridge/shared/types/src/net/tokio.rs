@@ -14,29 +14,55 @@ use futures_util::FutureExt as _;
 use crate::support::*;
 use crate::*;
 pub struct TokioAsyncContext {
-    pub(crate) rt: tokio::runtime::Runtime,
+    rt: RuntimeOwnership,
     tasks: Arc<Mutex<HashMap<CancellationId, tokio::sync::oneshot::Sender<()>>>>,
     next_raw_cancellation_id: AtomicU64,
 }
 
+/// The runtime a [`TokioAsyncContext`] runs its tasks on.
+enum RuntimeOwnership {
+    /// Created for (and shut down along with) the context.
+    Owned(tokio::runtime::Runtime),
+    /// Provided by the embedder, who remains responsible for shutting it down.
+    Borrowed(tokio::runtime::Handle),
+}
+
 impl TokioAsyncContext {
     // This is an expensive operation, so we don't want to just use Default.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Self {
-            rt: tokio::runtime::Builder::new_multi_thread()
+        Self::with_runtime(RuntimeOwnership::Owned(
+            tokio::runtime::Builder::new_multi_thread()
                 .enable_io()
                 .enable_time()
                 .thread_name("libsignal-tokio-worker")
                 .build()
                 .expect("failed to create runtime"),
+        ))
+    }
+
+    /// Creates a context that runs its tasks on an existing runtime instead of
+    /// starting its own.
+    ///
+    /// The runtime must have IO and time enabled. Dropping the context does not
+    /// shut the runtime down, nor does it stop tasks that are still running.
+    pub fn with_runtime_handle(handle: tokio::runtime::Handle) -> Self {
+        Self::with_runtime(RuntimeOwnership::Borrowed(handle))
+    }
+
+    fn with_runtime(rt: RuntimeOwnership) -> Self {
+        Self {
+            rt,
             tasks: Default::default(),
             next_raw_cancellation_id: AtomicU64::new(1),
         }
     }
 
     pub fn handle(&self) -> tokio::runtime::Handle {
-        self.rt.handle().clone()
+        match &self.rt {
+            RuntimeOwnership::Owned(runtime) => runtime.handle().clone(),
+            RuntimeOwnership::Borrowed(handle) => handle.clone(),
+        }
     }
 }
 
@@ -154,11 +180,11 @@ impl TokioAsyncContext {
 
         let future = make_future(TokioContextCancellation(cancel_rx));
 
-        let handle = self.rt.handle().clone();
+        let handle = self.handle();
         let task_map_weak = Arc::downgrade(&self.tasks);
 
         #[allow(clippy::let_underscore_future)]
-        let _: tokio::task::JoinHandle<()> = self.rt.spawn(async move {
+        let _: tokio::task::JoinHandle<()> = handle.clone().spawn(async move {
             let report_fn = future.await;
             let _: tokio::task::JoinHandle<()> = handle.spawn_blocking(report_fn);
             // What happens if we don't get here? We leak an entry in the task map. Also, we
@@ -251,11 +277,7 @@ mod test {
         let (sum_tx, mut sum_rx, sum_future) = sum_task();
         runtime.spawn(sum_future);
 
-        let async_context = TokioAsyncContext {
-            rt: runtime,
-            tasks: Default::default(),
-            next_raw_cancellation_id: AtomicU64::new(1),
-        };
+        let async_context = TokioAsyncContext::with_runtime(RuntimeOwnership::Owned(runtime));
 
         let (send_to_task, task_output, when_reporting) = {
             let (sender, receiver) = oneshot::channel();
@@ -315,11 +337,7 @@ mod test {
         runtime_builder.worker_threads(1);
         let runtime = runtime_builder.build().expect("valid runtime");
 
-        let async_context = TokioAsyncContext {
-            rt: runtime,
-            tasks: Default::default(),
-            next_raw_cancellation_id: AtomicU64::new(1),
-        };
+        let async_context = TokioAsyncContext::with_runtime(RuntimeOwnership::Owned(runtime));
 
         let (on_start_reporting1, mut when_reporting1) = oneshot::channel();
         let cancellation_id1 = async_context.run_future(
@@ -364,4 +382,40 @@ mod test {
         async_context.cancel(cancellation_id1);
         when_reporting1.blocking_recv().expect("completed");
     }
+
+    #[test]
+    fn borrowed_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("embedder-worker")
+            .build()
+            .expect("valid runtime");
+
+        let async_context = TokioAsyncContext::with_runtime_handle(runtime.handle().clone());
+        let output = Arc::new(Mutex::new(None));
+        let (on_start_reporting, when_reporting) = oneshot::channel();
+        async_context.run_future(
+            |_cancel| {
+                let output = output.clone();
+                async move {
+                    NotifyingReporter {
+                        on_start_reporting,
+                        reporter: (std::thread::current().name().map(String::from), output),
+                    }
+                }
+            },
+            (),
+        );
+        when_reporting.blocking_recv().expect("completed");
+
+        // Dropping the context must leave the embedder's runtime usable.
+        drop(async_context);
+        assert_eq!(runtime.block_on(async { 1 + 2 }), 3);
+        drop(runtime);
+
+        assert_eq!(
+            *output.lock().expect("not poisoned"),
+            Some(Some("embedder-worker".to_owned()))
+        );
+    }
 }
@@ -6,6 +6,7 @@
 use std::sync::Arc;
 
 use libsignal_net::chat::server_requests::DisconnectCause;
+use libsignal_net::connect_state::ConnectStage;
 use libsignal_protocol::Timestamp;
 use neon::context::FunctionContext;
 use neon::event::Channel;
@@ -14,7 +15,9 @@ use neon::prelude::{Context, Finalize, JsObject, Object};
 use neon::result::NeonResult;
 use signal_neon_futures::call_method;
 
-use crate::net::chat::{ChatListener, ServerMessageAck};
+use crate::net::chat::{
+    bridged_connect_stage, ChatConnectListener, ChatListener, ServerMessageAck,
+};
 use crate::node::{ResultTypeInfo, SignalNodeError as _};
 
 #[derive(Clone)]
@@ -83,6 +86,33 @@ impl ChatListener for NodeChatListener {
         });
     }
 
+    fn received_unrecognized_request(
+        &mut self,
+        path: String,
+        body: Option<Vec<u8>>,
+        ack: ServerMessageAck,
+    ) {
+        let roots_shared = self.roots.clone();
+        self.js_channel.send(move |mut cx| {
+            let callback_object_shared = &roots_shared.callback_object;
+            let callback = callback_object_shared.to_inner(&mut cx);
+            let ack = ack.convert_into(&mut cx)?;
+            let body = body.unwrap_or_default().convert_into(&mut cx)?.upcast();
+            let path = cx
+                .try_string(path)
+                .unwrap_or_else(|_| cx.string("[invalid path]"))
+                .upcast();
+            let _result = call_method(
+                &mut cx,
+                callback,
+                "_received_unrecognized_request",
+                [path, body, ack],
+            )?;
+            roots_shared.finalize(&mut cx);
+            Ok(())
+        });
+    }
+
     fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
         let disconnect_cause = match disconnect_cause {
             DisconnectCause::LocalDisconnect => None,
@@ -142,3 +172,47 @@ impl Finalize for Roots {
         self.module.finalize(cx);
     }
 }
+
+pub struct NodeChatConnectListener {
+    js_channel: Channel,
+    callback_object: Arc<Root<JsObject>>,
+}
+
+impl ChatConnectListener for NodeChatConnectListener {
+    fn connect_progress(&mut self, stage: ConnectStage, at: Timestamp) {
+        let callback_object_shared = self.callback_object.clone();
+        self.js_channel.send(move |mut cx| {
+            let callback = callback_object_shared.to_inner(&mut cx);
+            let stage = cx.number(bridged_connect_stage(stage)).upcast();
+            let at = at.convert_into(&mut cx)?.upcast();
+            let _result = call_method(&mut cx, callback, "_connect_progress", [stage, at])?;
+            callback_object_shared.finalize(&mut cx);
+            Ok(())
+        });
+    }
+}
+
+impl NodeChatConnectListener {
+    pub(crate) fn new(cx: &mut FunctionContext, callbacks: Handle<JsObject>) -> NeonResult<Self> {
+        let mut channel = cx.channel();
+        channel.unref(cx);
+
+        Ok(Self {
+            js_channel: channel,
+            callback_object: Arc::new(callbacks.root(cx)),
+        })
+    }
+
+    pub(crate) fn make_listener(&mut self) -> Box<dyn ChatConnectListener> {
+        Box::new(Self {
+            js_channel: self.js_channel.clone(),
+            callback_object: self.callback_object.clone(),
+        })
+    }
+}
+
+impl Finalize for NodeChatConnectListener {
+    fn finalize<'a, C: neon::prelude::Context<'a>>(self, cx: &mut C) {
+        self.callback_object.finalize(cx);
+    }
+}
@@ -19,8 +19,8 @@ use paste::paste;
 use super::*;
 use crate::io::{InputStream, SyncInputStream};
 use crate::message_backup::MessageBackupValidationOutcome;
-use crate::net::chat::ChatListener;
-use crate::node::chat::NodeChatListener;
+use crate::net::chat::{bridged_response_headers, ChatConnectListener, ChatListener};
+use crate::node::chat::{NodeChatConnectListener, NodeChatListener};
 use crate::support::{extend_lifetime, Array, AsType, FixedLengthBincodeSerializable, Serialized};
 
 /// Converts arguments from their JavaScript form to their Rust form.
@@ -690,6 +690,22 @@ impl<'a> AsyncArgTypeInfo<'a> for Box<dyn ChatListener> {
     }
 }
 
+impl<'a> AsyncArgTypeInfo<'a> for Box<dyn ChatConnectListener> {
+    type ArgType = JsObject;
+    type StoredType = NodeChatConnectListener;
+
+    fn save_async_arg(
+        cx: &mut FunctionContext,
+        foreign: Handle<Self::ArgType>,
+    ) -> NeonResult<Self::StoredType> {
+        NodeChatConnectListener::new(cx, foreign)
+    }
+
+    fn load_async_arg(stored: &'a mut Self::StoredType) -> Self {
+        stored.make_listener()
+    }
+}
+
 impl<'storage, 'context: 'storage> ArgTypeInfo<'storage, 'context>
     for &'storage mut dyn SyncInputStream
 {
@@ -966,10 +982,11 @@ impl<'a> ResultTypeInfo<'a> for libsignal_net::chat::Response {
         } = self;
         let obj = JsObject::new(cx);
 
+        let headers = bridged_response_headers(&headers);
         let headers_arr = JsArray::new(cx, headers.len());
-        for ((name, value), i) in headers.iter().zip(0..) {
-            let name = cx.string(name.as_str());
-            let value = cx.string(value.to_str().expect("valid header value"));
+        for ((name, value), i) in headers.into_iter().zip(0..) {
+            let name = cx.string(name);
+            let value = cx.string(value);
             let entry = JsArray::new(cx, 2);
             entry.set(cx, 0, name)?;
             entry.set(cx, 1, value)?;
@@ -982,7 +999,7 @@ impl<'a> ResultTypeInfo<'a> for libsignal_net::chat::Response {
             None => cx.undefined().as_value(cx),
         };
         let body = match body {
-            Some(b) => b.convert_into(cx)?.as_value(cx),
+            Some(b) => Vec::from(b).convert_into(cx)?.as_value(cx),
             None => cx.undefined().as_value(cx),
         };
 
@@ -4,8 +4,10 @@
 //
 
 use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use libsignal_net::infra::errors::RetryLater;
+use libsignal_net::ws::RateLimitChallenge;
 use signal_media::sanitize::mp4::{Error as Mp4Error, ParseError as Mp4ParseError};
 use signal_media::sanitize::webp::{Error as WebpError, ParseError as WebpParseError};
 
@@ -142,6 +144,7 @@ pub trait SignalNodeError: Sized + fmt::Display {
 const INVALID_MEDIA_INPUT: &str = "InvalidMediaInput";
 const IO_ERROR: &str = "IoError";
 const RATE_LIMITED_ERROR: &str = "RateLimitedError";
+const RATE_LIMIT_CHALLENGE_ERROR: &str = "RateLimitChallengeError";
 const UNSUPPORTED_MEDIA_INPUT: &str = "UnsupportedMediaInput";
 
 impl SignalNodeError for SignalProtocolError {
@@ -407,12 +410,32 @@ impl SignalNodeError for libsignal_net::chat::ConnectError {
         operation_name: &str,
     ) -> Handle<'a, JsError> {
         let (name, properties) = match self {
-            Self::AppExpired => (Some("AppExpired"), None),
+            Self::AppExpired {
+                minimum_version,
+                upgrade_url,
+            } => {
+                let message = self.to_string();
+                return app_expired_error(
+                    cx,
+                    module,
+                    &message,
+                    minimum_version,
+                    upgrade_url,
+                    operation_name,
+                );
+            }
             Self::DeviceDeregistered => (Some("DeviceDelinked"), None),
-            Self::RetryLater(retry_later) => rate_limited_error(retry_later),
+            Self::ProxyFailure(_) => (Some("ProxyFailure"), None),
+            Self::RetryLater { retry_later, .. } => {
+                let retry_at = self.retry_at_system_time();
+                rate_limited_error(retry_later, retry_at)
+            }
+            Self::RateLimitChallenge(challenge) => {
+                return rate_limit_challenge_error(cx, module, challenge, operation_name)
+            }
             Self::WebSocket(_)
-            | Self::Timeout
-            | Self::AllAttemptsFailed
+            | Self::Timeout { .. }
+            | Self::AllAttemptsFailed { .. }
             | Self::InvalidConnectionConfiguration =>
             // TODO: Distinguish retryable errors from proper failures?
             {
@@ -439,11 +462,16 @@ impl SignalNodeError for libsignal_net::chat::SendError {
         operation_name: &str,
     ) -> Handle<'a, JsError> {
         let name = match self {
-            Self::Disconnected => Some("ChatServiceInactive"),
+            // Idle timeouts are routine; the app only needs to reconnect.
+            Self::Disconnected | Self::ConnectionIdleTimeout => Some("ChatServiceInactive"),
+            Self::ConnectionLost { close_code } => {
+                let message = self.to_string();
+                return connection_lost_error(cx, module, &message, close_code, operation_name);
+            }
             Self::WebSocket(_)
             | Self::IncomingDataInvalid
             | Self::RequestHasInvalidHeader
-            | Self::RequestTimedOut =>
+            | Self::RequestTimedOut { .. } =>
             // TODO: Distinguish retryable errors from proper failures?
             {
                 Some(IO_ERROR)
@@ -489,7 +517,10 @@ impl SignalNodeError for libsignal_net::cdsi::LookupError {
         operation_name: &str,
     ) -> Handle<'a, JsError> {
         let (name, make_extra_props) = match self {
-            Self::RateLimited(retry_later) => rate_limited_error(retry_later),
+            Self::RateLimited(retry_later) => rate_limited_error(retry_later, None),
+            Self::RateLimitChallenge(challenge) => {
+                return rate_limit_challenge_error(cx, module, challenge, operation_name)
+            }
             Self::AttestationError(e) => return e.into_throwable(cx, module, operation_name),
             Self::InvalidArgument { server_reason: _ } => (None, None),
             Self::InvalidToken => (Some("CdsiInvalidToken"), None),
@@ -516,6 +547,7 @@ impl SignalNodeError for libsignal_net::cdsi::LookupError {
 
 fn rate_limited_error<'a, C: Context<'a>>(
     retry_later: RetryLater,
+    retry_at: Option<SystemTime>,
 ) -> (
     Option<&'a str>,
     Option<impl Fn(&mut C) -> JsResult<'a, JsValue>>,
@@ -526,11 +558,100 @@ fn rate_limited_error<'a, C: Context<'a>>(
             let props = cx.empty_object();
             let retry_after = retry_later.retry_after_seconds.convert_into(cx)?;
             props.set(cx, "retryAfterSecs", retry_after)?;
+            if let Some(retry_at) = retry_at {
+                let millis = retry_at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as f64;
+                let retry_at = cx.number(millis);
+                props.set(cx, "retryAtMillis", retry_at)?;
+            }
             Ok(props.upcast())
         }),
     )
 }
 
+fn rate_limit_challenge_error<'a, C: Context<'a>>(
+    cx: &mut C,
+    module: Handle<'a, JsObject>,
+    challenge: RateLimitChallenge,
+    operation_name: &str,
+) -> Handle<'a, JsError> {
+    let message = challenge.to_string();
+    let RateLimitChallenge { token, options } = challenge;
+    let make_props = move |cx: &mut C| {
+        let props = cx.empty_object();
+        let token = cx.string(token);
+        props.set(cx, "token", token)?;
+        let options = options.into_boxed_slice().convert_into(cx)?;
+        props.set(cx, "options", options)?;
+        Ok(props.upcast())
+    };
+    new_js_error(
+        cx,
+        module,
+        Some(RATE_LIMIT_CHALLENGE_ERROR),
+        &message,
+        operation_name,
+        make_props,
+    )
+}
+
+fn connection_lost_error<'a, C: Context<'a>>(
+    cx: &mut C,
+    module: Handle<'a, JsObject>,
+    message: &str,
+    close_code: Option<u16>,
+    operation_name: &str,
+) -> Handle<'a, JsError> {
+    let make_props = move |cx: &mut C| {
+        let props = cx.empty_object();
+        if let Some(close_code) = close_code {
+            let close_code = cx.number(close_code);
+            props.set(cx, "closeCode", close_code)?;
+        }
+        Ok(props.upcast())
+    };
+    new_js_error(
+        cx,
+        module,
+        Some("ConnectionLost"),
+        message,
+        operation_name,
+        make_props,
+    )
+}
+
+fn app_expired_error<'a, C: Context<'a>>(
+    cx: &mut C,
+    module: Handle<'a, JsObject>,
+    message: &str,
+    minimum_version: Option<String>,
+    upgrade_url: Option<String>,
+    operation_name: &str,
+) -> Handle<'a, JsError> {
+    let make_props = move |cx: &mut C| {
+        let props = cx.empty_object();
+        if let Some(minimum_version) = minimum_version {
+            let minimum_version = cx.string(minimum_version);
+            props.set(cx, "minimumVersion", minimum_version)?;
+        }
+        if let Some(upgrade_url) = upgrade_url {
+            let upgrade_url = cx.string(upgrade_url);
+            props.set(cx, "upgradeUrl", upgrade_url)?;
+        }
+        Ok(props.upcast())
+    };
+    new_js_error(
+        cx,
+        module,
+        Some("AppExpired"),
+        message,
+        operation_name,
+        make_props,
+    )
+}
+
 impl SignalNodeError for CancellationError {
     fn into_throwable<'a, C: Context<'a>>(
         self,
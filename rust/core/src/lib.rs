@@ -7,6 +7,7 @@ mod address;
 // Not exporting the members because they have overly-generic names.
 pub mod curve;
 mod e164;
+mod unidentified_access_key;
 mod version;
 
 pub use address::{
@@ -14,4 +15,5 @@ pub use address::{
     WrongKindOfServiceIdError,
 };
 pub use e164::E164;
+pub use unidentified_access_key::{InvalidUnidentifiedAccessKey, UnidentifiedAccessKey};
 pub use version::VERSION;
@@ -0,0 +1,125 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use subtle::ConstantTimeEq;
+
+/// A key derived from a user's profile key that proves the holder may send
+/// them sealed-sender messages or look them up by phone number.
+///
+/// Compared in constant time, and never printed by [`Debug`].
+#[derive(Copy, Clone, derive_more::From, derive_more::Into)]
+pub struct UnidentifiedAccessKey([u8; UnidentifiedAccessKey::LEN]);
+
+#[derive(Debug, displaydoc::Display, PartialEq, Eq)]
+pub enum InvalidUnidentifiedAccessKey {
+    /// unidentified access key must be 16 bytes, not {0}
+    WrongLength(usize),
+    /// unidentified access key is not valid base64
+    InvalidBase64,
+}
+
+impl std::error::Error for InvalidUnidentifiedAccessKey {}
+
+impl UnidentifiedAccessKey {
+    pub const LEN: usize = 16;
+
+    pub const fn new(bytes: [u8; Self::LEN]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; Self::LEN] {
+        &self.0
+    }
+
+    /// Encodes the key as padded standard base64, as used in chat server
+    /// requests.
+    pub fn to_base64(&self) -> String {
+        BASE64_STANDARD.encode(self.0)
+    }
+
+    /// The inverse of [`Self::to_base64`].
+    pub fn from_base64(encoded: &str) -> Result<Self, InvalidUnidentifiedAccessKey> {
+        let bytes = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|_| InvalidUnidentifiedAccessKey::InvalidBase64)?;
+        bytes.as_slice().try_into()
+    }
+}
+
+impl TryFrom<&[u8]> for UnidentifiedAccessKey {
+    type Error = InvalidUnidentifiedAccessKey;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        value
+            .try_into()
+            .map(Self)
+            .map_err(|_| InvalidUnidentifiedAccessKey::WrongLength(value.len()))
+    }
+}
+
+impl ConstantTimeEq for UnidentifiedAccessKey {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for UnidentifiedAccessKey {
+    fn eq(&self, other: &Self) -> bool {
+        bool::from(self.ct_eq(other))
+    }
+}
+
+impl Eq for UnidentifiedAccessKey {}
+
+impl std::fmt::Debug for UnidentifiedAccessKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UnidentifiedAccessKey(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use hex_literal::hex;
+
+    use super::*;
+
+    const KEY: [u8; 16] = hex!("c6f7c258c24d69538ea553b4a943c8d9");
+
+    #[test]
+    fn from_slice_checks_length() {
+        assert_matches!(
+            UnidentifiedAccessKey::try_from(&KEY[..]),
+            Ok(key) if key.as_bytes() == &KEY
+        );
+        assert_eq!(
+            UnidentifiedAccessKey::try_from(&KEY[1..]),
+            Err(InvalidUnidentifiedAccessKey::WrongLength(15))
+        );
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let key = UnidentifiedAccessKey::from(KEY);
+        let encoded = key.to_base64();
+        assert_eq!(encoded, "xvfCWMJNaVOOpVO0qUPI2Q==");
+        assert_eq!(UnidentifiedAccessKey::from_base64(&encoded), Ok(key));
+        assert_eq!(
+            UnidentifiedAccessKey::from_base64("not base64!"),
+            Err(InvalidUnidentifiedAccessKey::InvalidBase64)
+        );
+        assert_eq!(
+            UnidentifiedAccessKey::from_base64("AAAA"),
+            Err(InvalidUnidentifiedAccessKey::WrongLength(3))
+        );
+    }
+
+    #[test]
+    fn debug_is_redacted() {
+        let key = UnidentifiedAccessKey::from(KEY);
+        assert_eq!(format!("{key:?}"), "UnidentifiedAccessKey(<redacted>)");
+    }
+}
@@ -0,0 +1,213 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Packaging of key transparency state for transfer to another device.
+//!
+//! The exported form is
+//!
+//! ```text
+//! magic (4 bytes) | version (1 byte) | payload length (u32, big-endian) | payload | checksum
+//! ```
+//!
+//! where the payload is an `ExportedState` protobuf message and the checksum
+//! is the SHA-256 digest of everything before it.
+
+use prost::Message as _;
+use sha2::{Digest as _, Sha256};
+
+use crate::proto::ExportedState;
+use crate::{AccountData, LastTreeHead, StoredAccountData};
+
+const MAGIC: &[u8; 4] = b"SKTS";
+const VERSION: u8 = 1;
+const CHECKSUM_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq, displaydoc::Display)]
+pub enum ImportError {
+    /// Not exported key transparency state
+    NotExportedState,
+    /// Unsupported export version {0}
+    UnsupportedVersion(u8),
+    /// Exported state is truncated
+    Truncated,
+    /// Exported state does not match its checksum
+    BadChecksum,
+    /// Invalid exported state: {0}
+    InvalidContents(String),
+}
+
+impl std::error::Error for ImportError {}
+
+/// Packages `account_data` and the last distinguished tree head for
+/// [`import_state`] on another device.
+pub fn export_state(account_data: AccountData, distinguished: LastTreeHead) -> Vec<u8> {
+    let payload = ExportedState {
+        account_data: Some(account_data.into()),
+        last_distinguished_tree_head: Some(distinguished.into()),
+    }
+    .encode_to_vec();
+    let payload_len = u32::try_from(payload.len()).expect("state is far smaller than 4GiB");
+
+    let mut exported = Vec::with_capacity(MAGIC.len() + 1 + 4 + payload.len() + CHECKSUM_LEN);
+    exported.extend_from_slice(MAGIC);
+    exported.push(VERSION);
+    exported.extend_from_slice(&payload_len.to_be_bytes());
+    exported.extend_from_slice(&payload);
+    let checksum = Sha256::digest(&exported);
+    exported.extend_from_slice(&checksum);
+    exported
+}
+
+/// Unpacks state produced by [`export_state`], returning the account data and
+/// the last distinguished tree head.
+pub fn import_state(bytes: &[u8]) -> Result<(AccountData, LastTreeHead), ImportError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return Err(if MAGIC.starts_with(bytes) {
+            ImportError::Truncated
+        } else {
+            ImportError::NotExportedState
+        });
+    };
+    let (&version, rest) = rest.split_first().ok_or(ImportError::Truncated)?;
+    if version != VERSION {
+        return Err(ImportError::UnsupportedVersion(version));
+    }
+    let (payload_len, rest) = rest
+        .split_first_chunk::<4>()
+        .ok_or(ImportError::Truncated)?;
+    let payload_len = usize::try_from(u32::from_be_bytes(*payload_len)).expect("u32 fits in usize");
+    let expected_len = payload_len
+        .checked_add(CHECKSUM_LEN)
+        .ok_or(ImportError::Truncated)?;
+    if rest.len() < expected_len {
+        return Err(ImportError::Truncated);
+    }
+    if rest.len() > expected_len {
+        return Err(ImportError::InvalidContents(
+            "trailing data after checksum".to_owned(),
+        ));
+    }
+
+    let (checked, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if Sha256::digest(checked).as_slice() != checksum {
+        return Err(ImportError::BadChecksum);
+    }
+    let payload = &rest[..payload_len];
+
+    let ExportedState {
+        account_data,
+        last_distinguished_tree_head,
+    } = ExportedState::decode(payload).map_err(|e| ImportError::InvalidContents(e.to_string()))?;
+
+    let account_data: StoredAccountData = account_data
+        .ok_or_else(|| ImportError::InvalidContents("missing account data".to_owned()))?;
+    account_data.validate().map_err(|problems| {
+        let problems: Vec<_> = problems.iter().map(ToString::to_string).collect();
+        ImportError::InvalidContents(problems.join("; "))
+    })?;
+    let account_data = AccountData::try_from(account_data)
+        .map_err(|e| ImportError::InvalidContents(e.to_string()))?;
+
+    let distinguished = last_distinguished_tree_head
+        .and_then(|stored| stored.into_last_tree_head())
+        .ok_or_else(|| {
+            ImportError::InvalidContents("missing distinguished tree head".to_owned())
+        })?;
+
+    Ok((account_data, distinguished))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use assert_matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{MonitoringData, TreeHead};
+
+    fn test_tree_head(tree_size: u64) -> LastTreeHead {
+        (
+            TreeHead {
+                tree_size,
+                timestamp: 1_700_000_000_000,
+                signature: vec![0xaa; 64],
+            },
+            [0x55; 32],
+        )
+    }
+
+    fn test_account_data() -> AccountData {
+        AccountData {
+            aci: MonitoringData {
+                index: [0x11; 32],
+                pos: 10,
+                ptrs: HashMap::from([(12, 0)]),
+                owned: true,
+                search_key_hash: None,
+            },
+            e164: None,
+            username_hash: None,
+            last_tree_head: test_tree_head(42),
+        }
+    }
+
+    fn exported() -> Vec<u8> {
+        export_state(test_account_data(), test_tree_head(40))
+    }
+
+    #[test]
+    fn round_trip() {
+        let (account_data, distinguished) = import_state(&exported()).expect("valid");
+        assert_eq!(account_data, test_account_data());
+        assert_eq!(distinguished, test_tree_head(40));
+    }
+
+    #[test_case(|_| 0; "empty")]
+    #[test_case(|_| 3; "partial magic")]
+    #[test_case(|_| 7; "partial length")]
+    #[test_case(|_| 20; "partial payload")]
+    #[test_case(|len| len - 1; "missing last byte of checksum")]
+    fn truncated(truncated_len: fn(usize) -> usize) {
+        let exported = exported();
+        let len = truncated_len(exported.len());
+        assert_eq!(import_state(&exported[..len]), Err(ImportError::Truncated));
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut exported = exported();
+        let payload_byte = MAGIC.len() + 1 + 4 + 2;
+        exported[payload_byte] ^= 0x01;
+        assert_eq!(import_state(&exported), Err(ImportError::BadChecksum));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut exported = exported();
+        exported[MAGIC.len()] = VERSION + 1;
+        assert_eq!(
+            import_state(&exported),
+            Err(ImportError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn not_exported_state() {
+        let stored = StoredAccountData::from(test_account_data()).encode_to_vec();
+        assert_eq!(import_state(&stored), Err(ImportError::NotExportedState));
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut exported = exported();
+        exported.push(0);
+        assert_matches!(
+            import_state(&exported),
+            Err(ImportError::InvalidContents(_))
+        );
+    }
+}
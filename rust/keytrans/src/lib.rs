@@ -6,29 +6,38 @@
 #![cfg_attr(not(test), warn(clippy::unwrap_used))]
 
 mod commitments;
+mod export;
 mod guide;
 mod implicit;
 mod left_balanced;
 mod log;
 mod prefix;
 mod proto;
+mod rotation;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testutil;
 mod verify;
 mod vrf;
 
 use std::collections::HashMap;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 pub use ed25519_dalek::VerifyingKey;
+pub use export::{export_state, import_state, ImportError};
+use prost::Message as _;
 pub use proto::{
-    ChatMonitorResponse, CondensedTreeSearchResponse,
-    DistinguishedResponse as ChatDistinguishedResponse, FullTreeHead, MonitorKey, MonitorProof,
-    MonitorRequest, MonitorResponse, SearchResponse as ChatSearchResponse, StoredAccountData,
-    StoredMonitoringData, StoredTreeHead, TreeHead, UpdateRequest, UpdateResponse,
+    key_rotation, AuditorTreeHead, ChatMonitorResponse, CondensedTreeSearchResponse,
+    DistinguishedResponse as ChatDistinguishedResponse, FullTreeHead, KeyRotation, MonitorKey,
+    MonitorProof, MonitorRequest, MonitorResponse, SearchResponse as ChatSearchResponse,
+    SignedKeyRotation, StoredAccountData, StoredKeyRotations, StoredMonitoringData, StoredTreeHead,
+    TreeHead, UpdateRequest, UpdateResponse,
 };
-pub use verify::Error;
+pub use rotation::{KeyRotationError, KeyRotations, RotatableKey, RotatedKey};
+use sha2::{Digest as _, Sha256};
 use verify::{
     truncate_search_response, verify_distinguished, verify_monitor, verify_search, verify_update,
 };
+pub use verify::{Error, MAX_TREE_HEAD_AGE, MAX_TREE_HEAD_CLOCK_SKEW};
 pub use vrf::PublicKey as VrfPublicKey;
 
 /// DeploymentMode specifies the way that a transparency log is deployed.
@@ -55,16 +64,54 @@ impl DeploymentMode {
             DeploymentMode::ThirdPartyAuditing(key) => Some(key),
         }
     }
+
+    fn get_associated_key_mut(&mut self) -> Option<&mut VerifyingKey> {
+        match self {
+            DeploymentMode::ContactMonitoring => None,
+            DeploymentMode::ThirdPartyManagement(key) => Some(key),
+            DeploymentMode::ThirdPartyAuditing(key) => Some(key),
+        }
+    }
 }
 
 pub type TreeRoot = [u8; 32];
 pub type LastTreeHead = (TreeHead, TreeRoot);
 
 impl StoredTreeHead {
+    /// Returns `None` if the tree head is missing or the root is not exactly
+    /// 32 bytes long.
     pub fn into_last_tree_head(self) -> Option<LastTreeHead> {
         let StoredTreeHead { tree_head, root } = self;
         Some((tree_head?, root.try_into().ok()?))
     }
+
+    /// Decodes a tree head persisted with [`Self::encode_last_tree_head`].
+    ///
+    /// Returns `Ok(None)` if the bytes are a valid `StoredTreeHead` message
+    /// that doesn't hold a valid tree head (see [`Self::into_last_tree_head`]).
+    pub fn decode_last_tree_head(bytes: &[u8]) -> Result<Option<LastTreeHead>, prost::DecodeError> {
+        Ok(Self::decode(bytes)?.into_last_tree_head())
+    }
+
+    /// Serializes `last_tree_head` as a `StoredTreeHead` message, for
+    /// persisting.
+    pub fn encode_last_tree_head(last_tree_head: LastTreeHead) -> Vec<u8> {
+        Self::from(last_tree_head).encode_to_vec()
+    }
+}
+
+/// Conversion of a [`LastTreeHead`] from its persisted form.
+///
+/// [`LastTreeHead`] is a tuple, so this can't be an inherent method.
+pub trait LastTreeHeadExt: Sized {
+    /// See [`StoredTreeHead::into_last_tree_head`].
+    fn from_stored(stored: StoredTreeHead) -> Option<Self>;
+}
+
+impl LastTreeHeadExt for LastTreeHead {
+    fn from_stored(stored: StoredTreeHead) -> Option<Self> {
+        stored.into_last_tree_head()
+    }
 }
 
 impl From<LastTreeHead> for StoredTreeHead {
@@ -94,6 +141,35 @@ pub struct MonitorContext<'a> {
 pub struct VerifiedSearchResult {
     pub value: Vec<u8>,
     pub state_update: SearchStateUpdate,
+    pub consistency: TreeHeadConsistency,
+}
+
+#[derive(Clone, Debug)]
+pub struct VerifiedMonitorResult {
+    pub state_update: MonitorStateUpdate,
+    pub consistency: TreeHeadConsistency,
+}
+
+/// How a verified tree head was checked against one of the tree heads the
+/// client already had.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsistencyCheck {
+    /// The earlier tree head is smaller, and the server's (non-empty)
+    /// consistency proof against it was verified.
+    Proven,
+    /// The earlier tree head is the same size, so no proof was needed; its
+    /// root and timestamp were checked to be the same instead.
+    SameSize,
+    /// There was no earlier tree head to check against.
+    NoBaseline,
+}
+
+/// The [`ConsistencyCheck`]s against the client's last tree head and last
+/// distinguished tree head.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreeHeadConsistency {
+    pub last: ConsistencyCheck,
+    pub distinguished: ConsistencyCheck,
 }
 
 impl VerifiedSearchResult {
@@ -124,6 +200,21 @@ pub struct LocalStateUpdate<T> {
     pub monitoring_data: T,
 }
 
+impl<T> LocalStateUpdate<T> {
+    /// The new tree head, in the form to be persisted.
+    ///
+    /// Any monitoring data is dropped; it is stored separately, as part of
+    /// [`StoredAccountData`].
+    pub fn into_stored(self) -> StoredTreeHead {
+        let Self {
+            tree_head,
+            tree_root,
+            monitoring_data: _,
+        } = self;
+        StoredTreeHead::from((tree_head, tree_root))
+    }
+}
+
 pub type SearchStateUpdate = LocalStateUpdate<Option<MonitoringData>>;
 pub type MonitorStateUpdate = LocalStateUpdate<HashMap<Vec<u8>, MonitoringData>>;
 
@@ -173,6 +264,7 @@ impl<'a> FullSearchResponse<'a> {
 }
 
 /// Key transparency main API entrypoint
+#[derive(Clone)]
 pub struct KeyTransparency {
     /// Key transparency system configuration
     pub config: PublicConfig,
@@ -191,7 +283,7 @@ impl KeyTransparency {
         now: SystemTime,
     ) -> Result<VerifiedSearchResult, verify::Error> {
         let unverified_value = response.condensed.value.as_ref().map(|v| v.value.clone());
-        let state_update =
+        let (state_update, consistency) =
             verify_search(&self.config, request, response, context, force_monitor, now)?;
         Ok(VerifiedSearchResult {
             // the value has now been verified
@@ -199,6 +291,7 @@ impl KeyTransparency {
                 "unverified_value is not set".to_string(),
             ))?,
             state_update,
+            consistency,
         })
     }
 
@@ -233,8 +326,13 @@ impl KeyTransparency {
         response: &'a MonitorResponse,
         context: MonitorContext,
         now: SystemTime,
-    ) -> Result<MonitorStateUpdate, verify::Error> {
-        verify_monitor(&self.config, request, response, context, now)
+    ) -> Result<VerifiedMonitorResult, verify::Error> {
+        let (state_update, consistency) =
+            verify_monitor(&self.config, request, response, context, now)?;
+        Ok(VerifiedMonitorResult {
+            state_update,
+            consistency,
+        })
     }
 
     /// Checks that the output of an Update operation is valid and updates the
@@ -262,9 +360,24 @@ pub struct MonitoringData {
     pub ptrs: HashMap<u64, u32>,
     /// Whether this client owns the key.
     pub owned: bool,
+    /// SHA-256 of the search key, recorded by the first search that sees the
+    /// data. `None` for data saved before it was recorded.
+    pub search_key_hash: Option<[u8; 32]>,
 }
 
 impl MonitoringData {
+    /// The hash recorded in [`Self::search_key_hash`] for `search_key`.
+    pub fn hash_search_key(search_key: &[u8]) -> [u8; 32] {
+        Sha256::digest(search_key).into()
+    }
+
+    /// Whether this data is known to be for `search_key`.
+    ///
+    /// Data without a recorded hash isn't known to be for any key.
+    pub fn is_for_search_key(&self, search_key: &[u8]) -> bool {
+        self.search_key_hash == Some(Self::hash_search_key(search_key))
+    }
+
     /// The smallest tree size where monitoring would be valuable.
     pub fn next_monitor(&self) -> u64 {
         implicit::next_monitor(&self.entries())
@@ -303,6 +416,7 @@ impl From<MonitoringData> for StoredMonitoringData {
             pos: value.pos,
             ptrs: value.ptrs,
             owned: value.owned,
+            search_key_hash: value.search_key_hash.map(Vec::from).unwrap_or_default(),
         }
     }
 }
@@ -314,6 +428,8 @@ impl From<StoredMonitoringData> for MonitoringData {
             pos: value.pos,
             ptrs: value.ptrs,
             owned: value.owned,
+            // Anything but a hash is treated as not having been recorded.
+            search_key_hash: value.search_key_hash.try_into().ok(),
         }
     }
 }
@@ -346,7 +462,7 @@ impl TryFrom<StoredAccountData> for AccountData {
             username_hash: username_hash.map(MonitoringData::from),
             last_tree_head: last_tree_head
                 .into_last_tree_head()
-                .expect("valid tree head"),
+                .ok_or(Error::InvalidProofElement)?,
         })
     }
 }
@@ -367,3 +483,643 @@ impl From<AccountData> for StoredAccountData {
         }
     }
 }
+
+/// Inspecting persisted account data without converting it to [`AccountData`].
+///
+/// These never fail, so they can be used on partial or corrupted data.
+impl StoredAccountData {
+    /// The size of the tree the data was last verified against, if there is a
+    /// tree head.
+    pub fn tree_size(&self) -> Option<u64> {
+        self.last_tree_head
+            .as_ref()?
+            .tree_head
+            .as_ref()
+            .map(|head| head.tree_size)
+    }
+
+    pub fn has_e164(&self) -> bool {
+        self.e164.is_some()
+    }
+
+    pub fn has_username_hash(&self) -> bool {
+        self.username_hash.is_some()
+    }
+
+    /// The latest known log position of the ACI, if there is any ACI
+    /// monitoring data.
+    pub fn aci_entry_position(&self) -> Option<u64> {
+        self.aci.as_ref()?.ptrs.keys().max().copied()
+    }
+
+    /// Checks everything that converting to [`AccountData`] (and then using
+    /// it) relies on, reporting every problem found rather than just the
+    /// first.
+    pub fn validate(&self) -> Result<(), Vec<StoredAccountDataProblem>> {
+        let Self {
+            aci,
+            e164,
+            username_hash,
+            last_tree_head,
+        } = self;
+        let mut problems = vec![];
+
+        match last_tree_head {
+            None => problems.push(StoredAccountDataProblem::MissingField(
+                StoredAccountDataField::LastTreeHead,
+            )),
+            Some(StoredTreeHead { tree_head, root }) => {
+                if tree_head.is_none() {
+                    problems.push(StoredAccountDataProblem::MissingField(
+                        StoredAccountDataField::LastTreeHead,
+                    ));
+                }
+                if root.len() != 32 {
+                    problems.push(StoredAccountDataProblem::WrongLength {
+                        field: StoredAccountDataField::LastTreeRoot,
+                        len: root.len(),
+                    });
+                }
+            }
+        }
+
+        if aci.is_none() {
+            problems.push(StoredAccountDataProblem::MissingField(
+                StoredAccountDataField::Aci,
+            ));
+        }
+        for (name, data) in [
+            (StoredAccountDataField::Aci, aci),
+            (StoredAccountDataField::E164, e164),
+            (StoredAccountDataField::UsernameHash, username_hash),
+        ] {
+            let Some(data) = data else {
+                continue;
+            };
+            if data.index.len() != 32 {
+                problems.push(StoredAccountDataProblem::WrongLength {
+                    field: name,
+                    len: data.index.len(),
+                });
+            }
+            if data.ptrs.is_empty() {
+                problems.push(StoredAccountDataProblem::NoVersions(name));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+/// A problem found by [`StoredAccountData::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+pub enum StoredAccountDataProblem {
+    /// {0} is missing
+    MissingField(StoredAccountDataField),
+    /// {field} has length {len}, expected 32
+    WrongLength {
+        field: StoredAccountDataField,
+        len: usize,
+    },
+    /// No versions recorded for {0}
+    NoVersions(StoredAccountDataField),
+}
+
+/// The part of [`StoredAccountData`] a [`StoredAccountDataProblem`] is about.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+pub enum StoredAccountDataField {
+    /// ACI
+    Aci,
+    /// E.164
+    E164,
+    /// Username hash
+    UsernameHash,
+    /// Last tree head
+    LastTreeHead,
+    /// Last tree root
+    LastTreeRoot,
+}
+
+impl AccountData {
+    /// The time recorded in the last verified tree head.
+    ///
+    /// This is the server's time when the tree head was signed, so it says how
+    /// stale the account data is regardless of when it was last saved locally.
+    pub fn last_tree_head_timestamp(&self) -> SystemTime {
+        let (tree_head, _) = &self.last_tree_head;
+        let millis = u64::try_from(tree_head.timestamp).unwrap_or_default();
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    /// The tree size at which monitoring this account would first be useful.
+    fn next_monitor_tree_size(&self) -> u64 {
+        [
+            Some(&self.aci),
+            self.e164.as_ref(),
+            self.username_hash.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(MonitoringData::next_monitor)
+        .min()
+        .expect("ACI is always present")
+    }
+
+    /// Suggests when this account should next be monitored.
+    ///
+    /// `latest_tree_size` is the size of the most recent tree head the app
+    /// knows about (such as the distinguished tree head), if any. If the log
+    /// has grown enough since the last tree head to make monitoring
+    /// worthwhile, the next monitor is due as soon as `policy` allows.
+    /// Otherwise it's due [`MonitorPolicy::max_interval`] after the last tree
+    /// head's timestamp.
+    ///
+    /// The result is never earlier than `now + min_interval` or later than
+    /// `now + max_interval`.
+    pub fn suggested_next_monitor(
+        &self,
+        now: SystemTime,
+        policy: &MonitorPolicy,
+        latest_tree_size: Option<u64>,
+    ) -> SystemTime {
+        let MonitorPolicy {
+            min_interval,
+            max_interval,
+            tree_growth_threshold,
+        } = *policy;
+        let earliest = saturating_add(now, min_interval);
+        let latest = saturating_add(now, max_interval.max(min_interval));
+
+        let (last_tree_head, _) = &self.last_tree_head;
+        let has_grown_enough = latest_tree_size.is_some_and(|size| {
+            size.saturating_sub(last_tree_head.tree_size) >= tree_growth_threshold
+                || size >= self.next_monitor_tree_size()
+        });
+        if has_grown_enough {
+            return earliest;
+        }
+
+        saturating_add(self.last_tree_head_timestamp(), max_interval).clamp(earliest, latest)
+    }
+
+    /// Summarizes what changed between two monitoring passes over the same
+    /// account, so the app can decide whether to tell the user.
+    pub fn diff(old: &AccountData, new: &AccountData) -> AccountDataDiff {
+        let (old_head, _) = &old.last_tree_head;
+        let (new_head, _) = &new.last_tree_head;
+        let tree_size_delta = i128::from(new_head.tree_size) - i128::from(old_head.tree_size);
+
+        AccountDataDiff {
+            aci: MonitoredKeyDiff::between(Some(&old.aci), Some(&new.aci))
+                .expect("ACI is always present"),
+            e164: MonitoredKeyDiff::between(old.e164.as_ref(), new.e164.as_ref()),
+            username_hash: MonitoredKeyDiff::between(
+                old.username_hash.as_ref(),
+                new.username_hash.as_ref(),
+            ),
+            tree_size_delta: tree_size_delta
+                .clamp(i64::MIN.into(), i64::MAX.into())
+                .try_into()
+                .expect("clamped"),
+        }
+    }
+}
+
+/// The result of [`AccountData::diff`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountDataDiff {
+    pub aci: MonitoredKeyDiff,
+    /// `None` if the E.164 was monitored in neither pass.
+    pub e164: Option<MonitoredKeyDiff>,
+    /// `None` if the username hash was monitored in neither pass.
+    pub username_hash: Option<MonitoredKeyDiff>,
+    /// How much the tree grew between the two passes' tree heads.
+    ///
+    /// This is only negative if the newer data was verified against an older
+    /// tree head, such as after restoring from a backup.
+    pub tree_size_delta: i64,
+}
+
+impl AccountDataDiff {
+    /// Whether a key started or stopped being monitored, or was replaced by a
+    /// different one.
+    pub fn monitored_keys_changed(&self) -> bool {
+        [Some(self.aci), self.e164, self.username_hash]
+            .into_iter()
+            .flatten()
+            .any(|diff| !matches!(diff, MonitoredKeyDiff::Kept { .. }))
+    }
+
+    /// Whether a new entry was seen for any key monitored in both passes.
+    pub fn any_advanced(&self) -> bool {
+        [Some(self.aci), self.e164, self.username_hash]
+            .into_iter()
+            .flatten()
+            .any(|diff| diff == MonitoredKeyDiff::Kept { advanced: true })
+    }
+}
+
+/// How a single monitored key changed between two [`AccountData`]s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonitoredKeyDiff {
+    /// The key is monitored in the new data but wasn't in the old.
+    Added,
+    /// The key was monitored in the old data but isn't in the new.
+    Removed,
+    /// A different search key is being monitored, such as a new phone number.
+    ///
+    /// Log positions for different search keys can't be compared, so this
+    /// doesn't say whether anything advanced.
+    Replaced,
+    /// The same search key is monitored in both.
+    Kept {
+        /// Whether the latest known log position for the key moved forward,
+        /// meaning the key has been updated since the old data.
+        advanced: bool,
+    },
+}
+
+impl MonitoredKeyDiff {
+    fn between(old: Option<&MonitoringData>, new: Option<&MonitoringData>) -> Option<Self> {
+        match (old, new) {
+            (None, None) => None,
+            (None, Some(_)) => Some(Self::Added),
+            (Some(_), None) => Some(Self::Removed),
+            (Some(old), Some(new)) if old.index != new.index => Some(Self::Replaced),
+            (Some(old), Some(new)) => Some(Self::Kept {
+                advanced: new.latest_log_position() > old.latest_log_position(),
+            }),
+        }
+    }
+}
+
+/// Like `time + duration`, but saturates at the latest representable time
+/// instead of panicking.
+fn saturating_add(time: SystemTime, duration: Duration) -> SystemTime {
+    if let Some(sum) = time.checked_add(duration) {
+        return sum;
+    }
+    // There's no SystemTime::MAX, so close in on it by halving the step each
+    // time it overshoots.
+    let mut time = time;
+    let mut step = duration;
+    while !step.is_zero() {
+        match time.checked_add(step) {
+            Some(sum) => time = sum,
+            None => step /= 2,
+        }
+    }
+    time
+}
+
+/// Parameters for [`AccountData::suggested_next_monitor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MonitorPolicy {
+    /// Don't monitor more often than this.
+    pub min_interval: Duration,
+    /// Monitor at least this often, measured from the last tree head.
+    pub max_interval: Duration,
+    /// How many entries the log can grow by since the last tree head before
+    /// it's worth monitoring again early.
+    pub tree_growth_threshold: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+
+    fn test_tree_head() -> LastTreeHead {
+        (
+            TreeHead {
+                tree_size: 42,
+                timestamp: 1_700_000_000_000,
+                signature: vec![0xaa; 64],
+            },
+            [0x55; 32],
+        )
+    }
+
+    #[test]
+    fn stored_tree_head_round_trip() {
+        let head = test_tree_head();
+        let encoded = StoredTreeHead::encode_last_tree_head(head.clone());
+        assert_matches!(StoredTreeHead::decode_last_tree_head(&encoded), Ok(Some(decoded)) => {
+            assert_eq!(decoded, head);
+        });
+    }
+
+    #[test]
+    fn state_update_into_stored() {
+        let (tree_head, tree_root) = test_tree_head();
+        let update = SearchStateUpdate {
+            tree_head: tree_head.clone(),
+            tree_root,
+            monitoring_data: None,
+        };
+        let stored = update.into_stored();
+        assert_eq!(stored.tree_head.as_ref(), Some(&tree_head));
+        assert_eq!(stored.root, tree_root);
+        assert_eq!(
+            LastTreeHead::from_stored(stored),
+            Some((tree_head, tree_root))
+        );
+    }
+
+    #[test_case(31; "truncated")]
+    #[test_case(33; "too long")]
+    #[test_case(0; "empty")]
+    fn stored_tree_head_root_must_be_32_bytes(root_len: usize) {
+        let (tree_head, _) = test_tree_head();
+        let stored = StoredTreeHead {
+            tree_head: Some(tree_head),
+            root: vec![0x55; root_len],
+        };
+        assert_eq!(LastTreeHead::from_stored(stored.clone()), None);
+        assert_matches!(
+            StoredTreeHead::decode_last_tree_head(&stored.encode_to_vec()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn stored_tree_head_requires_tree_head() {
+        let stored = StoredTreeHead {
+            tree_head: None,
+            root: vec![0x55; 32],
+        };
+        assert_eq!(LastTreeHead::from_stored(stored), None);
+    }
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    const TEST_POLICY: MonitorPolicy = MonitorPolicy {
+        min_interval: HOUR,
+        max_interval: Duration::from_secs(24 * 60 * 60),
+        tree_growth_threshold: 1000,
+    };
+
+    fn test_account_data(tree_size: u64, timestamp: SystemTime) -> AccountData {
+        let timestamp_millis = timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        AccountData {
+            aci: MonitoringData {
+                index: [0; 32],
+                pos: 1023,
+                // Monitoring won't be needed until the tree reaches 2048.
+                ptrs: HashMap::from([(1023, 1)]),
+                owned: false,
+                search_key_hash: None,
+            },
+            e164: None,
+            username_hash: None,
+            last_tree_head: (
+                TreeHead {
+                    tree_size,
+                    timestamp: timestamp_millis.try_into().unwrap(),
+                    signature: vec![],
+                },
+                [0; 32],
+            ),
+        }
+    }
+
+    #[test]
+    fn last_tree_head_timestamp() {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let account_data = test_account_data(1030, timestamp);
+        assert_eq!(account_data.last_tree_head_timestamp(), timestamp);
+    }
+
+    #[test_case(0, None, 23; "fresh head, unknown tree size")]
+    #[test_case(0, Some(1035), 23; "fresh head, little growth")]
+    #[test_case(0, Some(2040), 1; "fresh head, lots of growth")]
+    #[test_case(20, None, 3; "older head")]
+    #[test_case(30, None, 1; "head older than max interval")]
+    fn suggested_next_monitor(
+        head_age_hours: u32,
+        latest_tree_size: Option<u64>,
+        expected_hours_from_now: u32,
+    ) {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let account_data = test_account_data(1030, now - HOUR * head_age_hours - HOUR);
+        assert_eq!(
+            account_data.suggested_next_monitor(now, &TEST_POLICY, latest_tree_size),
+            now + HOUR * expected_hours_from_now
+        );
+    }
+
+    #[test]
+    fn suggested_next_monitor_with_clock_skew() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        // The tree head claims to be from the future.
+        let account_data = test_account_data(1030, now + 48 * HOUR);
+        assert_eq!(
+            account_data.suggested_next_monitor(now, &TEST_POLICY, None),
+            now + TEST_POLICY.max_interval
+        );
+    }
+
+    #[test]
+    fn suggested_next_monitor_saturates() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let account_data = test_account_data(1030, now - HOUR);
+        let policy = MonitorPolicy {
+            min_interval: Duration::MAX,
+            max_interval: Duration::MAX,
+            ..TEST_POLICY
+        };
+        let next = account_data.suggested_next_monitor(now, &policy, None);
+        assert!(next > now + 1000 * 365 * 24 * HOUR);
+        assert_eq!(next.checked_add(Duration::from_secs(1)), None);
+    }
+
+    fn test_monitoring_data(index: u8, ptrs: &[(u64, u32)]) -> MonitoringData {
+        MonitoringData {
+            index: [index; 32],
+            pos: ptrs[0].0,
+            ptrs: HashMap::from_iter(ptrs.iter().copied()),
+            owned: true,
+            search_key_hash: None,
+        }
+    }
+
+    #[test]
+    fn search_key_hash_is_stored() {
+        let mut data = test_monitoring_data(1, &[(10, 1)]);
+        assert!(!data.is_for_search_key(b"key"), "not recorded yet");
+
+        data.search_key_hash = Some(MonitoringData::hash_search_key(b"key"));
+        let restored = MonitoringData::from(StoredMonitoringData::from(data.clone()));
+        assert_eq!(restored, data);
+        assert!(restored.is_for_search_key(b"key"));
+        assert!(!restored.is_for_search_key(b"other"));
+    }
+
+    #[test_case(None, None => None; "absent in both")]
+    #[test_case(None, Some((1, &[(10, 1)])) => Some(MonitoredKeyDiff::Added); "added")]
+    #[test_case(Some((1, &[(10, 1)])), None => Some(MonitoredKeyDiff::Removed); "removed")]
+    #[test_case(Some((1, &[(10, 1)])), Some((2, &[(10, 1)])) => Some(MonitoredKeyDiff::Replaced); "replaced")]
+    #[test_case(Some((1, &[(10, 1)])), Some((2, &[(5, 1)])) => Some(MonitoredKeyDiff::Replaced); "replaced with earlier position")]
+    #[test_case(Some((1, &[(10, 1)])), Some((1, &[(10, 1)])) => Some(MonitoredKeyDiff::Kept { advanced: false }); "unchanged")]
+    #[test_case(Some((1, &[(10, 1)])), Some((1, &[(10, 1), (20, 2)])) => Some(MonitoredKeyDiff::Kept { advanced: true }); "advanced")]
+    #[test_case(Some((1, &[(10, 1), (20, 2)])), Some((1, &[(10, 1)])) => Some(MonitoredKeyDiff::Kept { advanced: false }); "went backwards")]
+    fn account_data_diff_e164(
+        old: Option<(u8, &[(u64, u32)])>,
+        new: Option<(u8, &[(u64, u32)])>,
+    ) -> Option<MonitoredKeyDiff> {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut old_data = test_account_data(1030, now);
+        old_data.e164 = old.map(|(index, ptrs)| test_monitoring_data(index, ptrs));
+        let mut new_data = test_account_data(1030, now);
+        new_data.e164 = new.map(|(index, ptrs)| test_monitoring_data(index, ptrs));
+
+        let diff = AccountData::diff(&old_data, &new_data);
+        assert_eq!(diff.aci, MonitoredKeyDiff::Kept { advanced: false });
+        assert_eq!(diff.username_hash, None);
+        assert_eq!(
+            diff.monitored_keys_changed(),
+            diff.e164
+                .is_some_and(|e164| !matches!(e164, MonitoredKeyDiff::Kept { .. }))
+        );
+        assert_eq!(
+            diff.any_advanced(),
+            diff.e164 == Some(MonitoredKeyDiff::Kept { advanced: true })
+        );
+        diff.e164
+    }
+
+    #[test_case(1030, 1030 => 0; "same tree")]
+    #[test_case(1030, 2000 => 970; "grown")]
+    #[test_case(2000, 1030 => -970; "older tree head")]
+    fn account_data_diff_tree_size(old_size: u64, new_size: u64) -> i64 {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        AccountData::diff(
+            &test_account_data(old_size, now),
+            &test_account_data(new_size, now),
+        )
+        .tree_size_delta
+    }
+
+    #[test]
+    fn account_data_diff_across_legs() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let old = AccountData {
+            e164: Some(test_monitoring_data(1, &[(10, 1)])),
+            ..test_account_data(1030, now)
+        };
+        let mut new = AccountData {
+            username_hash: Some(test_monitoring_data(2, &[(1025, 1)])),
+            ..test_account_data(1100, now)
+        };
+        new.aci.ptrs.insert(1050, 2);
+
+        assert_eq!(
+            AccountData::diff(&old, &new),
+            AccountDataDiff {
+                aci: MonitoredKeyDiff::Kept { advanced: true },
+                e164: Some(MonitoredKeyDiff::Removed),
+                username_hash: Some(MonitoredKeyDiff::Added),
+                tree_size_delta: 70,
+            }
+        );
+        assert!(AccountData::diff(&old, &new).monitored_keys_changed());
+        assert!(AccountData::diff(&old, &new).any_advanced());
+
+        let same = AccountData::diff(&old, &old);
+        assert!(!same.monitored_keys_changed());
+        assert!(!same.any_advanced());
+    }
+
+    #[test]
+    fn stored_account_data_with_truncated_root_is_rejected() {
+        let (tree_head, _) = test_tree_head();
+        let stored = StoredAccountData {
+            aci: Some(StoredMonitoringData::default()),
+            e164: None,
+            username_hash: None,
+            last_tree_head: Some(StoredTreeHead {
+                tree_head: Some(tree_head),
+                root: vec![0x55; 31],
+            }),
+        };
+        assert_matches!(
+            AccountData::try_from(stored),
+            Err(Error::InvalidProofElement)
+        );
+    }
+
+    fn valid_stored_account_data() -> StoredAccountData {
+        let monitoring_data = StoredMonitoringData {
+            index: vec![0; 32],
+            pos: 10,
+            ptrs: HashMap::from([(10, 1), (25, 2)]),
+            owned: false,
+            search_key_hash: vec![],
+        };
+        StoredAccountData {
+            aci: Some(monitoring_data.clone()),
+            e164: None,
+            username_hash: Some(monitoring_data),
+            last_tree_head: Some(test_tree_head().into()),
+        }
+    }
+
+    #[test]
+    fn stored_account_data_accessors() {
+        let stored = valid_stored_account_data();
+        assert_eq!(stored.tree_size(), Some(42));
+        assert!(!stored.has_e164());
+        assert!(stored.has_username_hash());
+        assert_eq!(stored.aci_entry_position(), Some(25));
+        assert_eq!(stored.validate(), Ok(()));
+
+        let empty = StoredAccountData::default();
+        assert_eq!(empty.tree_size(), None);
+        assert!(!empty.has_e164());
+        assert!(!empty.has_username_hash());
+        assert_eq!(empty.aci_entry_position(), None);
+    }
+
+    #[test]
+    fn stored_account_data_validate_reports_every_problem() {
+        let mut stored = valid_stored_account_data();
+        stored.aci = None;
+        if let Some(username_hash) = &mut stored.username_hash {
+            username_hash.index.truncate(31);
+            username_hash.ptrs.clear();
+        }
+        if let Some(last_tree_head) = &mut stored.last_tree_head {
+            last_tree_head.tree_head = None;
+        }
+
+        assert_eq!(
+            stored.validate(),
+            Err(vec![
+                StoredAccountDataProblem::MissingField(StoredAccountDataField::LastTreeHead),
+                StoredAccountDataProblem::MissingField(StoredAccountDataField::Aci),
+                StoredAccountDataProblem::WrongLength {
+                    field: StoredAccountDataField::UsernameHash,
+                    len: 31
+                },
+                StoredAccountDataProblem::NoVersions(StoredAccountDataField::UsernameHash),
+            ])
+        );
+        assert_eq!(
+            StoredAccountDataProblem::WrongLength {
+                field: StoredAccountDataField::UsernameHash,
+                len: 31
+            }
+            .to_string(),
+            "Username hash has length 31, expected 32"
+        );
+    }
+}
@@ -0,0 +1,540 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Replacement of the log's signing and auditor keys without an app update.
+//!
+//! A rotation is a [`KeyRotation`] message signed by the key it replaces. A
+//! client applies it on top of its built-in [`PublicConfig`] and remembers it
+//! in [`KeyRotations`], so that the same rotation can't be applied twice and
+//! later rotations are checked against the new key.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use prost::Message as _;
+
+use crate::proto::{key_rotation, stored_key_rotations, KeyRotation, SignedKeyRotation};
+use crate::{PublicConfig, StoredKeyRotations};
+
+/// Prepended to the serialized [`KeyRotation`] before signing, so that the
+/// signature can't be mistaken for one over anything else.
+pub(crate) const SIGNATURE_CONTEXT: &[u8] = b"Signal_KeyTransparency_KeyRotation_20250101";
+
+/// Which of a [`PublicConfig`]'s keys a rotation replaces.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+pub enum RotatableKey {
+    /// signing key
+    Signing,
+    /// auditor key
+    Auditor,
+}
+
+/// A key installed by a rotation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RotatedKey {
+    pub counter: u64,
+    pub key: VerifyingKey,
+}
+
+/// The rotations a client has applied, most recent for each key.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyRotations {
+    pub signing: Option<RotatedKey>,
+    pub auditor: Option<RotatedKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, displaydoc::Display)]
+pub enum KeyRotationError {
+    /// Invalid key rotation protobuf: {0}
+    DecodingFailed(prost::DecodeError),
+    /// Key rotation does not say which key it replaces
+    UnknownKey,
+    /// There is no {0} to rotate
+    NoCurrentKey(RotatableKey),
+    /// Key rotation is not signed by the current {0}
+    InvalidSignature(RotatableKey),
+    /// Key rotation counter {counter} is not greater than {last}
+    Replayed { counter: u64, last: u64 },
+    /// Key rotation expired at {0:?}
+    Expired(SystemTime),
+    /// Key rotation replacement key is invalid
+    InvalidNewKey,
+    /// Stored key rotation is invalid
+    InvalidStoredRotation,
+}
+
+impl std::error::Error for KeyRotationError {}
+
+impl From<prost::DecodeError> for KeyRotationError {
+    fn from(err: prost::DecodeError) -> Self {
+        Self::DecodingFailed(err)
+    }
+}
+
+impl KeyRotations {
+    fn get(&self, which: RotatableKey) -> Option<&RotatedKey> {
+        match which {
+            RotatableKey::Signing => self.signing.as_ref(),
+            RotatableKey::Auditor => self.auditor.as_ref(),
+        }
+    }
+
+    fn get_mut(&mut self, which: RotatableKey) -> &mut Option<RotatedKey> {
+        match which {
+            RotatableKey::Signing => &mut self.signing,
+            RotatableKey::Auditor => &mut self.auditor,
+        }
+    }
+
+    /// Replaces the keys in `config` with any that have been rotated.
+    ///
+    /// A rotated auditor key is ignored if `config` doesn't use an auditor.
+    pub fn install(&self, config: &mut PublicConfig) {
+        let Self { signing, auditor } = self;
+        if let Some(RotatedKey { key, .. }) = signing {
+            config.signature_key = *key;
+        }
+        if let (Some(RotatedKey { key, .. }), Some(current)) =
+            (auditor, config.mode.get_associated_key_mut())
+        {
+            *current = *key;
+        }
+    }
+
+    /// Whether `document` is the rotation most recently applied to its key.
+    ///
+    /// The latest rotation stays on offer after it has been applied, and
+    /// applying it again fails because it's signed by the key it replaced.
+    /// This lets callers recognize it instead.
+    pub fn is_applied(&self, document: &SignedKeyRotation) -> bool {
+        let Ok(rotation) = KeyRotation::decode(document.rotation.as_slice()) else {
+            return false;
+        };
+        let which = match rotation.key() {
+            key_rotation::Key::Unknown => return false,
+            key_rotation::Key::Signing => RotatableKey::Signing,
+            key_rotation::Key::Auditor => RotatableKey::Auditor,
+        };
+        self.get(which).is_some_and(|RotatedKey { counter, key }| {
+            *counter == rotation.counter && key.as_bytes().as_slice() == rotation.new_key
+        })
+    }
+
+    /// Checks `document` against the current keys in `config` and, if it's
+    /// valid, installs the new key and records the rotation.
+    ///
+    /// `config` should already have any previous rotations installed (see
+    /// [`Self::install`]). Nothing is changed if the document is rejected.
+    pub fn apply(
+        &mut self,
+        config: &mut PublicConfig,
+        document: &SignedKeyRotation,
+        now: SystemTime,
+    ) -> Result<RotatableKey, KeyRotationError> {
+        let SignedKeyRotation {
+            rotation: serialized,
+            signature,
+        } = document;
+        let rotation = KeyRotation::decode(serialized.as_slice())?;
+        let which = match rotation.key() {
+            key_rotation::Key::Unknown => return Err(KeyRotationError::UnknownKey),
+            key_rotation::Key::Signing => RotatableKey::Signing,
+            key_rotation::Key::Auditor => RotatableKey::Auditor,
+        };
+        let current_key = match which {
+            RotatableKey::Signing => Some(&config.signature_key),
+            RotatableKey::Auditor => config.mode.get_associated_key(),
+        }
+        .ok_or(KeyRotationError::NoCurrentKey(which))?;
+
+        // Check the signature before anything else, so that a forged document
+        // is always reported as such.
+        let signature = Signature::from_slice(signature)
+            .map_err(|_| KeyRotationError::InvalidSignature(which))?;
+        let signed = [SIGNATURE_CONTEXT, serialized.as_slice()].concat();
+        current_key
+            .verify_strict(&signed, &signature)
+            .map_err(|_| KeyRotationError::InvalidSignature(which))?;
+
+        let KeyRotation {
+            key: _,
+            new_key,
+            counter,
+            expires_at,
+        } = rotation;
+        let last = self.get(which).map_or(0, |rotated| rotated.counter);
+        if counter <= last {
+            return Err(KeyRotationError::Replayed { counter, last });
+        }
+        // An expiry too far in the future to represent can't have passed yet.
+        if let Some(expires_at) = UNIX_EPOCH.checked_add(Duration::from_millis(expires_at)) {
+            if now >= expires_at {
+                return Err(KeyRotationError::Expired(expires_at));
+            }
+        }
+        let new_key = parse_key(&new_key).ok_or(KeyRotationError::InvalidNewKey)?;
+
+        match which {
+            RotatableKey::Signing => config.signature_key = new_key,
+            RotatableKey::Auditor => {
+                *config.mode.get_associated_key_mut().expect("checked above") = new_key
+            }
+        }
+        *self.get_mut(which) = Some(RotatedKey {
+            counter,
+            key: new_key,
+        });
+        Ok(which)
+    }
+}
+
+fn parse_key(bytes: &[u8]) -> Option<VerifyingKey> {
+    let key = VerifyingKey::from_bytes(bytes.try_into().ok()?).ok()?;
+    (!key.is_weak()).then_some(key)
+}
+
+impl From<KeyRotations> for StoredKeyRotations {
+    fn from(rotations: KeyRotations) -> Self {
+        let store = |rotated: Option<RotatedKey>| {
+            rotated.map(
+                |RotatedKey { counter, key }| stored_key_rotations::RotatedKey {
+                    counter,
+                    key: key.as_bytes().to_vec(),
+                },
+            )
+        };
+        Self {
+            signing: store(rotations.signing),
+            auditor: store(rotations.auditor),
+        }
+    }
+}
+
+impl TryFrom<StoredKeyRotations> for KeyRotations {
+    type Error = KeyRotationError;
+
+    fn try_from(stored: StoredKeyRotations) -> Result<Self, Self::Error> {
+        let load = |stored: Option<stored_key_rotations::RotatedKey>| {
+            stored
+                .map(|stored_key_rotations::RotatedKey { counter, key }| {
+                    parse_key(&key)
+                        .map(|key| RotatedKey { counter, key })
+                        .ok_or(KeyRotationError::InvalidStoredRotation)
+                })
+                .transpose()
+        };
+        Ok(Self {
+            signing: load(stored.signing)?,
+            auditor: load(stored.auditor)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ed25519_dalek::{Signer as _, SigningKey};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{DeploymentMode, VrfPublicKey};
+
+    const VRF_KEY: [u8; 32] =
+        hex_literal::hex!("1e71563470c1b8a6e0aadf280b6aa96f8ad064674e69b80292ee46d1ab655fcf");
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn config() -> PublicConfig {
+        PublicConfig {
+            mode: DeploymentMode::ThirdPartyAuditing(signing_key(2).verifying_key()),
+            signature_key: signing_key(1).verifying_key(),
+            vrf_key: VrfPublicKey::try_from(VRF_KEY).expect("valid"),
+        }
+    }
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_750_000_000)
+    }
+
+    fn document(
+        signer: &SigningKey,
+        key: key_rotation::Key,
+        new_key: &SigningKey,
+        counter: u64,
+    ) -> SignedKeyRotation {
+        let expires_at = (now() + Duration::from_secs(60))
+            .duration_since(UNIX_EPOCH)
+            .expect("after epoch")
+            .as_millis()
+            .try_into()
+            .expect("fits");
+        let rotation = KeyRotation {
+            key: key.into(),
+            new_key: new_key.verifying_key().as_bytes().to_vec(),
+            counter,
+            expires_at,
+        }
+        .encode_to_vec();
+        let signature = signer
+            .sign(&[SIGNATURE_CONTEXT, rotation.as_slice()].concat())
+            .to_bytes()
+            .to_vec();
+        SignedKeyRotation {
+            rotation,
+            signature,
+        }
+    }
+
+    /// Replaces the signature on `doc` with one from the key with `seed`.
+    fn resign(doc: &mut SignedKeyRotation, seed: u8) {
+        doc.signature = signing_key(seed)
+            .sign(&[SIGNATURE_CONTEXT, doc.rotation.as_slice()].concat())
+            .to_bytes()
+            .to_vec();
+    }
+
+    #[test]
+    fn rotation_installs_new_key() {
+        let mut config = config();
+        let mut rotations = KeyRotations::default();
+        let doc = document(
+            &signing_key(1),
+            key_rotation::Key::Signing,
+            &signing_key(3),
+            1,
+        );
+
+        assert_eq!(
+            rotations.apply(&mut config, &doc, now()),
+            Ok(RotatableKey::Signing)
+        );
+        assert_eq!(config.signature_key, signing_key(3).verifying_key());
+        assert_eq!(
+            rotations,
+            KeyRotations {
+                signing: Some(RotatedKey {
+                    counter: 1,
+                    key: signing_key(3).verifying_key(),
+                }),
+                auditor: None,
+            }
+        );
+
+        // The next rotation must be signed by the new key.
+        let next = document(
+            &signing_key(3),
+            key_rotation::Key::Signing,
+            &signing_key(4),
+            2,
+        );
+        assert_eq!(
+            rotations.apply(&mut config, &next, now()),
+            Ok(RotatableKey::Signing)
+        );
+        assert_eq!(config.signature_key, signing_key(4).verifying_key());
+
+        // Installing the record on a fresh config gives the same keys.
+        let mut reloaded = self::config();
+        rotations.install(&mut reloaded);
+        assert_eq!(reloaded.signature_key, config.signature_key);
+        assert!(reloaded.mode == config.mode);
+    }
+
+    #[test]
+    fn auditor_rotation() {
+        let mut config = config();
+        let mut rotations = KeyRotations::default();
+        let doc = document(
+            &signing_key(2),
+            key_rotation::Key::Auditor,
+            &signing_key(3),
+            7,
+        );
+
+        assert_eq!(
+            rotations.apply(&mut config, &doc, now()),
+            Ok(RotatableKey::Auditor)
+        );
+        assert!(config.mode == DeploymentMode::ThirdPartyAuditing(signing_key(3).verifying_key()));
+        assert_eq!(config.signature_key, signing_key(1).verifying_key());
+
+        let mut contact_monitoring = PublicConfig {
+            mode: DeploymentMode::ContactMonitoring,
+            ..self::config()
+        };
+        assert_eq!(
+            KeyRotations::default().apply(&mut contact_monitoring, &doc, now()),
+            Err(KeyRotationError::NoCurrentKey(RotatableKey::Auditor))
+        );
+    }
+
+    #[test]
+    fn replayed_rotation_is_rejected() {
+        let mut config = config();
+        let mut rotations = KeyRotations::default();
+        let doc = document(
+            &signing_key(1),
+            key_rotation::Key::Signing,
+            &signing_key(3),
+            5,
+        );
+        rotations
+            .apply(&mut config, &doc, now())
+            .expect("first use succeeds");
+
+        // Signed by the old key, which is no longer trusted.
+        assert_eq!(
+            rotations.apply(&mut config, &doc, now()),
+            Err(KeyRotationError::InvalidSignature(RotatableKey::Signing))
+        );
+
+        // Even a properly signed rotation must move the counter forward.
+        let stale = document(
+            &signing_key(3),
+            key_rotation::Key::Signing,
+            &signing_key(4),
+            5,
+        );
+        assert_eq!(
+            rotations.apply(&mut config, &stale, now()),
+            Err(KeyRotationError::Replayed {
+                counter: 5,
+                last: 5
+            })
+        );
+        assert_eq!(config.signature_key, signing_key(3).verifying_key());
+    }
+
+    #[test_case(|doc| doc.signature[0] ^= 1; "corrupted signature")]
+    #[test_case(|doc| doc.signature.truncate(10); "truncated signature")]
+    #[test_case(|doc| doc.rotation.extend([0x18, 0x02]); "signed bytes changed")]
+    #[test_case(|doc| resign(doc, 9); "signed by another key")]
+    #[test_case(|doc| resign(doc, 2); "signed by the auditor key")]
+    fn forged_rotation_is_rejected(forge: fn(&mut SignedKeyRotation)) {
+        let mut config = config();
+        let mut rotations = KeyRotations::default();
+        let mut doc = document(
+            &signing_key(1),
+            key_rotation::Key::Signing,
+            &signing_key(3),
+            1,
+        );
+        forge(&mut doc);
+
+        assert_eq!(
+            rotations.apply(&mut config, &doc, now()),
+            Err(KeyRotationError::InvalidSignature(RotatableKey::Signing))
+        );
+        assert_eq!(config.signature_key, signing_key(1).verifying_key());
+        assert_eq!(rotations, KeyRotations::default());
+    }
+
+    #[test]
+    fn expired_rotation_is_rejected() {
+        let mut config = config();
+        let doc = document(
+            &signing_key(1),
+            key_rotation::Key::Signing,
+            &signing_key(3),
+            1,
+        );
+        let later = now() + Duration::from_secs(60);
+
+        assert_eq!(
+            KeyRotations::default().apply(&mut config, &doc, later),
+            Err(KeyRotationError::Expired(later))
+        );
+        assert_eq!(config.signature_key, signing_key(1).verifying_key());
+    }
+
+    #[test]
+    fn applied_rotation_is_recognized() {
+        let mut config = config();
+        let mut rotations = KeyRotations::default();
+        let doc = document(
+            &signing_key(1),
+            key_rotation::Key::Signing,
+            &signing_key(3),
+            1,
+        );
+        assert!(!rotations.is_applied(&doc));
+
+        rotations
+            .apply(&mut config, &doc, now())
+            .expect("valid rotation");
+        assert!(rotations.is_applied(&doc));
+
+        let next = document(
+            &signing_key(3),
+            key_rotation::Key::Signing,
+            &signing_key(4),
+            2,
+        );
+        assert!(!rotations.is_applied(&next));
+    }
+
+    #[test]
+    fn unrepresentable_expiry_never_passes() {
+        let mut doc = document(
+            &signing_key(1),
+            key_rotation::Key::Signing,
+            &signing_key(3),
+            1,
+        );
+        let mut rotation = KeyRotation::decode(doc.rotation.as_slice()).expect("valid");
+        rotation.expires_at = u64::MAX;
+        doc.rotation = rotation.encode_to_vec();
+        resign(&mut doc, 1);
+
+        assert_eq!(
+            KeyRotations::default().apply(&mut config(), &doc, now()),
+            Ok(RotatableKey::Signing)
+        );
+    }
+
+    #[test]
+    fn zero_counter_is_rejected() {
+        let doc = document(
+            &signing_key(1),
+            key_rotation::Key::Signing,
+            &signing_key(3),
+            0,
+        );
+        assert_eq!(
+            KeyRotations::default().apply(&mut config(), &doc, now()),
+            Err(KeyRotationError::Replayed {
+                counter: 0,
+                last: 0
+            })
+        );
+    }
+
+    #[test]
+    fn stored_rotations_round_trip() {
+        let rotations = KeyRotations {
+            signing: None,
+            auditor: Some(RotatedKey {
+                counter: 3,
+                key: signing_key(5).verifying_key(),
+            }),
+        };
+        let stored = StoredKeyRotations::from(rotations);
+        let decoded = StoredKeyRotations::decode(stored.encode_to_vec().as_slice()).expect("valid");
+        assert_eq!(KeyRotations::try_from(decoded), Ok(rotations));
+
+        let corrupted = StoredKeyRotations {
+            signing: Some(stored_key_rotations::RotatedKey {
+                counter: 1,
+                key: vec![1, 2, 3],
+            }),
+            auditor: None,
+        };
+        assert_eq!(
+            KeyRotations::try_from(corrupted),
+            Err(KeyRotationError::InvalidStoredRotation)
+        );
+    }
+}
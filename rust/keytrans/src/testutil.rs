@@ -0,0 +1,275 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! A key transparency log that can be run in-process, for testing clients
+//! without a real key transparency service.
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use ed25519_dalek::{Signer as _, SigningKey};
+use prost::Message as _;
+
+use crate::commitments::commit;
+use crate::prefix::evaluate as evaluate_prefix;
+use crate::proto::{
+    key_rotation, CondensedTreeSearchResponse, FullTreeHead, KeyRotation, PrefixProof, ProofStep,
+    SearchProof, SignedKeyRotation, TreeHead, UpdateValue,
+};
+use crate::rotation::SIGNATURE_CONTEXT;
+use crate::verify::{leaf_hash, marshal_tree_head_tbs, marshal_update_value};
+use crate::{
+    vrf, DeploymentMode, LastTreeHead, MonitoringData, PublicConfig, TreeRoot, VerifyingKey,
+};
+
+const SIGNING_KEY: [u8; 32] = [1; 32];
+const VRF_KEY: [u8; 32] = [2; 32];
+const OPENING: [u8; 16] = [3; 16];
+
+/// A log with a single entry, which sets `search_key` to `value`.
+///
+/// Every proof the log produces is valid for a client using
+/// [`Self::public_config`]. Because there's only one entry, proofs are as
+/// small as they can be: monitoring any key in the log takes no proof steps,
+/// and no consistency proofs are ever needed, since the log never grows.
+pub struct FakeLog {
+    signing_key: SigningKey,
+    vrf_key: vrf::SecretKey,
+    search_key: Vec<u8>,
+    value: Vec<u8>,
+    timestamp: i64,
+}
+
+impl FakeLog {
+    /// Creates a log whose one tree head was signed at `timestamp`.
+    pub fn new(search_key: &[u8], value: &[u8], timestamp: SystemTime) -> Self {
+        let timestamp = timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("after the epoch")
+            .as_millis()
+            .try_into()
+            .expect("timestamp fits in i64");
+        Self {
+            signing_key: SigningKey::from_bytes(&SIGNING_KEY),
+            vrf_key: vrf::SecretKey::from_bytes(&VRF_KEY),
+            search_key: search_key.to_vec(),
+            value: value.to_vec(),
+            timestamp,
+        }
+    }
+
+    /// The configuration a client needs to verify this log's proofs.
+    pub fn public_config(&self) -> PublicConfig {
+        PublicConfig {
+            mode: DeploymentMode::ContactMonitoring,
+            signature_key: self.signing_key.verifying_key(),
+            vrf_key: self.vrf_key.public_key().clone(),
+        }
+    }
+
+    /// The log's only tree head, along with its root.
+    pub fn tree_head(&self) -> LastTreeHead {
+        let root = self.root();
+        let mut tree_head = TreeHead {
+            tree_size: 1,
+            timestamp: self.timestamp,
+            signature: vec![],
+        };
+        let tbs = marshal_tree_head_tbs(
+            tree_head.tree_size,
+            tree_head.timestamp,
+            &root,
+            &self.public_config(),
+        )
+        .expect("can marshal tree head");
+        tree_head.signature = self.signing_key.sign(&tbs).to_bytes().to_vec();
+        (tree_head, root)
+    }
+
+    /// The tree head as sent in responses.
+    ///
+    /// Only valid for clients whose last tree heads are either absent or
+    /// [`Self::tree_head`].
+    pub fn full_tree_head(&self) -> FullTreeHead {
+        FullTreeHead {
+            tree_head: Some(self.tree_head().0),
+            last: vec![],
+            distinguished: vec![],
+            auditor_tree_head: None,
+        }
+    }
+
+    /// The response to a search for the log's search key.
+    pub fn search_response(&self) -> CondensedTreeSearchResponse {
+        CondensedTreeSearchResponse {
+            vrf_proof: self.vrf_key.prove(&self.search_key).to_vec(),
+            search: Some(SearchProof {
+                pos: 0,
+                steps: vec![ProofStep {
+                    prefix: Some(Self::prefix_proof()),
+                    commitment: self.commitment().to_vec(),
+                }],
+                inclusion: vec![],
+            }),
+            opening: OPENING.to_vec(),
+            value: Some(UpdateValue {
+                value: self.value.clone(),
+            }),
+        }
+    }
+
+    /// The inclusion proof for a monitor response, which has no proof steps.
+    pub fn monitor_inclusion_proof(&self) -> Vec<Vec<u8>> {
+        vec![self.root().to_vec()]
+    }
+
+    /// Monitoring data for `search_key` as if it were first added by the log's
+    /// only entry.
+    ///
+    /// The log only commits to its own search key, but with a single entry
+    /// there's nothing a monitor response could check that would tell them
+    /// apart.
+    pub fn monitoring_data(&self, search_key: &[u8]) -> MonitoringData {
+        let proof = self.vrf_key.prove(search_key);
+        let index = self
+            .vrf_key
+            .public_key()
+            .proof_to_hash(search_key, &proof)
+            .expect("valid proof");
+        MonitoringData {
+            index,
+            pos: 0,
+            ptrs: HashMap::from([(0, 0)]),
+            owned: true,
+            search_key_hash: Some(MonitoringData::hash_search_key(search_key)),
+        }
+    }
+
+    /// A rotation of the log's signing key to `new_key`, signed by the log.
+    pub fn signing_key_rotation(
+        &self,
+        new_key: &VerifyingKey,
+        counter: u64,
+        expires_at: SystemTime,
+    ) -> SignedKeyRotation {
+        let expires_at = expires_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("after the epoch")
+            .as_millis()
+            .try_into()
+            .expect("expiry fits in u64");
+        let rotation = KeyRotation {
+            key: key_rotation::Key::Signing.into(),
+            new_key: new_key.as_bytes().to_vec(),
+            counter,
+            expires_at,
+        }
+        .encode_to_vec();
+        let signature = self
+            .signing_key
+            .sign(&[SIGNATURE_CONTEXT, rotation.as_slice()].concat())
+            .to_bytes()
+            .to_vec();
+        SignedKeyRotation {
+            rotation,
+            signature,
+        }
+    }
+
+    fn prefix_proof() -> PrefixProof {
+        PrefixProof {
+            proof: vec![vec![0; 32]; 256],
+            counter: 0,
+        }
+    }
+
+    fn commitment(&self) -> [u8; 32] {
+        let value = marshal_update_value(&self.value).expect("value is short enough");
+        commit(&self.search_key, &value, &OPENING)
+            .try_into()
+            .expect("commitment is a hash")
+    }
+
+    fn root(&self) -> TreeRoot {
+        let index = {
+            let proof = self.vrf_key.prove(&self.search_key);
+            self.vrf_key
+                .public_key()
+                .proof_to_hash(&self.search_key, &proof)
+                .expect("valid proof")
+        };
+        let prefix_root =
+            evaluate_prefix(&index, 0, &Self::prefix_proof()).expect("valid prefix proof");
+        leaf_hash(&prefix_root, &self.commitment())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        FullSearchResponse, KeyTransparency, MonitorContext, MonitorKey, MonitorProof,
+        MonitorRequest, MonitorResponse, SearchContext, SlimSearchRequest,
+    };
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_750_000_000)
+    }
+
+    #[test]
+    fn search_response_verifies() {
+        let log = FakeLog::new(b"distinguished", b"value", now());
+        let kt = KeyTransparency {
+            config: log.public_config(),
+        };
+        let full_tree_head = log.full_tree_head();
+        let result = kt
+            .verify_search(
+                SlimSearchRequest::new(b"distinguished".to_vec()),
+                FullSearchResponse::new(log.search_response(), &full_tree_head),
+                SearchContext::default(),
+                false,
+                now(),
+            )
+            .expect("valid proof");
+        assert_eq!(result.value, b"value");
+        assert_eq!(result.state_update.tree_head, log.tree_head().0);
+    }
+
+    #[test]
+    fn monitor_response_verifies() {
+        let log = FakeLog::new(b"distinguished", b"value", now());
+        let kt = KeyTransparency {
+            config: log.public_config(),
+        };
+        let data = log.monitoring_data(b"other");
+        let request = MonitorRequest {
+            keys: vec![MonitorKey {
+                search_key: b"other".to_vec(),
+                entry_position: 0,
+                commitment_index: data.index.to_vec(),
+            }],
+            consistency: None,
+        };
+        let response = MonitorResponse {
+            tree_head: Some(log.full_tree_head()),
+            proofs: vec![MonitorProof { steps: vec![] }],
+            inclusion: log.monitor_inclusion_proof(),
+        };
+        let tree_head = log.tree_head();
+        kt.verify_monitor(
+            &request,
+            &response,
+            MonitorContext {
+                last_tree_head: Some(&tree_head),
+                last_distinguished_tree_head: &tree_head,
+                data: HashMap::from([(b"other".to_vec(), data)]),
+            },
+            now(),
+        )
+        .expect("valid proof");
+    }
+}
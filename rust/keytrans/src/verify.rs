@@ -15,16 +15,22 @@ use crate::log::{evaluate_batch_proof, truncate_batch_proof, verify_consistency_
 use crate::prefix::{evaluate as evaluate_prefix, MalformedProof};
 use crate::proto::*;
 use crate::{
-    guide, log, vrf, DeploymentMode, FullSearchResponse, LastTreeHead, MonitorContext,
-    MonitorStateUpdate, MonitoringData, PublicConfig, SearchContext, SearchStateUpdate,
-    SlimSearchRequest, TreeRoot,
+    guide, log, vrf, ConsistencyCheck, DeploymentMode, FullSearchResponse, LastTreeHead,
+    MonitorContext, MonitorStateUpdate, MonitoringData, PublicConfig, SearchContext,
+    SearchStateUpdate, SlimSearchRequest, TreeHeadConsistency, TreeRoot,
 };
 
+/// How far ahead of "now" a tree head's timestamp can be.
+pub const MAX_TREE_HEAD_CLOCK_SKEW: Duration = Duration::from_secs(10);
+
+/// How far behind "now" a tree head's timestamp can be.
+pub const MAX_TREE_HEAD_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
 /// The range of allowed timestamp values relative to "now".
 /// The timestamps will have to be in [now - max_behind .. now + max_ahead]
 const ALLOWED_TIMESTAMP_RANGE: &TimestampRange = &TimestampRange {
-    max_behind: Duration::from_secs(24 * 60 * 60),
-    max_ahead: Duration::from_secs(10),
+    max_behind: MAX_TREE_HEAD_AGE,
+    max_ahead: MAX_TREE_HEAD_CLOCK_SKEW,
 };
 
 /// The range of allowed timestamp values relative to "now" used for auditor.
@@ -44,13 +50,28 @@ pub enum Error {
     ValueTooLong,
     /// Verification failed: {0}
     VerificationFailed(String),
+    /// Invalid signature: {0}
+    InvalidSignature(&'static str),
+    /// Inconsistent with previously verified state: {0}
+    Inconsistent(String),
+    /// Out of date: {0}
+    Stale(String),
 }
 
 impl std::error::Error for Error {}
 
 impl From<log::Error> for Error {
     fn from(err: log::Error) -> Self {
-        Self::VerificationFailed(err.to_string())
+        match err {
+            // A consistency proof that doesn't match means the server has
+            // presented two different views of the log.
+            log::Error::ProofMismatch(_) => Self::Inconsistent(err.to_string()),
+            log::Error::EmptyChain
+            | log::Error::MalformedChain
+            | log::Error::InvalidInput(_)
+            | log::Error::MalformedProof
+            | log::Error::Unexpected(_) => Self::VerificationFailed(err.to_string()),
+        }
     }
 }
 
@@ -93,7 +114,7 @@ fn serialize_key(buffer: &mut Vec<u8>, key_material: &[u8], key_kind: &str) {
     buffer.extend_from_slice(key_material);
 }
 
-fn marshal_tree_head_tbs(
+pub(crate) fn marshal_tree_head_tbs(
     tree_size: u64,
     timestamp: i64,
     root: &[u8; 32],
@@ -118,7 +139,7 @@ fn marshal_tree_head_tbs(
     Ok(buf)
 }
 
-fn marshal_update_value(value: &[u8]) -> Result<Vec<u8>> {
+pub(crate) fn marshal_update_value(value: &[u8]) -> Result<Vec<u8>> {
     let mut buf = vec![];
 
     let length = u32::try_from(value.len()).map_err(|_| Error::ValueTooLong)?;
@@ -129,7 +150,7 @@ fn marshal_update_value(value: &[u8]) -> Result<Vec<u8>> {
 }
 
 /// Returns the hash of the leaf of the transparency tree.
-fn leaf_hash(prefix_root: &[u8; 32], commitment: &[u8; 32]) -> [u8; 32] {
+pub(crate) fn leaf_hash(prefix_root: &[u8; 32], commitment: &[u8; 32]) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(prefix_root);
     hasher.update(commitment);
@@ -146,11 +167,11 @@ fn verify_tree_head_signature(
 ) -> Result<()> {
     let raw = marshal_tree_head_tbs(head.tree_size, head.timestamp, root, config)?;
     let sig = Signature::from_slice(&head.signature).map_err(|_| {
-        Error::VerificationFailed("failed to verify tree head signature (bad format)".to_string())
+        Error::InvalidSignature("failed to verify tree head signature (bad format)")
     })?;
     verifying_key
         .verify(&raw, &sig)
-        .map_err(|_| Error::VerificationFailed("failed to verify tree head signature".to_string()))
+        .map_err(|_| Error::InvalidSignature("failed to verify tree head signature"))
 }
 
 /// Checks that a FullTreeHead structure is valid. It stores the tree head for
@@ -162,26 +183,24 @@ fn verify_full_tree_head(
     last_tree_head: Option<&LastTreeHead>,
     last_distinguished_tree_head: Option<&LastTreeHead>,
     now: SystemTime,
-) -> Result<LastTreeHead> {
+) -> Result<(LastTreeHead, TreeHeadConsistency)> {
     let tree_head = get_proto_field(&fth.tree_head, "tree_head")?;
 
-    {
+    let consistency = {
         let current_tree_head = (tree_head, &root);
-        if let Some(verify) = check_consistency_metadata(
-            current_tree_head,
-            &get_hash_proof(&fth.last)?,
-            last_tree_head,
-        )? {
-            verify()?
+        TreeHeadConsistency {
+            last: check_consistency(
+                current_tree_head,
+                &get_hash_proof(&fth.last)?,
+                last_tree_head,
+            )?,
+            distinguished: check_consistency(
+                current_tree_head,
+                &get_hash_proof(&fth.distinguished)?,
+                last_distinguished_tree_head,
+            )?,
         }
-        if let Some(verify) = check_consistency_metadata(
-            current_tree_head,
-            &get_hash_proof(&fth.distinguished)?,
-            last_distinguished_tree_head,
-        )? {
-            verify()?
-        }
-    }
+    };
 
     // 2. Verify the signature in TreeHead.signature.
     verify_tree_head_signature(config, tree_head, &root, &config.signature_key)?;
@@ -206,12 +225,12 @@ fn verify_full_tree_head(
         // 3. Verify that TreeHead.tree_size is sufficiently close to the most
         //    recent tree head from the service operator.
         if auditor_head.tree_size > tree_head.tree_size {
-            return Err(Error::VerificationFailed(
+            return Err(Error::Inconsistent(
                 "auditor tree head may not be further along than service tree head".to_string(),
             ));
         }
         if tree_head.tree_size - auditor_head.tree_size > ENTRIES_MAX_BEHIND {
-            return Err(Error::VerificationFailed(
+            return Err(Error::Stale(
                 "auditor tree head is too far behind service tree head".to_string(),
             ));
         }
@@ -250,7 +269,24 @@ fn verify_full_tree_head(
         }
     }
 
-    Ok((tree_head.clone(), root))
+    Ok(((tree_head.clone(), root), consistency))
+}
+
+/// Checks the consistency proof against the baseline tree head, as described
+/// in [`check_consistency_metadata`], and reports how it was satisfied.
+fn check_consistency(
+    current: (&TreeHead, &TreeRoot),
+    proof: &[[u8; 32]],
+    baseline: Option<&LastTreeHead>,
+) -> Result<ConsistencyCheck> {
+    match check_consistency_metadata(current, proof, baseline)? {
+        Some(verify) => {
+            verify()?;
+            Ok(ConsistencyCheck::Proven)
+        }
+        None if baseline.is_some() => Ok(ConsistencyCheck::SameSize),
+        None => Ok(ConsistencyCheck::NoBaseline),
+    }
 }
 
 /// Checks if the consistency proof against the baseline tree head needs to be
@@ -283,14 +319,12 @@ fn check_consistency_metadata<'a>(
         }
         Some((last, last_root)) if last.tree_size == current_head.tree_size => {
             if current_root != last_root {
-                return Err(Error::VerificationFailed(
+                return Err(Error::Inconsistent(
                     "root is different but tree size is same".to_string(),
                 ));
             }
             if current_head.timestamp != last.timestamp {
-                return Err(Error::VerificationFailed(
-                    "tree size is the same b".to_string(),
-                ));
+                return Err(Error::Inconsistent("tree size is the same b".to_string()));
             }
             if !proof.is_empty() {
                 return Err(Error::VerificationFailed(
@@ -301,12 +335,12 @@ fn check_consistency_metadata<'a>(
         }
         Some((last_head, last_root)) => {
             if current_head.tree_size < last_head.tree_size {
-                return Err(Error::VerificationFailed(
+                return Err(Error::Inconsistent(
                     "current tree size is less than previous tree size".to_string(),
                 ));
             }
             if current_head.timestamp < last_head.timestamp {
-                return Err(Error::VerificationFailed(
+                return Err(Error::Inconsistent(
                     "current timestamp is less than previous timestamp".to_string(),
                 ));
             }
@@ -351,11 +385,11 @@ fn verify_timestamp(
     };
     if delta > max_behind.as_millis() as i128 {
         let message = format_message("timestamp is too far behind current time");
-        return Err(Error::VerificationFailed(message));
+        return Err(Error::Stale(message));
     }
     if (-delta) > max_ahead.as_millis() as i128 {
         let message = format_message("timestamp is too far ahead of current time");
-        return Err(Error::VerificationFailed(message));
+        return Err(Error::Stale(message));
     }
     Ok(())
 }
@@ -395,7 +429,7 @@ pub fn verify_distinguished(
         let result = if root == distinguished_root {
             Ok(())
         } else {
-            Err(Error::VerificationFailed(
+            Err(Error::Inconsistent(
                 "root hash does not match expected value".to_string(),
             ))
         };
@@ -428,7 +462,7 @@ fn verify_search_internal(
     context: SearchContext,
     monitor: bool,
     now: SystemTime,
-) -> Result<SearchStateUpdate> {
+) -> Result<(SearchStateUpdate, TreeHeadConsistency)> {
     // NOTE: Update this function in tandem with truncate_search_response.
     let SlimSearchRequest {
         search_key,
@@ -522,7 +556,7 @@ fn verify_search_internal(
     } = context;
 
     // Verify the tree head with the candidate root.
-    let updated_tree_head = verify_full_tree_head(
+    let (updated_tree_head, consistency) = verify_full_tree_head(
         config,
         full_tree_head,
         root,
@@ -547,12 +581,14 @@ fn verify_search_internal(
     }
     mdw.check_search_consistency(size, &index, search_proof.pos, result_id, ver, monitor)?;
     mdw.update(size, &steps)?;
+    mdw.record_search_key(&search_key);
 
-    Ok(SearchStateUpdate {
+    let state_update = SearchStateUpdate {
         tree_head: updated_tree_head.0,
         tree_root: updated_tree_head.1,
         monitoring_data: mdw.into_data_update(),
-    })
+    };
+    Ok((state_update, consistency))
 }
 
 /// Checks that the output of a Search operation is valid and updates the
@@ -565,7 +601,7 @@ pub fn verify_search(
     context: SearchContext,
     force_monitor: bool,
     now: SystemTime,
-) -> Result<SearchStateUpdate> {
+) -> Result<(SearchStateUpdate, TreeHeadConsistency)> {
     verify_search_internal(config, req, res, context, force_monitor, now)
 }
 
@@ -606,6 +642,7 @@ pub fn verify_update(
         true,
         now,
     )
+    .map(|(state_update, _)| state_update)
 }
 
 /// Checks that the output of a Monitor operation is valid and updates the
@@ -616,7 +653,7 @@ pub fn verify_monitor<'a>(
     res: &'a MonitorResponse,
     context: MonitorContext,
     now: SystemTime,
-) -> Result<MonitorStateUpdate> {
+) -> Result<(MonitorStateUpdate, TreeHeadConsistency)> {
     // Verify proof responses are the expected lengths.
     if req.keys.len() != res.proofs.len() {
         return Err(Error::VerificationFailed(
@@ -658,7 +695,7 @@ pub fn verify_monitor<'a>(
     };
 
     // Verify the tree head with the candidate root.
-    let updated_tree_head = verify_full_tree_head(
+    let (updated_tree_head, tree_head_consistency) = verify_full_tree_head(
         config,
         full_tree_head,
         root,
@@ -697,11 +734,12 @@ pub fn verify_monitor<'a>(
         }
     }
 
-    Ok(MonitorStateUpdate {
+    let state_update = MonitorStateUpdate {
         tree_head: updated_tree_head.0,
         tree_root: updated_tree_head.1,
         monitoring_data: data_updates,
-    })
+    };
+    Ok((state_update, tree_head_consistency))
 }
 
 struct MonitorProofAcc {
@@ -803,10 +841,19 @@ impl MonitoringDataWrapper {
                 pos: zero_pos,
                 ptrs: HashMap::from([(ver_pos, version)]),
                 owned,
+                search_key_hash: None,
             });
         }
     }
 
+    /// Records which search key the data is for, once the search that found
+    /// it has been checked against it.
+    fn record_search_key(&mut self, search_key: &[u8]) {
+        if let Some(data) = self.inner.as_mut() {
+            data.search_key_hash = Some(MonitoringData::hash_search_key(search_key));
+        }
+    }
+
     fn check_search_consistency(
         &mut self,
         tree_size: u64,
@@ -821,12 +868,12 @@ impl MonitoringDataWrapper {
         };
 
         if *index != data.index {
-            return Err(Error::VerificationFailed(
+            return Err(Error::Inconsistent(
                 "given search key index does not match database".to_string(),
             ));
         }
         if zero_pos != data.pos {
-            return Err(Error::VerificationFailed(
+            return Err(Error::Inconsistent(
                 "given search start position does not match database".to_string(),
             ));
         }
@@ -834,7 +881,7 @@ impl MonitoringDataWrapper {
         match data.ptrs.get(&ver_pos) {
             Some(ver) => {
                 if *ver != version {
-                    return Err(Error::VerificationFailed(
+                    return Err(Error::Inconsistent(
                         "different versions of key recorded at same position".to_string(),
                     ));
                 }
@@ -844,7 +891,7 @@ impl MonitoringDataWrapper {
                 {
                     Some(ver) => {
                         if *ver < version {
-                            return Err(Error::VerificationFailed(
+                            return Err(Error::Inconsistent(
                                 "prefix tree has unexpectedly low version counter".to_string(),
                             ));
                         }
@@ -885,7 +932,7 @@ impl MonitoringDataWrapper {
                     Some(step) => {
                         let ctr = get_proto_field(&step.prefix, "prefix")?.counter;
                         if ctr < ver {
-                            return Err(Error::VerificationFailed(
+                            return Err(Error::Inconsistent(
                                 "prefix tree has unexpectedly low version counter".to_string(),
                             ));
                         }
@@ -899,7 +946,7 @@ impl MonitoringDataWrapper {
             match ptrs.get(&entry) {
                 Some(other) => {
                     if ver != *other {
-                        return Err(Error::VerificationFailed(
+                        return Err(Error::Inconsistent(
                             "inconsistent versions found".to_string(),
                         ));
                     }
@@ -1006,6 +1053,7 @@ fn into_sorted_pairs<K: Ord + Copy, V>(map: HashMap<K, V>) -> (Vec<K>, Vec<V>) {
 #[cfg(test)]
 mod test {
     use assert_matches::assert_matches;
+    use ed25519_dalek::{Signer as _, SigningKey};
     use hex_literal::hex;
     use prost::Message as _;
     use test_case::test_case;
@@ -1032,7 +1080,7 @@ mod test {
         let ts = make_timestamp(time);
         assert_matches!(
             verify_timestamp(ts, TIMESTAMP_RANGE, None, SystemTime::now()),
-            Err(Error::VerificationFailed(_))
+            Err(Error::Stale(_))
         );
     }
 
@@ -1047,6 +1095,63 @@ mod test {
         );
     }
 
+    #[test_case(false; "without auditor tree head")]
+    #[test_case(true; "with auditor tree head")]
+    fn contact_monitoring_needs_no_auditor_signature(include_auditor_tree_head: bool) {
+        let signing_key = SigningKey::from_bytes(&[1; 32]);
+        let config = PublicConfig {
+            mode: DeploymentMode::ContactMonitoring,
+            signature_key: signing_key.verifying_key(),
+            vrf_key: vrf::PublicKey::try_from(hex!(
+                "1e71563470c1b8a6e0aadf280b6aa96f8ad064674e69b80292ee46d1ab655fcf"
+            ))
+            .unwrap(),
+        };
+        let root = [2; 32];
+        let now = SystemTime::now();
+
+        let mut tree_head = TreeHead {
+            tree_size: 42,
+            timestamp: make_timestamp(now),
+            signature: vec![],
+        };
+        let tbs = marshal_tree_head_tbs(tree_head.tree_size, tree_head.timestamp, &root, &config)
+            .unwrap();
+        tree_head.signature = signing_key.sign(&tbs).to_bytes().to_vec();
+
+        let full_tree_head = FullTreeHead {
+            tree_head: Some(tree_head.clone()),
+            last: vec![],
+            distinguished: vec![],
+            // Not signed by anyone; contact monitoring doesn't look at it.
+            auditor_tree_head: include_auditor_tree_head.then(|| AuditorTreeHead {
+                tree_head: Some(TreeHead {
+                    signature: vec![0; 64],
+                    ..tree_head.clone()
+                }),
+                root_value: None,
+                consistency: vec![],
+            }),
+        };
+        assert_matches!(
+            verify_full_tree_head(&config, &full_tree_head, root, None, None, now),
+            Ok(((verified, verified_root), _)) if verified == tree_head && verified_root == root
+        );
+
+        // The deployment mode is part of what the log signs, so the same tree
+        // head must not verify for an audited deployment.
+        let audited_config = PublicConfig {
+            mode: DeploymentMode::ThirdPartyAuditing(
+                SigningKey::from_bytes(&[3; 32]).verifying_key(),
+            ),
+            ..config
+        };
+        assert_matches!(
+            verify_full_tree_head(&audited_config, &full_tree_head, root, None, None, now),
+            Err(Error::InvalidSignature(_))
+        );
+    }
+
     #[test]
     fn can_verify_search_response() {
         let sig_key = VerifyingKey::from_bytes(&hex!(
@@ -1111,14 +1216,19 @@ mod test {
             pos: 5594,
             ptrs: HashMap::from([(6143, 0)]),
             owned: true,
+            search_key_hash: Some(MonitoringData::hash_search_key(&request.search_key)),
         };
 
         assert_matches!(
             verify_search_internal(&config, request.clone(), response.clone(), SearchContext::default(), true, valid_at),
-            Ok(update) => {
+            Ok((update, consistency)) => {
                 assert_eq!(update.tree_head, last_tree_head);
                 assert_eq!(update.tree_root, last_root);
                 assert_eq!(update.monitoring_data, Some(expected_data_update.clone()));
+                assert_eq!(consistency, TreeHeadConsistency {
+                    last: ConsistencyCheck::NoBaseline,
+                    distinguished: ConsistencyCheck::NoBaseline,
+                });
             }
         );
         // Verification result should always include the monitoring data field, even if it has not changed.
@@ -1131,15 +1241,21 @@ mod test {
 
         assert_matches!(
             verify_search_internal(&config, request.clone(), response.clone(), context, true, valid_at),
-            Ok(update) => {
+            Ok((update, consistency)) => {
                 assert_eq!(&update.tree_head, &last_tree_head);
                 assert_eq!(update.tree_root, last_root);
                 assert_eq!(update.monitoring_data, Some(expected_data_update));
+                // The last tree head is the one in the response, so there is
+                // no proof to check.
+                assert_eq!(consistency, TreeHeadConsistency {
+                    last: ConsistencyCheck::SameSize,
+                    distinguished: ConsistencyCheck::NoBaseline,
+                });
             }
         );
         assert_matches!(
             verify_search_internal(&config, request, response, SearchContext::default(), false, valid_at),
-            Ok(update) => {
+            Ok((update, _)) => {
                 assert_eq!(update.tree_head, last_tree_head);
                 assert_eq!(update.tree_root, last_root);
                 // When monitor == false there should be no data update
@@ -1182,36 +1298,8 @@ mod test {
         has_proof: bool,
         outcome: VerifierOutcome,
     ) {
-        let current_head = TreeHead {
-            tree_size: 42,
-            ..TreeHead::default()
-        };
-        let current_root = [0u8; 32];
-
-        let baseline = {
-            let head = current_head.clone();
-            let root = [0u8; 32];
-            let mut baseline = Some((head, root));
-
-            for baseline_mod in baseline_mods {
-                let Some(result) = baseline.as_mut() else {
-                    break;
-                };
-                match baseline_mod {
-                    Baseline::Absent => baseline = None,
-                    Baseline::WithSize(n) => {
-                        result.0.tree_size = *n;
-                    }
-                    Baseline::WithTimestamp(ts) => {
-                        result.0.timestamp = *ts;
-                    }
-                    Baseline::WithRoot(r) => {
-                        result.1 = *r;
-                    }
-                }
-            }
-            baseline
-        };
+        let (current_head, current_root) = current_tree_head();
+        let baseline = make_baseline(baseline_mods);
 
         let proof = [[0u8; 32]];
 
@@ -1227,4 +1315,51 @@ mod test {
             VerifierOutcome::Verifier => assert!(matches!(result, Ok(Some(_)))),
         }
     }
+
+    fn current_tree_head() -> LastTreeHead {
+        let head = TreeHead {
+            tree_size: 42,
+            ..TreeHead::default()
+        };
+        (head, [0u8; 32])
+    }
+
+    /// The current tree head, modified by `baseline_mods`.
+    fn make_baseline(baseline_mods: &[Baseline]) -> Option<LastTreeHead> {
+        let mut baseline = Some(current_tree_head());
+
+        for baseline_mod in baseline_mods {
+            let Some(result) = baseline.as_mut() else {
+                break;
+            };
+            match baseline_mod {
+                Baseline::Absent => baseline = None,
+                Baseline::WithSize(n) => {
+                    result.0.tree_size = *n;
+                }
+                Baseline::WithTimestamp(ts) => {
+                    result.0.timestamp = *ts;
+                }
+                Baseline::WithRoot(r) => {
+                    result.1 = *r;
+                }
+            }
+        }
+        baseline
+    }
+
+    #[test_case(&[Baseline::Absent] => matches Ok(ConsistencyCheck::NoBaseline); "no baseline")]
+    #[test_case(&[] => matches Ok(ConsistencyCheck::SameSize); "baseline is current")]
+    #[test_case(&[Baseline::WithRoot([1u8; 32])] => matches Err(Error::Inconsistent(_)); "baseline different root")]
+    #[test_case(&[Baseline::WithSize(41)] => matches Err(_); "baseline is smaller with bad proof")]
+    fn consistency_check_outcomes(baseline_mods: &[Baseline]) -> Result<ConsistencyCheck> {
+        let (current_head, current_root) = current_tree_head();
+        let baseline = make_baseline(baseline_mods);
+        let proof: &[[u8; 32]] = if matches!(baseline_mods, [Baseline::WithSize(_)]) {
+            &[[0u8; 32]]
+        } else {
+            &[]
+        };
+        check_consistency((&current_head, &current_root), proof, baseline.as_ref())
+    }
 }
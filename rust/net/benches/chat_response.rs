@@ -0,0 +1,87 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use base64::prelude::{Engine as _, BASE64_STANDARD_NO_PAD};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use libsignal_keytrans::{
+    ChatMonitorResponse, ChatSearchResponse, CondensedTreeSearchResponse, MonitorProof,
+};
+use libsignal_net::chat::envelope::decode_envelope;
+use libsignal_net::chat::{Response, ResponseProto};
+use prost::Message as _;
+
+/// A recorded search for an ACI, E.164, and username hash.
+const CHAT_SEARCH_RESPONSE: &[u8] = include_bytes!("../tests/data/chat_search_response.dat");
+
+/// A monitor response for the same three keys, built from the recorded
+/// search's tree head and proofs.
+///
+/// Monitor proofs are made of the same steps as search proofs, so this has
+/// the shape and size of a real response.
+fn monitor_response() -> ChatMonitorResponse {
+    let ChatSearchResponse {
+        tree_head,
+        aci,
+        e164,
+        username_hash,
+    } = ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE).expect("valid search response");
+    let mut inclusion = vec![];
+    let mut monitor_proof = |leg: Option<CondensedTreeSearchResponse>| {
+        let search = leg?.search?;
+        inclusion.extend(search.inclusion);
+        Some(MonitorProof {
+            steps: search.steps,
+        })
+    };
+    let aci = monitor_proof(aci);
+    let e164 = monitor_proof(e164);
+    let username_hash = monitor_proof(username_hash);
+    ChatMonitorResponse {
+        tree_head,
+        aci,
+        e164,
+        username_hash,
+        inclusion,
+    }
+}
+
+/// [`monitor_response`] as the chat server sends it.
+fn monitor_response_proto() -> ResponseProto {
+    let serialized = BASE64_STANDARD_NO_PAD.encode(monitor_response().encode_to_vec());
+    ResponseProto {
+        id: Some(1),
+        status: Some(200),
+        message: Some("OK".to_owned()),
+        headers: vec!["content-type:application/json".to_owned()],
+        body: Some(format!(r#"{{"serializedResponse":"{serialized}"}}"#).into_bytes()),
+    }
+}
+
+fn bench_monitor_response(c: &mut Criterion) {
+    let mut group = c.benchmark_group("monitor_response");
+
+    group.bench_function("from_proto", |b| {
+        b.iter_batched(
+            monitor_response_proto,
+            |proto| Response::try_from(proto).expect("valid"),
+            BatchSize::LargeInput,
+        )
+    });
+
+    let response = Response::try_from(monitor_response_proto()).expect("valid");
+    group.bench_function("clone", |b| b.iter(|| response.clone()));
+    group.bench_function("decode_envelope", |b| {
+        b.iter_batched(
+            || response.clone(),
+            |response| decode_envelope::<ChatMonitorResponse>(response, usize::MAX).expect("valid"),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_monitor_response);
+criterion_main!(benches);
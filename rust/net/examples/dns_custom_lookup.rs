@@ -87,6 +87,7 @@ async fn main() {
     let lookup_request = DnsLookupRequest {
         hostname: Arc::from(args.domain.as_str()),
         ipv6_enabled: true,
+        local_bind_address: None,
     };
 
     // first time making a DNS query
@@ -63,7 +63,7 @@ async fn main() {
         },
     };
 
-    let doh_transport = DohTransport::connect(vec![route.clone()], !args.no_ipv6)
+    let doh_transport = DohTransport::connect(vec![route.clone()], !args.no_ipv6, None)
         .await
         .expect("connected to the DNS server");
     log::info!("successfully connected to the DNS server at {:?}", route);
@@ -71,6 +71,7 @@ async fn main() {
     let request = DnsLookupRequest {
         hostname: Arc::from(args.domain),
         ipv6_enabled: !args.no_ipv6,
+        local_bind_address: None,
     };
     log::info!("sending DNS request: {:?}", request);
     let mut stream = doh_transport.send_queries(request).await.unwrap();
@@ -41,7 +41,7 @@ async fn main() {
         args.ns_port,
     );
 
-    let udp_transport = UdpTransport::connect(ns_address, !args.no_ipv6)
+    let udp_transport = UdpTransport::connect(ns_address, !args.no_ipv6, None)
         .await
         .expect("connected to the DNS server");
     log::info!(
@@ -52,6 +52,7 @@ async fn main() {
     let request = DnsLookupRequest {
         hostname: Arc::from(args.domain.as_str()),
         ipv6_enabled: !args.no_ipv6,
+        local_bind_address: None,
     };
     log::info!("sending DNS request: {:?}", request);
     let mut stream = udp_transport.send_queries(request).await.unwrap();
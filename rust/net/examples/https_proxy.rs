@@ -123,13 +123,14 @@ async fn main() {
             target_host,
             target_port,
             authorization,
+            connect_headers: Default::default(),
         },
     };
     log::info!("unresolved: {unresolved_route:?}");
     let resolved = libsignal_net::infra::route::resolve_route(&dns_resolver, unresolved_route)
         .await
         .expect("failed to resolve");
-    let connector = libsignal_net::infra::tcp_ssl::proxy::StatelessProxied;
+    let connector = libsignal_net::infra::tcp_ssl::proxy::StatelessProxied::default();
 
     const START_NEXT_DELAY: Duration = Duration::from_secs(5);
     let connect_attempts = FuturesUnordered::from_iter(resolved.zip(0..).map(|(route, i)| {
@@ -124,8 +124,8 @@ async fn main() {
             .expect("failed to resolve");
         let connector =
             libsignal_net::infra::route::ComposedConnector::<_, _, TransportConnectError>::new(
-                libsignal_net::infra::tcp_ssl::StatelessDirect,
-                libsignal_net::infra::tcp_ssl::proxy::StatelessProxied,
+                libsignal_net::infra::tcp_ssl::StatelessDirect::default(),
+                libsignal_net::infra::tcp_ssl::proxy::StatelessProxied::default(),
             );
 
         const START_NEXT_DELAY: Duration = Duration::from_secs(5);
@@ -4,11 +4,13 @@
 //
 
 use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::future::Future;
+use std::num::NonZeroU16;
 use std::ops::Add;
 use std::panic::RefUnwindSafe;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::Duration;
 
 use async_trait::async_trait;
@@ -17,9 +19,10 @@ use tokio::sync::Mutex;
 use tokio::time::{timeout_at, Instant};
 
 use crate::errors::LogSafeDisplay;
+use crate::host::Host;
 use crate::timeouts::{CONNECTION_ROUTE_COOLDOWN_INTERVALS, CONNECTION_ROUTE_MAX_COOLDOWN};
 use crate::utils::{EventSubscription, ObservableEvent};
-use crate::ConnectionParams;
+use crate::{ConnectionParams, RouteType};
 
 /// Represents the outcome of the connection attempt
 #[derive(Debug)]
@@ -169,6 +172,20 @@ impl ThrottlingConnectionManagerState {
         // attempt as a single failure.
         *self = self.clone().after_attempt(false, latest_attempt);
     }
+
+    /// Calls [`Self::network_changed`] on `state` as soon as possible.
+    fn reset_for_network_change(state: Arc<Mutex<Self>>, network_change_time: Instant) {
+        // We'd like to reset the cooldowns synchronously, but tokio won't let us block on an
+        // async-aware mutex if we're currently within an async runtime. Spawn a task to do the
+        // reset ASAP instead.
+        if let Ok(tokio_runtime) = tokio::runtime::Handle::try_current() {
+            tokio_runtime.spawn(async move {
+                state.lock().await.network_changed(network_change_time);
+            });
+        } else {
+            state.blocking_lock().network_changed(network_change_time);
+        }
+    }
 }
 
 /// A connection manager that only attempts one route (i.e. one [ConnectionParams]).
@@ -180,9 +197,108 @@ pub struct SingleRouteThrottlingConnectionManager<C = ConnectionParams> {
     state: Arc<Mutex<ThrottlingConnectionManagerState>>,
     connection_params: C,
     connection_timeout: Duration,
+    /// `None` if the state is reset by the [`RouteCooldowns`] it came from instead.
+    _network_changed_subscription: Option<Arc<EventSubscription>>,
+}
+
+/// Remembers which routes are in cooldown, independently of any one connection manager.
+///
+/// Every [`SingleRouteThrottlingConnectionManager`] made by [`Self::manager`] for the same route
+/// shares its cooldown state, so a route that has been failing stays in cooldown even when the
+/// managers for it are thrown away and rebuilt (e.g. because the set of routes changed). Routes are
+/// identified by type, hosts, and port; request decorators aren't considered.
+///
+/// Every cooldown is reset when the network changes, including those of routes that currently
+/// have no manager.
+#[derive(Clone, Debug)]
+pub struct RouteCooldowns {
+    states: Arc<std::sync::Mutex<HashMap<RouteKey, Arc<Mutex<ThrottlingConnectionManagerState>>>>>,
     _network_changed_subscription: Arc<EventSubscription>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RouteKey {
+    route_type: RouteType,
+    http_host: Arc<str>,
+    sni: Arc<str>,
+    tcp_host: Host<Arc<str>>,
+    port: NonZeroU16,
+}
+
+impl RouteKey {
+    fn new(connection_params: &ConnectionParams) -> Self {
+        let ConnectionParams {
+            route_type,
+            http_host,
+            http_request_decorator: _,
+            connection_confirmation_header: _,
+            transport,
+        } = connection_params;
+        Self {
+            route_type: *route_type,
+            http_host: http_host.clone(),
+            sni: transport.sni.clone(),
+            tcp_host: transport.tcp_host.clone(),
+            port: transport.port,
+        }
+    }
+}
+
+impl RouteCooldowns {
+    pub fn new(network_changed_event: &ObservableEvent) -> Self {
+        let states: Arc<std::sync::Mutex<HashMap<_, _>>> = Default::default();
+        // As in SingleRouteThrottlingConnectionManager::new, the subscription shouldn't keep the
+        // states alive.
+        let states_for_network_changed: Weak<std::sync::Mutex<HashMap<_, _>>> =
+            Arc::downgrade(&states);
+        let network_changed_subscription = network_changed_event.subscribe(Box::new(move || {
+            let Some(states) = states_for_network_changed.upgrade() else {
+                return;
+            };
+            let time_of_event = Instant::now();
+            let states = states
+                .lock()
+                .expect("not poisoned")
+                .values()
+                .cloned()
+                .collect_vec();
+            for state in states {
+                ThrottlingConnectionManagerState::reset_for_network_change(state, time_of_event);
+            }
+        }));
+        Self {
+            states,
+            _network_changed_subscription: Arc::new(network_changed_subscription),
+        }
+    }
+
+    /// Makes a manager for `connection_params` that shares its cooldown with every other manager
+    /// made for the same route.
+    pub fn manager(
+        &self,
+        connection_params: ConnectionParams,
+        connection_timeout: Duration,
+    ) -> SingleRouteThrottlingConnectionManager {
+        let state = self
+            .states
+            .lock()
+            .expect("not poisoned")
+            .entry(RouteKey::new(&connection_params))
+            .or_insert_with(|| {
+                Arc::new(Mutex::new(ThrottlingConnectionManagerState::new(
+                    Instant::now(),
+                )))
+            })
+            .clone();
+        SingleRouteThrottlingConnectionManager {
+            state,
+            connection_params,
+            connection_timeout,
+            _network_changed_subscription: None,
+        }
+    }
+}
+
 /// A connection manager that holds a list of [SingleRouteThrottlingConnectionManager] instances.
 ///
 /// It iterates over them until it can find one that results in a successful connection attempt.
@@ -352,24 +468,14 @@ impl<C> SingleRouteThrottlingConnectionManager<C> {
             let Some(state) = state_for_network_changed.upgrade() else {
                 return;
             };
-            let time_of_event = Instant::now();
-            // We'd like to reset the cooldowns synchronously, but tokio won't let us block on an
-            // async-aware mutex if we're currently within an async runtime. Spawn a task to do the
-            // reset ASAP instead.
-            if let Ok(tokio_runtime) = tokio::runtime::Handle::try_current() {
-                tokio_runtime.spawn(async move {
-                    state.lock().await.network_changed(time_of_event);
-                });
-            } else {
-                state.blocking_lock().network_changed(time_of_event);
-            }
+            ThrottlingConnectionManagerState::reset_for_network_change(state, Instant::now());
         }));
 
         Self {
             connection_params,
             connection_timeout,
             state,
-            _network_changed_subscription: Arc::new(network_changed_subscription),
+            _network_changed_subscription: Some(Arc::new(network_changed_subscription)),
         }
     }
 
@@ -592,6 +698,54 @@ mod test {
         assert_matches!(attempt_outcome, ConnectionAttemptOutcome::Attempted(Ok(())));
     }
 
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn route_cooldowns_outlive_managers() {
+        let network_changed_event = ObservableEvent::default();
+        let cooldowns = RouteCooldowns::new(&network_changed_event);
+        let manager = cooldowns.manager(
+            example_connection_params("chat.staging.signal.org"),
+            TIMEOUT_DURATION,
+        );
+        for _ in 0..MANY_ATTEMPTS {
+            time::advance(TIME_ADVANCE_VALUE).await;
+            let _attempt_outcome: ConnectionAttemptOutcome<(), TestError> = manager
+                .connect_or_wait(|_| future::ready(Err(TestError::Expected)))
+                .await;
+        }
+        drop(manager);
+
+        // A new manager for the same route picks up the cooldown...
+        let same_route = cooldowns.manager(
+            example_connection_params("chat.staging.signal.org"),
+            TIMEOUT_DURATION,
+        );
+        let attempt_outcome: ConnectionAttemptOutcome<(), TestError> =
+            same_route.connect_or_wait(|_| future::ready(Ok(()))).await;
+        assert_matches!(attempt_outcome, ConnectionAttemptOutcome::WaitUntil(_));
+
+        // ...but one for a different route doesn't.
+        let other_route = cooldowns.manager(
+            example_connection_params("cdsi.staging.signal.org"),
+            TIMEOUT_DURATION,
+        );
+        let attempt_outcome: ConnectionAttemptOutcome<(), TestError> =
+            other_route.connect_or_wait(|_| future::ready(Ok(()))).await;
+        assert_matches!(attempt_outcome, ConnectionAttemptOutcome::Attempted(Ok(())));
+
+        // The network changing resets the cooldown, even with no manager around to notice.
+        drop(same_route);
+        network_changed_event.fire();
+        tokio::task::yield_now().await;
+        let same_route = cooldowns.manager(
+            example_connection_params("chat.staging.signal.org"),
+            TIMEOUT_DURATION,
+        );
+        assert_eq!(same_route.state.lock().await.reset_counter, 1);
+        let attempt_outcome: ConnectionAttemptOutcome<(), TestError> =
+            same_route.connect_or_wait(|_| future::ready(Ok(()))).await;
+        assert_matches!(attempt_outcome, ConnectionAttemptOutcome::Attempted(Ok(())));
+    }
+
     #[tokio::test(flavor = "current_thread", start_paused = true)]
     async fn single_route_manager_resets_cooldown_count_on_network_changed() {
         let network_changed_event = ObservableEvent::default();
@@ -9,14 +9,18 @@ use std::str::FromStr as _;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use futures_util::{FutureExt as _, StreamExt as _};
+use futures_util::{FutureExt as _, Stream, StreamExt as _};
 use oneshot_broadcast::Sender;
+use tokio::sync::mpsc;
 use tokio::time::Instant;
+use tokio_stream::wrappers::UnboundedReceiverStream;
 
 use crate::certs::RootCertificates;
 use crate::dns::custom_resolver::CustomDnsResolver;
 use crate::dns::dns_errors::Error;
-use crate::dns::dns_lookup::{DnsLookup, DnsLookupRequest, StaticDnsMap, SystemDnsLookup};
+use crate::dns::dns_lookup::{
+    split_by_family, DnsLookup, DnsLookupRequest, FamilyLookupResult, StaticDnsMap, SystemDnsLookup,
+};
 use crate::dns::dns_transport_doh::{DohTransport, CLOUDFLARE_IPS};
 use crate::dns::dns_types::ResourceType;
 use crate::dns::dns_utils::log_safe_domain;
@@ -28,7 +32,7 @@ use crate::route::{
 use crate::timeouts::{DNS_FALLBACK_LOOKUP_TIMEOUTS, DNS_SYSTEM_LOOKUP_TIMEOUT};
 use crate::utils::oneshot_broadcast::{self, Receiver};
 use crate::utils::{self, ObservableEvent};
-use crate::Alpn;
+use crate::{Alpn, DnsSource, IpType};
 
 pub mod custom_resolver;
 mod dns_errors;
@@ -43,17 +47,68 @@ pub mod lookup_result;
 pub type DnsError = Error;
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The longest that addresses set by [`DnsResolver::set_ip_hints`] are kept.
+///
+/// Hints are meant for riding out a censorship event; if one lasts longer than this, the app
+/// can set them again.
+pub const MAX_IP_HINT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
 struct DnsResolverState {
     /// Controls if lookup results will contain IPv6 entries.
     ipv6_enabled: bool,
+    /// The local address that DNS transports bind their sockets to, if any.
+    local_bind_address: Option<IpAddr>,
     in_flight_lookups: HashMap<String, Receiver<Result<LookupResult>>>,
+    /// Addresses supplied out-of-band for a hostname, added to the results of lookups.
+    ip_hints: HashMap<String, IpHints>,
+}
+
+/// Addresses for a hostname that were supplied by the application rather than looked up.
+struct IpHints {
+    addrs: Vec<IpAddr>,
+    expires_at: Instant,
+}
+
+impl DnsResolverState {
+    /// Returns the hints for `hostname` if there are any that haven't expired yet.
+    ///
+    /// Expired hints are discarded.
+    fn live_ip_hints(&mut self, hostname: &str, now: Instant) -> Option<&IpHints> {
+        if self
+            .ip_hints
+            .get(hostname)
+            .is_some_and(|hints| hints.expires_at <= now)
+        {
+            _ = self.ip_hints.remove(hostname);
+        }
+        self.ip_hints.get(hostname)
+    }
+
+    /// Builds a request for `hostname` using the current settings.
+    fn lookup_request(&self, hostname: Arc<str>) -> DnsLookupRequest {
+        DnsLookupRequest {
+            hostname,
+            ipv6_enabled: self.ipv6_enabled,
+            local_bind_address: self.local_bind_address,
+        }
+    }
 }
 
 impl std::fmt::Debug for DnsResolverState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DnsResolverState")
             .field("ipv6_enabled", &self.ipv6_enabled)
+            .field("local_bind_address", &self.local_bind_address)
             .field("in_flight_lookups", &self.in_flight_lookups.keys())
+            // The hinted addresses themselves are deliberately left out.
+            .field(
+                "ip_hints",
+                &self
+                    .ip_hints
+                    .iter()
+                    .map(|(hostname, hints)| (log_safe_domain(hostname), hints.addrs.len()))
+                    .collect::<Vec<_>>(),
+            )
             .finish()
     }
 }
@@ -62,7 +117,9 @@ impl Default for DnsResolverState {
     fn default() -> Self {
         Self {
             ipv6_enabled: true,
+            local_bind_address: None,
             in_flight_lookups: Default::default(),
+            ip_hints: Default::default(),
         }
     }
 }
@@ -185,61 +242,162 @@ impl DnsResolver {
         }
     }
 
+    /// Sets the local address that DNS transports bind to, or `None` to let the OS choose.
+    ///
+    /// Lookups already in progress keep using the old address, but new lookups won't join them.
+    pub fn set_local_bind_address(&self, local_bind_address: Option<IpAddr>) {
+        let mut guard = self.state.lock().expect("not poisoned");
+        if guard.local_bind_address != local_bind_address {
+            guard.local_bind_address = local_bind_address;
+            guard.in_flight_lookups.clear();
+        }
+    }
+
+    /// Forgets any lookups in progress, so that the next lookup of each hostname starts over.
+    ///
+    /// Callers already waiting on a lookup still get its result. Returns the number of lookups
+    /// forgotten.
+    pub fn flush(&self) -> usize {
+        let mut guard = self.state.lock().expect("not poisoned");
+        let count = guard.in_flight_lookups.len();
+        guard.in_flight_lookups.clear();
+        count
+    }
+
+    /// Adds `addrs` to the results of every lookup of `hostname` for the next `ttl`.
+    ///
+    /// Hinted addresses are placed after the ones from the regular lookup, so they're only tried
+    /// once those have been; if the regular lookup fails, the hints are used on their own.
+    /// Replaces any hints previously set for `hostname`.
+    ///
+    /// `ttl` is capped at [`MAX_IP_HINT_TTL`].
+    pub fn set_ip_hints(&self, hostname: &str, addrs: Vec<IpAddr>, ttl: Duration) {
+        let mut guard = self.state.lock().expect("not poisoned");
+        if addrs.is_empty() {
+            _ = guard.ip_hints.remove(hostname);
+            return;
+        }
+        let now = Instant::now();
+        let Some(expires_at) = now.checked_add(ttl.min(MAX_IP_HINT_TTL)) else {
+            log::warn!(
+                "cannot keep IP hints for {} that long; ignoring them",
+                log_safe_domain(hostname)
+            );
+            _ = guard.ip_hints.remove(hostname);
+            return;
+        };
+        _ = guard
+            .ip_hints
+            .insert(hostname.to_owned(), IpHints { addrs, expires_at });
+    }
+
+    /// Discards any hints set for `hostname` by [`Self::set_ip_hints`].
+    pub fn clear_ip_hints(&self, hostname: &str) {
+        _ = self
+            .state
+            .lock()
+            .expect("not poisoned")
+            .ip_hints
+            .remove(hostname);
+    }
+
+    /// The number of unexpired hinted addresses for `hostname`.
+    pub fn ip_hint_count(&self, hostname: &str) -> usize {
+        self.state
+            .lock()
+            .expect("not poisoned")
+            .live_ip_hints(hostname, Instant::now())
+            .map_or(0, |hints| hints.addrs.len())
+    }
+
     pub async fn lookup_ip(&self, hostname: &str) -> Result<LookupResult> {
-        let parse_as_ip_addr = hostname.parse().ok().or_else(|| {
-            let hostname = hostname.strip_prefix('[')?;
-            let hostname = hostname.strip_suffix(']')?;
-            Ipv6Addr::from_str(hostname).ok().map(std::net::IpAddr::V6)
-        });
-        if let Some(addr) = parse_as_ip_addr {
-            let (ipv4, ipv6) = match addr {
-                std::net::IpAddr::V4(ip) => (vec![ip], vec![]),
-                std::net::IpAddr::V6(ip) => (vec![], vec![ip]),
-            };
-            return Ok(LookupResult {
-                source: super::DnsSource::Static,
-                ipv4,
-                ipv6,
-            });
+        if let Some(result) = parse_ip_literal(hostname) {
+            return Ok(result);
         }
-        match self.start_or_join_lookup(hostname).val().await {
+        let result = match self.start_or_join_lookup(hostname).val().await {
             Ok(r) => r,
             Err(_) => {
                 log::warn!("Lookup task dropped before publishing the result");
                 Err(Error::LookupFailed)
             }
+        };
+        self.add_ip_hints(hostname, result)
+    }
+
+    /// Like [`Self::lookup_ip`], but produces the addresses of each IP family as soon as they're
+    /// known, in the order they arrive.
+    ///
+    /// Each family is looked up with the same strategies as `lookup_ip`, but falls back to the
+    /// next strategy on its own: a strategy that only answers for one family is still used for
+    /// that family. Hints from [`Self::set_ip_hints`] are added to each family's addresses. Unlike
+    /// `lookup_ip`, concurrent calls don't share a lookup.
+    pub fn lookup_ip_by_family(
+        &self,
+        hostname: &str,
+    ) -> impl Stream<Item = FamilyLookupResult> + Send + 'static {
+        if let Some(result) = parse_ip_literal(hostname) {
+            return futures_util::stream::iter(split_by_family(Ok(result))).left_stream();
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.spawn_lookup_by_family(Arc::from(hostname), tx);
+        UnboundedReceiverStream::new(rx).right_stream()
+    }
+
+    fn add_ip_hints(&self, hostname: &str, result: Result<LookupResult>) -> Result<LookupResult> {
+        let (hints, ipv6_enabled) = {
+            let mut guard = self.state.lock().expect("not poisoned");
+            let ipv6_enabled = guard.ipv6_enabled;
+            match guard.live_ip_hints(hostname, Instant::now()) {
+                Some(hints) => (hints.addrs.clone(), ipv6_enabled),
+                None => return result,
+            }
+        };
+
+        let (mut combined, error) = match result {
+            Ok(result) => (result, None),
+            Err(e) => (
+                LookupResult::new(DnsSource::Static, vec![], vec![]),
+                Some(e),
+            ),
+        };
+        for addr in hints {
+            match addr {
+                IpAddr::V4(ip) if !combined.ipv4.contains(&ip) => combined.ipv4.push(ip),
+                IpAddr::V6(ip) if ipv6_enabled && !combined.ipv6.contains(&ip) => {
+                    combined.ipv6.push(ip)
+                }
+                IpAddr::V4(_) | IpAddr::V6(_) => {}
+            }
+        }
+
+        match error {
+            Some(e) if combined.is_empty() => Err(e),
+            Some(_) | None => Ok(combined),
         }
     }
 
     fn start_or_join_lookup(&self, hostname: &str) -> Receiver<Result<LookupResult>> {
         let mut guard = self.state.lock().expect("not poisoned");
-        let ipv6_enabled = guard.ipv6_enabled;
+        let request = guard.lookup_request(Arc::from(hostname));
         guard
             .in_flight_lookups
             .entry(hostname.to_string())
             .or_insert_with(|| {
                 let (tx, rx) = oneshot_broadcast::channel();
-                self.spawn_lookup(hostname.to_string(), tx, ipv6_enabled);
+                self.spawn_lookup(request, tx);
                 rx
             })
             .clone()
     }
 
-    fn spawn_lookup(
-        &self,
-        hostname: String,
-        result_sender: Sender<Result<LookupResult>>,
-        ipv6_enabled: bool,
-    ) {
+    fn spawn_lookup(&self, request: DnsLookupRequest, result_sender: Sender<Result<LookupResult>>) {
         let Self {
             lookup_options,
             state,
         } = self.clone();
         tokio::spawn(async move {
-            let request = DnsLookupRequest {
-                hostname: Arc::from(hostname.as_str()),
-                ipv6_enabled,
-            };
+            let hostname = Arc::clone(&request.hostname);
+            let ipv6_enabled = request.ipv6_enabled;
 
             let successful_lookups = futures_util::stream::iter(lookup_options.iter())
                 .filter_map(|lookup_option| lookup_option.attempt(request.clone()).map(Result::ok));
@@ -262,7 +420,7 @@ impl DnsResolver {
                 .lock()
                 .expect("not poisoned")
                 .in_flight_lookups
-                .remove(&hostname);
+                .remove(&*hostname);
             if result_sender.send(result).is_err() {
                 log::debug!(
                     "No DNS result listeners left for domain [{}]",
@@ -271,6 +429,135 @@ impl DnsResolver {
             }
         });
     }
+
+    fn spawn_lookup_by_family(
+        &self,
+        hostname: Arc<str>,
+        result_sender: mpsc::UnboundedSender<FamilyLookupResult>,
+    ) {
+        let this = self.clone();
+        let request = self
+            .state
+            .lock()
+            .expect("not poisoned")
+            .lookup_request(Arc::clone(&hostname));
+        tokio::spawn(async move {
+            let mut pending = if request.ipv6_enabled {
+                vec![IpType::V6, IpType::V4]
+            } else {
+                vec![IpType::V4]
+            };
+            let send = |family: IpType, result: Result<Vec<IpAddr>>| {
+                if result_sender.send((family, result)).is_err() {
+                    log::debug!(
+                        "No DNS result listeners left for domain [{}]",
+                        log_safe_domain(&hostname)
+                    );
+                }
+            };
+
+            for lookup_option in this.lookup_options.iter() {
+                if pending.is_empty() {
+                    break;
+                }
+                let started_at = Instant::now();
+                let families = lookup_option.lookup.dns_lookup_by_family(request.clone());
+                let answer_pending = async {
+                    let mut families = std::pin::pin!(families);
+                    while let Some((family, result)) = families.next().await {
+                        let Some(index) = pending.iter().position(|p| *p == family) else {
+                            continue;
+                        };
+                        match result {
+                            Ok(addrs) => {
+                                log::debug!(
+                                    "Resolved {} for domain [{}] after {:?}",
+                                    family,
+                                    log_safe_domain(&hostname),
+                                    started_at.elapsed(),
+                                );
+                                pending.remove(index);
+                                send(
+                                    family,
+                                    Ok(this.add_family_ip_hints(&hostname, family, addrs)),
+                                );
+                            }
+                            Err(error) => {
+                                log::warn!(
+                                    "Failed to resolve {} for domain [{}] after {:?}: {}",
+                                    family,
+                                    log_safe_domain(&hostname),
+                                    started_at.elapsed(),
+                                    error,
+                                );
+                            }
+                        }
+                        if pending.is_empty() {
+                            break;
+                        }
+                    }
+                };
+                if tokio::time::timeout(lookup_option.timeout_after, answer_pending)
+                    .await
+                    .is_err()
+                {
+                    log::warn!(
+                        "Timed out resolving domain [{}] after {:?}",
+                        log_safe_domain(&hostname),
+                        started_at.elapsed(),
+                    );
+                }
+            }
+
+            for family in pending {
+                let hints = this.add_family_ip_hints(&hostname, family, vec![]);
+                send(
+                    family,
+                    if hints.is_empty() {
+                        Err(Error::LookupFailed)
+                    } else {
+                        Ok(hints)
+                    },
+                );
+            }
+        });
+    }
+
+    /// Adds any live hints for `hostname` in `family` to `addrs`.
+    fn add_family_ip_hints(
+        &self,
+        hostname: &str,
+        family: IpType,
+        mut addrs: Vec<IpAddr>,
+    ) -> Vec<IpAddr> {
+        let mut guard = self.state.lock().expect("not poisoned");
+        if let Some(hints) = guard.live_ip_hints(hostname, Instant::now()) {
+            for addr in &hints.addrs {
+                if IpType::from(addr) == family && !addrs.contains(addr) {
+                    addrs.push(*addr);
+                }
+            }
+        }
+        addrs
+    }
+}
+
+/// Parses `hostname` if it's an IP address literal, optionally with IPv6 brackets.
+fn parse_ip_literal(hostname: &str) -> Option<LookupResult> {
+    let addr = hostname.parse().ok().or_else(|| {
+        let hostname = hostname.strip_prefix('[')?;
+        let hostname = hostname.strip_suffix(']')?;
+        Ipv6Addr::from_str(hostname).ok().map(IpAddr::V6)
+    })?;
+    let (ipv4, ipv6) = match addr {
+        IpAddr::V4(ip) => (vec![ip], vec![]),
+        IpAddr::V6(ip) => (vec![], vec![ip]),
+    };
+    Some(LookupResult {
+        source: DnsSource::Static,
+        ipv4,
+        ipv6,
+    })
 }
 
 impl LookupOption {
@@ -315,6 +602,8 @@ mod test {
     use assert_matches::assert_matches;
     use async_trait::async_trait;
     use const_str::ip_addr;
+    use futures_util::stream::BoxStream;
+    use test_case::test_case;
 
     use super::*;
     use crate::dns::dns_lookup::DnsLookupRequest;
@@ -553,6 +842,30 @@ mod test {
         );
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_local_bind_address_is_passed_with_request() {
+        let test_lookup = TestLookup::standard_responses(Duration::ZERO);
+        let dns_resolver = DnsResolver::new_custom(vec![(test_lookup.clone(), ATTEMPT_TIMEOUT)]);
+        let local = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        let _ = dns_resolver.lookup_ip(IPV4_ONLY_DOMAIN).await;
+        dns_resolver.set_local_bind_address(Some(local));
+        let _ = dns_resolver.lookup_ip(IPV4_ONLY_DOMAIN).await;
+        assert_matches!(
+            test_lookup.logged_requests().as_slice(),
+            [
+                DnsLookupRequest {
+                    local_bind_address: None,
+                    ..
+                },
+                DnsLookupRequest {
+                    local_bind_address: Some(address),
+                    ..
+                },
+            ] if *address == local
+        );
+    }
+
     #[tokio::test(start_paused = true)]
     async fn test_flag_value_used_from_the_time_of_request() {
         let lookup_time = ATTEMPT_TIMEOUT / 2;
@@ -764,4 +1077,229 @@ mod test {
         // making sure that the `test_lookup` have only seen one request
         assert_matches!(test_lookup.logged_requests().as_slice(), [_, _]);
     }
+
+    /// Answers each IP family after its own delay, or fails it if there's no delay.
+    #[derive(Debug)]
+    struct PerFamilyLookup {
+        ipv4_delay: Option<Duration>,
+        ipv6_delay: Option<Duration>,
+    }
+
+    #[async_trait]
+    impl DnsLookup for PerFamilyLookup {
+        async fn dns_lookup(&self, _request: DnsLookupRequest) -> Result<LookupResult> {
+            unreachable!("only looked up by family")
+        }
+
+        fn dns_lookup_by_family(
+            &self,
+            _request: DnsLookupRequest,
+        ) -> BoxStream<'_, FamilyLookupResult> {
+            let answer = |family: IpType, delay: Option<Duration>, addr: IpAddr| async move {
+                match delay {
+                    Some(delay) => {
+                        tokio::time::sleep(delay).await;
+                        (family, Ok(vec![addr]))
+                    }
+                    None => (family, Err(Error::LookupFailed)),
+                }
+            };
+            futures_util::stream::select(
+                answer(IpType::V4, self.ipv4_delay, IPV4.into()).into_stream(),
+                answer(IpType::V6, self.ipv6_delay, IPV6.into()).into_stream(),
+            )
+            .boxed()
+        }
+    }
+
+    async fn lookup_by_family_with_timing(
+        dns_resolver: &DnsResolver,
+        hostname: &str,
+    ) -> Vec<(IpType, Option<Vec<IpAddr>>, Duration)> {
+        let started_at = Instant::now();
+        dns_resolver
+            .lookup_ip_by_family(hostname)
+            .map(|(family, result)| (family, result.ok(), started_at.elapsed()))
+            .collect()
+            .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lookup_by_family_produces_families_as_they_arrive() {
+        let dns_resolver = DnsResolver::new_custom(vec![(
+            Box::new(PerFamilyLookup {
+                ipv4_delay: Some(ATTEMPT_TIMEOUT / 2),
+                ipv6_delay: Some(Duration::ZERO),
+            }),
+            ATTEMPT_TIMEOUT,
+        )]);
+
+        assert_eq!(
+            lookup_by_family_with_timing(&dns_resolver, DUAL_STACK_DOMAIN).await,
+            [
+                (IpType::V6, Some(vec![IPV6.into()]), Duration::ZERO),
+                (IpType::V4, Some(vec![IPV4.into()]), ATTEMPT_TIMEOUT / 2),
+            ]
+        );
+    }
+
+    #[test_case(None => Duration::ZERO; "failed")]
+    #[test_case(Some(ATTEMPT_TIMEOUT * 10) => ATTEMPT_TIMEOUT; "timed out")]
+    #[tokio::test(start_paused = true)]
+    async fn test_lookup_by_family_falls_back_per_family(ipv4_delay: Option<Duration>) -> Duration {
+        let fallback = TestLookup::standard_responses(Duration::ZERO);
+        let dns_resolver = DnsResolver::new_custom(vec![
+            (
+                Box::new(PerFamilyLookup {
+                    ipv4_delay,
+                    ipv6_delay: Some(Duration::ZERO),
+                }),
+                ATTEMPT_TIMEOUT,
+            ),
+            (fallback.clone(), ATTEMPT_TIMEOUT),
+        ]);
+
+        let results = lookup_by_family_with_timing(&dns_resolver, DUAL_STACK_DOMAIN).await;
+        // The fallback's IPv6 answer is ignored, since the first lookup already gave one.
+        let [(IpType::V6, Some(ipv6), Duration::ZERO), (IpType::V4, Some(ipv4), ipv4_elapsed)] =
+            results.as_slice()
+        else {
+            panic!("unexpected results: {results:?}");
+        };
+        assert_eq!(ipv6, &[IpAddr::from(IPV6)]);
+        assert_eq!(ipv4, &[IpAddr::from(IPV4)]);
+        assert_matches!(fallback.logged_requests().as_slice(), [_]);
+        *ipv4_elapsed
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lookup_by_family_adds_ip_hints() {
+        const HINT_V4: Ipv4Addr = ip_addr!(v4, "192.0.2.2");
+
+        let dns_resolver = DnsResolver::new_custom(vec![(
+            TestLookup::standard_responses(Duration::ZERO),
+            ATTEMPT_TIMEOUT,
+        )]);
+        dns_resolver.set_ip_hints(
+            IPV6_ONLY_DOMAIN,
+            vec![HINT_V4.into()],
+            Duration::from_secs(60),
+        );
+
+        assert_eq!(
+            lookup_by_family_with_timing(&dns_resolver, IPV6_ONLY_DOMAIN).await,
+            [
+                (IpType::V6, Some(vec![IPV6.into()]), Duration::ZERO),
+                (IpType::V4, Some(vec![HINT_V4.into()]), Duration::ZERO),
+            ]
+        );
+
+        // Without IPv6, only IPv4 is looked up.
+        dns_resolver.set_ipv6_enabled(false);
+        assert_eq!(
+            lookup_by_family_with_timing(&dns_resolver, IPV6_ONLY_DOMAIN).await,
+            [(IpType::V4, Some(vec![HINT_V4.into()]), Duration::ZERO)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ip_hints_follow_lookup_results() {
+        const HINT_V4: Ipv4Addr = ip_addr!(v4, "192.0.2.2");
+        const HINT_V6: Ipv6Addr = ip_addr!(v6, "3fff::2");
+
+        let dns_resolver = DnsResolver::new_custom(vec![(
+            TestLookup::standard_responses(Duration::ZERO),
+            ATTEMPT_TIMEOUT,
+        )]);
+        dns_resolver.set_ip_hints(
+            DUAL_STACK_DOMAIN,
+            vec![HINT_V4.into(), IPV4.into(), HINT_V6.into()],
+            Duration::from_secs(60),
+        );
+        assert_eq!(dns_resolver.ip_hint_count(DUAL_STACK_DOMAIN), 3);
+
+        let result = dns_resolver
+            .lookup_ip(DUAL_STACK_DOMAIN)
+            .await
+            .expect("success");
+        assert_eq!(result.ipv4, [IPV4, HINT_V4]);
+        assert_eq!(result.ipv6, [IPV6, HINT_V6]);
+        assert_eq!(result.source(), DnsSource::Test);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ip_hints_used_when_lookup_fails() {
+        let dns_resolver = DnsResolver::new_custom(vec![(
+            TestLookup::standard_responses(Duration::ZERO),
+            ATTEMPT_TIMEOUT,
+        )]);
+        dns_resolver.set_ip_hints(
+            FALLBACK_ONLY_DOMAIN,
+            vec![IPV4.into(), IPV6.into()],
+            Duration::from_secs(60),
+        );
+
+        let result = dns_resolver
+            .lookup_ip(FALLBACK_ONLY_DOMAIN)
+            .await
+            .expect("success");
+        assert_eq!(result.ipv4, [IPV4]);
+        assert_eq!(result.ipv6, [IPV6]);
+
+        // Hints that are all filtered out don't hide the lookup failure.
+        dns_resolver.set_ipv6_enabled(false);
+        dns_resolver.set_ip_hints(
+            FALLBACK_ONLY_DOMAIN,
+            vec![IPV6.into()],
+            Duration::from_secs(60),
+        );
+        let result = dns_resolver.lookup_ip(FALLBACK_ONLY_DOMAIN).await;
+        assert_matches!(result, Err(Error::LookupFailed));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ip_hints_expire_and_can_be_cleared() {
+        const TTL: Duration = Duration::from_secs(60);
+
+        let dns_resolver = DnsResolver::new_custom(vec![(
+            TestLookup::standard_responses(Duration::ZERO),
+            ATTEMPT_TIMEOUT,
+        )]);
+        dns_resolver.set_ip_hints(FALLBACK_ONLY_DOMAIN, vec![IPV4.into()], TTL);
+
+        tokio::time::sleep(TTL).await;
+        assert_eq!(dns_resolver.ip_hint_count(FALLBACK_ONLY_DOMAIN), 0);
+        let result = dns_resolver.lookup_ip(FALLBACK_ONLY_DOMAIN).await;
+        assert_matches!(result, Err(Error::LookupFailed));
+
+        dns_resolver.set_ip_hints(FALLBACK_ONLY_DOMAIN, vec![IPV4.into()], TTL);
+        dns_resolver.clear_ip_hints(FALLBACK_ONLY_DOMAIN);
+        assert_eq!(dns_resolver.ip_hint_count(FALLBACK_ONLY_DOMAIN), 0);
+        let result = dns_resolver.lookup_ip(FALLBACK_ONLY_DOMAIN).await;
+        assert_matches!(result, Err(Error::LookupFailed));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ip_hints_ttl_is_capped() {
+        let dns_resolver = DnsResolver::new_custom(vec![]);
+        dns_resolver.set_ip_hints(FALLBACK_ONLY_DOMAIN, vec![IPV4.into()], Duration::MAX);
+        assert_eq!(dns_resolver.ip_hint_count(FALLBACK_ONLY_DOMAIN), 1);
+
+        tokio::time::sleep(MAX_IP_HINT_TTL).await;
+        assert_eq!(dns_resolver.ip_hint_count(FALLBACK_ONLY_DOMAIN), 0);
+    }
+
+    #[test]
+    fn test_ip_hints_debug_output_omits_addresses() {
+        let dns_resolver = DnsResolver::new_custom(vec![]);
+        dns_resolver.set_ip_hints(
+            DUAL_STACK_DOMAIN,
+            vec![IPV4.into(), IPV6.into()],
+            Duration::from_secs(60),
+        );
+        let debug = format!("{dns_resolver:?}");
+        assert!(debug.contains("ip_hints"), "{debug}");
+        assert!(!debug.contains(&IPV4.to_string()), "{debug}");
+        assert!(!debug.contains(&IPV6.to_string()), "{debug}");
+    }
 }
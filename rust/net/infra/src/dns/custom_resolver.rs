@@ -7,7 +7,7 @@ use std::cmp::min;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::future::Future;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -18,14 +18,14 @@ use tokio::time::Instant;
 
 use crate::connection_manager::{ConnectionAttemptOutcome, SingleRouteThrottlingConnectionManager};
 use crate::dns::dns_errors::Error;
-use crate::dns::dns_lookup::DnsLookupRequest;
+use crate::dns::dns_lookup::{split_by_family, DnsLookupRequest, FamilyLookupResult};
 use crate::dns::dns_types::Expiring;
 use crate::dns::dns_utils::log_safe_domain;
 use crate::dns::lookup_result::LookupResult;
 use crate::timeouts::{DNS_CALL_BACKGROUND_TIMEOUT, DNS_RESOLUTION_DELAY};
 use crate::utils::future::results_within_interval;
 use crate::utils::{EventSubscription, ObservableEvent};
-use crate::{dns, DnsSource};
+use crate::{dns, DnsSource, IpType};
 
 pub type DnsIpv4Result = Expiring<Vec<Ipv4Addr>>;
 pub type DnsIpv6Result = Expiring<Vec<Ipv6Addr>>;
@@ -47,9 +47,13 @@ pub trait DnsTransport: Debug + Sized + Send {
     ///
     /// Connection will be held open for as long as the returned instance is in use.
     /// Dropping the instance will close the connection and free the resources.
+    ///
+    /// If `local_bind_address` is set, the connection is made from that address; servers of
+    /// the other IP version can't be reached.
     fn connect(
         connection_params: Self::ConnectionParameters,
         ipv6_enabled: bool,
+        local_bind_address: Option<IpAddr>,
     ) -> impl Future<Output = dns::Result<Self>> + Send;
 
     /// Sends DNS queries and returns an async stream of the results
@@ -146,6 +150,52 @@ impl<T: DnsTransport + Sync + 'static> CustomDnsResolver<T> {
         }
     }
 
+    /// Like [`Self::resolve`], but produces each IP family's addresses as soon
+    /// as they arrive, instead of waiting up to [`DNS_RESOLUTION_DELAY`] for the
+    /// other family.
+    ///
+    /// A family whose query fails or never gets an answer produces
+    /// [`Error::LookupFailed`].
+    pub fn resolve_by_family(
+        &self,
+        request: DnsLookupRequest,
+    ) -> impl Stream<Item = FamilyLookupResult> + Send + '_ {
+        async move {
+            if let Some(res) = self.cache_get(&request.hostname) {
+                log::info!(
+                    "DNS record for {} found in cache",
+                    log_safe_domain(&request.hostname)
+                );
+                return futures_util::stream::iter(split_by_family(Ok(res))).left_stream();
+            }
+            log::info!(
+                "Starting DNS lookup for {}",
+                log_safe_domain(&request.hostname)
+            );
+            let transport = match self.connect_transport(&request).await {
+                Ok(transport) => transport,
+                Err(e) => return futures_util::stream::iter(split_by_family(Err(e))).left_stream(),
+            };
+            let (ipv4_res_rx, ipv6_res_rx) = self.send_dns_queries(transport, request);
+            let ipv4 = ipv4_res_rx.map(|result| match result {
+                Ok(res) => (
+                    IpType::V4,
+                    Ok(res.data.into_iter().map(IpAddr::V4).collect()),
+                ),
+                Err(_) => (IpType::V4, Err(Error::LookupFailed)),
+            });
+            let ipv6 = ipv6_res_rx.map(|result| match result {
+                Ok(res) => (
+                    IpType::V6,
+                    Ok(res.data.into_iter().map(IpAddr::V6).collect()),
+                ),
+                Err(_) => (IpType::V6, Err(Error::LookupFailed)),
+            });
+            futures_util::stream::select(ipv4.into_stream(), ipv6.into_stream()).right_stream()
+        }
+        .flatten_stream()
+    }
+
     fn cache_get(&self, hostname: &str) -> Option<LookupResult> {
         let mut guard = self.cache.lock().expect("not poisoned");
         match guard.map.get(hostname) {
@@ -158,16 +208,26 @@ impl<T: DnsTransport + Sync + 'static> CustomDnsResolver<T> {
         }
     }
 
-    async fn lookup(&self, request: DnsLookupRequest) -> dns::Result<LookupResult> {
-        let transport = match self
+    async fn connect_transport(&self, request: &DnsLookupRequest) -> dns::Result<T> {
+        match self
             .connection_manager
-            .connect_or_wait(|params| T::connect(params.clone(), request.ipv6_enabled))
+            .connect_or_wait(|params| {
+                T::connect(
+                    params.clone(),
+                    request.ipv6_enabled,
+                    request.local_bind_address,
+                )
+            })
             .await
         {
             ConnectionAttemptOutcome::Attempted(result) => result,
             ConnectionAttemptOutcome::TimedOut => Err(Error::Timeout),
             ConnectionAttemptOutcome::WaitUntil(_) => Err(Error::Cooldown),
-        }?;
+        }
+    }
+
+    async fn lookup(&self, request: DnsLookupRequest) -> dns::Result<LookupResult> {
+        let transport = self.connect_transport(&request).await?;
         let (ipv4_res_rx, ipv6_res_rx) = self.send_dns_queries(transport, request);
         let (maybe_ipv4, maybe_ipv6) = results_within_interval(
             ipv4_res_rx.map(Result::ok),
@@ -378,6 +438,7 @@ pub(crate) mod test {
         async fn connect(
             connection_params: Self::ConnectionParameters,
             _ipv6_enabled: bool,
+            _local_bind_address: Option<IpAddr>,
         ) -> dns::Result<Self> {
             Err(connection_params)
         }
@@ -468,6 +529,7 @@ pub(crate) mod test {
         async fn connect(
             connection_params: Self::ConnectionParameters,
             _ipv6_enabled: bool,
+            _local_bind_address: Option<IpAddr>,
         ) -> dns::Result<Self> {
             Ok(connection_params)
         }
@@ -547,6 +609,7 @@ pub(crate) mod test {
         DnsLookupRequest {
             hostname: Arc::from("chat.signal.org"),
             ipv6_enabled: true,
+            local_bind_address: None,
         }
     }
 
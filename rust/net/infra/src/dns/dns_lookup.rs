@@ -5,27 +5,60 @@
 
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use either::Either;
+use futures_util::stream::BoxStream;
+use futures_util::{FutureExt as _, StreamExt as _};
 use itertools::Itertools;
 
 use crate::dns::custom_resolver::{CustomDnsResolver, DnsTransport};
 use crate::dns::dns_errors::Error;
 use crate::dns::lookup_result::LookupResult;
-use crate::{dns, DnsSource};
+use crate::{dns, DnsSource, IpType};
 
 #[derive(Clone, Debug)]
 pub struct DnsLookupRequest {
     pub hostname: Arc<str>,
     pub ipv6_enabled: bool,
+    /// The local address transports should bind to when connecting to a DNS server.
+    pub local_bind_address: Option<IpAddr>,
 }
 
+/// The addresses of one IP family, as found by a lookup that produces each
+/// family separately.
+pub type FamilyLookupResult = (IpType, dns::Result<Vec<IpAddr>>);
+
 #[async_trait]
 pub trait DnsLookup: Debug + Send + Sync {
     async fn dns_lookup(&self, request: DnsLookupRequest) -> dns::Result<LookupResult>;
+
+    /// Like [`Self::dns_lookup`], but produces the addresses of each IP family
+    /// as soon as they're known.
+    ///
+    /// The default implementation waits for [`Self::dns_lookup`] and then
+    /// produces both families at once.
+    fn dns_lookup_by_family(&self, request: DnsLookupRequest) -> BoxStream<'_, FamilyLookupResult> {
+        self.dns_lookup(request)
+            .map(|result| futures_util::stream::iter(split_by_family(result)))
+            .flatten_stream()
+            .boxed()
+    }
+}
+
+/// Splits the result of a lookup into one result per IP family, IPv6 first.
+///
+/// A failed lookup fails for both families.
+pub(crate) fn split_by_family(result: dns::Result<LookupResult>) -> [FamilyLookupResult; 2] {
+    match result {
+        Ok(LookupResult { ipv4, ipv6, .. }) => [
+            (IpType::V6, Ok(ipv6.into_iter().map(IpAddr::V6).collect())),
+            (IpType::V4, Ok(ipv4.into_iter().map(IpAddr::V4).collect())),
+        ],
+        Err(e) => [(IpType::V6, Err(e.clone())), (IpType::V4, Err(e))],
+    }
 }
 
 /// Performs DNS lookup using system resolver
@@ -73,4 +106,8 @@ where
     async fn dns_lookup(&self, request: DnsLookupRequest) -> dns::Result<LookupResult> {
         self.resolve(request).await
     }
+
+    fn dns_lookup_by_family(&self, request: DnsLookupRequest) -> BoxStream<'_, FamilyLookupResult> {
+        self.resolve_by_family(request).boxed()
+    }
 }
@@ -44,12 +44,24 @@ impl DnsTransport for DohTransport {
     async fn connect(
         mut connection_params: Self::ConnectionParameters,
         ipv6_enabled: bool,
+        local_bind_address: Option<IpAddr>,
     ) -> dns::Result<Self> {
         let log_tag = "DNS-over-HTTPS".into();
 
-        connection_params.retain(|route| ipv6_enabled || route.immediate_target().is_ipv4());
+        connection_params.retain(|route| {
+            let target = route.immediate_target();
+            (ipv6_enabled || target.is_ipv4())
+                && !local_bind_address.is_some_and(|local| local.is_ipv4() != target.is_ipv4())
+        });
 
-        match http2_client(connection_params, MAX_RESPONSE_SIZE, &log_tag).await {
+        match http2_client(
+            connection_params,
+            MAX_RESPONSE_SIZE,
+            local_bind_address,
+            &log_tag,
+        )
+        .await
+        {
             Ok(http_client) => Ok(Self { http_client }),
             Err(error) => {
                 log::error!("[{log_tag}] Failed to create HTTP2 client: {error}");
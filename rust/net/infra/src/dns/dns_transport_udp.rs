@@ -36,11 +36,16 @@ impl DnsTransport for UdpTransport {
     async fn connect(
         connection_params: Self::ConnectionParameters,
         ipv6_enabled: bool,
+        local_bind_address: Option<IpAddr>,
     ) -> dns::Result<UdpTransport> {
-        let local_addr = match connection_params.0 {
-            IpAddr::V4(_) => (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
-            IpAddr::V6(_) if !ipv6_enabled => return Err(Error::TransportRestricted),
-            IpAddr::V6(_) => (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        let local_addr = match (connection_params.0, local_bind_address) {
+            (IpAddr::V6(_), _) if !ipv6_enabled => return Err(Error::TransportRestricted),
+            (server, Some(local)) if server.is_ipv4() != local.is_ipv4() => {
+                return Err(Error::TransportRestricted)
+            }
+            (_, Some(local)) => (local, 0),
+            (IpAddr::V4(_), None) => (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            (IpAddr::V6(_), None) => (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
         };
         let socket = UdpSocket::bind(local_addr).await?;
         socket.connect(connection_params).await?;
@@ -4,7 +4,7 @@
 //
 
 use std::fmt::Display;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
 use tokio_boring_signal::HandshakeError;
 
@@ -23,7 +23,7 @@ pub struct RetryLater {
 }
 
 /// Errors that can occur during transport-level connection establishment.
-#[derive(displaydoc::Display, Debug, thiserror::Error)]
+#[derive(displaydoc::Display, Debug, Clone, thiserror::Error)]
 pub enum TransportConnectError {
     /// Invalid configuration for this connection
     InvalidConfiguration,
@@ -37,14 +37,40 @@ pub enum TransportConnectError {
     CertError,
     /// Failed to establish SSL connection: {0}
     SslFailedHandshake(FailedHandshakeReason),
-    /// Proxy handshake failed
-    ProxyProtocol,
+    /// Proxy connection failed: {0}
+    ProxyFailure(ProxyFailureKind),
+    /// Failed to bind to the configured local address
+    LocalBindFailed,
     /// Abort due to local error
     ClientAbort,
 }
 impl LogSafeDisplay for TransportConnectError {}
 
-#[derive(Debug)]
+/// Why a connection through a proxy failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+pub enum ProxyFailureKind {
+    /// proxy is unreachable
+    Unreachable,
+    /// proxy rejected the handshake
+    HandshakeRejected,
+    /// proxy requires authentication
+    AuthRequired,
+    /// proxy rejected the provided credentials
+    AuthFailed,
+}
+
+impl TransportConnectError {
+    /// Reports a failure to connect to a proxy server as the proxy being
+    /// unreachable, leaving other errors as they are.
+    pub(crate) fn connecting_to_proxy(self) -> Self {
+        match self {
+            Self::TcpConnectionFailed => Self::ProxyFailure(ProxyFailureKind::Unreachable),
+            e => e,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SslErrorReasons(boring_signal::error::ErrorStack);
 
 impl Display for SslErrorReasons {
@@ -60,7 +86,7 @@ impl Display for SslErrorReasons {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FailedHandshakeReason {
     io: Option<std::io::ErrorKind>,
     code: Option<boring_signal::ssl::ErrorCode>,
@@ -75,6 +101,15 @@ impl<S> From<HandshakeError<S>> for FailedHandshakeReason {
     }
 }
 
+impl From<std::io::ErrorKind> for FailedHandshakeReason {
+    fn from(io: std::io::ErrorKind) -> Self {
+        Self {
+            io: Some(io),
+            code: None,
+        }
+    }
+}
+
 impl LogSafeDisplay for FailedHandshakeReason {}
 impl Display for FailedHandshakeReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -95,9 +130,73 @@ impl Display for FailedHandshakeReason {
 }
 
 impl RetryLater {
-    /// The amount of time to wait before retrying, as a [`Duration`].
-    pub fn duration(&self) -> Duration {
-        Duration::from_secs(self.retry_after_seconds.into())
+    /// The amount of time to wait before retrying, capped at
+    /// [`RetryDelay::DEFAULT_MAX`].
+    pub fn delay(&self) -> RetryDelay {
+        RetryDelay::from_secs(self.retry_after_seconds)
+    }
+}
+
+/// How long to wait before retrying, as asked for by the server.
+///
+/// The server's value is untrusted and can be arbitrarily large, so it's
+/// capped before it's ever added to a clock, and all the arithmetic here
+/// saturates instead of overflowing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RetryDelay(Duration);
+
+impl RetryDelay {
+    /// Longer than any delay the server has a reason to ask for.
+    pub const DEFAULT_MAX: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    /// A delay of `secs` seconds, capped at [`Self::DEFAULT_MAX`].
+    pub fn from_secs(secs: u32) -> Self {
+        Self(Duration::from_secs(secs.into()).min(Self::DEFAULT_MAX))
+    }
+
+    /// Caps the delay at `max`, for callers that won't wait as long as
+    /// [`Self::DEFAULT_MAX`].
+    pub fn capped_at(self, max: Duration) -> Self {
+        Self(self.0.min(max))
+    }
+
+    pub fn as_duration(self) -> Duration {
+        self.0
+    }
+
+    /// The delay in whole milliseconds, for platform APIs that take them.
+    pub fn as_millis(self) -> u64 {
+        self.0.as_millis().try_into().unwrap_or(u64::MAX)
+    }
+
+    /// When to retry, given when the response asking for the delay was
+    /// received.
+    ///
+    /// If the full delay can't be represented, this is as late as can be.
+    pub fn retry_at(self, received_at: Instant) -> Instant {
+        saturating_add(received_at, self.0, Instant::checked_add)
+    }
+
+    /// Like [`Self::retry_at`], but for wall-clock times.
+    pub fn retry_at_system_time(self, received_at: SystemTime) -> SystemTime {
+        saturating_add(received_at, self.0, SystemTime::checked_add)
+    }
+}
+
+/// Adds as much of `delay` to `start` as fits.
+///
+/// Neither [`Instant`] nor [`SystemTime`] has a maximum value to saturate to,
+/// so this backs off until the sum is representable.
+fn saturating_add<T: Copy>(
+    start: T,
+    mut delay: Duration,
+    checked_add: impl Fn(&T, Duration) -> Option<T>,
+) -> T {
+    loop {
+        if let Some(sum) = checked_add(&start, delay) {
+            return sum;
+        }
+        delay /= 2;
     }
 }
 
@@ -124,14 +223,73 @@ impl From<TransportConnectError> for std::io::Error {
         use std::io::ErrorKind;
         let kind = match value {
             TransportConnectError::InvalidConfiguration => ErrorKind::InvalidInput,
-            TransportConnectError::TcpConnectionFailed => ErrorKind::ConnectionRefused,
+            TransportConnectError::TcpConnectionFailed
+            | TransportConnectError::ProxyFailure(ProxyFailureKind::Unreachable) => {
+                ErrorKind::ConnectionRefused
+            }
             TransportConnectError::SslFailedHandshake(_)
             | TransportConnectError::SslError(_)
             | TransportConnectError::CertError
-            | TransportConnectError::ProxyProtocol => ErrorKind::InvalidData,
+            | TransportConnectError::ProxyFailure(
+                ProxyFailureKind::HandshakeRejected
+                | ProxyFailureKind::AuthRequired
+                | ProxyFailureKind::AuthFailed,
+            ) => ErrorKind::InvalidData,
             TransportConnectError::DnsError => ErrorKind::NotFound,
+            TransportConnectError::LocalBindFailed => ErrorKind::AddrNotAvailable,
             TransportConnectError::ClientAbort => ErrorKind::ConnectionAborted,
         };
         Self::new(kind, value.to_string())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use proptest::proptest;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn retry_delay_is_capped(secs: u32) {
+            let delay = RetryDelay::from_secs(secs).as_duration();
+            assert!(delay <= RetryDelay::DEFAULT_MAX);
+            assert_eq!(delay, Duration::from_secs(secs.into()).min(RetryDelay::DEFAULT_MAX));
+        }
+
+        #[test]
+        fn retry_delay_respects_smaller_caps(secs: u32, max_secs: u32) {
+            let max = Duration::from_secs(max_secs.into());
+            let capped = RetryDelay::from_secs(secs).capped_at(max);
+            assert!(capped.as_duration() <= max);
+            assert!(capped <= RetryDelay::from_secs(secs));
+        }
+
+        #[test]
+        fn retry_delay_conversions_agree(secs: u32) {
+            let delay = RetryDelay::from_secs(secs);
+            assert_eq!(u128::from(delay.as_millis()), delay.as_duration().as_millis());
+
+            let received_at = Instant::now();
+            assert_eq!(delay.retry_at(received_at) - received_at, delay.as_duration());
+
+            let received_at = SystemTime::UNIX_EPOCH;
+            assert_eq!(
+                delay
+                    .retry_at_system_time(received_at)
+                    .duration_since(received_at)
+                    .expect("not earlier"),
+                delay.as_duration()
+            );
+        }
+    }
+
+    #[test]
+    fn retry_at_saturates() {
+        let delay = RetryDelay(Duration::MAX);
+        let now = Instant::now();
+        assert!(delay.retry_at(now) > now);
+        let wall_now = SystemTime::now();
+        assert!(delay.retry_at_system_time(wall_now) > wall_now);
+    }
+}
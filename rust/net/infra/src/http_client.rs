@@ -197,11 +197,15 @@ where
 pub(crate) async fn http2_client(
     targets: impl IntoIterator<Item = HttpsTlsRoute<TlsRoute<TcpRoute<IpAddr>>>>,
     max_response_size: usize,
+    local_bind_address: Option<IpAddr>,
     log_tag: &Arc<str>,
 ) -> Result<AggregatingHttp2Client, HttpError> {
     let tls_connector = crate::route::ComposedConnector::new(
-        crate::tcp_ssl::StatelessDirect,
-        ThrottlingConnector::new(crate::tcp_ssl::StatelessDirect, 1),
+        crate::tcp_ssl::StatelessDirect::bound_to(local_bind_address),
+        ThrottlingConnector::new(
+            crate::tcp_ssl::StatelessDirect::bound_to(local_bind_address),
+            1,
+        ),
     );
     let connector = StatelessHttp2Connector(tls_connector);
     let CompletedH2Connection {
@@ -332,6 +336,7 @@ mod test {
                 },
             }],
             MAX_RESPONSE_SIZE,
+            None,
             &"test".into(),
         )
         .await
@@ -407,6 +412,7 @@ mod test {
                 },
             }],
             MAX_RESPONSE_SIZE,
+            None,
             &"test".into(),
         )
         .await
@@ -17,13 +17,10 @@ use http::{HeaderMap, HeaderName, HeaderValue};
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::certs::RootCertificates;
-use crate::connection_manager::{
-    MultiRouteConnectionManager, SingleRouteThrottlingConnectionManager,
-};
+use crate::connection_manager::{MultiRouteConnectionManager, RouteCooldowns};
 use crate::errors::{LogSafeDisplay, RetryLater, TransportConnectError};
 use crate::host::Host;
 use crate::timeouts::{WS_KEEP_ALIVE_INTERVAL, WS_MAX_IDLE_INTERVAL};
-use crate::utils::ObservableEvent;
 use crate::ws::WebSocketConfig;
 
 pub mod certs;
@@ -41,8 +38,7 @@ pub mod utils;
 pub mod ws;
 pub mod ws2;
 
-#[derive(Copy, Clone, Debug)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum IpType {
     V4 = 1,
@@ -244,6 +240,21 @@ pub enum RouteType {
     Test,
 }
 
+impl RouteType {
+    /// The same coarse description as
+    /// [`UnresolvedRouteDescription::route_kind`](route::UnresolvedRouteDescription::route_kind),
+    /// for connections that weren't made through a [`route`] provider.
+    pub fn route_kind(&self) -> &'static str {
+        match self {
+            RouteType::Direct => "direct",
+            RouteType::ProxyF | RouteType::ProxyG => "domain_fronted",
+            RouteType::TlsProxy | RouteType::SocksProxy => "proxy",
+            #[cfg(any(test, feature = "test-util"))]
+            RouteType::Test => "direct",
+        }
+    }
+}
+
 impl ServiceConnectionInfo {
     pub fn description(&self) -> String {
         let ip_type = match IpType::from_host(&self.address) {
@@ -357,19 +368,13 @@ impl EndpointConnection<MultiRouteConnectionManager> {
         connection_params: impl IntoIterator<Item = ConnectionParams>,
         one_route_connect_timeout: Duration,
         config: WebSocketConfig,
-        network_changed_event: &ObservableEvent,
+        route_cooldowns: &RouteCooldowns,
     ) -> Self {
         Self {
             manager: MultiRouteConnectionManager::new(
                 connection_params
                     .into_iter()
-                    .map(|params| {
-                        SingleRouteThrottlingConnectionManager::new(
-                            params,
-                            one_route_connect_timeout,
-                            network_changed_event,
-                        )
-                    })
+                    .map(|params| route_cooldowns.manager(params, one_route_connect_timeout))
                     .collect(),
             ),
             config,
@@ -5,6 +5,7 @@
 
 use std::hash::Hash;
 use std::net::IpAddr;
+use std::num::NonZeroUsize;
 use std::ops::ControlFlow;
 use std::sync::Arc;
 use std::time::Duration;
@@ -220,6 +221,25 @@ pub struct OutcomeUpdates<R> {
     pub finished_at: Instant,
 }
 
+/// Controls how [`connect`] races attempts over different routes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionRacing {
+    /// How long to wait before starting another connection attempt, for each
+    /// attempt already in progress.
+    pub per_connection_wait: Duration,
+    /// The most connection attempts to have in progress at once.
+    pub max_concurrent_attempts: NonZeroUsize,
+}
+
+impl Default for ConnectionRacing {
+    fn default() -> Self {
+        Self {
+            per_connection_wait: PER_CONNECTION_WAIT_DURATION,
+            max_concurrent_attempts: NonZeroUsize::MAX,
+        }
+    }
+}
+
 /// Attempt to connect to routes from the given [`RouteProvider`].
 ///
 /// Generates the sequence of routes from the given `RouteProvider` and then
@@ -235,10 +255,14 @@ pub struct OutcomeUpdates<R> {
 /// server is reachable but immediately closes the connection with an HTTP 4xx
 /// error.
 ///
+/// Attempts are started one after another, as described by `racing`, so that
+/// a slow route doesn't hold up the rest.
+///
 /// The `Future` returned by this function resolves when all connection attempts
 /// are exhausted or a one of them produces a fatal error.
 pub async fn connect<R, UR, C, Inner, FatalError>(
     route_resolver: &RouteResolver,
+    racing: ConnectionRacing,
     delay_policy: impl RouteDelayPolicy<R>,
     ordered_routes: impl Iterator<Item = UR>,
     resolver: &impl Resolver,
@@ -260,6 +284,7 @@ where
 
     connect_inner(
         resolver_stream,
+        racing,
         delay_policy,
         connector,
         inner,
@@ -291,6 +316,7 @@ where
 {
     connect_inner(
         futures_util::stream::once(std::future::ready(schedule::as_resolved_group(routes))),
+        ConnectionRacing::default(),
         delay_policy,
         connector,
         inner,
@@ -302,6 +328,7 @@ where
 
 async fn connect_inner<R, C, Inner, FatalError>(
     resolver_stream: impl FusedStream<Item = (ResolvedRoutes<R>, ResolveMeta)>,
+    racing: ConnectionRacing,
     delay_policy: impl RouteDelayPolicy<R>,
     connector: C,
     inner: Inner,
@@ -348,16 +375,21 @@ where
 
     let outcome = loop {
         // If there's still a Schedule to pull from, poll it for more routes
-        // or sleep until that's supposed to start.
-        let poll_or_wait = schedule.as_mut().as_pin_mut().map(|schedule| {
-            if poll_schedule_for_next {
-                Either::Left(schedule.next().map(Event::NextRouteAvailable))
-            } else {
-                Either::Right(
+        // or sleep until that's supposed to start. If there are already as
+        // many attempts in progress as allowed, wait for one of them to finish
+        // first.
+        let at_max_attempts = connects_in_progress.len() >= racing.max_concurrent_attempts.get();
+        let poll_or_wait = schedule.as_mut().as_pin_mut().and_then(|schedule| {
+            if !poll_schedule_for_next {
+                Some(Either::Right(
                     sleep_until_start_next_connection
                         .as_mut()
                         .map(|()| Event::StartNextConnection),
-                )
+                ))
+            } else if !at_max_attempts {
+                Some(Either::Left(schedule.next().map(Event::NextRouteAvailable)))
+            } else {
+                None
             }
         });
 
@@ -398,7 +430,8 @@ where
                 most_recent_connection_start = Instant::now();
 
                 sleep_until_start_next_connection.as_mut().reset(
-                    most_recent_connection_start + pull_next_route_delay(&connects_in_progress),
+                    most_recent_connection_start
+                        + pull_next_route_delay(racing, &connects_in_progress),
                 );
             }
             Event::NextRouteAvailable(None) => {
@@ -429,7 +462,8 @@ where
 
                 // We probably now want to start the next connection sooner.
                 sleep_until_start_next_connection.as_mut().reset(
-                    most_recent_connection_start + pull_next_route_delay(&connects_in_progress),
+                    most_recent_connection_start
+                        + pull_next_route_delay(racing, &connects_in_progress),
                 );
             }
             Event::LogStatus => {
@@ -463,10 +497,13 @@ impl<E: std::fmt::Display> std::fmt::Display for ConnectError<E> {
 
 const PER_CONNECTION_WAIT_DURATION: Duration = Duration::from_millis(500);
 
-fn pull_next_route_delay<F>(connects_in_progress: &FuturesUnordered<F>) -> Duration {
+fn pull_next_route_delay<F>(
+    racing: ConnectionRacing,
+    connects_in_progress: &FuturesUnordered<F>,
+) -> Duration {
     let connections_factor = connects_in_progress.len().try_into().unwrap_or(u32::MAX);
 
-    PER_CONNECTION_WAIT_DURATION * connections_factor
+    racing.per_connection_wait * connections_factor
 }
 
 impl<R: RouteProvider> RouteProvider for &R {
@@ -916,6 +953,7 @@ mod test {
         let _connection_task = tokio::spawn(async move {
             connect(
                 &RouteResolver::default(),
+                ConnectionRacing::default(),
                 &outcomes,
                 HOSTNAMES
                     .iter()
@@ -1017,6 +1055,7 @@ mod test {
 
         let (result, updates) = connect(
             &RouteResolver::default(),
+            ConnectionRacing::default(),
             &outcomes,
             HOSTNAMES
                 .iter()
@@ -1096,6 +1135,7 @@ mod test {
 
         let (result, _updates) = connect(
             &RouteResolver::default(),
+            ConnectionRacing::default(),
             &outcomes,
             HOSTNAMES
                 .iter()
@@ -1167,6 +1207,7 @@ mod test {
             let route_resolver = RouteResolver::default();
             super::connect(
                 &route_resolver,
+                ConnectionRacing::default(),
                 outcomes,
                 HOSTNAMES
                     .iter()
@@ -1222,6 +1263,7 @@ mod test {
             let route_resolver = RouteResolver::default();
             super::connect(
                 &route_resolver,
+                ConnectionRacing::default(),
                 outcomes,
                 HOSTNAMES
                     .iter()
@@ -1260,4 +1302,75 @@ mod test {
         let (result, _outcomes) = connect_task.await.unwrap();
         assert_matches!(result, Err(_));
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_respects_racing_limits() {
+        const HOSTNAMES: &[(&str, Ipv6Addr)] = &[
+            ("A", ip_addr!(v6, "3fff::1")),
+            ("B", ip_addr!(v6, "3fff::2")),
+            ("C", ip_addr!(v6, "3fff::3")),
+        ];
+        const WAIT: Duration = Duration::from_secs(2);
+
+        let (connector, mut connection_responders) = FakeConnector::new();
+
+        let outcomes = NoDelay;
+        let resolver = HashMap::from_iter(HOSTNAMES.iter().map(|(name, ip)| {
+            (
+                *name,
+                LookupResult {
+                    source: DnsSource::Test,
+                    ipv4: vec![],
+                    ipv6: vec![*ip],
+                },
+            )
+        }));
+
+        let start = Instant::now();
+        let connect_task = tokio::spawn(async move {
+            let route_resolver = RouteResolver::default();
+            super::connect(
+                &route_resolver,
+                ConnectionRacing {
+                    per_connection_wait: WAIT,
+                    max_concurrent_attempts: nonzero!(2usize),
+                },
+                outcomes,
+                HOSTNAMES
+                    .iter()
+                    .map(|(h, _addr)| FakeRoute(UnresolvedHost::from(Arc::from(*h)))),
+                &resolver,
+                connector,
+                (),
+                "test".into(),
+                |_err: FakeConnectError| ControlFlow::<Infallible>::Continue(()),
+            )
+            .await
+        });
+
+        let a = connection_responders.next().await.expect("first");
+        let _b = connection_responders.next().await.expect("second");
+        assert_eq!(
+            start + WAIT,
+            Instant::now(),
+            "should use the custom stagger"
+        );
+
+        // With two attempts in progress, the third shouldn't start no matter
+        // how long we wait.
+        tokio::time::sleep(WAIT * 10).await;
+        assert_matches!(
+            connection_responders.next().now_or_never(),
+            None,
+            "should not exceed the maximum number of attempts"
+        );
+
+        a.respond(Err(FakeConnectError));
+        let c = connection_responders.next().await.expect("third");
+        assert_eq!(c.route(), &FakeRoute(ip_addr!("3fff::3")));
+        c.respond(Ok(()));
+
+        let (result, _outcomes) = connect_task.await.unwrap();
+        assert_matches!(result, Ok(_));
+    }
 }
@@ -67,6 +67,13 @@ impl<R, F: ConnectorFactory<R>> PreconnectingFactory<R, F> {
         }
     }
 
+    /// The wrapped factory, for changing how future connections are made.
+    ///
+    /// A connection that has already been saved is still used.
+    pub fn inner_factory_mut(&mut self) -> &mut F {
+        &mut self.inner_factory
+    }
+
     pub fn save_preconnected(&self, route: R, connection: F::Connection, established: Instant) {
         let mut saved_guard = self.shared.saved.lock().expect("not poisoned");
         if saved_guard
@@ -134,6 +134,16 @@ impl std::fmt::Display for UnresolvedRouteDescription {
 }
 
 impl UnresolvedRouteDescription {
+    /// A coarse, low-cardinality description of how the route reaches its
+    /// target: `"proxy"`, `"domain_fronted"`, or `"direct"`.
+    pub fn route_kind(&self) -> &'static str {
+        match (&self.proxy, &self.front) {
+            (Some(_), _) => "proxy",
+            (None, Some(_)) => "domain_fronted",
+            (None, None) => "direct",
+        }
+    }
+
     pub fn fake() -> Self {
         Self {
             front: None,
@@ -43,6 +43,8 @@ pub struct HttpProxyRouteFragment<Addr> {
     pub target_port: NonZeroU16,
     /// An authorization header to pass to the proxy.
     pub authorization: Option<HttpProxyAuth>,
+    /// Additional headers to pass to the proxy.
+    pub connect_headers: HttpProxyConnectHeaders,
 }
 
 /// Username and password to pass to an HTTP proxy.
@@ -52,6 +54,91 @@ pub struct HttpProxyAuth {
     pub password: String,
 }
 
+/// Extra headers to send to an HTTP proxy with the CONNECT request.
+///
+/// Some proxies require more than a username and password, like a bearer token
+/// in `Proxy-Authorization` or a header identifying the tenant. These are sent
+/// after the [`HttpProxyAuth`] header, if there is one. `Proxy-Authorization`
+/// is reserved for that header when it's present; see
+/// [`Self::proxy_authorization`].
+///
+/// The values might be credentials, so only the header names are included in
+/// the `Debug` output.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct HttpProxyConnectHeaders(Arc<[(http::HeaderName, http::HeaderValue)]>);
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum InvalidProxyConnectHeader {
+    /// header name is not a valid HTTP token
+    InvalidName,
+    /// value for header '{0}' is not printable ASCII
+    InvalidValue(http::HeaderName),
+    /// header '{0}' is set by libsignal and can't be overridden
+    Reserved(http::HeaderName),
+    /// headers are larger than the allowed total size
+    TooLarge,
+}
+
+impl LogSafeDisplay for InvalidProxyConnectHeader {}
+
+impl HttpProxyConnectHeaders {
+    /// The most bytes of header names and values that can be sent, to keep
+    /// the CONNECT request well under what proxies accept.
+    pub const MAX_TOTAL_SIZE: usize = 4096;
+
+    pub fn new<'a>(
+        headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, InvalidProxyConnectHeader> {
+        let mut total_size = 0;
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| {
+                let name = http::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| InvalidProxyConnectHeader::InvalidName)?;
+                if name == http::header::HOST {
+                    return Err(InvalidProxyConnectHeader::Reserved(name));
+                }
+                // Unlike the `TryFrom` impls, this rejects non-ASCII bytes.
+                let value = http::HeaderValue::from_str(value)
+                    .map_err(|_| InvalidProxyConnectHeader::InvalidValue(name.clone()))?;
+
+                total_size += name.as_str().len() + value.len();
+                if total_size > Self::MAX_TOTAL_SIZE {
+                    return Err(InvalidProxyConnectHeader::TooLarge);
+                }
+                Ok((name, value))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(headers.into()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&http::HeaderName, &http::HeaderValue)> {
+        self.0.iter().map(|(name, value)| (name, value))
+    }
+
+    /// Whether these headers include their own `Proxy-Authorization`.
+    ///
+    /// That header can't be combined with an [`HttpProxyAuth`], which sends
+    /// `Proxy-Authorization` too.
+    pub fn proxy_authorization(&self) -> bool {
+        self.0
+            .iter()
+            .any(|(name, _)| name == http::header::PROXY_AUTHORIZATION)
+    }
+}
+
+impl std::fmt::Debug for HttpProxyConnectHeaders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, _)| (name, "REDACTED")))
+            .finish()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, strum::EnumDiscriminants)]
 #[strum_discriminants(name(ConnectionProxyKind))]
 pub enum ConnectionProxyRoute<Addr> {
@@ -124,6 +211,7 @@ pub struct HttpProxy {
     pub proxy_port: NonZeroU16,
     pub proxy_tls: Option<RootCertificates>,
     pub proxy_authorization: Option<HttpProxyAuth>,
+    pub connect_headers: HttpProxyConnectHeaders,
     pub resolve_hostname_locally: bool,
 }
 
@@ -145,6 +233,10 @@ pub enum ProxyFromPartsError {
     SchemeDoesNotSupportUsernames(&'static str),
     /// '{0}' proxies do not support passwords
     SchemeDoesNotSupportPasswords(&'static str),
+    /// '{0}' proxies do not support CONNECT headers
+    SchemeDoesNotSupportConnectHeaders(&'static str),
+    /// {0}
+    InvalidConnectHeader(InvalidProxyConnectHeader),
 }
 
 impl LogSafeDisplay for ProxyFromPartsError {}
@@ -156,11 +248,15 @@ impl ConnectionProxyConfig {
     ///
     /// Not all types of proxies support authentication. For those that support usernames but not
     /// passwords, the second element of the `auth` tuple must be empty.
+    ///
+    /// `connect_headers` are only supported by HTTP proxies, and can't include
+    /// `Proxy-Authorization` if `auth` is provided.
     pub fn from_parts(
         scheme: &str,
         host: &str,
         port: Option<NonZeroU16>,
         auth: Option<(String, String)>,
+        connect_headers: HttpProxyConnectHeaders,
     ) -> Result<Self, ProxyFromPartsError> {
         if host.is_empty() {
             return Err(ProxyFromPartsError::MissingHost);
@@ -169,12 +265,28 @@ impl ConnectionProxyConfig {
         let host = Host::parse_as_ip_or_domain(host);
         let auth = auth.map(|(username, password)| HttpProxyAuth { username, password });
 
+        let no_connect_headers = |scheme| {
+            if connect_headers.is_empty() {
+                Ok(())
+            } else {
+                Err(ProxyFromPartsError::SchemeDoesNotSupportConnectHeaders(
+                    scheme,
+                ))
+            }
+        };
+        if auth.is_some() && connect_headers.proxy_authorization() {
+            return Err(ProxyFromPartsError::InvalidConnectHeader(
+                InvalidProxyConnectHeader::Reserved(http::header::PROXY_AUTHORIZATION),
+            ));
+        }
+
         // Proxies that use TLS are permitted to use any valid certificate, not just our pinned
         // ones, so we have to defer to the system trust store.
         const CERTS_FOR_ARBITRARY_PROXY: RootCertificates = RootCertificates::Native;
 
         let proxy: ConnectionProxyConfig = match scheme {
             SIGNAL_TLS_PROXY_SCHEME => {
+                no_connect_headers(SIGNAL_TLS_PROXY_SCHEME)?;
                 if auth
                     .as_ref()
                     .is_some_and(|auth| auth.username == "UNENCRYPTED_FOR_TESTING")
@@ -205,6 +317,7 @@ impl ConnectionProxyConfig {
                 proxy_port: port.unwrap_or(nonzero!(80u16)),
                 proxy_tls: None,
                 proxy_authorization: auth,
+                connect_headers: connect_headers.clone(),
                 resolve_hostname_locally: true,
             }
             .into(),
@@ -213,10 +326,12 @@ impl ConnectionProxyConfig {
                 proxy_port: port.unwrap_or(nonzero!(443u16)),
                 proxy_tls: Some(CERTS_FOR_ARBITRARY_PROXY),
                 proxy_authorization: auth,
+                connect_headers: connect_headers.clone(),
                 resolve_hostname_locally: true,
             }
             .into(),
             "socks4" | "socks4a" => {
+                no_connect_headers("socks4")?;
                 if auth.as_ref().is_some_and(|auth| !auth.password.is_empty()) {
                     return Err(ProxyFromPartsError::SchemeDoesNotSupportPasswords("socks4"));
                 }
@@ -230,13 +345,16 @@ impl ConnectionProxyConfig {
                 }
             }
             .into(),
-            "socks" | "socks5" | "socks5h" => SocksProxy {
-                proxy_host: host,
-                proxy_port: port.unwrap_or(nonzero!(1080u16)),
-                protocol: socks::Protocol::Socks5 {
-                    username_password: auth.map(|auth| (auth.username, auth.password)),
-                },
-                resolve_hostname_locally: scheme != "socks5h",
+            "socks" | "socks5" | "socks5h" => {
+                no_connect_headers("socks5")?;
+                SocksProxy {
+                    proxy_host: host,
+                    proxy_port: port.unwrap_or(nonzero!(1080u16)),
+                    protocol: socks::Protocol::Socks5 {
+                        username_password: auth.map(|auth| (auth.username, auth.password)),
+                    },
+                    resolve_hostname_locally: scheme != "socks5h",
+                }
             }
             .into(),
             scheme => {
@@ -464,6 +582,7 @@ impl AsReplacer for HttpProxy {
             proxy_port,
             resolve_hostname_locally,
             proxy_authorization,
+            connect_headers,
             proxy_tls,
         } = self;
         let proxy_tcp_route = TcpRoute {
@@ -492,6 +611,7 @@ impl AsReplacer for HttpProxy {
                         },
                         target_port: port,
                         authorization: proxy_authorization.clone(),
+                        connect_headers: connect_headers.clone(),
                     },
                     inner: inner_route.clone(),
                 })
@@ -540,7 +660,13 @@ mod test {
         } = {
             let port = port.map(|p| NonZeroU16::try_from(p).expect("valid for testing"));
             assert_matches!(
-                ConnectionProxyConfig::from_parts(SIGNAL_TLS_PROXY_SCHEME, host, port, None),
+                ConnectionProxyConfig::from_parts(
+                    SIGNAL_TLS_PROXY_SCHEME,
+                    host,
+                    port,
+                    None,
+                    Default::default(),
+                ),
                 Ok(ConnectionProxyConfig::Tls(tls)) => tls
             )
         };
@@ -564,7 +690,13 @@ mod test {
             let port = port.map(|p| NonZeroU16::try_from(p).expect("valid for testing"));
             let auth = auth.map(|u| (u.to_owned(), "".to_owned()));
             assert_matches!(
-                ConnectionProxyConfig::from_parts(SIGNAL_TLS_PROXY_SCHEME, host, port, auth),
+                ConnectionProxyConfig::from_parts(
+                    SIGNAL_TLS_PROXY_SCHEME,
+                    host,
+                    port,
+                    auth,
+                    Default::default(),
+                ),
                 Ok(ConnectionProxyConfig::Tcp(tcp)) => tcp
             )
         };
@@ -594,12 +726,19 @@ mod test {
             proxy_port,
             proxy_tls,
             proxy_authorization,
+            connect_headers,
             resolve_hostname_locally,
         } = {
             let port = port.map(|p| NonZeroU16::try_from(p).expect("valid for testing"));
             let auth = auth.map(|(u, p)| (u.to_owned(), p.to_owned()));
             assert_matches!(
-                ConnectionProxyConfig::from_parts(scheme, host, port, auth),
+                ConnectionProxyConfig::from_parts(
+                    scheme,
+                    host,
+                    port,
+                    auth,
+                    Default::default(),
+                ),
                 Ok(ConnectionProxyConfig::Http(http)) => http
             )
         };
@@ -622,12 +761,46 @@ mod test {
                 .as_ref()
                 .map(|auth| (auth.username.as_str(), auth.password.as_str())),
         );
+        assert!(connect_headers.is_empty());
         assert!(
             resolve_hostname_locally,
             "this endpoint never produces a config that defers to the proxy"
         );
     }
 
+    #[test]
+    fn http_connect_headers_are_redacted_in_debug_output() {
+        let headers = HttpProxyConnectHeaders::new([
+            ("Proxy-Authorization", "Bearer secret-token"),
+            ("X-Tenant", "secret-tenant"),
+        ])
+        .expect("valid");
+        let debug = format!("{headers:?}");
+        assert!(!debug.contains("secret"), "{debug}");
+        assert_eq!(
+            debug,
+            r#"{"proxy-authorization": "REDACTED", "x-tenant": "REDACTED"}"#
+        );
+    }
+
+    #[test_case("bad name", "value" => matches InvalidProxyConnectHeader::InvalidName; "invalid name")]
+    #[test_case("X-Tenant", "caf\u{e9}" => matches InvalidProxyConnectHeader::InvalidValue(_); "non-ASCII value")]
+    #[test_case("X-Tenant", "line\nbreak" => matches InvalidProxyConnectHeader::InvalidValue(_); "control character")]
+    #[test_case("Host", "example.com" => matches InvalidProxyConnectHeader::Reserved(_); "host")]
+    fn http_connect_headers_are_validated(name: &str, value: &str) -> InvalidProxyConnectHeader {
+        HttpProxyConnectHeaders::new([(name, value)]).expect_err("invalid")
+    }
+
+    #[test]
+    fn http_connect_headers_are_size_capped() {
+        let value = "v".repeat(HttpProxyConnectHeaders::MAX_TOTAL_SIZE / 2);
+        HttpProxyConnectHeaders::new([("a", value.as_str())]).expect("fits");
+        assert_matches!(
+            HttpProxyConnectHeaders::new([("a", value.as_str()), ("b", value.as_str())]),
+            Err(InvalidProxyConnectHeader::TooLarge)
+        );
+    }
+
     #[test_case("socks4", EXAMPLE_HOST, None, None, Host::Domain(EXAMPLE_HOST); "simple")]
     #[test_case("socks4a", EXAMPLE_HOST, None, None, Host::Domain(EXAMPLE_HOST); "simple with socks4a")]
     #[test_case("socks4", EXAMPLE_HOST, Some(4433), None, Host::Domain(EXAMPLE_HOST); "with port")]
@@ -652,7 +825,13 @@ mod test {
             let port = port.map(|p| NonZeroU16::try_from(p).expect("valid for testing"));
             let auth = auth.map(|u| (u.to_owned(), "".to_owned()));
             assert_matches!(
-                ConnectionProxyConfig::from_parts(scheme, host, port, auth),
+                ConnectionProxyConfig::from_parts(
+                    scheme,
+                    host,
+                    port,
+                    auth,
+                    Default::default(),
+                ),
                 Ok(ConnectionProxyConfig::Socks(socks)) => socks
             )
         };
@@ -699,7 +878,13 @@ mod test {
             let port = port.map(|p| NonZeroU16::try_from(p).expect("valid for testing"));
             let auth = auth.map(|(u, p)| (u.to_owned(), p.to_owned()));
             assert_matches!(
-                ConnectionProxyConfig::from_parts(scheme, host, port, auth),
+                ConnectionProxyConfig::from_parts(
+                    scheme,
+                    host,
+                    port,
+                    auth,
+                    Default::default(),
+                ),
                 Ok(ConnectionProxyConfig::Socks(socks)) => socks
             )
         };
@@ -745,6 +930,37 @@ mod test {
         };
         let port = None; // no interesting tests for ports, they're all valid
 
-        ConnectionProxyConfig::from_parts(scheme, host, port, auth).expect_err("invalid input")
+        ConnectionProxyConfig::from_parts(
+            scheme,
+            host,
+            port,
+            auth,
+            HttpProxyConnectHeaders::default(),
+        )
+        .expect_err("invalid input")
+    }
+
+    #[test]
+    fn proxy_from_parts_http_connect_headers() {
+        let headers = HttpProxyConnectHeaders::new([("X-Tenant", "tenant")]).expect("valid");
+        let http = assert_matches!(
+            ConnectionProxyConfig::from_parts("http", EXAMPLE_HOST, None, None, headers.clone()),
+            Ok(ConnectionProxyConfig::Http(http)) => http
+        );
+        assert_eq!(http.connect_headers, headers);
+    }
+
+    #[test_case(SIGNAL_TLS_PROXY_SCHEME, None, "X-Tenant" => matches ProxyFromPartsError::SchemeDoesNotSupportConnectHeaders(SIGNAL_TLS_PROXY_SCHEME); "Signal TLS")]
+    #[test_case("socks5", None, "X-Tenant" => matches ProxyFromPartsError::SchemeDoesNotSupportConnectHeaders("socks5"); "SOCKS")]
+    #[test_case("http", Some(("user", "pass")), "Proxy-Authorization" => matches ProxyFromPartsError::InvalidConnectHeader(InvalidProxyConnectHeader::Reserved(name)) if name == http::header::PROXY_AUTHORIZATION; "Proxy-Authorization with username")]
+    fn proxy_from_parts_invalid_connect_headers(
+        scheme: &str,
+        auth: Option<(&str, &str)>,
+        header_name: &str,
+    ) -> ProxyFromPartsError {
+        let auth = auth.map(|(u, p)| (u.to_owned(), p.to_owned()));
+        let headers = HttpProxyConnectHeaders::new([(header_name, "value")]).expect("valid");
+        ConnectionProxyConfig::from_parts(scheme, EXAMPLE_HOST, None, auth, headers)
+            .expect_err("invalid input")
     }
 }
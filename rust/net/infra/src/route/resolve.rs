@@ -6,13 +6,17 @@
 use std::fmt::Debug;
 use std::future::Future;
 use std::net::IpAddr;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use either::Either;
-use futures_util::stream::FuturesUnordered;
-use futures_util::{FutureExt as _, TryStreamExt as _};
+use futures_util::stream::{FusedStream, FuturesUnordered};
+use futures_util::{FutureExt as _, Stream, StreamExt as _, TryStreamExt as _};
 use itertools::Itertools;
 
+use crate::dns::dns_lookup::split_by_family;
+pub use crate::dns::dns_lookup::FamilyLookupResult;
 use crate::dns::lookup_result::LookupResult;
 use crate::dns::{DnsError, DnsResolver};
 use crate::host::Host;
@@ -21,6 +25,7 @@ use crate::route::{
     HttpsTlsRoute, ProxyTarget, SocksRoute, TcpRoute, TlsRoute, UnresolvedHost, UsePreconnect,
     WebSocketRoute,
 };
+use crate::IpType;
 
 /// A route with hostnames that can be resolved.
 ///
@@ -55,7 +60,7 @@ pub trait ResolvedRoute {
 ///
 /// This exists mostly as an abstraction over [`DnsResolver`] for the purposes
 /// of mocking during tests.
-pub trait Resolver {
+pub trait Resolver: Sync {
     /// Asynchronously looks up a single domain name.
     ///
     /// Returns a [`Future`] that resolves to the result of the lookup.
@@ -63,12 +68,33 @@ pub trait Resolver {
         &self,
         hostname: &str,
     ) -> impl Future<Output = Result<LookupResult, DnsError>> + Send;
+
+    /// Looks up a single domain name, producing the addresses of each IP
+    /// family as soon as they're known.
+    ///
+    /// The returned stream yields one item per family, in the order the
+    /// lookups finish. The default implementation waits for
+    /// [`Self::lookup_ip`] and then yields both families at once.
+    fn lookup_ip_by_family(
+        &self,
+        hostname: Arc<str>,
+    ) -> impl Stream<Item = FamilyLookupResult> + Send {
+        async move { futures_util::stream::iter(split_by_family(self.lookup_ip(&hostname).await)) }
+            .flatten_stream()
+    }
 }
 
 impl Resolver for DnsResolver {
     fn lookup_ip(&self, hostname: &str) -> impl Future<Output = Result<LookupResult, DnsError>> {
         DnsResolver::lookup_ip(self, hostname)
     }
+
+    fn lookup_ip_by_family(
+        &self,
+        hostname: Arc<str>,
+    ) -> impl Stream<Item = FamilyLookupResult> + Send {
+        DnsResolver::lookup_ip_by_family(self, &hostname)
+    }
 }
 
 /// The output of [`resolve_route`] on successful resolution.
@@ -135,6 +161,237 @@ pub async fn resolve_route<R: ResolveHostnames + Clone + 'static>(
     Ok(resolved_routes)
 }
 
+/// Like [`resolve_route`], but produces resolved routes as addresses arrive
+/// instead of waiting for every lookup to finish.
+///
+/// Each item holds the routes made possible by the addresses that arrived
+/// since the previous item, ordered the same way as by `resolve_route`.
+/// Addresses of the `preferred` family get a head start: the other family's
+/// addresses are held back until every lookup of the preferred family has
+/// finished or `head_start` has passed, so that a slightly slower preferred
+/// answer still goes first but a much slower one doesn't hold up the other.
+///
+/// If some hostname can't be resolved at all, the stream ends with an error.
+pub fn resolve_route_incrementally<'a, R: ResolveHostnames + Clone + 'static>(
+    dns: &'a impl Resolver,
+    route: R,
+    preferred: IpType,
+    head_start: Duration,
+) -> impl FusedStream<Item = Result<Vec<R::Resolved>, (Arc<str>, DnsError)>> + 'a {
+    let hosts = route
+        .hostnames()
+        .map(|UnresolvedHost(hostname)| HostAddresses::new(Arc::clone(hostname)))
+        .collect_vec();
+    let lookups =
+        futures_util::stream::select_all(hosts.iter().enumerate().map(|(index, host)| {
+            Box::pin(
+                dns.lookup_ip_by_family(Arc::clone(&host.hostname))
+                    .map(Some)
+                    // Marks the end of this host's lookups.
+                    .chain(futures_util::stream::once(std::future::ready(None)))
+                    .map(move |result| (index, result)),
+            )
+        }));
+    let resolution = IncrementalResolution {
+        route,
+        hosts,
+        lookups,
+        preferred,
+        head_start,
+        head_start_timer: None,
+        release_held: false,
+        emitted_any: false,
+        done: false,
+    };
+    futures_util::stream::unfold(resolution, |mut resolution| async move {
+        let routes = resolution.next_routes().await?;
+        Some((routes, resolution))
+    })
+    .fuse()
+}
+
+/// The addresses found so far for one hostname in [`resolve_route_incrementally`].
+struct HostAddresses {
+    hostname: Arc<str>,
+    /// Addresses that routes can be made with, in the order they arrived.
+    usable: Vec<IpAddr>,
+    /// How many of `usable` have been made into routes already.
+    used: usize,
+    /// Addresses of the non-preferred family waiting out the preferred
+    /// family's head start.
+    held: Vec<IpAddr>,
+    preferred_finished: bool,
+    finished: bool,
+    error: Option<DnsError>,
+}
+
+impl HostAddresses {
+    fn new(hostname: Arc<str>) -> Self {
+        Self {
+            hostname,
+            usable: vec![],
+            used: 0,
+            held: vec![],
+            preferred_finished: false,
+            finished: false,
+            error: None,
+        }
+    }
+
+    fn record(&mut self, preferred: IpType, result: Option<FamilyLookupResult>) {
+        let (family, result) = match result {
+            Some(result) => result,
+            None => {
+                self.finished = true;
+                self.preferred_finished = true;
+                return;
+            }
+        };
+        if family == preferred {
+            self.preferred_finished = true;
+        }
+        match result {
+            Ok(addrs) => {
+                for addr in addrs {
+                    if IpType::from(&addr) == preferred {
+                        self.usable.push(addr);
+                    } else {
+                        self.held.push(addr);
+                    }
+                }
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+struct IncrementalResolution<R, S> {
+    route: R,
+    hosts: Vec<HostAddresses>,
+    lookups: S,
+    preferred: IpType,
+    head_start: Duration,
+    /// Started on the first poll, along with the lookups.
+    head_start_timer: Option<Pin<Box<tokio::time::Sleep>>>,
+    release_held: bool,
+    emitted_any: bool,
+    done: bool,
+}
+
+impl<R, S> IncrementalResolution<R, S>
+where
+    R: ResolveHostnames + Clone,
+    S: FusedStream<Item = (usize, Option<FamilyLookupResult>)> + Unpin,
+{
+    async fn next_routes(&mut self) -> Option<Result<Vec<R::Resolved>, (Arc<str>, DnsError)>> {
+        let head_start = self.head_start;
+        let mut head_start_timer = self
+            .head_start_timer
+            .take()
+            .unwrap_or_else(|| Box::pin(tokio::time::sleep(head_start)));
+
+        let result = loop {
+            if self.done {
+                break None;
+            }
+            if let Some(host) = self
+                .hosts
+                .iter_mut()
+                .find(|host| host.finished && host.usable.is_empty() && host.held.is_empty())
+            {
+                self.done = true;
+                let error = host.error.take().unwrap_or(DnsError::LookupFailed);
+                break Some(Err((Arc::clone(&host.hostname), error)));
+            }
+            if self.hosts.iter().all(|host| host.preferred_finished) {
+                self.release_held = true;
+            }
+
+            let routes = self.take_new_routes();
+            if !routes.is_empty() {
+                break Some(Ok(routes));
+            }
+            if self.lookups.is_terminated() {
+                self.done = true;
+                break None;
+            }
+
+            tokio::select! {
+                item = self.lookups.next() => {
+                    if let Some((index, result)) = item {
+                        self.hosts[index].record(self.preferred, result);
+                    }
+                }
+                () = head_start_timer.as_mut(), if !self.release_held => self.release_held = true,
+            }
+            // Take whatever else has already arrived, so that addresses found
+            // together are interleaved together.
+            while let Some(Some((index, result))) = self.lookups.next().now_or_never() {
+                self.hosts[index].record(self.preferred, result);
+            }
+        };
+
+        self.head_start_timer = Some(head_start_timer);
+        result
+    }
+
+    /// Makes routes out of every combination of usable addresses that hasn't
+    /// been used yet.
+    fn take_new_routes(&mut self) -> Vec<R::Resolved> {
+        if self.release_held {
+            for host in &mut self.hosts {
+                let held = std::mem::take(&mut host.held);
+                host.usable.extend(held);
+            }
+        }
+
+        let hosts = &self.hosts;
+        let [mut v4_routes, mut v6_routes, mut other_routes] = [(); 3].map(|_| Vec::new());
+        for indices in hosts
+            .iter()
+            .map(|host| 0..host.usable.len())
+            .multi_cartesian_product()
+        {
+            // A route without any hostnames has a single, empty combination.
+            let is_new = !self.emitted_any
+                || indices
+                    .iter()
+                    .zip(hosts)
+                    .any(|(&index, host)| index >= host.used);
+            if !is_new {
+                continue;
+            }
+
+            let mut route_ip_version = RouteIpVersion::None;
+            let resolved = self.route.clone().resolve(|hostname| {
+                let (&index, host) = indices
+                    .iter()
+                    .zip(hosts)
+                    .find(|(_, host)| *host.hostname == *hostname)
+                    .expect("every hostname was looked up");
+                let addr = host.usable[index];
+                route_ip_version.update_from(&addr);
+                addr
+            });
+            let destination = match route_ip_version {
+                RouteIpVersion::V4 => &mut v4_routes,
+                RouteIpVersion::V6 => &mut v6_routes,
+                RouteIpVersion::None | RouteIpVersion::Mixed => &mut other_routes,
+            };
+            destination.push(resolved);
+        }
+
+        for host in &mut self.hosts {
+            host.used = host.usable.len();
+        }
+        let routes = itertools::interleave(v6_routes, v4_routes)
+            .chain(other_routes)
+            .collect_vec();
+        self.emitted_any |= !routes.is_empty();
+        routes
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum RouteIpVersion {
     None,
@@ -274,6 +531,7 @@ impl<A: ResolveHostnames> ResolveHostnames for HttpsProxyRoute<A> {
                     target_host,
                     target_port: _,
                     authorization: _,
+                    connect_headers: _,
                 },
         } = self;
         inner
@@ -289,12 +547,14 @@ impl<A: ResolveHostnames> ResolveHostnames for HttpsProxyRoute<A> {
                     target_host,
                     target_port,
                     authorization,
+                    connect_headers,
                 },
         } = self;
         let fragment = HttpProxyRouteFragment {
             target_host: target_host.replace_locally_resolved(&mut lookup),
             target_port,
             authorization,
+            connect_headers,
         };
         Self::Resolved {
             inner: inner.map_either_with(
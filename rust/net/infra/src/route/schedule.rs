@@ -11,7 +11,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use derive_where::derive_where;
-use futures_util::stream::{FusedStream, FuturesUnordered};
+use futures_util::stream::FusedStream;
 use futures_util::{Stream, StreamExt};
 use pin_project::pin_project;
 use rangemap::RangeSet;
@@ -19,9 +19,14 @@ use tokio::time::{Duration, Instant};
 
 use crate::dns::dns_utils::log_safe_domain;
 use crate::dns::DnsError;
-use crate::route::{ResolveHostnames, ResolvedRoute, Resolver, TransportRoute, UsesTransport};
+use crate::route::{
+    resolve_route_incrementally, ResolveHostnames, ResolvedRoute, Resolver, TransportRoute,
+    UsesTransport,
+};
+use crate::timeouts::DNS_RESOLUTION_DELAY;
 use crate::utils::binary_heap::{MinKeyValueQueue, Queue};
 use crate::utils::future::SomeOrPending;
+use crate::IpType;
 
 /// Resolves routes with domain names to equivalent routes with IP addresses.
 ///
@@ -30,6 +35,17 @@ use crate::utils::future::SomeOrPending;
 #[derive(Clone)]
 pub struct RouteResolver {
     pub allow_ipv6: bool,
+    /// Whether to try all IPv6 addresses for a route before any IPv4 ones,
+    /// instead of alternating between them.
+    pub prefer_ipv6: bool,
+    /// How long addresses of the other family are held back waiting for the
+    /// preferred family's, so that a slightly slower preferred lookup still
+    /// gets to go first.
+    ///
+    /// The preferred family is IPv6 if `prefer_ipv6` is set and IPv4
+    /// otherwise. Both families are looked up at once, and routes over
+    /// whichever arrives later are added as soon as it does.
+    pub head_start: Duration,
 }
 
 /// A policy object that decides how much to delay a route.
@@ -89,7 +105,11 @@ pub struct ConnectionOutcomeParams {
 
 impl Default for RouteResolver {
     fn default() -> Self {
-        Self { allow_ipv6: true }
+        Self {
+            allow_ipv6: true,
+            prefer_ipv6: false,
+            head_start: DNS_RESOLUTION_DELAY,
+        }
     }
 }
 
@@ -99,9 +119,11 @@ impl RouteResolver {
     ///
     /// Produces a sequence of [`ResolvedRoutes`] in roughly priority order by
     /// resolving the hostnames in each of the input routes. Each input route
-    /// corresponds to a single `ResolvedRoutes` in the output, though not
-    /// necessarily in the same order as the input sequence. The order is
-    /// maintained as much as possible subject to delays in name resolution.
+    /// corresponds to one or more `ResolvedRoutes` in the output, one for each
+    /// batch of addresses as they arrive (see
+    /// [`resolve_route_incrementally`]), though not necessarily in the same
+    /// order as the input sequence. The order is maintained as much as
+    /// possible subject to delays in name resolution.
     pub fn resolve<'r, R>(
         &'r self,
         ordered_routes: impl Iterator<Item = R> + 'r,
@@ -110,10 +132,25 @@ impl RouteResolver {
     where
         R: ResolveHostnames<Resolved: ResolvedRoute> + Clone + 'static,
     {
-        let Self { allow_ipv6 } = self;
+        let Self {
+            allow_ipv6,
+            prefer_ipv6,
+            head_start,
+        } = self;
+        let preferred = if *prefer_ipv6 && *allow_ipv6 {
+            IpType::V6
+        } else {
+            IpType::V4
+        };
+        // There's nothing to wait for if IPv6 routes would be dropped anyway.
+        let head_start = if *allow_ipv6 {
+            *head_start
+        } else {
+            Duration::ZERO
+        };
 
-        let resolved = eagerly_resolve_each(ordered_routes, resolver).filter_map(
-            |(resolution_result, meta)| {
+        let resolved = eagerly_resolve_each(ordered_routes, resolver, preferred, head_start)
+            .filter_map(|(resolution_result, meta)| {
                 std::future::ready(match resolution_result {
                     Ok(route_group) => Some((route_group, meta)),
                     Err((name, err)) => {
@@ -124,15 +161,21 @@ impl RouteResolver {
                         None
                     }
                 })
-            },
-        );
+            });
 
-        // Prune routes that connect directly to IPv6 addresses if necessary.
+        // Prune or reorder routes that connect directly to IPv6 addresses if
+        // necessary.
         resolved.map(|(mut routes, meta)| {
             if !*allow_ipv6 {
                 routes
                     .routes
                     .retain(|route| route.immediate_target().is_ipv4())
+            } else if *prefer_ipv6 {
+                // The sort is stable, so this otherwise preserves the order
+                // from the resolver.
+                routes
+                    .routes
+                    .sort_by_key(|route| route.immediate_target().is_ipv4())
             }
             (routes, meta)
         })
@@ -306,6 +349,11 @@ impl<R: Hash + Eq + Clone> ConnectionOutcomes<R> {
         }
     }
 
+    /// The number of routes with recent failures on record.
+    pub fn failing_route_count(&self) -> usize {
+        self.recent_failures.len()
+    }
+
     /// Clear any outcomes from before the cutoff.
     ///
     /// Assumes those that completed after the cutoff are still relevant.
@@ -487,23 +535,21 @@ type EagerResolutionResult<R> = Result<ResolvedRoutes<R>, (Arc<str>, DnsError)>;
 
 /// Produces a stream of resolved routes.
 ///
-/// Resolves all the input routes in parallel.
+/// Resolves all the input routes in parallel, producing each route's resolved
+/// routes in batches as their addresses arrive.
 fn eagerly_resolve_each<'r, R: ResolveHostnames + Clone + 'static>(
     routes: impl Iterator<Item = R> + 'r,
     resolver: &'r impl Resolver,
+    preferred: IpType,
+    head_start: Duration,
 ) -> impl FusedStream<Item = (EagerResolutionResult<R::Resolved>, ResolveMeta)> + 'r {
-    FuturesUnordered::from_iter(routes.enumerate().map(|(index, route)| async move {
-        let resolution = super::resolve_route(resolver, route)
-            .await
-            .map(|routes| ResolvedRoutes {
-                routes: routes.collect(),
-            });
-
-        (
-            resolution,
-            ResolveMeta {
-                original_group_index: index,
-            },
+    futures_util::stream::select_all(routes.enumerate().map(|(index, route)| {
+        let meta = ResolveMeta {
+            original_group_index: index,
+        };
+        Box::pin(
+            resolve_route_incrementally(resolver, route, preferred, head_start)
+                .map(move |resolution| (resolution.map(|routes| ResolvedRoutes { routes }), meta)),
         )
     }))
 }
@@ -657,21 +703,22 @@ impl<S: FusedStream<Item = (K, V)>, K: SequentialKey, V> FusedStream
 #[cfg(test)]
 mod test {
     use std::collections::{HashMap, HashSet};
-    use std::net::{IpAddr, Ipv4Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
     use assert_matches::assert_matches;
     use const_str::ip_addr;
     use futures_util::FutureExt as _;
     use itertools::Itertools as _;
     use proptest::proptest;
+    use test_case::test_case;
     use tokio::sync::mpsc;
     use tokio_stream::wrappers::UnboundedReceiverStream;
 
     use super::*;
     use crate::dns::lookup_result::LookupResult;
     use crate::route::testutils::FakeRoute;
-    use crate::route::{NoDelay, UnresolvedHost};
-    use crate::DnsSource;
+    use crate::route::{FamilyLookupResult, NoDelay, UnresolvedHost};
+    use crate::{DnsSource, IpType};
 
     impl<S, R, SP> Schedule<S, R, SP>
     where
@@ -688,7 +735,7 @@ mod test {
 
     #[tokio::test(start_paused = true)]
     async fn single_resolved_route_e2e() {
-        let resolver = RouteResolver { allow_ipv6: true };
+        let resolver = RouteResolver::default();
         let name_resolver = HashMap::from([(
             "domain-name",
             LookupResult {
@@ -720,9 +767,148 @@ mod test {
         );
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn prefer_ipv6_orders_ipv6_routes_first() {
+        let resolver = RouteResolver {
+            prefer_ipv6: true,
+            ..Default::default()
+        };
+        let name_resolver = HashMap::from([(
+            "domain-name",
+            LookupResult {
+                ipv4: vec![ip_addr!(v4, "192.0.2.1"), ip_addr!(v4, "192.0.2.2")],
+                ipv6: vec![ip_addr!(v6, "3fff::1"), ip_addr!(v6, "3fff::2")],
+                source: DnsSource::Static,
+            },
+        )]);
+
+        let unresolved_routes = [FakeRoute(UnresolvedHost("domain-name".into()))];
+
+        let resolved: Vec<_> = resolver
+            .resolve(unresolved_routes.into_iter(), &name_resolver)
+            .map(|(routes, _meta)| routes.routes)
+            .collect()
+            .await;
+
+        assert_eq!(
+            resolved,
+            vec![vec![
+                FakeRoute(ip_addr!("3fff::1")),
+                FakeRoute(ip_addr!("3fff::2")),
+                FakeRoute(ip_addr!("192.0.2.1")),
+                FakeRoute(ip_addr!("192.0.2.2")),
+            ]]
+        );
+    }
+
+    /// Resolves every name to the same addresses, with each IP family taking
+    /// its own time to arrive.
+    struct DelayedFamilyResolver {
+        ipv4: (Ipv4Addr, Duration),
+        ipv6: (Ipv6Addr, Duration),
+    }
+
+    impl Resolver for DelayedFamilyResolver {
+        fn lookup_ip(
+            &self,
+            _hostname: &str,
+        ) -> impl Future<Output = Result<LookupResult, DnsError>> + Send {
+            let Self {
+                ipv4: (ipv4, ipv4_delay),
+                ipv6: (ipv6, ipv6_delay),
+            } = *self;
+            async move {
+                tokio::time::sleep(ipv4_delay.max(ipv6_delay)).await;
+                Ok(LookupResult::new(DnsSource::Test, vec![ipv4], vec![ipv6]))
+            }
+        }
+
+        fn lookup_ip_by_family(
+            &self,
+            _hostname: Arc<str>,
+        ) -> impl Stream<Item = FamilyLookupResult> + Send {
+            let Self {
+                ipv4: (ipv4, ipv4_delay),
+                ipv6: (ipv6, ipv6_delay),
+            } = *self;
+            let after = |delay: Duration, item: FamilyLookupResult| {
+                futures_util::stream::once(tokio::time::sleep(delay).map(move |()| item))
+            };
+            futures_util::stream::select(
+                after(ipv4_delay, (IpType::V4, Ok(vec![ipv4.into()]))),
+                after(ipv6_delay, (IpType::V6, Ok(vec![ipv6.into()]))),
+            )
+        }
+    }
+
+    const V4: IpAddr = ip_addr!("192.0.2.1");
+    const V6: IpAddr = ip_addr!("3fff::1");
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test_case(true, Duration::ZERO, Duration::ZERO => vec![(vec![V6, V4], Duration::ZERO)]; "together")]
+    #[test_case(true, Duration::ZERO, SECOND => vec![(vec![V6], Duration::ZERO), (vec![V4], SECOND)]; "slow IPv4")]
+    #[test_case(true, DNS_RESOLUTION_DELAY / 2, Duration::ZERO => vec![(vec![V6, V4], DNS_RESOLUTION_DELAY / 2)]; "IPv6 within head start")]
+    #[test_case(true, SECOND, Duration::ZERO => vec![(vec![V4], DNS_RESOLUTION_DELAY), (vec![V6], SECOND)]; "slow IPv6")]
+    #[test_case(false, Duration::ZERO, Duration::ZERO => vec![(vec![V6, V4], Duration::ZERO)]; "together without preferring IPv6")]
+    #[test_case(false, Duration::ZERO, DNS_RESOLUTION_DELAY / 2 => vec![(vec![V6, V4], DNS_RESOLUTION_DELAY / 2)]; "IPv4 within head start")]
+    #[test_case(false, Duration::ZERO, SECOND => vec![(vec![V6], DNS_RESOLUTION_DELAY), (vec![V4], SECOND)]; "slow IPv4 without preferring IPv6")]
+    #[test_case(false, SECOND, Duration::ZERO => vec![(vec![V4], Duration::ZERO), (vec![V6], SECOND)]; "slow IPv6 without preferring IPv6")]
+    #[tokio::test(start_paused = true)]
+    async fn resolves_ip_families_independently(
+        prefer_ipv6: bool,
+        ipv6_delay: Duration,
+        ipv4_delay: Duration,
+    ) -> Vec<(Vec<IpAddr>, Duration)> {
+        let resolver = RouteResolver {
+            prefer_ipv6,
+            ..Default::default()
+        };
+        let name_resolver = DelayedFamilyResolver {
+            ipv4: (ip_addr!(v4, "192.0.2.1"), ipv4_delay),
+            ipv6: (ip_addr!(v6, "3fff::1"), ipv6_delay),
+        };
+
+        let unresolved_routes = [FakeRoute(UnresolvedHost("domain-name".into()))];
+
+        let start_at = Instant::now();
+        resolver
+            .resolve(unresolved_routes.into_iter(), &name_resolver)
+            .map(|(routes, _meta)| {
+                (
+                    routes.routes.into_iter().map(|route| route.0).collect(),
+                    Instant::now().duration_since(start_at),
+                )
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn resolving_without_ipv6_does_not_wait_for_it() {
+        let resolver = RouteResolver {
+            allow_ipv6: false,
+            ..Default::default()
+        };
+        let name_resolver = DelayedFamilyResolver {
+            ipv4: (ip_addr!(v4, "192.0.2.1"), Duration::ZERO),
+            ipv6: (ip_addr!(v6, "3fff::1"), SECOND),
+        };
+
+        let unresolved_routes = [FakeRoute(UnresolvedHost("domain-name".into()))];
+
+        let start_at = Instant::now();
+        let (routes, _meta) = resolver
+            .resolve(unresolved_routes.into_iter(), &name_resolver)
+            .next()
+            .await
+            .expect("has routes");
+        assert_eq!(routes.routes, vec![FakeRoute(V4)]);
+        assert_eq!(Instant::now().duration_since(start_at), Duration::ZERO);
+    }
+
     #[tokio::test(start_paused = true)]
     async fn multiple_resolved_routes_e2e() {
-        let resolver = RouteResolver { allow_ipv6: true };
+        let resolver = RouteResolver::default();
 
         let name_resolver = HashMap::from([
             (
@@ -4,20 +4,22 @@
 //
 
 use std::future::Future;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::num::NonZeroU16;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use auto_enums::enum_derive;
-use boring_signal::ssl::{ConnectConfiguration, SslConnector, SslMethod, SslSignatureAlgorithm};
+use boring_signal::ssl::{
+    ConnectConfiguration, SslConnector, SslConnectorBuilder, SslMethod, SslSignatureAlgorithm,
+};
 use futures_util::TryFutureExt;
-use tokio::net::TcpStream;
+use tokio::net::{TcpSocket, TcpStream};
 use tokio_boring_signal::SslStream;
 
 use crate::certs::RootCertificates;
 use crate::dns::DnsResolver;
-use crate::errors::TransportConnectError;
+use crate::errors::{LogSafeDisplay, TransportConnectError};
 use crate::host::Host;
 use crate::route::{
     ConnectionProxyConfig, Connector, ConnectorExt as _, TcpProxy, TcpRoute, TlsProxy,
@@ -35,11 +37,16 @@ use crate::{
 };
 
 pub mod proxy;
+mod session_cache;
+pub use session_cache::{TlsHandshakeCounts, TlsSessionCache};
 
 #[derive(Clone, Debug)]
 pub struct TcpSslConnector {
     dns_resolver: DnsResolver,
     proxy: Result<Option<ConnectionProxyConfig>, InvalidProxyConfig>,
+    local_bind_address: Option<IpAddr>,
+    /// Cleared whenever the proxy changes.
+    tls_session_cache: Arc<TlsSessionCache>,
 }
 
 impl TcpSslConnector {
@@ -47,6 +54,8 @@ impl TcpSslConnector {
         Self {
             dns_resolver,
             proxy: Ok(None),
+            local_bind_address: None,
+            tls_session_cache: Default::default(),
         }
     }
 
@@ -56,14 +65,37 @@ impl TcpSslConnector {
 
     pub fn set_proxy(&mut self, proxy: ConnectionProxyConfig) {
         self.proxy = Ok(Some(proxy));
+        self.tls_session_cache.clear();
     }
 
     pub fn set_invalid(&mut self) {
-        self.proxy = Err(InvalidProxyConfig)
+        self.proxy = Err(InvalidProxyConfig);
+        self.tls_session_cache.clear();
     }
 
     pub fn clear_proxy(&mut self) {
         self.proxy = Ok(None);
+        self.tls_session_cache.clear();
+    }
+
+    /// The TLS sessions saved for connections made through this connector.
+    ///
+    /// Share this with other connectors that use the same proxy settings, and
+    /// [clear](TlsSessionCache::clear) it when the network changes.
+    pub fn tls_session_cache(&self) -> &Arc<TlsSessionCache> {
+        &self.tls_session_cache
+    }
+
+    /// Binds the sockets for future connections to `address`, or lets the OS
+    /// pick if `None`.
+    ///
+    /// See [`check_local_bind_address`].
+    pub fn set_local_bind_address(&mut self, address: Option<IpAddr>) {
+        self.local_bind_address = address;
+    }
+
+    pub fn local_bind_address(&self) -> Option<IpAddr> {
+        self.local_bind_address
     }
 
     pub fn proxy(&self) -> Result<Option<&ConnectionProxyConfig>, InvalidProxyConfig> {
@@ -84,11 +116,42 @@ impl TryFrom<&TcpSslConnector> for Option<ConnectionProxyConfig> {
         let TcpSslConnector {
             dns_resolver: _,
             proxy,
+            local_bind_address: _,
+            tls_session_cache: _,
         } = value;
         proxy.clone()
     }
 }
 
+/// Why an address can't be used as the local end of connections.
+#[derive(Clone, Debug, PartialEq, Eq, displaydoc::Display, thiserror::Error)]
+pub enum InvalidLocalBindAddress {
+    /// not a unicast address
+    NotUnicast,
+    /// not available on this device: {0}
+    Unavailable(std::io::ErrorKind),
+}
+impl LogSafeDisplay for InvalidLocalBindAddress {}
+
+/// Checks that sockets can be bound to `address` before it's used for
+/// connections.
+///
+/// This catches typos and stale addresses up front; binding can still fail
+/// later, if the device's addresses change, which is reported as
+/// [`TransportConnectError::LocalBindFailed`].
+pub fn check_local_bind_address(address: IpAddr) -> Result<(), InvalidLocalBindAddress> {
+    let is_unicast = match address {
+        IpAddr::V4(v4) => !(v4.is_unspecified() || v4.is_multicast() || v4.is_broadcast()),
+        IpAddr::V6(v6) => !(v6.is_unspecified() || v6.is_multicast()),
+    };
+    if !is_unicast {
+        return Err(InvalidLocalBindAddress::NotUnicast);
+    }
+    std::net::UdpSocket::bind(SocketAddr::new(address, 0))
+        .map(drop)
+        .map_err(|e| InvalidLocalBindAddress::Unavailable(e.kind()))
+}
+
 #[enum_derive(tokio1::AsyncRead, tokio1::AsyncWrite)]
 pub enum TcpSslConnectorStream {
     Direct(<DirectConnector as TransportConnector>::Stream),
@@ -98,10 +161,42 @@ pub enum TcpSslConnectorStream {
 #[derive(Clone, Debug)]
 pub struct DirectConnector {
     pub dns_resolver: DnsResolver,
+    local_bind_address: Option<IpAddr>,
+    tls_session_cache: Option<Arc<TlsSessionCache>>,
+}
+
+/// [`Connector`] for TCP and TLS that keeps no state between connections,
+/// other than the TLS sessions in [`Self::with_tls_session_cache`].
+///
+/// TCP sockets are bound to [`Self::bound_to`]'s address, if any.
+#[derive(Clone, Debug, Default)]
+pub struct StatelessDirect {
+    local_bind_address: Option<IpAddr>,
+    tls_session_cache: Option<Arc<TlsSessionCache>>,
 }
 
-#[derive(Debug, Default)]
-pub struct StatelessDirect;
+impl StatelessDirect {
+    /// Binds TCP sockets to `local_bind_address` before connecting, or lets the
+    /// OS pick if `None`.
+    pub fn bound_to(local_bind_address: Option<IpAddr>) -> Self {
+        Self {
+            local_bind_address,
+            tls_session_cache: None,
+        }
+    }
+
+    /// Resumes TLS sessions saved in `cache`, and saves new ones there.
+    pub fn with_tls_session_cache(self, cache: Option<Arc<TlsSessionCache>>) -> Self {
+        Self {
+            tls_session_cache: cache,
+            ..self
+        }
+    }
+
+    pub fn local_bind_address(&self) -> Option<IpAddr> {
+        self.local_bind_address
+    }
+}
 
 #[async_trait]
 impl TransportConnector for DirectConnector {
@@ -118,11 +213,19 @@ impl TransportConnector for DirectConnector {
             RouteType::Direct,
             connection_params.tcp_host.as_deref(),
             connection_params.port,
+            self.local_bind_address,
             log_tag.clone(),
         )
         .await?;
 
-        let ssl_stream = connect_tls(tcp_stream, connection_params, alpn, log_tag).await?;
+        let ssl_stream = connect_tls(
+            tcp_stream,
+            connection_params,
+            alpn,
+            self.tls_session_cache.clone(),
+            log_tag,
+        )
+        .await?;
 
         Ok(StreamAndInfo(ssl_stream, remote_address))
     }
@@ -130,12 +233,23 @@ impl TransportConnector for DirectConnector {
 
 impl DirectConnector {
     pub fn new(dns_resolver: DnsResolver) -> Self {
-        Self { dns_resolver }
+        Self {
+            dns_resolver,
+            local_bind_address: None,
+            tls_session_cache: None,
+        }
     }
 
     pub fn with_proxy(&self, proxy_addr: (Host<Arc<str>>, NonZeroU16)) -> TlsProxyConnector {
-        let Self { dns_resolver } = self;
-        TlsProxyConnector::new(dns_resolver.clone(), proxy_addr)
+        let Self {
+            dns_resolver,
+            local_bind_address,
+            tls_session_cache,
+        } = self;
+        let mut connector = TlsProxyConnector::new(dns_resolver.clone(), proxy_addr);
+        connector.local_bind_address = *local_bind_address;
+        connector.tls_session_cache = tls_session_cache.clone();
+        connector
     }
 }
 
@@ -148,12 +262,39 @@ impl Connector<TcpRoute<IpAddr>, ()> for StatelessDirect {
         &self,
         (): (),
         route: TcpRoute<IpAddr>,
-        _log_tag: Arc<str>,
+        log_tag: Arc<str>,
     ) -> impl Future<Output = Result<Self::Connection, Self::Error>> {
         let TcpRoute { address, port } = route;
+        let remote_address = SocketAddr::new(address, port.get());
+        let local_bind_address = self.local_bind_address;
+
+        async move {
+            let Some(local_bind_address) = local_bind_address else {
+                return TcpStream::connect(remote_address)
+                    .map_err(|_e| TransportConnectError::TcpConnectionFailed)
+                    .await;
+            };
 
-        TcpStream::connect((address, port.get()))
-            .map_err(|_e| TransportConnectError::TcpConnectionFailed)
+            if local_bind_address.is_ipv4() != address.is_ipv4() {
+                log::debug!("[{log_tag}] local address can't reach a different IP version");
+                return Err(TransportConnectError::LocalBindFailed);
+            }
+            let socket = match address {
+                IpAddr::V4(_) => TcpSocket::new_v4(),
+                IpAddr::V6(_) => TcpSocket::new_v6(),
+            }
+            .map_err(|_e| TransportConnectError::TcpConnectionFailed)?;
+            socket
+                .bind(SocketAddr::new(local_bind_address, 0))
+                .map_err(|e| {
+                    log::warn!("[{log_tag}] failed to bind to local address: {}", e.kind());
+                    TransportConnectError::LocalBindFailed
+                })?;
+            socket
+                .connect(remote_address)
+                .map_err(|_e| TransportConnectError::TcpConnectionFailed)
+                .await
+        }
     }
 }
 
@@ -178,7 +319,11 @@ where
         } = fragment;
         let host = sni;
 
-        let ssl_config = ssl_config(&root_certs, host.as_deref(), alpn);
+        let tls_session_cache = self.tls_session_cache.clone();
+        let ssl_config = match &tls_session_cache {
+            None => ssl_config(&root_certs, host.as_deref(), alpn),
+            Some(cache) => cache.ssl_config(&root_certs, host.as_deref(), alpn),
+        };
 
         async move {
             let domain = match &host {
@@ -187,9 +332,13 @@ where
             };
             let ssl_config = ssl_config?;
 
-            tokio_boring_signal::connect(ssl_config, &domain, inner)
+            let stream = tokio_boring_signal::connect(ssl_config, &domain, inner)
                 .await
-                .map_err(TransportConnectError::from)
+                .map_err(TransportConnectError::from)?;
+            if let Some(cache) = tls_session_cache {
+                cache.record_handshake(stream.ssl().session_reused());
+            }
+            Ok(stream)
         }
     }
 }
@@ -205,6 +354,16 @@ fn ssl_config(
     host: Host<&str>,
     alpn: Option<Alpn>,
 ) -> Result<ConnectConfiguration, TransportConnectError> {
+    Ok(ssl_connector_builder(certs, host, alpn)?
+        .build()
+        .configure()?)
+}
+
+fn ssl_connector_builder(
+    certs: &RootCertificates,
+    host: Host<&str>,
+    alpn: Option<Alpn>,
+) -> Result<SslConnectorBuilder, TransportConnectError> {
     let mut ssl = SslConnector::builder(SslMethod::tls_client())?;
     certs.apply_to_connector(&mut ssl, host)?;
     if let Some(alpn) = alpn {
@@ -231,13 +390,14 @@ fn ssl_config(
     // #[cfg(feature = "dev-util")]
     // development_only_enable_nss_standard_debug_interop(&mut ssl)?;
 
-    Ok(ssl.build().configure()?)
+    Ok(ssl)
 }
 
 async fn connect_tls<S: AsyncDuplexStream>(
     transport: S,
     connection_params: &TransportConnectionParams,
     alpn: Alpn,
+    tls_session_cache: Option<Arc<TlsSessionCache>>,
     log_tag: Arc<str>,
 ) -> Result<SslStream<S>, TransportConnectError> {
     let route = TlsRouteFragment {
@@ -246,7 +406,8 @@ async fn connect_tls<S: AsyncDuplexStream>(
         alpn: Some(alpn),
     };
 
-    StatelessDirect
+    StatelessDirect::default()
+        .with_tls_session_cache(tls_session_cache)
         .connect_over(transport, route, log_tag)
         .await
 }
@@ -256,6 +417,7 @@ async fn connect_tcp(
     route_type: RouteType,
     host: Host<&str>,
     port: NonZeroU16,
+    local_bind_address: Option<IpAddr>,
     log_tag: Arc<str>,
 ) -> Result<StreamAndInfo<TcpStream>, TransportConnectError> {
     let dns_lookup = match host {
@@ -290,7 +452,7 @@ async fn connect_tcp(
     // First, for each resolved IP address, constructing a future
     // that incorporates the delay based on its position in the list.
     // This way we can start all futures at once and simply wait for the first one to complete successfully.
-    let connector = StatelessDirect;
+    let connector = StatelessDirect::bound_to(local_bind_address);
     let staggered_futures = dns_lookup.into_iter().enumerate().map(|(idx, ip)| {
         let delay = TCP_CONNECTION_ATTEMPT_DELAY * idx.try_into().unwrap();
         let connector = &connector;
@@ -337,6 +499,8 @@ impl TransportConnector for TcpSslConnector {
         let Self {
             dns_resolver,
             proxy,
+            local_bind_address,
+            tls_session_cache,
         } = self;
         let proxy = proxy
             .as_ref()
@@ -346,6 +510,8 @@ impl TransportConnector for TcpSslConnector {
             None => {
                 let stream_and_info = DirectConnector {
                     dns_resolver: dns_resolver.clone(),
+                    local_bind_address: *local_bind_address,
+                    tls_session_cache: Some(tls_session_cache.clone()),
                 }
                 .connect(connection_params, alpn)
                 .await?;
@@ -356,10 +522,12 @@ impl TransportConnector for TcpSslConnector {
                 proxy_host,
                 proxy_port,
             })) => {
-                let connector = TlsProxyConnector::new_tcp(
+                let mut connector = TlsProxyConnector::new_tcp(
                     dns_resolver.clone(),
                     (proxy_host.clone(), *proxy_port),
                 );
+                connector.local_bind_address = *local_bind_address;
+                connector.tls_session_cache = Some(tls_session_cache.clone());
                 let stream_and_info = connector.connect(connection_params, alpn).await?;
                 stream_and_info.map_stream(TcpSslConnectorStream::Proxy)
             }
@@ -371,6 +539,8 @@ impl TransportConnector for TcpSslConnector {
                 let mut connector =
                     TlsProxyConnector::new(dns_resolver.clone(), (proxy_host.clone(), *proxy_port));
                 connector.proxy_certs = proxy_certs.clone();
+                connector.local_bind_address = *local_bind_address;
+                connector.tls_session_cache = Some(tls_session_cache.clone());
                 let stream_and_info = connector.connect(connection_params, alpn).await?;
                 stream_and_info.map_stream(TcpSslConnectorStream::Proxy)
             }
@@ -445,7 +615,7 @@ pub(crate) mod testutil {
 mod test {
     use std::borrow::Cow;
     use std::collections::HashMap;
-    use std::net::Ipv6Addr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
 
     use assert_matches::assert_matches;
     use test_case::test_case;
@@ -493,6 +663,111 @@ mod test {
         make_http_request_response_over(stream).await
     }
 
+    #[tokio::test]
+    async fn reconnect_resumes_tls_session_until_proxy_changes() {
+        let (addr, server) = localhost_http_server();
+        let _server_handle = tokio::spawn(server);
+
+        let mut connector = TcpSslConnector::new_direct(DnsResolver::new_from_static_map(
+            HashMap::from([(SERVER_HOSTNAME, LookupResult::localhost())]),
+        ));
+        let connection_params = TransportConnectionParams {
+            sni: SERVER_HOSTNAME.into(),
+            tcp_host: Host::Ip(addr.ip()),
+            port: addr.port().try_into().expect("bound port"),
+            certs: RootCertificates::FromDer(Cow::Borrowed(SERVER_CERTIFICATE.cert.der())),
+        };
+        let connect_and_request = |connector: TcpSslConnector| {
+            let connection_params = connection_params.clone();
+            async move {
+                let Ok(StreamAndInfo(stream, _info)) =
+                    connector.connect(&connection_params, Alpn::Http1_1).await
+                else {
+                    panic!("can connect");
+                };
+                // Reading the response also receives the session ticket.
+                make_http_request_response_over(stream).await
+            }
+        };
+
+        connect_and_request(connector.clone()).await;
+        connect_and_request(connector.clone()).await;
+        assert_eq!(
+            connector.tls_session_cache().handshake_counts(),
+            TlsHandshakeCounts {
+                resumed: 1,
+                full: 1
+            }
+        );
+
+        connector.clear_proxy();
+        connect_and_request(connector.clone()).await;
+        assert_eq!(
+            connector.tls_session_cache().handshake_counts(),
+            TlsHandshakeCounts {
+                resumed: 1,
+                full: 2
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn connect_from_local_bind_address() {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .expect("can bind");
+        let server_addr = listener.local_addr().expect("bound");
+
+        let connector = StatelessDirect::bound_to(Some(Ipv4Addr::LOCALHOST.into()));
+        let route = TcpRoute {
+            address: server_addr.ip(),
+            port: server_addr.port().try_into().expect("bound port"),
+        };
+        let (stream, (_accepted, client_addr)) =
+            tokio::try_join!(connector.connect_over((), route, "test".into()), async {
+                listener
+                    .accept()
+                    .await
+                    .map_err(|_| TransportConnectError::TcpConnectionFailed)
+            })
+            .expect("can connect");
+
+        assert_eq!(client_addr.ip(), IpAddr::from(Ipv4Addr::LOCALHOST));
+        assert_eq!(stream.local_addr().expect("connected"), client_addr);
+    }
+
+    #[tokio::test]
+    async fn local_bind_address_of_other_ip_version_is_a_bind_failure() {
+        let connector = StatelessDirect::bound_to(Some(Ipv4Addr::LOCALHOST.into()));
+        let route = TcpRoute {
+            address: Ipv6Addr::LOCALHOST.into(),
+            port: nonzero_ext::nonzero!(443u16),
+        };
+        assert_matches!(
+            connector.connect_over((), route, "test".into()).await,
+            Err(TransportConnectError::LocalBindFailed)
+        );
+    }
+
+    #[test_case(Ipv4Addr::LOCALHOST.into() => Ok(()); "IPv4 loopback")]
+    #[test_case(Ipv4Addr::UNSPECIFIED.into() => Err(InvalidLocalBindAddress::NotUnicast); "IPv4 unspecified")]
+    #[test_case(Ipv4Addr::BROADCAST.into() => Err(InvalidLocalBindAddress::NotUnicast); "IPv4 broadcast")]
+    #[test_case(Ipv4Addr::new(224, 0, 0, 1).into() => Err(InvalidLocalBindAddress::NotUnicast); "IPv4 multicast")]
+    #[test_case(Ipv6Addr::UNSPECIFIED.into() => Err(InvalidLocalBindAddress::NotUnicast); "IPv6 unspecified")]
+    #[test_case(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).into() => Err(InvalidLocalBindAddress::NotUnicast); "IPv6 multicast")]
+    fn local_bind_address_must_be_unicast(address: IpAddr) -> Result<(), InvalidLocalBindAddress> {
+        check_local_bind_address(address)
+    }
+
+    #[test]
+    fn local_bind_address_must_belong_to_this_device() {
+        // TEST-NET-1, which is reserved for documentation.
+        assert_matches!(
+            check_local_bind_address(Ipv4Addr::new(192, 0, 2, 1).into()),
+            Err(InvalidLocalBindAddress::Unavailable(_))
+        );
+    }
+
     #[tokio::test]
     async fn connect_through_invalid() {
         let (addr, server) = localhost_http_server();
@@ -504,6 +779,8 @@ mod test {
                 LookupResult::localhost(),
             )])),
             proxy: Err(InvalidProxyConfig),
+            local_bind_address: None,
+            tls_session_cache: Default::default(),
         };
         let connection_params = TransportConnectionParams {
             sni: SERVER_HOSTNAME.into(),
@@ -22,8 +22,23 @@ mod stream;
 pub use stream::ProxyStream;
 
 /// Stateless [`Connector`] impl for [`ConnectionProxyRoute`].
-#[derive(Debug, Default)]
-pub struct StatelessProxied;
+///
+/// Like [`super::StatelessDirect`], TCP sockets to the proxy are bound to
+/// [`Self::bound_to`]'s address, if any.
+#[derive(Clone, Debug, Default)]
+pub struct StatelessProxied {
+    local_bind_address: Option<IpAddr>,
+}
+
+impl StatelessProxied {
+    pub fn bound_to(local_bind_address: Option<IpAddr>) -> Self {
+        Self { local_bind_address }
+    }
+
+    fn tcp_connector(&self) -> super::StatelessDirect {
+        super::StatelessDirect::bound_to(self.local_bind_address)
+    }
+}
 
 impl Connector<ConnectionProxyRoute<IpAddr>, ()> for StatelessProxied {
     type Connection = ProxyStream;
@@ -43,19 +58,22 @@ impl Connector<ConnectionProxyRoute<IpAddr>, ()> for StatelessProxied {
                     inner,
                 } = proxy;
 
-                let connector = super::StatelessDirect;
+                let connector = self.tcp_connector();
 
-                let tcp = connector.connect(inner, log_tag.clone()).await?;
+                let tcp = connector
+                    .connect(inner, log_tag.clone())
+                    .await
+                    .map_err(TransportConnectError::connecting_to_proxy)?;
                 connector
                     .connect_over(tcp, tls_fragment, log_tag)
                     .await
                     .map(Into::into)
             }
             ConnectionProxyRoute::Tcp { proxy } => {
-                let connector = super::StatelessDirect;
+                let connector = self.tcp_connector();
                 match connector.connect(proxy, log_tag).await {
                     Ok(connection) => Ok(connection.into()),
-                    Err(_io_error) => Err(TransportConnectError::TcpConnectionFailed),
+                    Err(e) => Err(e.connecting_to_proxy()),
                 }
             }
             ConnectionProxyRoute::Socks(route) => {
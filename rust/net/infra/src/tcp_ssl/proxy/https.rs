@@ -20,11 +20,11 @@ use static_assertions::assert_impl_all;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::either::Either;
 
-use crate::errors::{LogSafeDisplay, TransportConnectError};
+use crate::errors::{LogSafeDisplay, ProxyFailureKind, TransportConnectError};
 use crate::host::Host;
 use crate::route::{
-    ComposedConnector, Connector, ConnectorExt as _, HttpProxyAuth, HttpProxyRouteFragment,
-    HttpsProxyRoute, ProxyTarget,
+    ComposedConnector, Connector, ConnectorExt as _, HttpProxyAuth, HttpProxyConnectHeaders,
+    HttpProxyRouteFragment, HttpsProxyRoute, ProxyTarget,
 };
 use crate::ws::error::HttpFormatError;
 use crate::{AsHttpHeader, AsyncDuplexStream, Connection, TransportInfo};
@@ -42,7 +42,6 @@ pub struct HttpProxyStream {
 
 assert_impl_all!(HttpProxyStream: AsyncDuplexStream);
 
-type StatelessTcpConnector = super::super::StatelessDirect;
 type StatelessTlsConnector = ComposedConnector<
     super::super::StatelessDirect,
     super::super::StatelessDirect,
@@ -61,9 +60,10 @@ impl Connector<HttpsProxyRoute<IpAddr>, ()> for super::StatelessProxied {
         log_tag: Arc<str>,
     ) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send {
         let HttpsProxyRoute { fragment, inner } = route;
+        let tcp_connector = self.tcp_connector();
         async move {
-            let tls_connector = StatelessTlsConnector::default();
-            let tcp_connector = StatelessTcpConnector::default();
+            let tls_connector =
+                StatelessTlsConnector::new(Default::default(), tcp_connector.clone());
             let inner = inner
                 .map_either(
                     |tls| {
@@ -77,13 +77,15 @@ impl Connector<HttpsProxyRoute<IpAddr>, ()> for super::StatelessProxied {
                             .map_ok(Either::Right)
                     },
                 )
-                .await?;
+                .await
+                .map_err(TransportConnectError::connecting_to_proxy)?;
             let info = inner.transport_info();
 
             let HttpProxyRouteFragment {
                 target_host,
                 target_port,
                 authorization,
+                connect_headers,
             } = fragment;
 
             let target_host = match target_host {
@@ -95,6 +97,7 @@ impl Connector<HttpsProxyRoute<IpAddr>, ()> for super::StatelessProxied {
                 inner,
                 (target_host.as_deref(), target_port),
                 authorization.as_ref(),
+                &connect_headers,
             )
             .await
             {
@@ -104,7 +107,9 @@ impl Connector<HttpsProxyRoute<IpAddr>, ()> for super::StatelessProxied {
                 }),
                 Err(e) => {
                     log::info!("[{log_tag}] failed to connect via HTTP proxy: {e}");
-                    Err(TransportConnectError::ProxyProtocol)
+                    let sent_authorization =
+                        authorization.is_some() || connect_headers.proxy_authorization();
+                    Err(e.into_transport_error(sent_authorization))
                 }
             }
         }
@@ -154,12 +159,38 @@ impl Display for ConnectError {
     }
 }
 
+impl ConnectError {
+    /// Summarizes the error for callers, given whether credentials were sent
+    /// to the proxy.
+    fn into_transport_error(self, sent_authorization: bool) -> TransportConnectError {
+        let kind = match self {
+            ConnectError::Transport(e) => return *e,
+            ConnectError::InvalidRequest(_) | ConnectError::InvalidUri(_) => {
+                return TransportConnectError::InvalidConfiguration
+            }
+            ConnectError::HttpRequestRejected(http::StatusCode::PROXY_AUTHENTICATION_REQUIRED) => {
+                if sent_authorization {
+                    ProxyFailureKind::AuthFailed
+                } else {
+                    ProxyFailureKind::AuthRequired
+                }
+            }
+            ConnectError::HttpConnectionFailed(_)
+            | ConnectError::HttpUpgradeFailed(_)
+            | ConnectError::HttpRequestRejected(_)
+            | ConnectError::HttpRequestFailed(_) => ProxyFailureKind::HandshakeRejected,
+        };
+        TransportConnectError::ProxyFailure(kind)
+    }
+}
+
 async fn connect_https11_proxy(
     tls_to_proxy: impl AsyncDuplexStream + 'static,
     host_port: (Host<&str>, NonZeroU16),
     authorization: Option<&HttpProxyAuth>,
+    connect_headers: &HttpProxyConnectHeaders,
 ) -> Result<hyper::upgrade::Upgraded, ConnectError> {
-    let http_request = make_connect_request(host_port, authorization)?;
+    let http_request = make_connect_request(host_port, authorization, connect_headers)?;
 
     let (mut send_request, connection) = hyper::client::conn::http1::Builder::new()
         .handshake(TokioIo::new(tls_to_proxy))
@@ -200,6 +231,7 @@ impl LogSafeDisplay for LogSafeHyperError {}
 fn make_connect_request(
     (tcp_host, port): (Host<&str>, NonZeroU16),
     authorization: Option<&HttpProxyAuth>,
+    connect_headers: &HttpProxyConnectHeaders,
 ) -> Result<http::Request<Empty<Bytes>>, ConnectError> {
     let authority = Authority::from_maybe_shared(format!("{tcp_host}:{port}"))
         .map_err(ConnectError::InvalidUri)?;
@@ -216,6 +248,13 @@ fn make_connect_request(
         let (name, value) = auth.as_header();
         http_request = http_request.header(name, value);
     }
+    for (name, value) in connect_headers.iter() {
+        // Proxy-Authorization is reserved for the header above.
+        if authorization.is_some() && name == http::header::PROXY_AUTHORIZATION {
+            continue;
+        }
+        http_request = http_request.header(name, value);
+    }
 
     http_request
         .body(Empty::new())
@@ -273,6 +312,8 @@ impl Connection for HttpProxyStream {
 
 #[cfg(test)]
 mod test {
+    use std::net::Ipv6Addr;
+
     use assert_matches::assert_matches;
     use either::Either;
     use futures_util::future::BoxFuture;
@@ -284,6 +325,7 @@ mod test {
     use hyper::service::Service;
     use hyper::{Request, Response};
     use nonzero_ext::nonzero;
+    use test_case::test_case;
     use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
     use tokio::sync::mpsc;
 
@@ -341,7 +383,7 @@ mod test {
                 let auth = req.headers().get(HttpProxyAuth::HEADER_NAME);
                 if auth != expected_auth.as_ref() {
                     log::error!("auth header mismatch; expected {expected_auth:?}, got {auth:?}");
-                    *res.status_mut() = StatusCode::UNAUTHORIZED;
+                    *res.status_mut() = StatusCode::PROXY_AUTHENTICATION_REQUIRED;
                     return Ok(res);
                 }
 
@@ -416,11 +458,12 @@ mod test {
                 },
                 target_port: TARGET_PORT,
                 authorization,
+                connect_headers: Default::default(),
             },
             inner: Either::Right(route_to_proxy),
         };
 
-        let mut client_stream = super::super::StatelessProxied
+        let mut client_stream = super::super::StatelessProxied::default()
             .connect(route, "test".into())
             .await
             .expect("can connect");
@@ -473,8 +516,10 @@ mod test {
         assert_eq!(received_by_server, SEND_TO_SERVER.as_bytes());
     }
 
+    #[test_case(Some(("wrong-user", "wrong-pass")) => ProxyFailureKind::AuthFailed; "wrong credentials")]
+    #[test_case(None => ProxyFailureKind::AuthRequired; "no credentials")]
     #[test_log::test(tokio::test)]
-    async fn authorization_rejected() {
+    async fn authorization_rejected(sent_auth: Option<(&str, &str)>) -> ProxyFailureKind {
         let authorization = Some(HttpProxyAuth {
             username: USERNAME.to_owned(),
             password: PASSWORD.to_owned(),
@@ -493,18 +538,172 @@ mod test {
                     name: TARGET_HOST.into(),
                 },
                 target_port: TARGET_PORT,
-                authorization: Some(HttpProxyAuth {
-                    username: "wrong-user".into(),
-                    password: "wrong-pass".into(),
+                authorization: sent_auth.map(|(username, password)| HttpProxyAuth {
+                    username: username.into(),
+                    password: password.into(),
                 }),
+                connect_headers: Default::default(),
+            },
+            inner: Either::Right(route_to_proxy),
+        };
+
+        let connect_result = super::super::StatelessProxied::default()
+            .connect(route, "test".into())
+            .await;
+
+        assert_matches!(connect_result, Err(TransportConnectError::ProxyFailure(kind)) => kind)
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn sends_custom_connect_headers() {
+        let (proxy_upstream_tx, mut proxy_upstream_rx) = mpsc::unbounded_channel();
+
+        let route_to_proxy = spawn_localhost_proxy(ProxyService {
+            upgrades_tx: proxy_upstream_tx,
+            expected_auth: None,
+        });
+
+        let route = HttpsProxyRoute {
+            fragment: HttpProxyRouteFragment {
+                target_host: ProxyTarget::ResolvedRemotely {
+                    name: TARGET_HOST.into(),
+                },
+                target_port: TARGET_PORT,
+                authorization: None,
+                connect_headers: HttpProxyConnectHeaders::new([("X-Tenant", "fake-tenant")])
+                    .expect("valid"),
+            },
+            inner: Either::Right(route_to_proxy),
+        };
+
+        let _client_stream = super::super::StatelessProxied::default()
+            .connect(route, "test".into())
+            .await
+            .expect("can connect");
+
+        let UpgradeOutcome { headers, .. } = proxy_upstream_rx
+            .recv()
+            .await
+            .expect("server still running");
+
+        assert_eq!(
+            headers,
+            HeaderMap::from_iter([
+                (
+                    http::header::HOST,
+                    HeaderValue::from_static(EXPECTED_AUTHORITY),
+                ),
+                (
+                    http::HeaderName::from_static("x-tenant"),
+                    HeaderValue::from_static("fake-tenant"),
+                ),
+            ])
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn rejected_bearer_token_is_auth_failure() {
+        let (proxy_upstream_tx, _proxy_upstream_rx) = mpsc::unbounded_channel();
+
+        let route_to_proxy = spawn_localhost_proxy(ProxyService {
+            upgrades_tx: proxy_upstream_tx,
+            expected_auth: Some(HttpProxyAuth {
+                username: USERNAME.to_owned(),
+                password: PASSWORD.to_owned(),
+            }),
+        });
+
+        let route = HttpsProxyRoute {
+            fragment: HttpProxyRouteFragment {
+                target_host: ProxyTarget::ResolvedRemotely {
+                    name: TARGET_HOST.into(),
+                },
+                target_port: TARGET_PORT,
+                authorization: None,
+                connect_headers: HttpProxyConnectHeaders::new([(
+                    "Proxy-Authorization",
+                    "Bearer wrong-token",
+                )])
+                .expect("valid"),
             },
             inner: Either::Right(route_to_proxy),
         };
 
-        let connect_result = super::super::StatelessProxied
+        let connect_result = super::super::StatelessProxied::default()
+            .connect(route, "test".into())
+            .await;
+
+        assert_matches!(
+            connect_result,
+            Err(TransportConnectError::ProxyFailure(
+                ProxyFailureKind::AuthFailed
+            ))
+        );
+    }
+
+    #[test]
+    fn connect_request_does_not_duplicate_proxy_authorization() {
+        let auth = HttpProxyAuth {
+            username: USERNAME.to_owned(),
+            password: PASSWORD.to_owned(),
+        };
+        let connect_headers = HttpProxyConnectHeaders::new([
+            ("Proxy-Authorization", "Bearer token"),
+            ("X-Tenant", "fake-tenant"),
+        ])
+        .expect("valid");
+        let request = make_connect_request(
+            (Host::Domain(TARGET_HOST), TARGET_PORT),
+            Some(&auth),
+            &connect_headers,
+        )
+        .expect("valid request");
+
+        assert_eq!(
+            request
+                .headers()
+                .get_all(http::header::PROXY_AUTHORIZATION)
+                .iter()
+                .collect::<Vec<_>>(),
+            [&crate::utils::basic_authorization(USERNAME, PASSWORD)]
+        );
+        assert_eq!(
+            request.headers().get("x-tenant"),
+            Some(&HeaderValue::from_static("fake-tenant"))
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn proxy_unreachable() {
+        // Find a port that nothing is listening on.
+        let listener = std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).expect("can bind");
+        let port = listener.local_addr().expect("bound").port();
+        drop(listener);
+
+        let route = HttpsProxyRoute {
+            fragment: HttpProxyRouteFragment {
+                target_host: ProxyTarget::ResolvedRemotely {
+                    name: TARGET_HOST.into(),
+                },
+                target_port: TARGET_PORT,
+                authorization: None,
+                connect_headers: Default::default(),
+            },
+            inner: Either::Right(TcpRoute {
+                address: Ipv6Addr::LOCALHOST.into(),
+                port: port.try_into().expect("nonzero"),
+            }),
+        };
+
+        let connect_result = super::super::StatelessProxied::default()
             .connect(route, "test".into())
             .await;
 
-        assert_matches!(connect_result, Err(TransportConnectError::ProxyProtocol));
+        assert_matches!(
+            connect_result,
+            Err(TransportConnectError::ProxyFailure(
+                ProxyFailureKind::Unreachable
+            ))
+        );
     }
 }
@@ -20,7 +20,7 @@ use tokio_socks::TargetAddr;
 
 use crate::dns::lookup_result::LookupResult;
 use crate::dns::DnsResolver;
-use crate::errors::TransportConnectError;
+use crate::errors::{ProxyFailureKind, TransportConnectError};
 use crate::host::Host;
 use crate::route::{Connector, ConnectorExt as _, SocksRoute, TcpRoute};
 use crate::{
@@ -91,9 +91,11 @@ impl TransportConnector for SocksConnector {
             RouteType::SocksProxy,
             proxy_host.as_deref(),
             *proxy_port,
+            None,
             log_tag.clone(),
         )
-        .await?;
+        .await
+        .map_err(TransportConnectError::connecting_to_proxy)?;
         let is_ipv6 = tcp_stream
             .peer_addr()
             .expect("can retrieve addr info")
@@ -137,14 +139,15 @@ impl TransportConnector for SocksConnector {
             .connect_to_proxy(tcp_stream, target)
             .await
             .map_err(|e| {
-                let e = ErrorForLog(e);
-                log::warn!("proxy connection failed: {e}");
-                TransportConnectError::ProxyProtocol
+                let kind = proxy_failure_kind(&e);
+                log::warn!("proxy connection failed: {}", ErrorForLog(e));
+                TransportConnectError::ProxyFailure(kind)
             })?;
 
         log::debug!("connecting TLS through proxy");
         let stream =
-            crate::tcp_ssl::connect_tls(socks_stream, connection_params, alpn, log_tag).await?;
+            crate::tcp_ssl::connect_tls(socks_stream, connection_params, alpn, None, log_tag)
+                .await?;
 
         log::info!("connection through SOCKS proxy established successfully");
         Ok(StreamAndInfo(
@@ -175,6 +178,7 @@ impl Connector<SocksRoute<IpAddr>, ()> for super::StatelessProxied {
             target_addr,
             target_port,
         } = route;
+        let tcp_connector = self.tcp_connector();
 
         async move {
             log::info!("[{log_tag}] establishing connection to host over SOCKS proxy");
@@ -199,15 +203,16 @@ impl Connector<SocksRoute<IpAddr>, ()> for super::StatelessProxied {
                 }
             };
 
-            let stream = super::super::StatelessDirect
+            let stream = tcp_connector
                 .connect(proxy, log_tag.clone())
-                .await?;
+                .await
+                .map_err(TransportConnectError::connecting_to_proxy)?;
             log::info!("[{log_tag}] performing proxy handshake");
             log::debug!("[{log_tag}] performing proxy handshake with {target:?}");
             protocol
                 .connect_to_proxy(stream, target)
                 .await
-                .map_err(|_: tokio_socks::Error| TransportConnectError::ProxyProtocol)
+                .map_err(|e| TransportConnectError::ProxyFailure(proxy_failure_kind(&e)))
         }
     }
 }
@@ -261,6 +266,36 @@ impl Protocol {
     }
 }
 
+fn proxy_failure_kind(error: &tokio_socks::Error) -> ProxyFailureKind {
+    use tokio_socks::Error;
+    match error {
+        Error::ProxyServerUnreachable => ProxyFailureKind::Unreachable,
+        Error::NoAcceptableAuthMethods | Error::AuthorizationRequired => {
+            ProxyFailureKind::AuthRequired
+        }
+        Error::PasswordAuthFailure(_)
+        | Error::IdentdAuthFailure
+        | Error::InvalidUserIdAuthFailure => ProxyFailureKind::AuthFailed,
+        Error::ParseError(infallible) => match *infallible {},
+        Error::Io(_)
+        | Error::InvalidTargetAddress(_)
+        | Error::InvalidAuthValues(_)
+        | Error::InvalidResponseVersion
+        | Error::UnknownAuthMethod
+        | Error::GeneralSocksServerFailure
+        | Error::ConnectionNotAllowedByRuleset
+        | Error::NetworkUnreachable
+        | Error::HostUnreachable
+        | Error::ConnectionRefused
+        | Error::TtlExpired
+        | Error::CommandNotSupported
+        | Error::AddressTypeNotSupported
+        | Error::UnknownError
+        | Error::InvalidReservedByte
+        | Error::UnknownAddressType => ProxyFailureKind::HandshakeRejected,
+    }
+}
+
 struct ErrorForLog(tokio_socks::Error);
 
 impl Display for ErrorForLog {
@@ -639,6 +674,11 @@ mod test {
         );
 
         // The client should see the rejection as well.
-        assert_matches!(client_result, Err(TransportConnectError::ProxyProtocol));
+        assert_matches!(
+            client_result,
+            Err(TransportConnectError::ProxyFailure(
+                ProxyFailureKind::AuthFailed
+            ))
+        );
     }
 }
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::net::IpAddr;
 use std::num::NonZeroU16;
 use std::sync::Arc;
 
@@ -15,7 +16,7 @@ use crate::certs::RootCertificates;
 use crate::dns::DnsResolver;
 use crate::errors::TransportConnectError;
 use crate::host::Host;
-use crate::tcp_ssl::{connect_tcp, connect_tls, ssl_config};
+use crate::tcp_ssl::{connect_tcp, connect_tls, ssl_config, TlsSessionCache};
 use crate::{
     Alpn, RouteType, ServiceConnectionInfo, StreamAndInfo, TransportConnectionParams,
     TransportConnector,
@@ -37,6 +38,9 @@ pub struct TlsProxyConnector {
     proxy_port: NonZeroU16,
     pub(crate) proxy_certs: RootCertificates,
     use_tls_for_proxy: ShouldUseTls,
+    pub(crate) local_bind_address: Option<IpAddr>,
+    /// Used for the TLS connection to the target server, not the proxy.
+    pub(crate) tls_session_cache: Option<Arc<TlsSessionCache>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -60,9 +64,11 @@ impl TransportConnector for TlsProxyConnector {
             RouteType::TlsProxy,
             self.proxy_host.as_deref(),
             self.proxy_port,
+            self.local_bind_address,
             log_tag.clone(),
         )
-        .await?;
+        .await
+        .map_err(TransportConnectError::connecting_to_proxy)?;
 
         let inner_stream = match self.use_tls_for_proxy {
             ShouldUseTls::Yes => {
@@ -93,7 +99,14 @@ impl TransportConnector for TlsProxyConnector {
             }
         };
 
-        let tls_stream = connect_tls(inner_stream, connection_params, alpn, log_tag).await?;
+        let tls_stream = connect_tls(
+            inner_stream,
+            connection_params,
+            alpn,
+            self.tls_session_cache.clone(),
+            log_tag,
+        )
+        .await?;
 
         Ok(StreamAndInfo(
             tls_stream,
@@ -122,6 +135,8 @@ impl TlsProxyConnector {
             // is also TLS-encrypted.
             proxy_certs: RootCertificates::Native,
             use_tls_for_proxy,
+            local_bind_address: None,
+            tls_session_cache: None,
         }
     }
 
@@ -0,0 +1,216 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use boring_signal::ssl::{ConnectConfiguration, SslConnector, SslSession, SslSessionCacheMode};
+use indexmap::IndexMap;
+use nonzero_ext::nonzero;
+
+use crate::certs::RootCertificates;
+use crate::errors::TransportConnectError;
+use crate::host::Host;
+use crate::Alpn;
+
+/// Saves TLS sessions so that later connections to the same server can resume
+/// them instead of doing a full handshake.
+///
+/// Sessions are kept per hostname, ALPN, and set of root certificates, so a
+/// session is only resumed under the same certificate validation policy that
+/// was used to verify the server originally. The proxy in use is part of the
+/// key implicitly: the cache is meant to be [cleared](Self::clear) whenever the
+/// proxy changes, as [`TcpSslConnector`](super::TcpSslConnector) does.
+///
+/// Only the most recently used [`Self::max_entries`] servers are remembered.
+pub struct TlsSessionCache {
+    entries: Mutex<IndexMap<SessionKey, CachedContext>>,
+    max_entries: NonZeroUsize,
+    resumed: AtomicU64,
+    full: AtomicU64,
+}
+
+/// How many TLS handshakes resumed a saved session, and how many didn't.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsHandshakeCounts {
+    pub resumed: u64,
+    pub full: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct SessionKey {
+    host: Host<Arc<str>>,
+    alpn: Option<Alpn>,
+    root_certs: RootCertificates,
+}
+
+/// A session can only be resumed with the context it was created from, so the
+/// context is kept along with it.
+#[derive(Clone)]
+struct CachedContext {
+    connector: SslConnector,
+    session: Arc<Mutex<Option<SslSession>>>,
+}
+
+impl TlsSessionCache {
+    pub const DEFAULT_MAX_ENTRIES: NonZeroUsize = nonzero!(16usize);
+
+    pub fn new(max_entries: NonZeroUsize) -> Self {
+        Self {
+            entries: Default::default(),
+            max_entries,
+            resumed: Default::default(),
+            full: Default::default(),
+        }
+    }
+
+    pub fn max_entries(&self) -> NonZeroUsize {
+        self.max_entries
+    }
+
+    /// Forgets every saved session.
+    ///
+    /// Handshake counts are kept.
+    pub fn clear(&self) {
+        self.entries.lock().expect("not poisoned").clear();
+    }
+
+    pub fn handshake_counts(&self) -> TlsHandshakeCounts {
+        TlsHandshakeCounts {
+            resumed: self.resumed.load(Ordering::Relaxed),
+            full: self.full.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Like [`super::ssl_config`], but resumes the session saved for this
+    /// server, if there is one, and saves any new session the server sends.
+    pub(crate) fn ssl_config(
+        &self,
+        root_certs: &RootCertificates,
+        host: Host<&str>,
+        alpn: Option<Alpn>,
+    ) -> Result<ConnectConfiguration, TransportConnectError> {
+        let key = SessionKey {
+            host: host.map_domain(Arc::from),
+            alpn,
+            root_certs: root_certs.clone(),
+        };
+
+        let context = {
+            let mut entries = self.entries.lock().expect("not poisoned");
+            let context = match entries.shift_remove(&key) {
+                Some(context) => context,
+                None => CachedContext::new(root_certs, host, alpn)?,
+            };
+            // Keep the most recently used entries at the end.
+            entries.insert(key, context.clone());
+            if entries.len() > self.max_entries.get() {
+                entries.shift_remove_index(0);
+            }
+            context
+        };
+
+        let mut config = context.connector.configure()?;
+        if let Some(session) = &*context.session.lock().expect("not poisoned") {
+            // SAFETY: the session was saved by the new-session callback of this
+            // same context.
+            unsafe { config.set_session(session)? };
+        }
+        Ok(config)
+    }
+
+    /// Records whether a completed handshake resumed a saved session.
+    pub(crate) fn record_handshake(&self, resumed: bool) {
+        let counter = if resumed { &self.resumed } else { &self.full };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for TlsSessionCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl std::fmt::Debug for TlsSessionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsSessionCache")
+            .field("entries", &self.entries.lock().expect("not poisoned").len())
+            .field("max_entries", &self.max_entries)
+            .field("handshake_counts", &self.handshake_counts())
+            .finish()
+    }
+}
+
+impl CachedContext {
+    fn new(
+        root_certs: &RootCertificates,
+        host: Host<&str>,
+        alpn: Option<Alpn>,
+    ) -> Result<Self, TransportConnectError> {
+        let session = Arc::new(Mutex::new(None));
+        let mut builder = super::ssl_connector_builder(root_certs, host, alpn)?;
+        builder.set_session_cache_mode(SslSessionCacheMode::CLIENT);
+        builder.set_new_session_callback({
+            let session = Arc::clone(&session);
+            move |_ssl, new_session| {
+                *session.lock().expect("not poisoned") = Some(new_session);
+            }
+        });
+        Ok(Self {
+            connector: builder.build(),
+            session,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    fn entry_count(cache: &TlsSessionCache) -> usize {
+        cache.entries.lock().expect("not poisoned").len()
+    }
+
+    #[test]
+    fn entries_are_bounded() {
+        let cache = TlsSessionCache::new(nonzero!(2usize));
+        for host in ["a.example", "b.example", "c.example"] {
+            assert_matches!(
+                cache.ssl_config(&RootCertificates::Native, Host::Domain(host), None),
+                Ok(_)
+            );
+        }
+        assert_eq!(entry_count(&cache), 2);
+
+        let entries = cache.entries.lock().expect("not poisoned");
+        let hosts = entries
+            .keys()
+            .map(|key| key.host.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(hosts, ["b.example", "c.example"]);
+    }
+
+    #[test]
+    fn root_certificates_are_part_of_the_key() {
+        let cache = TlsSessionCache::default();
+        for certs in [
+            RootCertificates::Native,
+            RootCertificates::FromStaticDers(&[]),
+        ] {
+            assert_matches!(
+                cache.ssl_config(&certs, Host::Domain("a.example"), Some(Alpn::Http1_1)),
+                Ok(_)
+            );
+        }
+        assert_eq!(entry_count(&cache), 2);
+
+        cache.clear();
+        assert_eq!(entry_count(&cache), 0);
+    }
+}
@@ -16,6 +16,8 @@ pub mod future;
 mod observable_event;
 pub use observable_event::*;
 pub mod oneshot_broadcast;
+mod single_flight;
+pub use single_flight::*;
 
 /// Constructs the value of the `Authorization` header for the `Basic` auth scheme.
 pub fn basic_authorization(username: &str, password: &str) -> HeaderValue {
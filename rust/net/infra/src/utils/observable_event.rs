@@ -20,30 +20,42 @@ use std::time::Duration;
 /// Of course, any *particular* callback might spawn a task or send a message on a channel.
 #[derive(Default)]
 pub struct ObservableEvent {
-    // We could make ObservableEvent Clone by putting the Condvar inside the Arc, but *not* doing so
-    // lets us control who can fire the event.
-    state: Arc<std::sync::Mutex<ObservableEventState>>,
+    inner: ObservableEventWithPayload<()>,
+}
+
+/// Like [`ObservableEvent`], but each firing carries a value that is passed to every callback.
+///
+/// This lets subscribers tell different kinds of event apart without needing a separate
+/// [`ObservableEvent`] for each one.
+pub struct ObservableEventWithPayload<T> {
+    // We could make the event Clone by putting the Condvar inside the Arc, but *not* doing so lets
+    // us control who can fire the event.
+    state: Arc<std::sync::Mutex<ObservableEventState<T>>>,
     fire_in_progress_cvar: std::sync::Condvar,
 }
 
-#[derive(Default)]
-struct ObservableEventState {
-    actions: indexmap::IndexMap<u64, Box<dyn FnMut() + Send>>,
+struct ObservableEventState<T> {
+    actions: indexmap::IndexMap<u64, Box<dyn FnMut(&T) + Send>>,
     fire_in_progress: bool,
     next_id: u64,
     ids_to_remove: Vec<u64>,
 }
 
-/// Represents an action subscription to an [`ObservableEvent`].
+/// Represents an action subscription to an [`ObservableEvent`] or [`ObservableEventWithPayload`].
 ///
 /// When dropped, removes the registered callback from the event's list of callbacks.
 #[must_use]
-#[derive(Debug)]
 pub struct EventSubscription {
-    event: std::sync::Weak<std::sync::Mutex<ObservableEventState>>,
+    event: std::sync::Weak<dyn Unsubscribe>,
     id: u64,
 }
 
+/// Type-erased access to an event's state, so that [`EventSubscription`] doesn't need to know the
+/// payload type.
+trait Unsubscribe: Send + Sync {
+    fn unsubscribe(&self, id: u64);
+}
+
 /// A backstop timeout after which an event firing is considered to have failed because a previous
 /// fire is taking too long.
 const STALLED_EVENT_TIMEOUT: Duration = Duration::from_secs(5);
@@ -55,11 +67,45 @@ impl ObservableEvent {
 
     /// Fires the event, running all its callbacks **synchronously**.
     ///
+    /// See [`ObservableEventWithPayload::fire`].
+    pub fn fire(&self) {
+        self.inner.fire(&())
+    }
+
+    /// Adds a callback to the list that will be invoked when the event fires.
+    ///
+    /// See [`ObservableEventWithPayload::subscribe`].
+    pub fn subscribe(&self, mut callback: Box<dyn FnMut() + Send>) -> EventSubscription {
+        self.inner.subscribe(Box::new(move |()| callback()))
+    }
+}
+
+impl<T> Default for ObservableEventWithPayload<T> {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(std::sync::Mutex::new(ObservableEventState {
+                actions: Default::default(),
+                fire_in_progress: false,
+                next_id: 0,
+                ids_to_remove: vec![],
+            })),
+            fire_in_progress_cvar: Default::default(),
+        }
+    }
+}
+
+impl<T: 'static> ObservableEventWithPayload<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fires the event, running all its callbacks **synchronously** with `payload`.
+    ///
     /// Subscriptions may be added during the execution of `fire` (that is,
     /// [`subscribe`](Self::subscribe) won't block waiting for `fire` to complete), but they will
     /// not be invoked unless `fire` is called again. If `fire` is called again during the execution
     /// of `fire`, the second call will block until the first one completes.
-    pub fn fire(&self) {
+    pub fn fire(&self, payload: &T) {
         // Take the list of actions out of the mutex to avoid running arbitrary code while holding
         // the lock.
         let mut actions = {
@@ -84,7 +130,7 @@ impl ObservableEvent {
         };
 
         for f in actions.values_mut() {
-            f()
+            f(payload)
         }
 
         let mut guard = self
@@ -116,7 +162,7 @@ impl ObservableEvent {
     ///
     /// The returned EventSubscription must be stored; dropping it will remove the callback from the
     /// list.
-    pub fn subscribe(&self, callback: Box<dyn FnMut() + Send>) -> EventSubscription {
+    pub fn subscribe(&self, callback: Box<dyn FnMut(&T) + Send>) -> EventSubscription {
         let id = {
             let mut guard = self
                 .state
@@ -127,28 +173,40 @@ impl ObservableEvent {
             guard.actions.insert(id, callback);
             id
         };
-        EventSubscription {
-            event: Arc::downgrade(&self.state),
-            id,
+        let event: std::sync::Weak<dyn Unsubscribe> = Arc::downgrade(&self.state);
+        EventSubscription { event, id }
+    }
+}
+
+impl<T> Unsubscribe for std::sync::Mutex<ObservableEventState<T>> {
+    fn unsubscribe(&self, id: u64) {
+        let mut guard = self.lock().expect("no panics because no arbitrary code");
+        if let Some(callback) = guard.actions.shift_remove(&id) {
+            // Make sure we drop the lock before we drop the callback (which could run arbitrary
+            // Drop impls).
+            drop(guard);
+            drop(callback);
+        } else {
+            guard.ids_to_remove.push(id);
         }
     }
 }
 
+impl std::fmt::Debug for EventSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSubscription")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Drop for EventSubscription {
     fn drop(&mut self) {
         let Some(event) = self.event.upgrade() else {
             // If the event owner is gone, there's nothing to unsubscribe from.
             return;
         };
-        let mut guard = event.lock().expect("no panics because no arbitrary code");
-        if let Some(callback) = guard.actions.shift_remove(&self.id) {
-            // Make sure we drop the lock before we drop the callback (which could run arbitrary
-            // Drop impls).
-            drop(guard);
-            drop(callback);
-        } else {
-            guard.ids_to_remove.push(self.id);
-        }
+        event.unsubscribe(self.id);
     }
 }
 
@@ -184,6 +242,25 @@ mod test {
         assert_eq!(2, counter.load(Ordering::Relaxed));
     }
 
+    #[test]
+    fn observable_event_with_payload() {
+        let event = ObservableEventWithPayload::<u32>::new();
+
+        let record = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let record_for_event = record.clone();
+        let subscription = event.subscribe(Box::new(move |value| {
+            record_for_event.lock().expect("not poisoned").push(*value);
+        }));
+
+        event.fire(&1);
+        event.fire(&2);
+        assert_eq!(&[1, 2], record.lock().expect("not poisoned").as_slice());
+
+        drop(subscription);
+        event.fire(&3);
+        assert_eq!(&[1, 2], record.lock().expect("not poisoned").as_slice());
+    }
+
     #[test]
     fn observable_event_remove_preserves_order() {
         let event = Arc::new(ObservableEvent::default());
@@ -0,0 +1,202 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use super::oneshot_broadcast;
+
+/// Lets concurrent callers share a single in-progress operation per key.
+///
+/// The first caller for a key runs the operation; callers that arrive while it
+/// is still running wait for it instead of starting their own, and receive a
+/// shared copy of its result. Nothing is cached: once the operation finishes,
+/// successfully or not, the next caller for the key starts a new one.
+///
+/// If the leading caller is cancelled, one of the waiting callers takes over
+/// and runs its own operation.
+pub struct SingleFlight<K, S> {
+    in_flight: Mutex<HashMap<K, oneshot_broadcast::Receiver<S>>>,
+}
+
+/// How a caller of [`SingleFlight::run`] got its result.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Flight<T, S> {
+    /// The caller ran the operation itself.
+    Led(T),
+    /// The caller waited for another caller's operation.
+    Joined(S),
+}
+
+impl<K, S> Default for SingleFlight<K, S> {
+    fn default() -> Self {
+        Self {
+            in_flight: Default::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, S: Clone> SingleFlight<K, S> {
+    /// Runs `operation`, or waits for the one already running for `key`.
+    ///
+    /// The leading caller gets the operation's output as is; `share` produces
+    /// the copy handed to every caller that joined it.
+    pub async fn run<T, F: Future<Output = T>>(
+        &self,
+        key: K,
+        operation: impl FnOnce() -> F,
+        share: impl FnOnce(&T) -> S,
+    ) -> Flight<T, S> {
+        loop {
+            let mut waiting = {
+                let mut in_flight = self.in_flight.lock().expect("not poisoned");
+                match in_flight.get(&key) {
+                    Some(receiver) => receiver.clone(),
+                    None => {
+                        let (sender, receiver) = oneshot_broadcast::channel();
+                        in_flight.insert(key.clone(), receiver);
+                        drop(in_flight);
+
+                        let guard = LeaderGuard {
+                            in_flight: &self.in_flight,
+                            key,
+                        };
+                        let output = operation().await;
+                        let shared = share(&output);
+                        // Clear the slot before publishing the result so that
+                        // later callers start a fresh operation.
+                        drop(guard);
+                        // It's fine if nobody joined.
+                        let _ = sender.send(shared);
+                        return Flight::Led(output);
+                    }
+                }
+            };
+            match waiting.val().await {
+                Ok(shared) => return Flight::Joined(shared),
+                // The leader was cancelled; try again, possibly as the leader.
+                Err(oneshot_broadcast::RecvError) => continue,
+            }
+        }
+    }
+}
+
+/// Removes the leader's entry when it finishes or is cancelled.
+struct LeaderGuard<'a, K: Eq + Hash, S> {
+    in_flight: &'a Mutex<HashMap<K, oneshot_broadcast::Receiver<S>>>,
+    key: K,
+}
+
+impl<K: Eq + Hash, S> Drop for LeaderGuard<'_, K, S> {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .expect("not poisoned")
+            .remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use assert_matches::assert_matches;
+    use futures_util::future::join_all;
+
+    use super::*;
+
+    const OPERATION_TIME: Duration = Duration::from_secs(5);
+
+    async fn count_and_sleep(count: &AtomicUsize) -> Result<usize, String> {
+        let n = count.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::time::sleep(OPERATION_TIME).await;
+        if n % 2 == 1 {
+            Ok(n)
+        } else {
+            Err(format!("attempt {n} failed"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_callers_share_one_operation() {
+        let flight = SingleFlight::<(), Result<usize, String>>::default();
+        let count = AtomicUsize::new(0);
+
+        let results = join_all(
+            (0..10).map(|_| flight.run((), || count_and_sleep(&count), |result| result.clone())),
+        )
+        .await;
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_matches!(results[0], Flight::Led(Ok(1)));
+        for result in &results[1..] {
+            assert_matches!(result, Flight::Joined(Ok(1)));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failure_is_delivered_once_and_not_cached() {
+        let flight = SingleFlight::<(), Result<usize, String>>::default();
+        let count = AtomicUsize::new(0);
+        // Use up the first (successful) attempt.
+        let _ = flight
+            .run((), || count_and_sleep(&count), |result| result.clone())
+            .await;
+
+        let results = join_all(
+            (0..3).map(|_| flight.run((), || count_and_sleep(&count), |result| result.clone())),
+        )
+        .await;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        for result in results {
+            let (Flight::Led(result) | Flight::Joined(result)) = result;
+            assert_eq!(result, Err("attempt 2 failed".to_owned()));
+        }
+
+        // The next caller tries again.
+        assert_matches!(
+            flight
+                .run((), || count_and_sleep(&count), |result| result.clone())
+                .await,
+            Flight::Led(Ok(3))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn different_keys_run_separately() {
+        let flight = SingleFlight::<u8, Result<usize, String>>::default();
+        let count = AtomicUsize::new(0);
+
+        let _ = join_all(
+            (0..4).map(|i| flight.run(i % 2, || count_and_sleep(&count), |result| result.clone())),
+        )
+        .await;
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiter_takes_over_from_cancelled_leader() {
+        let flight = SingleFlight::<(), Result<usize, String>>::default();
+        let count = AtomicUsize::new(0);
+
+        let mut leader =
+            Box::pin(flight.run((), || count_and_sleep(&count), |result| result.clone()));
+        let mut follower =
+            Box::pin(flight.run((), || count_and_sleep(&count), |result| result.clone()));
+
+        // Start both, so the second one is waiting on the first.
+        assert!(futures_util::poll!(leader.as_mut()).is_pending());
+        assert!(futures_util::poll!(follower.as_mut()).is_pending());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        drop(leader);
+        assert_matches!(follower.await, Flight::Led(Err(_)));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+}
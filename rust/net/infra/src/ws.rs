@@ -31,6 +31,9 @@ use crate::{
     ServiceConnectionInfo, StreamAndInfo, TransportConnector,
 };
 
+mod counting;
+pub use counting::{CountingWebSocket, TransferCounter, TransferTotals};
+
 pub mod error;
 pub use error::{LogSafeTungsteniteError, WebSocketConnectError};
 
@@ -0,0 +1,143 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::{Sink, Stream};
+use pin_project::pin_project;
+use tungstenite::Message;
+
+/// Running totals of bytes sent and received.
+///
+/// Counts are of websocket message payloads, so they don't include websocket
+/// framing, TLS, or TCP overhead.
+#[derive(Debug, Default)]
+pub struct TransferCounter {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+/// A snapshot of a [`TransferCounter`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransferTotals {
+    pub sent: u64,
+    pub received: u64,
+}
+
+impl TransferCounter {
+    pub fn record_sent(&self, bytes: usize) {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn totals(&self) -> TransferTotals {
+        TransferTotals {
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets both counts back to zero, returning what they were.
+    pub fn reset(&self) -> TransferTotals {
+        TransferTotals {
+            sent: self.sent.swap(0, Ordering::Relaxed),
+            received: self.received.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Wraps a websocket to add the size of every message sent or received to a
+/// [`TransferCounter`].
+#[pin_project]
+pub struct CountingWebSocket<S> {
+    #[pin]
+    inner: S,
+    counter: Arc<TransferCounter>,
+}
+
+impl<S> CountingWebSocket<S> {
+    pub fn new(inner: S, counter: Arc<TransferCounter>) -> Self {
+        Self { inner, counter }
+    }
+}
+
+impl<S: Stream<Item = Result<Message, tungstenite::Error>>> Stream for CountingWebSocket<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let result = this.inner.poll_next(cx);
+        if let Poll::Ready(Some(Ok(message))) = &result {
+            this.counter.record_received(message.len());
+        }
+        result
+    }
+}
+
+impl<S: Sink<Message, Error = tungstenite::Error>> Sink<Message> for CountingWebSocket<S> {
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        let this = self.project();
+        let len = item.len();
+        this.inner.start_send(item)?;
+        this.counter.record_sent(len);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures_util::{SinkExt as _, StreamExt as _};
+
+    use super::*;
+    use crate::ws::testutil::fake_websocket;
+
+    #[tokio::test]
+    async fn counts_messages_in_both_directions() {
+        let (mut server, client) = fake_websocket().await;
+        let counter = Arc::new(TransferCounter::default());
+        let mut client = CountingWebSocket::new(client, counter.clone());
+
+        client
+            .send(Message::Binary(b"hello".to_vec()))
+            .await
+            .expect("can send");
+        server
+            .send(Message::Text("hi there".into()))
+            .await
+            .expect("can send");
+        let _ = server.next().await.expect("open").expect("ok");
+        let _ = client.next().await.expect("open").expect("ok");
+
+        assert_eq!(
+            counter.totals(),
+            TransferTotals {
+                sent: 5,
+                received: 8
+            }
+        );
+        assert_eq!(counter.reset().sent, 5);
+        assert_eq!(counter.totals(), TransferTotals::default());
+    }
+}
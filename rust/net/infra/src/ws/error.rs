@@ -35,6 +35,45 @@ impl std::fmt::Display for WebSocketConnectError {
 
 impl LogSafeDisplay for WebSocketConnectError {}
 
+impl WebSocketConnectError {
+    /// Produces an equivalent error, for reporting the same failure more than
+    /// once.
+    ///
+    /// Most errors are copied exactly, including any HTTP response that
+    /// rejected the websocket upgrade. Errors that can't be copied, like I/O
+    /// errors, keep their kind (if they have one) and message but lose their
+    /// source.
+    pub fn duplicate(&self) -> Self {
+        match self {
+            Self::Transport(e) => Self::Transport(e.clone()),
+            Self::Timeout => Self::Timeout,
+            Self::WebSocketError(e) => Self::WebSocketError(duplicate_tungstenite_error(e)),
+        }
+    }
+}
+
+fn duplicate_tungstenite_error(error: &tungstenite::Error) -> tungstenite::Error {
+    use tungstenite::Error;
+    match error {
+        Error::ConnectionClosed => Error::ConnectionClosed,
+        Error::AlreadyClosed => Error::AlreadyClosed,
+        Error::Io(e) => Error::Io(std::io::Error::new(e.kind(), e.to_string())),
+        Error::Capacity(e) => Error::Capacity(*e),
+        Error::Protocol(e) => Error::Protocol(e.clone()),
+        Error::WriteBufferFull(message) => Error::WriteBufferFull(message.clone()),
+        Error::Utf8 => Error::Utf8,
+        Error::AttackAttempt => Error::AttackAttempt,
+        Error::Http(response) => {
+            let mut copy = http::Response::new(response.body().clone());
+            *copy.status_mut() = response.status();
+            *copy.version_mut() = response.version();
+            *copy.headers_mut() = response.headers().clone();
+            Error::Http(copy)
+        }
+        e => Error::Io(std::io::Error::other(e.to_string())),
+    }
+}
+
 impl From<std::io::Error> for WebSocketConnectError {
     fn from(value: std::io::Error) -> Self {
         Self::WebSocketError(value.into())
@@ -238,3 +277,43 @@ impl<'a> From<&'a tungstenite::Error> for LogSafeTungsteniteError {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn duplicate_keeps_upgrade_response() {
+        let response = http::Response::builder()
+            .status(http::StatusCode::TOO_MANY_REQUESTS)
+            .header(http::header::RETRY_AFTER, "5")
+            .body(Some(b"slow down".to_vec()))
+            .expect("valid response");
+        let error = WebSocketConnectError::WebSocketError(tungstenite::Error::Http(response));
+
+        assert_matches!(
+            error.duplicate(),
+            WebSocketConnectError::WebSocketError(tungstenite::Error::Http(copy)) => {
+                assert_eq!(copy.status(), http::StatusCode::TOO_MANY_REQUESTS);
+                assert_eq!(copy.headers()[http::header::RETRY_AFTER], "5");
+                assert_eq!(copy.body().as_deref(), Some(b"slow down".as_slice()));
+            }
+        );
+    }
+
+    #[test]
+    fn duplicate_keeps_io_error_kind() {
+        let error = WebSocketConnectError::WebSocketError(tungstenite::Error::Io(
+            std::io::ErrorKind::ConnectionReset.into(),
+        ));
+
+        assert_matches!(
+            error.duplicate(),
+            WebSocketConnectError::WebSocketError(tungstenite::Error::Io(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::ConnectionReset);
+            }
+        );
+    }
+}
@@ -96,6 +96,9 @@ pub struct Connection<S, R> {
     /// This is always <= `last_sent_to_server`.
     last_sent_ping_to_server: Option<Instant>,
 
+    /// Whether the most recent [`Message::Ping`] hasn't been answered yet.
+    awaiting_pong: bool,
+
     /// The last time that a message was received from the server.
     last_heard_from_server: Option<Instant>,
 
@@ -138,7 +141,15 @@ pub enum MessageEvent<Meta> {
     /// A ping was sent successfully.
     SentPing,
     /// A ping or pong frame were received.
+    ///
+    /// Pongs answering the most recent ping are reported as
+    /// [`Self::ReceivedPong`] instead.
     ReceivedPingPong,
+    /// A pong frame answering the most recent ping was received.
+    ReceivedPong {
+        /// How long after the ping was sent the pong arrived.
+        round_trip: Duration,
+    },
 }
 
 /// Why the task finished.
@@ -215,6 +226,7 @@ where
             last_heard_from_server: None,
             last_sent_to_server: None,
             last_sent_ping_to_server: None,
+            awaiting_pong: false,
             log_tag,
         }
     }
@@ -257,6 +269,7 @@ where
                 },
             last_sent_to_server,
             last_sent_ping_to_server,
+            awaiting_pong,
             last_heard_from_server,
             log_tag,
         } = self.project();
@@ -341,6 +354,7 @@ where
                         let now = Instant::now();
                         *last_sent_to_server = now;
                         *last_sent_ping_to_server = now;
+                        *awaiting_pong = true;
                         Outcome::Continue(MessageEvent::SentPing)
                     }
                     Err(err) => Outcome::Finished(Err(NextEventError::PingFailed(err))),
@@ -377,7 +391,8 @@ where
                 Outcome::Finished(Err(NextEventError::UnexpectedConnectionClose))
             }
             Event::Received(Ok(message)) => {
-                *last_heard_from_server = Instant::now();
+                let now = Instant::now();
+                *last_heard_from_server = now;
                 match message {
                     Message::Text(text) => {
                         Outcome::Continue(MessageEvent::ReceivedMessage(TextOrBinary::Text(text)))
@@ -385,6 +400,12 @@ where
                     Message::Binary(binary) => Outcome::Continue(MessageEvent::ReceivedMessage(
                         TextOrBinary::Binary(binary),
                     )),
+                    Message::Pong(payload) if *awaiting_pong && payload == [*ping_count] => {
+                        *awaiting_pong = false;
+                        Outcome::Continue(MessageEvent::ReceivedPong {
+                            round_trip: now - *last_sent_ping_to_server,
+                        })
+                    }
                     Message::Ping(_) | Message::Pong(_) => {
                         // tungstenite handles pings internally, nothing to do here.
                         Outcome::Continue(MessageEvent::ReceivedPingPong)
@@ -927,6 +948,55 @@ mod test {
         assert_ne!(first_ping, second_ping);
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn reports_round_trip_for_pong_answering_ping() {
+        const LOCAL_IDLE_TIMEOUT: Duration = Duration::from_secs(123);
+        const ROUND_TRIP: Duration = Duration::from_millis(250);
+
+        let (mut ws_server, ws_client) = TestStream::new_pair(2);
+        let outgoing_rx = futures_util::stream::pending::<(_, ())>();
+        let connection = Connection::new(
+            ws_client,
+            outgoing_rx,
+            Config {
+                local_idle_timeout: LOCAL_IDLE_TIMEOUT,
+                remote_idle_ping_timeout: FOREVER,
+                remote_idle_disconnect_timeout: FOREVER,
+            },
+            "test".into(),
+        );
+        pin_mut!(connection);
+
+        assert_matches!(
+            connection.as_mut().handle_next_event().await,
+            Outcome::Continue(MessageEvent::SentPing)
+        );
+        let ping = ws_server.next().now_or_never().expect("now");
+        let payload = assert_matches!(ping, Some(Ok(Message::Ping(payload))) => payload);
+
+        tokio::time::sleep(ROUND_TRIP).await;
+        ws_server
+            .send_all(
+                &mut futures_util::stream::iter([
+                    Message::Pong(payload.clone()),
+                    // A repeated pong doesn't answer anything.
+                    Message::Pong(payload),
+                ])
+                .map(Ok),
+            )
+            .await
+            .expect("can send all");
+
+        assert_matches!(
+            connection.as_mut().handle_next_event().await,
+            Outcome::Continue(MessageEvent::ReceivedPong { round_trip }) if round_trip == ROUND_TRIP
+        );
+        assert_matches!(
+            connection.as_mut().handle_next_event().await,
+            Outcome::Continue(MessageEvent::ReceivedPingPong)
+        );
+    }
+
     #[tokio::test(start_paused = true)]
     async fn sends_ping_after_remote_inactivity_then_time_out() {
         // A single ping will be sent locally before the server times out.
@@ -292,7 +292,9 @@ async fn spawned_task_body(
                         return Ok(());
                     }
                 }
-                MessageEvent::SentPing | MessageEvent::ReceivedPingPong => (),
+                MessageEvent::SentPing
+                | MessageEvent::ReceivedPingPong
+                | MessageEvent::ReceivedPong { .. } => (),
             },
             Outcome::Finished(Ok(FinishReason::RemoteDisconnect)) => {
                 if incoming_tx
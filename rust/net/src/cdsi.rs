@@ -4,6 +4,8 @@
 //
 
 use std::default::Default;
+use std::num::NonZeroUsize;
+use std::time::Instant;
 
 use futures_util::TryFutureExt as _;
 use http::{HeaderName, StatusCode};
@@ -27,9 +29,11 @@ use uuid::Uuid;
 
 use crate::auth::Auth;
 use crate::connect_state::{ConnectState, WebSocketTransportConnectorFactory};
-use crate::enclave::{Cdsi, EnclaveEndpointConnection, EndpointParams};
+use crate::enclave::{AttestedMeasurement, Cdsi, EnclaveEndpointConnection, EndpointParams};
+use crate::metrics::ServiceKind;
+use crate::network_events::NetworkEventLog;
 use crate::proto::cds2::{ClientRequest, ClientResponse};
-use crate::ws::WebSocketServiceConnectError;
+use crate::ws::{RateLimitChallenge, WebSocketServiceConnectError};
 
 trait FixedLengthSerializable {
     const SERIALIZED_LEN: usize;
@@ -91,15 +95,41 @@ impl FixedLengthSerializable for AciAndAccessKey {
 pub struct LookupRequest {
     pub new_e164s: Vec<E164>,
     pub prev_e164s: Vec<E164>,
+    /// Numbers from `prev_e164s` that should no longer be covered by the
+    /// returned token.
+    pub discard_e164s: Vec<E164>,
     pub acis_and_access_keys: Vec<AciAndAccessKey>,
     pub token: Box<[u8]>,
 }
 
 impl LookupRequest {
+    /// Creates a request that continues from an earlier lookup.
+    ///
+    /// `prev_e164s` must be the full set of numbers covered by
+    /// `previous_token`; only `new_e164s` count against the rate limit. If the
+    /// server no longer accepts the token, the lookup fails with
+    /// [`LookupError::InvalidToken`] and a full lookup (without a token) should
+    /// be made instead.
+    pub fn continuing_from(
+        previous_token: Token,
+        prev_e164s: Vec<E164>,
+        new_e164s: Vec<E164>,
+        removed_e164s: Vec<E164>,
+    ) -> Self {
+        Self {
+            new_e164s,
+            prev_e164s,
+            discard_e164s: removed_e164s,
+            acis_and_access_keys: Vec::new(),
+            token: previous_token.0,
+        }
+    }
+
     fn into_client_request(self) -> ClientRequest {
         let Self {
             new_e164s,
             prev_e164s,
+            discard_e164s,
             acis_and_access_keys,
             token,
         } = self;
@@ -107,15 +137,15 @@ impl LookupRequest {
         let aci_uak_pairs = acis_and_access_keys.into_iter().collect_serialized();
         let new_e164s = new_e164s.into_iter().collect_serialized();
         let prev_e164s = prev_e164s.into_iter().collect_serialized();
+        let discard_e164s = discard_e164s.into_iter().collect_serialized();
 
         ClientRequest {
             aci_uak_pairs,
             new_e164s,
             prev_e164s,
+            discard_e164s,
             token: token.into_vec(),
             token_ack: false,
-            // TODO: use these for supporting non-desktop client requirements.
-            discard_e164s: Vec::new(),
         }
     }
 }
@@ -226,7 +256,7 @@ impl FixedLengthSerializable for LookupResponseEntry {
 }
 
 #[cfg_attr(test, derive(Debug))]
-pub struct CdsiConnection(AttestedConnection);
+pub struct CdsiConnection(AttestedConnection, AttestedMeasurement);
 
 impl AsMut<AttestedConnection> for CdsiConnection {
     fn as_mut(&mut self) -> &mut AttestedConnection {
@@ -243,6 +273,8 @@ pub enum LookupError {
     InvalidResponse,
     /// retry later
     RateLimited(#[from] RetryLater),
+    /// {0}
+    RateLimitChallenge(RateLimitChallenge),
     /// request token was invalid
     InvalidToken,
     /// failed to parse the response from the server
@@ -293,6 +325,9 @@ impl From<crate::enclave::Error> for LookupError {
                             return Self::RateLimited(retry_later);
                         }
                     }
+                    if let Some(challenge) = RateLimitChallenge::from_response(&response) {
+                        return Self::RateLimitChallenge(challenge);
+                    }
                     Self::WebSocket(WebSocketServiceError::Http(response))
                 }
                 WebSocketServiceConnectError::Connect(e, _) => match e {
@@ -331,20 +366,59 @@ impl CdsiConnection {
         transport_connector: T,
         auth: Auth,
     ) -> Result<Self, LookupError>
+    where
+        C: ConnectionManager,
+        T: TransportConnector,
+    {
+        Self::connect_and_record(endpoint, transport_connector, auth, None).await
+    }
+
+    /// Like [`Self::connect`], but also records the attempt in `network_events`.
+    pub async fn connect_recording_events<C, T>(
+        endpoint: &EnclaveEndpointConnection<Cdsi, C>,
+        transport_connector: T,
+        auth: Auth,
+        network_events: &NetworkEventLog,
+    ) -> Result<Self, LookupError>
+    where
+        C: ConnectionManager,
+        T: TransportConnector,
+    {
+        Self::connect_and_record(endpoint, transport_connector, auth, Some(network_events)).await
+    }
+
+    async fn connect_and_record<C, T>(
+        endpoint: &EnclaveEndpointConnection<Cdsi, C>,
+        transport_connector: T,
+        auth: Auth,
+        network_events: Option<&NetworkEventLog>,
+    ) -> Result<Self, LookupError>
     where
         C: ConnectionManager,
         T: TransportConnector,
     {
         log::info!("connecting to CDSI endpoint");
-        let (connection, _info) = endpoint
+        let started = Instant::now();
+        let (connection, info) = endpoint
             .connect(auth, transport_connector, "cdsi".into())
             .inspect_err(|e| {
                 log::warn!("CDSI connection failed: {e}");
+                if let Some(network_events) = network_events {
+                    network_events.connect_failed(ServiceKind::Cdsi, e, started.elapsed());
+                }
             })
             .await?;
+        if let Some(network_events) = network_events {
+            network_events.connect_succeeded(
+                ServiceKind::Cdsi,
+                info.route_type.route_kind(),
+                started.elapsed(),
+            );
+        }
 
-        log::info!("successfully established attested connection to CDSI endpoint");
-        Ok(Self(connection))
+        let measurement = AttestedMeasurement::from(&endpoint.params.mr_enclave);
+        log::info!("successfully established attested connection to CDSI endpoint ({measurement})");
+        Ok(Self(connection, measurement))
     }
 
     pub async fn connect_with(
@@ -356,7 +430,10 @@ impl CdsiConnection {
         params: &EndpointParams<'_, Cdsi>,
         auth: Auth,
     ) -> Result<Self, LookupError> {
-        let (connection, _route_info) = ConnectState::connect_attested_ws(
+        let timer = crate::metrics::connect_attempted(ServiceKind::Cdsi);
+        let started = Instant::now();
+        let network_events = connect.read().await.network_events.clone();
+        let (connection, route_info) = ConnectState::connect_attested_ws(
             connect,
             route_provider,
             auth,
@@ -374,9 +451,27 @@ impl CdsiConnection {
             ),
             "cdsi".into(),
             params,
+            ServiceKind::Cdsi,
         )
-        .await?;
-        Ok(Self(connection))
+        .await
+        .inspect_err(|e| {
+            crate::metrics::connect_failed(ServiceKind::Cdsi, e);
+            network_events.connect_failed(ServiceKind::Cdsi, e, started.elapsed());
+        })?;
+        crate::metrics::connect_succeeded(ServiceKind::Cdsi, route_info.route_kind(), timer);
+        network_events.connect_succeeded(
+            ServiceKind::Cdsi,
+            route_info.route_kind(),
+            started.elapsed(),
+        );
+        let measurement = AttestedMeasurement::from(&params.mr_enclave);
+        log::info!("cdsi: attested {measurement} via {route_info}");
+        Ok(Self(connection, measurement))
+    }
+
+    /// The enclave measurement this connection was attested against.
+    pub fn attested_measurement(&self) -> &AttestedMeasurement {
+        &self.1
     }
 
     pub async fn send_request(
@@ -439,12 +534,136 @@ impl ClientResponseCollector {
         }
         Ok(response.try_into()?)
     }
+
+    /// Like [`Self::collect`], but hands out the results a batch at a time.
+    ///
+    /// See [`LookupResponseBatches::next_batch`].
+    pub fn into_batches(self) -> LookupResponseBatches {
+        let Self(connection) = self;
+        LookupResponseBatches {
+            connection: Some(connection),
+            sent_token_ack: false,
+            received_response: false,
+            pending: Vec::new(),
+            pending_offset: 0,
+            debug_permits_used: 0,
+        }
+    }
+}
+
+/// The results of a lookup, produced incrementally.
+///
+/// Created by [`ClientResponseCollector::into_batches`].
+#[cfg_attr(test, derive(Debug))]
+pub struct LookupResponseBatches {
+    /// `None` once the server has closed the connection.
+    connection: Option<CdsiConnection>,
+    sent_token_ack: bool,
+    received_response: bool,
+    /// Serialized records from the most recent response message.
+    pending: Vec<u8>,
+    /// How much of `pending` has already been handed out.
+    pending_offset: usize,
+    debug_permits_used: i32,
+}
+
+impl LookupResponseBatches {
+    /// Returns up to `max_entries` more records, or an empty list once all of
+    /// them have been returned.
+    ///
+    /// Records are only parsed as they are requested, and the next message
+    /// from the server isn't read until everything from the previous one has
+    /// been handed out.
+    pub async fn next_batch(
+        &mut self,
+        max_entries: NonZeroUsize,
+    ) -> Result<Vec<LookupResponseEntry>, LookupError> {
+        loop {
+            if self.pending_offset < self.pending.len() {
+                let end = self.pending.len().min(
+                    self.pending_offset + max_entries.get() * LookupResponseEntry::SERIALIZED_LEN,
+                );
+                let records = self.pending[self.pending_offset..end]
+                    .chunks(LookupResponseEntry::SERIALIZED_LEN)
+                    .flat_map(|record| {
+                        LookupResponseEntry::try_parse_from(
+                            record.try_into().expect("chunk size is correct"),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                self.pending_offset = end;
+                if self.pending_offset == self.pending.len() {
+                    self.pending = Vec::new();
+                    self.pending_offset = 0;
+                }
+                // Records without a valid E164 are skipped, which shouldn't
+                // be mistaken for the end of the results.
+                if !records.is_empty() {
+                    return Ok(records);
+                }
+                continue;
+            }
+
+            let Some(connection) = &mut self.connection else {
+                return Ok(vec![]);
+            };
+            if !self.sent_token_ack {
+                let token_ack = ClientRequest {
+                    token_ack: true,
+                    ..Default::default()
+                };
+                connection.0.send(token_ack).await?;
+                self.sent_token_ack = true;
+            }
+
+            match connection.0.receive_bytes().await? {
+                NextOrClose::Next(frame) => {
+                    let ClientResponse {
+                        e164_pni_aci_triples,
+                        token: _,
+                        debug_permits_used,
+                    } = ClientResponse::decode(frame.as_slice())?;
+                    if e164_pni_aci_triples.len() % LookupResponseEntry::SERIALIZED_LEN != 0 {
+                        return Err(LookupResponseParseError::InvalidNumberOfBytes {
+                            actual_length: e164_pni_aci_triples.len(),
+                        }
+                        .into());
+                    }
+                    if debug_permits_used != 0 {
+                        self.debug_permits_used = debug_permits_used;
+                    }
+                    self.received_response = true;
+                    self.pending = e164_pni_aci_triples;
+                    self.pending_offset = 0;
+                }
+                NextOrClose::Close(
+                    None
+                    | Some(CloseFrame {
+                        code: CloseCode::Normal,
+                        reason: _,
+                    }),
+                ) if self.received_response => {
+                    log::info!("finished CDSI lookup");
+                    self.connection = None;
+                }
+                NextOrClose::Close(close) => return Err(err_for_close(close)),
+            }
+        }
+    }
+
+    /// The number of debug permits used, as reported by the server so far.
+    ///
+    /// This is only final once [`Self::next_batch`] has returned an empty list.
+    pub fn debug_permits_used(&self) -> i32 {
+        self.debug_permits_used
+    }
 }
 
 /// For logging information about an initiated CDSI request.
 struct LookupRequestDebugInfo {
     new_e164s: usize,
     prev_e164s: usize,
+    discard_e164s: usize,
     acis_and_access_keys: usize,
     token: usize,
 }
@@ -454,6 +673,7 @@ impl std::fmt::Display for LookupRequestDebugInfo {
         f.debug_struct("LookupRequestDebugInfo")
             .field("new_e164s", &self.new_e164s)
             .field("prev_e164s", &self.prev_e164s)
+            .field("discard_e164s", &self.discard_e164s)
             .field("acis_and_access_keys", &self.acis_and_access_keys)
             .field("token", &self.token)
             .finish()
@@ -467,12 +687,14 @@ impl From<&LookupRequest> for LookupRequestDebugInfo {
         let LookupRequest {
             new_e164s,
             prev_e164s,
+            discard_e164s,
             acis_and_access_keys,
             token,
         } = value;
         Self {
             new_e164s: new_e164s.len(),
             prev_e164s: prev_e164s.len(),
+            discard_e164s: discard_e164s.len(),
             acis_and_access_keys: acis_and_access_keys.len(),
             token: token.len(),
         }
@@ -735,6 +957,10 @@ mod test {
         remote_idle_disconnect_timeout: Duration::from_secs(100),
     };
 
+    fn fake_measurement() -> AttestedMeasurement {
+        AttestedMeasurement::from(&crate::env::PROD.cdsi.params.mr_enclave)
+    }
+
     #[tokio::test]
     async fn lookup_success() {
         let (server, client) = fake_websocket().await;
@@ -758,6 +984,7 @@ mod test {
             )
             .await
             .expect("handshake failed"),
+            fake_measurement(),
         );
 
         let (token, collector) = cdsi_connection
@@ -781,6 +1008,91 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn lookup_success_in_batches() {
+        const NUMBER_OF_ENTRIES: u64 = 5;
+
+        let (server, client) = fake_websocket().await;
+
+        let mut fake_server = FakeServerState::default();
+        tokio::spawn(run_attested_server(
+            server,
+            attest::sgx_session::testutil::private_key(),
+            move |next_or_close| {
+                let frame = next_or_close.next_or(()).unwrap();
+                let mut output = fake_server.receive_frame(&frame);
+                if matches!(fake_server, FakeServerState::Finished) {
+                    // Start with an unparseable record to make sure it
+                    // doesn't end the results early.
+                    let mut triples = vec![0; LookupResponseEntry::SERIALIZED_LEN];
+                    triples.extend(
+                        (1..=NUMBER_OF_ENTRIES)
+                            .map(|i| LookupResponseEntry {
+                                e164: E164::new(NonZeroU64::new(i).unwrap()),
+                                aci: None,
+                                pni: None,
+                            })
+                            .collect_serialized(),
+                    );
+                    *output.message.as_mut().unwrap() = ClientResponse {
+                        debug_permits_used: 1,
+                        e164_pni_aci_triples: triples,
+                        ..Default::default()
+                    }
+                    .encode_to_vec();
+                }
+                output
+            },
+        ));
+
+        let cdsi_connection = CdsiConnection(
+            AttestedConnection::connect(
+                client,
+                FAKE_WS_CONFIG,
+                "test".into(),
+                |fake_attestation| {
+                    assert_eq!(fake_attestation, FAKE_ATTESTATION);
+                    attest::sgx_session::testutil::handshake_from_tests_data()
+                },
+            )
+            .await
+            .expect("handshake failed"),
+            fake_measurement(),
+        );
+
+        let (_token, collector) = cdsi_connection
+            .send_request(LookupRequest {
+                token: b"valid but ignored token".as_slice().into(),
+                ..Default::default()
+            })
+            .await
+            .expect("request accepted");
+
+        let mut batches = collector.into_batches();
+        let mut batch_sizes = vec![];
+        let mut e164s = vec![];
+        loop {
+            let batch = batches
+                .next_batch(nonzero!(2usize))
+                .await
+                .expect("successful request");
+            if batch.is_empty() {
+                break;
+            }
+            batch_sizes.push(batch.len());
+            e164s.extend(batch.into_iter().map(|entry| entry.e164));
+        }
+
+        assert_eq!(batch_sizes, [1, 2, 2]);
+        assert_eq!(
+            e164s,
+            (1..=NUMBER_OF_ENTRIES)
+                .map(|i| E164::new(NonZeroU64::new(i).unwrap()))
+                .collect_vec()
+        );
+        assert_eq!(batches.debug_permits_used(), 1);
+    }
+
     #[tokio::test]
     async fn large_request_split() {
         // Large requests should be split into multiple Noise packets, but those
@@ -845,6 +1157,7 @@ mod test {
             )
             .await
             .expect("handshake failed"),
+            fake_measurement(),
         );
 
         let large_number_of_e164s = (1..=LARGE_NUMBER_OF_ENTRIES)
@@ -854,6 +1167,7 @@ mod test {
             token: b"valid but ignored token".as_slice().into(),
             new_e164s: large_number_of_e164s.clone(),
             prev_e164s: large_number_of_e164s,
+            discard_e164s: vec![],
             acis_and_access_keys: (1..=LARGE_NUMBER_OF_ENTRIES)
                 .map(|i| {
                     let mut bytes = [0; 16];
@@ -923,6 +1237,7 @@ mod test {
             )
             .await
             .expect("handshake failed"),
+            fake_measurement(),
         );
 
         let response = cdsi_connection
@@ -974,6 +1289,7 @@ mod test {
             )
             .await
             .expect("handshake failed"),
+            fake_measurement(),
         );
 
         let (_token, collector) = cdsi_connection
@@ -1024,6 +1340,43 @@ mod test {
         )
     }
 
+    #[tokio::test]
+    async fn failed_connect_is_recorded_in_network_events() {
+        let h2_server = warp::get().then(|| async move {
+            warp::reply::with_status("(ignored body)", warp::http::StatusCode::FORBIDDEN)
+        });
+        let connector = InMemoryWarpConnector::new(h2_server);
+
+        let env = crate::env::PROD;
+        let endpoint_connection = EnclaveEndpointConnection::new(
+            &env.cdsi,
+            Duration::from_secs(10),
+            &ObservableEvent::default(),
+        );
+        let auth = Auth {
+            username: "username".to_string(),
+            password: "password".to_string(),
+        };
+        let network_events = NetworkEventLog::default();
+
+        let _ = CdsiConnection::connect_recording_events(
+            &endpoint_connection,
+            connector,
+            auth,
+            &network_events,
+        )
+        .await
+        .map(|_| ())
+        .expect_err("rejected by server");
+
+        let exported: serde_json::Value =
+            serde_json::from_str(&network_events.export_json()).expect("valid JSON");
+        let events = exported.as_array().expect("array");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["service"], "cdsi");
+        assert_eq!(events[0]["outcome"], "websocket");
+    }
+
     #[tokio::test]
     async fn websocket_invalid_token_close() {
         let (server, client) = fake_websocket().await;
@@ -1055,6 +1408,7 @@ mod test {
             )
             .await
             .expect("handshake failed"),
+            fake_measurement(),
         );
 
         let response = cdsi_connection
@@ -1066,4 +1420,93 @@ mod test {
 
         assert_matches!(response, Err(LookupError::InvalidToken));
     }
+
+    #[tokio::test]
+    async fn continuing_lookup_with_expired_token() {
+        const EXPIRED_TOKEN: &[u8] = b"expired token";
+        let e164 = |n: u64| E164::new(NonZeroU64::new(n).unwrap());
+
+        let (server, client) = fake_websocket().await;
+
+        let (received_request_tx, mut received_request_rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(run_attested_server(
+            server,
+            attest::sgx_session::testutil::private_key(),
+            move |next_or_close| {
+                let frame = next_or_close.next_or(()).unwrap();
+                received_request_tx
+                    .send(ClientRequest::decode(&*frame).expect("can decode"))
+                    .unwrap();
+                AttestedServerOutput::close(Some(CloseFrame {
+                    code: CloseCode::Bad(CdsiCloseCode::InvalidToken as u16),
+                    reason: "token expired".into(),
+                }))
+            },
+        ));
+
+        let cdsi_connection = CdsiConnection(
+            AttestedConnection::connect(
+                client,
+                FAKE_WS_CONFIG,
+                "test".into(),
+                |fake_attestation| {
+                    assert_eq!(fake_attestation, FAKE_ATTESTATION);
+                    attest::sgx_session::testutil::handshake_from_tests_data()
+                },
+            )
+            .await
+            .expect("handshake failed"),
+            fake_measurement(),
+        );
+
+        let response = cdsi_connection
+            .send_request(LookupRequest::continuing_from(
+                Token(EXPIRED_TOKEN.into()),
+                vec![e164(18005550101), e164(18005550102)],
+                vec![e164(18005550103)],
+                vec![e164(18005550102)],
+            ))
+            .await;
+
+        assert_matches!(response, Err(LookupError::InvalidToken));
+
+        let sent = received_request_rx.recv().await.expect("request was sent");
+        assert_eq!(sent.token, EXPIRED_TOKEN);
+        assert_eq!(
+            sent.prev_e164s,
+            [e164(18005550101), e164(18005550102)]
+                .into_iter()
+                .collect_serialized()
+        );
+        assert_eq!(
+            sent.new_e164s,
+            [e164(18005550103)].into_iter().collect_serialized()
+        );
+        assert_eq!(
+            sent.discard_e164s,
+            [e164(18005550102)].into_iter().collect_serialized()
+        );
+    }
+
+    #[test]
+    fn rate_limit_challenge_on_connect() {
+        let response = http::Response::builder()
+            .status(StatusCode::PRECONDITION_REQUIRED)
+            .body(Some(
+                br#"{"token":"abc","options":["pushChallenge"]}"#.to_vec(),
+            ))
+            .expect("valid");
+
+        let error = LookupError::from(crate::enclave::Error::WebSocketConnect(
+            WebSocketServiceConnectError::RejectedByServer {
+                response,
+                received_at: tokio::time::Instant::now(),
+            },
+        ));
+        assert_matches!(
+            error,
+            LookupError::RateLimitChallenge(RateLimitChallenge { token, options })
+                if token == "abc" && options == ["pushChallenge"]
+        );
+    }
 }
@@ -3,13 +3,18 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::cell::RefCell;
 use std::fmt::{Debug, Display};
+use std::io::{Read as _, Write as _};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
 use ::http::uri::PathAndQuery;
 use ::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
-use libsignal_net_infra::connection_manager::MultiRouteConnectionManager;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use libsignal_net_infra::connection_manager::{MultiRouteConnectionManager, RouteCooldowns};
 use libsignal_net_infra::dns::DnsResolver;
 use libsignal_net_infra::route::{
     Connector, HttpsTlsRoute, RouteProvider, RouteProviderExt, ThrottlingConnector, TransportRoute,
@@ -17,8 +22,7 @@ use libsignal_net_infra::route::{
     WebSocketRouteFragment,
 };
 use libsignal_net_infra::timeouts::ONE_ROUTE_CONNECTION_TIMEOUT;
-use libsignal_net_infra::utils::ObservableEvent;
-use libsignal_net_infra::ws::StreamWithResponseHeaders;
+use libsignal_net_infra::ws::{CountingWebSocket, StreamWithResponseHeaders};
 use libsignal_net_infra::{
     make_ws_config, AsHttpHeader, Connection, EndpointConnection, IpType, TransportInfo,
 };
@@ -26,17 +30,28 @@ use tokio_tungstenite::WebSocketStream;
 
 use crate::auth::Auth;
 use crate::connect_state::{
-    ConnectState, DefaultTransportConnector, RouteInfo, WebSocketTransportConnectorFactory,
+    ConnectProgress, ConnectState, DefaultTransportConnector, RouteInfo,
+    WebSocketTransportConnectorFactory,
 };
+use crate::connection_quality::RoundTripEstimator;
+use crate::data_usage::DataUsage;
 use crate::env::{add_user_agent_header, ConnectionConfig, UserAgent};
+use crate::metrics::ServiceKind;
 use crate::proto;
 
+// visibility::make isn't supported for modules, so we have to write it twice instead.
+#[cfg(feature = "test-util")]
+pub mod envelope;
+#[cfg(not(feature = "test-util"))]
+pub(crate) mod envelope;
 mod error;
-pub use error::{ConnectError, SendError};
+pub use error::{ConnectError, FailurePhase, SendError};
 
 pub mod fake;
 pub mod noise;
+pub mod retry;
 pub mod server_requests;
+pub mod state;
 pub mod ws;
 pub mod ws2;
 
@@ -63,6 +78,279 @@ pub struct Request {
     pub body: Option<Box<[u8]>>,
     pub headers: HeaderMap,
     pub path: PathAndQuery,
+    /// How urgently the request should be sent relative to others on the same
+    /// connection.
+    pub priority: Priority,
+    /// Identifies the request in debug logs.
+    ///
+    /// If this is `None`, an ID is generated when the request is sent.
+    pub correlation_id: Option<CorrelationId>,
+    /// Gzip-compresses the body when it's sent, if it's at least as large as
+    /// the connection's
+    /// [threshold](ChatConnection::with_gzip_request_body_threshold).
+    ///
+    /// If the server rejects the encoding, [`ChatConnection::send`] resends
+    /// the request once uncompressed.
+    pub gzip_body: bool,
+}
+
+/// A short random tag for a [`Request`], used to match up log lines and
+/// errors about it.
+///
+/// It is never derived from the request's contents.
+#[derive(Copy, Clone, Eq, Hash, PartialEq)]
+pub struct CorrelationId(u32);
+
+impl CorrelationId {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl Display for CorrelationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
+
+impl Debug for CorrelationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// Relative ordering of outgoing requests on a single connection.
+///
+/// Requests of the same priority are sent in the order they were submitted.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Priority {
+    /// A request that a user is waiting on, like a message send.
+    #[default]
+    Interactive,
+    /// A request that can yield to interactive ones, like periodic monitoring.
+    ///
+    /// Background requests are still sent eventually even if interactive
+    /// requests keep arriving.
+    Background,
+}
+
+impl Request {
+    /// Starts building a `GET` request with no headers and no body.
+    pub fn builder() -> RequestBuilder {
+        RequestBuilder::default()
+    }
+
+    /// Whether [`Self::gzip_body`] applies to this request's body.
+    fn should_gzip_body(&self, threshold: usize) -> bool {
+        self.gzip_body
+            && self
+                .body
+                .as_ref()
+                .is_some_and(|body| body.len() >= threshold)
+    }
+
+    /// Compresses the body and marks it with a gzip `Content-Encoding`.
+    fn encode_gzip_body(&mut self) {
+        let Some(body) = self.body.take() else {
+            return;
+        };
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(&body)
+            .expect("writing to a Vec doesn't fail");
+        let compressed = encoder.finish().expect("writing to a Vec doesn't fail");
+        self.body = Some(compressed.into_boxed_slice());
+        self.headers.insert(
+            ::http::header::CONTENT_ENCODING,
+            HeaderValue::from_static("gzip"),
+        );
+        self.gzip_body = false;
+    }
+}
+
+/// The smallest body [`Request::gzip_body`] compresses unless the connection
+/// is [configured](ChatConnection::with_gzip_request_body_threshold)
+/// otherwise, since compressing small bodies doesn't save enough to be worth
+/// it.
+pub const DEFAULT_GZIP_REQUEST_BODY_THRESHOLD: usize = 4 * 1024;
+
+/// The largest total size of request headers a [`RequestBuilder`] allows.
+///
+/// The chat server doesn't limit the headers of requests sent over the
+/// websocket. This mirrors the 8 KiB default request header size of Jetty, the
+/// HTTP server it runs on, which is the limit the same request would face over
+/// plain HTTP. Jetty doesn't limit the number of headers, so neither does the
+/// builder.
+///
+/// Each header is counted as its name and value plus the `": "` separator.
+pub const MAX_REQUEST_HEADERS_SIZE: usize = 8 * 1024;
+
+/// Error produced when constructing an invalid [`Request`] with a
+/// [`RequestBuilder`].
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum InvalidRequestError {
+    /// header names and values must contain only visible ASCII text
+    InvalidHeader,
+    /// request headers are larger than {MAX_REQUEST_HEADERS_SIZE} bytes
+    HeadersTooLarge,
+    /// request path is not a valid path and query
+    InvalidPath,
+    /// request path was not set
+    MissingPath,
+    /// request body could not be serialized as JSON
+    InvalidJsonBody,
+}
+
+/// Incrementally constructs a [`Request`], checking its contents along the way.
+///
+/// Constructing a `Request` directly is still allowed, but problems with it
+/// won't be detected until it is sent.
+#[derive(Debug, Default)]
+pub struct RequestBuilder {
+    method: ::http::Method,
+    path: Option<PathAndQuery>,
+    headers: HeaderMap,
+    headers_size: usize,
+    body: Option<Box<[u8]>>,
+    priority: Priority,
+    gzip_body: bool,
+}
+
+impl RequestBuilder {
+    pub fn method(self, method: ::http::Method) -> Self {
+        Self { method, ..self }
+    }
+
+    pub fn path(self, path: &str) -> Result<Self, InvalidRequestError> {
+        if !path.starts_with('/') {
+            return Err(InvalidRequestError::InvalidPath);
+        }
+        let path = PathAndQuery::try_from(path).map_err(|_| InvalidRequestError::InvalidPath)?;
+        Ok(Self {
+            path: Some(path),
+            ..self
+        })
+    }
+
+    /// Appends a header, checking that it can be sent to the server.
+    pub fn header<K, V>(mut self, name: K, value: V) -> Result<Self, InvalidRequestError>
+    where
+        HeaderName: TryFrom<K>,
+        HeaderValue: TryFrom<V>,
+    {
+        let name = HeaderName::try_from(name).map_err(|_| InvalidRequestError::InvalidHeader)?;
+        let value = HeaderValue::try_from(value).map_err(|_| InvalidRequestError::InvalidHeader)?;
+        // This is the same check that's done when the request is serialized.
+        if value.to_str().is_err() {
+            return Err(InvalidRequestError::InvalidHeader);
+        }
+
+        let headers_size = self.headers_size + header_size(&name, &value);
+        if headers_size > MAX_REQUEST_HEADERS_SIZE {
+            return Err(InvalidRequestError::HeadersTooLarge);
+        }
+
+        self.headers.append(name, value);
+        self.headers_size = headers_size;
+        Ok(self)
+    }
+
+    /// Sets the body to `value` serialized as JSON, along with a matching
+    /// `Content-Type` header.
+    pub fn json_body(self, value: &impl serde::Serialize) -> Result<Self, InvalidRequestError> {
+        let body = serialize_json(value).map_err(|_| InvalidRequestError::InvalidJsonBody)?;
+        let mut builder = Self {
+            body: Some(body),
+            ..self
+        };
+        let content_type = ::http::header::CONTENT_TYPE;
+        let previous_size: usize = builder
+            .headers
+            .get_all(&content_type)
+            .iter()
+            .map(|value| header_size(&content_type, value))
+            .sum();
+        builder.headers.remove(&content_type);
+        builder.headers_size -= previous_size;
+        builder.header(content_type, "application/json")
+    }
+
+    /// Asks the server to gzip-compress the response body.
+    ///
+    /// Compressed responses are transparently decompressed by
+    /// [`ChatConnection::send`], so this is only an optimization for requests
+    /// with potentially large responses.
+    pub fn accept_gzip(self) -> Result<Self, InvalidRequestError> {
+        self.header(::http::header::ACCEPT_ENCODING, "gzip")
+    }
+
+    /// Compresses large bodies when the request is sent; see
+    /// [`Request::gzip_body`].
+    pub fn gzip_body(self) -> Self {
+        Self {
+            gzip_body: true,
+            ..self
+        }
+    }
+
+    pub fn body(self, body: impl Into<Box<[u8]>>) -> Self {
+        Self {
+            body: Some(body.into()),
+            ..self
+        }
+    }
+
+    pub fn priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
+    }
+
+    pub fn build(self) -> Result<Request, InvalidRequestError> {
+        let Self {
+            method,
+            path,
+            headers,
+            headers_size: _,
+            body,
+            priority,
+            gzip_body,
+        } = self;
+        Ok(Request {
+            method,
+            body,
+            headers,
+            path: path.ok_or(InvalidRequestError::MissingPath)?,
+            priority,
+            correlation_id: None,
+            gzip_body,
+        })
+    }
+}
+
+fn header_size(name: &HeaderName, value: &HeaderValue) -> usize {
+    name.as_str().len() + ": ".len() + value.len()
+}
+
+/// Serializes `value` as JSON through a per-thread scratch buffer, so that
+/// only the exactly-sized result is allocated.
+fn serialize_json(value: &impl serde::Serialize) -> serde_json::Result<Box<[u8]>> {
+    thread_local! {
+        static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+    /// A buffer that grew past this for an unusually large body isn't kept.
+    const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+
+    BUFFER.with(|buffer| {
+        // Only reentrant serialization would find the buffer in use.
+        let Ok(mut buffer) = buffer.try_borrow_mut() else {
+            return serde_json::to_vec(value).map(Vec::into_boxed_slice);
+        };
+        buffer.clear();
+        let result = serde_json::to_writer(&mut *buffer, value).map(|()| Box::from(&buffer[..]));
+        if buffer.capacity() > MAX_RETAINED_CAPACITY {
+            *buffer = Vec::new();
+        }
+        result
+    })
 }
 
 #[derive(Clone, Debug)]
@@ -70,7 +358,9 @@ pub struct Request {
 pub struct Response {
     pub status: StatusCode,
     pub message: Option<String>,
-    pub body: Option<Box<[u8]>>,
+    /// The response body, shared rather than copied when the response is
+    /// cloned or a part of the body is split off.
+    pub body: Option<Bytes>,
     pub headers: HeaderMap,
 }
 
@@ -89,7 +379,7 @@ impl TryFrom<ResponseProto> for Response {
                 StatusCode::from_u16(status_code).map_err(|_| ResponseProtoInvalidError)
             })?;
         let message = response_proto.message;
-        let body = response_proto.body.map(|v| v.into_boxed_slice());
+        let body = response_proto.body.map(Bytes::from);
         let headers = response_proto.headers.into_iter().try_fold(
             HeaderMap::new(),
             |mut headers, header_string| {
@@ -119,6 +409,45 @@ impl From<ResponseProtoInvalidError> for SendError {
     }
 }
 
+/// The largest response body that will be produced by decompressing a
+/// gzip-encoded response.
+pub const MAX_DECOMPRESSED_RESPONSE_SIZE: usize = 8 * 1024 * 1024;
+
+impl Response {
+    /// Decompresses the body if the response has a gzip `Content-Encoding`.
+    ///
+    /// The server only compresses responses to requests that opted in with
+    /// [`RequestBuilder::accept_gzip`]. Other encodings are passed through
+    /// unchanged.
+    fn decode_content_encoding(mut self) -> Result<Self, SendError> {
+        let is_gzip = self
+            .headers
+            .get(::http::header::CONTENT_ENCODING)
+            .is_some_and(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"gzip"));
+        if !is_gzip {
+            return Ok(self);
+        }
+
+        if let Some(compressed) = self.body.take() {
+            let mut decompressed = Vec::new();
+            let limit = u64::try_from(MAX_DECOMPRESSED_RESPONSE_SIZE).expect("fits") + 1;
+            flate2::read::GzDecoder::new(&*compressed)
+                .take(limit)
+                .read_to_end(&mut decompressed)
+                .map_err(|_| SendError::IncomingDataInvalid)?;
+            if decompressed.len() > MAX_DECOMPRESSED_RESPONSE_SIZE {
+                log::warn!(
+                    "decompressed response body exceeds {MAX_DECOMPRESSED_RESPONSE_SIZE} bytes"
+                );
+                return Err(SendError::IncomingDataInvalid);
+            }
+            self.body = Some(decompressed.into());
+        }
+        self.headers.remove(::http::header::CONTENT_ENCODING);
+        Ok(self)
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, derive_more::From)]
 pub struct ReceiveStories(bool);
 
@@ -134,7 +463,7 @@ pub fn endpoint_connection(
     connection_config: &ConnectionConfig,
     user_agent: &UserAgent,
     include_fallback: bool,
-    network_change_event: &ObservableEvent,
+    route_cooldowns: &RouteCooldowns,
 ) -> EndpointConnection<MultiRouteConnectionManager> {
     let chat_endpoint = PathAndQuery::from_static(crate::env::constants::WEB_SOCKET_PATH);
     let chat_connection_params = if include_fallback {
@@ -148,10 +477,50 @@ pub fn endpoint_connection(
         chat_connection_params,
         ONE_ROUTE_CONNECTION_TIMEOUT,
         chat_ws_config,
-        network_change_event,
+        route_cooldowns,
     )
 }
 
+/// Converts an absolute deadline for a request into a timeout for
+/// [`ChatConnection::send`].
+///
+/// Fails with [`SendError::RequestTimedOut`] if the deadline has already
+/// passed, so that the request isn't sent at all.
+pub fn timeout_until(deadline: SystemTime, now: SystemTime) -> Result<Duration, SendError> {
+    deadline
+        .duration_since(now)
+        .ok()
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or(SendError::RequestTimedOut {
+            correlation_id: None,
+        })
+}
+
+/// Added to the start of the path of every request sent over a
+/// [`ChatConnection`].
+///
+/// For reaching the chat server through a gateway that serves it under a
+/// subpath, like `/signal/v1/...`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestPathPrefix(Arc<str>);
+
+impl RequestPathPrefix {
+    /// Checks that `prefix` starts with a `/`, doesn't end with one, and is
+    /// otherwise a valid path with no query.
+    pub fn new(prefix: &str) -> Result<Self, InvalidRequestError> {
+        if !prefix.starts_with('/') || prefix.ends_with('/') || prefix.contains(['?', '#']) {
+            return Err(InvalidRequestError::InvalidPath);
+        }
+        PathAndQuery::try_from(prefix).map_err(|_| InvalidRequestError::InvalidPath)?;
+        Ok(Self(prefix.into()))
+    }
+
+    fn apply(&self, path: &PathAndQuery) -> PathAndQuery {
+        PathAndQuery::try_from(format!("{}{path}", self.0))
+            .expect("prefix and path were both valid")
+    }
+}
+
 /// Information about an established connection.
 #[derive(Clone, Debug)]
 pub struct ConnectionInfo {
@@ -162,6 +531,39 @@ pub struct ConnectionInfo {
 pub struct ChatConnection {
     inner: self::ws2::Chat,
     connection_info: ConnectionInfo,
+    request_path_prefix: Option<RequestPathPrefix>,
+    /// See [`Self::with_gzip_request_body_threshold`].
+    gzip_request_body_threshold: usize,
+    /// Updated from the connection handshake and keepalive pings.
+    round_trip: Arc<RoundTripEstimator>,
+    data_usage: Arc<DataUsage>,
+    /// One permit per request that can be waiting for a response; see
+    /// [`MAX_OUTSTANDING_REQUESTS`].
+    outstanding_requests: Arc<tokio::sync::Semaphore>,
+}
+
+/// The most requests a [`ChatConnection`] will have waiting for responses at
+/// once.
+pub const MAX_OUTSTANDING_REQUESTS: usize = 32;
+
+/// A response to a request sent with [`ChatConnection::start_send`] that
+/// hasn't arrived yet.
+///
+/// Responses can be awaited in any order. Dropping this abandons the request
+/// and frees its place among the [`MAX_OUTSTANDING_REQUESTS`]; the response is
+/// discarded when it arrives. If the connection is lost first, this resolves to
+/// an error.
+pub struct PendingResponse(BoxFuture<'static, Result<Response, SendError>>);
+
+impl std::future::Future for PendingResponse {
+    type Output = Result<Response, SendError>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
 }
 
 type ChatTransportConnection =
@@ -177,6 +579,11 @@ pub struct PendingChatConnection<T = ChatTransportConnection> {
     ws_config: ws2::Config,
     route_info: RouteInfo,
     log_tag: Arc<str>,
+    request_path_prefix: Option<RequestPathPrefix>,
+    gzip_request_body_threshold: usize,
+    /// Starts out with the handshake's round trips, if they were reported.
+    round_trip: Arc<RoundTripEstimator>,
+    data_usage: Arc<DataUsage>,
 }
 
 #[cfg_attr(test, derive(Clone))]
@@ -204,7 +611,44 @@ impl ChatConnection {
             Connection = ChatTransportConnection,
         >,
     {
-        Self::start_connect_with_transport(
+        Self::start_connect_with_progress(
+            connect,
+            resolver,
+            http_route_provider,
+            confirmation_header_name,
+            user_agent,
+            ws_config,
+            auth,
+            log_tag,
+            |_| {},
+        )
+        .await
+    }
+
+    /// Like [`ChatConnection::start_connect_with`], but reports the stages of
+    /// establishing the connection to `on_progress`.
+    ///
+    /// See [`ConnectState::connect_ws_reporting_progress`] for when stages are
+    /// reported.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start_connect_with_progress<TC>(
+        connect: &tokio::sync::RwLock<ConnectState<TC>>,
+        resolver: &DnsResolver,
+        http_route_provider: impl RouteProvider<Route = UnresolvedHttpsServiceRoute>,
+        confirmation_header_name: Option<HeaderName>,
+        user_agent: &UserAgent,
+        ws_config: self::ws2::Config,
+        auth: Option<AuthenticatedChatHeaders>,
+        log_tag: &str,
+        on_progress: impl FnMut(ConnectProgress) + Send,
+    ) -> Result<PendingChatConnection, ConnectError>
+    where
+        TC: WebSocketTransportConnectorFactory<
+            UsePreconnect<TransportRoute>,
+            Connection = ChatTransportConnection,
+        >,
+    {
+        Self::start_connect_inner(
             connect,
             resolver,
             http_route_provider,
@@ -213,6 +657,7 @@ impl ChatConnection {
             ws_config,
             auth,
             log_tag,
+            on_progress,
         )
         .await
     }
@@ -231,6 +676,41 @@ impl ChatConnection {
     where
         TC: WebSocketTransportConnectorFactory<UsePreconnect<TransportRoute>>,
     {
+        Self::start_connect_inner(
+            connect,
+            resolver,
+            http_route_provider,
+            confirmation_header_name,
+            user_agent,
+            ws_config,
+            auth,
+            log_tag,
+            |_| {},
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn start_connect_inner<TC>(
+        connect: &tokio::sync::RwLock<ConnectState<TC>>,
+        resolver: &DnsResolver,
+        http_route_provider: impl RouteProvider<Route = UnresolvedHttpsServiceRoute>,
+        confirmation_header_name: Option<HeaderName>,
+        user_agent: &UserAgent,
+        ws_config: self::ws2::Config,
+        auth: Option<AuthenticatedChatHeaders>,
+        log_tag: &str,
+        on_progress: impl FnMut(ConnectProgress) + Send,
+    ) -> Result<PendingChatConnection<TC::Connection>, ConnectError>
+    where
+        TC: WebSocketTransportConnectorFactory<UsePreconnect<TransportRoute>>,
+    {
+        let timer = crate::metrics::connect_attempted(ServiceKind::Chat);
+        let started = Instant::now();
+        let (data_usage, network_events) = {
+            let connect = connect.read().await;
+            (connect.data_usage.clone(), connect.network_events.clone())
+        };
         let should_preconnect = auth.is_some();
         let headers = auth
             .into_iter()
@@ -259,21 +739,46 @@ impl ChatConnection {
         });
 
         let log_tag: Arc<str> = log_tag.into();
-        let (connection, route_info) = ConnectState::connect_ws(
-            connect,
-            ws_routes,
-            // If we create multiple authenticated chat websocket connections at
-            // the same time, the server will terminate earlier ones as later
-            // ones complete. Throttling at the websocket connection level
-            // lets us get connection parallelism at the transport level (which
-            // is useful) while limiting us to one fully established connection
-            // at a time.
-            ThrottlingConnector::new(crate::infra::ws::Stateless, 1),
-            resolver,
-            confirmation_header_name.as_ref(),
-            log_tag.clone(),
-        )
-        .await?;
+        let upgrade_attempted = AtomicBool::new(false);
+        let (connection, route_info, handshake_duration) =
+            ConnectState::connect_ws_reporting_progress(
+                connect,
+                ws_routes,
+                NoteUpgradeAttempts {
+                    // If we create multiple authenticated chat websocket connections at
+                    // the same time, the server will terminate earlier ones as later
+                    // ones complete. Throttling at the websocket connection level
+                    // lets us get connection parallelism at the transport level (which
+                    // is useful) while limiting us to one fully established connection
+                    // at a time.
+                    inner: ThrottlingConnector::new(crate::infra::ws::Stateless, 1),
+                    attempted: &upgrade_attempted,
+                },
+                resolver,
+                confirmation_header_name.as_ref(),
+                log_tag.clone(),
+                ServiceKind::Chat,
+                on_progress,
+            )
+            .await
+            .map_err(|e| {
+                let furthest_phase = if upgrade_attempted.load(Ordering::Relaxed) {
+                    FailurePhase::WebSocketUpgrade
+                } else {
+                    FailurePhase::Transport
+                };
+                ConnectError::from_route_error(e, furthest_phase)
+            })
+            .inspect_err(|e| {
+                crate::metrics::connect_failed(ServiceKind::Chat, e);
+                network_events.connect_failed(ServiceKind::Chat, e, started.elapsed());
+            })?;
+        crate::metrics::connect_succeeded(ServiceKind::Chat, route_info.route_kind(), timer);
+        network_events.connect_succeeded(
+            ServiceKind::Chat,
+            route_info.route_kind(),
+            started.elapsed(),
+        );
 
         // It's okay to discard the ThrottlingConnection layer here, because no other routes are
         // still connecting.
@@ -282,12 +787,19 @@ impl ChatConnection {
             response_headers,
         } = connection.into_inner();
 
+        let round_trip = Arc::new(RoundTripEstimator::new());
+        round_trip.record_handshake(handshake_duration);
+
         Ok(PendingChatConnection {
             connection: stream,
             connect_response_headers: response_headers,
             route_info,
             ws_config,
             log_tag,
+            request_path_prefix: None,
+            gzip_request_body_threshold: DEFAULT_GZIP_REQUEST_BODY_THRESHOLD,
+            round_trip,
+            data_usage,
         })
     }
 
@@ -302,11 +814,23 @@ impl ChatConnection {
             ws_config,
             route_info,
             log_tag,
+            request_path_prefix,
+            gzip_request_body_threshold,
+            round_trip,
+            data_usage,
         } = pending;
+        let transport_info = connection.transport_info();
+        let connection =
+            CountingWebSocket::new(connection, data_usage.counter(ServiceKind::Chat).clone());
         Self {
+            request_path_prefix,
+            gzip_request_body_threshold,
+            round_trip: round_trip.clone(),
+            data_usage,
+            outstanding_requests: Arc::new(tokio::sync::Semaphore::new(MAX_OUTSTANDING_REQUESTS)),
             connection_info: ConnectionInfo {
                 route_info,
-                transport_info: connection.transport_info(),
+                transport_info,
             },
             inner: ws2::Chat::new(
                 tokio_runtime,
@@ -315,27 +839,190 @@ impl ChatConnection {
                 ws_config,
                 log_tag,
                 listener,
+                round_trip,
             ),
         }
     }
 
-    pub async fn send(&self, msg: Request, timeout: Duration) -> Result<Response, SendError> {
-        let send_result = tokio::time::timeout(timeout, self.inner.send(msg))
-            .await
-            .map_err(|_elapsed| SendError::RequestTimedOut)?;
-        Ok(send_result?)
+    /// Sends every request with `prefix` added to the start of its path.
+    pub fn with_request_path_prefix(self, prefix: Option<RequestPathPrefix>) -> Self {
+        Self {
+            request_path_prefix: prefix,
+            ..self
+        }
+    }
+
+    /// Compresses the bodies of requests that set [`Request::gzip_body`] only
+    /// if they're at least `threshold` bytes.
+    ///
+    /// Defaults to [`DEFAULT_GZIP_REQUEST_BODY_THRESHOLD`].
+    pub fn with_gzip_request_body_threshold(self, threshold: usize) -> Self {
+        Self {
+            gzip_request_body_threshold: threshold,
+            ..self
+        }
+    }
+
+    /// Sends a request and waits for its response.
+    ///
+    /// Unlike [`Self::start_send`], this resends a request with a compressed
+    /// body uncompressed if the server rejects the encoding, within the same
+    /// `timeout`.
+    pub async fn send(&self, mut msg: Request, timeout: Duration) -> Result<Response, SendError> {
+        if !msg.should_gzip_body(self.gzip_request_body_threshold) {
+            return self.start_send(msg, timeout).await?.await;
+        }
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        let correlation_id = *msg.correlation_id.get_or_insert_with(CorrelationId::random);
+        let uncompressed = Request {
+            gzip_body: false,
+            ..msg.clone()
+        };
+        let response = self.start_send(msg, timeout).await?.await?;
+        if response.status != StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            return Ok(response);
+        }
+        log::info!("[{correlation_id}] server rejected gzip request body; resending uncompressed");
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        self.start_send(uncompressed, remaining).await?.await
+    }
+
+    /// Sends a request without waiting for its response, so that several
+    /// requests can be outstanding on the connection at once.
+    ///
+    /// If [`MAX_OUTSTANDING_REQUESTS`] responses are already pending, this
+    /// first waits for one of them to arrive or be abandoned. `timeout` covers
+    /// the whole exchange, including that wait; if it runs out after the
+    /// request is sent, the [`PendingResponse`] resolves to
+    /// [`SendError::RequestTimedOut`].
+    pub async fn start_send(
+        &self,
+        mut msg: Request,
+        timeout: Duration,
+    ) -> Result<PendingResponse, SendError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let correlation_id = *msg.correlation_id.get_or_insert_with(CorrelationId::random);
+        let is_key_transparency = msg
+            .path
+            .path()
+            .starts_with(crate::keytrans::KEY_TRANSPARENCY_PATH_PREFIX);
+        if let Some(prefix) = &self.request_path_prefix {
+            msg.path = prefix.apply(&msg.path);
+        }
+        if msg.should_gzip_body(self.gzip_request_body_threshold) {
+            msg.encode_gzip_body();
+        }
+        let timer = crate::metrics::Timer::start();
+        let bytes_sent = msg.body.as_ref().map_or(0, |body| body.len());
+        log::debug!("[{correlation_id}] sending request");
+        let (permit, pending) = tokio::time::timeout_at(deadline, async {
+            let permit = self
+                .outstanding_requests
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+            let pending = self.inner.start_send(msg).await?;
+            Ok::<_, SendError>((permit, pending))
+        })
+        .await
+        .map_err(|_elapsed| SendError::RequestTimedOut {
+            correlation_id: Some(correlation_id),
+        })
+        .and_then(|start_result| start_result)
+        .inspect_err(|e| {
+            log::debug!("[{correlation_id}] request failed: {e}");
+            crate::metrics::request_failed(ServiceKind::Chat, e)
+        })?;
+
+        let data_usage = self.data_usage.clone();
+        Ok(PendingResponse(Box::pin(async move {
+            // Holding the permit until the response arrives or this future is
+            // dropped is what bounds the number of outstanding requests.
+            let _permit = permit;
+            let response = tokio::time::timeout_at(deadline, pending)
+                .await
+                .map_err(|_elapsed| SendError::RequestTimedOut {
+                    correlation_id: Some(correlation_id),
+                })
+                .and_then(|send_result| send_result.map_err(SendError::from))
+                .inspect_err(|e| {
+                    log::debug!("[{correlation_id}] request failed: {e}");
+                    crate::metrics::request_failed(ServiceKind::Chat, e)
+                })?;
+            log::debug!("[{correlation_id}] received response: {}", response.status);
+            let bytes_received = response.body.as_ref().map_or(0, |body| body.len());
+            crate::metrics::request_finished(ServiceKind::Chat, timer, bytes_sent, bytes_received);
+            if is_key_transparency {
+                data_usage.record_key_transparency(bytes_sent, bytes_received);
+            }
+            response.decode_content_encoding()
+        })))
     }
 
     pub async fn disconnect(&self) {
         self.inner.disconnect().await
     }
 
+    /// The connection's smoothed round-trip time, measured from its handshake
+    /// and keepalive pings.
+    pub fn round_trip_estimator(&self) -> &RoundTripEstimator {
+        &self.round_trip
+    }
+
     pub fn connection_info(&self) -> &ConnectionInfo {
         &self.connection_info
     }
 }
 
+/// Wraps a websocket [`Connector`] to note whether any connection attempt got
+/// as far as starting the websocket upgrade.
+struct NoteUpgradeAttempts<'a, C> {
+    inner: C,
+    attempted: &'a AtomicBool,
+}
+
+impl<R, Inner, C: Connector<R, Inner>> Connector<R, Inner> for NoteUpgradeAttempts<'_, C> {
+    type Connection = C::Connection;
+    type Error = C::Error;
+
+    fn connect_over(
+        &self,
+        over: Inner,
+        route: R,
+        log_tag: Arc<str>,
+    ) -> impl std::future::Future<Output = Result<Self::Connection, Self::Error>> + Send {
+        self.attempted.store(true, Ordering::Relaxed);
+        self.inner.connect_over(over, route, log_tag)
+    }
+}
+
 impl PendingChatConnection {
+    /// See [`ChatConnection::with_request_path_prefix`].
+    pub fn with_request_path_prefix(self, prefix: Option<RequestPathPrefix>) -> Self {
+        Self {
+            request_path_prefix: prefix,
+            ..self
+        }
+    }
+
+    /// See [`ChatConnection::with_gzip_request_body_threshold`].
+    pub fn with_gzip_request_body_threshold(self, threshold: usize) -> Self {
+        Self {
+            gzip_request_body_threshold: threshold,
+            ..self
+        }
+    }
+
+    /// The estimator the connection will keep updating once it's
+    /// [finished](ChatConnection::finish_connect).
+    ///
+    /// It already includes the connection's handshake, if that was measured.
+    pub fn round_trip_estimator(&self) -> &Arc<RoundTripEstimator> {
+        &self.round_trip
+    }
+
     pub fn connection_info(&self) -> ConnectionInfo {
         ConnectionInfo {
             route_info: self.route_info.clone(),
@@ -373,6 +1060,7 @@ pub mod test_support {
     use std::time::Duration;
 
     use libsignal_net_infra::dns::DnsResolver;
+    use libsignal_net_infra::utils::ObservableEvent;
     use libsignal_net_infra::EnableDomainFronting;
 
     use super::*;
@@ -402,7 +1090,7 @@ pub mod test_support {
 
         let connect = ConnectState::new_with_transport_connector(
             SUGGESTED_CONNECT_CONFIG,
-            PreconnectingFactory::new(DefaultConnectorFactory, Duration::ZERO),
+            PreconnectingFactory::new(DefaultConnectorFactory::default(), Duration::ZERO),
         );
         let user_agent = UserAgent::with_libsignal_version("test_simple_chat_connection");
 
@@ -462,6 +1150,235 @@ pub(crate) mod test {
     use super::*;
     use crate::connect_state::SUGGESTED_CONNECT_CONFIG;
 
+    #[test_case(1500 => matches Ok(d) if d == Duration::from_millis(500); "in the future")]
+    #[test_case(1000 => matches Err(SendError::RequestTimedOut { .. }); "now")]
+    #[test_case(500 => matches Err(SendError::RequestTimedOut { .. }); "in the past")]
+    fn timeout_until_deadline(deadline_millis: u64) -> Result<Duration, SendError> {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_millis(1000);
+        timeout_until(
+            SystemTime::UNIX_EPOCH + Duration::from_millis(deadline_millis),
+            now,
+        )
+    }
+
+    #[test]
+    fn request_builder_produces_request() {
+        let request = Request::builder()
+            .method(http::Method::PUT)
+            .path("/v1/thing?query=yes")
+            .and_then(|b| b.header("x-custom", "value"))
+            .and_then(|b| b.json_body(&serde_json::json!({"key": 1})))
+            .expect("valid")
+            .priority(Priority::Background)
+            .build()
+            .expect("valid");
+
+        assert_eq!(request.method, http::Method::PUT);
+        assert_eq!(request.path.as_str(), "/v1/thing?query=yes");
+        assert_eq!(request.headers["x-custom"], "value");
+        assert_eq!(
+            request.headers[http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        assert_eq!(request.body.as_deref(), Some(&br#"{"key":1}"#[..]));
+        assert_eq!(request.priority, Priority::Background);
+    }
+
+    #[test]
+    fn request_builder_json_body_replaces_content_type() {
+        let request = Request::builder()
+            .path("/")
+            .and_then(|b| b.header(http::header::CONTENT_TYPE, "text/plain"))
+            .and_then(|b| b.json_body(&"body"))
+            .expect("valid")
+            .build()
+            .expect("valid");
+
+        assert_eq!(
+            request
+                .headers
+                .get_all(http::header::CONTENT_TYPE)
+                .iter()
+                .map(|value| value.to_str().expect("ASCII"))
+                .collect_vec(),
+            ["application/json"]
+        );
+    }
+
+    #[test]
+    fn request_builder_json_body_does_not_leak_previous_body() {
+        let body = |value: &serde_json::Value| {
+            Request::builder()
+                .path("/")
+                .and_then(|b| b.json_body(value))
+                .expect("valid")
+                .build()
+                .expect("valid")
+                .body
+        };
+
+        assert_eq!(
+            body(&serde_json::json!({"long": "x".repeat(100)})).map(|b| b.len()),
+            Some(r#"{"long":""}"#.len() + 100)
+        );
+        assert_eq!(
+            body(&serde_json::json!({"key": 1})).as_deref(),
+            Some(&br#"{"key":1}"#[..])
+        );
+    }
+
+    #[test_case("x-bad name", "value"; "invalid name")]
+    #[test_case("x-name", "caf\u{e9}"; "non-ASCII value")]
+    #[test_case("x-name", "line\nbreak"; "control character")]
+    fn request_builder_rejects_invalid_header(name: &str, value: &str) {
+        assert_matches!(
+            Request::builder().header(name, value),
+            Err(InvalidRequestError::InvalidHeader)
+        );
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("no-leading-slash"; "relative")]
+    #[test_case("/has space"; "space")]
+    fn request_builder_rejects_invalid_path(path: &str) {
+        assert_matches!(
+            Request::builder().path(path),
+            Err(InvalidRequestError::InvalidPath)
+        );
+    }
+
+    #[test]
+    fn request_builder_requires_path() {
+        assert_matches!(
+            Request::builder().build(),
+            Err(InvalidRequestError::MissingPath)
+        );
+    }
+
+    #[test]
+    fn request_builder_limits_header_size() {
+        let name = "x-large";
+        let value = "v".repeat(MAX_REQUEST_HEADERS_SIZE - name.len() - ": ".len());
+        let builder = Request::builder()
+            .header(name, value.as_str())
+            .expect("exactly at limit");
+        assert_matches!(
+            builder.header("x", ""),
+            Err(InvalidRequestError::HeadersTooLarge)
+        );
+    }
+
+    #[test_case("/signal" => matches Ok(_); "single segment")]
+    #[test_case("/signal/api" => matches Ok(_); "multiple segments")]
+    #[test_case("" => matches Err(InvalidRequestError::InvalidPath); "empty")]
+    #[test_case("/" => matches Err(InvalidRequestError::InvalidPath); "root")]
+    #[test_case("signal" => matches Err(InvalidRequestError::InvalidPath); "relative")]
+    #[test_case("/signal/" => matches Err(InvalidRequestError::InvalidPath); "trailing slash")]
+    #[test_case("/signal?x=1" => matches Err(InvalidRequestError::InvalidPath); "query")]
+    #[test_case("/has space" => matches Err(InvalidRequestError::InvalidPath); "space")]
+    fn request_path_prefix_validation(
+        prefix: &str,
+    ) -> Result<RequestPathPrefix, InvalidRequestError> {
+        RequestPathPrefix::new(prefix)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn request_path_prefix_is_applied_to_every_request() {
+        let server = crate::chat::fake::server::FakeChatServer::new();
+        let chat = server
+            .connect(Box::new(|_| {}))
+            .with_request_path_prefix(Some(RequestPathPrefix::new("/signal").expect("valid")));
+
+        for path in ["/v1/thing", "/v1/thing?query=yes"] {
+            let request = Request::builder()
+                .method(http::Method::GET)
+                .path(path)
+                .expect("valid")
+                .build()
+                .expect("valid");
+            chat.send(request, Duration::from_secs(5))
+                .await
+                .expect("response");
+        }
+
+        let paths = server
+            .received_requests()
+            .into_iter()
+            .map(|request| request.path().to_owned())
+            .collect_vec();
+        assert_eq!(paths, ["/signal/v1/thing", "/signal/v1/thing?query=yes"]);
+    }
+
+    fn gzip_response(body: Option<&[u8]>) -> Response {
+        Response {
+            status: StatusCode::OK,
+            message: None,
+            body: body.map(Bytes::copy_from_slice),
+            headers: HeaderMap::from_iter([(
+                http::header::CONTENT_ENCODING,
+                HeaderValue::from_static("gzip"),
+            )]),
+        }
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Write as _;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).expect("in-memory");
+        encoder.finish().expect("in-memory")
+    }
+
+    #[test]
+    fn gzip_response_is_decompressed() {
+        let response = gzip_response(Some(&gzip(b"hello, world")))
+            .decode_content_encoding()
+            .expect("valid");
+        assert_eq!(response.body.as_deref(), Some(&b"hello, world"[..]));
+        assert_eq!(response.headers.get(http::header::CONTENT_ENCODING), None);
+    }
+
+    #[test]
+    fn unencoded_response_is_unchanged() {
+        let response = Response {
+            headers: HeaderMap::new(),
+            ..gzip_response(Some(b"plain"))
+        };
+        assert_eq!(
+            response.clone().decode_content_encoding().expect("valid"),
+            response
+        );
+    }
+
+    #[test]
+    fn corrupt_gzip_response_is_invalid() {
+        let mut compressed = gzip(b"hello, world");
+        let last = compressed.len() - 1;
+        compressed[last] ^= 0xff;
+        assert_matches!(
+            gzip_response(Some(&compressed)).decode_content_encoding(),
+            Err(SendError::IncomingDataInvalid)
+        );
+        assert_matches!(
+            gzip_response(Some(b"not gzip at all")).decode_content_encoding(),
+            Err(SendError::IncomingDataInvalid)
+        );
+    }
+
+    #[test]
+    fn oversized_gzip_response_is_invalid() {
+        let at_limit = gzip(&vec![0; MAX_DECOMPRESSED_RESPONSE_SIZE]);
+        assert_matches!(
+            gzip_response(Some(&at_limit)).decode_content_encoding(),
+            Ok(_)
+        );
+
+        let over_limit = gzip(&vec![0; MAX_DECOMPRESSED_RESPONSE_SIZE + 1]);
+        assert_matches!(
+            gzip_response(Some(&over_limit)).decode_content_encoding(),
+            Err(SendError::IncomingDataInvalid)
+        );
+    }
+
     #[test]
     fn proto_into_response_works_with_valid_data() {
         let expected_body = b"content";
@@ -606,12 +1523,12 @@ pub(crate) mod test {
     // It's easier to use this with test_case in string form.
     const CONFIRMATION_HEADER: &str = "x-really-signal";
 
-    #[test_case(403, &[] => matches ConnectError::AllAttemptsFailed)]
+    #[test_case(403, &[] => matches ConnectError::AllAttemptsFailed { furthest_phase: FailurePhase::WebSocketUpgrade })]
     #[test_case(403, &[(CONFIRMATION_HEADER, "1")] => matches ConnectError::DeviceDeregistered)]
-    #[test_case(499, &[(CONFIRMATION_HEADER, "1")] => matches ConnectError::AppExpired)]
-    #[test_case(429, &[(CONFIRMATION_HEADER, "1"), ("retry-after", "20")] => matches ConnectError::RetryLater(RetryLater { retry_after_seconds: 20 }))]
-    #[test_case(500, &[(CONFIRMATION_HEADER, "1"), ("retry-after", "20")] => matches ConnectError::RetryLater(RetryLater { retry_after_seconds: 20 }))]
-    #[test_case(429, &[("retry-after", "20")] => matches ConnectError::AllAttemptsFailed)]
+    #[test_case(499, &[(CONFIRMATION_HEADER, "1")] => matches ConnectError::AppExpired { minimum_version: None, upgrade_url: None })]
+    #[test_case(429, &[(CONFIRMATION_HEADER, "1"), ("retry-after", "20")] => matches ConnectError::RetryLater { retry_later: RetryLater { retry_after_seconds: 20 }, .. })]
+    #[test_case(500, &[(CONFIRMATION_HEADER, "1"), ("retry-after", "20")] => matches ConnectError::RetryLater { retry_later: RetryLater { retry_after_seconds: 20 }, .. })]
+    #[test_case(429, &[("retry-after", "20")] => matches ConnectError::AllAttemptsFailed { furthest_phase: FailurePhase::WebSocketUpgrade })]
     #[test_log::test(tokio::test(start_paused = true))]
     async fn html_status_tests(
         status: u16,
@@ -781,7 +1698,12 @@ pub(crate) mod test {
         .await
         .expect_err("should fail to connect");
 
-        assert_matches!(err, ConnectError::AllAttemptsFailed);
+        assert_matches!(
+            err,
+            ConnectError::AllAttemptsFailed {
+                furthest_phase: FailurePhase::WebSocketUpgrade
+            }
+        );
         // 1 preconnect that subsequently fails, 1 IPv4 follow-up connection that also fails.
         assert_eq!(number_of_times_called.load(atomic::Ordering::SeqCst), 2);
 
@@ -803,7 +1725,290 @@ pub(crate) mod test {
         .await
         .expect_err("should fail to connect");
 
-        assert_matches!(err, ConnectError::AllAttemptsFailed);
+        assert_matches!(
+            err,
+            ConnectError::AllAttemptsFailed {
+                furthest_phase: FailurePhase::WebSocketUpgrade
+            }
+        );
         assert_eq!(number_of_times_called.load(atomic::Ordering::SeqCst), 4);
     }
+
+    fn fake_connection() -> (ChatConnection, fake::FakeChatRemote) {
+        ChatConnection::new_fake(tokio::runtime::Handle::current(), Box::new(|_| {}), [])
+    }
+
+    fn get_request(path: &str) -> Request {
+        Request::builder()
+            .path(path)
+            .expect("valid path")
+            .build()
+            .expect("valid request")
+    }
+
+    fn respond_with_path(remote: &fake::FakeChatRemote, request: &RequestProto) {
+        remote
+            .send_response(ResponseProto {
+                id: request.id,
+                status: Some(200),
+                message: None,
+                headers: vec![],
+                body: request.path.clone().map(String::into_bytes),
+            })
+            .expect("still connected");
+    }
+
+    #[tokio::test]
+    async fn pipelined_responses_can_arrive_out_of_order() {
+        const TIMEOUT: Duration = Duration::from_secs(10);
+        let (chat, remote) = fake_connection();
+
+        let first = chat
+            .start_send(get_request("/first"), TIMEOUT)
+            .await
+            .expect("sent");
+        let second = chat
+            .start_send(get_request("/second"), TIMEOUT)
+            .await
+            .expect("sent");
+
+        let mut requests = vec![];
+        for _ in 0..2 {
+            requests.push(
+                remote
+                    .receive_request()
+                    .await
+                    .expect("valid request")
+                    .expect("not closed"),
+            );
+        }
+        assert_ne!(requests[0].id, requests[1].id);
+        for request in requests.iter().rev() {
+            respond_with_path(&remote, request);
+        }
+
+        let second = second.await.expect("response");
+        let first = first.await.expect("response");
+        assert_eq!(first.body.as_deref(), Some(b"/first".as_slice()));
+        assert_eq!(second.body.as_deref(), Some(b"/second".as_slice()));
+    }
+
+    fn post_with_gzip_body(body: Vec<u8>) -> Request {
+        Request::builder()
+            .method(::http::Method::POST)
+            .path("/")
+            .expect("valid path")
+            .body(body)
+            .gzip_body()
+            .build()
+            .expect("valid request")
+    }
+
+    fn respond_with_status(
+        remote: &fake::FakeChatRemote,
+        request: &RequestProto,
+        status: StatusCode,
+    ) {
+        remote
+            .send_response(ResponseProto {
+                id: request.id,
+                status: Some(status.as_u16().into()),
+                message: None,
+                headers: vec![],
+                body: None,
+            })
+            .expect("still connected");
+    }
+
+    #[test_case(None, DEFAULT_GZIP_REQUEST_BODY_THRESHOLD - 1, false; "small body")]
+    #[test_case(None, DEFAULT_GZIP_REQUEST_BODY_THRESHOLD, true; "large body")]
+    #[test_case(Some(16), 15, false; "below configured threshold")]
+    #[test_case(Some(16), 16, true; "at configured threshold")]
+    #[tokio::test]
+    async fn gzip_body_is_compressed_above_threshold(
+        threshold: Option<usize>,
+        body_len: usize,
+        expect_compressed: bool,
+    ) {
+        const TIMEOUT: Duration = Duration::from_secs(10);
+        let (mut chat, remote) = fake_connection();
+        if let Some(threshold) = threshold {
+            chat = chat.with_gzip_request_body_threshold(threshold);
+        }
+        let body = vec![b'x'; body_len];
+
+        let _pending = chat
+            .start_send(post_with_gzip_body(body.clone()), TIMEOUT)
+            .await
+            .expect("sent");
+        let request = remote
+            .receive_request()
+            .await
+            .expect("valid request")
+            .expect("not closed");
+
+        let is_compressed = request
+            .headers
+            .contains(&"content-encoding: gzip".to_owned());
+        assert_eq!(is_compressed, expect_compressed);
+        let sent_body = request.body.expect("has body");
+        if expect_compressed {
+            let mut decompressed = vec![];
+            flate2::read::GzDecoder::new(&*sent_body)
+                .read_to_end(&mut decompressed)
+                .expect("valid gzip");
+            assert_eq!(decompressed, body);
+        } else {
+            assert_eq!(sent_body, body);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_gzip_body_is_resent_uncompressed() {
+        const TIMEOUT: Duration = Duration::from_secs(10);
+        let (chat, remote) = fake_connection();
+        let body = vec![b'x'; DEFAULT_GZIP_REQUEST_BODY_THRESHOLD];
+
+        let send = tokio::spawn(async move { chat.send(post_with_gzip_body(body), TIMEOUT).await });
+
+        let compressed = remote
+            .receive_request()
+            .await
+            .expect("valid request")
+            .expect("not closed");
+        assert!(compressed
+            .headers
+            .contains(&"content-encoding: gzip".to_owned()));
+        respond_with_status(&remote, &compressed, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let uncompressed = remote
+            .receive_request()
+            .await
+            .expect("valid request")
+            .expect("not closed");
+        assert!(!uncompressed
+            .headers
+            .iter()
+            .any(|header| header.starts_with("content-encoding")));
+        assert_eq!(
+            uncompressed.body.map(|body| body.len()),
+            Some(DEFAULT_GZIP_REQUEST_BODY_THRESHOLD)
+        );
+        // Only one retry: a second rejection is returned as is.
+        respond_with_status(&remote, &uncompressed, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = send.await.expect("no panic").expect("response");
+        assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropping_pending_response_frees_its_slot() {
+        const TIMEOUT: Duration = Duration::from_secs(10);
+        let (chat, _remote) = fake_connection();
+
+        let mut pending = vec![];
+        for _ in 0..MAX_OUTSTANDING_REQUESTS {
+            pending.push(
+                chat.start_send(get_request("/"), TIMEOUT)
+                    .await
+                    .expect("sent"),
+            );
+        }
+        assert_matches!(
+            chat.start_send(get_request("/"), TIMEOUT).await,
+            Err(SendError::RequestTimedOut { .. })
+        );
+
+        drop(pending.pop());
+        let _another = chat
+            .start_send(get_request("/"), TIMEOUT)
+            .await
+            .expect("slot was freed");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timed_out_request_reports_its_correlation_id() {
+        let (chat, _remote) = fake_connection();
+        let correlation_id = CorrelationId::random();
+        let request = Request {
+            correlation_id: Some(correlation_id),
+            ..get_request("/")
+        };
+
+        let err = chat
+            .send(request, Duration::from_secs(10))
+            .await
+            .expect_err("never answered");
+        assert_matches!(
+            err,
+            SendError::RequestTimedOut { correlation_id: Some(id) } if id == correlation_id
+        );
+    }
+
+    #[tokio::test]
+    async fn pending_responses_fail_when_connection_is_lost() {
+        const TIMEOUT: Duration = Duration::from_secs(10);
+        let (chat, remote) = fake_connection();
+
+        let first = chat
+            .start_send(get_request("/first"), TIMEOUT)
+            .await
+            .expect("sent");
+        let second = chat
+            .start_send(get_request("/second"), TIMEOUT)
+            .await
+            .expect("sent");
+        for _ in 0..2 {
+            _ = remote
+                .receive_request()
+                .await
+                .expect("valid request")
+                .expect("not closed");
+        }
+        drop(remote);
+
+        assert_matches!(first.await, Err(SendError::Disconnected));
+        assert_matches!(second.await, Err(SendError::Disconnected));
+    }
+
+    enum CloseFromServer {
+        Code(u16),
+        Reset,
+        GoSilent,
+    }
+
+    #[test_case(CloseFromServer::Code(1000) => matches SendError::ConnectionLost { close_code: Some(1000) }; "normal close")]
+    #[test_case(CloseFromServer::Code(4001) => matches SendError::ConnectionLost { close_code: Some(4001) }; "error close")]
+    #[test_case(CloseFromServer::Reset => matches SendError::ConnectionLost { close_code: None }; "reset")]
+    #[test_case(CloseFromServer::GoSilent => matches SendError::ConnectionIdleTimeout; "idle timeout")]
+    #[tokio::test(start_paused = true)]
+    async fn lost_connection_reports_how_it_was_closed(close: CloseFromServer) -> SendError {
+        let (stopped_tx, stopped_rx) = tokio::sync::oneshot::channel();
+        let mut stopped_tx = Some(stopped_tx);
+        let (_chat, remote) = ChatConnection::new_fake(
+            tokio::runtime::Handle::current(),
+            Box::new(move |event: ws2::ListenerEvent| {
+                if let Ok(server_requests::ServerEvent::Stopped(cause)) = event.try_into() {
+                    _ = stopped_tx.take().expect("stops once").send(cause);
+                }
+            }),
+            [],
+        );
+
+        let _remote = match close {
+            CloseFromServer::Code(code) => {
+                remote.send_close(Some(code)).expect("still connected");
+                Some(remote)
+            }
+            CloseFromServer::Reset => {
+                drop(remote);
+                None
+            }
+            // The fake never answers pings, so the client eventually gives up.
+            CloseFromServer::GoSilent => Some(remote),
+        };
+
+        let cause = stopped_rx.await.expect("listener was told");
+        assert_matches!(cause, server_requests::DisconnectCause::Error(e) => e)
+    }
 }
@@ -0,0 +1,198 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! The chat server's envelope for protobuf responses: a JSON object whose
+//! `serializedResponse` field holds the message, base64-encoded without
+//! padding. Some gateways re-encode the field with padding, so that's accepted
+//! too.
+//!
+//! The message is decoded before anything in it can be verified, so decoding
+//! is bounded: the message size by the caller's limit, which is checked
+//! before anything is allocated for it, and the nesting depth by prost's
+//! recursion limit.
+
+use std::borrow::Cow;
+
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::prelude::Engine as _;
+use bytes::Bytes;
+use serde::Deserialize;
+
+use crate::chat;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+#[cfg_attr(feature = "test-util", visibility::make(pub))]
+pub(crate) enum EnvelopeError {
+    /// bad status code: {0}
+    Status(http::StatusCode),
+    /// missing body
+    MissingBody,
+    /// invalid JSON
+    InvalidJson,
+    /// invalid base64
+    InvalidBase64,
+    /// message is {size} bytes (at most {max} allowed)
+    TooLarge { size: usize, max: usize },
+    /// invalid protobuf encoding: {0}
+    InvalidProtobuf(prost::DecodeError),
+}
+
+/// Standard base64, with or without padding.
+const BASE64_STANDARD_ANY_PAD: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RawEnvelope<'a> {
+    /// Borrowed from the response body unless the JSON escapes something.
+    #[serde(borrow)]
+    serialized_response: Cow<'a, str>,
+}
+
+/// Decodes the message in an enveloped response, after checking that the
+/// request succeeded.
+///
+/// Messages longer than `max_size` bytes are rejected without being decoded.
+#[cfg_attr(feature = "test-util", visibility::make(pub))]
+pub(crate) fn decode_envelope<R: prost::Message + Default>(
+    response: chat::Response,
+    max_size: usize,
+) -> Result<R, EnvelopeError> {
+    if !response.status.is_success() {
+        return Err(EnvelopeError::Status(response.status));
+    }
+    let body = response.body.ok_or(EnvelopeError::MissingBody)?;
+    let RawEnvelope {
+        serialized_response,
+    } = serde_json::from_slice(&body).map_err(|_| EnvelopeError::InvalidJson)?;
+    // Exact for valid base64 once any padding is left out, and checked before
+    // decoding so that an oversized message is never allocated.
+    let size = serialized_response.trim_end_matches('=').len() * 3 / 4;
+    if size > max_size {
+        return Err(EnvelopeError::TooLarge {
+            size,
+            max: max_size,
+        });
+    }
+    let proto_bytes = BASE64_STANDARD_ANY_PAD
+        .decode(serialized_response.as_bytes())
+        .map_err(|_| EnvelopeError::InvalidBase64)?;
+    // prost checks every length prefix against the remaining input before
+    // using it, and fails past its recursion limit (as long as the
+    // "no-recursion-limit" feature stays off), so neither a huge length nor
+    // deep nesting makes decoding allocate more than the input.
+    R::decode(Bytes::from(proto_bytes)).map_err(EnvelopeError::InvalidProtobuf)
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use base64::prelude::{BASE64_STANDARD, BASE64_STANDARD_NO_PAD};
+    use http::StatusCode;
+    use test_case::test_case;
+
+    use super::*;
+
+    /// Any message will do.
+    type TestMessage = crate::proto::chat_websocket::WebSocketResponseMessage;
+
+    fn response(status: StatusCode, body: Option<&str>) -> chat::Response {
+        chat::Response {
+            status,
+            message: None,
+            body: body.map(|body| Bytes::copy_from_slice(body.as_bytes())),
+            headers: Default::default(),
+        }
+    }
+
+    #[test_case(StatusCode::NOT_FOUND, Some(r#"{"serializedResponse":""}"#) => Err(EnvelopeError::Status(StatusCode::NOT_FOUND)); "bad status")]
+    #[test_case(StatusCode::OK, None => Err(EnvelopeError::MissingBody); "missing body")]
+    #[test_case(StatusCode::OK, Some("[]") => Err(EnvelopeError::InvalidJson); "wrong JSON")]
+    #[test_case(StatusCode::OK, Some(r#"{"serializedResponse":"!!"}"#) => Err(EnvelopeError::InvalidBase64); "bad base64")]
+    #[test_case(StatusCode::OK, Some(r#"{"serializedResponse":"/w"}"#) => matches Err(EnvelopeError::InvalidProtobuf(_)); "bad protobuf")]
+    #[test_case(StatusCode::OK, Some(r#"{"serializedResponse":"\/w"}"#) => matches Err(EnvelopeError::InvalidProtobuf(_)); "escaped JSON")]
+    #[test_case(StatusCode::OK, Some(r#"{"serializedResponse":"/w=="}"#) => matches Err(EnvelopeError::InvalidProtobuf(_)); "padded")]
+    #[test_case(StatusCode::OK, Some(r#"{"serializedResponse":""}"#) => Ok(TestMessage::default()); "empty message")]
+    fn decode(status: StatusCode, body: Option<&str>) -> Result<TestMessage, EnvelopeError> {
+        decode_envelope(response(status, body), usize::MAX)
+    }
+
+    fn enveloped(proto_bytes: &[u8]) -> chat::Response {
+        enveloped_with(&BASE64_STANDARD_NO_PAD, proto_bytes)
+    }
+
+    fn enveloped_with(engine: &impl base64::Engine, proto_bytes: &[u8]) -> chat::Response {
+        let body = format!(
+            r#"{{"serializedResponse":"{}"}}"#,
+            engine.encode(proto_bytes)
+        );
+        response(StatusCode::OK, Some(&body))
+    }
+
+    #[test_case(5, 5 => matches Ok(_); "at the limit")]
+    #[test_case(5, 4 => Err(EnvelopeError::TooLarge { size: 5, max: 4 }); "over the limit")]
+    #[test_case(6, 5 => Err(EnvelopeError::TooLarge { size: 6, max: 5 }); "multiple of three over the limit")]
+    #[test_case(7, 5 => Err(EnvelopeError::TooLarge { size: 7, max: 5 }); "one more than a multiple of three over the limit")]
+    fn size_limit(size: usize, max_size: usize) -> Result<TestMessage, EnvelopeError> {
+        decode_envelope(enveloped(&unknown_field_of_size(size)), max_size)
+    }
+
+    #[test_case(5, 5 => matches Ok(_); "at the limit")]
+    #[test_case(5, 4 => Err(EnvelopeError::TooLarge { size: 5, max: 4 }); "over the limit")]
+    #[test_case(7, 7 => matches Ok(_); "one more than a multiple of three at the limit")]
+    fn padded_size_limit(size: usize, max_size: usize) -> Result<TestMessage, EnvelopeError> {
+        decode_envelope(
+            enveloped_with(&BASE64_STANDARD, &unknown_field_of_size(size)),
+            max_size,
+        )
+    }
+
+    /// An unknown length-delimited field (number 15), padded out to `size`.
+    fn unknown_field_of_size(size: usize) -> Vec<u8> {
+        let mut proto_bytes = vec![(15u8 << 3) | 2, (size - 2).try_into().unwrap()];
+        proto_bytes.resize(size, 0);
+        proto_bytes
+    }
+
+    #[test]
+    fn huge_length_prefix() {
+        // An unknown length-delimited field claiming to be 2^62 bytes long.
+        let proto_bytes = [
+            (15u8 << 3) | 2,
+            0x80,
+            0x80,
+            0x80,
+            0x80,
+            0x80,
+            0x80,
+            0x80,
+            0x80,
+            0x40,
+        ];
+        assert_matches!(
+            decode_envelope::<TestMessage>(enveloped(&proto_bytes), usize::MAX),
+            Err(EnvelopeError::InvalidProtobuf(_))
+        );
+    }
+
+    #[test]
+    fn deep_nesting() {
+        // Unknown groups (number 15) nested far past prost's recursion limit.
+        const DEPTH: usize = 1000;
+        let proto_bytes = [
+            vec![(15u8 << 3) | 3; DEPTH], // start group
+            vec![(15u8 << 3) | 4; DEPTH], // end group
+        ]
+        .concat();
+        let err = decode_envelope::<TestMessage>(enveloped(&proto_bytes), usize::MAX)
+            .expect_err("too deep");
+        assert_matches!(err, EnvelopeError::InvalidProtobuf(_));
+        assert!(err.to_string().contains("recursion limit reached"), "{err}");
+    }
+}
@@ -3,21 +3,37 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use libsignal_net_infra::errors::{LogSafeDisplay, RetryLater};
+use libsignal_net_infra::errors::{
+    LogSafeDisplay, ProxyFailureKind, RetryLater, TransportConnectError,
+};
 use libsignal_net_infra::extract_retry_later;
 use libsignal_net_infra::route::ConnectError as RouteConnectError;
 use libsignal_net_infra::timeouts::TimeoutOr;
 use libsignal_net_infra::ws::{WebSocketConnectError, WebSocketServiceError};
+use tokio::time::Instant;
 
-use crate::ws::WebSocketServiceConnectError;
+use crate::chat::CorrelationId;
+use crate::ws::{RateLimitChallenge, WebSocketServiceConnectError};
 
 /// Error that can occur when sending a request to the Chat service.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum SendError {
-    /// timed out while sending a request
-    RequestTimedOut,
+    /// timed out while sending a request (correlation id: {correlation_id:?})
+    RequestTimedOut {
+        /// Identifies the request in debug logs, if the timeout applied to a
+        /// single request that was sent.
+        correlation_id: Option<CorrelationId>,
+    },
     /// connection is already closed
     Disconnected,
+    /// connection timed out after hearing nothing from the server
+    ConnectionIdleTimeout,
+    /// connection was closed by the server or the network (close code: {close_code:?})
+    ConnectionLost {
+        /// The code from the server's close frame, or `None` if the connection
+        /// ended without one, e.g. because it was reset.
+        close_code: Option<u16>,
+    },
     /// websocket error: {0}
     WebSocket(#[from] WebSocketServiceError),
     /// failed to decode data received from the server
@@ -26,57 +42,257 @@ pub enum SendError {
     RequestHasInvalidHeader,
 }
 
+impl SendError {
+    /// Whether the connection went away in the ordinary course of things, so
+    /// that the caller can reconnect without logging or alerting about it.
+    ///
+    /// This is the case for [idle timeouts](Self::ConnectionIdleTimeout), but
+    /// not for other [lost connections](Self::ConnectionLost), which might
+    /// point to a problem with the server or the network.
+    pub fn is_silently_retryable(&self) -> bool {
+        match self {
+            Self::ConnectionIdleTimeout => true,
+            Self::RequestTimedOut { .. }
+            | Self::Disconnected
+            | Self::ConnectionLost { .. }
+            | Self::WebSocket(_)
+            | Self::IncomingDataInvalid
+            | Self::RequestHasInvalidHeader => false,
+        }
+    }
+}
+
+/// How far a chat connection got before an error occurred.
+///
+/// Failures before the websocket upgrade usually point at the network (for
+/// example, censorship or a captive portal), while later ones are more likely
+/// to be server-side.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailurePhase {
+    /// Nothing was sent over the network, e.g. due to invalid configuration
+    /// or a failed DNS lookup.
+    BeforeTransport,
+    /// While establishing a TCP, TLS, or proxy connection.
+    Transport,
+    /// While upgrading an established transport to a websocket, including
+    /// rejection of the upgrade request by the server.
+    WebSocketUpgrade,
+    /// After the websocket was established.
+    Established,
+}
+
 /// Error that can occur when connecting to the Chat service.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum ConnectError {
     /// timed out while establishing a connection
-    Timeout,
+    Timeout {
+        /// The furthest phase reached by any connection attempt.
+        furthest_phase: FailurePhase,
+    },
     /// all connect attempts failed
-    AllAttemptsFailed,
+    AllAttemptsFailed {
+        /// The furthest phase reached by any connection attempt.
+        furthest_phase: FailurePhase,
+    },
     /// the connection information was invalid
     InvalidConnectionConfiguration,
+    /// proxy connection failed: {0}
+    ProxyFailure(ProxyFailureKind),
     /// websocket error: {0}
     WebSocket(#[from] WebSocketConnectError),
-    /// {0}
-    RetryLater(#[from] RetryLater),
+    /// {retry_later}
+    RetryLater {
+        retry_later: RetryLater,
+        /// When the response containing the retry delay was received.
+        received_at: Instant,
+    },
     /// app version is too old
-    AppExpired,
+    AppExpired {
+        /// The oldest app version the server still accepts, if it said.
+        minimum_version: Option<String>,
+        /// Where to get a newer version of the app, if the server said.
+        ///
+        /// Always an `https` URL.
+        upgrade_url: Option<String>,
+    },
     /// device was deregistered
     DeviceDeregistered,
+    /// {0}
+    RateLimitChallenge(RateLimitChallenge),
 }
 impl LogSafeDisplay for ConnectError {}
 
-impl From<TimeoutOr<RouteConnectError<WebSocketServiceConnectError>>> for ConnectError {
-    fn from(e: TimeoutOr<RouteConnectError<WebSocketServiceConnectError>>) -> Self {
+impl SendError {
+    /// Where in the connection process the error occurred.
+    ///
+    /// Requests can only be sent on an established connection, so this is
+    /// always [`FailurePhase::Established`].
+    pub fn failure_phase(&self) -> FailurePhase {
+        FailurePhase::Established
+    }
+}
+
+impl ConnectError {
+    /// When the server response that produced this error was received, if
+    /// the error came from the server.
+    pub fn received_at(&self) -> Option<Instant> {
+        match self {
+            Self::RetryLater {
+                retry_later: _,
+                received_at,
+            } => Some(*received_at),
+            Self::Timeout { .. }
+            | Self::AllAttemptsFailed { .. }
+            | Self::InvalidConnectionConfiguration
+            | Self::ProxyFailure(_)
+            | Self::WebSocket(_)
+            | Self::AppExpired { .. }
+            | Self::DeviceDeregistered
+            | Self::RateLimitChallenge(_) => None,
+        }
+    }
+
+    /// For [`ConnectError::RetryLater`], the earliest time at which a new
+    /// attempt should be made.
+    ///
+    /// This is measured from when the server's response was received, so it
+    /// doesn't drift no matter how long the error takes to be handled.
+    pub fn retry_at(&self) -> Option<Instant> {
+        match self {
+            Self::RetryLater {
+                retry_later,
+                received_at,
+            } => Some(retry_later.delay().retry_at(*received_at)),
+            _ => None,
+        }
+    }
+
+    /// Where in the connection process the error occurred.
+    ///
+    /// When multiple routes were tried, this is the furthest any of them got.
+    pub fn failure_phase(&self) -> FailurePhase {
+        match self {
+            Self::Timeout { furthest_phase } | Self::AllAttemptsFailed { furthest_phase } => {
+                *furthest_phase
+            }
+            Self::InvalidConnectionConfiguration => FailurePhase::BeforeTransport,
+            Self::WebSocket(WebSocketConnectError::Transport(
+                TransportConnectError::InvalidConfiguration
+                | TransportConnectError::DnsError
+                | TransportConnectError::CertError
+                | TransportConnectError::LocalBindFailed,
+            )) => FailurePhase::BeforeTransport,
+            Self::ProxyFailure(_)
+            | Self::WebSocket(
+                WebSocketConnectError::Transport(_) | WebSocketConnectError::Timeout,
+            ) => FailurePhase::Transport,
+            Self::WebSocket(WebSocketConnectError::WebSocketError(_))
+            | Self::RetryLater { .. }
+            | Self::AppExpired { .. }
+            | Self::DeviceDeregistered
+            | Self::RateLimitChallenge(_) => FailurePhase::WebSocketUpgrade,
+        }
+    }
+
+    /// Converts the result of racing connection attempts into a
+    /// [`ConnectError`].
+    ///
+    /// `furthest_phase` is how far any of the attempts got; it's used for
+    /// errors that don't identify a single failed attempt.
+    pub fn from_route_error(
+        e: TimeoutOr<RouteConnectError<WebSocketServiceConnectError>>,
+        furthest_phase: FailurePhase,
+    ) -> Self {
         match e {
             TimeoutOr::Other(RouteConnectError::NoResolvedRoutes) => {
                 ConnectError::InvalidConnectionConfiguration
             }
             TimeoutOr::Other(RouteConnectError::AllAttemptsFailed) => {
-                ConnectError::AllAttemptsFailed
+                ConnectError::AllAttemptsFailed { furthest_phase }
             }
             TimeoutOr::Other(RouteConnectError::FatalConnect(err)) => err.into(),
             TimeoutOr::Timeout {
                 attempt_duration: _,
-            } => ConnectError::Timeout,
+            } => ConnectError::Timeout { furthest_phase },
+        }
+    }
+
+    /// Like [`ConnectError::retry_at`], but as a wall-clock time suitable for
+    /// passing to code that doesn't share our monotonic clock.
+    pub fn retry_at_system_time(&self) -> Option<std::time::SystemTime> {
+        let retry_at = self.retry_at()?;
+        let now = Instant::now();
+        let wall_now = std::time::SystemTime::now();
+        Some(if retry_at >= now {
+            wall_now + (retry_at - now)
+        } else {
+            wall_now - (now - retry_at)
+        })
+    }
+}
+
+impl ConnectError {
+    /// The longest [`ConnectError::AppExpired::minimum_version`] kept from the
+    /// server's response.
+    const MAX_MINIMUM_VERSION_LEN: usize = 64;
+    /// The longest [`ConnectError::AppExpired::upgrade_url`] kept from the
+    /// server's response.
+    const MAX_UPGRADE_URL_LEN: usize = 2048;
+
+    /// Builds an [`ConnectError::AppExpired`] from the body of a 499 response.
+    ///
+    /// The body is expected to be a JSON object with `minimumVersion` and
+    /// `upgradeUrl` strings. Each field is kept only if it is present,
+    /// non-empty, free of control characters, and not too long, and the URL
+    /// only if it is an `https` URL; anything else is dropped rather than
+    /// failing.
+    fn app_expired(body: Option<&[u8]>) -> Self {
+        let fields = body
+            .filter(|body| !body.is_empty())
+            .and_then(|body| {
+                serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(body)
+                    .inspect_err(|e| log::warn!("failed to parse app expiration details: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let field = |name: &str, max_len: usize| {
+            let value = fields.get(name)?.as_str()?.trim();
+            let acceptable =
+                !value.is_empty() && value.len() <= max_len && !value.contains(char::is_control);
+            acceptable.then(|| value.to_owned())
+        };
+
+        Self::AppExpired {
+            minimum_version: field("minimumVersion", Self::MAX_MINIMUM_VERSION_LEN),
+            upgrade_url: field("upgradeUrl", Self::MAX_UPGRADE_URL_LEN)
+                .filter(|url| url::Url::parse(url).is_ok_and(|url| url.scheme() == "https")),
         }
     }
 }
 
 impl From<WebSocketServiceConnectError> for ConnectError {
     fn from(e: WebSocketServiceConnectError) -> Self {
+        if let Some(kind) = e.proxy_failure() {
+            return Self::ProxyFailure(kind);
+        }
         match e {
             WebSocketServiceConnectError::Connect(e, _) => Self::WebSocket(e),
             WebSocketServiceConnectError::RejectedByServer {
                 response,
-                received_at: _,
+                received_at,
             } => {
                 // Retry-After takes precedence over everything else.
-                if let Some(retry_after) = extract_retry_later(response.headers()) {
-                    return Self::RetryLater(retry_after);
+                if let Some(retry_later) = extract_retry_later(response.headers()) {
+                    return Self::RetryLater {
+                        retry_later,
+                        received_at,
+                    };
+                }
+                if let Some(challenge) = RateLimitChallenge::from_response(&response) {
+                    return Self::RateLimitChallenge(challenge);
                 }
                 match response.status().as_u16() {
-                    499 => Self::AppExpired,
+                    499 => Self::app_expired(response.body().as_deref()),
                     403 => {
                         // Technically this only applies to identified sockets,
                         // but unidentified sockets should never produce a 403 anyway.
@@ -90,3 +306,161 @@ impl From<WebSocketServiceConnectError> for ConnectError {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use assert_matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(ConnectError::InvalidConnectionConfiguration => FailurePhase::BeforeTransport)]
+    #[test_case(WebSocketConnectError::Transport(TransportConnectError::DnsError).into() => FailurePhase::BeforeTransport)]
+    #[test_case(WebSocketConnectError::Transport(TransportConnectError::LocalBindFailed).into() => FailurePhase::BeforeTransport)]
+    #[test_case(WebSocketConnectError::Transport(TransportConnectError::TcpConnectionFailed).into() => FailurePhase::Transport)]
+    #[test_case(WebSocketConnectError::WebSocketError(tungstenite::Error::ConnectionClosed).into() => FailurePhase::WebSocketUpgrade)]
+    #[test_case(ConnectError::ProxyFailure(ProxyFailureKind::Unreachable) => FailurePhase::Transport)]
+    #[test_case(ConnectError::DeviceDeregistered => FailurePhase::WebSocketUpgrade)]
+    #[test_case(ConnectError::AllAttemptsFailed { furthest_phase: FailurePhase::Transport } => FailurePhase::Transport)]
+    fn connect_error_failure_phase(error: ConnectError) -> FailurePhase {
+        error.failure_phase()
+    }
+
+    #[test]
+    fn proxy_failure_is_surfaced() {
+        let error = ConnectError::from(WebSocketServiceConnectError::from_websocket_error(
+            WebSocketConnectError::Transport(TransportConnectError::ProxyFailure(
+                ProxyFailureKind::AuthRequired,
+            )),
+            None,
+            Instant::now(),
+        ));
+        assert_matches!(
+            error,
+            ConnectError::ProxyFailure(ProxyFailureKind::AuthRequired)
+        );
+    }
+
+    #[test]
+    fn rejected_upgrade_is_upgrade_failure() {
+        let response = http::Response::builder()
+            .status(500)
+            .body(None)
+            .expect("valid");
+
+        let error = ConnectError::from(WebSocketServiceConnectError::RejectedByServer {
+            response,
+            received_at: Instant::now(),
+        });
+        assert_eq!(error.failure_phase(), FailurePhase::WebSocketUpgrade);
+    }
+
+    #[test_case(None => (None, None); "no body")]
+    #[test_case(Some("not json") => (None, None); "not json")]
+    #[test_case(Some(r#"{"minimumVersion":"7.40.0","upgradeUrl":"https://signal.org/install"}"#)
+        => (Some("7.40.0".to_owned()), Some("https://signal.org/install".to_owned())); "both")]
+    #[test_case(Some(r#"{"minimumVersion":" 7.40.0 ","unexpected":[]}"#)
+        => (Some("7.40.0".to_owned()), None); "trimmed, missing URL")]
+    #[test_case(Some(r#"{"minimumVersion":7,"upgradeUrl":"http://signal.org/install"}"#)
+        => (None, None); "wrong types")]
+    #[test_case(Some(r#"{"minimumVersion":"7.40\n0","upgradeUrl":"javascript:alert(1)"}"#)
+        => (None, None); "unsafe values")]
+    fn app_expired_details(body: Option<&str>) -> (Option<String>, Option<String>) {
+        let response = http::Response::builder()
+            .status(499)
+            .body(body.map(|body| body.as_bytes().to_vec()))
+            .expect("valid");
+
+        let error = ConnectError::from(WebSocketServiceConnectError::RejectedByServer {
+            response,
+            received_at: Instant::now(),
+        });
+        assert_matches!(error, ConnectError::AppExpired { minimum_version, upgrade_url } => {
+            (minimum_version, upgrade_url)
+        })
+    }
+
+    #[test]
+    fn app_expired_details_are_length_capped() {
+        let body = serde_json::json!({
+            "minimumVersion": "7".repeat(ConnectError::MAX_MINIMUM_VERSION_LEN + 1),
+            "upgradeUrl": format!("https://signal.org/{}", "a".repeat(ConnectError::MAX_UPGRADE_URL_LEN)),
+        });
+        assert_matches!(
+            ConnectError::app_expired(Some(body.to_string().as_bytes())),
+            ConnectError::AppExpired {
+                minimum_version: None,
+                upgrade_url: None,
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_later_is_measured_from_receipt() {
+        let received_at = Instant::now();
+        let response = http::Response::builder()
+            .status(429)
+            .header("retry-after", "20")
+            .body(None)
+            .expect("valid");
+
+        let error = ConnectError::from(WebSocketServiceConnectError::RejectedByServer {
+            response,
+            received_at,
+        });
+        assert_matches!(
+            error,
+            ConnectError::RetryLater {
+                retry_later: RetryLater {
+                    retry_after_seconds: 20
+                },
+                ..
+            }
+        );
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        assert_eq!(error.received_at(), Some(received_at));
+        assert_eq!(
+            error.retry_at(),
+            Some(received_at + Duration::from_secs(20))
+        );
+    }
+
+    #[test]
+    fn rate_limit_challenge_is_surfaced() {
+        let response = http::Response::builder()
+            .status(428)
+            .body(Some(br#"{"token":"abc","options":["recaptcha"]}"#.to_vec()))
+            .expect("valid");
+
+        let error = ConnectError::from(WebSocketServiceConnectError::RejectedByServer {
+            response,
+            received_at: Instant::now(),
+        });
+        let challenge = assert_matches!(error, ConnectError::RateLimitChallenge(c) => c);
+        assert_eq!(challenge.token, "abc");
+        assert_eq!(challenge.options, ["recaptcha"]);
+    }
+
+    #[test]
+    fn malformed_rate_limit_challenge_is_plain_rejection() {
+        let response = http::Response::builder()
+            .status(428)
+            .body(Some(b"{}".to_vec()))
+            .expect("valid");
+
+        let error = ConnectError::from(WebSocketServiceConnectError::RejectedByServer {
+            response,
+            received_at: Instant::now(),
+        });
+        assert_matches!(
+            error,
+            ConnectError::WebSocket(WebSocketConnectError::WebSocketError(
+                tungstenite::Error::Http(_)
+            ))
+        );
+    }
+}
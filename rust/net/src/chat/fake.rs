@@ -4,9 +4,11 @@
 //
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::time::Duration;
 
 use futures_util::{Sink, Stream};
+use libsignal_net_infra::ws::CountingWebSocket;
 use libsignal_net_infra::{IpType, TransportInfo};
 use pin_project::pin_project;
 use prost::Message;
@@ -14,7 +16,12 @@ use tokio_stream::wrappers::UnboundedReceiverStream;
 
 use crate::chat::{ws2, ChatConnection, ConnectionInfo, MessageProto, RequestProto, ResponseProto};
 use crate::connect_state::RouteInfo;
+use crate::data_usage::DataUsage;
 use crate::env::ALERT_HEADER_NAME;
+use crate::metrics::ServiceKind;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod server;
 
 /// The remote end of a fake connection to the chat server.
 #[derive(Debug)]
@@ -33,6 +40,7 @@ pub enum ReceiveRequestError {
     InvalidProto(String),
     InvalidWebsocketMessageType,
     GotResponse,
+    GotRequest,
 }
 
 impl ChatConnection {
@@ -41,6 +49,17 @@ impl ChatConnection {
         tokio_runtime: tokio::runtime::Handle,
         listener: ws2::EventListener,
         alerts: impl IntoIterator<Item = &'a str>,
+    ) -> (Self, FakeChatRemote) {
+        Self::new_fake_with_data_usage(tokio_runtime, listener, alerts, Default::default())
+    }
+
+    /// Like [`Self::new_fake`], but counts the connection's traffic in
+    /// `data_usage` the way a real connection would.
+    pub fn new_fake_with_data_usage<'a>(
+        tokio_runtime: tokio::runtime::Handle,
+        listener: ws2::EventListener,
+        alerts: impl IntoIterator<Item = &'a str>,
+        data_usage: Arc<DataUsage>,
     ) -> (Self, FakeChatRemote) {
         let (tx_to_local, rx_from_remote) = tokio::sync::mpsc::unbounded_channel();
         let (tx_to_remote, rx_from_local) = tokio::sync::mpsc::unbounded_channel();
@@ -57,7 +76,10 @@ impl ChatConnection {
             })?;
             Ok(tx)
         });
-        let local = StreamSink(incoming, outgoing, PhantomData);
+        let local = CountingWebSocket::new(
+            StreamSink(incoming, outgoing, PhantomData),
+            data_usage.counter(ServiceKind::Chat).clone(),
+        );
 
         let connection_info = ConnectionInfo {
             route_info: RouteInfo::fake(),
@@ -87,8 +109,16 @@ impl ChatConnection {
                 config,
                 log_tag,
                 listener,
+                Default::default(),
             ),
             connection_info,
+            request_path_prefix: None,
+            gzip_request_body_threshold: crate::chat::DEFAULT_GZIP_REQUEST_BODY_THRESHOLD,
+            round_trip: Default::default(),
+            data_usage,
+            outstanding_requests: Arc::new(tokio::sync::Semaphore::new(
+                crate::chat::MAX_OUTSTANDING_REQUESTS,
+            )),
         };
         (chat, remote)
     }
@@ -136,6 +166,23 @@ impl FakeChatRemote {
         }
     }
 
+    /// Like [`Self::receive_request`], but for the client's response to a
+    /// request sent with [`Self::send_request`].
+    pub async fn receive_response(&self) -> Result<Option<ResponseProto>, ReceiveRequestError> {
+        log::debug!("waiting for next response");
+        let Some(message) = self.rx.lock().await.recv().await else {
+            return Ok(None);
+        };
+        let proto = match message {
+            tungstenite::Message::Binary(message) => ws2::decode_and_validate(&message)?,
+            _ => return Err(ReceiveRequestError::InvalidWebsocketMessageType),
+        };
+        match proto {
+            ws2::ChatMessageProto::Request(_) => Err(ReceiveRequestError::GotRequest),
+            ws2::ChatMessageProto::Response(response) => Ok(Some(response)),
+        }
+    }
+
     /// Send a close frame to the client.
     pub fn send_close(&self, code: Option<u16>) -> Result<(), Disconnected> {
         self.tx
@@ -0,0 +1,295 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use http::StatusCode;
+
+use crate::chat::fake::FakeChatRemote;
+use crate::chat::{ws2, ChatConnection, RequestProto, ResponseProto};
+use crate::data_usage::DataUsage;
+
+/// An in-process chat server that answers requests with canned responses.
+///
+/// Responses are registered by request path (not including any query string).
+/// Requests for paths without a registered response get a 404 Not Found.
+///
+/// Connections made with [`FakeChatServer::connect`] don't go over the
+/// network; they talk to the server over an in-memory channel that carries
+/// the same websocket frames a real connection would.
+#[derive(Clone, Debug, Default)]
+pub struct FakeChatServer {
+    state: Arc<Mutex<ServerState>>,
+}
+
+#[derive(Debug, Default)]
+struct ServerState {
+    replies: HashMap<String, CannedReply>,
+    received: Vec<RequestProto>,
+}
+
+/// What the server does when it receives a request for a particular path.
+#[derive(Clone, Debug)]
+enum CannedReply {
+    Respond(CannedResponse),
+    Close { code: Option<u16> },
+}
+
+/// A response for [`FakeChatServer`] to send.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CannedResponse {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl CannedResponse {
+    /// A response with the given status and no headers or body.
+    pub fn status(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    /// A 200 OK response with a JSON body.
+    pub fn json(body: &impl serde::Serialize) -> Self {
+        Self::status(StatusCode::OK)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_vec(body).expect("can serialize"))
+    }
+
+    /// A 429 Too Many Requests response with a Retry-After header.
+    pub fn retry_later(retry_after: Duration) -> Self {
+        Self::status(StatusCode::TOO_MANY_REQUESTS)
+            .with_header("retry-after", retry_after.as_secs().to_string())
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    fn into_proto(self, id: Option<u64>) -> ResponseProto {
+        let Self {
+            status,
+            headers,
+            body,
+        } = self;
+        ResponseProto {
+            id,
+            status: Some(status.as_u16().into()),
+            message: status.canonical_reason().map(ToOwned::to_owned),
+            headers: headers
+                .into_iter()
+                .map(|(name, value)| format!("{name}: {value}"))
+                .collect(),
+            body,
+        }
+    }
+}
+
+impl FakeChatServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Responds to every request for `path` with `response`.
+    ///
+    /// Replaces any previous behavior registered for `path`.
+    pub fn respond(&self, path: impl Into<String>, response: CannedResponse) {
+        self.set_reply(path.into(), CannedReply::Respond(response));
+    }
+
+    /// Closes the connection, with the given close code, on any request for
+    /// `path`.
+    ///
+    /// Replaces any previous behavior registered for `path`.
+    pub fn close_on(&self, path: impl Into<String>, code: Option<u16>) {
+        self.set_reply(path.into(), CannedReply::Close { code });
+    }
+
+    /// Returns all the requests the server has received so far, across all
+    /// connections, in the order they were received.
+    pub fn received_requests(&self) -> Vec<RequestProto> {
+        self.state.lock().expect("not poisoned").received.clone()
+    }
+
+    /// Creates a [`ChatConnection`] connected to this server.
+    ///
+    /// Must be called from within a tokio runtime; the server's end of the
+    /// connection runs as a task on that runtime until the connection is
+    /// closed by either side.
+    pub fn connect(&self, listener: ws2::EventListener) -> ChatConnection {
+        self.connect_with_data_usage(listener, Default::default())
+    }
+
+    /// Like [`Self::connect`], but counts the connection's traffic in
+    /// `data_usage`.
+    pub fn connect_with_data_usage(
+        &self,
+        listener: ws2::EventListener,
+        data_usage: Arc<DataUsage>,
+    ) -> ChatConnection {
+        let tokio_runtime = tokio::runtime::Handle::current();
+        let (chat, remote) = ChatConnection::new_fake_with_data_usage(
+            tokio_runtime.clone(),
+            listener,
+            [],
+            data_usage,
+        );
+        tokio_runtime.spawn(self.clone().serve(remote));
+        chat
+    }
+
+    fn set_reply(&self, path: String, reply: CannedReply) {
+        _ = self
+            .state
+            .lock()
+            .expect("not poisoned")
+            .replies
+            .insert(path, reply);
+    }
+
+    async fn serve(self, remote: FakeChatRemote) {
+        loop {
+            let request = match remote.receive_request().await {
+                Ok(Some(request)) => request,
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("fake chat server got invalid request: {e:?}");
+                    break;
+                }
+            };
+
+            let reply = {
+                let mut state = self.state.lock().expect("not poisoned");
+                let path = request.path().split('?').next().unwrap_or_default();
+                let reply = state.replies.get(path).cloned();
+                state.received.push(request.clone());
+                reply
+            };
+
+            let sent = match reply {
+                Some(CannedReply::Respond(response)) => {
+                    remote.send_response(response.into_proto(request.id))
+                }
+                None => remote.send_response(
+                    CannedResponse::status(StatusCode::NOT_FOUND).into_proto(request.id),
+                ),
+                Some(CannedReply::Close { code }) => {
+                    _ = remote.send_close(code);
+                    break;
+                }
+            };
+            if sent.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+
+    use super::*;
+    use crate::chat::Request;
+    use crate::infra::ws2::NextEventError;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn get(path: &'static str) -> Request {
+        Request {
+            method: http::Method::GET,
+            path: http::uri::PathAndQuery::from_static(path),
+            headers: Default::default(),
+            body: None,
+            priority: Default::default(),
+            correlation_id: None,
+            gzip_body: false,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn responds_by_path() {
+        let server = FakeChatServer::new();
+        server.respond(
+            "/v1/greeting",
+            CannedResponse::status(StatusCode::OK)
+                .with_header("x-greeting", "hello")
+                .with_body(b"hi".as_slice()),
+        );
+        let chat = server.connect(Box::new(|_| {}));
+
+        let response = chat
+            .send(get("/v1/greeting?lang=en"), TIMEOUT)
+            .await
+            .expect("response");
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.headers["x-greeting"], "hello");
+        assert_eq!(response.body.as_deref(), Some(b"hi".as_slice()));
+
+        let response = chat
+            .send(get("/v1/unknown"), TIMEOUT)
+            .await
+            .expect("response");
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+        let paths = server
+            .received_requests()
+            .into_iter()
+            .map(|request| request.path().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/v1/greeting?lang=en", "/v1/unknown"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn injects_retry_after() {
+        let server = FakeChatServer::new();
+        server.respond(
+            "/v1/busy",
+            CannedResponse::retry_later(Duration::from_secs(30)),
+        );
+        let chat = server.connect(Box::new(|_| {}));
+
+        let response = chat.send(get("/v1/busy"), TIMEOUT).await.expect("response");
+        assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers["retry-after"], "30");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closes_with_code() {
+        let server = FakeChatServer::new();
+        server.close_on("/v1/goodbye", Some(4401));
+
+        let (finished_tx, finished_rx) = tokio::sync::oneshot::channel();
+        let mut finished_tx = Some(finished_tx);
+        let chat = server.connect(Box::new(move |event| {
+            if let ws2::ListenerEvent::Finished(result) = event {
+                _ = finished_tx.take().expect("only finishes once").send(result);
+            }
+        }));
+
+        let result = chat.send(get("/v1/goodbye"), TIMEOUT).await;
+        assert_matches!(result, Err(_));
+
+        let finished = finished_rx.await.expect("listener was notified");
+        assert_matches!(
+            finished,
+            Err(ws2::FinishError::Error(ws2::TaskExitError::WebsocketError(
+                NextEventError::AbnormalServerClose { code, .. }
+            ))) if u16::from(code) == 4401
+        );
+    }
+}
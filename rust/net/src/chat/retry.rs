@@ -0,0 +1,79 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::time::Duration;
+
+use rand::Rng;
+
+/// How long to wait between automatic attempts to re-establish a chat
+/// connection that was lost.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReconnectBackoff {
+    /// The delay after the first failed attempt.
+    pub initial_delay: Duration,
+    /// The delay stops doubling once it reaches this.
+    pub max_delay: Duration,
+    /// The largest fraction, between 0 and 1, by which any delay is randomly
+    /// shortened.
+    ///
+    /// This keeps clients that lost their connections at the same moment from
+    /// all coming back at once.
+    pub jitter: f64,
+}
+
+pub const SUGGESTED_RECONNECT_BACKOFF: ReconnectBackoff = ReconnectBackoff {
+    initial_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(60),
+    jitter: 0.25,
+};
+
+impl ReconnectBackoff {
+    /// The delay before the next attempt, after `failed_attempts` attempts in a
+    /// row have failed.
+    ///
+    /// A connection that was just lost gets its first attempt immediately.
+    pub fn delay_after(&self, failed_attempts: u32, rng: &mut impl Rng) -> Duration {
+        let Some(doublings) = failed_attempts.checked_sub(1) else {
+            return Duration::ZERO;
+        };
+        let delay = self
+            .initial_delay
+            .checked_mul(1u32 << doublings.min(31))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        delay.mul_f64(1.0 - rng.gen_range(0.0..=jitter))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reconnect_backoff_doubles_up_to_max() {
+        let backoff = ReconnectBackoff {
+            jitter: 0.0,
+            ..SUGGESTED_RECONNECT_BACKOFF
+        };
+        let mut rng = rand::thread_rng();
+        let delays = [0, 1, 2, 3, 6, 7, 100].map(|n| backoff.delay_after(n, &mut rng));
+        assert_eq!(
+            delays,
+            [0, 1, 2, 4, 32, 60, 60].map(Duration::from_secs),
+            "{delays:?}"
+        );
+    }
+
+    #[test]
+    fn reconnect_backoff_jitter_only_shortens_delays() {
+        let backoff = SUGGESTED_RECONNECT_BACKOFF;
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let delay = backoff.delay_after(4, &mut rng);
+            assert!(delay <= Duration::from_secs(8), "{delay:?}");
+            assert!(delay >= Duration::from_secs(6), "{delay:?}");
+        }
+    }
+}
@@ -5,6 +5,7 @@
 
 use libsignal_net_infra::ws::WebSocketServiceError;
 use libsignal_protocol::Timestamp;
+use tungstenite::protocol::frame::coding::CloseCode;
 
 use crate::chat::{ws2, RequestProto, SendError};
 use crate::env::TIMESTAMP_HEADER_NAME;
@@ -21,6 +22,15 @@ pub enum ServerEvent {
         send_ack: ResponseEnvelopeSender,
     },
     Alerts(Vec<String>),
+    /// A server-initiated request for a path that isn't handled by one of the
+    /// other events.
+    ///
+    /// The recipient is responsible for responding using `send_ack`.
+    UnrecognizedRequest {
+        path: String,
+        body: Option<Vec<u8>>,
+        send_ack: ResponseEnvelopeSender,
+    },
     Stopped(DisconnectCause),
 }
 
@@ -46,6 +56,18 @@ impl std::fmt::Debug for ServerEvent {
                 .field("server_delivery_timestamp", server_delivery_timestamp)
                 .finish(),
             Self::Alerts(alerts) => f.debug_tuple("Alerts").field(&alerts.len()).finish(),
+            Self::UnrecognizedRequest {
+                path,
+                body,
+                send_ack: _,
+            } => f
+                .debug_struct("UnrecognizedRequest")
+                .field("path", path)
+                .field(
+                    "body",
+                    &format_args!("{} bytes", body.as_deref().unwrap_or_default().len()),
+                )
+                .finish(),
             Self::Stopped(error) => f
                 .debug_struct("ConnectionInterrupted")
                 .field("reason", error)
@@ -60,8 +82,6 @@ pub enum ServerEventError {
     UnexpectedVerb(String),
     /// server request missing path
     MissingPath,
-    /// server sent an unknown request: {0}
-    UnrecognizedPath(String),
 }
 
 impl TryFrom<ws2::ListenerEvent> for ServerEvent {
@@ -72,16 +92,18 @@ impl TryFrom<ws2::ListenerEvent> for ServerEvent {
             ws2::ListenerEvent::ReceivedAlerts(alerts) => Ok(Self::Alerts(alerts)),
 
             ws2::ListenerEvent::ReceivedMessage(proto, responder) => {
-                convert_received_message(proto, || {
+                convert_or_reject_received_message(proto, || {
                     Box::new(move |status| Ok(responder.send_response(status)?))
                 })
             }
 
             ws2::ListenerEvent::Finished(reason) => Ok(ServerEvent::Stopped(match reason {
                 Ok(ws2::FinishReason::LocalDisconnect) => DisconnectCause::LocalDisconnect,
-                Ok(ws2::FinishReason::RemoteDisconnect) => DisconnectCause::Error(
-                    SendError::WebSocket(WebSocketServiceError::ChannelClosed),
-                ),
+                Ok(ws2::FinishReason::RemoteDisconnect) => {
+                    DisconnectCause::Error(SendError::ConnectionLost {
+                        close_code: Some(CloseCode::Normal.into()),
+                    })
+                }
                 Err(ws2::FinishError::Unknown) => DisconnectCause::Error(SendError::WebSocket(
                     WebSocketServiceError::Other("unexpected exit"),
                 )),
@@ -91,6 +113,27 @@ impl TryFrom<ws2::ListenerEvent> for ServerEvent {
     }
 }
 
+/// Like [`convert_received_message`], but if the request can't be converted,
+/// responds to the server with 404 Not Found so that it doesn't wait on an ack
+/// that will never come.
+fn convert_or_reject_received_message(
+    proto: crate::proto::chat_websocket::WebSocketRequestMessage,
+    make_send_ack: impl FnOnce() -> ResponseEnvelopeSender,
+) -> Result<ServerEvent, ServerEventError> {
+    let mut make_send_ack = Some(make_send_ack);
+    let result = convert_received_message(proto, || {
+        (make_send_ack.take().expect("only called once"))()
+    });
+
+    if let (Err(e), Some(make_send_ack)) = (&result, make_send_ack) {
+        log::warn!("rejecting server request: {e}");
+        if let Err(send_error) = make_send_ack()(http::StatusCode::NOT_FOUND) {
+            log::warn!("failed to reject server request: {send_error}");
+        }
+    }
+    result
+}
+
 fn convert_received_message(
     proto: crate::proto::chat_websocket::WebSocketRequestMessage,
     make_send_ack: impl FnOnce() -> ResponseEnvelopeSender,
@@ -140,6 +183,89 @@ fn convert_received_message(
             })
         }
         "" => Err(ServerEventError::MissingPath),
-        _unknown_path => Err(ServerEventError::UnrecognizedPath(path)),
+        _unknown_path => Ok(ServerEvent::UnrecognizedRequest {
+            path,
+            body,
+            send_ack: make_send_ack(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use assert_matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+
+    fn request(verb: &str, path: &str, body: Option<&[u8]>) -> RequestProto {
+        RequestProto {
+            verb: Some(verb.to_owned()),
+            path: Some(path.to_owned()),
+            body: body.map(<[u8]>::to_vec),
+            headers: vec![],
+            id: Some(1),
+        }
+    }
+
+    /// Returns an ack factory that records the status it was called with.
+    fn recording_ack(
+        acked: &Arc<Mutex<Option<http::StatusCode>>>,
+    ) -> impl FnOnce() -> ResponseEnvelopeSender {
+        let acked = acked.clone();
+        move || {
+            Box::new(move |status| {
+                *acked.lock().expect("not poisoned") = Some(status);
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn unrecognized_path_is_passed_through() {
+        let acked = Arc::default();
+        let event = convert_or_reject_received_message(
+            request("PUT", "/api/v1/something/new", Some(b"payload")),
+            recording_ack(&acked),
+        )
+        .expect("valid request");
+
+        let send_ack = assert_matches!(
+            event,
+            ServerEvent::UnrecognizedRequest { path, body, send_ack } => {
+                assert_eq!(path, "/api/v1/something/new");
+                assert_eq!(body.as_deref(), Some(&b"payload"[..]));
+                send_ack
+            }
+        );
+        assert_eq!(*acked.lock().unwrap(), None, "not acked automatically");
+
+        send_ack(http::StatusCode::OK).expect("can ack");
+        assert_eq!(*acked.lock().unwrap(), Some(http::StatusCode::OK));
+    }
+
+    #[test_case(request("GET", "/api/v1/queue/empty", None); "unexpected verb")]
+    #[test_case(request("PUT", "", None); "missing path")]
+    fn invalid_request_is_rejected(request: RequestProto) {
+        let acked = Arc::default();
+        let result = convert_or_reject_received_message(request, recording_ack(&acked));
+
+        assert_matches!(result, Err(_));
+        assert_eq!(*acked.lock().unwrap(), Some(http::StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn known_request_is_not_rejected() {
+        let acked = Arc::default();
+        let event = convert_or_reject_received_message(
+            request("PUT", "/api/v1/queue/empty", None),
+            recording_ack(&acked),
+        )
+        .expect("valid request");
+
+        assert_matches!(event, ServerEvent::QueueEmpty);
+        assert_eq!(*acked.lock().unwrap(), None);
     }
 }
@@ -0,0 +1,294 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use crate::chat::FailurePhase;
+
+/// The state of a chat connection, as seen by whoever owns it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// There is no connection, and none is being established.
+    Disconnected(DisconnectReason),
+    /// A connection is being established.
+    Connecting {
+        /// The number of consecutive connection attempts, including this one,
+        /// since the last successful connection.
+        attempt: NonZeroU32,
+    },
+    /// A connection has been established and is usable.
+    Connected {
+        /// When the connection was established.
+        since: SystemTime,
+    },
+}
+
+/// Why a chat connection is in the [`ConnectionState::Disconnected`] state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// No connection has been attempted yet.
+    NotStarted,
+    /// The connection was closed locally.
+    LocalDisconnect,
+    /// The most recent connection attempt failed.
+    ConnectFailed(FailurePhase),
+    /// An established connection was lost, either because the server closed
+    /// it or because of a network error.
+    ConnectionLost,
+}
+
+impl Default for ConnectionState {
+    fn default() -> Self {
+        Self::Disconnected(DisconnectReason::NotStarted)
+    }
+}
+
+/// Tracks the [`ConnectionState`] of a chat connection and publishes changes.
+///
+/// Changes are published in order, but observers that fall behind only see
+/// the most recent state rather than every intermediate one.
+///
+/// Each connection attempt starts a new [`ConnectionGeneration`], and changes
+/// reported for an earlier generation are ignored, so that the end of a
+/// connection that has since been replaced doesn't hide the state of its
+/// replacement.
+#[derive(Debug, Default)]
+pub struct ConnectionStateMachine {
+    state: tokio::sync::watch::Sender<ConnectionState>,
+    /// Failed connection attempts since the last successful one.
+    failed_attempts: AtomicU32,
+    /// The generation of the most recent connection attempt.
+    ///
+    /// Only changed while holding the lock on `state`.
+    generation: AtomicU64,
+}
+
+/// Identifies a connection attempt, and the connection it produces, to a
+/// [`ConnectionStateMachine`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionGeneration(u64);
+
+/// A connection attempt started with [`ConnectionStateMachine::connecting`].
+///
+/// Dropping it without calling [`ConnectAttempt::connected`] or
+/// [`ConnectAttempt::failed`] means the attempt was cancelled, which is
+/// recorded as [`DisconnectReason::LocalDisconnect`].
+#[derive(Debug)]
+#[must_use]
+pub struct ConnectAttempt<'a> {
+    machine: &'a ConnectionStateMachine,
+    /// Taken once the outcome has been recorded.
+    generation: Option<ConnectionGeneration>,
+}
+
+/// Observes changes to a [`ConnectionStateMachine`].
+pub type ConnectionStateReceiver = tokio::sync::watch::Receiver<ConnectionState>;
+
+impl ConnectionStateMachine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current state.
+    pub fn current(&self) -> ConnectionState {
+        self.state.borrow().clone()
+    }
+
+    /// Returns a receiver for observing state changes.
+    ///
+    /// The receiver starts out with the current state marked as seen.
+    pub fn subscribe(&self) -> ConnectionStateReceiver {
+        self.state.subscribe()
+    }
+
+    /// Records the start of a new connection attempt, superseding any earlier
+    /// attempt or connection.
+    pub fn connecting(&self) -> ConnectAttempt<'_> {
+        let failed_attempts = self.failed_attempts.load(Ordering::Relaxed);
+        let attempt = NonZeroU32::MIN.saturating_add(failed_attempts);
+        let mut generation = None;
+        self.state.send_if_modified(|state| {
+            generation = Some(ConnectionGeneration(
+                self.generation.fetch_add(1, Ordering::Relaxed) + 1,
+            ));
+            Self::transition(state, ConnectionState::Connecting { attempt })
+        });
+        ConnectAttempt {
+            machine: self,
+            generation,
+        }
+    }
+
+    /// Records that the connection for `generation` is gone, unless a newer
+    /// attempt has started since.
+    pub fn disconnected(&self, generation: ConnectionGeneration, reason: DisconnectReason) {
+        self.update(generation, |state| {
+            if let DisconnectReason::ConnectFailed(_) = reason {
+                self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+            }
+            Self::transition(state, ConnectionState::Disconnected(reason))
+        });
+    }
+
+    fn connected(&self, generation: ConnectionGeneration, since: SystemTime) {
+        self.update(generation, |state| {
+            self.failed_attempts.store(0, Ordering::Relaxed);
+            Self::transition(state, ConnectionState::Connected { since })
+        });
+    }
+
+    /// Applies `modify` if `generation` is still the latest one.
+    fn update(
+        &self,
+        generation: ConnectionGeneration,
+        modify: impl FnOnce(&mut ConnectionState) -> bool,
+    ) {
+        self.state.send_if_modified(|state| {
+            let ConnectionGeneration(generation) = generation;
+            if generation != self.generation.load(Ordering::Relaxed) {
+                log::info!("ignoring state change for superseded chat connection {generation}");
+                return false;
+            }
+            modify(state)
+        });
+    }
+
+    fn transition(state: &mut ConnectionState, next: ConnectionState) -> bool {
+        if *state == next {
+            return false;
+        }
+        log::info!("chat connection state: {state:?} -> {next:?}");
+        *state = next;
+        true
+    }
+}
+
+impl ConnectAttempt<'_> {
+    /// Records that the attempt succeeded at `since`.
+    ///
+    /// Returns the generation to report the connection's end under.
+    pub fn connected(mut self, since: SystemTime) -> ConnectionGeneration {
+        let generation = self.generation.take().expect("only taken once");
+        self.machine.connected(generation, since);
+        generation
+    }
+
+    /// Records that the attempt failed.
+    pub fn failed(mut self, phase: FailurePhase) {
+        let generation = self.generation.take().expect("only taken once");
+        self.machine
+            .disconnected(generation, DisconnectReason::ConnectFailed(phase));
+    }
+}
+
+impl Drop for ConnectAttempt<'_> {
+    fn drop(&mut self) {
+        if let Some(generation) = self.generation.take() {
+            self.machine
+                .disconnected(generation, DisconnectReason::LocalDisconnect);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn attempts_count_consecutive_failures() {
+        let machine = ConnectionStateMachine::new();
+        assert_eq!(
+            machine.current(),
+            ConnectionState::Disconnected(DisconnectReason::NotStarted)
+        );
+
+        let attempt = machine.connecting();
+        assert_matches!(machine.current(), ConnectionState::Connecting { attempt } if attempt.get() == 1);
+        attempt.failed(FailurePhase::Transport);
+        let attempt = machine.connecting();
+        assert_matches!(machine.current(), ConnectionState::Connecting { attempt } if attempt.get() == 2);
+
+        let since = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let generation = attempt.connected(since);
+        assert_eq!(machine.current(), ConnectionState::Connected { since });
+
+        machine.disconnected(generation, DisconnectReason::ConnectionLost);
+        let _attempt = machine.connecting();
+        assert_matches!(machine.current(), ConnectionState::Connecting { attempt } if attempt.get() == 1);
+    }
+
+    #[tokio::test]
+    async fn slow_observers_see_latest_state() {
+        let machine = ConnectionStateMachine::new();
+        let mut receiver = machine.subscribe();
+
+        let generation = machine.connecting().connected(SystemTime::UNIX_EPOCH);
+        machine.disconnected(generation, DisconnectReason::LocalDisconnect);
+
+        receiver.changed().await.expect("not closed");
+        assert_eq!(
+            *receiver.borrow_and_update(),
+            ConnectionState::Disconnected(DisconnectReason::LocalDisconnect)
+        );
+        assert!(!receiver.has_changed().expect("not closed"));
+    }
+
+    #[test]
+    fn repeated_state_is_not_republished() {
+        let machine = ConnectionStateMachine::new();
+        let generation = machine.connecting().connected(SystemTime::UNIX_EPOCH);
+        machine.disconnected(generation, DisconnectReason::LocalDisconnect);
+        let receiver = machine.subscribe();
+
+        machine.disconnected(generation, DisconnectReason::LocalDisconnect);
+        assert!(!receiver.has_changed().expect("not closed"));
+    }
+
+    #[test]
+    fn stale_disconnect_does_not_overwrite_newer_connection() {
+        let machine = ConnectionStateMachine::new();
+        let old = machine.connecting().connected(SystemTime::UNIX_EPOCH);
+
+        let since = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let _new = machine.connecting().connected(since);
+
+        machine.disconnected(old, DisconnectReason::ConnectionLost);
+        assert_eq!(machine.current(), ConnectionState::Connected { since });
+    }
+
+    #[test]
+    fn superseded_attempt_does_not_report_its_outcome() {
+        let machine = ConnectionStateMachine::new();
+        let old = machine.connecting();
+        let new = machine.connecting();
+
+        old.failed(FailurePhase::Transport);
+        assert_matches!(machine.current(), ConnectionState::Connecting { attempt } if attempt.get() == 1);
+
+        let since = SystemTime::UNIX_EPOCH;
+        new.connected(since);
+        assert_eq!(machine.current(), ConnectionState::Connected { since });
+    }
+
+    #[test]
+    fn cancelled_attempt_resets_state() {
+        let machine = ConnectionStateMachine::new();
+        drop(machine.connecting());
+        assert_eq!(
+            machine.current(),
+            ConnectionState::Disconnected(DisconnectReason::LocalDisconnect)
+        );
+
+        // A cancelled attempt isn't a failure.
+        let _attempt = machine.connecting();
+        assert_matches!(machine.current(), ConnectionState::Connecting { attempt } if attempt.get() == 1);
+    }
+}
@@ -25,7 +25,11 @@ use tokio::task::JoinHandle;
 use tokio::time::Duration;
 use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
 
-use crate::chat::{ChatMessageType, MessageProto, Request, RequestProto, Response, ResponseProto};
+use crate::chat::{
+    ChatMessageType, CorrelationId, MessageProto, Priority, Request, RequestProto, Response,
+    ResponseProto,
+};
+use crate::connection_quality::RoundTripEstimator;
 use crate::env::ALERT_HEADER_NAME;
 use crate::infra::ws::TextOrBinary;
 use crate::infra::ws2::{MessageEvent, NextEventError, TungsteniteSendError};
@@ -158,6 +162,7 @@ impl Chat {
         config: Config,
         log_tag: Arc<str>,
         mut listener: EventListener,
+        round_trip: Arc<RoundTripEstimator>,
     ) -> Self
     where
         T: WebSocketStreamLike + Send + 'static,
@@ -184,6 +189,7 @@ impl Chat {
             initial_request_id,
             log_tag,
             listener,
+            round_trip,
             tokio_runtime,
         )
     }
@@ -208,6 +214,17 @@ impl Chat {
     /// If the request can't be sent or the response isn't received, this
     /// returns an error.
     pub async fn send(&self, request: Request) -> Result<Response, SendError> {
+        self.start_send(request).await?.await
+    }
+
+    /// Sends a request to the server without waiting for the response.
+    ///
+    /// This returns once the request has been handed off to the connection's
+    /// task, so requests are sent in the order their `start_send` calls
+    /// complete (within each [`Priority`]). Their responses can then be
+    /// awaited in any order, which lets several requests be outstanding at
+    /// once on the same connection.
+    pub async fn start_send(&self, request: Request) -> Result<PendingResponse, SendError> {
         let Self { state } = self;
 
         let Request {
@@ -215,6 +232,9 @@ impl Chat {
             body,
             headers,
             path,
+            priority,
+            correlation_id,
+            gzip_body: _,
         } = request;
         let headers = headers
             .iter()
@@ -229,7 +249,8 @@ impl Chat {
             headers,
         };
 
-        send_request(state, request).await
+        let correlation_id = correlation_id.unwrap_or_else(CorrelationId::random);
+        start_send_request(state, request, priority, correlation_id).await
     }
 
     /// Requests a graceful disconnect from the server.
@@ -301,9 +322,15 @@ impl Chat {
         initial_request_id: u64,
         log_tag: Arc<str>,
         listener: EventListener,
+        round_trip: Arc<RoundTripEstimator>,
         tokio_runtime: tokio::runtime::Handle,
     ) -> Self {
-        let (request_tx, request_rx) = mpsc::channel(1);
+        let (interactive_tx, interactive_rx) = mpsc::channel(1);
+        let (background_tx, background_rx) = mpsc::channel(1);
+        let request_tx = RequestSenders {
+            interactive: interactive_tx,
+            background: background_tx,
+        };
         let (response_tx, response_rx) = mpsc::unbounded_channel();
 
         let requests_in_flight = InFlightRequests {
@@ -312,11 +339,20 @@ impl Chat {
         };
 
         let mut request_id = initial_request_id;
-        let request_rx = ReceiverStream::new(request_rx).map(move |request: OutgoingRequest| {
+        let log_tag_for_requests = log_tag.clone();
+        let request_rx = PrioritizedRequests::new(
+            ReceiverStream::new(interactive_rx),
+            ReceiverStream::new(background_rx),
+        )
+        .map(move |request: OutgoingRequest| {
             let id = {
                 let next_id = request_id.wrapping_add(1);
                 std::mem::replace(&mut request_id, next_id)
             };
+            log::trace!(
+                "[{log_tag_for_requests}] [{}] sending as outgoing request {id}",
+                request.correlation_id
+            );
             let (message, meta) = request.make_message(id);
 
             (message, meta)
@@ -340,6 +376,7 @@ impl Chat {
         let connection = ConnectionImpl {
             inner: inner_connection,
             requests_in_flight,
+            round_trip,
         };
 
         let task = tokio_runtime.spawn(spawned_task_body(
@@ -386,7 +423,7 @@ impl Responder {
 enum TaskState {
     /// The task isn't known to have finished, and might still be listening for events.
     MaybeStillRunning {
-        request_tx: mpsc::Sender<OutgoingRequest>,
+        request_tx: RequestSenders,
         response_tx: mpsc::UnboundedSender<OutgoingResponse>,
         task: JoinHandle<Result<FinishReason, TaskErrorState>>,
     },
@@ -397,6 +434,91 @@ enum TaskState {
     Finished(Result<FinishReason, TaskErrorState>),
 }
 
+/// The sending halves of the per-[`Priority`] queues read by the spawned task.
+#[derive(Debug)]
+struct RequestSenders {
+    interactive: mpsc::Sender<OutgoingRequest>,
+    background: mpsc::Sender<OutgoingRequest>,
+}
+
+impl RequestSenders {
+    fn for_priority(&self, priority: Priority) -> &mpsc::Sender<OutgoingRequest> {
+        match priority {
+            Priority::Interactive => &self.interactive,
+            Priority::Background => &self.background,
+        }
+    }
+}
+
+/// The maximum number of interactive requests that will be sent in a row while
+/// a background request is waiting.
+const MAX_CONSECUTIVE_INTERACTIVE_REQUESTS: usize = 8;
+
+/// Merges the per-[`Priority`] request streams.
+///
+/// Interactive requests are always preferred, except that a waiting background
+/// request is let through after [`MAX_CONSECUTIVE_INTERACTIVE_REQUESTS`]
+/// interactive ones so that background work isn't starved. The stream ends
+/// when both inputs have ended.
+#[pin_project]
+struct PrioritizedRequests<S> {
+    #[pin]
+    interactive: futures_util::stream::Fuse<S>,
+    #[pin]
+    background: futures_util::stream::Fuse<S>,
+    consecutive_interactive: usize,
+}
+
+impl<S: Stream> PrioritizedRequests<S> {
+    fn new(interactive: S, background: S) -> Self {
+        Self {
+            interactive: interactive.fuse(),
+            background: background.fuse(),
+            consecutive_interactive: 0,
+        }
+    }
+}
+
+impl<S: Stream> Stream for PrioritizedRequests<S> {
+    type Item = S::Item;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::task::Poll;
+
+        let mut this = self.project();
+
+        if *this.consecutive_interactive >= MAX_CONSECUTIVE_INTERACTIVE_REQUESTS {
+            if let Poll::Ready(Some(item)) = this.background.as_mut().poll_next(cx) {
+                *this.consecutive_interactive = 0;
+                return Poll::Ready(Some(item));
+            }
+        }
+
+        let interactive = this.interactive.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(item)) = interactive {
+            *this.consecutive_interactive += 1;
+            return Poll::Ready(Some(item));
+        }
+
+        match this.background.as_mut().poll_next(cx) {
+            Poll::Ready(Some(item)) => {
+                *this.consecutive_interactive = 0;
+                Poll::Ready(Some(item))
+            }
+            Poll::Ready(None) if interactive.is_ready() => Poll::Ready(None),
+            Poll::Ready(None) | Poll::Pending => {
+                // Nothing is waiting in the background queue, so there's
+                // nothing to starve.
+                *this.consecutive_interactive = 0;
+                Poll::Pending
+            }
+        }
+    }
+}
+
 struct InFlightRequests {
     outstanding_reqs: HashMap<RequestId, oneshot::Sender<Result<Response, TaskSendError>>>,
     log_tag: Arc<str>,
@@ -482,6 +604,7 @@ struct PartialRequestProto {
 struct OutgoingRequest {
     request: PartialRequestProto,
     response_sender: oneshot::Sender<Result<Response, TaskSendError>>,
+    correlation_id: CorrelationId,
 }
 
 struct OutgoingResponse {
@@ -494,6 +617,7 @@ impl OutgoingRequest {
         let Self {
             request,
             response_sender,
+            correlation_id: _,
         } = self;
         let PartialRequestProto {
             verb,
@@ -530,6 +654,8 @@ struct ConnectionImpl<I> {
     #[pin]
     inner: I,
     requests_in_flight: InFlightRequests,
+    /// Updated from the keepalive pings sent by `inner`.
+    round_trip: Arc<RoundTripEstimator>,
 }
 
 /// The metadata for an outgoing message.
@@ -665,10 +791,44 @@ async fn spawned_task_body<I: InnerConnection>(
     task_result
 }
 
-async fn send_request(
+/// A response to a request sent with [`Chat::start_send`] that hasn't arrived
+/// yet.
+///
+/// Dropping this abandons the request: the connection stops tracking it the
+/// next time it sends a request or receives a response, and discards the
+/// response if it arrives later. If the connection ends first, this resolves
+/// to an error.
+#[derive(Debug)]
+pub struct PendingResponse {
+    receiver: oneshot::Receiver<Result<Response, TaskSendError>>,
+}
+
+impl Future for PendingResponse {
+    type Output = Result<Response, SendError>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|result| {
+            // The sender is dropped without a response if the connection
+            // ends before one arrives.
+            let response =
+                result.map_err(|_: oneshot::error::RecvError| SendError::Disconnected {
+                    #[cfg(test)]
+                    reason: "response channel sender was dropped",
+                })?;
+            response.map_err(SendError::from)
+        })
+    }
+}
+
+async fn start_send_request(
     state: &TokioMutex<TaskState>,
     request: PartialRequestProto,
-) -> Result<Response, SendError> {
+    priority: Priority,
+    correlation_id: CorrelationId,
+) -> Result<PendingResponse, SendError> {
     // Use a block to limit the scope of the lock guard's lifetime. We don't
     // want the lock to be held for the entire send, just the outgoing bit.
     let tx = {
@@ -677,7 +837,7 @@ async fn send_request(
                 request_tx,
                 response_tx: _,
                 task: _,
-            } => request_tx.clone(),
+            } => request_tx.for_priority(priority).clone(),
             TaskState::SignaledToEnd(_) => {
                 return Err(SendError::Disconnected {
                     #[cfg(test)]
@@ -700,19 +860,14 @@ async fn send_request(
         .send(OutgoingRequest {
             request,
             response_sender: sender,
+            correlation_id,
         })
         .await
         .is_ok()
     {
-        // The request was sent, now wait for the response to be sent back.
-        let response =
-            receiver
-                .await
-                .map_err(|_: oneshot::error::RecvError| SendError::Disconnected {
-                    #[cfg(test)]
-                    reason: "response channel sender was dropped",
-                })?;
-        response.map_err(SendError::from)
+        // The request was handed off; the response will be sent back through
+        // the channel.
+        Ok(PendingResponse { receiver })
     } else {
         // The request couldn't be sent to the task. We could give up now
         // and return SendError::Disconnected but that's not as useful as
@@ -787,10 +942,13 @@ impl InFlightRequests {
         id: RequestId,
         response_sender: oneshot::Sender<Result<Response, TaskSendError>>,
     ) {
+        self.remove_abandoned();
         let Self {
             outstanding_reqs,
             log_tag: _,
         } = self;
+        // IDs come from a wrapping 64-bit counter, so a collision would need
+        // 2^64 requests to be outstanding at once.
         let prev = outstanding_reqs.insert(id, response_sender);
         assert!(
             prev.is_none(),
@@ -807,11 +965,32 @@ impl InFlightRequests {
         if let Some(sender) = outstanding_reqs.remove(&id) {
             let _ignore_send_error = sender.send(result);
         } else {
-            log::error!(
-                "[{log_tag}] tried to send response to nonexistent request {}",
+            // Also the case for requests whose PendingResponse was dropped,
+            // since those are removed by remove_abandoned.
+            log::info!(
+                "[{log_tag}] got response to request {}, which is no longer outstanding",
                 id.0
             );
         }
+        self.remove_abandoned();
+    }
+
+    /// Forgets requests whose [`PendingResponse`] was dropped.
+    ///
+    /// The task can't be interrupted while it waits for the next event, so
+    /// this happens whenever a request is sent or a response arrives, rather
+    /// than as soon as the `PendingResponse` is dropped.
+    fn remove_abandoned(&mut self) {
+        let Self {
+            outstanding_reqs,
+            log_tag,
+        } = self;
+        let before = outstanding_reqs.len();
+        outstanding_reqs.retain(|_id, sender| !sender.is_closed());
+        let abandoned = before - outstanding_reqs.len();
+        if abandoned != 0 {
+            log::debug!("[{log_tag}] forgot {abandoned} abandoned request(s)");
+        }
     }
 }
 
@@ -883,15 +1062,17 @@ impl<I: InnerConnection> ConnectionImpl<I> {
         let ConnectionImplProj {
             mut inner,
             requests_in_flight,
+            round_trip,
         } = self.project();
 
         let inner_event = inner.as_mut().handle_next_event().await;
 
-        Self::handle_inner_response(requests_in_flight, inner_event)
+        Self::handle_inner_response(requests_in_flight, round_trip, inner_event)
     }
 
     fn handle_inner_response(
         requests_in_flight: &mut InFlightRequests,
+        round_trip: &RoundTripEstimator,
         event: Outcome<MessageEvent<OutgoingMeta>, Result<FinishReason, NextEventError>>,
     ) -> Outcome<Option<IncomingEvent>, Result<FinishReason, TaskExitError>> {
         let log_tag = &requests_in_flight.log_tag;
@@ -901,6 +1082,12 @@ impl<I: InnerConnection> ConnectionImpl<I> {
                 return Outcome::Finished(Err(TaskExitError::WebsocketError(err)))
             }
             Outcome::Continue(MessageEvent::SentPing | MessageEvent::ReceivedPingPong) => {}
+            Outcome::Continue(MessageEvent::ReceivedPong {
+                round_trip: measured,
+            }) => {
+                log::debug!("[{log_tag}] keepalive round trip took {measured:?}");
+                round_trip.record_sample(measured);
+            }
             Outcome::Continue(MessageEvent::SentMessage(OutgoingMeta::SentRequest(
                 id,
                 response_sender,
@@ -1149,18 +1336,20 @@ impl From<&TungsteniteSendError> for SendError {
 
 impl From<TaskExitError> for crate::chat::SendError {
     fn from(value: TaskExitError) -> Self {
-        crate::chat::SendError::WebSocket(match value {
+        let websocket_error = match value {
             TaskExitError::WebsocketError(err) => match err {
                 NextEventError::PingFailed(tungstenite_error)
                 | NextEventError::CloseFailed(tungstenite_error) => tungstenite_error.into(),
                 NextEventError::ReceiveError(tungstenite_error) => tungstenite_error.into(),
-                NextEventError::UnexpectedConnectionClose
-                | NextEventError::AbnormalServerClose { .. } => {
-                    WebSocketServiceError::ChannelClosed
+                NextEventError::UnexpectedConnectionClose => {
+                    return Self::ConnectionLost { close_code: None }
                 }
-                NextEventError::ServerIdleTimeout(_duration) => {
-                    WebSocketServiceError::ChannelIdleTooLong
+                NextEventError::AbnormalServerClose { code, reason: _ } => {
+                    return Self::ConnectionLost {
+                        close_code: Some(code.into()),
+                    }
                 }
+                NextEventError::ServerIdleTimeout(_duration) => return Self::ConnectionIdleTimeout,
             },
             TaskExitError::SendIo(error_kind) => {
                 WebSocketServiceError::Io(std::io::Error::new(error_kind, "[redacted]"))
@@ -1173,7 +1362,8 @@ impl From<TaskExitError> for crate::chat::SendError {
             TaskExitError::SendProtocol(protocol_error) => {
                 WebSocketServiceError::Protocol(protocol_error.into())
             }
-        })
+        };
+        Self::WebSocket(websocket_error)
     }
 }
 
@@ -1208,6 +1398,7 @@ mod test {
 
     use assert_matches::assert_matches;
     use futures_util::stream::FuturesUnordered;
+    use futures_util::FutureExt as _;
     use http::HeaderMap;
     use test_case::test_case;
     use tokio::select;
@@ -1272,6 +1463,7 @@ mod test {
                 initial_request_id,
                 "test".into(),
                 listener,
+                Default::default(),
                 tokio::runtime::Handle::current(),
             );
 
@@ -1404,6 +1596,9 @@ mod test {
                     path: PathAndQuery::from_static(path),
                     headers: request_headers.clone(),
                     body: None,
+                    priority: Default::default(),
+                    correlation_id: None,
+                    gzip_body: false,
                 })
             })
             .buffered(REQUEST_PATHS.len())
@@ -1675,6 +1870,9 @@ mod test {
             path: PathAndQuery::from_static("/request"),
             headers: HeaderMap::default(),
             body: None,
+            priority: Default::default(),
+            correlation_id: None,
+            gzip_body: false,
         };
         let send_request = chat.send(request);
         pin_mut!(send_request);
@@ -1739,6 +1937,9 @@ mod test {
                 body: None,
                 headers: Default::default(),
                 path: PathAndQuery::from_static("/"),
+                priority: Default::default(),
+                correlation_id: None,
+                gzip_body: false,
             })
             .await;
         assert_matches!(failed_send, Err(SendError::Disconnected { .. }));
@@ -1772,6 +1973,9 @@ mod test {
                 path: PathAndQuery::from_static("/"),
                 headers: HeaderMap::default(),
                 body: None,
+                priority: Default::default(),
+                correlation_id: None,
+                gzip_body: false,
             });
             Some(send)
         } else {
@@ -1937,6 +2141,9 @@ mod test {
                 path: PathAndQuery::from_static(path),
                 headers: Default::default(),
                 body: None,
+                priority: Default::default(),
+                correlation_id: None,
+                gzip_body: false,
             })
         }));
 
@@ -2067,4 +2274,130 @@ mod test {
         );
         assert_matches!(listener_rx.try_recv(), Err(TryRecvError::Empty));
     }
+
+    #[test_log::test(tokio::test(start_paused = true))]
+    async fn interactive_request_sent_before_earlier_background_request() {
+        let (chat, (mut chat_events, _inner_responses)) = fake::new_chat(Box::new(|_| ()));
+
+        let request = |path, priority| Request {
+            method: Method::GET,
+            path: PathAndQuery::from_static(path),
+            headers: HeaderMap::default(),
+            body: None,
+            priority,
+            correlation_id: None,
+            gzip_body: false,
+        };
+
+        let mut sends = FuturesUnordered::from_iter([
+            chat.send(request("/background", Priority::Background)),
+            chat.send(request("/interactive", Priority::Interactive)),
+        ]);
+        // Get both requests queued before the task gets a chance to pull either
+        // of them.
+        assert_matches!(futures_util::poll!(sends.next()), std::task::Poll::Pending);
+
+        let mut sent_paths = vec![];
+        for _ in 0..2 {
+            let fake::OutgoingMessage(message, _meta) =
+                chat_events.recv().await.expect("not ended");
+            let TextOrBinary::Binary(message) = message else {
+                panic!("expected binary message");
+            };
+            let request = assert_matches!(
+                decode_and_validate(&message),
+                Ok(ChatMessageProto::Request(request)) => request
+            );
+            sent_paths.push(request.path.expect("has path"));
+        }
+
+        assert_eq!(sent_paths, ["/interactive", "/background"]);
+    }
+
+    #[test]
+    fn abandoned_requests_are_forgotten() {
+        let mut requests_in_flight = InFlightRequests {
+            outstanding_reqs: Default::default(),
+            log_tag: "test".into(),
+        };
+        let mut receivers = [1, 2, 3].map(|id| {
+            let (sender, receiver) = oneshot::channel();
+            requests_in_flight.record_send(RequestId(id), sender);
+            Some(receiver)
+        });
+        assert_eq!(requests_in_flight.outstanding_reqs.len(), 3);
+
+        // Dropping the receiver is what dropping a PendingResponse does.
+        receivers[0] = None;
+        let (sender, _receiver) = oneshot::channel();
+        requests_in_flight.record_send(RequestId(4), sender);
+        assert_eq!(
+            requests_in_flight
+                .outstanding_reqs
+                .keys()
+                .map(|id| id.0)
+                .sorted()
+                .collect_vec(),
+            [2, 3, 4]
+        );
+
+        receivers[1] = None;
+        requests_in_flight.finish_send(RequestId(3), Err(TaskSendError::InvalidResponse));
+        assert_matches!(
+            receivers[2].take().expect("present").try_recv(),
+            Ok(Err(TaskSendError::InvalidResponse))
+        );
+        assert_eq!(
+            requests_in_flight
+                .outstanding_reqs
+                .keys()
+                .map(|id| id.0)
+                .collect_vec(),
+            [4]
+        );
+
+        // A late response to an abandoned request is ignored.
+        requests_in_flight.finish_send(RequestId(1), Err(TaskSendError::InvalidResponse));
+    }
+
+    #[test]
+    fn prioritized_requests_drains_interactive_first() {
+        let interactive = futures_util::stream::iter(vec!["i1", "i2", "i3"]);
+        let background = futures_util::stream::iter(vec!["b1", "b2"]);
+
+        let order = PrioritizedRequests::new(interactive, background)
+            .collect::<Vec<_>>()
+            .now_or_never()
+            .expect("all items available");
+
+        assert_eq!(order, ["i1", "i2", "i3", "b1", "b2"]);
+    }
+
+    #[test]
+    fn prioritized_requests_does_not_starve_background() {
+        let interactive_count = 2 * MAX_CONSECUTIVE_INTERACTIVE_REQUESTS + 1;
+        let interactive = futures_util::stream::iter(
+            (0..interactive_count)
+                .map(|i| format!("i{i}"))
+                .collect_vec(),
+        );
+        let background = futures_util::stream::iter(vec!["b0".to_owned(), "b1".to_owned()]);
+
+        let order = PrioritizedRequests::new(interactive, background)
+            .collect::<Vec<_>>()
+            .now_or_never()
+            .expect("all items available");
+
+        let expected = (0..MAX_CONSECUTIVE_INTERACTIVE_REQUESTS)
+            .map(|i| format!("i{i}"))
+            .chain(["b0".to_owned()])
+            .chain(
+                (MAX_CONSECUTIVE_INTERACTIVE_REQUESTS..2 * MAX_CONSECUTIVE_INTERACTIVE_REQUESTS)
+                    .map(|i| format!("i{i}")),
+            )
+            .chain(["b1".to_owned()])
+            .chain([format!("i{}", interactive_count - 1)])
+            .collect_vec();
+        assert_eq!(order, expected);
+    }
 }
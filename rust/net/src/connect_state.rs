@@ -6,6 +6,8 @@
 use std::default::Default;
 use std::fmt::Debug;
 use std::future::Future;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
 use std::ops::ControlFlow;
 use std::sync::Arc;
 use std::time::Duration;
@@ -17,27 +19,34 @@ use libsignal_net_infra::connection_manager::{ErrorClass, ErrorClassifier as _};
 use libsignal_net_infra::dns::DnsResolver;
 use libsignal_net_infra::errors::{LogSafeDisplay, TransportConnectError};
 use libsignal_net_infra::route::{
-    ComposedConnector, ConnectError, ConnectionOutcomeParams, ConnectionOutcomes, Connector,
-    ConnectorFactory, DelayBasedOnTransport, DescribeForLog, DescribedRouteConnector,
+    ComposedConnector, ConnectError, ConnectionOutcomeParams, ConnectionOutcomes, ConnectionRacing,
+    Connector, ConnectorFactory, DelayBasedOnTransport, DescribeForLog, DescribedRouteConnector,
     HttpRouteFragment, ResolveHostnames, ResolveWithSavedDescription, ResolvedRoute, RouteProvider,
     RouteProviderContext, RouteProviderExt as _, RouteResolver, ThrottlingConnector,
     TransportRoute, UnresolvedRouteDescription, UnresolvedTransportRoute,
     UnresolvedWebsocketServiceRoute, UsePreconnect, UsesTransport, WebSocketRouteFragment,
     WebSocketServiceRoute,
 };
+use libsignal_net_infra::tcp_ssl::TlsSessionCache;
 use libsignal_net_infra::timeouts::{TimeoutOr, ONE_ROUTE_CONNECTION_TIMEOUT};
-use libsignal_net_infra::ws::{WebSocketConnectError, WebSocketStreamLike};
+use libsignal_net_infra::ws::{CountingWebSocket, WebSocketConnectError, WebSocketStreamLike};
 use libsignal_net_infra::ws2::attested::AttestedConnection;
 use libsignal_net_infra::{AsHttpHeader as _, AsyncDuplexStream};
 use rand::Rng;
 use rand_core::OsRng;
-use static_assertions::assert_eq_size_val;
 use tokio::time::Instant;
 
 use crate::auth::Auth;
+use crate::data_usage::DataUsage;
 use crate::enclave::{EndpointParams, NewHandshake};
+use crate::metrics::ServiceKind;
+use crate::network_events::NetworkEventLog;
 use crate::ws::WebSocketServiceConnectError;
 
+mod outage;
+use outage::OutageDetector;
+pub use outage::{OutageDetectionConfig, OutageWatch, SUGGESTED_OUTAGE_DETECTION_CONFIG};
+
 /// Suggested values for [`ConnectionOutcomeParams`].
 pub const SUGGESTED_CONNECT_PARAMS: ConnectionOutcomeParams = ConnectionOutcomeParams {
     age_cutoff: Duration::from_secs(5 * 60),
@@ -56,6 +65,59 @@ pub const SUGGESTED_CONNECT_CONFIG: Config = Config {
 /// Suggested lifetime for a [`PreconnectingConnector`] that handles up to a TLS handshake.
 pub const SUGGESTED_TLS_PRECONNECT_LIFETIME: Duration = Duration::from_millis(1500);
 
+/// The kind of network the device is using, as reported by the app.
+///
+/// Used as a hint to adapt connection attempts to the expected network
+/// characteristics.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, num_enum::TryFromPrimitive)]
+#[repr(u8)]
+pub enum NetworkType {
+    #[default]
+    Unknown = 0,
+    Wifi = 1,
+    Cellular = 2,
+    Vpn = 3,
+}
+
+impl NetworkType {
+    /// Adjusts a connect timeout for the expected latency of this kind of
+    /// network.
+    ///
+    /// Cellular and VPN connections are given 50% longer to account for
+    /// slower handshakes.
+    pub fn scale_connect_timeout(self, timeout: Duration) -> Duration {
+        match self {
+            NetworkType::Unknown | NetworkType::Wifi => timeout,
+            NetworkType::Cellular | NetworkType::Vpn => timeout
+                .checked_mul(3)
+                .map_or(Duration::MAX, |scaled| scaled / 2),
+        }
+    }
+
+    /// Whether IPv6 routes should be tried before IPv4 ones.
+    ///
+    /// Cellular networks are frequently IPv6-only with IPv4 provided through
+    /// translation, so native IPv6 is more likely to work well.
+    pub fn prefer_ipv6(self) -> bool {
+        match self {
+            NetworkType::Cellular => true,
+            NetworkType::Unknown | NetworkType::Wifi | NetworkType::Vpn => false,
+        }
+    }
+
+    /// Whether domain-fronted routes should be left out when a direct route
+    /// is available.
+    ///
+    /// A VPN already gets traffic past local network blocking, so racing
+    /// fronted routes against direct ones would only add load.
+    pub fn skip_fronted_fallbacks(self) -> bool {
+        match self {
+            NetworkType::Vpn => true,
+            NetworkType::Unknown | NetworkType::Wifi | NetworkType::Cellular => false,
+        }
+    }
+}
+
 /// Effectively an alias for [`ConnectorFactory`] with connection, route, and error
 /// requirements appropriate for websockets.
 ///
@@ -86,13 +148,37 @@ impl<F, Transport> WebSocketTransportConnectorFactory<Transport> for F where
 pub struct ConnectState<ConnectorFactory = DefaultConnectorFactory> {
     pub route_resolver: RouteResolver,
     /// The amount of time allowed for each connection attempt.
+    ///
+    /// This is further adjusted by [`Self::network_type`].
     pub connect_timeout: Duration,
+    /// The kind of network in use, as reported by the app.
+    ///
+    /// See [`NetworkType`] for how this changes websocket connection
+    /// attempts.
+    pub network_type: NetworkType,
+    /// How connection attempts over different routes are staggered.
+    pub connection_racing: ConnectionRacing,
     /// Transport-level connector used for all connections.
     make_transport_connector: ConnectorFactory,
     /// Record of connection outcomes.
     attempts_record: ConnectionOutcomes<TransportRoute>,
     /// [`RouteProviderContext`] passed to route providers.
     route_provider_context: RouteProviderContextImpl,
+    /// Bytes transferred over connections made with this state.
+    pub data_usage: Arc<DataUsage>,
+    /// Recent connection attempts made with this state.
+    pub network_events: Arc<NetworkEventLog>,
+    /// Notices when no service is reachable, so that connect attempts can
+    /// fail fast.
+    outage_detector: OutageDetector,
+    /// How many times [`Self::reset`] has been called.
+    ///
+    /// Attempts that started before the most recent reset don't record their
+    /// outcomes.
+    reset_count: u64,
+    /// See [`Self::set_route_fault_injector`].
+    #[cfg(any(test, feature = "test-util"))]
+    route_fault_injector: Option<RouteFaultInjector>,
 }
 
 pub type DefaultTransportConnector = ComposedConnector<
@@ -111,7 +197,86 @@ pub struct Config {
     pub connect_timeout: Duration,
 }
 
-pub struct DefaultConnectorFactory;
+/// What [`ConnectState::reset`] discarded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectStateReset {
+    /// The number of routes that were being delayed because of recent
+    /// failures.
+    pub route_failures_cleared: usize,
+    /// Whether connect attempts were failing fast because no service seemed
+    /// to be reachable.
+    pub outage_cleared: bool,
+}
+
+/// Adjustments to how [`ConnectState`] makes connections, for apps and tests
+/// that need something other than [`SUGGESTED_CONNECT_CONFIG`].
+///
+/// The [`Default`] matches the suggested behavior, so that individual fields
+/// can be overridden with struct update syntax.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectConfigOverrides {
+    /// How long to wait before starting another connection attempt, for each
+    /// attempt already in progress.
+    pub per_connection_wait: Duration,
+    /// The most connection attempts to have in progress at once.
+    pub max_concurrent_attempts: usize,
+    /// The amount of time allowed for each connection, across all attempts.
+    ///
+    /// This is still adjusted by [`ConnectState::network_type`].
+    pub connect_timeout: Duration,
+    /// When to treat the network as down and skip connect attempts, or `None`
+    /// to always try.
+    pub outage_detection: Option<OutageDetectionConfig>,
+}
+
+impl Default for ConnectConfigOverrides {
+    fn default() -> Self {
+        let ConnectionRacing {
+            per_connection_wait,
+            max_concurrent_attempts,
+        } = ConnectionRacing::default();
+        Self {
+            per_connection_wait,
+            max_concurrent_attempts: max_concurrent_attempts.get(),
+            connect_timeout: SUGGESTED_CONNECT_CONFIG.connect_timeout,
+            outage_detection: Some(SUGGESTED_OUTAGE_DETECTION_CONFIG),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum InvalidConnectConfig {
+    /// connect timeout must be nonzero
+    ZeroConnectTimeout,
+    /// at least one connection attempt must be allowed at a time
+    ZeroConcurrentAttempts,
+    /// outage detection must require failures from at least two services
+    OutageDetectionForSingleService,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DefaultConnectorFactory {
+    local_bind_address: Option<IpAddr>,
+    tls_session_cache: Option<Arc<TlsSessionCache>>,
+}
+
+impl DefaultConnectorFactory {
+    /// Binds the sockets of connectors made from now on to `address`, or lets
+    /// the OS pick if `None`.
+    pub fn set_local_bind_address(&mut self, address: Option<IpAddr>) {
+        self.local_bind_address = address;
+    }
+
+    /// Lets connectors made from now on resume TLS sessions saved in `cache`,
+    /// or always do a full handshake if `None`.
+    ///
+    /// Only the TLS connection to the service uses the cache, not any TLS
+    /// connection to a proxy.
+    pub fn set_tls_session_cache(&mut self, cache: Option<Arc<TlsSessionCache>>) {
+        self.tls_session_cache = cache;
+    }
+}
+
 impl<R> ConnectorFactory<R> for DefaultConnectorFactory
 where
     DefaultTransportConnector: Connector<R, ()>,
@@ -120,15 +285,234 @@ where
     type Connection = <DefaultTransportConnector as Connector<R, ()>>::Connection;
 
     fn make(&self) -> Self::Connector {
-        let throttle_tls_connections = ThrottlingConnector::new(Default::default(), 1);
-        let proxy_or_direct_connector = Default::default();
+        let throttle_tls_connections = ThrottlingConnector::new(
+            crate::infra::tcp_ssl::StatelessDirect::default()
+                .with_tls_session_cache(self.tls_session_cache.clone()),
+            1,
+        );
+        let proxy_or_direct_connector = crate::infra::route::DirectOrProxy::new(
+            crate::infra::tcp_ssl::StatelessDirect::bound_to(self.local_bind_address),
+            crate::infra::tcp_ssl::proxy::StatelessProxied::bound_to(self.local_bind_address),
+        );
         ComposedConnector::new(throttle_tls_connections, proxy_or_direct_connector)
     }
 }
 
+/// A milestone reached while establishing a websocket connection.
+///
+/// Stages are ordered by how far along a connection attempt they are.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConnectStage {
+    /// A route's hostname was resolved and connecting over it began.
+    DnsResolved,
+    /// The transport (TCP and TLS, possibly through a proxy) was established.
+    TransportEstablished,
+    /// The websocket upgrade completed.
+    WebSocketOpened,
+}
+
+/// Reported to the progress observer passed to
+/// [`ConnectState::connect_ws_reporting_progress`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectProgress {
+    pub stage: ConnectStage,
+    pub at: Instant,
+}
+
+/// Wraps a [`Connector`] to record when each connection attempt starts.
+///
+/// Used to time the handshake of the winning route.
+struct StartTimeConnector<C>(C);
+
+impl<R, Inner, C: Connector<R, Inner>> Connector<R, Inner> for StartTimeConnector<C> {
+    type Connection = (C::Connection, Instant);
+    type Error = C::Error;
+
+    fn connect_over(
+        &self,
+        over: Inner,
+        route: R,
+        log_tag: Arc<str>,
+    ) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send {
+        let started = Instant::now();
+        self.0
+            .connect_over(over, route, log_tag)
+            .map_ok(move |connection| (connection, started))
+    }
+}
+
+/// Passes each [`ConnectStage`] on to a progress observer the first time any
+/// connection attempt reaches it.
+///
+/// Racing attempts reach the same stages at different times, so only the first
+/// arrival is reported, and never a stage before one that was already reported.
+struct ProgressReporter<F>(std::sync::Mutex<(Option<ConnectStage>, F)>);
+
+impl<F: FnMut(ConnectProgress)> ProgressReporter<F> {
+    fn new(on_progress: F) -> Self {
+        Self(std::sync::Mutex::new((None, on_progress)))
+    }
+
+    fn reached(&self, stage: ConnectStage) {
+        let mut guard = self.0.lock().expect("not poisoned");
+        let (furthest, on_progress) = &mut *guard;
+        if furthest.is_some_and(|furthest| furthest >= stage) {
+            return;
+        }
+        *furthest = Some(stage);
+        on_progress(ConnectProgress {
+            stage,
+            at: Instant::now(),
+        });
+    }
+}
+
+/// Wraps a [`Connector`] to tell a [`ProgressReporter`] when an attempt starts
+/// and when it succeeds.
+struct ReportProgress<'a, C, F> {
+    inner: C,
+    on_start: Option<ConnectStage>,
+    on_success: Option<ConnectStage>,
+    progress: &'a ProgressReporter<F>,
+}
+
+impl<R, Inner, C, F> Connector<R, Inner> for ReportProgress<'_, C, F>
+where
+    C: Connector<R, Inner>,
+    F: FnMut(ConnectProgress) + Send,
+{
+    type Connection = C::Connection;
+    type Error = C::Error;
+
+    fn connect_over(
+        &self,
+        over: Inner,
+        route: R,
+        log_tag: Arc<str>,
+    ) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send {
+        if let Some(stage) = self.on_start {
+            self.progress.reached(stage);
+        }
+        self.inner
+            .connect_over(over, route, log_tag)
+            .inspect_ok(move |_| {
+                if let Some(stage) = self.on_success {
+                    self.progress.reached(stage);
+                }
+            })
+    }
+}
+
+/// A failure to simulate instead of attempting a connection; see
+/// [`ConnectState::set_route_fault_injector`].
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InjectedFault {
+    /// The attempt never finishes, so it only ends when another route
+    /// succeeds or the connect timeout expires.
+    Timeout,
+    /// The TCP connection is refused.
+    TcpRefused,
+    /// The TLS handshake fails.
+    TlsFailed,
+    /// The server answers the websocket upgrade request with this status.
+    RejectedByServer(http::StatusCode),
+}
+
+/// Picks the failure, if any, to simulate for a connection attempt over a
+/// route.
+#[cfg(any(test, feature = "test-util"))]
+pub type RouteFaultInjector = Arc<dyn Fn(&RouteInfo) -> Option<InjectedFault> + Send + Sync>;
+
+#[cfg(any(test, feature = "test-util"))]
+impl InjectedFault {
+    async fn into_result<T>(self) -> Result<T, WebSocketConnectError> {
+        let error = match self {
+            Self::Timeout => return std::future::pending().await,
+            Self::TcpRefused => {
+                WebSocketConnectError::Transport(TransportConnectError::TcpConnectionFailed)
+            }
+            Self::TlsFailed => {
+                WebSocketConnectError::Transport(TransportConnectError::SslFailedHandshake(
+                    std::io::ErrorKind::ConnectionReset.into(),
+                ))
+            }
+            Self::RejectedByServer(status) => {
+                let mut response = http::Response::new(None);
+                *response.status_mut() = status;
+                WebSocketConnectError::WebSocketError(tungstenite::Error::Http(response))
+            }
+        };
+        Err(error)
+    }
+}
+
+/// Wraps a [`Connector`] to fail the attempts picked by a
+/// [`RouteFaultInjector`] without making them.
+#[cfg(any(test, feature = "test-util"))]
+struct FaultInjectingConnector<C> {
+    inner: C,
+    injector: Option<RouteFaultInjector>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl<R, Inner, C>
+    Connector<crate::infra::route::WithLoggableDescription<R, UnresolvedRouteDescription>, Inner>
+    for FaultInjectingConnector<C>
+where
+    C: Connector<
+        crate::infra::route::WithLoggableDescription<R, UnresolvedRouteDescription>,
+        Inner,
+        Error = WebSocketConnectError,
+    >,
+{
+    type Connection = C::Connection;
+    type Error = WebSocketConnectError;
+
+    fn connect_over(
+        &self,
+        over: Inner,
+        route: crate::infra::route::WithLoggableDescription<R, UnresolvedRouteDescription>,
+        log_tag: Arc<str>,
+    ) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send {
+        let info = RouteInfo {
+            unresolved: route.description.clone(),
+        };
+        match self.injector.as_ref().and_then(|injector| injector(&info)) {
+            None => {
+                futures_util::future::Either::Left(self.inner.connect_over(over, route, log_tag))
+            }
+            Some(fault) => {
+                log::info!("[{log_tag}] injecting {fault:?} for {info}");
+                futures_util::future::Either::Right(fault.into_result())
+            }
+        }
+    }
+}
+
 impl ConnectState {
     pub fn new(config: Config) -> tokio::sync::RwLock<Self> {
-        Self::new_with_transport_connector(config, DefaultConnectorFactory)
+        Self::new_with_transport_connector(config, DefaultConnectorFactory::default())
+    }
+}
+
+impl ConnectState<PreconnectingFactory> {
+    /// Binds the sockets of future connections to `address`, or lets the OS
+    /// pick if `None`.
+    ///
+    /// Connections that are already established, including saved preconnected
+    /// ones, are unaffected.
+    pub fn set_local_bind_address(&mut self, address: Option<IpAddr>) {
+        self.make_transport_connector
+            .inner_factory_mut()
+            .set_local_bind_address(address);
+    }
+
+    /// See [`DefaultConnectorFactory::set_tls_session_cache`].
+    pub fn set_tls_session_cache(&mut self, cache: Option<Arc<TlsSessionCache>>) {
+        self.make_transport_connector
+            .inner_factory_mut()
+            .set_tls_session_cache(cache);
     }
 }
 
@@ -144,15 +528,132 @@ impl<ConnectorFactory> ConnectState<ConnectorFactory> {
         Self {
             route_resolver: RouteResolver::default(),
             connect_timeout,
+            network_type: NetworkType::default(),
+            connection_racing: ConnectionRacing::default(),
             make_transport_connector,
             attempts_record: ConnectionOutcomes::new(connect_params),
             route_provider_context: RouteProviderContextImpl::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(Some(SUGGESTED_OUTAGE_DETECTION_CONFIG)),
+            reset_count: 0,
+            #[cfg(any(test, feature = "test-util"))]
+            route_fault_injector: None,
         }
         .into()
     }
 
+    /// Discards everything learned from previous connection attempts and
+    /// returns to `config` with default settings.
+    ///
+    /// Unlike [`Self::network_changed`], this also undoes
+    /// [`Self::set_config_overrides`] and any changes to the route resolver
+    /// other than whether IPv6 is allowed. The network type, data usage,
+    /// recent network events, and transport connector are kept.
+    ///
+    /// Attempts already in progress finish with the state they started with,
+    /// but their outcomes are not recorded.
+    pub fn reset(&mut self, config: Config, now: Instant) -> ConnectStateReset {
+        let Config {
+            connect_params,
+            connect_timeout,
+        } = config;
+        let summary = ConnectStateReset {
+            route_failures_cleared: self.attempts_record.failing_route_count(),
+            outage_cleared: self.outage_detector.in_outage(now),
+        };
+
+        self.route_resolver = RouteResolver {
+            allow_ipv6: self.route_resolver.allow_ipv6,
+            ..RouteResolver::default()
+        };
+        self.connect_timeout = connect_timeout;
+        self.connection_racing = ConnectionRacing::default();
+        self.attempts_record = ConnectionOutcomes::new(connect_params);
+        // Replace the configuration in place so that outage watchers aren't
+        // cut off.
+        self.outage_detector
+            .set_config(Some(SUGGESTED_OUTAGE_DETECTION_CONFIG));
+        self.reset_count += 1;
+
+        summary
+    }
+
+    /// Replaces the randomness used to pick between routes with `rng`, so that
+    /// tests can predict which routes will be attempted.
+    ///
+    /// Together with [`Self::set_config_overrides`] (to control how attempts
+    /// are staggered) and paused Tokio time, this makes the order of
+    /// connection attempts deterministic. The replacement survives
+    /// [`Self::reset`].
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn set_route_randomness_for_testing(&mut self, rng: impl rand::RngCore + Send + 'static) {
+        self.route_provider_context = RouteProviderContextImpl {
+            deterministic: Some(Arc::new(std::sync::Mutex::new(rng))),
+        };
+    }
+
+    /// Consults `injector`, if set, before each websocket connection attempt,
+    /// so that tests can make specific routes fail without a real network.
+    ///
+    /// Routes for which `injector` returns `None` are connected to as usual.
+    /// Preconnects are not affected. The injector survives [`Self::reset`].
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn set_route_fault_injector(&mut self, injector: Option<RouteFaultInjector>) {
+        self.route_fault_injector = injector;
+    }
+
     pub fn network_changed(&mut self, network_change_time: Instant) {
         self.attempts_record.reset(network_change_time);
+        self.outage_detector.reset();
+    }
+
+    /// If connect attempts are currently failing fast because the network
+    /// seems to be down, when they'll start trying routes again.
+    ///
+    /// A call to [`Self::network_changed`] ends the outage early.
+    pub fn outage_ends_at(&self, now: Instant) -> Option<Instant> {
+        self.outage_detector.outage_ends_at(now)
+    }
+
+    /// Follows the outages detected by connect attempts made with this state.
+    ///
+    /// The watch holds when the current outage stops blocking connect
+    /// attempts, or `None` when there isn't one. It's cleared early by
+    /// [`Self::network_changed`] and [`Self::reset`], and keeps working across
+    /// both.
+    pub fn outage_watch(&self) -> OutageWatch {
+        self.outage_detector.subscribe()
+    }
+
+    /// Applies `overrides` to all subsequent connection attempts.
+    ///
+    /// Leaves the current configuration unchanged if `overrides` is invalid.
+    pub fn set_config_overrides(
+        &mut self,
+        overrides: ConnectConfigOverrides,
+    ) -> Result<(), InvalidConnectConfig> {
+        let ConnectConfigOverrides {
+            per_connection_wait,
+            max_concurrent_attempts,
+            connect_timeout,
+            outage_detection,
+        } = overrides;
+        if connect_timeout.is_zero() {
+            return Err(InvalidConnectConfig::ZeroConnectTimeout);
+        }
+        let max_concurrent_attempts = NonZeroUsize::new(max_concurrent_attempts)
+            .ok_or(InvalidConnectConfig::ZeroConcurrentAttempts)?;
+        if outage_detection.is_some_and(|config| config.min_distinct_services < 2) {
+            return Err(InvalidConnectConfig::OutageDetectionForSingleService);
+        }
+        self.outage_detector.set_config(outage_detection);
+        self.connect_timeout = connect_timeout;
+        self.connection_racing = ConnectionRacing {
+            per_connection_wait,
+            max_concurrent_attempts,
+        };
+        Ok(())
     }
 }
 
@@ -170,6 +671,11 @@ impl std::fmt::Display for RouteInfo {
 }
 
 impl RouteInfo {
+    /// See [`UnresolvedRouteDescription::route_kind`].
+    pub fn route_kind(&self) -> &'static str {
+        self.unresolved.route_kind()
+    }
+
     pub fn fake() -> Self {
         Self {
             unresolved: UnresolvedRouteDescription::fake(),
@@ -177,15 +683,31 @@ impl RouteInfo {
     }
 }
 
+/// Removes domain-fronted routes from `routes` if there's a direct route to
+/// use instead.
+fn drop_fronted_fallbacks<R: DescribeForLog<Description = UnresolvedRouteDescription>>(
+    routes: &mut Vec<R>,
+) {
+    let is_kind = |route: &R, kind| route.describe_for_log().route_kind() == kind;
+    if routes.iter().any(|route| is_kind(route, "direct")) {
+        routes.retain(|route| !is_kind(route, "domain_fronted"));
+    }
+}
+
 /// A snapshot of [`ConnectState`] for a particular connection attempt.
 ///
 /// "Like `ConnectState`, but with a single instantiated connector."
 struct ConnectStateSnapshot<C> {
     route_resolver: RouteResolver,
     connect_timeout: Duration,
+    connection_racing: ConnectionRacing,
+    skip_fronted_fallbacks: bool,
     transport_connector: C,
     attempts_record: ConnectionOutcomes<TransportRoute>,
     route_provider_context: RouteProviderContextImpl,
+    reset_count: u64,
+    #[cfg(any(test, feature = "test-util"))]
+    route_fault_injector: Option<RouteFaultInjector>,
 }
 
 impl<TC> ConnectState<TC> {
@@ -196,20 +718,41 @@ impl<TC> ConnectState<TC> {
         let Self {
             route_resolver,
             connect_timeout,
+            network_type,
+            connection_racing,
             make_transport_connector,
             attempts_record,
             route_provider_context,
+            data_usage: _,
+            network_events: _,
+            outage_detector: _,
+            reset_count,
+            #[cfg(any(test, feature = "test-util"))]
+            route_fault_injector,
         } = self;
 
         ConnectStateSnapshot {
-            route_resolver: route_resolver.clone(),
-            connect_timeout: *connect_timeout,
+            route_resolver: RouteResolver {
+                prefer_ipv6: route_resolver.prefer_ipv6 || network_type.prefer_ipv6(),
+                ..route_resolver.clone()
+            },
+            connect_timeout: network_type.scale_connect_timeout(*connect_timeout),
+            connection_racing: *connection_racing,
+            skip_fronted_fallbacks: network_type.skip_fronted_fallbacks(),
             transport_connector: make_transport_connector.make(),
             attempts_record: attempts_record.clone(),
             route_provider_context: route_provider_context.clone(),
+            reset_count: *reset_count,
+            #[cfg(any(test, feature = "test-util"))]
+            route_fault_injector: route_fault_injector.clone(),
         }
     }
 
+    /// Connects to `service` over one of `routes`.
+    ///
+    /// While no service has been reachable recently (see
+    /// [`OutageDetectionConfig`]), fails immediately with
+    /// [`ConnectError::AllAttemptsFailed`] instead.
     pub async fn connect_ws<WC, UR, Transport>(
         this: &tokio::sync::RwLock<Self>,
         routes: impl RouteProvider<Route = UR>,
@@ -217,6 +760,7 @@ impl<TC> ConnectState<TC> {
         resolver: &DnsResolver,
         confirmation_header_name: Option<&HeaderName>,
         log_tag: Arc<str>,
+        service: ServiceKind,
     ) -> Result<(WC::Connection, RouteInfo), TimeoutOr<ConnectError<WebSocketServiceConnectError>>>
     where
         UR: ResolveHostnames<Resolved = WebSocketServiceRoute<Transport>>
@@ -237,15 +781,89 @@ impl<TC> ConnectState<TC> {
             > + Send
             + Sync,
     {
+        Self::connect_ws_reporting_progress(
+            this,
+            routes,
+            ws_connector,
+            resolver,
+            confirmation_header_name,
+            log_tag,
+            service,
+            |_| {},
+        )
+        .await
+        .map(|(connection, info, _handshake)| (connection, info))
+    }
+
+    /// Like [`ConnectState::connect_ws`], but reports progress to
+    /// `on_progress` as the connection is established.
+    ///
+    /// Each stage is reported once, as soon as the first attempt reaches it,
+    /// whichever route that attempt is for. If the connection fails, the last
+    /// stage reported is the furthest any attempt got.
+    ///
+    /// Also returns how long the winning route took from the start of its
+    /// transport connection to the completion of the websocket upgrade.
+    pub async fn connect_ws_reporting_progress<WC, UR, Transport>(
+        this: &tokio::sync::RwLock<Self>,
+        routes: impl RouteProvider<Route = UR>,
+        ws_connector: WC,
+        resolver: &DnsResolver,
+        confirmation_header_name: Option<&HeaderName>,
+        log_tag: Arc<str>,
+        service: ServiceKind,
+        on_progress: impl FnMut(ConnectProgress) + Send,
+    ) -> Result<
+        (WC::Connection, RouteInfo, Duration),
+        TimeoutOr<ConnectError<WebSocketServiceConnectError>>,
+    >
+    where
+        UR: ResolveHostnames<Resolved = WebSocketServiceRoute<Transport>>
+            + DescribeForLog<Description = UnresolvedRouteDescription>
+            + Clone
+            + 'static,
+        Transport: Clone + Send + UsesTransport + ResolvedRoute,
+        // Note that we're not using WebSocketTransportConnectorFactory here to make `connect_ws`
+        // easier to test; specifically, the output is not guaranteed to be an AsyncDuplexStream.
+        TC: ConnectorFactory<
+            Transport,
+            Connector: Sync + Connector<Transport, (), Error: Into<WebSocketConnectError>>,
+        >,
+        WC: Connector<
+                (WebSocketRouteFragment, HttpRouteFragment),
+                TC::Connection,
+                Error = tungstenite::Error,
+            > + Send
+            + Sync,
+    {
+        let (snapshot, in_outage) = {
+            let guard = this.read().await;
+            (
+                guard.snapshot(),
+                guard.outage_detector.in_outage(Instant::now()),
+            )
+        };
+        if in_outage {
+            log::info!("[{log_tag}] skipping connection attempt while no service is reachable");
+            return Err(TimeoutOr::Other(ConnectError::AllAttemptsFailed));
+        }
         let ConnectStateSnapshot {
             route_resolver,
             connect_timeout,
+            connection_racing,
+            skip_fronted_fallbacks,
             transport_connector,
             attempts_record,
             route_provider_context,
-        } = this.read().await.snapshot();
-
-        let routes = routes.routes(&route_provider_context).collect_vec();
+            reset_count,
+            #[cfg(any(test, feature = "test-util"))]
+            route_fault_injector,
+        } = snapshot;
+
+        let mut routes = routes.routes(&route_provider_context).collect_vec();
+        if skip_fronted_fallbacks {
+            drop_fronted_fallbacks(&mut routes);
+        }
 
         log::info!(
             "[{log_tag}] starting connection attempt with {} routes",
@@ -253,13 +871,34 @@ impl<TC> ConnectState<TC> {
         );
 
         let route_provider = routes.into_iter().map(ResolveWithSavedDescription);
-        let connector =
-            DescribedRouteConnector(ComposedConnector::new(ws_connector, &transport_connector));
+        let progress = ProgressReporter::new(on_progress);
+        let connector = DescribedRouteConnector(StartTimeConnector(ReportProgress {
+            inner: ComposedConnector::new(
+                ReportProgress {
+                    inner: ws_connector,
+                    on_start: Some(ConnectStage::TransportEstablished),
+                    on_success: Some(ConnectStage::WebSocketOpened),
+                    progress: &progress,
+                },
+                &transport_connector,
+            ),
+            on_start: Some(ConnectStage::DnsResolved),
+            on_success: None,
+            progress: &progress,
+        }));
+        #[cfg(any(test, feature = "test-util"))]
+        let connector = FaultInjectingConnector {
+            inner: connector,
+            injector: route_fault_injector,
+        };
         let delay_policy = DelayBasedOnTransport(attempts_record);
 
         let start = Instant::now();
+        let mut last_proxy_failure = None;
+        let mut reached_server = false;
         let connect = crate::infra::route::connect(
             &route_resolver,
+            connection_racing,
             delay_policy,
             route_provider,
             resolver,
@@ -273,18 +912,37 @@ impl<TC> ConnectState<TC> {
                     Instant::now(),
                 );
                 log::debug!("[{log_tag}] connection attempt failed with {error}");
+                reached_server |= !error.is_transport_failure();
                 match error.classify() {
-                    ErrorClass::Intermittent => ControlFlow::Continue(()),
+                    ErrorClass::Intermittent => {
+                        if error.proxy_failure().is_some() {
+                            last_proxy_failure = Some(error);
+                        }
+                        ControlFlow::Continue(())
+                    }
                     ErrorClass::Fatal | ErrorClass::RetryAt(_) => ControlFlow::Break(error),
                 }
             },
         );
 
-        let (result, updates) = tokio::time::timeout(connect_timeout, connect)
-            .await
-            .map_err(|_: tokio::time::error::Elapsed| TimeoutOr::Timeout {
+        let Ok((mut result, updates)) = tokio::time::timeout(connect_timeout, connect).await else {
+            this.write()
+                .await
+                .outage_detector
+                .record_unreachable(service, Instant::now());
+            return Err(TimeoutOr::Timeout {
                 attempt_duration: connect_timeout,
-            })?;
+            });
+        };
+
+        // When a proxy is in use, every route goes through it, so a proxy
+        // failure is more useful to report than the fact that all attempts
+        // failed.
+        if let (Err(e @ ConnectError::AllAttemptsFailed), Some(proxy_failure)) =
+            (&mut result, last_proxy_failure)
+        {
+            *e = ConnectError::FatalConnect(proxy_failure);
+        }
 
         match &result {
             Ok((_connection, route)) => log::info!(
@@ -294,20 +952,46 @@ impl<TC> ConnectState<TC> {
             Err(e) => log::info!("[{log_tag}] connection failed with {e}"),
         }
 
-        this.write().await.attempts_record.apply_outcome_updates(
-            updates
-                .outcomes
-                .into_iter()
-                .map(|(route, outcome)| (route.into_transport_part(), outcome)),
-            updates.finished_at,
-        );
+        {
+            let mut guard = this.write().await;
+            if guard.reset_count != reset_count {
+                log::info!(
+                    "[{log_tag}] not recording outcomes from before the connect state was reset"
+                );
+            } else {
+                guard.attempts_record.apply_outcome_updates(
+                    updates
+                        .outcomes
+                        .into_iter()
+                        .map(|(route, outcome)| (route.into_transport_part(), outcome)),
+                    updates.finished_at,
+                );
+                // Only attempts that never got past the transport count
+                // toward an outage; a server that answers with an error
+                // (like a 5xx) is still reachable.
+                match &result {
+                    Ok(_) | Err(ConnectError::FatalConnect(_)) => {
+                        guard.outage_detector.record_reachable()
+                    }
+                    Err(ConnectError::AllAttemptsFailed) if reached_server => {
+                        guard.outage_detector.record_reachable()
+                    }
+                    Err(ConnectError::AllAttemptsFailed | ConnectError::NoResolvedRoutes) => guard
+                        .outage_detector
+                        .record_unreachable(service, updates.finished_at),
+                }
+            }
+        }
 
-        let (connection, description) = result?;
+        let ((connection, route_started_at), description) = result?;
         Ok((
             connection,
             RouteInfo {
                 unresolved: description,
             },
+            updates
+                .finished_at
+                .saturating_duration_since(route_started_at),
         ))
     }
 
@@ -320,6 +1004,7 @@ impl<TC> ConnectState<TC> {
         (ws_config, ws_connector): (libsignal_net_infra::ws2::Config, WC),
         log_tag: Arc<str>,
         params: &EndpointParams<'_, E>,
+        service: ServiceKind,
     ) -> Result<(AttestedConnection, RouteInfo), crate::enclave::Error>
     where
         TC: WebSocketTransportConnectorFactory,
@@ -336,6 +1021,7 @@ impl<TC> ConnectState<TC> {
             route.fragment.headers.extend([auth.as_header()]);
             route
         });
+        let transfer_counter = connect.read().await.data_usage.counter(service).clone();
 
         let (ws, route_info) = ConnectState::connect_ws(
             connect,
@@ -344,6 +1030,7 @@ impl<TC> ConnectState<TC> {
             resolver,
             confirmation_header_name.as_ref(),
             log_tag.clone(),
+            service,
         )
         .await
         .map_err(|e| match e {
@@ -356,6 +1043,7 @@ impl<TC> ConnectState<TC> {
             }
         })?;
 
+        let ws = CountingWebSocket::new(ws, transfer_counter);
         let connection =
             AttestedConnection::connect(ws, ws_config, log_tag, move |attestation_message| {
                 E::new_handshake(params, attestation_message)
@@ -380,10 +1068,19 @@ where
         let ConnectStateSnapshot {
             route_resolver,
             connect_timeout,
+            connection_racing,
+            // Transport routes don't say whether they're fronted.
+            skip_fronted_fallbacks: _,
             transport_connector,
             attempts_record,
             route_provider_context,
+            reset_count,
+            #[cfg(any(test, feature = "test-util"))]
+            route_fault_injector,
         } = this.read().await.snapshot::<UsePreconnect<_>>();
+        // Preconnects aren't subject to injected faults.
+        #[cfg(any(test, feature = "test-util"))]
+        drop(route_fault_injector);
 
         let routes = routes
             .map_routes(|r| UsePreconnect {
@@ -428,6 +1125,7 @@ where
         let start = Instant::now();
         let connect = crate::infra::route::connect(
             &route_resolver,
+            connection_racing,
             delay_policy,
             route_provider,
             resolver,
@@ -463,13 +1161,15 @@ where
         {
             let mut connect_write = this.write().await;
 
-            connect_write.attempts_record.apply_outcome_updates(
-                updates
-                    .outcomes
-                    .into_iter()
-                    .map(|(route, outcome)| (route.into_transport_part(), outcome)),
-                updates.finished_at,
-            );
+            if connect_write.reset_count == reset_count {
+                connect_write.attempts_record.apply_outcome_updates(
+                    updates
+                        .outcomes
+                        .into_iter()
+                        .map(|(route, outcome)| (route.into_transport_part(), outcome)),
+                    updates.finished_at,
+                );
+            }
 
             let (
                 UsePreconnect {
@@ -490,15 +1190,31 @@ where
     }
 }
 
-#[derive(Debug, Default, Clone)]
-struct RouteProviderContextImpl(OsRng);
+/// The source of randomness for route providers, such as which SNI to use for
+/// a domain-fronted route.
+///
+/// This is [`OsRng`] unless it was replaced using
+/// [`ConnectState::set_route_randomness_for_testing`].
+#[derive(Default, Clone)]
+struct RouteProviderContextImpl {
+    #[cfg(any(test, feature = "test-util"))]
+    deterministic: Option<Arc<std::sync::Mutex<dyn rand::RngCore + Send>>>,
+}
+
+impl Debug for RouteProviderContextImpl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouteProviderContextImpl")
+            .finish_non_exhaustive()
+    }
+}
 
 impl RouteProviderContext for RouteProviderContextImpl {
     fn random_usize(&self) -> usize {
-        // OsRng is zero-sized, so we're not losing random values by copying it.
-        let mut owned_rng: OsRng = self.0;
-        assert_eq_size_val!(owned_rng, ());
-        owned_rng.gen()
+        #[cfg(any(test, feature = "test-util"))]
+        if let Some(rng) = &self.deterministic {
+            return rng.lock().expect("not poisoned").gen();
+        }
+        OsRng.gen()
     }
 }
 
@@ -518,14 +1234,18 @@ mod test {
     use http::HeaderMap;
     use libsignal_net_infra::certs::RootCertificates;
     use libsignal_net_infra::dns::lookup_result::LookupResult;
+    use libsignal_net_infra::errors::ProxyFailureKind;
     use libsignal_net_infra::host::Host;
     use libsignal_net_infra::route::testutils::ConnectFn;
     use libsignal_net_infra::route::{
-        DirectOrProxyRoute, HttpsTlsRoute, TcpRoute, TlsRoute, TlsRouteFragment, UnresolvedHost,
+        DirectOrProxyRoute, DomainFrontConfig, DomainFrontRouteProvider, HttpVersion,
+        HttpsTlsRoute, RouteProviderExt as _, TcpRoute, TlsRoute, TlsRouteFragment, UnresolvedHost,
         UnresolvedTransportRoute, WebSocketRoute,
     };
     use libsignal_net_infra::{Alpn, DnsSource, RouteType};
     use nonzero_ext::nonzero;
+    use rand::rngs::mock::StepRng;
+    use test_case::test_case;
 
     use super::*;
 
@@ -604,10 +1324,17 @@ mod test {
 
         let state = ConnectState {
             connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
             route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
             make_transport_connector: fake_transport_connector,
             route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
         }
         .into();
 
@@ -618,6 +1345,7 @@ mod test {
             &resolver,
             None,
             "test".into(),
+            ServiceKind::Chat,
         )
         // This previously hung forever due to a deadlock bug.
         .await;
@@ -633,99 +1361,614 @@ mod test {
     }
 
     #[tokio::test(start_paused = true)]
-    async fn connect_ws_timeout() {
-        let ws_connector = crate::infra::ws::Stateless;
+    async fn connect_ws_reports_each_stage_when_first_reached() {
+        const TRANSPORT_DELAY: Duration = Duration::from_secs(1);
+        const WS_DELAY: Duration = Duration::from_secs(2);
+
+        let [failing_route, succeeding_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let ws_connector = ConnectFn(|(), route, _log_tag| {
+            let fail = route
+                == (
+                    failing_route.fragment.clone(),
+                    failing_route.inner.fragment.clone(),
+                );
+            async move {
+                if fail {
+                    return Err(tungstenite::Error::ConnectionClosed);
+                }
+                tokio::time::sleep(WS_DELAY).await;
+                Ok(route)
+            }
+        });
         let resolver = DnsResolver::new_from_static_map(HashMap::from([(
             FAKE_HOST_NAME,
             LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
         )]));
 
-        let always_hangs_connector = ConnectFn(|(), _, _| {
-            std::future::pending::<Result<tokio::io::DuplexStream, WebSocketConnectError>>()
+        let slow_transport_connector = ConnectFn(move |(), _, _| async {
+            tokio::time::sleep(TRANSPORT_DELAY).await;
+            Ok::<_, WebSocketConnectError>(())
         });
 
-        const CONNECT_TIMEOUT: Duration = Duration::from_secs(31);
-
         let state = ConnectState {
-            connect_timeout: CONNECT_TIMEOUT,
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
             route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
-            make_transport_connector: always_hangs_connector,
+            make_transport_connector: slow_transport_connector,
             route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
         }
         .into();
 
-        let [failing_route, succeeding_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
-
-        let connect = ConnectState::connect_ws(
+        let mut progress = vec![];
+        let start = Instant::now();
+        let result = ConnectState::connect_ws_reporting_progress(
             &state,
             vec![failing_route.clone(), succeeding_route.clone()],
             ws_connector,
             &resolver,
             None,
             "test".into(),
-        );
-
-        let start = Instant::now();
-        let result: Result<_, TimeoutOr<ConnectError<_>>> = connect.await;
+            ServiceKind::Chat,
+            |p| progress.push(p),
+        )
+        .await;
 
-        assert_matches!(
-            result,
-            Err(TimeoutOr::Timeout {
-                attempt_duration: CONNECT_TIMEOUT
-            })
+        let (connection, _info, handshake) = result.expect("succeeded");
+        assert_eq!(
+            connection,
+            (succeeding_route.fragment, succeeding_route.inner.fragment)
         );
-        assert_eq!(start.elapsed(), CONNECT_TIMEOUT);
+        assert_eq!(handshake, TRANSPORT_DELAY + WS_DELAY);
+
+        // The failing route is tried first, so it's the one that reaches the
+        // first two stages; the succeeding route doesn't report them again.
+        let [resolved, transport, websocket] = progress.try_into().expect("three stages");
+        assert_eq!(resolved.stage, ConnectStage::DnsResolved);
+        assert_eq!(transport.stage, ConnectStage::TransportEstablished);
+        assert_eq!(websocket.stage, ConnectStage::WebSocketOpened);
+        assert!(resolved.at >= start);
+        assert_eq!(transport.at - resolved.at, TRANSPORT_DELAY);
+        assert!(websocket.at - transport.at >= WS_DELAY);
     }
 
     #[tokio::test(start_paused = true)]
-    async fn preconnect_records_outcomes() {
-        let ws_connector = ConnectFn(|(), route, _log_tag| std::future::ready(Ok(route)));
+    async fn connect_ws_reports_furthest_stage_on_failure() {
+        let ws_connector = ConnectFn(|(), _route, _log_tag| {
+            std::future::ready(Err::<(), _>(tungstenite::Error::ConnectionClosed))
+        });
         let resolver = DnsResolver::new_from_static_map(HashMap::from([(
             FAKE_HOST_NAME,
             LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
         )]));
-
-        let attempts_by_host = Mutex::new(HashMap::<Host<_>, u32>::new());
-        let make_transport_connector = PreconnectingFactory::new(
-            ConnectFn(|(), route: TransportRoute, _| {
-                let host = route.fragment.sni;
-                let result = if host == Host::parse_as_ip_or_domain("fail") {
-                    Err(TransportConnectError::TcpConnectionFailed)
-                } else {
-                    Ok(())
-                };
-                *attempts_by_host
-                    .lock()
-                    .expect("no panic")
-                    .entry(host)
-                    .or_default() += 1;
-                std::future::ready(result)
-            }),
-            Duration::from_secs(60),
-        );
-
-        const CONNECT_TIMEOUT: Duration = Duration::from_secs(31);
+        let transport_connector =
+            ConnectFn(|(), _, _| std::future::ready(Ok::<_, WebSocketConnectError>(())));
 
         let state = ConnectState {
-            connect_timeout: CONNECT_TIMEOUT,
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
             route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
-            make_transport_connector,
+            make_transport_connector: transport_connector,
             route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
         }
         .into();
 
-        let good_transport_route = FAKE_TRANSPORT_ROUTE.clone();
-        let mut bad_transport_route = good_transport_route.clone();
-        bad_transport_route.fragment.sni = Host::parse_as_ip_or_domain("fail");
-
-        ConnectState::preconnect_and_save(
+        let mut progress = vec![];
+        let result = ConnectState::connect_ws_reporting_progress(
             &state,
-            vec![bad_transport_route.clone(), good_transport_route.clone()],
+            (*FAKE_WEBSOCKET_ROUTES).clone().to_vec(),
+            ws_connector,
             &resolver,
-            "preconnect".into(),
-        )
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+            |p: ConnectProgress| progress.push(p.stage),
+        )
+        .await;
+
+        assert_matches!(
+            result,
+            Err(TimeoutOr::Other(ConnectError::AllAttemptsFailed))
+        );
+        assert_eq!(
+            progress,
+            [
+                ConnectStage::DnsResolved,
+                ConnectStage::TransportEstablished
+            ]
+        );
+    }
+
+    #[test_case(NetworkType::Unknown, Duration::from_secs(31); "unscaled")]
+    #[test_case(NetworkType::Cellular, Duration::from_millis(46_500); "cellular")]
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_timeout(network_type: NetworkType, expected_timeout: Duration) {
+        let ws_connector = crate::infra::ws::Stateless;
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let always_hangs_connector = ConnectFn(|(), _, _| {
+            std::future::pending::<Result<tokio::io::DuplexStream, WebSocketConnectError>>()
+        });
+
+        const CONNECT_TIMEOUT: Duration = Duration::from_secs(31);
+
+        let state = ConnectState {
+            connect_timeout: CONNECT_TIMEOUT,
+            network_type,
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: always_hangs_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        }
+        .into();
+
+        let [failing_route, succeeding_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let connect = ConnectState::connect_ws(
+            &state,
+            vec![failing_route.clone(), succeeding_route.clone()],
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        );
+
+        let start = Instant::now();
+        let result: Result<_, TimeoutOr<ConnectError<_>>> = connect.await;
+
+        assert_matches!(
+            result,
+            Err(TimeoutOr::Timeout {
+                attempt_duration
+            }) if attempt_duration == expected_timeout
+        );
+        assert_eq!(start.elapsed(), expected_timeout);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_reports_proxy_failure() {
+        let ws_connector = crate::infra::ws::Stateless;
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let proxy_rejects_connector = ConnectFn(|(), _, _| {
+            std::future::ready(Err::<tokio::io::DuplexStream, _>(
+                WebSocketConnectError::Transport(TransportConnectError::ProxyFailure(
+                    ProxyFailureKind::AuthFailed,
+                )),
+            ))
+        });
+
+        let state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: proxy_rejects_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        }
+        .into();
+
+        let result = ConnectState::connect_ws(
+            &state,
+            (*FAKE_WEBSOCKET_ROUTES).to_vec(),
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        )
+        .await;
+
+        let error = assert_matches!(
+            result,
+            Err(TimeoutOr::Other(ConnectError::FatalConnect(e))) => e
+        );
+        assert_eq!(error.proxy_failure(), Some(ProxyFailureKind::AuthFailed));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_skips_attempts_during_outage() {
+        let ws_connector = crate::infra::ws::Stateless;
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let attempts = Arc::new(Mutex::new(0));
+        let unreachable_connector = ConnectFn({
+            let attempts = attempts.clone();
+            move |(), _, _| {
+                *attempts.lock().unwrap() += 1;
+                std::future::ready(Err::<tokio::io::DuplexStream, _>(
+                    WebSocketConnectError::Transport(TransportConnectError::TcpConnectionFailed),
+                ))
+            }
+        });
+
+        let state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: unreachable_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(Some(OutageDetectionConfig {
+                failure_threshold: 2,
+                ..SUGGESTED_OUTAGE_DETECTION_CONFIG
+            })),
+            reset_count: 0,
+            route_fault_injector: None,
+        }
+        .into();
+
+        for service in [ServiceKind::Chat, ServiceKind::Cdsi] {
+            let result = ConnectState::connect_ws(
+                &state,
+                (*FAKE_WEBSOCKET_ROUTES).to_vec(),
+                ws_connector,
+                &resolver,
+                None,
+                "test".into(),
+                service,
+            )
+            .await;
+            assert_matches!(
+                result,
+                Err(TimeoutOr::Other(ConnectError::AllAttemptsFailed))
+            );
+        }
+        let attempts_before_outage = *attempts.lock().unwrap();
+        assert_ne!(attempts_before_outage, 0);
+
+        let start = Instant::now();
+        let result = ConnectState::connect_ws(
+            &state,
+            (*FAKE_WEBSOCKET_ROUTES).to_vec(),
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        )
+        .await;
+        assert_matches!(
+            result,
+            Err(TimeoutOr::Other(ConnectError::AllAttemptsFailed))
+        );
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(*attempts.lock().unwrap(), attempts_before_outage);
+
+        state.write().await.network_changed(Instant::now());
+        let _ = ConnectState::connect_ws(
+            &state,
+            (*FAKE_WEBSOCKET_ROUTES).to_vec(),
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        )
+        .await;
+        assert!(*attempts.lock().unwrap() > attempts_before_outage);
+    }
+
+    #[test_case(
+        [ServiceKind::Chat, ServiceKind::Cdsi, ServiceKind::Chat, ServiceKind::Cdsi],
+        Some(http::StatusCode::SERVICE_UNAVAILABLE);
+        "server errors"
+    )]
+    #[test_case(
+        [ServiceKind::Chat; 4],
+        None;
+        "single service"
+    )]
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_does_not_detect_outage(
+        services: [ServiceKind; 4],
+        server_error: Option<http::StatusCode>,
+    ) {
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let attempts = Arc::new(Mutex::new(0));
+        let transport_connector = ConnectFn({
+            let attempts = attempts.clone();
+            move |(), _, _| {
+                *attempts.lock().unwrap() += 1;
+                std::future::ready(match server_error {
+                    Some(_) => Ok(()),
+                    None => Err(WebSocketConnectError::Transport(
+                        TransportConnectError::TcpConnectionFailed,
+                    )),
+                })
+            }
+        });
+        let ws_connector = ConnectFn(|(), _route, _log_tag| {
+            let mut response = http::Response::new(None);
+            *response.status_mut() = server_error.expect("transport succeeded");
+            std::future::ready(Err::<(), _>(tungstenite::Error::Http(response)))
+        });
+
+        let state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: transport_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(Some(OutageDetectionConfig {
+                failure_threshold: 2,
+                ..SUGGESTED_OUTAGE_DETECTION_CONFIG
+            })),
+            reset_count: 0,
+            route_fault_injector: None,
+        }
+        .into();
+
+        for service in services {
+            let attempts_before = *attempts.lock().unwrap();
+            let result = ConnectState::connect_ws(
+                &state,
+                (*FAKE_WEBSOCKET_ROUTES).to_vec(),
+                &ws_connector,
+                &resolver,
+                None,
+                "test".into(),
+                service,
+            )
+            .await;
+            assert_matches!(
+                result,
+                Err(TimeoutOr::Other(ConnectError::AllAttemptsFailed))
+            );
+            assert!(
+                *attempts.lock().unwrap() > attempts_before,
+                "attempt for {service:?} was skipped"
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reset_forgets_failures_and_outage() {
+        let ws_connector = crate::infra::ws::Stateless;
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let unreachable_connector = ConnectFn(|(), _, _| {
+            std::future::ready(Err::<tokio::io::DuplexStream, _>(
+                WebSocketConnectError::Transport(TransportConnectError::TcpConnectionFailed),
+            ))
+        });
+
+        let state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: unreachable_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(Some(OutageDetectionConfig {
+                failure_threshold: 2,
+                ..SUGGESTED_OUTAGE_DETECTION_CONFIG
+            })),
+            reset_count: 0,
+            route_fault_injector: None,
+        }
+        .into();
+
+        for service in [ServiceKind::Chat, ServiceKind::Cdsi] {
+            let _ = ConnectState::connect_ws(
+                &state,
+                (*FAKE_WEBSOCKET_ROUTES).to_vec(),
+                ws_connector,
+                &resolver,
+                None,
+                "test".into(),
+                service,
+            )
+            .await;
+        }
+
+        let mut guard = state.write().await;
+        let now = Instant::now();
+        assert_matches!(guard.outage_ends_at(now), Some(_));
+
+        let summary = guard.reset(SUGGESTED_CONNECT_CONFIG, now);
+        assert!(summary.outage_cleared);
+        assert_ne!(summary.route_failures_cleared, 0);
+
+        assert_eq!(guard.outage_ends_at(now), None);
+        assert_eq!(guard.attempts_record.failing_route_count(), 0);
+        assert_eq!(
+            guard.connect_timeout,
+            SUGGESTED_CONNECT_CONFIG.connect_timeout
+        );
+    }
+
+    #[test]
+    fn reset_keeps_network_events() {
+        let mut state = ConnectState::new(SUGGESTED_CONNECT_CONFIG).into_inner();
+        let network_events = state.network_events.clone();
+        network_events.connect_succeeded(ServiceKind::Chat, "direct", Duration::from_millis(10));
+        let before = network_events.export_json();
+
+        let _ = state.reset(SUGGESTED_CONNECT_CONFIG, Instant::now());
+
+        // Holders of the log, like the bridge's ConnectionManager, must keep
+        // seeing new events.
+        assert!(Arc::ptr_eq(&network_events, &state.network_events));
+        assert_eq!(state.network_events.export_json(), before);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reset_discards_outcomes_of_attempts_in_progress() {
+        let ws_connector = crate::infra::ws::Stateless;
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let (started_tx, mut started_rx) = tokio::sync::watch::channel(false);
+        let (released_tx, released_rx) = tokio::sync::watch::channel(false);
+        let started_tx = Arc::new(started_tx);
+        let blocked_connector = ConnectFn(move |(), _, _| {
+            started_tx.send_replace(true);
+            let mut released_rx = released_rx.clone();
+            async move {
+                _ = released_rx.wait_for(|released| *released).await;
+                Err::<tokio::io::DuplexStream, _>(WebSocketConnectError::Transport(
+                    TransportConnectError::TcpConnectionFailed,
+                ))
+            }
+        });
+
+        let state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: blocked_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        }
+        .into();
+
+        let connect = ConnectState::connect_ws(
+            &state,
+            (*FAKE_WEBSOCKET_ROUTES).to_vec(),
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        );
+        let reset = async {
+            started_rx
+                .wait_for(|started| *started)
+                .await
+                .expect("connector still exists");
+            let summary = state
+                .write()
+                .await
+                .reset(SUGGESTED_CONNECT_CONFIG, Instant::now());
+            assert_eq!(summary, ConnectStateReset::default());
+            released_tx.send_replace(true);
+        };
+        let (result, ()) = tokio::join!(connect, reset);
+
+        assert_matches!(
+            result,
+            Err(TimeoutOr::Other(ConnectError::AllAttemptsFailed))
+        );
+        assert_eq!(state.read().await.attempts_record.failing_route_count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn preconnect_records_outcomes() {
+        let ws_connector = ConnectFn(|(), route, _log_tag| std::future::ready(Ok(route)));
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let attempts_by_host = Mutex::new(HashMap::<Host<_>, u32>::new());
+        let make_transport_connector = PreconnectingFactory::new(
+            ConnectFn(|(), route: TransportRoute, _| {
+                let host = route.fragment.sni;
+                let result = if host == Host::parse_as_ip_or_domain("fail") {
+                    Err(TransportConnectError::TcpConnectionFailed)
+                } else {
+                    Ok(())
+                };
+                *attempts_by_host
+                    .lock()
+                    .expect("no panic")
+                    .entry(host)
+                    .or_default() += 1;
+                std::future::ready(result)
+            }),
+            Duration::from_secs(60),
+        );
+
+        const CONNECT_TIMEOUT: Duration = Duration::from_secs(31);
+
+        let state = ConnectState {
+            connect_timeout: CONNECT_TIMEOUT,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        }
+        .into();
+
+        let good_transport_route = FAKE_TRANSPORT_ROUTE.clone();
+        let mut bad_transport_route = good_transport_route.clone();
+        bad_transport_route.fragment.sni = Host::parse_as_ip_or_domain("fail");
+
+        ConnectState::preconnect_and_save(
+            &state,
+            vec![bad_transport_route.clone(), good_transport_route.clone()],
+            &resolver,
+            "preconnect".into(),
+        )
         .await
         .expect("success");
 
@@ -761,6 +2004,7 @@ mod test {
             &resolver,
             None,
             "test".into(),
+            ServiceKind::Chat,
         )
         .await
         .expect("succeeded");
@@ -775,4 +2019,314 @@ mod test {
             ])
         );
     }
+
+    #[test_case(0 => "front-sni-a")]
+    #[test_case(1 => "front-sni-b")]
+    #[test_case(5 => "front-sni-c")]
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_uses_injected_randomness_to_pick_front(seed: u64) -> String {
+        const FRONT_SNIS: [&str; 3] = ["front-sni-a", "front-sni-b", "front-sni-c"];
+
+        // Only one SNI per front is tried, picked at random. With the OS's
+        // randomness, a test couldn't say which one would be attempted.
+        let routes = DomainFrontRouteProvider::new(
+            HttpVersion::Http1_1,
+            vec![DomainFrontConfig {
+                http_host: "front-host".into(),
+                sni_list: FRONT_SNIS.map(Arc::from).to_vec(),
+                root_certs: RootCertificates::Native,
+                path_prefix: "".into(),
+                front_name: RouteType::ProxyF.into(),
+                return_routes_with_all_snis: false,
+            }],
+        )
+        .map_routes(|HttpsTlsRoute { fragment, inner }| WebSocketRoute {
+            fragment: WebSocketRouteFragment {
+                ws_config: Default::default(),
+                endpoint: PathAndQuery::from_static("/"),
+                headers: HeaderMap::new(),
+            },
+            inner: HttpsTlsRoute {
+                fragment,
+                inner: TlsRoute {
+                    fragment: inner.fragment,
+                    inner: DirectOrProxyRoute::Direct(inner.inner),
+                },
+            },
+        });
+        let resolver = DnsResolver::new_from_static_map(HashMap::from(FRONT_SNIS.map(|sni| {
+            (
+                sni,
+                LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+            )
+        })));
+
+        let attempted_snis = Mutex::new(vec![]);
+        let transport_connector = ConnectFn(|(), route: TransportRoute, _| {
+            attempted_snis
+                .lock()
+                .expect("not poisoned")
+                .push(route.fragment.sni);
+            std::future::ready(Ok::<_, WebSocketConnectError>(()))
+        });
+        let ws_connector = ConnectFn(|(), route, _log_tag| std::future::ready(Ok(route)));
+
+        let mut state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: transport_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        };
+        state.set_route_randomness_for_testing(StepRng::new(seed, 0));
+        let state = state.into();
+
+        ConnectState::connect_ws(
+            &state,
+            routes,
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        )
+        .await
+        .expect("succeeded");
+
+        let [sni] = attempted_snis
+            .into_inner()
+            .expect("not poisoned")
+            .try_into()
+            .expect("one attempt");
+        sni.to_string()
+    }
+
+    #[test_case(InjectedFault::Timeout; "timeout")]
+    #[test_case(InjectedFault::TcpRefused; "TCP refused")]
+    #[test_case(InjectedFault::TlsFailed; "TLS failed")]
+    #[test_case(InjectedFault::RejectedByServer(http::StatusCode::BAD_GATEWAY); "server error")]
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_falls_back_to_fronted_route_when_direct_fails(fault: InjectedFault) {
+        let [direct_route, fronted_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let ws_connector = ConnectFn(|(), route, _log_tag| std::future::ready(Ok(route)));
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+        let fake_transport_connector =
+            ConnectFn(move |(), _, _| std::future::ready(Ok::<_, WebSocketConnectError>(())));
+
+        let mut state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: fake_transport_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        };
+        let attempted_kinds = Arc::new(Mutex::new(vec![]));
+        state.set_route_fault_injector(Some(Arc::new({
+            let attempted_kinds = attempted_kinds.clone();
+            move |info: &RouteInfo| {
+                let kind = info.route_kind();
+                attempted_kinds.lock().expect("not poisoned").push(kind);
+                (kind == "direct").then_some(fault)
+            }
+        })));
+        let state = state.into();
+
+        let (connection, info) = ConnectState::connect_ws(
+            &state,
+            vec![direct_route, fronted_route.clone()],
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        )
+        .await
+        .expect("fell back");
+
+        assert_eq!(
+            connection,
+            (fronted_route.fragment, fronted_route.inner.fragment)
+        );
+        assert_eq!(info.route_kind(), "domain_fronted");
+        assert_eq!(
+            *attempted_kinds.lock().expect("not poisoned"),
+            ["direct", "domain_fronted"]
+        );
+    }
+
+    #[test_case(NetworkType::Wifi, true => vec!["direct", "domain_fronted"]; "fallback")]
+    #[test_case(NetworkType::Vpn, true => vec!["direct"]; "no fallback on VPN")]
+    #[test_case(NetworkType::Vpn, false => vec!["domain_fronted"]; "fronted only on VPN")]
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_on_vpn_skips_fronted_fallback(
+        network_type: NetworkType,
+        include_direct: bool,
+    ) -> Vec<&'static str> {
+        let [direct_route, fronted_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let ws_connector = ConnectFn(|(), route, _log_tag| std::future::ready(Ok(route)));
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+        let fake_transport_connector =
+            ConnectFn(move |(), _, _| std::future::ready(Ok::<_, WebSocketConnectError>(())));
+
+        let mut state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type,
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: fake_transport_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        };
+        let attempted_kinds = Arc::new(Mutex::new(vec![]));
+        state.set_route_fault_injector(Some(Arc::new({
+            let attempted_kinds = attempted_kinds.clone();
+            move |info: &RouteInfo| {
+                let kind = info.route_kind();
+                attempted_kinds.lock().expect("not poisoned").push(kind);
+                (kind == "direct").then_some(InjectedFault::TcpRefused)
+            }
+        })));
+        let state = state.into();
+
+        let routes = if include_direct {
+            vec![direct_route, fronted_route]
+        } else {
+            vec![fronted_route]
+        };
+        _ = ConnectState::connect_ws(
+            &state,
+            routes,
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        )
+        .await;
+
+        attempted_kinds.lock().expect("not poisoned").clone()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_stops_after_injected_client_error() {
+        let [direct_route, fronted_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let ws_connector = ConnectFn(|(), route, _log_tag| std::future::ready(Ok(route)));
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(DnsSource::Static, vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+        let fake_transport_connector =
+            ConnectFn(move |(), _, _| std::future::ready(Ok::<_, WebSocketConnectError>(())));
+
+        let mut state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_type: NetworkType::default(),
+            route_resolver: RouteResolver::default(),
+            connection_racing: Default::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: fake_transport_connector,
+            route_provider_context: Default::default(),
+            data_usage: Default::default(),
+            network_events: Default::default(),
+            outage_detector: OutageDetector::new(None),
+            reset_count: 0,
+            route_fault_injector: None,
+        };
+        state.set_route_fault_injector(Some(Arc::new(|info: &RouteInfo| {
+            (info.route_kind() == "direct")
+                .then_some(InjectedFault::RejectedByServer(http::StatusCode::FORBIDDEN))
+        })));
+        let state = state.into();
+
+        // A 4xx response means the request itself was rejected, so there's no
+        // point in trying the fronted route.
+        let result = ConnectState::connect_ws(
+            &state,
+            vec![direct_route, fronted_route],
+            ws_connector,
+            &resolver,
+            None,
+            "test".into(),
+            ServiceKind::Chat,
+        )
+        .await;
+
+        assert_matches!(
+            result,
+            Err(TimeoutOr::Other(ConnectError::FatalConnect(
+                WebSocketServiceConnectError::RejectedByServer { response, .. }
+            ))) if response.status() == http::StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn default_overrides_match_suggested_config() {
+        let mut state = ConnectState::new(SUGGESTED_CONNECT_CONFIG).into_inner();
+        let before = (state.connect_timeout, state.connection_racing);
+        state
+            .set_config_overrides(ConnectConfigOverrides::default())
+            .expect("valid");
+        assert_eq!(before, (state.connect_timeout, state.connection_racing));
+    }
+
+    #[test_case(ConnectConfigOverrides {
+        connect_timeout: Duration::ZERO,
+        ..Default::default()
+    } => matches Err(InvalidConnectConfig::ZeroConnectTimeout); "zero timeout")]
+    #[test_case(ConnectConfigOverrides {
+        max_concurrent_attempts: 0,
+        ..Default::default()
+    } => matches Err(InvalidConnectConfig::ZeroConcurrentAttempts); "zero attempts")]
+    #[test_case(ConnectConfigOverrides {
+        per_connection_wait: Duration::ZERO,
+        max_concurrent_attempts: 1,
+        connect_timeout: Duration::from_secs(60),
+        outage_detection: None,
+    } => matches Ok(()); "single attempt")]
+    fn config_overrides_are_validated(
+        overrides: ConnectConfigOverrides,
+    ) -> Result<(), InvalidConnectConfig> {
+        let mut state = ConnectState::new(SUGGESTED_CONNECT_CONFIG).into_inner();
+        let before = (state.connect_timeout, state.connection_racing);
+        let result = state.set_config_overrides(overrides);
+        let after = (state.connect_timeout, state.connection_racing);
+        if result.is_ok() {
+            assert_eq!(after.0, overrides.connect_timeout);
+            assert_eq!(
+                after.1.max_concurrent_attempts.get(),
+                overrides.max_concurrent_attempts
+            );
+        } else {
+            assert_eq!(before, after, "should not be partially applied");
+        }
+        result
+    }
 }
@@ -0,0 +1,255 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use itertools::Itertools as _;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::metrics::ServiceKind;
+
+/// How [`ConnectState`](super::ConnectState) decides that the network is down.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutageDetectionConfig {
+    /// How many connect attempts in a row must fail without reaching a server.
+    pub failure_threshold: usize,
+    /// How many different services those attempts must have been for.
+    ///
+    /// Requiring more than one keeps a problem with a single service, like a
+    /// blocked chat server, from stopping connections to the others.
+    pub min_distinct_services: usize,
+    /// How close together the failed attempts must be.
+    pub window: Duration,
+    /// How long connect attempts fail immediately once an outage is detected,
+    /// unless the network changes first.
+    pub cooldown: Duration,
+}
+
+pub const SUGGESTED_OUTAGE_DETECTION_CONFIG: OutageDetectionConfig = OutageDetectionConfig {
+    failure_threshold: 3,
+    min_distinct_services: 2,
+    window: Duration::from_secs(60),
+    cooldown: Duration::from_secs(30),
+};
+
+/// When the most recent outage stops blocking connect attempts, or `None` if it
+/// was ended early (or there hasn't been one).
+///
+/// An outage isn't announced again when it ends on schedule, so a time that's
+/// already passed also means there's no outage.
+///
+/// See [`ConnectState::outage_watch`](super::ConnectState::outage_watch).
+pub type OutageWatch = watch::Receiver<Option<Instant>>;
+
+/// Watches the results of connect attempts across services for signs that the
+/// network is down entirely.
+#[derive(Debug)]
+pub(super) struct OutageDetector {
+    config: Option<OutageDetectionConfig>,
+    /// The most recent attempts that failed without reaching a server, oldest
+    /// first, since the last one that did.
+    recent_failures: VecDeque<(Instant, ServiceKind)>,
+    outage_until: Option<Instant>,
+    /// Publishes every change to `outage_until` to [`Self::subscribe`]rs.
+    announce: watch::Sender<Option<Instant>>,
+}
+
+impl OutageDetector {
+    pub(super) fn new(config: Option<OutageDetectionConfig>) -> Self {
+        Self {
+            config,
+            recent_failures: VecDeque::new(),
+            outage_until: None,
+            announce: watch::channel(None).0,
+        }
+    }
+
+    /// Replaces the configuration, forgetting any failures seen so far.
+    ///
+    /// Existing subscribers keep receiving updates.
+    pub(super) fn set_config(&mut self, config: Option<OutageDetectionConfig>) {
+        self.config = config;
+        self.reset();
+    }
+
+    /// Follows when outages start and end.
+    pub(super) fn subscribe(&self) -> OutageWatch {
+        self.announce.subscribe()
+    }
+
+    /// Whether connect attempts should fail without trying any routes.
+    pub(super) fn in_outage(&self, now: Instant) -> bool {
+        self.outage_until.is_some_and(|until| now < until)
+    }
+
+    /// When the outage in progress, if any, stops blocking connect attempts.
+    pub(super) fn outage_ends_at(&self, now: Instant) -> Option<Instant> {
+        self.outage_until.filter(|until| now < *until)
+    }
+
+    /// Records a connect attempt that reached a server, whether or not the
+    /// connection was ultimately established.
+    pub(super) fn record_reachable(&mut self) {
+        self.recent_failures.clear();
+    }
+
+    /// Records a connect attempt for which every route failed without reaching
+    /// a server.
+    pub(super) fn record_unreachable(&mut self, service: ServiceKind, now: Instant) {
+        let Some(OutageDetectionConfig {
+            failure_threshold,
+            min_distinct_services,
+            window,
+            cooldown,
+        }) = self.config
+        else {
+            return;
+        };
+
+        self.recent_failures.push_back((now, service));
+        while self.recent_failures.len() > failure_threshold
+            || self
+                .recent_failures
+                .front()
+                .is_some_and(|(at, _)| now.saturating_duration_since(*at) > window)
+        {
+            self.recent_failures.pop_front();
+        }
+
+        let distinct_services = self
+            .recent_failures
+            .iter()
+            .map(|(_, service)| service)
+            .unique()
+            .count();
+        if self.recent_failures.len() >= failure_threshold
+            && distinct_services >= min_distinct_services
+        {
+            log::warn!(
+                "no service has been reachable for {} attempts; skipping connect attempts for {cooldown:?}",
+                self.recent_failures.len()
+            );
+            self.outage_until = Some(now + cooldown);
+            self.recent_failures.clear();
+            self.announce.send_replace(self.outage_until);
+        }
+    }
+
+    /// Forgets all previous results, ending any outage in progress.
+    pub(super) fn reset(&mut self) {
+        self.recent_failures.clear();
+        if self.outage_until.take().is_some() {
+            self.announce.send_replace(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: OutageDetectionConfig = OutageDetectionConfig {
+        failure_threshold: 3,
+        min_distinct_services: 2,
+        window: Duration::from_secs(60),
+        cooldown: Duration::from_secs(30),
+    };
+
+    #[test]
+    fn failures_across_services_trigger_outage() {
+        let mut detector = OutageDetector::new(Some(CONFIG));
+        let start = Instant::now();
+
+        detector.record_unreachable(ServiceKind::Chat, start);
+        detector.record_unreachable(ServiceKind::Cdsi, start + Duration::from_secs(1));
+        assert!(!detector.in_outage(start + Duration::from_secs(2)));
+        detector.record_unreachable(ServiceKind::Chat, start + Duration::from_secs(2));
+
+        let detected_at = start + Duration::from_secs(2);
+        assert!(detector.in_outage(detected_at));
+        assert!(detector.in_outage(detected_at + CONFIG.cooldown - Duration::from_millis(1)));
+        assert!(!detector.in_outage(detected_at + CONFIG.cooldown));
+        assert_eq!(
+            detector.outage_ends_at(detected_at),
+            Some(detected_at + CONFIG.cooldown)
+        );
+        assert_eq!(detector.outage_ends_at(detected_at + CONFIG.cooldown), None);
+    }
+
+    #[test]
+    fn single_service_failures_do_not_trigger_outage() {
+        let mut detector = OutageDetector::new(Some(CONFIG));
+        let start = Instant::now();
+
+        for i in 0..10 {
+            detector.record_unreachable(ServiceKind::Chat, start + Duration::from_secs(i));
+        }
+        assert!(!detector.in_outage(start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn reachable_attempt_or_stale_failures_start_over() {
+        let mut detector = OutageDetector::new(Some(CONFIG));
+        let start = Instant::now();
+
+        detector.record_unreachable(ServiceKind::Chat, start);
+        detector.record_unreachable(ServiceKind::Cdsi, start);
+        detector.record_reachable();
+        detector.record_unreachable(ServiceKind::Svr, start);
+        assert!(!detector.in_outage(start));
+
+        detector.record_unreachable(ServiceKind::Chat, start + CONFIG.window * 2);
+        assert!(!detector.in_outage(start + CONFIG.window * 2));
+    }
+
+    #[test]
+    fn reset_ends_outage() {
+        let mut detector = OutageDetector::new(Some(CONFIG));
+        let start = Instant::now();
+
+        for service in [ServiceKind::Chat, ServiceKind::Cdsi, ServiceKind::Svr] {
+            detector.record_unreachable(service, start);
+        }
+        assert!(detector.in_outage(start));
+
+        detector.reset();
+        assert!(!detector.in_outage(start));
+    }
+
+    #[test]
+    fn subscribers_follow_outages() {
+        let mut detector = OutageDetector::new(Some(CONFIG));
+        let mut outage = detector.subscribe();
+        let start = Instant::now();
+
+        for service in [ServiceKind::Chat, ServiceKind::Cdsi, ServiceKind::Svr] {
+            detector.record_unreachable(service, start);
+        }
+        assert!(outage.has_changed().expect("sender alive"));
+        assert_eq!(*outage.borrow_and_update(), Some(start + CONFIG.cooldown));
+
+        detector.set_config(Some(CONFIG));
+        assert_eq!(*outage.borrow_and_update(), None);
+
+        for service in [ServiceKind::Chat, ServiceKind::Cdsi, ServiceKind::Svr] {
+            detector.record_unreachable(service, start);
+        }
+        detector.reset();
+        assert_eq!(*outage.borrow_and_update(), None);
+    }
+
+    #[test]
+    fn disabled_detector_never_triggers() {
+        let mut detector = OutageDetector::new(None);
+        let start = Instant::now();
+
+        for service in [ServiceKind::Chat, ServiceKind::Cdsi, ServiceKind::Svr] {
+            detector.record_unreachable(service, start);
+        }
+        assert!(!detector.in_outage(start));
+    }
+}
@@ -0,0 +1,167 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Estimating the quality of a chat connection from its round-trip time.
+//!
+//! This doesn't send anything extra: samples come from how long it took to
+//! establish the connection, and from the keepalive pings that are already sent
+//! while the connection is idle.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A coarse summary of how responsive a connection is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionQuality {
+    Good,
+    Ok,
+    Bad,
+}
+
+/// Where the round-trip time estimate is split into [`ConnectionQuality`]
+/// levels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionQualityThresholds {
+    /// Round trips faster than this are [`ConnectionQuality::Good`].
+    pub good_below: Duration,
+    /// Round trips slower than this are [`ConnectionQuality::Bad`].
+    pub bad_above: Duration,
+}
+
+impl Default for ConnectionQualityThresholds {
+    fn default() -> Self {
+        Self {
+            good_below: Duration::from_millis(300),
+            bad_above: Duration::from_secs(1),
+        }
+    }
+}
+
+/// the "good" threshold must not be above the "bad" one
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub struct InvalidConnectionQualityThresholds;
+
+impl ConnectionQualityThresholds {
+    /// Checks that the thresholds describe non-overlapping levels.
+    pub fn validate(self) -> Result<Self, InvalidConnectionQualityThresholds> {
+        if self.good_below > self.bad_above {
+            return Err(InvalidConnectionQualityThresholds);
+        }
+        Ok(self)
+    }
+
+    pub fn classify(&self, round_trip: Duration) -> ConnectionQuality {
+        if round_trip < self.good_below {
+            ConnectionQuality::Good
+        } else if round_trip > self.bad_above {
+            ConnectionQuality::Bad
+        } else {
+            ConnectionQuality::Ok
+        }
+    }
+}
+
+/// Tracks a smoothed estimate of a connection's round-trip time.
+///
+/// Each sample is blended into a running estimate (an exponentially weighted
+/// moving average), so a single slow round trip doesn't make the connection
+/// look bad.
+#[derive(Debug, Default)]
+pub struct RoundTripEstimator {
+    estimate: Mutex<Option<Duration>>,
+}
+
+impl RoundTripEstimator {
+    /// How much weight a new sample gets relative to the existing estimate.
+    const SMOOTHING_DIVISOR: u32 = 8;
+
+    /// How many round trips it takes to establish a connection: one each for
+    /// TCP, TLS 1.3, and the websocket upgrade.
+    const HANDSHAKE_ROUND_TRIPS: u32 = 3;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records how long it took to establish the connection, from the start of
+    /// the TCP connection to the completion of the websocket upgrade.
+    pub fn record_handshake(&self, handshake: Duration) {
+        self.record_sample(handshake / Self::HANDSHAKE_ROUND_TRIPS)
+    }
+
+    /// Records a single measured round trip.
+    pub fn record_sample(&self, round_trip: Duration) {
+        let mut guard = self.estimate.lock().expect("not poisoned");
+        let updated = match *guard {
+            Some(current) if round_trip >= current => {
+                current + (round_trip - current) / Self::SMOOTHING_DIVISOR
+            }
+            Some(current) => current - (current - round_trip) / Self::SMOOTHING_DIVISOR,
+            None => round_trip,
+        };
+        *guard = Some(updated);
+    }
+
+    /// The estimated round-trip time, if any samples have been recorded.
+    pub fn estimate(&self) -> Option<Duration> {
+        *self.estimate.lock().expect("not poisoned")
+    }
+
+    /// Classifies the current estimate, if any samples have been recorded.
+    pub fn quality(&self, thresholds: &ConnectionQualityThresholds) -> Option<ConnectionQuality> {
+        self.estimate()
+            .map(|round_trip| thresholds.classify(round_trip))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn no_samples_means_no_quality() {
+        let estimator = RoundTripEstimator::new();
+        assert_eq!(estimator.estimate(), None);
+        assert_eq!(estimator.quality(&Default::default()), None);
+    }
+
+    #[test]
+    fn handshake_covers_several_round_trips() {
+        let estimator = RoundTripEstimator::new();
+        estimator.record_handshake(Duration::from_millis(600));
+        assert_eq!(estimator.estimate(), Some(Duration::from_millis(200)));
+    }
+
+    #[test_case(900, 100 => 800; "faster")]
+    #[test_case(100, 900 => 200; "slower")]
+    fn later_samples_are_smoothed(first_millis: u64, second_millis: u64) -> u128 {
+        let estimator = RoundTripEstimator::new();
+        estimator.record_sample(Duration::from_millis(first_millis));
+        estimator.record_sample(Duration::from_millis(second_millis));
+        estimator.estimate().expect("has samples").as_millis()
+    }
+
+    #[test_case(Duration::from_millis(50) => ConnectionQuality::Good)]
+    #[test_case(Duration::from_millis(300) => ConnectionQuality::Ok)]
+    #[test_case(Duration::from_secs(1) => ConnectionQuality::Ok)]
+    #[test_case(Duration::from_millis(1001) => ConnectionQuality::Bad)]
+    fn default_thresholds(round_trip: Duration) -> ConnectionQuality {
+        ConnectionQualityThresholds::default().classify(round_trip)
+    }
+
+    #[test_case(300, 1000 => true; "ordered")]
+    #[test_case(500, 500 => true; "no ok level")]
+    #[test_case(1000, 300 => false; "overlapping")]
+    fn thresholds_are_validated(good_below_millis: u64, bad_above_millis: u64) -> bool {
+        ConnectionQualityThresholds {
+            good_below: Duration::from_millis(good_below_millis),
+            bad_above: Duration::from_millis(bad_above_millis),
+        }
+        .validate()
+        .is_ok()
+    }
+}
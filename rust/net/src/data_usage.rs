@@ -0,0 +1,119 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Accounting of the data sent and received for each service.
+
+use std::sync::Arc;
+
+use libsignal_net_infra::ws::{TransferCounter, TransferTotals};
+
+use crate::metrics::ServiceKind;
+
+/// Byte counters for each service, shared by every connection made through a
+/// [`ConnectState`](crate::connect_state::ConnectState).
+///
+/// Counts are of websocket message payloads; see [`TransferCounter`]. Key
+/// transparency requests travel over chat connections, so they're included in
+/// chat's counts. Their request and response bodies are also counted on their
+/// own, so apps can see how much of chat's traffic they account for.
+#[derive(Debug, Default)]
+pub struct DataUsage {
+    chat: Arc<TransferCounter>,
+    cdsi: Arc<TransferCounter>,
+    svr: Arc<TransferCounter>,
+    key_transparency: TransferCounter,
+}
+
+/// The totals from a [`DataUsage`] at some point in time.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DataUsageSnapshot {
+    /// All chat traffic, including key transparency requests.
+    pub chat: TransferTotals,
+    pub cdsi: TransferTotals,
+    pub svr: TransferTotals,
+    /// The bodies of key transparency requests and responses.
+    ///
+    /// Already included in [`Self::chat`], so don't add the two together.
+    pub key_transparency: TransferTotals,
+}
+
+impl DataUsage {
+    /// The counter for websocket traffic to `service`.
+    pub fn counter(&self, service: ServiceKind) -> &Arc<TransferCounter> {
+        match service {
+            ServiceKind::Chat => &self.chat,
+            ServiceKind::Cdsi => &self.cdsi,
+            ServiceKind::Svr => &self.svr,
+        }
+    }
+
+    /// Records the body sizes of a key transparency request and its response.
+    pub(crate) fn record_key_transparency(&self, sent: usize, received: usize) {
+        self.key_transparency.record_sent(sent);
+        self.key_transparency.record_received(received);
+    }
+
+    /// The totals so far.
+    ///
+    /// Each counter is read on its own, so the totals of different services
+    /// might not be from exactly the same moment.
+    pub fn snapshot(&self) -> DataUsageSnapshot {
+        DataUsageSnapshot {
+            chat: self.chat.totals(),
+            cdsi: self.cdsi.totals(),
+            svr: self.svr.totals(),
+            key_transparency: self.key_transparency.totals(),
+        }
+    }
+
+    /// Sets every counter back to zero.
+    pub fn reset(&self) {
+        let Self {
+            chat,
+            cdsi,
+            svr,
+            key_transparency,
+        } = self;
+        for counter in [&**chat, &**cdsi, &**svr, key_transparency] {
+            counter.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_transparency_is_reported_alongside_chat() {
+        let usage = DataUsage::default();
+        usage.counter(ServiceKind::Chat).record_sent(100);
+        usage.counter(ServiceKind::Chat).record_received(300);
+        usage.record_key_transparency(40, 200);
+        usage.counter(ServiceKind::Cdsi).record_sent(7);
+
+        assert_eq!(
+            usage.snapshot(),
+            DataUsageSnapshot {
+                chat: TransferTotals {
+                    sent: 100,
+                    received: 300
+                },
+                cdsi: TransferTotals {
+                    sent: 7,
+                    received: 0
+                },
+                svr: TransferTotals::default(),
+                key_transparency: TransferTotals {
+                    sent: 40,
+                    received: 200
+                },
+            }
+        );
+
+        usage.reset();
+        assert_eq!(usage.snapshot(), DataUsageSnapshot::default());
+    }
+}
@@ -13,7 +13,8 @@ use derive_where::derive_where;
 use http::uri::PathAndQuery;
 use http::HeaderMap;
 use libsignal_net_infra::connection_manager::{
-    ConnectionManager, MultiRouteConnectionManager, SingleRouteThrottlingConnectionManager,
+    ConnectionManager, MultiRouteConnectionManager, RouteCooldowns,
+    SingleRouteThrottlingConnectionManager,
 };
 use libsignal_net_infra::errors::LogSafeDisplay;
 use libsignal_net_infra::route::{
@@ -185,6 +186,32 @@ impl<Bytes: AsRef<[u8]>, S> AsRef<[u8]> for MrEnclave<Bytes, S> {
     }
 }
 
+/// Identifies the enclave build a connection was attested against.
+///
+/// Attestation only succeeds if the enclave's measurement is the one the
+/// endpoint expects, so this is the endpoint's MRENCLAVE at the time of the
+/// connection. Measurements are public (they're part of the endpoint's URL), so
+/// they're safe to log; the attestation evidence itself is not kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttestedMeasurement {
+    pub mr_enclave: Box<[u8]>,
+}
+
+impl<Bytes: AsRef<[u8]>, E> From<&MrEnclave<Bytes, E>> for AttestedMeasurement {
+    fn from(mr_enclave: &MrEnclave<Bytes, E>) -> Self {
+        Self {
+            mr_enclave: mr_enclave.as_ref().into(),
+        }
+    }
+}
+
+impl LogSafeDisplay for AttestedMeasurement {}
+impl std::fmt::Display for AttestedMeasurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MRENCLAVE {}", hex::encode(&self.mr_enclave))
+    }
+}
+
 #[derive_where(Clone)]
 pub struct EndpointParams<'a, E: EnclaveKind> {
     pub mr_enclave: MrEnclave<&'a [u8], E>,
@@ -360,7 +387,7 @@ impl<E: EnclaveKind> EnclaveEndpointConnection<E, MultiRouteConnectionManager> {
         endpoint: &EnclaveEndpoint<'static, E>,
         connection_params: impl IntoIterator<Item = ConnectionParams>,
         one_route_connect_timeout: Duration,
-        network_change_event: &ObservableEvent,
+        route_cooldowns: &RouteCooldowns,
     ) -> Self {
         Self {
             endpoint_connection: EndpointConnection::new_multi(
@@ -370,7 +397,7 @@ impl<E: EnclaveKind> EnclaveEndpointConnection<E, MultiRouteConnectionManager> {
                     E::url_path(endpoint.params.mr_enclave.as_ref()),
                     one_route_connect_timeout,
                 ),
-                network_change_event,
+                route_cooldowns,
             ),
             params: endpoint.params.clone(),
         }
@@ -490,6 +517,14 @@ mod test {
         }
     }
 
+    #[test]
+    fn attested_measurement_is_logged_as_hex() {
+        let measurement =
+            AttestedMeasurement::from(&MrEnclave::<_, Cdsi>::new(b"\xab\xcd".as_slice()));
+        assert_eq!(&*measurement.mr_enclave, b"\xab\xcd");
+        assert_eq!(measurement.to_string(), "MRENCLAVE abcd");
+    }
+
     #[tokio::test]
     async fn single_route_enclave_connect_failure() {
         let result = enclave_connect(SingleRouteThrottlingConnectionManager::new(
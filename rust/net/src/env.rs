@@ -8,11 +8,12 @@ use std::iter;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::num::NonZeroU16;
 use std::sync::Arc;
+use std::time::Duration;
 
 use const_str::ip_addr;
 use hex_literal::hex;
 use http::HeaderValue;
-use libsignal_keytrans::{DeploymentMode, PublicConfig, VerifyingKey, VrfPublicKey};
+use libsignal_keytrans::{DeploymentMode, KeyRotations, PublicConfig, VerifyingKey, VrfPublicKey};
 use libsignal_net_infra::certs::RootCertificates;
 use libsignal_net_infra::dns::lookup_result::LookupResult;
 use libsignal_net_infra::host::Host;
@@ -242,7 +243,15 @@ pub struct ConnectionProxyConfig {
 pub struct KeyTransConfig {
     pub signing_key_material: &'static [u8; 32],
     pub vrf_key_material: &'static [u8; 32],
-    pub auditor_key_material: &'static [u8; 32],
+    /// The third-party auditor's key.
+    ///
+    /// `None` for a deployment that relies on contact monitoring alone, in which case tree heads
+    /// are not expected to carry an auditor signature.
+    pub auditor_key_material: Option<&'static [u8; 32]>,
+    /// How long each key transparency request is given by default.
+    ///
+    /// See [`crate::keytrans::Config::for_env`].
+    pub request_timeout: Duration,
 }
 
 impl DomainConfig {
@@ -444,20 +453,35 @@ impl ProxyConfig {
     }
 }
 
+impl KeyTransConfig {
+    /// The keys to verify the log with, taking into account any rotations the client has applied
+    /// since this config was built.
+    pub fn load(self, rotations: &KeyRotations) -> PublicConfig {
+        let mut config = PublicConfig::from(self);
+        rotations.install(&mut config);
+        config
+    }
+}
+
 impl From<KeyTransConfig> for PublicConfig {
     fn from(src: KeyTransConfig) -> Self {
         let KeyTransConfig {
             signing_key_material,
             vrf_key_material,
             auditor_key_material,
+            request_timeout: _,
         } = src;
         let signature_key =
             VerifyingKey::from_bytes(signing_key_material).expect("valid signing key material");
-        let auditor_key =
-            VerifyingKey::from_bytes(auditor_key_material).expect("valid auditor key material");
+        let mode = match auditor_key_material {
+            Some(auditor_key_material) => DeploymentMode::ThirdPartyAuditing(
+                VerifyingKey::from_bytes(auditor_key_material).expect("valid auditor key material"),
+            ),
+            None => DeploymentMode::ContactMonitoring,
+        };
         let vrf_key = VrfPublicKey::try_from(*vrf_key_material).expect("valid VRF key material");
         Self {
-            mode: DeploymentMode::ThirdPartyAuditing(auditor_key),
+            mode,
             signature_key,
             vrf_key,
         }
@@ -502,7 +526,9 @@ pub const STAGING: Env<'static> = Env {
     keytrans_config: Some(KeyTransConfig {
         signing_key_material: KEYTRANS_SIGNING_KEY_MATERIAL_STAGING,
         vrf_key_material: KEYTRANS_VRF_KEY_MATERIAL_STAGING,
-        auditor_key_material: KEYTRANS_AUDITOR_KEY_MATERIAL_STAGING,
+        auditor_key_material: Some(KEYTRANS_AUDITOR_KEY_MATERIAL_STAGING),
+        // The staging deployment responds more slowly than prod.
+        request_timeout: Duration::from_secs(30),
     }),
 };
 
@@ -527,6 +553,7 @@ pub mod constants {
 mod test {
     use std::collections::HashSet;
 
+    use assert_matches::assert_matches;
     use itertools::Itertools as _;
     use libsignal_net_infra::dns::build_custom_resolver_cloudflare_doh;
     use libsignal_net_infra::dns::dns_lookup::DnsLookupRequest;
@@ -541,6 +568,27 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn keytrans_config_deployment_mode() {
+        let staging = STAGING.keytrans_config.expect("staging has KT");
+        let auditor_key_material = staging
+            .auditor_key_material
+            .expect("staging has an auditor");
+        assert_matches!(
+            PublicConfig::from(staging).mode,
+            DeploymentMode::ThirdPartyAuditing(key) if key.as_bytes() == auditor_key_material
+        );
+
+        let contact_monitoring = KeyTransConfig {
+            auditor_key_material: None,
+            ..STAGING.keytrans_config.expect("staging has KT")
+        };
+        assert_matches!(
+            PublicConfig::from(contact_monitoring).mode,
+            DeploymentMode::ContactMonitoring
+        );
+    }
+
     #[test_matrix([&DOMAIN_CONFIG_CHAT, &DOMAIN_CONFIG_CHAT_STAGING])]
     fn chat_has_confirmation_header(config: &DomainConfig) {
         assert_eq!(
@@ -668,6 +716,7 @@ mod test {
             .resolve(DnsLookupRequest {
                 hostname: Arc::from(hostname),
                 ipv6_enabled: true,
+                local_bind_address: None,
             })
             .await
             .unwrap_or_else(|_| panic!("Unable to resolve {hostname}"))
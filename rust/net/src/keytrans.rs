@@ -5,43 +5,62 @@
 
 use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{hash_map, BTreeSet, HashMap};
 use std::fmt::{Debug, Formatter};
 use std::future::Future;
-use std::time::{Duration, SystemTime};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use base64::prelude::{
     Engine as _, BASE64_STANDARD, BASE64_STANDARD_NO_PAD, BASE64_URL_SAFE_NO_PAD,
 };
 use futures_util::future::BoxFuture;
 use http::header::{ACCEPT, CONTENT_TYPE};
-use http::uri::PathAndQuery;
-use libsignal_core::{Aci, E164};
+use indexmap::IndexMap;
+use itertools::Itertools as _;
+use libsignal_core::{Aci, UnidentifiedAccessKey, E164};
 use libsignal_keytrans::{
-    AccountData, ChatDistinguishedResponse, ChatMonitorResponse, ChatSearchResponse,
-    CondensedTreeSearchResponse, FullSearchResponse, FullTreeHead, KeyTransparency, LastTreeHead,
-    LocalStateUpdate, MonitorContext, MonitorKey, MonitorProof, MonitorRequest, MonitorResponse,
-    MonitoringData, SearchContext, SearchStateUpdate, SlimSearchRequest, StoredAccountData,
-    StoredMonitoringData, StoredTreeHead, VerifiedSearchResult,
+    AccountData, AuditorTreeHead, ChatDistinguishedResponse, ChatMonitorResponse,
+    ChatSearchResponse, CondensedTreeSearchResponse, DeploymentMode, FullSearchResponse,
+    FullTreeHead, KeyRotationError, KeyRotations, KeyTransparency, LastTreeHead, LocalStateUpdate,
+    MonitorContext, MonitorKey, MonitorProof, MonitorRequest, MonitorResponse, MonitoringData,
+    RotatableKey, SearchContext, SearchStateUpdate, SignedKeyRotation, SlimSearchRequest,
+    StoredAccountData, StoredMonitoringData, StoredTreeHead, TreeHead, TreeHeadConsistency,
+    VerifiedMonitorResult, VerifiedSearchResult,
 };
+use libsignal_net_infra::errors::RetryDelay;
+use libsignal_net_infra::extract_retry_later;
 use libsignal_protocol::{IdentityKey, PublicKey};
 use prost::{DecodeError, Message};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 use thiserror::Error;
 
 use crate::chat;
+use crate::chat::envelope::{decode_envelope, EnvelopeError};
+use crate::server_time::{Clock, SystemClock};
 
+/// The start of the path of every key transparency request.
+pub(crate) const KEY_TRANSPARENCY_PATH_PREFIX: &str = "/v1/key-transparency/";
 const SEARCH_PATH: &str = "/v1/key-transparency/search";
 const DISTINGUISHED_PATH: &str = "/v1/key-transparency/distinguished";
 const MONITOR_PATH: &str = "/v1/key-transparency/monitor";
+/// Serves the log's most recent [`SignedKeyRotation`] (see `chat.proto` in
+/// libsignal-keytrans) in the same `serializedResponse` envelope as the other
+/// key transparency responses, or a 404 if the keys have never been rotated.
+const KEY_ROTATION_PATH: &str = "/v1/key-transparency/key-rotation";
 
 const MIME_TYPE: &str = "application/json";
 
-fn common_headers() -> http::HeaderMap {
-    http::HeaderMap::from_iter([
-        (CONTENT_TYPE, http::HeaderValue::from_static(MIME_TYPE)),
-        (ACCEPT, http::HeaderValue::from_static(MIME_TYPE)),
-    ])
+fn request_builder(method: http::Method) -> chat::RequestBuilder {
+    chat::Request::builder()
+        .method(method)
+        .header(CONTENT_TYPE, MIME_TYPE)
+        .and_then(|builder| builder.header(ACCEPT, MIME_TYPE))
+        // Monitor and search proofs can be large.
+        .and_then(|builder| builder.accept_gzip())
+        .expect("valid headers")
 }
 
 #[derive(Debug, Error, displaydoc::Display)]
@@ -51,14 +70,106 @@ pub enum Error {
     ChatSendError(#[from] chat::SendError),
     /// Bad status code: {0}
     RequestFailed(http::StatusCode),
+    /// Rate limited by the server
+    RateLimited {
+        /// How long the server asked to wait, if it said.
+        retry_after: Option<RetryDelay>,
+    },
     /// Verification failed: {0}
     VerificationFailed(#[from] libsignal_keytrans::Error),
+    /// Verification failed for the {leg} search: {source}
+    LegVerificationFailed {
+        leg: SearchKeyKind,
+        source: libsignal_keytrans::Error,
+    },
     /// Invalid response: {0}
     InvalidResponse(String),
-    /// Invalid request: {0}
-    InvalidRequest(&'static str),
+    /// Invalid arguments: {0}
+    BadArguments(BadArgumentsReason),
+    /// Internal error: {0}
+    ///
+    /// These indicate a bug in this crate rather than a problem with the
+    /// arguments or the response, and fail debug assertions when produced.
+    Internal(&'static str),
     /// Invalid protobuf: {0}
     DecodingFailed(DecodeError),
+    /// Tree head timestamp {timestamp:?} is out of range (now: {now:?})
+    TreeHeadOutOfRange {
+        timestamp: SystemTime,
+        now: SystemTime,
+    },
+    /// Search tree head (size {search_size}) lags too far behind the distinguished tree head (size {distinguished_size})
+    StaleView {
+        search_size: u64,
+        distinguished_size: u64,
+    },
+    /// Auditor tree head is {lag} entries behind the log (at most {max_lag} allowed)
+    AuditorTooFarBehind { lag: u64, max_lag: u64 },
+    /// ACI identity key does not match the key pinned for this account
+    PinnedKeyMismatch { aci: Aci },
+    /// The server does not support searching by {0}
+    UnsupportedSearchKey(AccountDataField),
+    /// Could not import key transparency state: {0}
+    ImportFailed(#[from] libsignal_keytrans::ImportError),
+    /// Key rotation rejected: {0}
+    KeyRotationRejected(#[from] KeyRotationError),
+    /// Ran out of time while {phase}
+    DeadlineExceeded { phase: OperationPhase },
+    /// Could not load key transparency state: {0}
+    StateLoadFailed(KtStateStoreError),
+    /// Verified key transparency state could not be saved: {0}
+    ///
+    /// The operation itself succeeded, but since its result wasn't saved, the
+    /// next one will start from outdated state.
+    StatePersistFailed(KtStateStoreError),
+}
+
+/// What was wrong with the arguments to an operation that failed with
+/// [`Error::BadArguments`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+pub enum BadArgumentsReason {
+    /// E.164 has more than 15 digits
+    E164OutOfRange,
+    /// unidentified access key has the wrong length
+    UnidentifiedAccessKeyLength,
+    /// unidentified access key without an E.164
+    UnidentifiedAccessKeyWithoutE164,
+    /// username hash must be 32 bytes
+    UsernameHashLength,
+    /// account data does not match the monitor request
+    AccountDataMismatch,
+    /// account data has the same monitoring data for more than one key
+    DuplicateMonitoringData,
+    /// no stored account data to monitor
+    MissingAccountData,
+    /// last distinguished tree head is required
+    MissingDistinguishedTreeHead,
+    /// no state store configured
+    MissingStateStore,
+    /// stored key rotations are invalid
+    InvalidKeyRotations,
+}
+
+impl Error {
+    /// The error for a response whose status isn't a success.
+    ///
+    /// A rate limited response keeps the delay the server asked for, so that
+    /// it can be honored.
+    fn for_failed_response(response: &chat::Response) -> Self {
+        match response.status {
+            http::StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                retry_after: extract_retry_later(&response.headers)
+                    .map(|retry_later| retry_later.delay()),
+            },
+            status => Error::RequestFailed(status),
+        }
+    }
+}
+
+impl From<BadArgumentsReason> for Error {
+    fn from(reason: BadArgumentsReason) -> Self {
+        Error::BadArguments(reason)
+    }
 }
 
 impl From<DecodeError> for Error {
@@ -67,19 +178,229 @@ impl From<DecodeError> for Error {
     }
 }
 
+impl From<EnvelopeError> for Error {
+    fn from(err: EnvelopeError) -> Self {
+        match err {
+            EnvelopeError::Status(status) => Error::RequestFailed(status),
+            EnvelopeError::MissingBody
+            | EnvelopeError::InvalidJson
+            | EnvelopeError::InvalidBase64
+            | EnvelopeError::TooLarge { .. }
+            | EnvelopeError::InvalidProtobuf(_) => Error::InvalidResponse(err.to_string()),
+        }
+    }
+}
+
+/// Which of a search's keys an [`Error::LegVerificationFailed`] is about.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+#[cfg_attr(
+    feature = "keytrans-trace",
+    derive(Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SearchKeyKind {
+    /// ACI
+    Aci,
+    /// E.164
+    E164,
+    /// username hash
+    UsernameHash,
+}
+
+/// How far an operation had gotten when its
+/// [overall deadline](Config::with_overall_deadline) passed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+pub enum OperationPhase {
+    /// waiting for the server
+    Request,
+    /// decoding the response
+    Decode,
+    /// verifying the response
+    Verify,
+}
+
+/// Tracks a single search, monitor, or distinguished operation against
+/// [`Config::with_overall_deadline`].
+struct OperationDeadline {
+    at: Option<tokio::time::Instant>,
+    phase: Mutex<OperationPhase>,
+}
+
+impl OperationDeadline {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            // A deadline too far away to represent is the same as none at all.
+            at: timeout.and_then(|timeout| tokio::time::Instant::now().checked_add(timeout)),
+            phase: OperationPhase::Request.into(),
+        }
+    }
+
+    fn phase(&self) -> OperationPhase {
+        *self.phase.lock().expect("not poisoned")
+    }
+
+    fn exceeded(&self) -> Error {
+        Error::DeadlineExceeded {
+            phase: self.phase(),
+        }
+    }
+
+    /// Fails if the deadline has already passed.
+    ///
+    /// Verification can't be interrupted by [`Self::run`], so this is checked
+    /// between its steps.
+    fn check(&self) -> Result<()> {
+        match self.at {
+            Some(at) if tokio::time::Instant::now() >= at => Err(self.exceeded()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the deadline, then records that `phase` has started.
+    fn enter(&self, phase: OperationPhase) -> Result<()> {
+        self.check()?;
+        *self.phase.lock().expect("not poisoned") = phase;
+        Ok(())
+    }
+
+    async fn run<T>(&self, operation: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.at {
+            None => operation.await,
+            Some(at) => tokio::time::timeout_at(at, operation)
+                .await
+                .unwrap_or_else(|_elapsed| Err(self.exceeded())),
+        }
+    }
+}
+
+/// What kind of problem caused an [`Error::VerificationFailed`] or
+/// [`Error::LegVerificationFailed`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerificationCategory {
+    /// A tree head signature didn't check out.
+    ///
+    /// This can mean the wrong keys are configured, or that someone other than
+    /// the log operator or auditor produced the tree head.
+    SignatureInvalid,
+    /// The response doesn't agree with tree heads or monitoring data verified
+    /// earlier.
+    ///
+    /// This is what the server presenting different views of the log to
+    /// different clients would look like.
+    ConsistencyViolation,
+    /// The proofs in the response are missing parts or can't be evaluated.
+    ProofMalformed,
+    /// A tree head is too old (or too far in the future).
+    ///
+    /// This usually means the local clock is wrong or the log is lagging.
+    Stale,
+}
+
+impl Error {
+    /// Classifies a verification failure, for reporting.
+    ///
+    /// Returns `None` for errors other than [`Error::VerificationFailed`] and
+    /// [`Error::LegVerificationFailed`].
+    pub fn verification_category(&self) -> Option<VerificationCategory> {
+        let (Error::VerificationFailed(inner) | Error::LegVerificationFailed { source: inner, .. }) =
+            self
+        else {
+            return None;
+        };
+        Some(match inner {
+            libsignal_keytrans::Error::InvalidSignature(_) => {
+                VerificationCategory::SignatureInvalid
+            }
+            libsignal_keytrans::Error::Inconsistent(_) => {
+                VerificationCategory::ConsistencyViolation
+            }
+            libsignal_keytrans::Error::Stale(_) => VerificationCategory::Stale,
+            libsignal_keytrans::Error::RequiredFieldMissing(_)
+            | libsignal_keytrans::Error::InvalidProofElement
+            | libsignal_keytrans::Error::ValueTooLong
+            | libsignal_keytrans::Error::VerificationFailed(_) => {
+                VerificationCategory::ProofMalformed
+            }
+        })
+    }
+
+    /// An [`Error::Internal`], after failing a debug assertion.
+    fn internal(message: &'static str) -> Self {
+        debug_assert!(false, "{message}");
+        Error::Internal(message)
+    }
+}
+
 type Result<T> = std::result::Result<T, Error>;
 
+/// An E.164 to search for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct E164SearchKey {
+    pub e164: E164,
+    /// Proves the searcher is allowed to find the account by its number.
+    ///
+    /// Needed to look up anyone else's number, but not one's own.
+    pub unidentified_access_key: Option<UnidentifiedAccessKey>,
+}
+
+impl E164SearchKey {
+    /// The largest number that fits in the 15 digits E.164 allows.
+    const MAX_E164: u64 = 999_999_999_999_999;
+
+    /// Checks that `e164` is in range for an E.164, and that the
+    /// unidentified access key, if present, is the right length.
+    pub fn new(e164: E164, unidentified_access_key: Option<&[u8]>) -> Result<Self> {
+        if u64::from_be_bytes(e164.to_be_bytes()) > Self::MAX_E164 {
+            return Err(BadArgumentsReason::E164OutOfRange.into());
+        }
+        Ok(Self {
+            e164,
+            unidentified_access_key: unidentified_access_key
+                .map(UnidentifiedAccessKey::try_from)
+                .transpose()
+                .map_err(|_| BadArgumentsReason::UnidentifiedAccessKeyLength)?,
+        })
+    }
+
+    /// Combines an E.164 and unidentified access key that were provided
+    /// separately.
+    ///
+    /// An unidentified access key without an E.164 is rejected, as is
+    /// anything [`Self::new`] rejects.
+    pub fn from_parts(
+        e164: Option<E164>,
+        unidentified_access_key: Option<&[u8]>,
+    ) -> Result<Option<Self>> {
+        match (e164, unidentified_access_key) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err(BadArgumentsReason::UnidentifiedAccessKeyWithoutE164.into()),
+            (Some(e164), unidentified_access_key) => {
+                Self::new(e164, unidentified_access_key).map(Some)
+            }
+        }
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RawChatSearchRequest {
     aci: String,
-    aci_identity_key: String,
+    /// Omitted when looking up a key we don't have yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aci_identity_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     e164: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     username_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     unidentified_access_key: Option<String>,
+    /// Size of the last tree head verified for this account, if any.
+    ///
+    /// The server proves the current tree is consistent with this size and, separately, with
+    /// `distinguished_tree_head_size`. The two are independent, and this one is routinely *ahead*
+    /// of the distinguished size: distinguished heads are published far less often than searches
+    /// are answered. Neither clamping it to the distinguished size nor rejecting it would be
+    /// correct; the former asks for a proof we can't check against our stored head.
     #[serde(skip_serializing_if = "Option::is_none")]
     last_tree_head_size: Option<u64>,
     distinguished_tree_head_size: u64,
@@ -88,50 +409,236 @@ struct RawChatSearchRequest {
 impl RawChatSearchRequest {
     fn new(
         aci: &Aci,
-        aci_identity_key: &PublicKey,
-        e164: Option<&(E164, Vec<u8>)>,
+        aci_identity_key: Option<&PublicKey>,
+        e164: Option<&E164SearchKey>,
         username_hash: Option<&UsernameHash>,
         last_tree_head_size: Option<u64>,
         distinguished_tree_head_size: u64,
     ) -> Self {
         Self {
             aci: aci.as_chat_value(),
-            aci_identity_key: BASE64_STANDARD.encode(aci_identity_key.serialize()),
-            e164: e164.map(|x| x.0.as_chat_value()),
+            aci_identity_key: aci_identity_key.map(|key| BASE64_STANDARD.encode(key.serialize())),
+            e164: e164.map(|x| x.e164.as_chat_value()),
             username_hash: username_hash.map(|x| x.as_chat_value()),
-            unidentified_access_key: e164.map(|x| BASE64_STANDARD.encode(&x.1)),
+            unidentified_access_key: e164
+                .and_then(|x| x.unidentified_access_key.as_ref())
+                .map(UnidentifiedAccessKey::to_base64),
             last_tree_head_size,
             distinguished_tree_head_size,
         }
     }
 }
 
-impl From<RawChatSearchRequest> for chat::Request {
-    fn from(request: RawChatSearchRequest) -> Self {
-        Self {
-            method: http::Method::POST,
-            body: Some(serde_json::to_vec(&request).unwrap().into_boxed_slice()),
-            headers: common_headers(),
-            path: PathAndQuery::from_static(SEARCH_PATH),
+impl TryFrom<RawChatSearchRequest> for chat::Request {
+    type Error = Error;
+
+    fn try_from(request: RawChatSearchRequest) -> std::result::Result<Self, Self::Error> {
+        Ok(request_builder(http::Method::POST)
+            .path(SEARCH_PATH)
+            .and_then(|builder| builder.json_body(&request))
+            .map_err(|_| Error::internal("search request could not be serialized"))?
+            .build()
+            .expect("path was set"))
+    }
+}
+
+/// Every hash in a proof is a SHA-256 output.
+const PROOF_HASH_LEN: usize = 32;
+/// Far more hashes than any honest proof over a 2^64-entry log needs.
+const MAX_PROOF_HASHES: usize = 4096;
+/// A search over a 2^64-entry log never takes more than two steps per level.
+const MAX_SEARCH_STEPS: usize = 128;
+
+/// A structural problem with a response, caught before any verification is attempted.
+#[derive(Debug, displaydoc::Display)]
+enum MalformedField {
+    /// {field} has {count} entries (at most {max} allowed)
+    TooManyEntries {
+        field: String,
+        count: usize,
+        max: usize,
+    },
+    /// {field} is {len} bytes (expected {PROOF_HASH_LEN})
+    WrongHashLength { field: String, len: usize },
+}
+
+impl From<MalformedField> for Error {
+    fn from(err: MalformedField) -> Self {
+        Error::InvalidResponse(err.to_string())
+    }
+}
+
+fn check_count(
+    field: impl FnOnce() -> String,
+    count: usize,
+    max: usize,
+) -> std::result::Result<(), MalformedField> {
+    if count > max {
+        return Err(MalformedField::TooManyEntries {
+            field: field(),
+            count,
+            max,
+        });
+    }
+    Ok(())
+}
+
+fn check_hash(
+    field: impl FnOnce() -> String,
+    hash: &[u8],
+) -> std::result::Result<(), MalformedField> {
+    if hash.len() != PROOF_HASH_LEN {
+        return Err(MalformedField::WrongHashLength {
+            field: field(),
+            len: hash.len(),
+        });
+    }
+    Ok(())
+}
+
+fn check_hashes(field: &str, hashes: &[Vec<u8>]) -> std::result::Result<(), MalformedField> {
+    check_count(|| field.to_owned(), hashes.len(), MAX_PROOF_HASHES)?;
+    for (i, hash) in hashes.iter().enumerate() {
+        check_hash(|| format!("{field}[{i}]"), hash)?;
+    }
+    Ok(())
+}
+
+/// Shallow checks on the proof material in a [`FullTreeHead`].
+///
+/// Only lengths and counts are checked here; whether the hashes are *correct* is left to
+/// verification.
+fn check_full_tree_head(full_tree_head: &FullTreeHead) -> std::result::Result<(), MalformedField> {
+    let FullTreeHead {
+        tree_head: _,
+        last,
+        distinguished,
+        auditor_tree_head,
+    } = full_tree_head;
+    check_hashes("tree_head.last", last)?;
+    check_hashes("tree_head.distinguished", distinguished)?;
+    if let Some(AuditorTreeHead {
+        tree_head: _,
+        root_value,
+        consistency,
+    }) = auditor_tree_head
+    {
+        if let Some(root_value) = root_value {
+            check_hash(
+                || "tree_head.auditor_tree_head.root_value".to_owned(),
+                root_value,
+            )?;
         }
+        check_hashes("tree_head.auditor_tree_head.consistency", consistency)?;
     }
+    Ok(())
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct RawChatSerializedResponse {
-    serialized_response: String,
+/// Shallow checks on the proof material in a [`CondensedTreeSearchResponse`].
+///
+/// `field` names the response for error messages.
+fn check_search_response(
+    field: &str,
+    response: &CondensedTreeSearchResponse,
+) -> std::result::Result<(), MalformedField> {
+    let Some(search) = &response.search else {
+        // Missing proofs are reported by verification.
+        return Ok(());
+    };
+    check_count(
+        || format!("{field}.search.steps"),
+        search.steps.len(),
+        MAX_SEARCH_STEPS,
+    )?;
+    for (i, step) in search.steps.iter().enumerate() {
+        if let Some(prefix) = &step.prefix {
+            check_hashes(
+                &format!("{field}.search.steps[{i}].prefix.proof"),
+                &prefix.proof,
+            )?;
+        }
+        check_hash(
+            || format!("{field}.search.steps[{i}].commitment"),
+            &step.commitment,
+        )?;
+    }
+    check_hashes(&format!("{field}.search.inclusion"), &search.inclusion)
 }
 
-impl TryFrom<chat::Response> for RawChatSerializedResponse {
-    type Error = Error;
+/// Shallow checks on the proof material in a [`MonitorProof`].
+///
+/// `field` names the proof for error messages.
+fn check_monitor_proof(
+    field: &str,
+    proof: &MonitorProof,
+) -> std::result::Result<(), MalformedField> {
+    check_count(
+        || format!("{field}.steps"),
+        proof.steps.len(),
+        MAX_SEARCH_STEPS,
+    )?;
+    for (i, step) in proof.steps.iter().enumerate() {
+        if let Some(prefix) = &step.prefix {
+            check_hashes(&format!("{field}.steps[{i}].prefix.proof"), &prefix.proof)?;
+        }
+        check_hash(
+            || format!("{field}.steps[{i}].commitment"),
+            &step.commitment,
+        )?;
+    }
+    Ok(())
+}
+
+/// The status a chat server is expected to respond with when the log doesn't
+/// index one of the requested search keys.
+///
+/// This is provisional. No published server API defines this error, and
+/// Signal's own chat server indexes every key and never sends it. Until a
+/// format is agreed with deployments that leave out a kind of search key, this
+/// recognizes a request to [`SEARCH_PATH`] being refused with
+/// `422 Unprocessable Entity` and a JSON body naming the key by its field name
+/// in the search request:
+///
+/// ```json
+/// {"code": "UNSUPPORTED_SEARCH_KEY", "searchKey": "usernameHash"}
+/// ```
+///
+/// `searchKey` is either `"e164"` or `"usernameHash"`; the ACI is always
+/// indexed. Any other 422, including one without this body, is still reported
+/// as [`Error::RequestFailed`]. Keep this internal until the format is settled.
+const UNSUPPORTED_SEARCH_KEY_STATUS: http::StatusCode = http::StatusCode::UNPROCESSABLE_ENTITY;
+/// The `code` in the JSON body accompanying [`UNSUPPORTED_SEARCH_KEY_STATUS`].
+const UNSUPPORTED_SEARCH_KEY_CODE: &str = "UNSUPPORTED_SEARCH_KEY";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UnsupportedSearchKeyBody {
+    code: String,
+    search_key: String,
+}
 
-    fn try_from(response: chat::Response) -> Result<Self> {
-        let body = response
-            .body
-            .ok_or(Error::InvalidResponse("missing body".to_string()))?;
-        serde_json::from_slice(&body)
-            .map_err(|_| Error::InvalidResponse("invalid JSON".to_string()))
+/// Recognizes the chat server refusing a search because the log doesn't index
+/// one of its keys.
+///
+/// Only keys that were actually requested are recognized; anything else is
+/// left to the usual status code handling.
+fn unsupported_search_key(
+    response: &chat::Response,
+    requested_e164: bool,
+    requested_username_hash: bool,
+) -> Option<AccountDataField> {
+    if response.status != UNSUPPORTED_SEARCH_KEY_STATUS {
+        return None;
+    }
+    let UnsupportedSearchKeyBody { code, search_key } =
+        serde_json::from_slice(response.body.as_deref()?).ok()?;
+    if code != UNSUPPORTED_SEARCH_KEY_CODE {
+        return None;
+    }
+    match search_key.as_str() {
+        "e164" if requested_e164 => Some(AccountDataField::E164),
+        "usernameHash" if requested_username_hash => Some(AccountDataField::UsernameHash),
+        _ => None,
     }
 }
 
@@ -162,32 +669,28 @@ impl TypedSearchResponse {
             e164,
             username_hash,
         } = response;
+        let full_tree_head =
+            tree_head.ok_or(Error::InvalidResponse("missing tree head".to_string()))?;
+        let aci_search_response = aci.ok_or(Error::InvalidResponse(
+            "missing ACI search response".to_string(),
+        ))?;
+        check_full_tree_head(&full_tree_head)?;
+        check_search_response("aci", &aci_search_response)?;
+        if let Some(e164) = &e164 {
+            check_search_response("e164", e164)?;
+        }
+        if let Some(username_hash) = &username_hash {
+            check_search_response("username_hash", username_hash)?;
+        }
         Ok(Self {
-            full_tree_head: tree_head
-                .ok_or(Error::InvalidResponse("missing tree head".to_string()))?,
-            aci_search_response: aci.ok_or(Error::InvalidResponse(
-                "missing ACI search response".to_string(),
-            ))?,
+            full_tree_head,
+            aci_search_response,
             e164_search_response: e164,
             username_hash_search_response: username_hash,
         })
     }
 }
 
-fn decode_response<S, R>(b64: S) -> Result<R>
-where
-    S: AsRef<str>,
-    R: Message + Default,
-{
-    let proto_bytes = BASE64_STANDARD_NO_PAD
-        .decode(b64.as_ref())
-        .map_err(|_| Error::InvalidResponse("invalid base64".to_string()))?;
-
-    R::decode(proto_bytes.as_slice()).map_err(|_| {
-        Error::InvalidResponse("invalid search response protobuf encoding".to_string())
-    })
-}
-
 // 0x00 is the current version prefix
 const SEARCH_VALUE_PREFIX: u8 = 0x00;
 
@@ -219,6 +722,14 @@ impl SearchValue<'_> {
     fn payload(&self) -> &[u8] {
         &self.raw[1..]
     }
+
+    /// Whether the value marks a mapping that was removed, such as a released
+    /// username.
+    ///
+    /// A tombstone is the version prefix with no payload.
+    fn is_tombstone(&self) -> bool {
+        self.payload().is_empty()
+    }
 }
 
 impl TryFrom<SearchValue<'_>> for Aci {
@@ -249,17 +760,22 @@ impl From<RawChatDistinguishedRequest> for chat::Request {
             .last_tree_head_size
             .map(|n| format!("lastTreeHeadSize={n}"))
             .unwrap_or_default();
-        let path_and_query = PathAndQuery::try_from(format!("{DISTINGUISHED_PATH}?{query_string}"))
-            .expect("valid path and query");
-        Self {
-            method: http::Method::GET,
-            body: None,
-            headers: common_headers(),
-            path: path_and_query,
-        }
+        request_builder(http::Method::GET)
+            .path(&format!("{DISTINGUISHED_PATH}?{query_string}"))
+            .expect("valid path and query")
+            .build()
+            .expect("path was set")
     }
 }
 
+fn key_rotation_request() -> chat::Request {
+    request_builder(http::Method::GET)
+        .path(KEY_ROTATION_PATH)
+        .expect("valid path")
+        .build()
+        .expect("path was set")
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ValueMonitor {
@@ -310,14 +826,19 @@ struct RawChatMonitorRequest {
     last_distinguished_tree_head_size: u64,
 }
 
-impl From<RawChatMonitorRequest> for chat::Request {
-    fn from(request: RawChatMonitorRequest) -> Self {
-        Self {
-            method: http::Method::POST,
-            body: Some(serde_json::to_vec(&request).unwrap().into_boxed_slice()),
-            headers: common_headers(),
-            path: PathAndQuery::from_static(MONITOR_PATH),
-        }
+impl TryFrom<RawChatMonitorRequest> for chat::Request {
+    type Error = Error;
+
+    fn try_from(request: RawChatMonitorRequest) -> std::result::Result<Self, Self::Error> {
+        Ok(request_builder(http::Method::POST)
+            .path(MONITOR_PATH)
+            .and_then(|builder| builder.json_body(&request))
+            .map_err(|_| Error::internal("monitor request could not be serialized"))?
+            // Batched monitor requests can carry many keys; only bodies past
+            // the connection's threshold are actually compressed.
+            .gzip_body()
+            .build()
+            .expect("path was set"))
     }
 }
 
@@ -334,9 +855,23 @@ impl RawChatMonitorRequest {
         if e164.is_some() != account_data.e164.is_some()
             || username_hash.is_some() != account_data.username_hash.is_some()
         {
-            return Err(Error::InvalidRequest(
-                "account data does not match the monitor request",
-            ));
+            return Err(BadArgumentsReason::AccountDataMismatch.into());
+        }
+
+        // Every key is committed to at its own index, so reused monitoring
+        // data (say, a stale entry saved under another key) can never verify.
+        // Catch it here rather than send a request whose proofs won't line up.
+        let mut indexes = BTreeSet::new();
+        if ![
+            Some(&account_data.aci),
+            account_data.e164.as_ref(),
+            account_data.username_hash.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .all(|data| indexes.insert(data.index))
+        {
+            return Err(BadArgumentsReason::DuplicateMonitoringData.into());
         }
 
         Ok(Self {
@@ -369,6 +904,275 @@ impl RawChatMonitorRequest {
     }
 }
 
+#[cfg(feature = "keytrans-blocking")]
+pub mod blocking;
+
+pub mod refresh;
+
+#[cfg(feature = "keytrans-trace")]
+pub mod trace;
+
+/// Where verification steps are recorded, if anywhere.
+///
+/// Without the `keytrans-trace` feature this is empty, and [`trace_event!`]
+/// expands to nothing.
+#[derive(Copy, Clone, Default)]
+struct Tracer<'a> {
+    #[cfg(feature = "keytrans-trace")]
+    trace: Option<&'a trace::VerificationTrace>,
+    #[cfg(not(feature = "keytrans-trace"))]
+    _trace: std::marker::PhantomData<&'a ()>,
+}
+
+/// Records the [`trace::TraceEvent`] built by `$event` if `$tracer` has a
+/// trace attached.
+///
+/// `$event` is only evaluated when there is a trace, and can use `$trace` to
+/// hash search keys.
+macro_rules! trace_event {
+    ($tracer:expr, |$trace:ident| $event:expr) => {
+        #[cfg(feature = "keytrans-trace")]
+        {
+            if let Some($trace) = $tracer.trace {
+                $trace.record($event);
+            }
+        }
+        #[cfg(not(feature = "keytrans-trace"))]
+        let _ = &$tracer;
+    };
+}
+
+/// Builds key transparency requests without a [`Kt`], for tools that want to
+/// send them some other way.
+///
+/// The requests are exactly what [`Kt`] would send for the same arguments.
+#[cfg(feature = "keytrans-request-builders")]
+pub mod request_builders {
+    use super::*;
+
+    pub fn search(
+        aci: &Aci,
+        aci_identity_key: Option<&PublicKey>,
+        e164: Option<&E164SearchKey>,
+        username_hash: Option<&UsernameHash>,
+        last_tree_head_size: Option<u64>,
+        distinguished_tree_head_size: u64,
+    ) -> chat::Request {
+        RawChatSearchRequest::new(
+            aci,
+            aci_identity_key,
+            e164,
+            username_hash,
+            last_tree_head_size,
+            distinguished_tree_head_size,
+        )
+        .into()
+    }
+
+    pub fn monitor(
+        aci: &Aci,
+        e164: Option<E164>,
+        username_hash: &Option<UsernameHash<'_>>,
+        account_data: &AccountData,
+        distinguished_tree_head_size: u64,
+    ) -> Result<chat::Request> {
+        RawChatMonitorRequest::new(
+            aci,
+            e164,
+            username_hash,
+            account_data,
+            distinguished_tree_head_size,
+        )
+        .map(Into::into)
+    }
+
+    pub fn distinguished(last_tree_head_size: Option<u64>) -> chat::Request {
+        RawChatDistinguishedRequest {
+            last_tree_head_size,
+        }
+        .into()
+    }
+
+    pub fn key_rotation() -> chat::Request {
+        key_rotation_request()
+    }
+}
+
+/// A key to monitor, along with its proof and the monitoring data to verify
+/// the proof against.
+struct MonitorEntry {
+    key: MonitorKey,
+    proof: MonitorProof,
+    data: MonitoringData,
+}
+
+impl MonitorEntry {
+    fn new(search_key: Vec<u8>, data: MonitoringData, proof: MonitorProof) -> Self {
+        Self {
+            key: MonitorKey {
+                search_key,
+                entry_position: data.latest_log_position(),
+                commitment_index: data.index.to_vec(),
+            },
+            proof,
+            data,
+        }
+    }
+}
+
+/// [`MonitorEntry`] items split up the way [`MonitorRequest`],
+/// [`MonitorResponse`], and [`MonitorContext`] want them.
+///
+/// `keys[i]` goes with `proofs[i]`. Duplicate search keys are an internal
+/// error; [`RawChatMonitorRequest::new`] rejects duplicated account data before
+/// a request is ever sent.
+struct MonitorParts {
+    keys: Vec<MonitorKey>,
+    proofs: Vec<MonitorProof>,
+    data: HashMap<Vec<u8>, MonitoringData>,
+}
+
+impl MonitorParts {
+    fn from_entries(entries: Vec<MonitorEntry>) -> Result<Self> {
+        let mut parts = Self {
+            keys: Vec::with_capacity(entries.len()),
+            proofs: Vec::with_capacity(entries.len()),
+            data: HashMap::with_capacity(entries.len()),
+        };
+        for MonitorEntry { key, proof, data } in entries {
+            match parts.data.entry(key.search_key.clone()) {
+                hash_map::Entry::Occupied(_) => {
+                    return Err(Error::internal("duplicate search key"))
+                }
+                hash_map::Entry::Vacant(entry) => entry.insert(data),
+            };
+            parts.keys.push(key);
+            parts.proofs.push(proof);
+        }
+        Ok(parts)
+    }
+}
+
+/// Remembers which monitored entries have already been verified against which
+/// tree heads, so that [`KtApi::monitor`] can skip verifying a response that
+/// proves nothing new.
+///
+/// Holds at most one entry per search key: verifying against a newer tree head
+/// replaces the old entry. When full, the least recently used search key is
+/// evicted.
+pub struct MonitorVerificationCache {
+    capacity: NonZeroUsize,
+    entries: Mutex<IndexMap<Vec<u8>, VerifiedMonitorEntry>>,
+}
+
+/// What a [`MonitorVerificationCache`] remembers about a search key.
+///
+/// A response only counts as already verified if all of these match exactly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct VerifiedMonitorEntry {
+    entry_position: u64,
+    tree_size: u64,
+    root: [u8; 32],
+    distinguished_tree_size: u64,
+}
+
+impl MonitorVerificationCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    fn contains(&self, search_key: &[u8], entry: &VerifiedMonitorEntry) -> bool {
+        let mut entries = self.entries.lock().expect("not poisoned");
+        let Some((index, _, cached)) = entries.get_full(search_key) else {
+            return false;
+        };
+        if cached != entry {
+            return false;
+        }
+        let last = entries.len() - 1;
+        entries.move_index(index, last);
+        true
+    }
+
+    fn insert(&self, search_key: Vec<u8>, entry: VerifiedMonitorEntry) {
+        let mut entries = self.entries.lock().expect("not poisoned");
+        entries.shift_remove(&search_key);
+        if entries.len() >= self.capacity.get() {
+            entries.shift_remove_index(0);
+        }
+        entries.insert(search_key, entry);
+    }
+}
+
+/// Whether key transparency is enabled for an environment, as reported by
+/// [`Kt::is_available`].
+#[derive(Debug)]
+pub enum KtAvailability {
+    Available,
+    /// The server doesn't offer key transparency at all.
+    Disabled,
+    /// The server couldn't be asked, or gave an answer that might change on
+    /// retry, such as a 5xx status.
+    Unreachable(Error),
+}
+
+impl KtAvailability {
+    fn for_response(response: &chat::Response) -> Self {
+        match response.status {
+            status if status.is_success() => Self::Available,
+            // 501 is the one 5xx that says the server will never support the
+            // request, rather than that it can't handle it right now.
+            http::StatusCode::NOT_FOUND | http::StatusCode::NOT_IMPLEMENTED => Self::Disabled,
+            _ => Self::Unreachable(Error::for_failed_response(response)),
+        }
+    }
+}
+
+/// Remembers the last definitive answer from [`Kt::is_available`] for a fixed
+/// period.
+///
+/// [`KtAvailability::Unreachable`] is never remembered, so that the next check
+/// asks the server again.
+pub struct KtAvailabilityCache {
+    ttl: Duration,
+    /// When the answer was received, and whether key transparency was
+    /// available.
+    cached: Mutex<Option<(tokio::time::Instant, bool)>>,
+}
+
+impl KtAvailabilityCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cached: Default::default(),
+        }
+    }
+
+    fn get(&self) -> Option<KtAvailability> {
+        let cached = *self.cached.lock().expect("not poisoned");
+        let (received_at, available) = cached?;
+        (received_at.elapsed() < self.ttl).then(|| {
+            if available {
+                KtAvailability::Available
+            } else {
+                KtAvailability::Disabled
+            }
+        })
+    }
+
+    fn record(&self, availability: &KtAvailability) {
+        let available = match availability {
+            KtAvailability::Available => true,
+            KtAvailability::Disabled => false,
+            KtAvailability::Unreachable(_) => return,
+        };
+        *self.cached.lock().expect("not poisoned") = Some((tokio::time::Instant::now(), available));
+    }
+}
+
 // Same as ChatMonitorResponse, only with the right optionality of fields
 #[derive(Clone, Debug)]
 struct TypedMonitorResponse {
@@ -380,13 +1184,23 @@ struct TypedMonitorResponse {
 }
 
 impl TypedMonitorResponse {
+    /// With `allow_dropped_legs`, a requested E.164 or username hash proof may
+    /// be missing from the response; see [`Config::with_dropped_monitor_legs`].
     fn from_untyped(
         require_e164: bool,
         require_username_hash: bool,
+        allow_dropped_legs: bool,
         response: ChatMonitorResponse,
     ) -> Result<Self> {
-        if require_e164 != response.e164.is_some()
-            || require_username_hash != response.username_hash.is_some()
+        let mismatched = |requested: bool, present: bool| {
+            if requested {
+                !present && !allow_dropped_legs
+            } else {
+                present
+            }
+        };
+        if mismatched(require_e164, response.e164.is_some())
+            || mismatched(require_username_hash, response.username_hash.is_some())
         {
             return Err(Error::InvalidResponse(
                 "request/response optionality mismatch".to_string(),
@@ -399,11 +1213,22 @@ impl TypedMonitorResponse {
             e164,
             inclusion,
         } = response;
+        let tree_head = tree_head.ok_or(Error::InvalidResponse("missing tree head".to_string()))?;
+        let aci = aci.ok_or(Error::InvalidResponse(
+            "missing ACI monitor proof".to_string(),
+        ))?;
+        check_full_tree_head(&tree_head)?;
+        check_monitor_proof("aci", &aci)?;
+        if let Some(e164) = &e164 {
+            check_monitor_proof("e164", e164)?;
+        }
+        if let Some(username_hash) = &username_hash {
+            check_monitor_proof("username_hash", username_hash)?;
+        }
+        check_hashes("inclusion", &inclusion)?;
         Ok(Self {
-            tree_head: tree_head.ok_or(Error::InvalidResponse("missing tree head".to_string()))?,
-            aci: aci.ok_or(Error::InvalidResponse(
-                "missing ACI monitor proof".to_string(),
-            ))?,
+            tree_head,
+            aci,
             e164,
             username_hash,
             inclusion,
@@ -419,24 +1244,683 @@ pub trait UnauthenticatedChat {
     ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>>;
 }
 
+pub trait AuthenticatedChat {
+    fn send_authenticated(
+        &self,
+        request: chat::Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>>;
+}
+
+impl<T: UnauthenticatedChat + ?Sized> UnauthenticatedChat for &T {
+    fn send_unauthenticated(
+        &self,
+        request: chat::Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+        (**self).send_unauthenticated(request, timeout)
+    }
+}
+
+impl<T: AuthenticatedChat + ?Sized> AuthenticatedChat for &T {
+    fn send_authenticated(
+        &self,
+        request: chat::Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+        (**self).send_authenticated(request, timeout)
+    }
+}
+
+/// The chat connection a [`Kt`] sends its requests over.
+///
+/// Only borrows the connection if the lifetime `'a` isn't `'static`; see
+/// [`Kt::new_shared`].
+#[derive(Clone)]
+pub enum KtChat<'a> {
+    Unauthenticated(Arc<dyn UnauthenticatedChat + Send + Sync + 'a>),
+    /// The server knows who is asking, so requests don't need to prove access
+    /// to the account being searched for.
+    Authenticated(Arc<dyn AuthenticatedChat + Send + Sync + 'a>),
+}
+
+impl KtChat<'_> {
+    fn send(
+        &self,
+        request: chat::Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+        match self {
+            Self::Unauthenticated(chat) => chat.send_unauthenticated(request, timeout),
+            Self::Authenticated(chat) => chat.send_authenticated(request, timeout),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
+    /// How long each request is given, unless [`Self::deadline`] is sooner.
     chat_timeout: Duration,
+    request_priority: chat::Priority,
+    /// Responses are verified as of the time reported by this clock.
+    clock: Arc<dyn Clock>,
+    /// If set, no request will be sent after this time, and requests will be
+    /// given at most until this time to complete.
+    ///
+    /// This is measured against the local clock, not [`Self::clock`].
+    deadline: Option<SystemTime>,
+    /// How long each operation is given from start to finish, including
+    /// decoding and verifying responses.
+    overall_deadline: Option<Duration>,
+    /// Called when a response fails verification with a
+    /// [`VerificationCategory::ConsistencyViolation`].
+    on_consistency_violation: Option<ConsistencyViolationCallback>,
+    /// Called with the [`ProofMetrics`] of every successfully verified
+    /// response.
+    on_proof_metrics: Option<ProofMetricsCallback>,
+    /// Lets [`KtApi::monitor`] skip verifying responses it has already
+    /// verified.
+    monitor_verification_cache: Option<Arc<MonitorVerificationCache>>,
+    /// Lets [`Kt::is_available`] reuse a recent answer.
+    availability_cache: Option<Arc<KtAvailabilityCache>>,
+    /// How far in the future (according to [`Self::clock`]) a tree head's
+    /// timestamp can be. Defaults to what verification allows.
+    max_clock_skew: Duration,
+    /// How far in the past (according to [`Self::clock`]) a tree head's
+    /// timestamp can be.
+    max_tree_head_age: Duration,
+    /// How far a search's tree head can lag behind the distinguished tree
+    /// head.
+    view_freshness: ViewFreshness,
+    /// How far the auditor's tree head can be behind the log's, in entries.
+    max_auditor_lag: Option<u64>,
+    /// Checked against (and updated with) the identity key of every verified
+    /// search.
+    pin_store: Option<Arc<dyn KtPinStore>>,
+    /// Where the `*_with_store` operations of [`Kt`] keep their state.
+    state_store: Option<Arc<dyn KtStateStore>>,
+    /// Bounds the requests in flight at once, across every [`Kt`] sharing
+    /// this config.
+    request_limit: RequestLimit,
+    /// Retry searches without any search key the server reports it doesn't
+    /// support, instead of failing with [`Error::UnsupportedSearchKey`].
+    auto_drop_unsupported_keys: bool,
+    /// Report a username hash that maps to another account, or to nothing, as
+    /// a [`UsernameChange`] instead of failing or returning the other ACI.
+    detect_username_changes: bool,
+    /// Let a monitor response leave out the E.164 or username hash proof,
+    /// dropping that key from monitoring, instead of failing with
+    /// [`Error::InvalidResponse`].
+    allow_dropped_monitor_legs: bool,
+    /// If set, [`KtApi::monitor`] doesn't send a request for account data
+    /// already verified against a tree head this fresh; see
+    /// [`Config::with_monitor_skip_window`].
+    monitor_skip_window: Option<Duration>,
+    /// The largest response message that will be decoded, in bytes.
+    max_response_size: usize,
+    /// How much of each request and response body to log, since key
+    /// transparency proofs can be large.
+    #[cfg(feature = "keytrans-body-logging")]
+    max_logged_body_len: usize,
+    /// Where every verification step is recorded; see
+    /// [`Config::with_verification_trace`].
+    #[cfg(feature = "keytrans-trace")]
+    verification_trace: Option<Arc<trace::VerificationTrace>>,
+}
+
+/// See [`Config::with_max_concurrent_requests`].
+#[derive(Clone)]
+struct RequestLimit {
+    max: NonZeroUsize,
+    /// Tokio's semaphore is fair, so waiting requests are sent in the order
+    /// they were made.
+    permits: Arc<tokio::sync::Semaphore>,
+}
+
+impl RequestLimit {
+    const DEFAULT_MAX: NonZeroUsize = nonzero_ext::nonzero!(4usize);
+
+    fn new(max: NonZeroUsize) -> Self {
+        Self {
+            max,
+            permits: Arc::new(tokio::sync::Semaphore::new(max.get())),
+        }
+    }
+
+    fn in_flight(&self) -> usize {
+        self.max.get() - self.permits.available_permits()
+    }
+}
+
+/// How far the tree head a search is answered from can lag behind the
+/// distinguished tree head, before the search fails with
+/// [`Error::StaleView`].
+///
+/// A server that answers searches from an old (but consistent) view of the log
+/// can hide recent key changes; comparing against the distinguished tree head,
+/// which everyone sees, limits how long it can do so.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViewFreshness {
+    /// How much smaller the search tree can be, in log entries.
+    ///
+    /// `None` means no limit.
+    pub max_size_lag: Option<u64>,
+    /// How much older the search tree head's timestamp can be.
+    ///
+    /// `None` means no limit.
+    pub max_time_lag: Option<Duration>,
+}
+
+impl Default for ViewFreshness {
+    fn default() -> Self {
+        // Searches are answered from the latest tree head, and distinguished
+        // tree heads are only published once the log has reached them, so an
+        // honest server is never behind by more than its replication lag.
+        Self {
+            max_size_lag: None,
+            max_time_lag: Some(Duration::from_secs(60 * 60)),
+        }
+    }
+}
+
+impl ViewFreshness {
+    fn check(&self, search: &TreeHead, distinguished: &TreeHead) -> Result<()> {
+        let size_lag = distinguished.tree_size.saturating_sub(search.tree_size);
+        let time_lag = Duration::from_millis(
+            distinguished
+                .timestamp
+                .saturating_sub(search.timestamp)
+                .try_into()
+                .unwrap_or_default(),
+        );
+        let too_small = self.max_size_lag.is_some_and(|max| size_lag > max);
+        let too_old = self.max_time_lag.is_some_and(|max| time_lag > max);
+        if too_small || too_old {
+            return Err(Error::StaleView {
+                search_size: search.tree_size,
+                distinguished_size: distinguished.tree_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Remembers the first ACI identity key verified for each account, for
+/// trust-on-first-use checking of later searches.
+///
+/// See [`Config::with_pin_store`]. Keys are identified by
+/// [`pinned_key_hash`], so the store never needs to hold the keys themselves.
+///
+/// This is only available to Rust callers; the app bridges don't expose a way
+/// to supply a store.
+pub trait KtPinStore: Send + Sync {
+    fn pinned_key_hash(&self, aci: &Aci) -> Option<[u8; 32]>;
+    fn set_pinned_key_hash(&self, aci: &Aci, key_hash: [u8; 32]);
+}
+
+/// The hash of `key` stored by a [`KtPinStore`].
+pub fn pinned_key_hash(key: &IdentityKey) -> [u8; 32] {
+    Sha256::digest(key.serialize()).into()
+}
+
+/// Keeps the key transparency state of each account, and the latest
+/// distinguished tree head, between operations.
+///
+/// See [`Config::with_state_store`]. State is kept in its serializable form,
+/// exactly as an app managing it itself would persist it.
+pub trait KtStateStore: Send + Sync {
+    fn account_data(
+        &self,
+        aci: Aci,
+    ) -> BoxFuture<'_, std::result::Result<Option<StoredAccountData>, KtStateStoreError>>;
+    fn set_account_data(
+        &self,
+        aci: Aci,
+        account_data: StoredAccountData,
+    ) -> BoxFuture<'_, std::result::Result<(), KtStateStoreError>>;
+    fn distinguished_tree_head(
+        &self,
+    ) -> BoxFuture<'_, std::result::Result<Option<StoredTreeHead>, KtStateStoreError>>;
+    fn set_distinguished_tree_head(
+        &self,
+        tree_head: StoredTreeHead,
+    ) -> BoxFuture<'_, std::result::Result<(), KtStateStoreError>>;
 }
 
+/// A failure reported by a [`KtStateStore`].
+pub type KtStateStoreError = Box<dyn std::error::Error + Send + Sync>;
+
+/// See [`Config::with_consistency_violation_callback`].
+pub type ConsistencyViolationCallback = Arc<dyn Fn(&Error) + Send + Sync>;
+
+/// See [`Config::with_proof_metrics_callback`].
+pub type ProofMetricsCallback = Arc<dyn Fn(KtOperation, &ProofMetrics) + Send + Sync>;
+
 impl Default for Config {
     fn default() -> Self {
         Self {
-            chat_timeout: Duration::from_secs(10),
+            chat_timeout: Self::DEFAULT_CHAT_TIMEOUT,
+            // Key transparency checks aren't usually something a user is
+            // actively waiting on.
+            request_priority: chat::Priority::Background,
+            clock: Arc::new(SystemClock),
+            deadline: None,
+            overall_deadline: None,
+            on_consistency_violation: None,
+            on_proof_metrics: None,
+            monitor_verification_cache: None,
+            availability_cache: None,
+            max_clock_skew: libsignal_keytrans::MAX_TREE_HEAD_CLOCK_SKEW,
+            max_tree_head_age: libsignal_keytrans::MAX_TREE_HEAD_AGE,
+            view_freshness: ViewFreshness::default(),
+            max_auditor_lag: None,
+            pin_store: None,
+            state_store: None,
+            request_limit: RequestLimit::new(RequestLimit::DEFAULT_MAX),
+            auto_drop_unsupported_keys: false,
+            detect_username_changes: false,
+            allow_dropped_monitor_legs: false,
+            monitor_skip_window: None,
+            max_response_size: Self::DEFAULT_MAX_RESPONSE_SIZE,
+            #[cfg(feature = "keytrans-body-logging")]
+            max_logged_body_len: Self::DEFAULT_MAX_LOGGED_BODY_LEN,
+            #[cfg(feature = "keytrans-trace")]
+            verification_trace: None,
+        }
+    }
+}
+
+impl Config {
+    pub const DEFAULT_CHAT_TIMEOUT: Duration = Duration::from_secs(10);
+    /// The most [`Kt::is_available`] waits for an answer, since it's meant to
+    /// be cheap enough to check before showing any key transparency UI.
+    pub const AVAILABILITY_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+    /// Four times the largest response on record: the search for an ACI,
+    /// E.164, and username hash in `tests/data/chat_search_response.dat` is
+    /// 504,145 bytes, about 170 KB per key.
+    ///
+    /// Responses are dominated by proofs that grow with the logarithm of the
+    /// log's size, so this leaves plenty of room for the log to grow.
+    pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 2 << 20;
+    #[cfg(feature = "keytrans-body-logging")]
+    pub const DEFAULT_MAX_LOGGED_BODY_LEN: usize = 1024;
+
+    /// The defaults for talking to `env`'s key transparency deployment.
+    ///
+    /// These differ from [`Config::default()`] only in the settings the
+    /// environment's [`KeyTransConfig`](crate::env::KeyTransConfig) tunes. An
+    /// environment without key transparency gets the plain defaults.
+    ///
+    /// Each call creates a new [request limit](Self::with_max_concurrent_requests),
+    /// so keep the result around and clone it for each operation rather than
+    /// calling this every time.
+    pub fn for_env(env: &crate::env::Env<'_>) -> Self {
+        let config = Self::default();
+        match &env.keytrans_config {
+            Some(keytrans_config) => config.with_chat_timeout(keytrans_config.request_timeout),
+            None => config,
+        }
+    }
+
+    /// Gives each request `timeout` to complete, unless the
+    /// [deadline](Self::with_deadline) is sooner.
+    pub fn with_chat_timeout(self, timeout: Duration) -> Self {
+        Self {
+            chat_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// Logs at most `max_len` bytes of each request and response body.
+    #[cfg(feature = "keytrans-body-logging")]
+    pub fn with_max_logged_body_len(self, max_len: usize) -> Self {
+        Self {
+            max_logged_body_len: max_len,
+            ..self
+        }
+    }
+
+    /// Records each step of verifying search and monitor responses in
+    /// `trace`.
+    ///
+    /// Several configs can share a trace.
+    #[cfg(feature = "keytrans-trace")]
+    pub fn with_verification_trace(self, trace: Arc<trace::VerificationTrace>) -> Self {
+        Self {
+            verification_trace: Some(trace),
+            ..self
+        }
+    }
+
+    /// Fails with [`Error::InvalidResponse`] rather than decode a response
+    /// message larger than `max_size` bytes.
+    pub fn with_max_response_size(self, max_size: usize) -> Self {
+        Self {
+            max_response_size: max_size,
+            ..self
+        }
+    }
+
+    pub fn with_request_priority(self, request_priority: chat::Priority) -> Self {
+        Self {
+            request_priority,
+            ..self
+        }
+    }
+
+    /// Uses `clock` instead of the local system clock to check the freshness
+    /// of responses.
+    ///
+    /// The point of key transparency is not to trust the chat server, so a
+    /// clock that follows the server's timestamps should be a
+    /// [`BoundedServerClock`](crate::server_time::BoundedServerClock), which
+    /// only lets the server move the time responses are checked at by a few
+    /// minutes.
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// Limits all requests made with this config to finish by `deadline`.
+    ///
+    /// Each request is still subject to the usual per-request timeout.
+    pub fn with_deadline(self, deadline: SystemTime) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Gives each search, monitor, or distinguished operation `timeout` to
+    /// finish, failing with [`Error::DeadlineExceeded`] otherwise.
+    ///
+    /// Unlike [`Self::with_chat_timeout`], this also covers decoding and
+    /// verifying responses, which can take a while for large responses on slow
+    /// devices.
+    pub fn with_overall_deadline(self, timeout: Duration) -> Self {
+        Self {
+            overall_deadline: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Calls `callback` whenever a response is inconsistent with previously
+    /// verified state.
+    ///
+    /// The operation still fails with the same error; this is for apps that
+    /// want to alert the user or report a possible split view of the log.
+    pub fn with_consistency_violation_callback(
+        self,
+        callback: ConsistencyViolationCallback,
+    ) -> Self {
+        Self {
+            on_consistency_violation: Some(callback),
+            ..self
+        }
+    }
+
+    /// Calls `callback` with the sizes of the proofs in every response that
+    /// passes verification, and how long verification took.
+    pub fn with_proof_metrics_callback(self, callback: ProofMetricsCallback) -> Self {
+        Self {
+            on_proof_metrics: Some(callback),
+            ..self
+        }
+    }
+
+    /// Remembers verified monitor responses in `cache`, to skip verifying them
+    /// again.
+    ///
+    /// The cache should be shared between all the [`Kt`] instances monitoring
+    /// the same log.
+    pub fn with_monitor_verification_cache(self, cache: Arc<MonitorVerificationCache>) -> Self {
+        Self {
+            monitor_verification_cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// Remembers the answers of [`Kt::is_available`] in `cache`.
+    ///
+    /// The cache should be shared between all the [`Kt`] instances talking to
+    /// the same environment.
+    pub fn with_availability_cache(self, cache: Arc<KtAvailabilityCache>) -> Self {
+        Self {
+            availability_cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// Rejects tree heads with timestamps outside the given window around the
+    /// current time, with [`Error::TreeHeadOutOfRange`].
+    ///
+    /// The window defaults to the one verification enforces anyway
+    /// ([`libsignal_keytrans::MAX_TREE_HEAD_CLOCK_SKEW`] and
+    /// [`libsignal_keytrans::MAX_TREE_HEAD_AGE`]). This check runs first, so
+    /// that timestamps that are off are reported as such rather than as
+    /// verification failures. Widening the window doesn't make verification
+    /// accept anything it otherwise wouldn't; narrowing it does tighten the
+    /// check.
+    pub fn with_tree_head_time_window(
+        self,
+        max_clock_skew: Duration,
+        max_tree_head_age: Duration,
+    ) -> Self {
+        Self {
+            max_clock_skew,
+            max_tree_head_age,
+            ..self
+        }
+    }
+
+    /// Rejects searches answered from a tree head that lags too far behind
+    /// the distinguished tree head, with [`Error::StaleView`].
+    pub fn with_view_freshness(self, view_freshness: ViewFreshness) -> Self {
+        Self {
+            view_freshness,
+            ..self
+        }
+    }
+
+    /// Fails with [`Error::AuditorTooFarBehind`] if a third-party auditor's
+    /// tree head is more than `max_lag` entries behind the log's.
+    ///
+    /// Verification already rejects auditor tree heads that are wildly out of
+    /// date; this is for apps that want a tighter bound. It has no effect on
+    /// deployments without a third-party auditor.
+    pub fn with_max_auditor_lag(self, max_lag: u64) -> Self {
+        Self {
+            max_auditor_lag: Some(max_lag),
+            ..self
+        }
+    }
+
+    /// Pins the ACI identity key of each account the first time a search for
+    /// it is verified, and fails later searches that return a different key
+    /// with [`Error::PinnedKeyMismatch`].
+    ///
+    /// This is for apps that don't monitor accounts over time; it stops the
+    /// log from quietly changing a key after it has been seen once.
+    ///
+    /// Pinning is Rust-only: the Java, Swift, and TypeScript clients build
+    /// their [`Config`] without a pin store, so it never applies to them.
+    pub fn with_pin_store(self, store: Arc<dyn KtPinStore>) -> Self {
+        Self {
+            pin_store: Some(store),
+            ..self
+        }
+    }
+
+    /// Lets [`Kt::search_with_store`], [`Kt::monitor_with_store`], and
+    /// [`Kt::distinguished_with_store`] load the state they start from out of
+    /// `store`, and save the updated state back to it before returning.
+    ///
+    /// The [`KtApi`] operations, which take their state as arguments, don't
+    /// use the store.
+    pub fn with_state_store(self, store: Arc<dyn KtStateStore>) -> Self {
+        Self {
+            state_store: Some(store),
+            ..self
+        }
+    }
+
+    /// Sends at most `max` requests at a time; the rest wait their turn, in
+    /// the order they were made.
+    ///
+    /// The limit is shared by every [`Kt`] using this config (or a clone of
+    /// it), so that bursts of key transparency requests don't crowd out other
+    /// traffic on the same chat connection. The default is 4.
+    pub fn with_max_concurrent_requests(self, max: NonZeroUsize) -> Self {
+        Self {
+            request_limit: RequestLimit::new(max),
+            ..self
+        }
+    }
+
+    /// Retries a search without the E.164 or username hash if the server
+    /// reports that it doesn't index that kind of key, rather than failing with
+    /// [`Error::UnsupportedSearchKey`].
+    ///
+    /// Dropped keys are reported in [`MaybePartial::missing_fields`], same as
+    /// if the server had left them out of its response.
+    pub fn with_auto_drop_unsupported_keys(self) -> Self {
+        Self {
+            auto_drop_unsupported_keys: true,
+            ..self
+        }
+    }
+
+    /// Checks that a searched-for username hash still belongs to the searched
+    /// ACI, reporting in [`SearchResult::username_change`] if it doesn't.
+    ///
+    /// Without this, a hash that moved to another account is returned as that
+    /// account's ACI in [`SearchResult::aci_for_username_hash`], and one whose
+    /// username was released fails the search with
+    /// [`Error::InvalidResponse`].
+    pub fn with_username_change_detection(self) -> Self {
+        Self {
+            detect_username_changes: true,
+            ..self
+        }
+    }
+
+    /// Treats an E.164 or username hash proof missing from a monitor response
+    /// as the server no longer monitoring that key, rather than failing with
+    /// [`Error::InvalidResponse`].
+    ///
+    /// The rest of the response is verified as usual. The dropped keys are
+    /// left out of the returned [`AccountData`] and reported in
+    /// [`MonitorResult::dropped_legs`].
+    pub fn with_dropped_monitor_legs(self) -> Self {
+        Self {
+            allow_dropped_monitor_legs: true,
+            ..self
+        }
+    }
+
+    /// Skips monitoring when the stored account data is recent enough.
+    ///
+    /// If the tree head the stored account data was verified against is at
+    /// most `window` older than the distinguished tree head, [`KtApi::monitor`]
+    /// returns the stored data without sending a request, and sets
+    /// [`MonitorResult::skipped`]. The stored data must be known to be for
+    /// the same ACI, E.164, and username hash being monitored, which is
+    /// recorded by the search that first found them; data saved before that
+    /// was recorded is always monitored.
+    ///
+    /// The window is measured between the two tree heads, not against
+    /// [`Self::with_clock`]: keeping the distinguished tree head itself fresh
+    /// is up to the caller, who refreshes it with [`KtApi::distinguished`].
+    ///
+    /// By default, a request is always sent.
+    pub fn with_monitor_skip_window(self, window: Duration) -> Self {
+        Self {
+            monitor_skip_window: Some(window),
+            ..self
+        }
+    }
+
+    fn request_timeout(&self) -> std::result::Result<Duration, chat::SendError> {
+        match self.deadline {
+            None => Ok(self.chat_timeout),
+            Some(deadline) => {
+                Ok(chat::timeout_until(deadline, SystemTime::now())?.min(self.chat_timeout))
+            }
         }
     }
 }
 
+/// Cloning a `Kt` is cheap, and the clone shares the original's chat
+/// connection.
+#[derive(Clone)]
 pub struct Kt<'a> {
     pub inner: KeyTransparency,
-    pub chat: &'a (dyn UnauthenticatedChat + Sync),
+    pub chat: KtChat<'a>,
     pub config: Config,
 }
 
+impl<'a> Kt<'a> {
+    /// Sends requests over a borrowed chat connection.
+    ///
+    /// To store a `Kt` or move it to another task, use [`Kt::new_shared`]
+    /// instead.
+    pub fn new(
+        inner: KeyTransparency,
+        chat: &'a (dyn UnauthenticatedChat + Sync),
+        config: Config,
+    ) -> Self {
+        Self {
+            inner,
+            chat: KtChat::Unauthenticated(Arc::new(chat)),
+            config,
+        }
+    }
+
+    /// Like [`Kt::new`], but sends requests over an authenticated connection.
+    ///
+    /// Unidentified access keys are never sent in this mode.
+    pub fn new_authenticated(
+        inner: KeyTransparency,
+        chat: &'a (dyn AuthenticatedChat + Sync),
+        config: Config,
+    ) -> Self {
+        Self {
+            inner,
+            chat: KtChat::Authenticated(Arc::new(chat)),
+            config,
+        }
+    }
+}
+
+impl Kt<'static> {
+    /// Like [`Kt::new`], but shares ownership of the chat connection, so the
+    /// result can outlive the caller.
+    pub fn new_shared(
+        inner: KeyTransparency,
+        chat: Arc<dyn UnauthenticatedChat + Send + Sync>,
+        config: Config,
+    ) -> Self {
+        Self {
+            inner,
+            chat: KtChat::Unauthenticated(chat),
+            config,
+        }
+    }
+
+    /// Like [`Kt::new_authenticated`], but shares ownership of the chat
+    /// connection, so the result can outlive the caller.
+    pub fn new_shared_authenticated(
+        inner: KeyTransparency,
+        chat: Arc<dyn AuthenticatedChat + Send + Sync>,
+        config: Config,
+    ) -> Self {
+        Self {
+            inner,
+            chat: KtChat::Authenticated(chat),
+            config,
+        }
+    }
+}
+
 /// A tag identifying an optional field in [`AccountData`]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, displaydoc::Display)]
 pub enum AccountDataField {
@@ -523,13 +2007,188 @@ impl<T, E> MaybePartial<std::result::Result<T, E>> {
     }
 }
 
+/// The key transparency operation a [`ProofMetrics`] is for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "keytrans-trace",
+    derive(Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum KtOperation {
+    Search,
+    Monitor,
+    Distinguished,
+}
+
+/// How big the proofs in a verified response were, and how long it took to
+/// verify them.
+///
+/// Steady growth is expected as the log grows; a sudden jump is worth looking
+/// into.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProofMetrics {
+    /// Number of hashes in the consistency proof from the last tree head the
+    /// client saw.
+    pub last_consistency_hashes: usize,
+    /// Number of hashes in the consistency proof from the last distinguished
+    /// tree head.
+    pub distinguished_consistency_hashes: usize,
+    /// Number of inclusion proof hashes, across all the keys in the response.
+    pub inclusion_hashes: usize,
+    /// Time spent verifying the response.
+    pub verification_time: Duration,
+}
+
+impl ProofMetrics {
+    fn new(full_tree_head: &FullTreeHead, inclusion_hashes: usize) -> Self {
+        Self {
+            last_consistency_hashes: full_tree_head.last.len(),
+            distinguished_consistency_hashes: full_tree_head.distinguished.len(),
+            inclusion_hashes,
+            verification_time: Duration::ZERO,
+        }
+    }
+
+    fn search_inclusion_hashes<'a>(
+        responses: impl IntoIterator<Item = &'a CondensedTreeSearchResponse>,
+    ) -> usize {
+        responses
+            .into_iter()
+            .filter_map(|response| response.search.as_ref())
+            .map(|search| search.inclusion.len())
+            .sum()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SearchResult {
+    /// The ACI identity key recorded in the log.
+    ///
+    /// For [`KtApi::search`] this has been checked to match the key passed in;
+    /// for [`Kt::lookup`] it is the key the log vouches for.
     pub aci_identity_key: IdentityKey,
     pub aci_for_e164: Option<Aci>,
     pub aci_for_username_hash: Option<Aci>,
     pub timestamp: SystemTime,
     pub account_data: StoredAccountData,
+    pub proof_metrics: ProofMetrics,
+    /// How many entries the third-party auditor's tree head is behind the
+    /// log's.
+    ///
+    /// `None` if the deployment doesn't use a third-party auditor.
+    pub auditor_lag: Option<u64>,
+    /// The timestamp of the third-party auditor's tree head, if there is one.
+    pub auditor_timestamp: Option<SystemTime>,
+    /// When the E.164-to-ACI mapping last changed, if an E.164 was searched
+    /// for and found.
+    pub e164_binding_age: Option<BindingAge>,
+    /// When the username-hash-to-ACI mapping last changed, if a username hash
+    /// was searched for and found.
+    pub username_binding_age: Option<BindingAge>,
+    /// Set if the searched username hash no longer belongs to the searched
+    /// ACI.
+    ///
+    /// Only reported with [`Config::with_username_change_detection`]. When
+    /// this is set, [`Self::aci_for_username_hash`] and
+    /// [`Self::username_binding_age`] are `None`, and no monitoring data is
+    /// kept for the username hash.
+    pub username_change: Option<UsernameChange>,
+    /// How the new tree head was checked against the stored last and
+    /// distinguished tree heads.
+    ///
+    /// Every search key in a request is checked against the same tree heads,
+    /// so this is taken from the ACI's result.
+    pub consistency: TreeHeadConsistency,
+}
+
+/// How a username hash's mapping differs from the account it was searched
+/// for with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsernameChange {
+    /// The username now belongs to another account.
+    Moved { new_aci: Aci },
+    /// The username was released and doesn't belong to any account.
+    Removed,
+}
+
+/// When a search key's mapping last changed in the log, as far as a search can
+/// tell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BindingAge {
+    /// The position of the log entry that last changed the mapping.
+    pub log_position: u64,
+    /// The timestamp of the smallest known tree head that includes that entry.
+    ///
+    /// The mapping changed no later than this. How much earlier depends on
+    /// which tree heads the client has seen: the distinguished tree head and
+    /// the stored tree head narrow it down, if they include the entry.
+    pub updated_by: SystemTime,
+}
+
+impl BindingAge {
+    fn new<'a>(
+        monitoring_data: &MonitoringData,
+        known_tree_heads: impl IntoIterator<Item = &'a TreeHead>,
+    ) -> Option<Self> {
+        let log_position = monitoring_data.latest_log_position();
+        let smallest_including = known_tree_heads
+            .into_iter()
+            .filter(|tree_head| tree_head.tree_size > log_position)
+            .min_by_key(|tree_head| tree_head.tree_size)?;
+        Some(Self {
+            log_position,
+            updated_by: tree_head_timestamp(smallest_including),
+        })
+    }
+}
+
+/// The result of [`KtApi::distinguished`].
+#[derive(Debug, Clone)]
+pub struct DistinguishedResult {
+    pub state_update: SearchStateUpdate,
+    /// See [`SearchResult::auditor_lag`].
+    pub auditor_lag: Option<u64>,
+    /// See [`SearchResult::auditor_timestamp`].
+    pub auditor_timestamp: Option<SystemTime>,
+    /// See [`SearchResult::proof_metrics`].
+    pub proof_metrics: ProofMetrics,
+}
+
+/// The third-party auditor's tree head, relative to the log's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct AuditorView {
+    lag: u64,
+    timestamp: SystemTime,
+}
+
+impl AuditorView {
+    /// Returns `None` unless `mode` uses a third-party auditor and the tree
+    /// head includes the auditor's.
+    fn new(mode: &DeploymentMode, full_tree_head: &FullTreeHead) -> Option<Self> {
+        let DeploymentMode::ThirdPartyAuditing(_) = mode else {
+            return None;
+        };
+        let tree_head = full_tree_head.tree_head.as_ref()?;
+        let auditor_tree_head = full_tree_head
+            .auditor_tree_head
+            .as_ref()?
+            .tree_head
+            .as_ref()?;
+        Some(Self {
+            lag: tree_head
+                .tree_size
+                .saturating_sub(auditor_tree_head.tree_size),
+            timestamp: tree_head_timestamp(auditor_tree_head),
+        })
+    }
+}
+
+fn tree_head_timestamp(tree_head: &TreeHead) -> SystemTime {
+    SystemTime::UNIX_EPOCH
+        .checked_add(Duration::from_millis(
+            u64::try_from(tree_head.timestamp).unwrap_or_default(),
+        ))
+        .unwrap_or(SystemTime::UNIX_EPOCH)
 }
 
 pub trait KtApi {
@@ -537,7 +2196,7 @@ pub trait KtApi {
         &self,
         aci: &Aci,
         aci_identity_key: &PublicKey,
-        e164: Option<(E164, Vec<u8>)>,
+        e164: Option<E164SearchKey>,
         username_hash: Option<UsernameHash<'_>>,
         stored_account_data: Option<AccountData>,
         distinguished_tree_head: &LastTreeHead,
@@ -546,7 +2205,7 @@ pub trait KtApi {
     fn distinguished(
         &self,
         last_distinguished: Option<LastTreeHead>,
-    ) -> impl Future<Output = Result<SearchStateUpdate>> + Send;
+    ) -> impl Future<Output = Result<DistinguishedResult>> + Send;
 
     fn monitor(
         &self,
@@ -555,32 +2214,106 @@ pub trait KtApi {
         username_hash: Option<UsernameHash<'_>>,
         account_data: AccountData,
         last_distinguished_tree_head: &LastTreeHead,
-    ) -> impl Future<Output = Result<AccountData>> + Send;
+    ) -> impl Future<Output = Result<MonitorResult>> + Send;
+}
+
+/// The result of [`KtApi::monitor`] and [`monitor_and_search`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorResult {
+    pub account_data: MaybePartial<AccountData>,
+    /// Whether the stored account data was returned as-is without making any
+    /// requests; see [`Config::with_monitor_skip_window`].
+    pub skipped: bool,
+    /// How the new tree head was checked against the stored last and
+    /// distinguished tree heads.
+    ///
+    /// `None` if the monitor response wasn't verified again: either it was
+    /// [skipped](Self::skipped), or it was already in
+    /// [`Config::with_monitor_verification_cache`].
+    pub consistency: Option<TreeHeadConsistency>,
+    /// The sizes of the verified proofs, `None` in the same cases as
+    /// [`Self::consistency`].
+    pub proof_metrics: Option<ProofMetrics>,
+    /// Keys the server has stopped monitoring, which have been left out of
+    /// [`Self::account_data`]; see [`Config::with_dropped_monitor_legs`].
+    ///
+    /// Unlike [`MaybePartial::missing_fields`], these aren't a problem with
+    /// the response: the stored monitoring data for them is no longer needed.
+    pub dropped_legs: BTreeSet<AccountDataField>,
+}
+
+impl MonitorResult {
+    fn monitored(
+        account_data: MaybePartial<AccountData>,
+        consistency: Option<TreeHeadConsistency>,
+        proof_metrics: Option<ProofMetrics>,
+    ) -> Self {
+        Self {
+            account_data,
+            skipped: false,
+            consistency,
+            proof_metrics,
+            dropped_legs: BTreeSet::new(),
+        }
+    }
+
+    fn with_dropped_legs(self, dropped_legs: impl IntoIterator<Item = AccountDataField>) -> Self {
+        Self {
+            dropped_legs: BTreeSet::from_iter(dropped_legs),
+            ..self
+        }
+    }
 }
 
 pub async fn monitor_and_search(
     kt: &impl KtApi,
     aci: &Aci,
     aci_identity_key: &PublicKey,
-    e164: Option<(E164, Vec<u8>)>,
-    username_hash: Option<UsernameHash<'_>>,
-    stored_account_data: AccountData,
+    mut e164: Option<E164SearchKey>,
+    mut username_hash: Option<UsernameHash<'_>>,
+    mut stored_account_data: AccountData,
     distinguished_tree_head: &LastTreeHead,
-) -> Result<MaybePartial<AccountData>> {
-    let updated_account_data = kt
+) -> Result<MonitorResult> {
+    let monitored = kt
         .monitor(
             aci,
-            e164.as_ref().map(|(e164, _)| *e164),
+            e164.as_ref().map(|key| key.e164),
             username_hash.clone(),
             stored_account_data.clone(),
             distinguished_tree_head,
         )
         .await?;
+    if monitored.skipped {
+        // Nothing can have changed, so there's nothing to search for either.
+        return Ok(monitored);
+    }
+    let MonitorResult {
+        account_data: updated_account_data,
+        skipped: _,
+        consistency,
+        proof_metrics,
+        dropped_legs,
+    } = monitored;
+
+    // Keys the server no longer monitors aren't searched for either.
+    for field in &dropped_legs {
+        match field {
+            AccountDataField::E164 => {
+                e164 = None;
+                stored_account_data.e164 = None;
+            }
+            AccountDataField::UsernameHash => {
+                username_hash = None;
+                stored_account_data.username_hash = None;
+            }
+        }
+    }
 
-    // Call to `monitor` guarantees that the optionality of E.164 and username hash data
-    // will match between `stored_account_data` and `updated_account_data`. Meaning, they will
-    // either both be Some() or both None.
-    let should_search = has_version_changed_between(&stored_account_data, &updated_account_data);
+    // Call to `monitor` guarantees that, once dropped legs are removed, the optionality of
+    // E.164 and username hash data will match between `stored_account_data` and
+    // `updated_account_data`. Meaning, they will either both be Some() or both None.
+    let should_search =
+        has_version_changed_between(&stored_account_data, &updated_account_data.inner);
     let final_account_data = if should_search {
         let search_result = kt
             .search(
@@ -596,9 +2329,12 @@ pub async fn monitor_and_search(
             .map(|res| AccountData::try_from(res.account_data))
             .transpose()?
     } else {
-        updated_account_data.into()
+        updated_account_data
     };
-    Ok(final_account_data)
+    Ok(
+        MonitorResult::monitored(final_account_data, consistency, proof_metrics)
+            .with_dropped_legs(dropped_legs),
+    )
 }
 
 fn cmp_by_key<T, K: Ord>(lhs: &T, rhs: &T, get_key: impl Fn(&T) -> K) -> Ordering {
@@ -623,80 +2359,531 @@ fn has_version_changed_between(stored: &AccountData, updated: &AccountData) -> b
 }
 
 impl Kt<'_> {
-    async fn send(&self, request: chat::Request) -> Result<chat::Response> {
-        log::debug!("{}", &request.path.as_str());
-        log::debug!(
-            "{}",
-            &String::from_utf8(request.clone().body.unwrap_or_default().to_vec()).unwrap()
-        );
-        let response = self
-            .chat
-            .send_unauthenticated(request, self.config.chat_timeout)
-            .await?;
-        log::debug!(
-            "{} {:?}, headers: {:?}, body: {}",
-            &response.status,
-            &response.message,
-            &response.headers,
-            hex::encode({
-                let body_slice = response.body.as_deref().unwrap_or_default();
-                &body_slice[..body_slice.len().min(1024)]
-            })
-        );
-        if !response.status.is_success() {
-            Err(Error::RequestFailed(response.status))
-        } else {
+    /// How many requests are waiting for a response right now, for
+    /// diagnostics.
+    ///
+    /// Includes requests from other [`Kt`]s that share this one's config; see
+    /// [`Config::with_max_concurrent_requests`].
+    pub fn requests_in_flight(&self) -> usize {
+        self.config.request_limit.in_flight()
+    }
+
+    fn now(&self) -> SystemTime {
+        self.config.clock.now()
+    }
+
+    #[cfg(feature = "keytrans-trace")]
+    fn tracer(&self) -> Tracer<'_> {
+        Tracer {
+            trace: self.config.verification_trace.as_deref(),
+        }
+    }
+
+    #[cfg(not(feature = "keytrans-trace"))]
+    fn tracer(&self) -> Tracer<'_> {
+        Tracer::default()
+    }
+
+    /// See [`Config::with_monitor_skip_window`].
+    ///
+    /// The stored data has to be known to be for exactly the keys being
+    /// monitored: skipping returns it as is, so a changed ACI, E.164, or
+    /// username hash would otherwise go unnoticed.
+    fn can_skip_monitor(
+        &self,
+        aci: &Aci,
+        e164: Option<E164>,
+        username_hash: Option<&UsernameHash<'_>>,
+        account_data: &AccountData,
+        last_distinguished_tree_head: &LastTreeHead,
+    ) -> bool {
+        let Some(window) = self.config.monitor_skip_window else {
+            return false;
+        };
+        let (stored_tree_head, _) = &account_data.last_tree_head;
+        let (distinguished_tree_head, _) = last_distinguished_tree_head;
+        // Stored data that's ahead of the distinguished tree head doesn't fit
+        // the usual order of operations, so it's monitored to be safe.
+        let is_fresh = stored_tree_head.tree_size <= distinguished_tree_head.tree_size
+            && tree_head_timestamp(distinguished_tree_head)
+                .duration_since(tree_head_timestamp(stored_tree_head))
+                .is_ok_and(|lag| lag <= window);
+        fn is_for(stored: Option<&MonitoringData>, key: Option<Vec<u8>>) -> bool {
+            match (stored, key) {
+                (None, None) => true,
+                (Some(stored), Some(key)) => stored.is_for_search_key(&key),
+                (Some(_), None) | (None, Some(_)) => false,
+            }
+        }
+        is_for(Some(&account_data.aci), Some(aci.as_search_key()))
+            && is_for(
+                account_data.e164.as_ref(),
+                e164.map(|e164| e164.as_search_key()),
+            )
+            && is_for(
+                account_data.username_hash.as_ref(),
+                username_hash.map(SearchKey::as_search_key),
+            )
+            && is_fresh
+    }
+
+    fn check_tree_head_timestamp(
+        &self,
+        full_tree_head: &FullTreeHead,
+        now: SystemTime,
+    ) -> Result<()> {
+        let tree_head = full_tree_head
+            .tree_head
+            .as_ref()
+            .ok_or_else(|| Error::InvalidResponse("tree head must be present".to_string()))?;
+        let timestamp = tree_head_timestamp(tree_head);
+        let too_new = timestamp
+            .duration_since(now)
+            .is_ok_and(|ahead| ahead > self.config.max_clock_skew);
+        let too_old = now
+            .duration_since(timestamp)
+            .is_ok_and(|behind| behind > self.config.max_tree_head_age);
+        if too_new || too_old {
+            return Err(Error::TreeHeadOutOfRange { timestamp, now });
+        }
+        Ok(())
+    }
+
+    /// Applies [`Config::with_max_auditor_lag`] to a verified tree head.
+    fn check_auditor_lag(&self, auditor: Option<AuditorView>) -> Result<()> {
+        match (auditor, self.config.max_auditor_lag) {
+            (Some(AuditorView { lag, .. }), Some(max_lag)) if lag > max_lag => {
+                Err(Error::AuditorTooFarBehind { lag, max_lag })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies [`Config::with_pin_store`] to a verified identity key.
+    fn check_pinned_key(&self, aci: &Aci, key: &IdentityKey) -> Result<()> {
+        let Some(store) = &self.config.pin_store else {
+            return Ok(());
+        };
+        let key_hash = pinned_key_hash(key);
+        match store.pinned_key_hash(aci) {
+            None => {
+                store.set_pinned_key_hash(aci, key_hash);
+                Ok(())
+            }
+            Some(pinned) if pinned == key_hash => Ok(()),
+            Some(_) => {
+                log::warn!(
+                    "key transparency returned a different identity key than the pinned one"
+                );
+                Err(Error::PinnedKeyMismatch { aci: *aci })
+            }
+        }
+    }
+
+    fn report_verification_failure(&self, error: &Error) {
+        let Some(category) = error.verification_category() else {
+            return;
+        };
+        log::warn!("key transparency verification failed ({category:?}): {error}");
+        if category != VerificationCategory::ConsistencyViolation {
+            return;
+        }
+        if let Some(callback) = &self.config.on_consistency_violation {
+            callback(error);
+        }
+    }
+
+    fn report_proof_metrics(&self, operation: KtOperation, metrics: &ProofMetrics) {
+        log::debug!("{operation:?} proof metrics: {metrics:?}");
+        if let Some(callback) = &self.config.on_proof_metrics {
+            callback(operation, metrics);
+        }
+    }
+
+    async fn send(&self, request: chat::Request) -> Result<chat::Response> {
+        let response = self.send_allowing_errors(request).await?;
+        if !response.status.is_success() {
+            Err(Error::for_failed_response(&response))
+        } else {
             Ok(response)
         }
     }
+
+    /// Like [`Self::send`], but leaves the status code for the caller to
+    /// check.
+    async fn send_allowing_errors(&self, request: chat::Request) -> Result<chat::Response> {
+        // Chosen here rather than by the chat connection so that these log
+        // lines can be matched up with the connection's own.
+        let correlation_id = request
+            .correlation_id
+            .unwrap_or_else(chat::CorrelationId::random);
+        let request = chat::Request {
+            priority: self.config.request_priority,
+            correlation_id: Some(correlation_id),
+            ..request
+        };
+        log::debug!("[{correlation_id}] {}", request.path.as_str());
+        #[cfg(feature = "keytrans-body-logging")]
+        if log::log_enabled!(log::Level::Debug) {
+            log::debug!(
+                "[{correlation_id}] {}",
+                String::from_utf8_lossy(truncate_for_logging(
+                    request.body.as_deref(),
+                    self.config.max_logged_body_len
+                ))
+            );
+        }
+        // Waiting for a turn counts against the deadline, same as the request.
+        let acquire = self.config.request_limit.permits.acquire();
+        let _permit = match self.config.deadline {
+            None => acquire.await,
+            Some(deadline) => {
+                let remaining = chat::timeout_until(deadline, SystemTime::now())?;
+                tokio::time::timeout(remaining, acquire)
+                    .await
+                    .map_err(|_elapsed| chat::SendError::RequestTimedOut {
+                        correlation_id: Some(correlation_id),
+                    })?
+            }
+        }
+        .expect("semaphore is never closed");
+        // Only start the clock once the request can actually be sent.
+        let timeout = self.config.request_timeout()?;
+        let response = self.chat.send(request, timeout).await?;
+        log::debug!(
+            "[{correlation_id}] {} {:?}, headers: {:?}",
+            response.status,
+            response.message,
+            response.headers,
+        );
+        #[cfg(feature = "keytrans-body-logging")]
+        if log::log_enabled!(log::Level::Debug) {
+            log::debug!(
+                "[{correlation_id}] body: {}",
+                hex::encode(truncate_for_logging(
+                    response.body.as_deref(),
+                    self.config.max_logged_body_len
+                ))
+            );
+        }
+        Ok(response)
+    }
+
+    /// Decodes an enveloped response, within the configured size limit.
+    fn decode_response<R: Message + Default>(&self, response: chat::Response) -> Result<R> {
+        if !response.status.is_success() {
+            return Err(Error::for_failed_response(&response));
+        }
+        Ok(decode_envelope(response, self.config.max_response_size)?)
+    }
+
+    /// Fetches the log's most recent key rotation, if there has been one.
+    ///
+    /// The result isn't trusted until it has been passed to
+    /// [`Self::apply_key_rotation`].
+    pub async fn fetch_key_rotation(&self) -> Result<Option<SignedKeyRotation>> {
+        let response = self.send_allowing_errors(key_rotation_request()).await?;
+        match response.status {
+            http::StatusCode::NOT_FOUND => Ok(None),
+            status if !status.is_success() => Err(Error::for_failed_response(&response)),
+            _ => self.decode_response(response).map(Some),
+        }
+    }
+
+    /// Checks whether key transparency is enabled for this environment,
+    /// without needing an account to search for.
+    ///
+    /// Sends a distinguished request, with at most
+    /// [`Config::AVAILABILITY_PROBE_TIMEOUT`] to complete, and only looks at
+    /// its status; the response isn't verified. See
+    /// [`Config::with_availability_cache`] to avoid asking every time.
+    pub async fn is_available(&self) -> KtAvailability {
+        let cache = self.config.availability_cache.as_deref();
+        if let Some(availability) = cache.and_then(KtAvailabilityCache::get) {
+            return availability;
+        }
+
+        let chat_timeout = self
+            .config
+            .chat_timeout
+            .min(Config::AVAILABILITY_PROBE_TIMEOUT);
+        let probe = Kt {
+            config: self.config.clone().with_chat_timeout(chat_timeout),
+            ..self.clone()
+        };
+        let request = RawChatDistinguishedRequest {
+            last_tree_head_size: None,
+        };
+        let availability = match probe.send_allowing_errors(request.into()).await {
+            Ok(response) => KtAvailability::for_response(&response),
+            Err(e) => KtAvailability::Unreachable(e),
+        };
+        if let Some(cache) = cache {
+            cache.record(&availability);
+        }
+        availability
+    }
+
+    /// Checks `document`, fetched with [`Self::fetch_key_rotation`] or
+    /// received out-of-band, against the keys in use and, if it's valid,
+    /// switches to the new key.
+    ///
+    /// The rotation is recorded in `rotations`, which should be persisted and
+    /// passed to [`KeyTransConfig::load`](crate::env::KeyTransConfig::load)
+    /// from then on. Nothing changes if the document is rejected.
+    pub fn apply_key_rotation(
+        &mut self,
+        rotations: &mut KeyRotations,
+        document: &SignedKeyRotation,
+    ) -> Result<RotatableKey> {
+        let now = self.now();
+        let key = rotations
+            .apply(&mut self.inner.config, document, now)
+            .inspect_err(|e| log::warn!("rejected key transparency key rotation: {e}"))?;
+        log::info!("key transparency {key} rotated");
+        Ok(key)
+    }
+
+    /// Fetches the log's most recent key rotation and applies it, unless it
+    /// has been applied already.
+    ///
+    /// Returns the key that was rotated, if any. Whenever one was,
+    /// `rotations` has changed and should be persisted.
+    pub async fn update_key_rotations(
+        &mut self,
+        rotations: &mut KeyRotations,
+    ) -> Result<Option<RotatableKey>> {
+        match self.fetch_key_rotation().await? {
+            Some(document) if !rotations.is_applied(&document) => {
+                self.apply_key_rotation(rotations, &document).map(Some)
+            }
+            Some(_) | None => Ok(None),
+        }
+    }
 }
 
-impl KtApi for Kt<'_> {
-    async fn search(
+/// Returns at most the first `max_len` bytes of `body`.
+#[cfg(feature = "keytrans-body-logging")]
+fn truncate_for_logging(body: Option<&[u8]>, max_len: usize) -> &[u8] {
+    let body = body.unwrap_or_default();
+    &body[..body.len().min(max_len)]
+}
+
+impl Kt<'_> {
+    /// Fetches and verifies the ACI identity key (and any other requested
+    /// mappings) for an account whose identity key isn't known locally yet.
+    ///
+    /// Unlike [`KtApi::search`], the returned
+    /// [`SearchResult::aci_identity_key`] isn't checked against anything; it
+    /// is whatever the log says the key is.
+    pub async fn lookup(
         &self,
         aci: &Aci,
-        aci_identity_key: &PublicKey,
-        e164: Option<(E164, Vec<u8>)>,
+        e164: Option<E164SearchKey>,
         username_hash: Option<UsernameHash<'_>>,
         stored_account_data: Option<AccountData>,
         distinguished_tree_head: &LastTreeHead,
     ) -> Result<MaybePartial<SearchResult>> {
-        let raw_request = RawChatSearchRequest::new(
-            aci,
-            aci_identity_key,
-            e164.as_ref(),
-            username_hash.as_ref(),
-            stored_account_data
-                .as_ref()
-                .map(|acc_data| acc_data.last_tree_head.0.tree_size),
-            distinguished_tree_head.0.tree_size,
-        );
-        let response = self.send(raw_request.into()).await?;
+        let deadline = OperationDeadline::new(self.config.overall_deadline);
+        deadline
+            .run(self.search_impl(
+                aci,
+                None,
+                e164,
+                username_hash,
+                stored_account_data,
+                distinguished_tree_head,
+                &deadline,
+            ))
+            .await
+    }
+
+    async fn search_impl(
+        &self,
+        aci: &Aci,
+        aci_identity_key: Option<&PublicKey>,
+        e164: Option<E164SearchKey>,
+        mut username_hash: Option<UsernameHash<'_>>,
+        stored_account_data: Option<AccountData>,
+        distinguished_tree_head: &LastTreeHead,
+        deadline: &OperationDeadline,
+    ) -> Result<MaybePartial<SearchResult>> {
+        // An authenticated connection is all the server needs to decide
+        // whether to reveal the E.164 mapping.
+        let mut e164 = match self.chat {
+            KtChat::Unauthenticated(_) => e164,
+            KtChat::Authenticated(_) => e164.map(|key| E164SearchKey {
+                unidentified_access_key: None,
+                ..key
+            }),
+        };
+        let mut dropped_fields = vec![];
+        let response = loop {
+            let raw_request = RawChatSearchRequest::new(
+                aci,
+                aci_identity_key,
+                e164.as_ref(),
+                username_hash.as_ref(),
+                stored_account_data
+                    .as_ref()
+                    .map(|acc_data| acc_data.last_tree_head.0.tree_size),
+                distinguished_tree_head.0.tree_size,
+            );
+            let response = self.send_allowing_errors(raw_request.try_into()?).await?;
+            // Each retry drops a key that was requested, so this terminates.
+            match unsupported_search_key(&response, e164.is_some(), username_hash.is_some()) {
+                None if !response.status.is_success() => {
+                    return Err(Error::for_failed_response(&response))
+                }
+                None => break response,
+                Some(field) if self.config.auto_drop_unsupported_keys => {
+                    log::info!("server does not support searching by {field}; retrying without");
+                    match field {
+                        AccountDataField::E164 => e164 = None,
+                        AccountDataField::UsernameHash => username_hash = None,
+                    }
+                    dropped_fields.push(field);
+                }
+                Some(field) => return Err(Error::UnsupportedSearchKey(field)),
+            }
+        };
 
-        let chat_search_response = RawChatSerializedResponse::try_from(response)
-            .and_then(|r| decode_response(r.serialized_response))
-            .and_then(|r| {
-                TypedSearchResponse::from_untyped(e164.is_some(), username_hash.is_some(), r)
-            })?;
+        deadline.enter(OperationPhase::Decode)?;
+        let chat_search_response = self.decode_response(response).and_then(|r| {
+            TypedSearchResponse::from_untyped(e164.is_some(), username_hash.is_some(), r)
+        })?;
 
-        let now = SystemTime::now();
+        let now = self.now();
+        self.check_tree_head_timestamp(&chat_search_response.full_tree_head, now)?;
+        let search_tree_head = chat_search_response
+            .full_tree_head
+            .tree_head
+            .as_ref()
+            .ok_or_else(|| Error::InvalidResponse("tree head must be present".to_string()))?;
+        // A search tree head that lags behind the distinguished one can never
+        // be proven consistent with it, so this has to come before
+        // verification to be reported at all. The tree head isn't verified
+        // yet, but a forged one can only make the search fail.
+        self.config
+            .view_freshness
+            .check(search_tree_head, &distinguished_tree_head.0)?;
+        let auditor = AuditorView::new(
+            &self.inner.config.mode,
+            &chat_search_response.full_tree_head,
+        );
 
-        verify_chat_search_response(
+        deadline.enter(OperationPhase::Verify)?;
+        let tracer = self.tracer();
+        let verified = verify_chat_search_response(
             &self.inner,
             aci,
-            e164.map(|(e164, _)| e164),
+            e164.map(|key| key.e164),
             username_hash,
             stored_account_data,
             chat_search_response,
             Some(distinguished_tree_head),
+            self.config.detect_username_changes,
             now,
+            deadline,
+            tracer,
         )
+        .inspect_err(|e| self.report_verification_failure(e))
+        .and_then(|result| {
+            self.check_auditor_lag(auditor)?;
+            self.check_pinned_key(aci, &result.inner.aci_identity_key)?;
+            Ok(result)
+        });
+        trace_event!(tracer, |_trace| trace::TraceEvent::decided(
+            verified.as_ref().err()
+        ));
+        let mut result = verified?;
+        result.inner.auditor_lag = auditor.map(|auditor| auditor.lag);
+        result.inner.auditor_timestamp = auditor.map(|auditor| auditor.timestamp);
+        result.missing_fields.extend(dropped_fields);
+        self.report_proof_metrics(KtOperation::Search, &result.inner.proof_metrics);
+        Ok(result)
+    }
+}
+
+impl KtApi for Kt<'_> {
+    async fn search(
+        &self,
+        aci: &Aci,
+        aci_identity_key: &PublicKey,
+        e164: Option<E164SearchKey>,
+        username_hash: Option<UsernameHash<'_>>,
+        stored_account_data: Option<AccountData>,
+        distinguished_tree_head: &LastTreeHead,
+    ) -> Result<MaybePartial<SearchResult>> {
+        let deadline = OperationDeadline::new(self.config.overall_deadline);
+        deadline
+            .run(self.search_impl(
+                aci,
+                Some(aci_identity_key),
+                e164,
+                username_hash,
+                stored_account_data,
+                distinguished_tree_head,
+                &deadline,
+            ))
+            .await
     }
 
     async fn distinguished(
         &self,
         last_distinguished: Option<LastTreeHead>,
-    ) -> Result<SearchStateUpdate> {
+    ) -> Result<DistinguishedResult> {
+        let deadline = OperationDeadline::new(self.config.overall_deadline);
+        deadline
+            .run(self.distinguished_impl(last_distinguished, &deadline))
+            .await
+    }
+
+    async fn monitor(
+        &self,
+        aci: &Aci,
+        e164: Option<E164>,
+        username_hash: Option<UsernameHash<'_>>,
+        account_data: AccountData,
+        last_distinguished_tree_head: &LastTreeHead,
+    ) -> Result<MonitorResult> {
+        if self.can_skip_monitor(
+            aci,
+            e164,
+            username_hash.as_ref(),
+            &account_data,
+            last_distinguished_tree_head,
+        ) {
+            log::debug!("account data is already at the distinguished tree head; skipping monitor");
+            return Ok(MonitorResult {
+                account_data: account_data.into(),
+                skipped: true,
+                consistency: None,
+                proof_metrics: None,
+                dropped_legs: BTreeSet::new(),
+            });
+        }
+
+        let deadline = OperationDeadline::new(self.config.overall_deadline);
+        deadline
+            .run(self.monitor_impl(
+                aci,
+                e164,
+                username_hash,
+                account_data,
+                last_distinguished_tree_head,
+                &deadline,
+            ))
+            .await
+    }
+}
+
+impl Kt<'_> {
+    async fn distinguished_impl(
+        &self,
+        last_distinguished: Option<LastTreeHead>,
+        deadline: &OperationDeadline,
+    ) -> Result<DistinguishedResult> {
         let distinguished_size = last_distinguished
             .as_ref()
             .map(|last_tree_head| last_tree_head.0.tree_size);
@@ -706,11 +2893,11 @@ impl KtApi for Kt<'_> {
         };
         let response = self.send(raw_request.into()).await?;
 
+        deadline.enter(OperationPhase::Decode)?;
         let ChatDistinguishedResponse {
             tree_head,
             distinguished,
-        } = RawChatSerializedResponse::try_from(response)
-            .and_then(|r| decode_response(r.serialized_response))?;
+        } = self.decode_response(response)?;
 
         let tree_head = tree_head.ok_or(Error::InvalidResponse(
             "tree head must be present".to_string(),
@@ -718,32 +2905,57 @@ impl KtApi for Kt<'_> {
         let condensed_response = distinguished.ok_or(Error::InvalidResponse(
             "search response must be present".to_string(),
         ))?;
+        check_full_tree_head(&tree_head)?;
+        check_search_response("distinguished", &condensed_response)?;
+        let now = self.now();
+        self.check_tree_head_timestamp(&tree_head, now)?;
+        let auditor = AuditorView::new(&self.inner.config.mode, &tree_head);
+        let mut proof_metrics = ProofMetrics::new(
+            &tree_head,
+            ProofMetrics::search_inclusion_hashes([&condensed_response]),
+        );
         let search_response = FullSearchResponse::new(condensed_response, &tree_head);
 
         let slim_search_request = SlimSearchRequest::new(b"distinguished".to_vec());
 
-        let verified_result = self.inner.verify_search(
-            slim_search_request,
-            search_response,
-            SearchContext {
-                last_tree_head: None,
-                last_distinguished_tree_head: last_distinguished.as_ref(),
-                data: None,
-            },
-            false,
-            SystemTime::now(),
-        )?;
-        Ok(verified_result.state_update)
+        deadline.enter(OperationPhase::Verify)?;
+        let started = Instant::now();
+        let verified_result = self
+            .inner
+            .verify_search(
+                slim_search_request,
+                search_response,
+                SearchContext {
+                    last_tree_head: None,
+                    last_distinguished_tree_head: last_distinguished.as_ref(),
+                    data: None,
+                },
+                false,
+                now,
+            )
+            .map_err(Error::from)
+            .inspect_err(|e| self.report_verification_failure(e))?;
+        proof_metrics.verification_time = started.elapsed();
+        self.check_auditor_lag(auditor)?;
+        self.report_proof_metrics(KtOperation::Distinguished, &proof_metrics);
+        Ok(DistinguishedResult {
+            state_update: verified_result.state_update,
+            auditor_lag: auditor.map(|auditor| auditor.lag),
+            auditor_timestamp: auditor.map(|auditor| auditor.timestamp),
+            proof_metrics,
+        })
     }
 
-    async fn monitor(
+    async fn monitor_impl(
         &self,
         aci: &Aci,
         e164: Option<E164>,
         username_hash: Option<UsernameHash<'_>>,
         account_data: AccountData,
         last_distinguished_tree_head: &LastTreeHead,
-    ) -> Result<AccountData> {
+        deadline: &OperationDeadline,
+    ) -> Result<MonitorResult> {
+        deadline.enter(OperationPhase::Request)?;
         let raw_request = RawChatMonitorRequest::new(
             aci,
             e164,
@@ -751,17 +2963,55 @@ impl KtApi for Kt<'_> {
             &account_data,
             last_distinguished_tree_head.0.tree_size,
         )?;
-        let response = self.send(raw_request.into()).await?;
+        let response = self.send(raw_request.try_into()?).await?;
+
+        deadline.enter(OperationPhase::Decode)?;
+        let chat_monitor_response = self.decode_response(response).and_then(|r| {
+            TypedMonitorResponse::from_untyped(
+                e164.is_some(),
+                username_hash.is_some(),
+                self.config.allow_dropped_monitor_legs,
+                r,
+            )
+        })?;
+
+        // Only possible with Config::with_dropped_monitor_legs. The server has
+        // stopped monitoring these keys, so their monitoring data is dropped
+        // along with them.
+        let mut dropped_legs = Vec::new();
+        let e164 = if e164.is_some() && chat_monitor_response.e164.is_none() {
+            log::info!("monitor response no longer includes the E.164; dropping it");
+            dropped_legs.push(AccountDataField::E164);
+            None
+        } else {
+            e164
+        };
+        let username_hash =
+            if username_hash.is_some() && chat_monitor_response.username_hash.is_none() {
+                log::info!("monitor response no longer includes the username hash; dropping it");
+                dropped_legs.push(AccountDataField::UsernameHash);
+                None
+            } else {
+                username_hash
+            };
 
-        let chat_monitor_response = RawChatSerializedResponse::try_from(response)
-            .and_then(|r| decode_response(r.serialized_response))
-            .and_then(|r| {
-                TypedMonitorResponse::from_untyped(e164.is_some(), username_hash.is_some(), r)
-            })?;
+        let now = self.now();
+        self.check_tree_head_timestamp(&chat_monitor_response.tree_head, now)?;
 
-        let now = SystemTime::now();
+        let tracer = self.tracer();
+        trace_event!(tracer, |_trace| trace::TraceEvent::Started {
+            operation: KtOperation::Monitor,
+            tree_size: chat_monitor_response
+                .tree_head
+                .tree_head
+                .as_ref()
+                .map(|head| head.tree_size),
+            last_tree_size: Some(account_data.last_tree_head.0.tree_size),
+            distinguished_tree_size: Some(last_distinguished_tree_head.0.tree_size),
+            key_kinds: traced_key_kinds(e164.is_some(), username_hash.is_some()),
+        });
 
-        let updated_account_data = {
+        let (updated_account_data, consistency, proof_metrics) = {
             let AccountData {
                 aci: aci_monitoring_data,
                 e164: e164_monitoring_data,
@@ -769,74 +3019,166 @@ impl KtApi for Kt<'_> {
                 last_tree_head,
             } = account_data;
 
-            let mut monitor_keys = Vec::with_capacity(3);
-            let mut proofs = Vec::with_capacity(3);
-            let mut monitoring_data_map = HashMap::with_capacity(3);
-
-            let aci_monitor_key = MonitorKey {
-                search_key: aci.as_search_key(),
-                entry_position: aci_monitoring_data.latest_log_position(),
-                commitment_index: aci_monitoring_data.index.to_vec(),
-            };
-            monitor_keys.push(aci_monitor_key);
-            proofs.push(chat_monitor_response.aci);
-            monitoring_data_map.insert(aci.as_search_key(), aci_monitoring_data.clone());
+            let mut entries = Vec::with_capacity(3);
+            let aci_search_key = aci.as_search_key();
+            trace_event!(tracer, |trace| trace::TraceEvent::ProofConsumed {
+                key: Some(trace.key(SearchKeyKind::Aci, &aci_search_key)),
+                proof: trace::TracedProof::Monitor {
+                    steps: chat_monitor_response.aci.steps.len(),
+                },
+            });
+            entries.push(MonitorEntry::new(
+                aci_search_key,
+                aci_monitoring_data,
+                chat_monitor_response.aci,
+            ));
 
             if let Some(e164) = e164 {
                 let monitoring_data = e164_monitoring_data
-                    .ok_or(Error::InvalidRequest("missing E.164 monitoring data"))?;
-                let key = MonitorKey {
-                    search_key: e164.as_search_key(),
-                    entry_position: monitoring_data.latest_log_position(),
-                    commitment_index: monitoring_data.index.to_vec(),
-                };
-                monitor_keys.push(key);
-
+                    .ok_or_else(|| Error::internal("missing E.164 monitoring data"))?;
                 // The proof must be present. Checked in TypedMonitorResponse::from_untyped
-                proofs.push(chat_monitor_response.e164.unwrap());
-                monitoring_data_map.insert(e164.as_search_key(), monitoring_data);
+                let proof = chat_monitor_response.e164.unwrap();
+                let search_key = e164.as_search_key();
+                trace_event!(tracer, |trace| trace::TraceEvent::ProofConsumed {
+                    key: Some(trace.key(SearchKeyKind::E164, &search_key)),
+                    proof: trace::TracedProof::Monitor {
+                        steps: proof.steps.len(),
+                    },
+                });
+                entries.push(MonitorEntry::new(search_key, monitoring_data, proof));
             }
 
             if let Some(username_hash) = username_hash.clone() {
-                let monitoring_data = username_hash_monitoring_data.ok_or(
-                    Error::InvalidRequest("missing username hash monitoring data"),
-                )?;
-                let key = MonitorKey {
-                    search_key: username_hash.as_search_key().to_vec(),
-                    entry_position: monitoring_data.latest_log_position(),
-                    commitment_index: monitoring_data.index.to_vec(),
-                };
-                monitor_keys.push(key);
+                let monitoring_data = username_hash_monitoring_data
+                    .ok_or_else(|| Error::internal("missing username hash monitoring data"))?;
                 // The proof must be present. Checked in TypedMonitorResponse::from_untyped
-                proofs.push(chat_monitor_response.username_hash.unwrap());
-                monitoring_data_map.insert(username_hash.as_search_key(), monitoring_data);
+                let proof = chat_monitor_response.username_hash.unwrap();
+                let search_key = username_hash.as_search_key().to_vec();
+                trace_event!(tracer, |trace| trace::TraceEvent::ProofConsumed {
+                    key: Some(trace.key(SearchKeyKind::UsernameHash, &search_key)),
+                    proof: trace::TracedProof::Monitor {
+                        steps: proof.steps.len(),
+                    },
+                });
+                entries.push(MonitorEntry::new(search_key, monitoring_data, proof));
             }
 
-            // We are using a single monitor request/response pair for all the possible keys
-            let monitor_request = MonitorRequest {
+            let MonitorParts {
                 keys: monitor_keys,
-                // Consistency is only used to verify "distinguished" search key
-                consistency: None,
-            };
-
-            let monitor_response = MonitorResponse {
-                tree_head: Some(chat_monitor_response.tree_head.clone()),
                 proofs,
-                inclusion: chat_monitor_response.inclusion,
-            };
-
-            let monitor_context = MonitorContext {
-                last_tree_head: Some(&last_tree_head),
-                last_distinguished_tree_head,
                 data: monitoring_data_map,
-            };
+            } = MonitorParts::from_entries(entries)?;
+
+            let distinguished_tree_size = last_distinguished_tree_head.0.tree_size;
+            let already_verified =
+                self.config
+                    .monitor_verification_cache
+                    .as_ref()
+                    .is_some_and(|cache| {
+                        chat_monitor_response.tree_head.tree_head.as_ref()
+                            == Some(&last_tree_head.0)
+                            && monitor_keys.iter().all(|key| {
+                                cache.contains(
+                                    &key.search_key,
+                                    &VerifiedMonitorEntry {
+                                        entry_position: key.entry_position,
+                                        tree_size: last_tree_head.0.tree_size,
+                                        root: last_tree_head.1,
+                                        distinguished_tree_size,
+                                    },
+                                )
+                            })
+                    });
+
+            deadline.enter(OperationPhase::Verify)?;
+            let (verified, consistency, proof_metrics) = if already_verified {
+                // The response is for the same tree head we already have, and
+                // every entry has already been proven against it. The tree
+                // head's timestamp has still been checked above.
+                log::debug!("monitor response has already been verified");
+                trace_event!(tracer, |_trace| trace::TraceEvent::AlreadyVerified);
+                trace_event!(tracer, |_trace| trace::TraceEvent::decided(None));
+                let cached = LocalStateUpdate {
+                    tree_head: last_tree_head.0.clone(),
+                    tree_root: last_tree_head.1,
+                    monitoring_data: monitoring_data_map,
+                };
+                (cached, None, None)
+            } else {
+                // We are using a single monitor request/response pair for all the possible keys
+                let monitor_request = MonitorRequest {
+                    keys: monitor_keys,
+                    // Consistency is only used to verify "distinguished" search key
+                    consistency: None,
+                };
 
-            let verified = self.inner.verify_monitor(
-                &monitor_request,
-                &monitor_response,
-                monitor_context,
-                now,
-            )?;
+                let mut proof_metrics = ProofMetrics::new(
+                    &chat_monitor_response.tree_head,
+                    chat_monitor_response.inclusion.len(),
+                );
+                let monitor_response = MonitorResponse {
+                    tree_head: Some(chat_monitor_response.tree_head.clone()),
+                    proofs,
+                    inclusion: chat_monitor_response.inclusion,
+                };
+
+                let monitor_context = MonitorContext {
+                    last_tree_head: Some(&last_tree_head),
+                    last_distinguished_tree_head,
+                    data: monitoring_data_map,
+                };
+
+                trace_event!(tracer, |_trace| trace::TraceEvent::ProofConsumed {
+                    key: None,
+                    proof: trace::TracedProof::LastConsistency {
+                        hashes: monitor_response
+                            .tree_head
+                            .as_ref()
+                            .map_or(0, |head| head.last.len()),
+                    },
+                });
+                trace_event!(tracer, |_trace| trace::TraceEvent::ProofConsumed {
+                    key: None,
+                    proof: trace::TracedProof::MonitorInclusion {
+                        hashes: monitor_response.inclusion.len(),
+                    },
+                });
+
+                let started = Instant::now();
+                let VerifiedMonitorResult {
+                    state_update: verified,
+                    consistency,
+                } = self
+                    .inner
+                    .verify_monitor(&monitor_request, &monitor_response, monitor_context, now)
+                    .map_err(Error::from)
+                    .inspect_err(|e| {
+                        self.report_verification_failure(e);
+                        trace_event!(tracer, |_trace| trace::TraceEvent::decided(Some(e)));
+                    })?;
+                trace_event!(tracer, |_trace| trace::TraceEvent::RootComputed {
+                    tree_size: verified.tree_head.tree_size,
+                    root: hex::encode(verified.tree_root),
+                });
+                trace_event!(tracer, |_trace| trace::TraceEvent::decided(None));
+                proof_metrics.verification_time = started.elapsed();
+                self.report_proof_metrics(KtOperation::Monitor, &proof_metrics);
+
+                if let Some(cache) = &self.config.monitor_verification_cache {
+                    for (search_key, data) in &verified.monitoring_data {
+                        cache.insert(
+                            search_key.clone(),
+                            VerifiedMonitorEntry {
+                                entry_position: data.latest_log_position(),
+                                tree_size: verified.tree_head.tree_size,
+                                root: verified.tree_root,
+                                distinguished_tree_size,
+                            },
+                        );
+                    }
+                }
+                (verified, Some(consistency), Some(proof_metrics))
+            };
 
             let LocalStateUpdate {
                 tree_head,
@@ -850,7 +3192,7 @@ impl KtApi for Kt<'_> {
                     .ok_or(Error::InvalidResponse(err_message.to_string()))
             };
 
-            AccountData {
+            let updated_account_data = AccountData {
                 aci: take_data(&aci.as_search_key(), "ACI monitoring data is missing")?,
                 e164: e164
                     .map(|e164| {
@@ -866,54 +3208,297 @@ impl KtApi for Kt<'_> {
                     })
                     .transpose()?,
                 last_tree_head: (tree_head, tree_root),
-            }
+            };
+            (updated_account_data, consistency, proof_metrics)
         };
 
-        Ok(updated_account_data)
+        Ok(
+            MonitorResult::monitored(updated_account_data.into(), consistency, proof_metrics)
+                .with_dropped_legs(dropped_legs),
+        )
     }
 }
 
-fn verify_single_search_response(
-    kt: &KeyTransparency,
-    search_key: Vec<u8>,
-    response: CondensedTreeSearchResponse,
-    monitoring_data: Option<MonitoringData>,
-    full_tree_head: &FullTreeHead,
-    last_tree_head: Option<&LastTreeHead>,
-    last_distinguished_tree_head: Option<&LastTreeHead>,
-    now: SystemTime,
-) -> Result<VerifiedSearchResult> {
-    let result = kt.verify_search(
-        SlimSearchRequest::new(search_key),
-        FullSearchResponse::new(response, full_tree_head),
-        SearchContext {
-            last_tree_head,
-            last_distinguished_tree_head,
-            data: monitoring_data,
-        },
-        true,
-        now,
-    )?;
-    Ok(result)
-}
+/// Operations that manage their own state with the [`KtStateStore`] set by
+/// [`Config::with_state_store`].
+///
+/// Each fails with [`Error::BadArguments`] if there is no store. If the
+/// updated state can't be saved, they fail with [`Error::StatePersistFailed`]
+/// rather than returning a result the next operation won't be able to build
+/// on.
+impl Kt<'_> {
+    /// Like [`KtApi::search`], starting from the account data stored for `aci`,
+    /// if any.
+    pub async fn search_with_store(
+        &self,
+        aci: &Aci,
+        aci_identity_key: &PublicKey,
+        e164: Option<E164SearchKey>,
+        username_hash: Option<UsernameHash<'_>>,
+    ) -> Result<MaybePartial<SearchResult>> {
+        let store = self.state_store()?;
+        let stored_account_data = load_account_data(store, aci).await?;
+        let distinguished_tree_head = self.stored_distinguished_tree_head(store).await?;
+        let result = self
+            .search(
+                aci,
+                aci_identity_key,
+                e164,
+                username_hash,
+                stored_account_data,
+                &distinguished_tree_head,
+            )
+            .await?;
+        store
+            .set_account_data(*aci, result.inner.account_data.clone())
+            .await
+            .map_err(Error::StatePersistFailed)?;
+        Ok(result)
+    }
 
-fn verify_chat_search_response(
-    kt: &KeyTransparency,
-    aci: &Aci,
-    e164: Option<E164>,
-    username_hash: Option<UsernameHash>,
-    stored_account_data: Option<AccountData>,
-    chat_search_response: TypedSearchResponse,
-    last_distinguished_tree_head: Option<&LastTreeHead>,
-    now: SystemTime,
-) -> Result<MaybePartial<SearchResult>> {
-    let TypedSearchResponse {
-        full_tree_head,
-        aci_search_response,
-        e164_search_response,
-        username_hash_search_response,
+    /// Like [`KtApi::monitor`], for the account data stored for `aci`.
+    ///
+    /// The account must have been searched for first.
+    pub async fn monitor_with_store(
+        &self,
+        aci: &Aci,
+        e164: Option<E164>,
+        username_hash: Option<UsernameHash<'_>>,
+    ) -> Result<MonitorResult> {
+        let store = self.state_store()?;
+        let account_data = load_account_data(store, aci)
+            .await?
+            .ok_or(BadArgumentsReason::MissingAccountData)?;
+        let distinguished_tree_head = self.stored_distinguished_tree_head(store).await?;
+        let result = self
+            .monitor(
+                aci,
+                e164,
+                username_hash,
+                account_data,
+                &distinguished_tree_head,
+            )
+            .await?;
+        if !result.skipped {
+            store
+                .set_account_data(*aci, result.account_data.inner.clone().into())
+                .await
+                .map_err(Error::StatePersistFailed)?;
+        }
+        Ok(result)
+    }
+
+    /// Like [`KtApi::distinguished`], starting from the stored distinguished
+    /// tree head, if any.
+    pub async fn distinguished_with_store(&self) -> Result<DistinguishedResult> {
+        let store = self.state_store()?;
+        let last_distinguished = store
+            .distinguished_tree_head()
+            .await
+            .map_err(Error::StateLoadFailed)?
+            .map(into_last_tree_head)
+            .transpose()?;
+        self.update_stored_distinguished(store, last_distinguished)
+            .await
+    }
+
+    fn state_store(&self) -> Result<&dyn KtStateStore> {
+        self.config
+            .state_store
+            .as_deref()
+            .ok_or(Error::BadArguments(BadArgumentsReason::MissingStateStore))
+    }
+
+    /// The stored distinguished tree head, fetching (and storing) one first
+    /// if there isn't one yet.
+    async fn stored_distinguished_tree_head(
+        &self,
+        store: &dyn KtStateStore,
+    ) -> Result<LastTreeHead> {
+        let stored = store
+            .distinguished_tree_head()
+            .await
+            .map_err(Error::StateLoadFailed)?;
+        if let Some(stored) = stored {
+            return into_last_tree_head(stored);
+        }
+        let LocalStateUpdate {
+            tree_head,
+            tree_root,
+            monitoring_data: _,
+        } = self
+            .update_stored_distinguished(store, None)
+            .await?
+            .state_update;
+        Ok((tree_head, tree_root))
+    }
+
+    async fn update_stored_distinguished(
+        &self,
+        store: &dyn KtStateStore,
+        last_distinguished: Option<LastTreeHead>,
+    ) -> Result<DistinguishedResult> {
+        let result = self.distinguished(last_distinguished).await?;
+        store
+            .set_distinguished_tree_head(result.state_update.clone().into_stored())
+            .await
+            .map_err(Error::StatePersistFailed)?;
+        Ok(result)
+    }
+}
+
+async fn load_account_data(store: &dyn KtStateStore, aci: &Aci) -> Result<Option<AccountData>> {
+    store
+        .account_data(*aci)
+        .await
+        .map_err(Error::StateLoadFailed)?
+        .map(|stored| {
+            AccountData::try_from(stored).map_err(|e| Error::StateLoadFailed(Box::new(e)))
+        })
+        .transpose()
+}
+
+fn into_last_tree_head(stored: StoredTreeHead) -> Result<LastTreeHead> {
+    stored
+        .into_last_tree_head()
+        .ok_or_else(|| Error::StateLoadFailed("stored distinguished tree head is malformed".into()))
+}
+
+fn verify_single_search_response(
+    kt: &KeyTransparency,
+    leg: SearchKeyKind,
+    search_key: Vec<u8>,
+    response: CondensedTreeSearchResponse,
+    monitoring_data: Option<MonitoringData>,
+    full_tree_head: &FullTreeHead,
+    last_tree_head: Option<&LastTreeHead>,
+    last_distinguished_tree_head: Option<&LastTreeHead>,
+    now: SystemTime,
+    tracer: Tracer<'_>,
+) -> Result<VerifiedSearchResult> {
+    trace_event!(tracer, |trace| trace::TraceEvent::ProofConsumed {
+        key: Some(trace.key(leg, &search_key)),
+        proof: trace::TracedProof::Search {
+            steps: response
+                .search
+                .as_ref()
+                .map_or(0, |proof| proof.steps.len()),
+            inclusion_hashes: response
+                .search
+                .as_ref()
+                .map_or(0, |proof| proof.inclusion.len()),
+        },
+    });
+    let result = kt
+        .verify_search(
+            SlimSearchRequest::new(search_key),
+            FullSearchResponse::new(response, full_tree_head),
+            SearchContext {
+                last_tree_head,
+                last_distinguished_tree_head,
+                data: monitoring_data,
+            },
+            true,
+            now,
+        )
+        .map_err(|source| {
+            if is_tree_head_failure(&source) {
+                Error::VerificationFailed(source)
+            } else {
+                Error::LegVerificationFailed { leg, source }
+            }
+        })?;
+    trace_event!(tracer, |_trace| trace::TraceEvent::RootComputed {
+        tree_size: result.state_update.tree_head.tree_size,
+        root: hex::encode(result.state_update.tree_root),
+    });
+    Ok(result)
+}
+
+/// Whether a search verification failure is about the tree head that all of a
+/// search's keys share, rather than about one key's proof.
+///
+/// A bad signature or timestamp, or a tree head that's inconsistent with one
+/// seen before, would fail the same way whichever key was verified first, so
+/// those aren't attributed to a key. A key whose proof computes the wrong root
+/// also fails the signature check, and can't be singled out either.
+fn is_tree_head_failure(error: &libsignal_keytrans::Error) -> bool {
+    match error {
+        libsignal_keytrans::Error::InvalidSignature(_)
+        | libsignal_keytrans::Error::Inconsistent(_)
+        | libsignal_keytrans::Error::Stale(_) => true,
+        libsignal_keytrans::Error::RequiredFieldMissing(_)
+        | libsignal_keytrans::Error::InvalidProofElement
+        | libsignal_keytrans::Error::ValueTooLong
+        | libsignal_keytrans::Error::VerificationFailed(_) => false,
+    }
+}
+
+/// The kinds of key in a search or monitor, for [`trace::TraceEvent::Started`].
+#[cfg(feature = "keytrans-trace")]
+fn traced_key_kinds(e164: bool, username_hash: bool) -> Vec<SearchKeyKind> {
+    [
+        Some(SearchKeyKind::Aci),
+        e164.then_some(SearchKeyKind::E164),
+        username_hash.then_some(SearchKeyKind::UsernameHash),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn verify_chat_search_response(
+    kt: &KeyTransparency,
+    aci: &Aci,
+    e164: Option<E164>,
+    username_hash: Option<UsernameHash>,
+    stored_account_data: Option<AccountData>,
+    chat_search_response: TypedSearchResponse,
+    last_distinguished_tree_head: Option<&LastTreeHead>,
+    detect_username_changes: bool,
+    now: SystemTime,
+    deadline: &OperationDeadline,
+    tracer: Tracer<'_>,
+) -> Result<MaybePartial<SearchResult>> {
+    let TypedSearchResponse {
+        full_tree_head,
+        aci_search_response,
+        e164_search_response,
+        username_hash_search_response,
     } = chat_search_response;
 
+    trace_event!(tracer, |_trace| trace::TraceEvent::Started {
+        operation: KtOperation::Search,
+        tree_size: full_tree_head.tree_head.as_ref().map(|head| head.tree_size),
+        last_tree_size: stored_account_data
+            .as_ref()
+            .map(|acc| acc.last_tree_head.0.tree_size),
+        distinguished_tree_size: last_distinguished_tree_head.map(|(head, _root)| head.tree_size),
+        key_kinds: traced_key_kinds(e164.is_some(), username_hash.is_some()),
+    });
+    trace_event!(tracer, |_trace| trace::TraceEvent::ProofConsumed {
+        key: None,
+        proof: trace::TracedProof::LastConsistency {
+            hashes: full_tree_head.last.len(),
+        },
+    });
+    trace_event!(tracer, |_trace| trace::TraceEvent::ProofConsumed {
+        key: None,
+        proof: trace::TracedProof::DistinguishedConsistency {
+            hashes: full_tree_head.distinguished.len(),
+        },
+    });
+
+    let started = Instant::now();
+    let mut proof_metrics = ProofMetrics::new(
+        &full_tree_head,
+        ProofMetrics::search_inclusion_hashes(
+            std::iter::once(&aci_search_response)
+                .chain(&e164_search_response)
+                .chain(&username_hash_search_response),
+        ),
+    );
+
     let (
         aci_monitoring_data,
         e164_monitoring_data,
@@ -934,6 +3519,7 @@ fn verify_chat_search_response(
 
     let aci_result = verify_single_search_response(
         kt,
+        SearchKeyKind::Aci,
         aci.as_search_key(),
         aci_search_response,
         aci_monitoring_data,
@@ -941,14 +3527,17 @@ fn verify_chat_search_response(
         stored_last_tree_head.as_ref(),
         last_distinguished_tree_head,
         now,
+        tracer,
     )?;
 
+    deadline.check()?;
     let e164_result = match_optional_fields(e164, e164_search_response, AccountDataField::E164)?
         .map(|non_partial| {
             non_partial
                 .map(|(e164, e164_search_response)| {
                     verify_single_search_response(
                         kt,
+                        SearchKeyKind::E164,
                         e164.as_search_key(),
                         e164_search_response,
                         e164_monitoring_data,
@@ -956,12 +3545,14 @@ fn verify_chat_search_response(
                         stored_last_tree_head.as_ref(),
                         last_distinguished_tree_head,
                         now,
+                        tracer,
                     )
                 })
                 .transpose()
         })
         .transpose()?;
 
+    deadline.check()?;
     let username_hash_result = match_optional_fields(
         username_hash,
         username_hash_search_response,
@@ -972,6 +3563,7 @@ fn verify_chat_search_response(
             .map(|(username_hash, username_hash_response)| {
                 verify_single_search_response(
                     kt,
+                    SearchKeyKind::UsernameHash,
                     username_hash.as_search_key(),
                     username_hash_response,
                     username_hash_monitoring_data,
@@ -979,6 +3571,7 @@ fn verify_chat_search_response(
                     stored_last_tree_head.as_ref(),
                     last_distinguished_tree_head,
                     now,
+                    tracer,
                 )
             })
             .transpose()
@@ -999,10 +3592,26 @@ fn verify_chat_search_response(
         .as_ref()
         .map(extract_value_as::<Aci>)
         .transpose()?;
-    let aci_for_username_hash = username_hash_result
-        .as_ref()
-        .map(extract_value_as::<Aci>)
-        .transpose()?;
+    let (aci_for_username_hash, username_change) = match &username_hash_result {
+        None => (None, None),
+        Some(result) => interpret_username_hash_value(result, aci, detect_username_changes)?,
+    };
+    // The mapping isn't this account's to monitor any more.
+    let username_hash_result = username_hash_result.filter(|_| username_change.is_none());
+
+    let known_tree_heads = [
+        Some(&aci_result.state_update.tree_head),
+        last_distinguished_tree_head.map(|(tree_head, _root)| tree_head),
+        stored_last_tree_head
+            .as_ref()
+            .map(|(tree_head, _root)| tree_head),
+    ];
+    let binding_age = |result: &Option<VerifiedSearchResult>| {
+        let monitoring_data = result.as_ref()?.state_update.monitoring_data.as_ref()?;
+        BindingAge::new(monitoring_data, known_tree_heads.into_iter().flatten())
+    };
+    let e164_binding_age = binding_age(&e164_result);
+    let username_binding_age = binding_age(&username_hash_result);
 
     // ACI response is guaranteed to be present, taking the last tree head from it.
     let LocalStateUpdate {
@@ -1027,12 +3636,22 @@ fn verify_chat_search_response(
         last_tree_head: Some(last_tree_head),
     };
 
+    proof_metrics.verification_time = started.elapsed();
+
     let search_result = SearchResult {
         aci_identity_key: identity_key,
         aci_for_e164,
         aci_for_username_hash,
         timestamp: now,
         account_data: updated_account_data,
+        proof_metrics,
+        // Filled in by the caller, which knows the deployment mode.
+        auditor_lag: None,
+        auditor_timestamp: None,
+        e164_binding_age,
+        username_binding_age,
+        username_change,
+        consistency: aci_result.consistency,
     };
 
     Ok(MaybePartial {
@@ -1072,6 +3691,28 @@ fn match_optional_fields<T, U>(
     }
 }
 
+/// Returns the ACI a username hash maps to, or how the mapping has changed
+/// from `searched_aci` if `detect_changes` is set.
+fn interpret_username_hash_value(
+    result: &VerifiedSearchResult,
+    searched_aci: &Aci,
+    detect_changes: bool,
+) -> Result<(Option<Aci>, Option<UsernameChange>)> {
+    let value = SearchValue::try_from(result)?;
+    if !detect_changes {
+        return Ok((Some(value.try_into()?), None));
+    }
+    if value.is_tombstone() {
+        return Ok((None, Some(UsernameChange::Removed)));
+    }
+    let aci = Aci::try_from(value)?;
+    if aci == *searched_aci {
+        Ok((Some(aci), None))
+    } else {
+        Ok((None, Some(UsernameChange::Moved { new_aci: aci })))
+    }
+}
+
 // Cannot be a method on VerifiedSearchResult due to use of SearchValue
 fn extract_value_as<T>(result: &VerifiedSearchResult) -> Result<T>
 where
@@ -1081,9 +3722,38 @@ where
     val.try_into()
 }
 
-const SEARCH_KEY_PREFIX_ACI: &[u8] = b"a";
-const SEARCH_KEY_PREFIX_E164: &[u8] = b"n";
-const SEARCH_KEY_PREFIX_USERNAME_HASH: &[u8] = b"u";
+/// Prefixes an ACI's 17-byte service ID binary in its search key.
+pub const SEARCH_KEY_PREFIX_ACI: &[u8] = b"a";
+/// Prefixes an E.164's string form (`+` and digits) in its search key.
+pub const SEARCH_KEY_PREFIX_E164: &[u8] = b"n";
+/// Prefixes the raw bytes of a username hash in its search key.
+pub const SEARCH_KEY_PREFIX_USERNAME_HASH: &[u8] = b"u";
+
+impl SearchKeyKind {
+    /// The bytes that start every search key of this kind.
+    pub const fn prefix(self) -> &'static [u8] {
+        match self {
+            SearchKeyKind::Aci => SEARCH_KEY_PREFIX_ACI,
+            SearchKeyKind::E164 => SEARCH_KEY_PREFIX_E164,
+            SearchKeyKind::UsernameHash => SEARCH_KEY_PREFIX_USERNAME_HASH,
+        }
+    }
+}
+
+/// Splits a search key into its kind and the rest of the key, the reverse of
+/// [`SearchKey::as_search_key`].
+///
+/// Only the prefix is checked; the rest isn't validated as an ACI, E.164, or
+/// username hash. Returns `None` for a key without a known prefix.
+pub fn parse_search_key(search_key: &[u8]) -> Option<(SearchKeyKind, &[u8])> {
+    [
+        SearchKeyKind::Aci,
+        SearchKeyKind::E164,
+        SearchKeyKind::UsernameHash,
+    ]
+    .into_iter()
+    .find_map(|kind| Some((kind, search_key.strip_prefix(kind.prefix())?)))
+}
 
 /// Representation of an object as "search key" aligned with conversion
 /// performed by the chat server.
@@ -1091,20 +3761,32 @@ const SEARCH_KEY_PREFIX_USERNAME_HASH: &[u8] = b"u";
 /// Search keys from the Key Transparency server perspective are just arrays of
 /// bytes, therefore in order to distinguish them and avoid (highly unlikely)
 /// clashes Chat server adds unique prefixes to keys representing ACIs, E.164's,
-/// and username hashes.
+/// and username hashes:
+///
+/// - an [`Aci`] is [`SEARCH_KEY_PREFIX_ACI`] followed by its service ID binary,
+/// - an [`E164`] is [`SEARCH_KEY_PREFIX_E164`] followed by its string form,
+/// - a [`UsernameHash`] is [`SEARCH_KEY_PREFIX_USERNAME_HASH`] followed by the
+///   hash.
+///
+/// Anything storing data by search key should use this trait rather than
+/// building keys itself, so that its keys match the ones [`Kt`] uses.
 pub trait SearchKey {
     fn as_search_key(&self) -> Vec<u8>;
 }
 
 impl SearchKey for Aci {
     fn as_search_key(&self) -> Vec<u8> {
-        [SEARCH_KEY_PREFIX_ACI, self.service_id_binary().as_slice()].concat()
+        [
+            SearchKeyKind::Aci.prefix(),
+            self.service_id_binary().as_slice(),
+        ]
+        .concat()
     }
 }
 
 impl SearchKey for E164 {
     fn as_search_key(&self) -> Vec<u8> {
-        [SEARCH_KEY_PREFIX_E164, self.to_string().as_bytes()].concat()
+        [SearchKeyKind::E164.prefix(), self.to_string().as_bytes()].concat()
     }
 }
 
@@ -1127,6 +3809,9 @@ impl Debug for UsernameHash<'_> {
 }
 
 impl<'a> UsernameHash<'a> {
+    /// The length of a username hash.
+    pub const LEN: usize = 32;
+
     pub fn new(bytes: Vec<u8>) -> Self {
         Self(Cow::Owned(bytes))
     }
@@ -1135,6 +3820,15 @@ impl<'a> UsernameHash<'a> {
         Self(Cow::Borrowed(bytes))
     }
 
+    /// Like [`Self::from_slice`], but rejects input that isn't
+    /// [`Self::LEN`] bytes long.
+    pub fn try_from_slice(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.len() != Self::LEN {
+            return Err(BadArgumentsReason::UsernameHashLength.into());
+        }
+        Ok(Self::from_slice(bytes))
+    }
+
     pub fn into_vec(self) -> Vec<u8> {
         self.0.into_owned()
     }
@@ -1154,7 +3848,7 @@ impl From<Box<[u8]>> for UsernameHash<'_> {
 
 impl SearchKey for UsernameHash<'_> {
     fn as_search_key(&self) -> Vec<u8> {
-        [SEARCH_KEY_PREFIX_USERNAME_HASH, self.0.as_ref()].concat()
+        [SearchKeyKind::UsernameHash.prefix(), self.0.as_ref()].concat()
     }
 }
 
@@ -1189,6 +3883,7 @@ mod test_support {
     use libsignal_net_infra::EnableDomainFronting;
 
     use super::*;
+    use crate::chat::fake::server::FakeChatServer;
     use crate::chat::ChatConnection;
     use crate::env;
     use crate::env::{
@@ -1201,11 +3896,11 @@ mod test_support {
 
         use hex_literal::hex;
         use libsignal_core::curve::PublicKey;
-        use libsignal_core::{Aci, E164};
+        use libsignal_core::{Aci, UnidentifiedAccessKey, E164};
         use nonzero_ext::nonzero;
         use uuid::Uuid;
 
-        use super::UsernameHash;
+        use super::{E164SearchKey, UsernameHash};
 
         pub const ACI: Uuid = uuid::uuid!("90c979fd-eab4-4a08-b6da-69dedeab9b29");
         pub const ACI_IDENTITY_KEY_BYTES: &[u8] =
@@ -1213,7 +3908,8 @@ mod test_support {
         pub const USERNAME_HASH: &[u8] =
             &hex!("d237a4b83b463ca7da58d4a16bf6a3ba104506eb412b235eb603ea10f467c655");
         pub const PHONE_NUMBER: E164 = E164::new(nonzero!(18005550100u64));
-        pub const UNIDENTIFIED_ACCESS_KEY: &[u8] = &hex!("c6f7c258c24d69538ea553b4a943c8d9");
+        pub const UNIDENTIFIED_ACCESS_KEY: UnidentifiedAccessKey =
+            UnidentifiedAccessKey::new(hex!("c6f7c258c24d69538ea553b4a943c8d9"));
 
         pub fn aci() -> Aci {
             Aci::from(ACI)
@@ -1226,6 +3922,13 @@ mod test_support {
         pub fn username_hash() -> UsernameHash<'static> {
             UsernameHash(Cow::Borrowed(USERNAME_HASH))
         }
+
+        pub fn e164_search_key() -> E164SearchKey {
+            E164SearchKey {
+                e164: PHONE_NUMBER,
+                unidentified_access_key: Some(UNIDENTIFIED_ACCESS_KEY),
+            }
+        }
     }
 
     pub(super) fn make_key_transparency() -> KeyTransparency {
@@ -1245,10 +3948,24 @@ mod test_support {
     }
 
     pub(super) fn make_kt(chat: &(dyn UnauthenticatedChat + Sync)) -> Kt<'_> {
+        Kt::new(make_key_transparency(), chat, Default::default())
+    }
+
+    pub(super) const CHAT_SEARCH_RESPONSE: &[u8] =
+        include_bytes!("../tests/data/chat_search_response.dat");
+    pub(super) const CHAT_SEARCH_RESPONSE_VALID_AT: Duration = Duration::from_secs(1740164663);
+
+    /// Like [`make_kt`], but using `config` with its clock set to when
+    /// [`CHAT_SEARCH_RESPONSE`] was recorded.
+    pub(super) fn kt_at_search_time(
+        chat: &(dyn UnauthenticatedChat + Sync),
+        config: Config,
+    ) -> Kt<'_> {
         Kt {
-            inner: make_key_transparency(),
-            chat,
-            config: Default::default(),
+            config: config.with_clock(Arc::new(
+                SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT,
+            )),
+            ..make_kt(chat)
         }
     }
 
@@ -1266,6 +3983,57 @@ mod test_support {
         }
     }
 
+    /// Wrapper for [`ChatConnection`] known to be connected with
+    /// authentication.
+    pub(super) struct KtAuthChatConnection(ChatConnection);
+
+    impl AuthenticatedChat for KtAuthChatConnection {
+        fn send_authenticated(
+            &self,
+            request: chat::Request,
+            timeout: Duration,
+        ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+            self.0.send(request, timeout).boxed()
+        }
+    }
+
+    /// Connects to `server` instead of the real chat server.
+    pub(super) fn make_fake_chat(server: &FakeChatServer) -> KtUnauthChatConnection {
+        KtUnauthChatConnection(server.connect(Box::new(|_event| {})))
+    }
+
+    /// Like [`make_fake_chat`], but as if the server were behind a gateway
+    /// that serves it under `prefix`.
+    pub(super) fn make_fake_chat_with_path_prefix(
+        server: &FakeChatServer,
+        prefix: &str,
+    ) -> KtUnauthChatConnection {
+        let prefix = crate::chat::RequestPathPrefix::new(prefix).expect("valid prefix");
+        KtUnauthChatConnection(
+            server
+                .connect(Box::new(|_event| {}))
+                .with_request_path_prefix(Some(prefix)),
+        )
+    }
+
+    /// Like [`make_fake_chat`], but compresses request bodies of at least
+    /// `threshold` bytes when asked to.
+    pub(super) fn make_fake_chat_with_gzip_threshold(
+        server: &FakeChatServer,
+        threshold: usize,
+    ) -> KtUnauthChatConnection {
+        KtUnauthChatConnection(
+            server
+                .connect(Box::new(|_event| {}))
+                .with_gzip_request_body_threshold(threshold),
+        )
+    }
+
+    /// Like [`make_fake_chat`], but pretends the connection is authenticated.
+    pub(super) fn make_fake_auth_chat(server: &FakeChatServer) -> KtAuthChatConnection {
+        KtAuthChatConnection(server.connect(Box::new(|_event| {})))
+    }
+
     pub(super) async fn make_chat() -> KtUnauthChatConnection {
         use crate::chat::test_support::simple_chat_connection;
         let chat = simple_chat_connection(
@@ -1310,7 +4078,11 @@ mod test_support {
         prompt("Let's collect some data (press ENTER)");
 
         println!("Requesting distinguished tree...");
-        let result = kt.distinguished(None).await.expect("can get distinguished");
+        let result = kt
+            .distinguished(None)
+            .await
+            .expect("can get distinguished")
+            .state_update;
 
         let distinguished_tree_size = result.tree_head.tree_size;
         println!("Distinguished tree");
@@ -1333,10 +4105,7 @@ mod test_support {
         let aci = Aci::from(test_account::ACI);
         let aci_identity_key =
             PublicKey::deserialize(test_account::ACI_IDENTITY_KEY_BYTES).expect("valid key bytes");
-        let e164 = (
-            test_account::PHONE_NUMBER,
-            test_account::UNIDENTIFIED_ACCESS_KEY.to_vec(),
-        );
+        let e164 = test_account::e164_search_key();
         let username_hash = UsernameHash(Cow::Borrowed(test_account::USERNAME_HASH));
 
         println!("Requesting account data...");
@@ -1382,27 +4151,29 @@ mod test_support {
 
         let raw_request = RawChatSearchRequest::new(
             &aci,
-            &aci_identity_key,
+            Some(&aci_identity_key),
             Some(&e164),
             Some(&username_hash),
             Some(account_data.last_tree_head.0.tree_size),
             distinguished_tree.0.tree_size,
         );
         let response = kt
-            .send(raw_request.into())
+            .send(
+                raw_request
+                    .try_into()
+                    .expect("can serialize search request"),
+            )
             .await
             .expect("can send raw search request");
 
-        let raw_response = RawChatSerializedResponse::try_from(response).expect("valid response");
-        let response_bytes = BASE64_STANDARD_NO_PAD
-            .decode(raw_response.serialized_response.as_bytes())
-            .expect("valid base64");
+        let chat_search_response: ChatSearchResponse =
+            kt.decode_response(response).expect("valid response");
+        let response_bytes = chat_search_response.encode_to_vec();
 
         {
-            let search_response = ChatSearchResponse::decode(response_bytes.as_ref())
-                .map_err(|_| Error::InvalidResponse("bad protobuf".to_string()))
-                .and_then(|r| TypedSearchResponse::from_untyped(true, true, r))
-                .expect("valid search response");
+            let search_response =
+                TypedSearchResponse::from_untyped(true, true, chat_search_response)
+                    .expect("valid search response");
 
             let tree_size = search_response.full_tree_head.tree_head.unwrap().tree_size;
             assert_ne!(last_tree_size, tree_size, "The tree did not advance!");
@@ -1417,21 +4188,92 @@ mod test_support {
         println!("Response written to '{PATH}'");
         std::fs::write(PATH, &response_bytes).unwrap()
     }
+
+    #[derive(Default)]
+    pub(super) struct InMemoryStateStore {
+        pub(super) account_data: Mutex<HashMap<Aci, StoredAccountData>>,
+        pub(super) distinguished: Mutex<Option<StoredTreeHead>>,
+        pub(super) fail_writes: bool,
+    }
+
+    impl KtStateStore for InMemoryStateStore {
+        fn account_data(
+            &self,
+            aci: Aci,
+        ) -> BoxFuture<'_, std::result::Result<Option<StoredAccountData>, KtStateStoreError>>
+        {
+            let stored = self
+                .account_data
+                .lock()
+                .expect("not poisoned")
+                .get(&aci)
+                .cloned();
+            std::future::ready(Ok(stored)).boxed()
+        }
+
+        fn set_account_data(
+            &self,
+            aci: Aci,
+            account_data: StoredAccountData,
+        ) -> BoxFuture<'_, std::result::Result<(), KtStateStoreError>> {
+            let result: std::result::Result<(), KtStateStoreError> = if self.fail_writes {
+                Err("disk full".into())
+            } else {
+                self.account_data
+                    .lock()
+                    .expect("not poisoned")
+                    .insert(aci, account_data);
+                Ok(())
+            };
+            std::future::ready(result).boxed()
+        }
+
+        fn distinguished_tree_head(
+            &self,
+        ) -> BoxFuture<'_, std::result::Result<Option<StoredTreeHead>, KtStateStoreError>> {
+            let stored = self.distinguished.lock().expect("not poisoned").clone();
+            std::future::ready(Ok(stored)).boxed()
+        }
+
+        fn set_distinguished_tree_head(
+            &self,
+            tree_head: StoredTreeHead,
+        ) -> BoxFuture<'_, std::result::Result<(), KtStateStoreError>> {
+            let result: std::result::Result<(), KtStateStoreError> = if self.fail_writes {
+                Err("disk full".into())
+            } else {
+                *self.distinguished.lock().expect("not poisoned") = Some(tree_head);
+                Ok(())
+            };
+            std::future::ready(result).boxed()
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use std::cmp::Ordering;
+    use std::sync::atomic::{self, AtomicUsize};
     use std::sync::{Arc, Mutex};
 
     use assert_matches::assert_matches;
+    use bytes::Bytes;
+    use futures_util::FutureExt as _;
     use hex_literal::hex;
     use http::StatusCode;
-    use libsignal_keytrans::TreeHead;
+    use libsignal_keytrans::testutil::FakeLog;
+    use libsignal_keytrans::{ConsistencyCheck, PublicConfig};
+    use nonzero_ext::nonzero;
     use test_case::test_case;
 
-    use super::test_support::{make_chat, make_key_transparency, make_kt, test_account};
+    use super::test_support::{
+        kt_at_search_time, make_fake_chat, make_fake_chat_with_path_prefix, make_key_transparency,
+        make_kt, test_account, InMemoryStateStore, KtUnauthChatConnection, CHAT_SEARCH_RESPONSE,
+        CHAT_SEARCH_RESPONSE_VALID_AT,
+    };
     use super::*;
+    use crate::chat::fake::server::{CannedResponse, FakeChatServer};
+    use crate::env;
+    use crate::server_time::{BoundedServerClock, ServerTimeEstimator};
 
     // Distinguished tree parameters as of size 11526
     const DISTINGUISHED_TREE_19941_HEAD: &[u8] =
@@ -1459,25 +4301,52 @@ mod test {
         AccountData::try_from(test_stored_account_data()).expect("valid account data")
     }
 
+    /// [`test_account_data`], recorded as being for the test account's ACI,
+    /// E.164, and username hash.
+    fn test_account_data_with_search_keys() -> AccountData {
+        let mut account_data = test_account_data();
+        account_data.aci.search_key_hash = Some(MonitoringData::hash_search_key(
+            &test_account::aci().as_search_key(),
+        ));
+        for (data, search_key) in [
+            (
+                &mut account_data.e164,
+                test_account::PHONE_NUMBER.as_search_key(),
+            ),
+            (
+                &mut account_data.username_hash,
+                test_account::username_hash().as_search_key(),
+            ),
+        ] {
+            data.as_mut().expect("present").search_key_hash =
+                Some(MonitoringData::hash_search_key(&search_key));
+        }
+        account_data
+    }
+
     #[tokio::test]
     #[test_case(false, false; "ACI")]
     #[test_case(true, false; "ACI + E164")]
     #[test_case(false, true; "ACI + Username Hash")]
     #[test_case(true, true; "ACI + E164 + Username Hash")]
-    async fn search_permutations_integration_test(use_e164: bool, use_username_hash: bool) {
-        if std::env::var("LIBSIGNAL_TESTING_RUN_NONHERMETIC_TESTS").is_err() {
-            println!("SKIPPED: running integration tests is not enabled");
-            return;
+    async fn search_permutations_against_fake_server(use_e164: bool, use_username_hash: bool) {
+        // The server leaves out the mappings it wasn't asked about.
+        let mut response =
+            ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE).expect("valid response");
+        if !use_e164 {
+            response.e164 = None;
         }
-        let chat = make_chat().await;
-        let kt = make_kt(&chat);
+        if !use_username_hash {
+            response.username_hash = None;
+        }
+        let server = FakeChatServer::new();
+        respond_to_search(&server, &response.encode_to_vec());
+        let chat = make_fake_chat(&server);
+        let kt = kt_at_search_time(&chat, Config::default());
 
         let aci = test_account::aci();
         let aci_identity_key = test_account::aci_identity_key();
-        let e164 = (
-            test_account::PHONE_NUMBER,
-            test_account::UNIDENTIFIED_ACCESS_KEY.to_vec(),
-        );
+        let e164 = test_account::e164_search_key();
         let username_hash = test_account::username_hash();
 
         let acc_data = test_account_data();
@@ -1495,84 +4364,2180 @@ mod test {
             .expect("can perform search");
 
         assert_eq!(
-            &hex::encode(test_account::ACI_IDENTITY_KEY_BYTES),
-            &hex::encode(result.inner.aci_identity_key.serialize())
+            &hex::encode(test_account::ACI_IDENTITY_KEY_BYTES),
+            &hex::encode(result.inner.aci_identity_key.serialize())
+        );
+    }
+
+    #[test_case(true, true, true, true, true; "all fields")]
+    #[test_case(false, true, true, true, true; "no identity key")]
+    #[test_case(true, false, false, true, true; "no e164")]
+    #[test_case(true, true, false, true, true; "e164 without access key")]
+    #[test_case(true, true, true, false, true; "no username hash")]
+    #[test_case(true, true, true, true, false; "no last tree head")]
+    #[test_case(false, false, false, false, false; "required fields only")]
+    fn search_request_json(
+        with_identity_key: bool,
+        with_e164: bool,
+        with_access_key: bool,
+        with_username_hash: bool,
+        with_last_tree_head: bool,
+    ) {
+        let identity_key = test_account::aci_identity_key();
+        let e164 = E164SearchKey {
+            unidentified_access_key: test_account::e164_search_key()
+                .unidentified_access_key
+                .filter(|_| with_access_key),
+            ..test_account::e164_search_key()
+        };
+        let username_hash = test_account::username_hash();
+        let request = RawChatSearchRequest::new(
+            &test_account::aci(),
+            with_identity_key.then_some(&identity_key),
+            with_e164.then_some(&e164),
+            with_username_hash.then_some(&username_hash),
+            with_last_tree_head.then_some(200),
+            100,
+        );
+
+        let expected = [
+            Some(r#""aci":"90c979fd-eab4-4a08-b6da-69dedeab9b29""#),
+            with_identity_key
+                .then_some(r#""aciIdentityKey":"BREflGTBgixqJAWs8cWkNmZ53DNJ/I6wFcjXJg4/dxF3""#),
+            with_e164.then_some(r#""e164":"+18005550100""#),
+            with_username_hash
+                .then_some(r#""usernameHash":"0jekuDtGPKfaWNSha_ajuhBFButBKyNetgPqEPRnxlU""#),
+            with_access_key.then_some(r#""unidentifiedAccessKey":"xvfCWMJNaVOOpVO0qUPI2Q==""#),
+            with_last_tree_head.then_some(r#""lastTreeHeadSize":200"#),
+            Some(r#""distinguishedTreeHeadSize":100"#),
+        ]
+        .into_iter()
+        .flatten()
+        .join(",");
+        assert_eq!(
+            serde_json::to_string(&request).expect("can serialize"),
+            format!("{{{expected}}}")
+        );
+
+        let request = chat::Request::try_from(request).expect("valid request");
+        assert_eq!(request.method, http::Method::POST);
+        assert_eq!(request.path.as_str(), SEARCH_PATH);
+    }
+
+    #[test]
+    fn search_request_keeps_last_tree_head_ahead_of_distinguished() {
+        let last_size = test_account_data().last_tree_head.0.tree_size;
+        let distinguished_size = test_distinguished_tree().0.tree_size;
+        // The recorded fixtures capture the normal case of a stored head newer than the
+        // distinguished one.
+        assert!(last_size > distinguished_size);
+
+        let request = RawChatSearchRequest::new(
+            &test_account::aci(),
+            None,
+            None,
+            None,
+            Some(last_size),
+            distinguished_size,
+        );
+        assert_eq!(request.last_tree_head_size, Some(last_size));
+        assert_eq!(request.distinguished_tree_head_size, distinguished_size);
+    }
+
+    fn monitoring_data_at(pos: u64) -> MonitoringData {
+        MonitoringData {
+            index: [pos as u8; 32],
+            pos,
+            ptrs: HashMap::from([(pos, 1)]),
+            owned: true,
+            search_key_hash: None,
+        }
+    }
+
+    fn proof_with_steps(n: usize) -> MonitorProof {
+        MonitorProof {
+            steps: vec![Default::default(); n],
+        }
+    }
+
+    #[test]
+    fn monitor_parts_keep_keys_with_proofs() {
+        let parts = MonitorParts::from_entries(vec![
+            MonitorEntry::new(b"a1".to_vec(), monitoring_data_at(10), proof_with_steps(1)),
+            MonitorEntry::new(b"n2".to_vec(), monitoring_data_at(20), proof_with_steps(2)),
+            MonitorEntry::new(b"u3".to_vec(), monitoring_data_at(30), proof_with_steps(3)),
+        ])
+        .expect("distinct keys");
+
+        let pairs = parts
+            .keys
+            .iter()
+            .zip(&parts.proofs)
+            .map(|(key, proof)| {
+                (
+                    key.search_key.as_slice(),
+                    key.entry_position,
+                    proof.steps.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            [
+                (b"a1".as_slice(), 10, 1),
+                (b"n2".as_slice(), 20, 2),
+                (b"u3".as_slice(), 30, 3)
+            ]
+        );
+        assert_eq!(parts.data.len(), 3);
+        assert_eq!(parts.data[b"n2".as_slice()], monitoring_data_at(20));
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "duplicate search key"))]
+    fn monitor_parts_reject_duplicate_search_key() {
+        let result = MonitorParts::from_entries(vec![
+            MonitorEntry::new(b"a1".to_vec(), monitoring_data_at(10), proof_with_steps(1)),
+            MonitorEntry::new(b"u3".to_vec(), monitoring_data_at(30), proof_with_steps(3)),
+            MonitorEntry::new(b"a1".to_vec(), monitoring_data_at(20), proof_with_steps(2)),
+        ]);
+        assert_matches!(result, Err(Error::Internal("duplicate search key")));
+    }
+
+    #[test_case(19941, 0 => matches Ok(()); "same head")]
+    #[test_case(19996, -600 => matches Ok(()); "search head is newer")]
+    #[test_case(19900, 30 * 60 => matches Ok(()); "search head is slightly older")]
+    #[test_case(19900, 2 * 60 * 60 => matches Err(Error::StaleView {
+        search_size: 19900,
+        distinguished_size: 19941,
+    }); "search head is too old")]
+    fn view_freshness_default(search_size: u64, seconds_behind: i64) -> Result<()> {
+        let (distinguished, _) = test_distinguished_tree();
+        let search = TreeHead {
+            tree_size: search_size,
+            timestamp: distinguished.timestamp - seconds_behind * 1000,
+            ..distinguished.clone()
+        };
+        ViewFreshness::default().check(&search, &distinguished)
+    }
+
+    #[test]
+    fn view_freshness_max_size_lag() {
+        let (distinguished, _) = test_distinguished_tree();
+        let search = TreeHead {
+            tree_size: distinguished.tree_size - 100,
+            ..distinguished.clone()
+        };
+        let freshness = ViewFreshness {
+            max_size_lag: Some(100),
+            max_time_lag: None,
+        };
+        assert_matches!(freshness.check(&search, &distinguished), Ok(()));
+
+        let search = TreeHead {
+            tree_size: search.tree_size - 1,
+            ..search
+        };
+        assert_matches!(
+            freshness.check(&search, &distinguished),
+            Err(Error::StaleView { search_size, distinguished_size: 19941 }) if search_size == 19840
+        );
+    }
+
+    fn verified_entry(entry_position: u64, tree_size: u64) -> VerifiedMonitorEntry {
+        VerifiedMonitorEntry {
+            entry_position,
+            tree_size,
+            root: [tree_size as u8; 32],
+            distinguished_tree_size: 100,
+        }
+    }
+
+    #[test_case(|_| {} => true; "exact match")]
+    #[test_case(|e| e.entry_position += 1 => false; "entry position")]
+    #[test_case(|e| e.tree_size += 1 => false; "tree size")]
+    #[test_case(|e| e.root[0] ^= 1 => false; "root")]
+    #[test_case(|e| e.distinguished_tree_size += 1 => false; "distinguished tree size")]
+    fn monitor_verification_cache_requires_exact_match(
+        tweak: fn(&mut VerifiedMonitorEntry),
+    ) -> bool {
+        let cache = MonitorVerificationCache::new(NonZeroUsize::new(4).unwrap());
+        cache.insert(b"a1".to_vec(), verified_entry(10, 200));
+
+        let mut entry = verified_entry(10, 200);
+        tweak(&mut entry);
+        assert!(!cache.contains(b"u3", &entry));
+        cache.contains(b"a1", &entry)
+    }
+
+    #[test]
+    fn monitor_verification_cache_replaces_older_tree_head() {
+        let cache = MonitorVerificationCache::new(NonZeroUsize::new(4).unwrap());
+        cache.insert(b"a1".to_vec(), verified_entry(10, 200));
+        cache.insert(b"a1".to_vec(), verified_entry(10, 300));
+
+        assert!(!cache.contains(b"a1", &verified_entry(10, 200)));
+        assert!(cache.contains(b"a1", &verified_entry(10, 300)));
+    }
+
+    #[test]
+    fn monitor_verification_cache_evicts_least_recently_used() {
+        let cache = MonitorVerificationCache::new(NonZeroUsize::new(2).unwrap());
+        cache.insert(b"a1".to_vec(), verified_entry(10, 200));
+        cache.insert(b"u3".to_vec(), verified_entry(30, 200));
+        // Using a1 makes u3 the least recently used.
+        assert!(cache.contains(b"a1", &verified_entry(10, 200)));
+        cache.insert(b"n2".to_vec(), verified_entry(20, 200));
+
+        assert!(cache.contains(b"a1", &verified_entry(10, 200)));
+        assert!(!cache.contains(b"u3", &verified_entry(30, 200)));
+        assert!(cache.contains(b"n2", &verified_entry(20, 200)));
+    }
+
+    #[test_case(false, None => matches Ok(None); "neither")]
+    #[test_case(true, Some(16) => matches Ok(Some(_)); "both")]
+    #[test_case(true, None => matches Ok(Some(E164SearchKey { unidentified_access_key: None, .. })); "e164 only")]
+    #[test_case(false, Some(16) => matches Err(Error::BadArguments(BadArgumentsReason::UnidentifiedAccessKeyWithoutE164)); "access key only")]
+    #[test_case(true, Some(15) => matches Err(Error::BadArguments(BadArgumentsReason::UnidentifiedAccessKeyLength)); "short access key")]
+    fn e164_search_key_from_parts(
+        with_e164: bool,
+        access_key_len: Option<usize>,
+    ) -> Result<Option<E164SearchKey>> {
+        E164SearchKey::from_parts(
+            with_e164.then_some(test_account::PHONE_NUMBER),
+            access_key_len.map(|len| vec![0; len]).as_deref(),
+        )
+    }
+
+    #[test_case(999_999_999_999_999 => matches Ok(_); "15 digits")]
+    #[test_case(1_000_000_000_000_000 => matches Err(Error::BadArguments(BadArgumentsReason::E164OutOfRange)); "16 digits")]
+    fn e164_search_key_checks_range(number: u64) -> Result<E164SearchKey> {
+        E164SearchKey::new(
+            E164::new(number.try_into().expect("nonzero")),
+            Some(test_account::UNIDENTIFIED_ACCESS_KEY.as_bytes().as_slice()),
+        )
+    }
+
+    #[test_case(32 => matches Ok(_); "right length")]
+    #[test_case(31 => matches Err(Error::BadArguments(BadArgumentsReason::UsernameHashLength)); "short")]
+    #[test_case(33 => matches Err(Error::BadArguments(BadArgumentsReason::UsernameHashLength)); "long")]
+    #[test_case(0 => matches Err(Error::BadArguments(BadArgumentsReason::UsernameHashLength)); "empty")]
+    fn username_hash_checks_length(len: usize) -> Result<()> {
+        UsernameHash::try_from_slice(&vec![0; len]).map(|_| ())
+    }
+
+    #[test_case(true, true; "all fields")]
+    #[test_case(true, false; "no username hash")]
+    #[test_case(false, true; "no e164")]
+    #[test_case(false, false; "ACI only")]
+    fn monitor_request_json(with_e164: bool, with_username_hash: bool) {
+        let monitoring_data = |pos| MonitoringData {
+            index: std::array::from_fn(|i| i as u8),
+            pos,
+            ptrs: HashMap::from([(pos, 1)]),
+            owned: true,
+            search_key_hash: None,
+        };
+        let account_data = AccountData {
+            aci: monitoring_data(10),
+            e164: with_e164.then(|| monitoring_data(20)),
+            username_hash: with_username_hash.then(|| monitoring_data(30)),
+            last_tree_head: (
+                TreeHead {
+                    tree_size: 200,
+                    ..Default::default()
+                },
+                [0; 32],
+            ),
+        };
+        let request = RawChatMonitorRequest::new(
+            &test_account::aci(),
+            with_e164.then_some(test_account::PHONE_NUMBER),
+            &with_username_hash.then(test_account::username_hash),
+            &account_data,
+            100,
+        )
+        .expect("valid request");
+
+        const INDEX: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8";
+        let expected = [
+            Some(format!(
+                r#""aci":{{"value":"90c979fd-eab4-4a08-b6da-69dedeab9b29","entryPosition":10,"commitmentIndex":"{INDEX}"}}"#
+            )),
+            with_e164.then(|| format!(
+                r#""e164":{{"value":"+18005550100","entryPosition":20,"commitmentIndex":"{INDEX}"}}"#
+            )),
+            with_username_hash.then(|| format!(
+                r#""usernameHash":{{"value":"0jekuDtGPKfaWNSha_ajuhBFButBKyNetgPqEPRnxlU","entryPosition":30,"commitmentIndex":"{INDEX}"}}"#
+            )),
+            Some(r#""lastNonDistinguishedTreeHeadSize":200"#.to_owned()),
+            Some(r#""lastDistinguishedTreeHeadSize":100"#.to_owned()),
+        ]
+        .into_iter()
+        .flatten()
+        .join(",");
+        assert_eq!(
+            serde_json::to_string(&request).expect("can serialize"),
+            format!("{{{expected}}}")
+        );
+
+        let request = chat::Request::try_from(request).expect("valid request");
+        assert_eq!(request.method, http::Method::POST);
+        assert_eq!(request.path.as_str(), MONITOR_PATH);
+        assert!(request.gzip_body);
+    }
+
+    #[test_case(Some(42), "/v1/key-transparency/distinguished?lastTreeHeadSize=42"; "with last tree head")]
+    #[test_case(None, "/v1/key-transparency/distinguished?"; "without last tree head")]
+    fn distinguished_request_path(last_tree_head_size: Option<u64>, expected: &str) {
+        let request = chat::Request::from(RawChatDistinguishedRequest {
+            last_tree_head_size,
+        });
+        assert_eq!(request.method, http::Method::GET);
+        assert_eq!(request.path.as_str(), expected);
+        assert_eq!(request.body, None);
+    }
+
+    fn respond_to_search(server: &FakeChatServer, serialized_response: &[u8]) {
+        respond_with_serialized(server, SEARCH_PATH, serialized_response)
+    }
+
+    fn respond_with_serialized(server: &FakeChatServer, path: &str, serialized_response: &[u8]) {
+        server.respond(
+            path,
+            CannedResponse::json(&serde_json::json!({
+                "serializedResponse": BASE64_STANDARD_NO_PAD.encode(serialized_response),
+            })),
+        );
+    }
+
+    /// A log whose only entry is the distinguished key, signed at `now`.
+    fn fake_distinguished_log(now: SystemTime) -> FakeLog {
+        FakeLog::new(b"distinguished", b"", now)
+    }
+
+    fn make_fake_log_kt<'a>(
+        log: &FakeLog,
+        chat: &'a (dyn UnauthenticatedChat + Sync),
+        now: SystemTime,
+    ) -> Kt<'a> {
+        Kt::new(
+            KeyTransparency {
+                config: log.public_config(),
+            },
+            chat,
+            Config::default().with_clock(Arc::new(now)),
+        )
+    }
+
+    #[tokio::test]
+    async fn auditor_signed_search_fails_under_contact_monitoring() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let audited = make_key_transparency();
+        let kt = Kt {
+            inner: KeyTransparency {
+                config: PublicConfig {
+                    mode: DeploymentMode::ContactMonitoring,
+                    ..audited.config
+                },
+            },
+            ..kt_at_search_time(&chat, Config::default())
+        };
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await;
+
+        // The log signs its deployment mode along with each tree head, so a
+        // response from an audited deployment can't pass for one without an
+        // auditor.
+        let error = result.expect_err("deployment mode mismatch");
+        assert_eq!(
+            error.verification_category(),
+            Some(VerificationCategory::SignatureInvalid)
+        );
+    }
+
+    #[tokio::test]
+    async fn search_against_fake_server() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let kt = kt_at_search_time(&chat, Config::default());
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await
+            .expect("can perform search");
+
+        assert_eq!(
+            &hex::encode(test_account::ACI_IDENTITY_KEY_BYTES),
+            &hex::encode(result.inner.aci_identity_key.serialize())
+        );
+        assert_eq!(result.missing_fields, BTreeSet::new());
+        // The log has grown past both stored tree heads, so both consistency
+        // proofs were checked.
+        assert_eq!(
+            result.inner.consistency,
+            TreeHeadConsistency {
+                last: ConsistencyCheck::Proven,
+                distinguished: ConsistencyCheck::Proven,
+            }
+        );
+        for binding_age in [
+            result.inner.e164_binding_age,
+            result.inner.username_binding_age,
+        ] {
+            let binding_age = binding_age.expect("found mapping");
+            assert!(
+                binding_age.updated_by <= SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT
+            );
+        }
+
+        let requests = server.received_requests();
+        assert_matches!(&requests[..], [request] => {
+            assert_eq!(request.verb(), "POST");
+            assert_eq!(request.path(), SEARCH_PATH);
+        });
+    }
+
+    #[test]
+    fn binding_age_uses_smallest_tree_head_including_entry() {
+        let monitoring_data = MonitoringData {
+            index: [0; 32],
+            pos: 10,
+            ptrs: HashMap::from([(10, 0), (25, 1)]),
+            owned: false,
+            search_key_hash: None,
+        };
+        let tree_head = |tree_size, timestamp| TreeHead {
+            tree_size,
+            timestamp,
+            signature: vec![],
+        };
+        let tree_heads = [
+            tree_head(40, 3000),
+            tree_head(20, 1000),
+            tree_head(30, 2000),
+        ];
+
+        assert_eq!(
+            BindingAge::new(&monitoring_data, &tree_heads),
+            Some(BindingAge {
+                log_position: 25,
+                updated_by: SystemTime::UNIX_EPOCH + Duration::from_millis(2000),
+            })
+        );
+        assert_eq!(BindingAge::new(&monitoring_data, &tree_heads[1..2]), None);
+    }
+
+    #[tokio::test]
+    async fn requests_honor_path_prefix() {
+        let server = FakeChatServer::new();
+        server.respond(
+            format!("/gateway{SEARCH_PATH}"),
+            CannedResponse::json(&serde_json::json!({
+                "serializedResponse": BASE64_STANDARD_NO_PAD.encode(CHAT_SEARCH_RESPONSE),
+            })),
+        );
+        let chat = make_fake_chat_with_path_prefix(&server, "/gateway");
+        let kt = kt_at_search_time(&chat, Config::default());
+
+        kt.search(
+            &test_account::aci(),
+            &test_account::aci_identity_key(),
+            None,
+            None,
+            None,
+            &test_distinguished_tree(),
+        )
+        .await
+        .expect("can perform search");
+        // Nothing is registered for these, but the paths are still recorded.
+        _ = kt.distinguished(None).await;
+        _ = kt
+            .monitor(
+                &test_account::aci(),
+                None,
+                None,
+                test_account_data(),
+                &test_distinguished_tree(),
+            )
+            .await;
+
+        let paths = server
+            .received_requests()
+            .into_iter()
+            .map(|request| request.path().to_owned())
+            .collect_vec();
+        assert_eq!(
+            paths,
+            [
+                format!("/gateway{SEARCH_PATH}"),
+                format!("/gateway{DISTINGUISHED_PATH}?"),
+                format!("/gateway{MONITOR_PATH}"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn search_reports_auditor_lag() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let search = |config: Config| {
+            let kt = kt_at_search_time(&chat, config);
+            async move {
+                kt.search(
+                    &test_account::aci(),
+                    &test_account::aci_identity_key(),
+                    None,
+                    None,
+                    None,
+                    &test_distinguished_tree(),
+                )
+                .await
+            }
+        };
+
+        // The test log is deployed with a third-party auditor.
+        let result = search(Config::default()).await.expect("can perform search");
+        let lag = result.inner.auditor_lag.expect("has auditor");
+        assert!(result.inner.auditor_timestamp.is_some());
+
+        search(Config::default().with_max_auditor_lag(lag))
+            .await
+            .expect("lag is within limit");
+        if let Some(max_lag) = lag.checked_sub(1) {
+            assert_matches!(
+                search(Config::default().with_max_auditor_lag(max_lag)).await,
+                Err(Error::AuditorTooFarBehind { lag: reported, max_lag: reported_max })
+                    if reported == lag && reported_max == max_lag
+            );
+        }
+    }
+
+    #[derive(Default)]
+    struct SlowChat {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl UnauthenticatedChat for SlowChat {
+        fn send_unauthenticated(
+            &self,
+            _request: chat::Request,
+            _timeout: Duration,
+        ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+            async move {
+                let in_flight = self.in_flight.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+                self.max_in_flight
+                    .fetch_max(in_flight, atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                self.in_flight.fetch_sub(1, atomic::Ordering::SeqCst);
+                Ok(chat::Response {
+                    status: StatusCode::SERVICE_UNAVAILABLE,
+                    message: None,
+                    body: None,
+                    headers: Default::default(),
+                })
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_requests_are_limited() {
+        let chat = SlowChat::default();
+        let kt = Kt {
+            config: Config::default().with_max_concurrent_requests(nonzero!(2usize)),
+            ..make_kt(&chat)
+        };
+
+        let start = tokio::time::Instant::now();
+        let requests = futures_util::future::join_all((0..5).map(|_| kt.distinguished(None)));
+        let check_in_flight = async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            assert_eq!(kt.requests_in_flight(), 2);
+        };
+        let (results, ()) = futures_util::future::join(requests, check_in_flight).await;
+
+        for result in results {
+            assert_matches!(
+                result,
+                Err(Error::RequestFailed(StatusCode::SERVICE_UNAVAILABLE))
+            );
+        }
+        assert_eq!(chat.max_in_flight.load(atomic::Ordering::SeqCst), 2);
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+        assert_eq!(kt.requests_in_flight(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiting_for_request_limit_counts_against_deadline() {
+        let chat = SlowChat::default();
+        let kt = Kt {
+            config: Config::default()
+                .with_max_concurrent_requests(nonzero!(1usize))
+                .with_deadline(SystemTime::now() + Duration::from_millis(500)),
+            ..make_kt(&chat)
+        };
+
+        let start = tokio::time::Instant::now();
+        let (first, second) =
+            futures_util::future::join(kt.distinguished(None), kt.distinguished(None)).await;
+        assert_matches!(
+            first,
+            Err(Error::RequestFailed(StatusCode::SERVICE_UNAVAILABLE))
+        );
+        assert_matches!(
+            second,
+            Err(Error::ChatSendError(
+                chat::SendError::RequestTimedOut { .. }
+            ))
+        );
+        assert_eq!(chat.max_in_flight.load(atomic::Ordering::SeqCst), 1);
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn overall_deadline_interrupts_slow_requests() {
+        let chat = SlowChat::default();
+        let kt = Kt {
+            config: Config::default().with_overall_deadline(Duration::from_millis(500)),
+            ..make_kt(&chat)
+        };
+
+        let start = tokio::time::Instant::now();
+        assert_matches!(
+            kt.distinguished(None).await,
+            Err(Error::DeadlineExceeded {
+                phase: OperationPhase::Request
+            })
+        );
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+        assert_eq!(kt.requests_in_flight(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn overall_deadline_reports_current_phase() {
+        let deadline = OperationDeadline::new(Some(Duration::from_secs(1)));
+        deadline
+            .enter(OperationPhase::Verify)
+            .expect("deadline not reached");
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_matches!(
+            deadline.check(),
+            Err(Error::DeadlineExceeded {
+                phase: OperationPhase::Verify
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn overall_deadline_too_far_away_is_no_deadline() {
+        let deadline = OperationDeadline::new(Some(Duration::MAX));
+        assert_matches!(deadline.at, None);
+        deadline.check().expect("no deadline");
+    }
+
+    /// Answers searches like a deployment that doesn't index username hashes.
+    #[derive(Default)]
+    struct NoUsernameIndexChat {
+        requests: Mutex<Vec<serde_json::Value>>,
+    }
+
+    impl UnauthenticatedChat for NoUsernameIndexChat {
+        fn send_unauthenticated(
+            &self,
+            request: chat::Request,
+            _timeout: Duration,
+        ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+            let body: serde_json::Value =
+                serde_json::from_slice(request.body.as_deref().expect("has body"))
+                    .expect("valid JSON");
+            let response = if body.get("usernameHash").is_some() {
+                chat::Response {
+                    status: UNSUPPORTED_SEARCH_KEY_STATUS,
+                    message: None,
+                    body: Some(
+                        br#"{"code":"UNSUPPORTED_SEARCH_KEY","searchKey":"usernameHash"}"#
+                            .as_slice()
+                            .into(),
+                    ),
+                    headers: Default::default(),
+                }
+            } else {
+                let mut search_response =
+                    ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE).expect("valid response");
+                search_response.username_hash = None;
+                let envelope = serde_json::json!({
+                    "serializedResponse":
+                        BASE64_STANDARD_NO_PAD.encode(search_response.encode_to_vec()),
+                });
+                chat::Response {
+                    status: StatusCode::OK,
+                    message: None,
+                    body: Some(envelope.to_string().into_bytes().into()),
+                    headers: Default::default(),
+                }
+            };
+            self.requests.lock().unwrap().push(body);
+            std::future::ready(Ok(response)).boxed()
+        }
+    }
+
+    #[tokio::test]
+    #[test_case(false; "reported")]
+    #[test_case(true; "dropped")]
+    async fn search_with_unsupported_key(auto_drop: bool) {
+        let chat = NoUsernameIndexChat::default();
+        let config = Config::default();
+        let kt = kt_at_search_time(
+            &chat,
+            if auto_drop {
+                config.with_auto_drop_unsupported_keys()
+            } else {
+                config
+            },
+        );
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await;
+
+        let requests = chat.requests.into_inner().unwrap();
+        if !auto_drop {
+            assert_matches!(
+                result,
+                Err(Error::UnsupportedSearchKey(AccountDataField::UsernameHash))
+            );
+            assert_eq!(requests.len(), 1);
+            return;
+        }
+
+        let result = result.expect("retried without the username hash");
+        assert_eq!(
+            result.missing_fields,
+            BTreeSet::from([AccountDataField::UsernameHash])
+        );
+        assert_eq!(result.inner.aci_for_e164, Some(test_account::aci()));
+        assert_eq!(result.inner.aci_for_username_hash, None);
+        assert_matches!(&requests[..], [first, second] => {
+            assert!(first.get("usernameHash").is_some());
+            assert!(second.get("usernameHash").is_none());
+            assert_eq!(first.get("e164"), second.get("e164"));
+        });
+    }
+
+    #[tokio::test]
+    async fn search_response_over_size_limit() {
+        let chat = NoUsernameIndexChat::default();
+        let kt = kt_at_search_time(&chat, Config::default().with_max_response_size(16));
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                None,
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await;
+        assert_matches!(
+            result,
+            Err(Error::InvalidResponse(message)) if message.contains("at most 16 allowed")
+        );
+    }
+
+    /// Answers every request with the same response.
+    struct FixedResponseChat(chat::Response);
+
+    impl UnauthenticatedChat for FixedResponseChat {
+        fn send_unauthenticated(
+            &self,
+            request: chat::Request,
+            _timeout: Duration,
+        ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+            assert_eq!(request.path.as_str(), KEY_ROTATION_PATH);
+            std::future::ready(Ok(self.0.clone())).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_key_rotation_when_there_is_none() {
+        let chat = FixedResponseChat(chat::Response {
+            status: StatusCode::NOT_FOUND,
+            message: None,
+            body: None,
+            headers: Default::default(),
+        });
+        let kt = make_kt(&chat);
+        assert_matches!(kt.fetch_key_rotation().await, Ok(None));
+    }
+
+    /// Answers every distinguished request with the same status, counting
+    /// them.
+    struct DistinguishedStatusChat {
+        status: StatusCode,
+        requests: AtomicUsize,
+    }
+
+    impl DistinguishedStatusChat {
+        fn new(status: StatusCode) -> Self {
+            Self {
+                status,
+                requests: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl UnauthenticatedChat for DistinguishedStatusChat {
+        fn send_unauthenticated(
+            &self,
+            request: chat::Request,
+            timeout: Duration,
+        ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+            assert_eq!(request.path.as_str(), format!("{DISTINGUISHED_PATH}?"));
+            assert!(timeout <= Config::AVAILABILITY_PROBE_TIMEOUT);
+            self.requests.fetch_add(1, atomic::Ordering::Relaxed);
+            std::future::ready(Ok(chat::Response {
+                status: self.status,
+                message: None,
+                body: None,
+                headers: Default::default(),
+            }))
+            .boxed()
+        }
+    }
+
+    #[test_case(StatusCode::OK => matches KtAvailability::Available; "ok")]
+    #[test_case(StatusCode::NOT_FOUND => matches KtAvailability::Disabled; "not found")]
+    #[test_case(StatusCode::NOT_IMPLEMENTED => matches KtAvailability::Disabled; "not implemented")]
+    #[test_case(StatusCode::SERVICE_UNAVAILABLE => matches KtAvailability::Unreachable(Error::RequestFailed(StatusCode::SERVICE_UNAVAILABLE)); "unavailable")]
+    #[test_case(StatusCode::TOO_MANY_REQUESTS => matches KtAvailability::Unreachable(Error::RateLimited { retry_after: None }); "rate limited")]
+    #[tokio::test]
+    async fn availability_from_distinguished_status(status: StatusCode) -> KtAvailability {
+        let chat = DistinguishedStatusChat::new(status);
+        make_kt(&chat).is_available().await
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[test_case(StatusCode::NOT_FOUND, 1; "definitive answers are cached")]
+    #[test_case(StatusCode::SERVICE_UNAVAILABLE, 2; "transient failures are not")]
+    async fn availability_is_cached(status: StatusCode, expected_requests: usize) {
+        let chat = DistinguishedStatusChat::new(status);
+        let kt = Kt::new(
+            make_key_transparency(),
+            &chat,
+            Config::default().with_availability_cache(Arc::new(KtAvailabilityCache::new(
+                Duration::from_secs(60),
+            ))),
+        );
+
+        _ = kt.is_available().await;
+        _ = kt.is_available().await;
+        assert_eq!(
+            chat.requests.load(atomic::Ordering::Relaxed),
+            expected_requests
+        );
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        _ = kt.is_available().await;
+        assert_eq!(
+            chat.requests.load(atomic::Ordering::Relaxed),
+            expected_requests + 1
+        );
+    }
+
+    fn enveloped_response(message: &impl Message) -> chat::Response {
+        let envelope = serde_json::json!({
+            "serializedResponse": BASE64_STANDARD_NO_PAD.encode(message.encode_to_vec()),
+        });
+        chat::Response {
+            status: StatusCode::OK,
+            message: None,
+            body: Some(envelope.to_string().into_bytes().into()),
+            headers: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn forged_key_rotation_is_not_applied() {
+        let document = SignedKeyRotation {
+            rotation: libsignal_keytrans::KeyRotation {
+                key: libsignal_keytrans::key_rotation::Key::Signing.into(),
+                new_key: vec![0x42; 32],
+                counter: 1,
+                expires_at: u64::MAX,
+            }
+            .encode_to_vec(),
+            signature: vec![0; 64],
+        };
+        let chat = FixedResponseChat(enveloped_response(&document));
+        let mut kt = make_kt(&chat);
+
+        let fetched = kt
+            .fetch_key_rotation()
+            .await
+            .expect("can fetch")
+            .expect("has a rotation");
+        assert_eq!(fetched, document);
+
+        let mut rotations = KeyRotations::default();
+        assert_matches!(
+            kt.apply_key_rotation(&mut rotations, &fetched),
+            Err(Error::KeyRotationRejected(
+                KeyRotationError::InvalidSignature(RotatableKey::Signing)
+            ))
+        );
+        assert_eq!(rotations, KeyRotations::default());
+        assert_eq!(
+            kt.inner.config.signature_key,
+            make_key_transparency().config.signature_key
+        );
+    }
+
+    #[tokio::test]
+    async fn valid_key_rotation_is_fetched_applied_and_reloaded() {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
+        let new_key = libsignal_keytrans::VerifyingKey::from_bytes(
+            crate::env::KEYTRANS_AUDITOR_KEY_MATERIAL_STAGING,
+        )
+        .expect("valid key");
+        let document = log.signing_key_rotation(&new_key, 1, now + Duration::from_secs(60));
+        let chat = FixedResponseChat(enveloped_response(&document));
+        let mut kt = make_fake_log_kt(&log, &chat, now);
+        let mut rotations = KeyRotations::default();
+
+        assert_matches!(
+            kt.update_key_rotations(&mut rotations).await,
+            Ok(Some(RotatableKey::Signing))
+        );
+        assert_eq!(kt.inner.config.signature_key, new_key);
+        assert_eq!(
+            rotations.signing,
+            Some(libsignal_keytrans::RotatedKey {
+                counter: 1,
+                key: new_key
+            })
+        );
+
+        // The server keeps offering the same rotation, which is recognized
+        // rather than rejected for being signed by the old key.
+        assert_matches!(kt.update_key_rotations(&mut rotations).await, Ok(None));
+
+        // Once persisted, the rotation is installed when the config is loaded.
+        let stored = libsignal_keytrans::StoredKeyRotations::from(rotations);
+        let reloaded = KeyRotations::try_from(stored).expect("valid");
+        let config = crate::env::STAGING
+            .keytrans_config
+            .expect("has keytrans config")
+            .load(&reloaded);
+        assert_eq!(config.signature_key, new_key);
+    }
+
+    #[test_case(UNSUPPORTED_SEARCH_KEY_STATUS, r#"{"code":"UNSUPPORTED_SEARCH_KEY","searchKey":"e164"}"# => Some(AccountDataField::E164); "e164")]
+    #[test_case(UNSUPPORTED_SEARCH_KEY_STATUS, r#"{"code":"UNSUPPORTED_SEARCH_KEY","searchKey":"usernameHash"}"# => None; "not requested")]
+    #[test_case(UNSUPPORTED_SEARCH_KEY_STATUS, r#"{"code":"SOMETHING_ELSE","searchKey":"e164"}"# => None; "other code")]
+    #[test_case(UNSUPPORTED_SEARCH_KEY_STATUS, "not json" => None; "bad body")]
+    #[test_case(StatusCode::BAD_REQUEST, r#"{"code":"UNSUPPORTED_SEARCH_KEY","searchKey":"e164"}"# => None; "other status")]
+    fn recognizes_unsupported_search_key(
+        status: StatusCode,
+        body: &str,
+    ) -> Option<AccountDataField> {
+        let response = chat::Response {
+            status,
+            message: None,
+            body: Some(Bytes::copy_from_slice(body.as_bytes())),
+            headers: Default::default(),
+        };
+        unsupported_search_key(&response, true, false)
+    }
+
+    #[derive(Default)]
+    struct InMemoryPinStore(Mutex<HashMap<Aci, [u8; 32]>>);
+
+    impl KtPinStore for InMemoryPinStore {
+        fn pinned_key_hash(&self, aci: &Aci) -> Option<[u8; 32]> {
+            self.0.lock().expect("not poisoned").get(aci).copied()
+        }
+
+        fn set_pinned_key_hash(&self, aci: &Aci, key_hash: [u8; 32]) {
+            self.0.lock().expect("not poisoned").insert(*aci, key_hash);
+        }
+    }
+
+    #[tokio::test]
+    async fn search_pins_identity_key_on_first_use() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let search = |store: Arc<InMemoryPinStore>| {
+            let kt = kt_at_search_time(&chat, Config::default().with_pin_store(store));
+            async move {
+                kt.search(
+                    &test_account::aci(),
+                    &test_account::aci_identity_key(),
+                    None,
+                    None,
+                    None,
+                    &test_distinguished_tree(),
+                )
+                .await
+            }
+        };
+        let expected_hash = pinned_key_hash(&IdentityKey::new(test_account::aci_identity_key()));
+
+        let store = Arc::new(InMemoryPinStore::default());
+        search(store.clone()).await.expect("first search pins");
+        assert_eq!(
+            store.pinned_key_hash(&test_account::aci()),
+            Some(expected_hash)
+        );
+        search(store.clone())
+            .await
+            .expect("same key matches the pin");
+
+        let mismatched_store = Arc::new(InMemoryPinStore::default());
+        mismatched_store.set_pinned_key_hash(&test_account::aci(), [0; 32]);
+        assert_matches!(
+            search(mismatched_store.clone()).await,
+            Err(Error::PinnedKeyMismatch { aci }) if aci == test_account::aci()
+        );
+        assert_eq!(
+            mismatched_store.pinned_key_hash(&test_account::aci()),
+            Some([0; 32]),
+            "pin is not overwritten"
+        );
+    }
+
+    #[tokio::test]
+    #[test_case(false; "saved")]
+    #[test_case(true; "write fails")]
+    async fn search_with_store_persists_account_data(fail_writes: bool) {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let store = Arc::new(InMemoryStateStore {
+            distinguished: Mutex::new(Some(StoredTreeHead::from(test_distinguished_tree()))),
+            fail_writes,
+            ..Default::default()
+        });
+        let kt = kt_at_search_time(&chat, Config::default().with_state_store(store.clone()));
+
+        let result = kt
+            .search_with_store(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                None,
+                None,
+            )
+            .await;
+
+        let stored = store
+            .account_data
+            .lock()
+            .expect("not poisoned")
+            .get(&test_account::aci())
+            .cloned();
+        if fail_writes {
+            assert_matches!(result, Err(Error::StatePersistFailed(_)));
+            assert_eq!(stored, None);
+        } else {
+            let result = result.expect("can search");
+            assert_eq!(stored, Some(result.inner.account_data));
+        }
+    }
+
+    #[tokio::test]
+    async fn operations_with_store_require_a_store() {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let kt = make_kt(&chat);
+        assert_matches!(
+            kt.monitor_with_store(&test_account::aci(), None, None)
+                .await,
+            Err(Error::BadArguments(BadArgumentsReason::MissingStateStore))
+        );
+        assert_matches!(
+            kt.distinguished_with_store().await,
+            Err(Error::BadArguments(BadArgumentsReason::MissingStateStore))
+        );
+    }
+
+    #[tokio::test]
+    async fn monitor_with_store_requires_stored_account_data() {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let kt = Kt {
+            config: Config::default().with_state_store(Arc::new(InMemoryStateStore::default())),
+            ..make_kt(&chat)
+        };
+        assert_matches!(
+            kt.monitor_with_store(&test_account::aci(), None, None)
+                .await,
+            Err(Error::BadArguments(BadArgumentsReason::MissingAccountData))
+        );
+    }
+
+    #[tokio::test]
+    #[test_case(false; "saved")]
+    #[test_case(true; "write fails")]
+    async fn monitor_with_store_persists_account_data(fail_writes: bool) {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
+        let aci = test_account::aci();
+        let account_data = AccountData {
+            aci: log.monitoring_data(&aci.as_search_key()),
+            e164: None,
+            username_hash: None,
+            last_tree_head: log.tree_head(),
+        };
+        let store = Arc::new(InMemoryStateStore {
+            account_data: Mutex::new(HashMap::from([(aci, account_data.clone().into())])),
+            distinguished: Mutex::new(Some(log.tree_head().into())),
+            fail_writes,
+        });
+
+        let server = FakeChatServer::new();
+        respond_with_serialized(
+            &server,
+            MONITOR_PATH,
+            &ChatMonitorResponse {
+                tree_head: Some(log.full_tree_head()),
+                aci: Some(MonitorProof { steps: vec![] }),
+                username_hash: None,
+                e164: None,
+                inclusion: log.monitor_inclusion_proof(),
+            }
+            .encode_to_vec(),
+        );
+        let chat = make_fake_chat(&server);
+        let kt = Kt {
+            config: Config::default()
+                .with_clock(Arc::new(now))
+                .with_state_store(store.clone()),
+            ..make_fake_log_kt(&log, &chat, now)
+        };
+
+        let result = kt.monitor_with_store(&aci, None, None).await;
+
+        let stored = store
+            .account_data
+            .lock()
+            .expect("not poisoned")
+            .get(&aci)
+            .cloned()
+            .expect("still stored");
+        if fail_writes {
+            // The write was attempted, and its failure wasn't swallowed.
+            assert_matches!(result, Err(Error::StatePersistFailed(_)));
+            assert_eq!(stored, StoredAccountData::from(account_data));
+        } else {
+            let result = result.expect("can monitor");
+            assert!(!result.skipped);
+            assert_eq!(stored, result.account_data.into_inner().into());
+        }
+        assert_eq!(server.received_requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn monitor_with_store_does_not_write_when_skipped() {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let account_data = test_account_data_with_search_keys();
+        let store = Arc::new(InMemoryStateStore {
+            account_data: Mutex::new(HashMap::from([(
+                test_account::aci(),
+                account_data.clone().into(),
+            )])),
+            distinguished: Mutex::new(Some(account_data.last_tree_head.clone().into())),
+            // Any write would fail the monitor.
+            fail_writes: true,
+        });
+        let kt = Kt {
+            config: Config::default()
+                .with_clock(Arc::new(tree_head_timestamp(
+                    &account_data.last_tree_head.0,
+                )))
+                .with_monitor_skip_window(Duration::from_secs(60))
+                .with_state_store(store.clone()),
+            ..make_kt(&chat)
+        };
+
+        let result = kt
+            .monitor_with_store(
+                &test_account::aci(),
+                Some(test_account::PHONE_NUMBER),
+                Some(test_account::username_hash()),
+            )
+            .await
+            .expect("skipped without writing");
+
+        assert!(result.skipped);
+        assert_eq!(result.consistency, None);
+        assert_eq!(result.account_data.into_inner(), account_data);
+        assert!(server.received_requests().is_empty());
+    }
+
+    #[tokio::test]
+    #[test_case(false, false; "first fetch")]
+    #[test_case(true, false; "update")]
+    #[test_case(false, true; "write fails")]
+    async fn distinguished_with_store_persists_tree_head(have_stored: bool, fail_writes: bool) {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
+        let server = FakeChatServer::new();
+        respond_with_serialized(
+            &server,
+            DISTINGUISHED_PATH,
+            &ChatDistinguishedResponse {
+                tree_head: Some(log.full_tree_head()),
+                distinguished: Some(log.search_response()),
+            }
+            .encode_to_vec(),
+        );
+        let chat = make_fake_chat(&server);
+        let store = Arc::new(InMemoryStateStore {
+            distinguished: Mutex::new(have_stored.then(|| log.tree_head().into())),
+            fail_writes,
+            ..Default::default()
+        });
+        let kt = Kt {
+            config: Config::default()
+                .with_clock(Arc::new(now))
+                .with_state_store(store.clone()),
+            ..make_fake_log_kt(&log, &chat, now)
+        };
+
+        let result = kt.distinguished_with_store().await;
+
+        let stored = store.distinguished.lock().expect("not poisoned").clone();
+        if fail_writes {
+            assert_matches!(result, Err(Error::StatePersistFailed(_)));
+            assert_eq!(stored, None);
+        } else {
+            let result = result.expect("can fetch distinguished");
+            assert_eq!(stored, Some(result.state_update.into_stored()));
+        }
+    }
+
+    #[test]
+    fn auditor_view_requires_third_party_auditing() {
+        let tree_head = |tree_size| TreeHead {
+            tree_size,
+            timestamp: 1_700_000_000_000,
+            signature: vec![],
+        };
+        let full_tree_head = FullTreeHead {
+            tree_head: Some(tree_head(1000)),
+            auditor_tree_head: Some(AuditorTreeHead {
+                tree_head: Some(tree_head(990)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            AuditorView::new(&DeploymentMode::ContactMonitoring, &full_tree_head),
+            None
+        );
+        let auditor_key = make_key_transparency().config.signature_key;
+        assert_eq!(
+            AuditorView::new(
+                &DeploymentMode::ThirdPartyAuditing(auditor_key),
+                &full_tree_head
+            ),
+            Some(AuditorView {
+                lag: 10,
+                timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_000),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn search_reports_proof_metrics() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let reported = Arc::new(std::sync::Mutex::new(vec![]));
+        let kt = kt_at_search_time(
+            &chat,
+            Config::default().with_proof_metrics_callback({
+                let reported = reported.clone();
+                Arc::new(move |operation, metrics| {
+                    reported
+                        .lock()
+                        .expect("not poisoned")
+                        .push((operation, metrics.clone()))
+                })
+            }),
+        );
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await
+            .expect("can perform search");
+
+        let response = ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE).expect("valid response");
+        let tree_head = response.tree_head.as_ref().expect("has tree head");
+        let expected_inclusion_hashes = [&response.aci, &response.e164, &response.username_hash]
+            .into_iter()
+            .flatten()
+            .filter_map(|r| r.search.as_ref())
+            .map(|search| search.inclusion.len())
+            .sum::<usize>();
+        assert_ne!(expected_inclusion_hashes, 0);
+
+        let metrics = &result.inner.proof_metrics;
+        assert_eq!(metrics.last_consistency_hashes, tree_head.last.len());
+        assert_eq!(
+            metrics.distinguished_consistency_hashes,
+            tree_head.distinguished.len()
+        );
+        assert_eq!(metrics.inclusion_hashes, expected_inclusion_hashes);
+
+        assert_eq!(
+            *reported.lock().expect("not poisoned"),
+            [(KtOperation::Search, metrics.clone())]
+        );
+    }
+
+    #[tokio::test]
+    async fn shared_kt_can_be_cloned_into_tasks() {
+        let server = FakeChatServer::new();
+        let kt = Kt::new_shared(
+            make_key_transparency(),
+            Arc::new(make_fake_chat(&server)),
+            Config::default(),
+        );
+
+        let tasks =
+            [kt.clone(), kt].map(|kt| tokio::spawn(async move { kt.distinguished(None).await }));
+        for task in tasks {
+            assert_matches!(
+                task.await.expect("task completed"),
+                Err(Error::RequestFailed(StatusCode::NOT_FOUND))
+            );
+        }
+        assert_eq!(server.received_requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn authenticated_search_omits_unidentified_access_key() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let unauth_chat = make_fake_chat(&server);
+        let auth_chat = make_fake_auth_chat(&server);
+
+        for kt in [
+            kt_at_search_time(&unauth_chat, Config::default()),
+            Kt {
+                chat: KtChat::Authenticated(Arc::new(&auth_chat)),
+                ..kt_at_search_time(&unauth_chat, Config::default())
+            },
+        ] {
+            kt.search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await
+            .expect("can perform search");
+        }
+
+        let requests = server.received_requests();
+        let [unauth_body, auth_body] =
+            <[_; 2]>::try_from(requests)
+                .expect("two requests")
+                .map(|request| {
+                    serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(
+                        request.body(),
+                    )
+                    .expect("JSON body")
+                });
+
+        assert!(unauth_body.contains_key("unidentifiedAccessKey"));
+        assert!(!auth_body.contains_key("unidentifiedAccessKey"));
+
+        let mut unauth_body_without_uak = unauth_body;
+        unauth_body_without_uak.remove("unidentifiedAccessKey");
+        assert_eq!(unauth_body_without_uak, auth_body);
+    }
+
+    #[tokio::test]
+    async fn lookup_fetches_identity_key() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let kt = kt_at_search_time(&chat, Config::default());
+
+        let result = kt
+            .lookup(
+                &test_account::aci(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await
+            .expect("can perform lookup");
+
+        assert_eq!(
+            result.inner.aci_identity_key.public_key(),
+            &test_account::aci_identity_key()
+        );
+
+        let requests = server.received_requests();
+        assert_matches!(&requests[..], [request] => {
+            let body: serde_json::Value =
+                serde_json::from_slice(request.body()).expect("JSON body");
+            assert_eq!(body.get("aci"), Some(&test_account::aci().as_chat_value().into()));
+            assert_eq!(body.get("aciIdentityKey"), None);
+        });
+    }
+
+    #[tokio::test]
+    async fn search_rejects_different_identity_key() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let kt = kt_at_search_time(&chat, Config::default());
+        // The Curve25519 base point; any valid key other than the account's.
+        let other_key = PublicKey::deserialize(&hex!(
+            "050900000000000000000000000000000000000000000000000000000000000000"
+        ))
+        .expect("valid key");
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &other_key,
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await;
+
+        assert_matches!(result, Err(Error::InvalidResponse(_)));
+    }
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[tokio::test]
+    #[test_case(SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT - DAY; "from the future")]
+    #[test_case(SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT + 2 * DAY; "too old")]
+    async fn search_rejects_tree_head_out_of_range(verify_at: SystemTime) {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let kt = Kt {
+            config: Config::default().with_clock(Arc::new(verify_at)),
+            ..make_kt(&chat)
+        };
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await;
+
+        assert_matches!(result, Err(Error::TreeHeadOutOfRange { now, .. }) if now == verify_at);
+    }
+
+    /// Records the server timestamps on the responses it passes on, like the
+    /// bridge's chat connections do.
+    struct ServerTimeRecordingChat<'a> {
+        inner: &'a KtUnauthChatConnection,
+        estimator: Arc<ServerTimeEstimator>,
+        received_at: SystemTime,
+    }
+
+    impl UnauthenticatedChat for ServerTimeRecordingChat<'_> {
+        fn send_unauthenticated(
+            &self,
+            request: chat::Request,
+            timeout: Duration,
+        ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+            async move {
+                let response = self.inner.send_unauthenticated(request, timeout).await?;
+                self.estimator
+                    .record_response(&response.headers, self.received_at);
+                Ok(response)
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    #[test_case(CHAT_SEARCH_RESPONSE_VALID_AT, 30 * DAY, true; "valid, server far ahead")]
+    #[test_case(CHAT_SEARCH_RESPONSE_VALID_AT, 30 * DAY, false; "valid, server far behind")]
+    #[test_case(CHAT_SEARCH_RESPONSE_VALID_AT + 2 * DAY, 2 * DAY, false; "too old, server behind")]
+    #[test_case(CHAT_SEARCH_RESPONSE_VALID_AT - DAY, DAY, true; "from the future, server ahead")]
+    async fn server_timestamp_cannot_change_search_outcome(
+        valid_at: Duration,
+        server_offset: Duration,
+        server_ahead: bool,
+    ) {
+        let verify_at = SystemTime::UNIX_EPOCH + valid_at;
+        let server_time = if server_ahead {
+            verify_at + server_offset
+        } else {
+            verify_at - server_offset
+        };
+        let server = FakeChatServer::new();
+        let server_millis = server_time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("after the epoch")
+            .as_millis();
+        server.respond(
+            SEARCH_PATH,
+            CannedResponse::json(&serde_json::json!({
+                "serializedResponse": BASE64_STANDARD_NO_PAD.encode(CHAT_SEARCH_RESPONSE),
+            }))
+            .with_header(env::TIMESTAMP_HEADER_NAME, server_millis.to_string()),
+        );
+        let chat = make_fake_chat(&server);
+        let estimator = Arc::new(ServerTimeEstimator::new());
+        let chat = ServerTimeRecordingChat {
+            inner: &chat,
+            estimator: estimator.clone(),
+            received_at: verify_at,
+        };
+        let search = |config: Config| {
+            let chat = &chat;
+            async move {
+                Kt {
+                    config,
+                    ..make_kt(chat)
+                }
+                .search(
+                    &test_account::aci(),
+                    &test_account::aci_identity_key(),
+                    Some(test_account::e164_search_key()),
+                    Some(test_account::username_hash()),
+                    Some(test_account_data()),
+                    &test_distinguished_tree(),
+                )
+                .await
+                .map(|_| ())
+            }
+        };
+
+        let expected = search(Config::default().with_clock(Arc::new(verify_at))).await;
+        assert_eq!(
+            estimator.offset_millis().map(i64::unsigned_abs),
+            Some(server_offset.as_millis().try_into().expect("fits")),
+        );
+
+        let result = search(Config::default().with_clock(Arc::new(
+            BoundedServerClock::with_local_clock(estimator, verify_at),
+        )))
+        .await;
+        match (expected, result) {
+            (Ok(()), Ok(())) => {}
+            (Err(Error::TreeHeadOutOfRange { .. }), Err(Error::TreeHeadOutOfRange { .. })) => {}
+            (expected, result) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn search_rejects_stale_view() {
+        let server = FakeChatServer::new();
+        respond_to_search(&server, CHAT_SEARCH_RESPONSE);
+        let chat = make_fake_chat(&server);
+        let kt = kt_at_search_time(&chat, Config::default());
+
+        let response = ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE).expect("valid response");
+        let search_head = response
+            .tree_head
+            .and_then(|head| head.tree_head)
+            .expect("has tree head");
+        // A distinguished tree head well past the one the search is answered
+        // from, as if the server were hiding the latest part of the log.
+        let (mut distinguished, root) = test_distinguished_tree();
+        distinguished.tree_size = search_head.tree_size + 1000;
+        distinguished.timestamp = search_head.timestamp + 2 * 60 * 60 * 1000;
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &(distinguished, root),
+            )
+            .await;
+
+        assert_matches!(
+            result,
+            Err(Error::StaleView { search_size, distinguished_size })
+                if search_size == search_head.tree_size
+                    && distinguished_size == search_head.tree_size + 1000
+        );
+    }
+
+    enum Doctoring {
+        FlipSignatureBit,
+        ChangeDistinguishedRoot,
+        PadConsistencyProof,
+        VerifyMuchLater,
+    }
+
+    #[tokio::test]
+    #[test_case(Doctoring::FlipSignatureBit, VerificationCategory::SignatureInvalid; "signature")]
+    #[test_case(Doctoring::ChangeDistinguishedRoot, VerificationCategory::ConsistencyViolation; "consistency")]
+    #[test_case(Doctoring::PadConsistencyProof, VerificationCategory::ProofMalformed; "malformed")]
+    #[test_case(Doctoring::VerifyMuchLater, VerificationCategory::Stale; "stale")]
+    async fn doctored_search_response_is_categorized(
+        doctoring: Doctoring,
+        expected: VerificationCategory,
+    ) {
+        let mut response = libsignal_keytrans::ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE)
+            .expect("valid response");
+        let mut distinguished = test_distinguished_tree();
+        let mut verify_at = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+
+        let full_tree_head = response.tree_head.as_mut().expect("has tree head");
+        match doctoring {
+            Doctoring::FlipSignatureBit => {
+                full_tree_head
+                    .tree_head
+                    .as_mut()
+                    .expect("has tree head")
+                    .signature[0] ^= 1;
+            }
+            Doctoring::ChangeDistinguishedRoot => distinguished.1[0] ^= 1,
+            Doctoring::PadConsistencyProof => {
+                full_tree_head.last.push(vec![0; 32]);
+            }
+            Doctoring::VerifyMuchLater => verify_at += Duration::from_secs(7 * 24 * 60 * 60),
+        }
+
+        let server = FakeChatServer::new();
+        respond_to_search(&server, &response.encode_to_vec());
+        let chat = make_fake_chat(&server);
+        let reported = Arc::new(Mutex::new(vec![]));
+        let kt = Kt {
+            config: Config::default()
+                .with_clock(Arc::new(verify_at))
+                // Leave the freshness check to verification.
+                .with_tree_head_time_window(Duration::MAX, Duration::MAX)
+                .with_consistency_violation_callback({
+                    let reported = reported.clone();
+                    Arc::new(move |error| {
+                        reported.lock().unwrap().push(error.verification_category())
+                    })
+                }),
+            ..make_kt(&chat)
+        };
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &distinguished,
+            )
+            .await;
+
+        let error = result.expect_err("doctored response should fail verification");
+        assert_eq!(error.verification_category(), Some(expected));
+        if expected == VerificationCategory::ProofMalformed {
+            // A malformed proof can't be told apart from a problem with one
+            // key's proof, so the first key verified is blamed.
+            assert_matches!(
+                error,
+                Error::LegVerificationFailed {
+                    leg: SearchKeyKind::Aci,
+                    ..
+                }
+            );
+        } else {
+            // The tree head is shared, so no key is blamed.
+            assert_matches!(error, Error::VerificationFailed(_));
+        }
+
+        let expected_reports = if expected == VerificationCategory::ConsistencyViolation {
+            vec![Some(expected)]
+        } else {
+            vec![]
+        };
+        assert_eq!(*reported.lock().unwrap(), expected_reports);
+    }
+
+    #[tokio::test]
+    #[test_case(|r| r.aci.as_mut().unwrap().vrf_proof[0] ^= 1, SearchKeyKind::Aci; "ACI")]
+    #[test_case(|r| r.e164.as_mut().unwrap().vrf_proof[0] ^= 1, SearchKeyKind::E164; "E.164")]
+    #[test_case(|r| r.username_hash.as_mut().unwrap().vrf_proof[0] ^= 1, SearchKeyKind::UsernameHash; "username hash")]
+    async fn doctored_search_leg_is_identified(
+        doctor: fn(&mut ChatSearchResponse),
+        expected_leg: SearchKeyKind,
+    ) {
+        let mut response =
+            ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE).expect("valid response");
+        doctor(&mut response);
+
+        let server = FakeChatServer::new();
+        respond_to_search(&server, &response.encode_to_vec());
+        let chat = make_fake_chat(&server);
+        let kt = kt_at_search_time(&chat, Config::default());
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                Some(test_account::e164_search_key()),
+                Some(test_account::username_hash()),
+                Some(test_account_data()),
+                &test_distinguished_tree(),
+            )
+            .await;
+
+        assert_matches!(
+            result,
+            Err(Error::LegVerificationFailed { leg, .. }) if leg == expected_leg
+        );
+    }
+
+    #[test_case(|r| r.tree_head.as_mut().unwrap().last.insert(0, vec![0; 31]), "tree_head.last[0] is 31 bytes"; "short consistency hash")]
+    #[test_case(|r| r.tree_head.as_mut().unwrap().distinguished = vec![vec![0; 32]; MAX_PROOF_HASHES + 1], "tree_head.distinguished has 4097 entries"; "too many hashes")]
+    #[test_case(|r| r.aci.as_mut().unwrap().search.as_mut().unwrap().steps[0].commitment.push(0), "aci.search.steps[0].commitment is 33 bytes"; "long commitment")]
+    #[test_case(|r| r.e164.as_mut().unwrap().search.as_mut().unwrap().steps[0].commitment.truncate(16), "e164.search.steps[0].commitment is 16 bytes"; "short commitment")]
+    fn malformed_proof_fields_are_rejected_before_verification(
+        doctor: fn(&mut ChatSearchResponse),
+        expected_message: &str,
+    ) {
+        let mut response =
+            ChatSearchResponse::decode(CHAT_SEARCH_RESPONSE).expect("valid response");
+        doctor(&mut response);
+
+        let result = TypedSearchResponse::from_untyped(true, true, response);
+        assert_matches!(
+            result,
+            Err(Error::InvalidResponse(message)) if message.starts_with(expected_message),
+            "expected {expected_message:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn search_after_deadline_is_not_sent() {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let kt = Kt {
+            config: Config::default().with_deadline(SystemTime::now() - Duration::from_secs(1)),
+            ..make_kt(&chat)
+        };
+
+        let result = kt
+            .search(
+                &test_account::aci(),
+                &test_account::aci_identity_key(),
+                None,
+                None,
+                None,
+                &test_distinguished_tree(),
+            )
+            .await;
+
+        assert_matches!(
+            result,
+            Err(Error::ChatSendError(
+                chat::SendError::RequestTimedOut { .. }
+            ))
+        );
+        assert_eq!(server.received_requests(), vec![]);
+    }
+
+    #[test]
+    fn config_for_env_uses_environment_request_timeout() {
+        let staging = env::STAGING.keytrans_config.expect("staging has KT");
+        assert_eq!(
+            Config::for_env(&env::STAGING)
+                .request_timeout()
+                .expect("no deadline"),
+            staging.request_timeout
+        );
+        // Prod doesn't have key transparency yet.
+        assert_eq!(
+            Config::for_env(&env::PROD)
+                .request_timeout()
+                .expect("no deadline"),
+            Config::DEFAULT_CHAT_TIMEOUT
+        );
+        assert_eq!(Config::DEFAULT_CHAT_TIMEOUT, Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    #[test_case(false; "unknown_distinguished")]
+    #[test_case(true; "known_distinguished")]
+    async fn distinguished_against_fake_server(have_last_distinguished: bool) {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
+        let server = FakeChatServer::new();
+        respond_with_serialized(
+            &server,
+            DISTINGUISHED_PATH,
+            &ChatDistinguishedResponse {
+                tree_head: Some(log.full_tree_head()),
+                distinguished: Some(log.search_response()),
+            }
+            .encode_to_vec(),
+        );
+        let chat = make_fake_chat(&server);
+        let kt = make_fake_log_kt(&log, &chat, now);
+
+        let result = kt
+            .distinguished(have_last_distinguished.then(|| log.tree_head()))
+            .await;
+
+        assert_matches!(result, Ok(DistinguishedResult { state_update: LocalStateUpdate {tree_head, ..}, .. }) => assert_eq!(tree_head, log.tree_head().0));
+    }
+
+    #[tokio::test]
+    #[test_case(false, false; "ACI")]
+    #[test_case(true, false; "ACI + E164")]
+    #[test_case(false, true; "ACI + Username Hash")]
+    #[test_case(true, true; "ACI + E164 + Username Hash")]
+    async fn monitor_permutations_against_fake_server(use_e164: bool, use_username_hash: bool) {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
+
+        let aci = test_account::aci();
+        let e164 = test_account::PHONE_NUMBER;
+        let username_hash = test_account::username_hash();
+
+        let account_data = AccountData {
+            aci: log.monitoring_data(&aci.as_search_key()),
+            e164: use_e164.then(|| log.monitoring_data(&e164.as_search_key())),
+            username_hash: use_username_hash
+                .then(|| log.monitoring_data(username_hash.as_search_key())),
+            last_tree_head: log.tree_head(),
+        };
+
+        let server = FakeChatServer::new();
+        let proof = || MonitorProof { steps: vec![] };
+        respond_with_serialized(
+            &server,
+            MONITOR_PATH,
+            &ChatMonitorResponse {
+                tree_head: Some(log.full_tree_head()),
+                aci: Some(proof()),
+                username_hash: use_username_hash.then(proof),
+                e164: use_e164.then(proof),
+                inclusion: log.monitor_inclusion_proof(),
+            }
+            .encode_to_vec(),
+        );
+        let chat = make_fake_chat(&server);
+        let kt = make_fake_log_kt(&log, &chat, now);
+
+        let result = kt
+            .monitor(
+                &aci,
+                use_e164.then_some(e164),
+                use_username_hash.then_some(username_hash),
+                account_data.clone(),
+                &log.tree_head(),
+            )
+            .await
+            .expect("can monitor");
+
+        // The log hasn't grown, so there's nothing new to record.
+        assert_eq!(result.account_data.into_inner(), account_data);
+        assert_eq!(
+            result.consistency,
+            Some(TreeHeadConsistency {
+                last: ConsistencyCheck::SameSize,
+                distinguished: ConsistencyCheck::SameSize,
+            })
+        );
+        let metrics = result.proof_metrics.expect("response was verified");
+        assert_eq!(
+            (
+                metrics.last_consistency_hashes,
+                metrics.distinguished_consistency_hashes,
+                metrics.inclusion_hashes,
+            ),
+            (0, 0, log.monitor_inclusion_proof().len())
+        );
+        let requests = server.received_requests();
+        assert_matches!(&requests[..], [request] => {
+            assert_eq!(request.path(), MONITOR_PATH);
+        });
+    }
+
+    #[tokio::test]
+    #[test_case(usize::MAX, false; "below threshold")]
+    #[test_case(1, true; "above threshold")]
+    async fn monitor_request_body_is_compressed_above_threshold(
+        threshold: usize,
+        expect_compressed: bool,
+    ) {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
+        let aci = test_account::aci();
+        let account_data = AccountData {
+            aci: log.monitoring_data(&aci.as_search_key()),
+            e164: None,
+            username_hash: None,
+            last_tree_head: log.tree_head(),
+        };
+
+        let server = FakeChatServer::new();
+        respond_with_serialized(
+            &server,
+            MONITOR_PATH,
+            &ChatMonitorResponse {
+                tree_head: Some(log.full_tree_head()),
+                aci: Some(MonitorProof { steps: vec![] }),
+                username_hash: None,
+                e164: None,
+                inclusion: log.monitor_inclusion_proof(),
+            }
+            .encode_to_vec(),
+        );
+        let chat = make_fake_chat_with_gzip_threshold(&server, threshold);
+        let kt = make_fake_log_kt(&log, &chat, now);
+
+        kt.monitor(&aci, None, None, account_data, &log.tree_head())
+            .await
+            .expect("can monitor");
+
+        let requests = server.received_requests();
+        let [request] = &requests[..] else {
+            panic!("expected one request, got {requests:?}");
+        };
+        assert_eq!(
+            request
+                .headers
+                .contains(&"content-encoding: gzip".to_owned()),
+            expect_compressed
         );
+        let mut body = request.body.clone().expect("has a body");
+        if expect_compressed {
+            let mut decompressed = vec![];
+            std::io::Read::read_to_end(
+                &mut flate2::read::GzDecoder::new(&*body),
+                &mut decompressed,
+            )
+            .expect("valid gzip");
+            body = decompressed;
+        }
+        let body: serde_json::Value = serde_json::from_slice(&body).expect("valid JSON");
+        assert!(body.get("aci").is_some(), "{body}");
     }
 
-    #[tokio::test]
-    #[test_case(false; "unknown_distinguished")]
-    #[test_case(true; "known_distinguished")]
-    async fn distinguished_integration_test(have_last_distinguished: bool) {
-        if std::env::var("LIBSIGNAL_TESTING_RUN_NONHERMETIC_TESTS").is_err() {
-            println!("SKIPPED: running integration tests is not enabled");
-            return;
+    /// Answers each monitor request with proofs from `log` for exactly the
+    /// keys it asks about.
+    struct FakeLogMonitorChat<'a> {
+        log: &'a FakeLog,
+        requests: Mutex<Vec<serde_json::Value>>,
+    }
+
+    impl UnauthenticatedChat for FakeLogMonitorChat<'_> {
+        fn send_unauthenticated(
+            &self,
+            request: chat::Request,
+            _timeout: Duration,
+        ) -> BoxFuture<'_, std::result::Result<chat::Response, chat::SendError>> {
+            assert_eq!(request.path.as_str(), MONITOR_PATH);
+            let body: serde_json::Value =
+                serde_json::from_slice(request.body.as_deref().expect("has a body"))
+                    .expect("valid JSON");
+            let proof = || MonitorProof { steps: vec![] };
+            let response = ChatMonitorResponse {
+                tree_head: Some(self.log.full_tree_head()),
+                aci: Some(proof()),
+                e164: body.get("e164").map(|_| proof()),
+                username_hash: body.get("usernameHash").map(|_| proof()),
+                inclusion: self.log.monitor_inclusion_proof(),
+            };
+            self.requests.lock().expect("not poisoned").push(body);
+            std::future::ready(Ok(enveloped_response(&response))).boxed()
         }
+    }
 
-        let chat = make_chat().await;
-        let kt = make_kt(&chat);
+    #[tokio::test]
+    async fn cached_monitor_reports_no_consistency() {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
 
-        let result = kt
-            .distinguished(have_last_distinguished.then_some(test_distinguished_tree()))
-            .await;
+        let aci = test_account::aci();
+        let account_data = AccountData {
+            aci: log.monitoring_data(&aci.as_search_key()),
+            e164: None,
+            username_hash: None,
+            last_tree_head: log.tree_head(),
+        };
+        let tree_head = log.tree_head();
+
+        let chat = FakeLogMonitorChat {
+            log: &log,
+            requests: Default::default(),
+        };
+        let kt = Kt::new(
+            KeyTransparency {
+                config: log.public_config(),
+            },
+            &chat,
+            Config::default()
+                .with_clock(Arc::new(now))
+                .with_monitor_verification_cache(Arc::new(MonitorVerificationCache::new(
+                    nonzero!(4usize),
+                ))),
+        );
+
+        let first = kt
+            .monitor(&aci, None, None, account_data.clone(), &tree_head)
+            .await
+            .expect("can monitor");
+        assert_eq!(
+            first.consistency,
+            Some(TreeHeadConsistency {
+                last: ConsistencyCheck::SameSize,
+                distinguished: ConsistencyCheck::SameSize,
+            })
+        );
+        assert!(first.proof_metrics.is_some());
 
-        assert_matches!(result, Ok( LocalStateUpdate {tree_head, ..}) => assert_ne!(tree_head.tree_size, 0));
+        let second = kt
+            .monitor(&aci, None, None, account_data, &tree_head)
+            .await
+            .expect("can monitor");
+        assert_eq!(chat.requests.lock().expect("not poisoned").len(), 2);
+        assert!(!second.skipped);
+        assert_eq!(second.consistency, None);
+        assert_eq!(second.proof_metrics, None);
+        assert_eq!(second.account_data, first.account_data);
     }
 
     #[tokio::test]
-    #[test_case(false, false; "ACI")]
-    #[test_case(true, false; "ACI + E164")]
-    #[test_case(false, true; "ACI + Username Hash")]
-    #[test_case(true, true; "ACI + E164 + Username Hash")]
-    async fn monitor_permutations_integration_test(use_e164: bool, use_username_hash: bool) {
-        if std::env::var("LIBSIGNAL_TESTING_RUN_NONHERMETIC_TESTS").is_err() {
-            println!("SKIPPED: running integration tests is not enabled");
-            return;
-        }
-        let chat = make_chat().await;
-        let kt = make_kt(&chat);
+    #[test_case(true, false; "E.164 dropped")]
+    #[test_case(false, true; "username hash dropped")]
+    #[test_case(true, true; "both dropped")]
+    async fn monitor_drops_legs_missing_from_response(drop_e164: bool, drop_username_hash: bool) {
+        let now = SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT;
+        let log = fake_distinguished_log(now);
 
         let aci = test_account::aci();
         let e164 = test_account::PHONE_NUMBER;
         let username_hash = test_account::username_hash();
 
-        let account_data = {
-            let mut data = test_account_data();
-            if !use_e164 {
-                data.e164 = None;
-            }
-            if !use_username_hash {
-                data.username_hash = None;
-            }
-            data
+        let account_data = AccountData {
+            aci: log.monitoring_data(&aci.as_search_key()),
+            e164: Some(log.monitoring_data(&e164.as_search_key())),
+            username_hash: Some(log.monitoring_data(username_hash.as_search_key())),
+            last_tree_head: log.tree_head(),
         };
 
-        let updated_account_data = kt
+        let server = FakeChatServer::new();
+        let proof = || MonitorProof { steps: vec![] };
+        respond_with_serialized(
+            &server,
+            MONITOR_PATH,
+            &ChatMonitorResponse {
+                tree_head: Some(log.full_tree_head()),
+                aci: Some(proof()),
+                e164: (!drop_e164).then(proof),
+                username_hash: (!drop_username_hash).then(proof),
+                inclusion: log.monitor_inclusion_proof(),
+            }
+            .encode_to_vec(),
+        );
+        let chat = make_fake_chat(&server);
+        let kt = Kt::new(
+            KeyTransparency {
+                config: log.public_config(),
+            },
+            &chat,
+            Config::default()
+                .with_clock(Arc::new(now))
+                .with_dropped_monitor_legs(),
+        );
+
+        let result = kt
             .monitor(
                 &aci,
-                use_e164.then_some(e164),
-                use_username_hash.then_some(username_hash),
+                Some(e164),
+                Some(username_hash),
                 account_data.clone(),
-                &test_distinguished_tree(),
+                &log.tree_head(),
             )
             .await
             .expect("can monitor");
 
-        match Ord::cmp(
-            &updated_account_data.last_tree_head.0.tree_size,
-            &account_data.last_tree_head.0.tree_size,
-        ) {
-            Ordering::Less => panic!("The tree is shrinking"),
-            Ordering::Equal => assert_eq!(&updated_account_data, &account_data),
-            Ordering::Greater => {
-                // verify that the initial position of the ACI in the tree has not changed, at least
-                assert_eq!(&updated_account_data.aci.pos, &account_data.aci.pos)
+        let expected_dropped = [
+            drop_e164.then_some(AccountDataField::E164),
+            drop_username_hash.then_some(AccountDataField::UsernameHash),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<BTreeSet<_>>();
+        assert_eq!(result.dropped_legs, expected_dropped);
+        // What's left still verified, and the dropped keys' data is gone.
+        assert!(result.consistency.is_some());
+        assert_eq!(
+            result.account_data,
+            AccountData {
+                e164: account_data.e164.filter(|_| !drop_e164),
+                username_hash: account_data.username_hash.filter(|_| !drop_username_hash),
+                ..account_data
             }
-        }
+            .into()
+        );
     }
 
-    const CHAT_SEARCH_RESPONSE: &[u8] = include_bytes!("../tests/data/chat_search_response.dat");
-    const CHAT_SEARCH_RESPONSE_VALID_AT: Duration = Duration::from_secs(1740164663);
+    #[test]
+    fn default_max_response_size_fits_recorded_search_response() {
+        assert!(CHAT_SEARCH_RESPONSE.len() * 4 <= Config::DEFAULT_MAX_RESPONSE_SIZE);
+    }
 
     fn test_search_response() -> TypedSearchResponse {
         let chat_search_response =
@@ -1615,7 +6580,10 @@ mod test {
             Some(account_data),
             test_search_response(),
             Some(&test_distinguished_tree()),
+            false,
             valid_at,
+            &OperationDeadline::new(None),
+            Tracer::default(),
         );
 
         assert_matches!(result, Err(Error::InvalidResponse(_)))
@@ -1654,7 +6622,10 @@ mod test {
             Some(account_data),
             search_response,
             Some(&test_distinguished_tree()),
+            false,
             valid_at,
+            &OperationDeadline::new(None),
+            Tracer::default(),
         );
 
         assert_matches!(result, Ok(MaybePartial {missing_fields, ..}) =>
@@ -1662,22 +6633,256 @@ mod test {
         );
     }
 
+    #[cfg(feature = "keytrans-trace")]
+    #[test]
+    fn search_verification_is_traced() {
+        use trace::{TraceEvent, TracedProof, VerificationTrace};
+
+        let aci = test_account::aci();
+        let e164 = test_account::PHONE_NUMBER;
+        let username_hash = test_account::username_hash();
+        let verification_trace = VerificationTrace::new();
+
+        let result = verify_chat_search_response(
+            &make_key_transparency(),
+            &aci,
+            Some(e164),
+            Some(username_hash.clone()),
+            Some(test_account_data()),
+            test_search_response(),
+            Some(&test_distinguished_tree()),
+            false,
+            SystemTime::UNIX_EPOCH + CHAT_SEARCH_RESPONSE_VALID_AT,
+            &OperationDeadline::new(None),
+            Tracer {
+                trace: Some(&verification_trace),
+            },
+        );
+        assert_matches!(result, Ok(_));
+
+        let events = verification_trace.events();
+        let search_tree_size = assert_matches!(
+            &events[..3],
+            [
+                TraceEvent::Started {
+                    operation: KtOperation::Search,
+                    tree_size: Some(tree_size),
+                    key_kinds,
+                    ..
+                },
+                TraceEvent::ProofConsumed {
+                    key: None,
+                    proof: TracedProof::LastConsistency { .. },
+                },
+                TraceEvent::ProofConsumed {
+                    key: None,
+                    proof: TracedProof::DistinguishedConsistency { .. },
+                },
+            ] => {
+                assert_eq!(
+                    key_kinds,
+                    &[SearchKeyKind::Aci, SearchKeyKind::E164, SearchKeyKind::UsernameHash]
+                );
+                *tree_size
+            }
+        );
+
+        let search_keys = [
+            (SearchKeyKind::Aci, aci.as_search_key()),
+            (SearchKeyKind::E164, e164.as_search_key()),
+            (
+                SearchKeyKind::UsernameHash,
+                username_hash.as_search_key().to_vec(),
+            ),
+        ];
+        assert_eq!(events.len(), 3 + 2 * search_keys.len());
+        for ((kind, search_key), events) in search_keys.iter().zip(events[3..].chunks(2)) {
+            assert_matches!(
+                events,
+                [
+                    TraceEvent::ProofConsumed {
+                        key: Some(key),
+                        proof: TracedProof::Search { .. },
+                    },
+                    TraceEvent::RootComputed { tree_size, .. },
+                ] => {
+                    assert_eq!(key, &verification_trace.key(*kind, search_key));
+                    assert_eq!(*tree_size, search_tree_size);
+                }
+            );
+        }
+
+        let json = verification_trace.to_json();
+        for (_kind, search_key) in &search_keys {
+            assert!(!json.contains(&hex::encode(search_key)));
+        }
+        assert!(!json.contains(&e164.to_string()));
+    }
+
+    fn verified_search_value(payload: &[u8]) -> VerifiedSearchResult {
+        let (tree_head, tree_root) = test_distinguished_tree();
+        VerifiedSearchResult {
+            value: [&[SEARCH_VALUE_PREFIX], payload].concat(),
+            state_update: SearchStateUpdate {
+                tree_head,
+                tree_root,
+                monitoring_data: None,
+            },
+            consistency: TreeHeadConsistency {
+                last: ConsistencyCheck::NoBaseline,
+                distinguished: ConsistencyCheck::NoBaseline,
+            },
+        }
+    }
+
+    const OTHER_ACI: Aci = Aci::from_uuid_bytes([0x11; 16]);
+
+    #[test_case(true; "detecting changes")]
+    #[test_case(false; "not detecting changes")]
+    fn username_hash_for_searched_aci(detect_changes: bool) {
+        let aci = test_account::aci();
+        let result = verified_search_value(&aci.service_id_binary());
+        assert_matches!(
+            interpret_username_hash_value(&result, &aci, detect_changes),
+            Ok((Some(found), None)) if found == aci
+        );
+    }
+
+    #[test]
+    fn username_hash_moved_to_other_aci() {
+        let result = verified_search_value(&OTHER_ACI.service_id_binary());
+        assert_matches!(
+            interpret_username_hash_value(&result, &test_account::aci(), true),
+            Ok((None, Some(UsernameChange::Moved { new_aci }))) if new_aci == OTHER_ACI
+        );
+        // Without detection, the other account's ACI is returned as-is.
+        assert_matches!(
+            interpret_username_hash_value(&result, &test_account::aci(), false),
+            Ok((Some(found), None)) if found == OTHER_ACI
+        );
+    }
+
+    #[test]
+    fn username_hash_removed() {
+        let result = verified_search_value(&[]);
+        assert_matches!(
+            interpret_username_hash_value(&result, &test_account::aci(), true),
+            Ok((None, Some(UsernameChange::Removed)))
+        );
+        assert_matches!(
+            interpret_username_hash_value(&result, &test_account::aci(), false),
+            Err(Error::InvalidResponse(_))
+        );
+    }
+
+    #[test_case(test_account::aci().as_search_key(), SearchKeyKind::Aci, &test_account::aci().service_id_binary(); "ACI")]
+    #[test_case(test_account::e164_search_key().e164.as_search_key(), SearchKeyKind::E164, test_account::e164_search_key().e164.to_string().as_bytes(); "E.164")]
+    #[test_case(test_account::username_hash().as_search_key(), SearchKeyKind::UsernameHash, test_account::username_hash().as_ref(); "username hash")]
+    fn search_key_round_trip(search_key: Vec<u8>, kind: SearchKeyKind, rest: &[u8]) {
+        assert!(search_key.starts_with(kind.prefix()));
+        assert_eq!(parse_search_key(&search_key), Some((kind, rest)));
+    }
+
+    #[test_case(b""; "empty")]
+    #[test_case(b"x1234"; "unknown prefix")]
+    fn search_key_without_known_prefix(search_key: &[u8]) {
+        assert_eq!(parse_search_key(search_key), None);
+    }
+
+    #[test]
+    fn username_hash_with_garbage_value_is_still_an_error() {
+        let result = verified_search_value(b"not an ACI");
+        assert_matches!(
+            interpret_username_hash_value(&result, &test_account::aci(), true),
+            Err(Error::InvalidResponse(_))
+        );
+    }
+
+    fn monitor_response_with(e164: bool, username_hash: bool) -> ChatMonitorResponse {
+        ChatMonitorResponse {
+            tree_head: Some(FullTreeHead::default()),
+            aci: Some(MonitorProof::default()),
+            e164: e164.then(MonitorProof::default),
+            username_hash: username_hash.then(MonitorProof::default),
+            inclusion: vec![],
+        }
+    }
+
+    #[test_case(false; "strict")]
+    #[test_case(true; "allowing dropped legs")]
+    fn monitor_response_with_all_requested_legs(allow_dropped_legs: bool) {
+        let response = TypedMonitorResponse::from_untyped(
+            true,
+            true,
+            allow_dropped_legs,
+            monitor_response_with(true, true),
+        )
+        .expect("valid");
+        assert!(response.e164.is_some());
+        assert!(response.username_hash.is_some());
+    }
+
+    #[test_case(true, false; "E.164 dropped")]
+    #[test_case(false, true; "username hash dropped")]
+    #[test_case(false, false; "both dropped")]
+    fn monitor_response_missing_requested_legs(has_e164: bool, has_username_hash: bool) {
+        assert_matches!(
+            TypedMonitorResponse::from_untyped(
+                true,
+                true,
+                false,
+                monitor_response_with(has_e164, has_username_hash),
+            ),
+            Err(Error::InvalidResponse(_))
+        );
+        let response = TypedMonitorResponse::from_untyped(
+            true,
+            true,
+            true,
+            monitor_response_with(has_e164, has_username_hash),
+        )
+        .expect("dropped legs are allowed");
+        assert_eq!(response.e164.is_some(), has_e164);
+        assert_eq!(response.username_hash.is_some(), has_username_hash);
+    }
+
+    #[test_case(false; "strict")]
+    #[test_case(true; "allowing dropped legs")]
+    fn monitor_response_with_unrequested_leg(allow_dropped_legs: bool) {
+        assert_matches!(
+            TypedMonitorResponse::from_untyped(
+                false,
+                true,
+                allow_dropped_legs,
+                monitor_response_with(true, true),
+            ),
+            Err(Error::InvalidResponse(_))
+        );
+    }
+
     struct TestKt {
-        monitor: Arc<Mutex<Option<Result<AccountData>>>>,
+        monitor: Arc<Mutex<Option<Result<MonitorResult>>>>,
         search: Arc<Mutex<Option<Result<MaybePartial<SearchResult>>>>>,
     }
 
     impl TestKt {
-        fn for_monitor(monitor: Result<AccountData>) -> Self {
+        fn for_monitor(monitor: Result<MaybePartial<AccountData>>) -> Self {
             Self {
-                monitor: Arc::new(Mutex::new(Some(monitor))),
+                monitor: Arc::new(Mutex::new(Some(
+                    monitor.map(|account_data| MonitorResult::monitored(account_data, None, None)),
+                ))),
                 search: Arc::new(Mutex::new(None)),
             }
         }
 
-        fn new(monitor: Result<AccountData>, search: Result<MaybePartial<SearchResult>>) -> Self {
+        fn new(
+            monitor: Result<MaybePartial<AccountData>>,
+            search: Result<MaybePartial<SearchResult>>,
+        ) -> Self {
             Self {
-                monitor: Arc::new(Mutex::new(Some(monitor))),
+                monitor: Arc::new(Mutex::new(Some(
+                    monitor.map(|account_data| MonitorResult::monitored(account_data, None, None)),
+                ))),
                 search: Arc::new(Mutex::new(Some(search))),
             }
         }
@@ -1688,7 +6893,7 @@ mod test {
             &self,
             _aci: &Aci,
             _aci_identity_key: &PublicKey,
-            _e164: Option<(E164, Vec<u8>)>,
+            _e164: Option<E164SearchKey>,
             _username_hash: Option<UsernameHash<'_>>,
             _stored_account_data: Option<AccountData>,
             _distinguished_tree_head: &LastTreeHead,
@@ -1702,7 +6907,7 @@ mod test {
             async move { result }
         }
 
-        async fn distinguished(&self, _: Option<LastTreeHead>) -> Result<SearchStateUpdate> {
+        async fn distinguished(&self, _: Option<LastTreeHead>) -> Result<DistinguishedResult> {
             // not used in the tests
             unreachable!()
         }
@@ -1714,7 +6919,7 @@ mod test {
             _username_hash: Option<UsernameHash<'_>>,
             _account_data: AccountData,
             _last_distinguished_tree_head: &LastTreeHead,
-        ) -> impl Future<Output = Result<AccountData>> + Send {
+        ) -> impl Future<Output = Result<MonitorResult>> + Send {
             let result = self
                 .monitor
                 .lock()
@@ -1725,6 +6930,167 @@ mod test {
         }
     }
 
+    #[test_case(None, 60, true => false; "off by default")]
+    #[test_case(Some(120), 0, true => true; "same tree head")]
+    #[test_case(Some(120), 60, true => true; "fresh")]
+    #[test_case(Some(120), 180, true => false; "stale")]
+    #[test_case(Some(120), -60, true => false; "ahead of the distinguished tree head")]
+    #[test_case(Some(120), 60, false => false; "different search keys")]
+    #[tokio::test]
+    async fn monitor_skip_decision(
+        window_secs: Option<u64>,
+        lag_secs: i64,
+        same_search_keys: bool,
+    ) -> bool {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let account_data = test_account_data_with_search_keys();
+        let stored_tree_head = &account_data.last_tree_head.0;
+
+        let mut distinguished_tree_head = account_data.last_tree_head.clone();
+        // The log grows along with the timestamp.
+        distinguished_tree_head.0.timestamp += lag_secs * 1000;
+        distinguished_tree_head.0.tree_size = stored_tree_head
+            .tree_size
+            .checked_add_signed(lag_secs.signum())
+            .expect("valid");
+
+        // The local clock doesn't matter, only the distance between the two
+        // tree heads.
+        let mut config = Config::default().with_clock(Arc::new(
+            tree_head_timestamp(stored_tree_head) + Duration::from_secs(7 * 24 * 60 * 60),
+        ));
+        if let Some(window_secs) = window_secs {
+            config = config.with_monitor_skip_window(Duration::from_secs(window_secs));
+        }
+        let kt = Kt {
+            config,
+            ..make_kt(&chat)
+        };
+
+        kt.can_skip_monitor(
+            &test_account::aci(),
+            same_search_keys.then_some(test_account::PHONE_NUMBER),
+            Some(&test_account::username_hash()),
+            &account_data,
+            &distinguished_tree_head,
+        )
+    }
+
+    #[test_case(true, Some("+18005550100"), true, true => true; "same keys")]
+    #[test_case(false, Some("+18005550100"), true, true => false; "different ACI")]
+    #[test_case(true, Some("+18005550199"), true, true => false; "different E.164")]
+    #[test_case(true, Some("+18005550100"), false, true => false; "different username hash")]
+    #[test_case(true, None, true, true => false; "E.164 not monitored")]
+    #[test_case(true, Some("+18005550100"), true, false => false; "keys not recorded")]
+    #[tokio::test]
+    async fn monitor_skip_requires_the_stored_search_keys(
+        same_aci: bool,
+        e164: Option<&str>,
+        same_username_hash: bool,
+        recorded: bool,
+    ) -> bool {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let account_data = if recorded {
+            test_account_data_with_search_keys()
+        } else {
+            test_account_data()
+        };
+        let kt = Kt {
+            config: Config::default()
+                .with_clock(Arc::new(tree_head_timestamp(
+                    &account_data.last_tree_head.0,
+                )))
+                .with_monitor_skip_window(Duration::from_secs(60)),
+            ..make_kt(&chat)
+        };
+
+        let e164 = e164.map(|e164| e164.parse().expect("valid E.164"));
+        let username_hash = if same_username_hash {
+            test_account::username_hash()
+        } else {
+            UsernameHash::from_slice(&[0; 32])
+        };
+        let aci = if same_aci {
+            test_account::aci()
+        } else {
+            Aci::from_uuid_bytes([0x11; 16])
+        };
+        kt.can_skip_monitor(
+            &aci,
+            e164,
+            Some(&username_hash),
+            &account_data,
+            &account_data.last_tree_head,
+        )
+    }
+
+    #[tokio::test]
+    async fn monitor_rejects_duplicate_monitoring_data_without_a_request() {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let kt = make_kt(&chat);
+        let mut account_data = test_account_data();
+        account_data.username_hash = Some(account_data.aci.clone());
+
+        let result = kt
+            .monitor(
+                &test_account::aci(),
+                Some(test_account::PHONE_NUMBER),
+                Some(test_account::username_hash()),
+                account_data.clone(),
+                &account_data.last_tree_head,
+            )
+            .await;
+
+        assert_matches!(
+            result,
+            Err(Error::BadArguments(
+                BadArgumentsReason::DuplicateMonitoringData
+            ))
+        );
+        assert!(server.received_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn monitor_skips_request_for_fresh_account_data() {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let account_data = test_account_data_with_search_keys();
+        let kt = Kt {
+            config: Config::default()
+                .with_clock(Arc::new(tree_head_timestamp(
+                    &account_data.last_tree_head.0,
+                )))
+                .with_monitor_skip_window(Duration::from_secs(60)),
+            ..make_kt(&chat)
+        };
+
+        let result = kt
+            .monitor(
+                &test_account::aci(),
+                Some(test_account::PHONE_NUMBER),
+                Some(test_account::username_hash()),
+                account_data.clone(),
+                &account_data.last_tree_head,
+            )
+            .await
+            .expect("skipped");
+
+        assert_eq!(
+            result,
+            MonitorResult {
+                account_data: account_data.into(),
+                skipped: true,
+                consistency: None,
+                proof_metrics: None,
+                dropped_legs: BTreeSet::new(),
+            }
+        );
+        assert!(server.received_requests().is_empty());
+    }
+
     #[tokio::test]
     async fn monitor_and_search_monitor_error_is_returned() {
         let kt = TestKt::for_monitor(Err(Error::RequestFailed(StatusCode::EXPECTATION_FAILED)));
@@ -1748,7 +7114,7 @@ mod test {
     async fn monitor_and_search_no_search_needed() {
         let monitor_result = test_account_data();
         // TestKt constructed like this will panic if search is invoked
-        let kt = TestKt::for_monitor(Ok(monitor_result.clone()));
+        let kt = TestKt::for_monitor(Ok(monitor_result.clone().into()));
 
         let actual = monitor_and_search(
             &kt,
@@ -1761,7 +7127,72 @@ mod test {
         )
         .await
         .expect("monitor should succeed");
-        assert_eq!(actual, monitor_result.into());
+        assert_eq!(
+            actual,
+            MonitorResult::monitored(monitor_result.into(), None, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn monitor_and_search_skipped_monitor() {
+        let skipped = MonitorResult {
+            account_data: test_account_data().into(),
+            skipped: true,
+            consistency: None,
+            proof_metrics: None,
+            dropped_legs: BTreeSet::new(),
+        };
+        // TestKt constructed like this will panic if search is invoked
+        let kt = TestKt {
+            monitor: Arc::new(Mutex::new(Some(Ok(skipped.clone())))),
+            search: Arc::new(Mutex::new(None)),
+        };
+
+        let actual = monitor_and_search(
+            &kt,
+            &test_account::aci(),
+            &test_account::aci_identity_key(),
+            Some(test_account::e164_search_key()),
+            Some(test_account::username_hash()),
+            test_account_data(),
+            &test_distinguished_tree(),
+        )
+        .await
+        .expect("monitor should succeed");
+        assert_eq!(actual, skipped);
+    }
+
+    #[tokio::test]
+    async fn monitor_and_search_reports_dropped_legs() {
+        let mut monitor_result = test_account_data();
+        monitor_result.e164 = None;
+        // TestKt constructed like this will panic if search is invoked
+        let kt = TestKt {
+            monitor: Arc::new(Mutex::new(Some(Ok(MonitorResult::monitored(
+                monitor_result.clone().into(),
+                None,
+                None,
+            )
+            .with_dropped_legs([AccountDataField::E164]))))),
+            search: Arc::new(Mutex::new(None)),
+        };
+
+        let actual = monitor_and_search(
+            &kt,
+            &test_account::aci(),
+            &test_account::aci_identity_key(),
+            Some(test_account::e164_search_key()),
+            None,
+            test_account_data(),
+            &test_distinguished_tree(),
+        )
+        .await
+        .expect("monitor should succeed");
+        assert_eq!(actual.account_data, monitor_result.into());
+        assert_eq!(
+            actual.dropped_legs,
+            BTreeSet::from([AccountDataField::E164])
+        );
     }
 
     enum BumpVersionFor {
@@ -1784,7 +7215,7 @@ mod test {
         subject.ptrs.insert(u64::MAX, max_version + 1);
 
         let kt = TestKt::new(
-            Ok(monitor_result.clone()),
+            Ok(monitor_result.clone().into()),
             Err(Error::RequestFailed(StatusCode::EXPECTATION_FAILED)),
         );
 
@@ -1834,9 +7265,19 @@ mod test {
             aci_for_username_hash: None,
             timestamp: SystemTime::now(),
             account_data: search_result_account_data.clone().into(),
+            proof_metrics: Default::default(),
+            auditor_lag: None,
+            auditor_timestamp: None,
+            e164_binding_age: None,
+            username_binding_age: None,
+            username_change: None,
+            consistency: TreeHeadConsistency {
+                last: ConsistencyCheck::Proven,
+                distinguished: ConsistencyCheck::Proven,
+            },
         };
 
-        let kt = TestKt::new(Ok(monitor_result.clone()), Ok(search_result.into()));
+        let kt = TestKt::new(Ok(monitor_result.clone().into()), Ok(search_result.into()));
 
         let updated_account_data = monitor_and_search(
             &kt,
@@ -1852,18 +7293,15 @@ mod test {
 
         assert_eq!(
             search_result_account_data,
-            updated_account_data.into_inner()
+            updated_account_data.account_data.into_inner()
         );
     }
 
     #[tokio::test]
     async fn search_for_deleted_account() {
-        if std::env::var("LIBSIGNAL_TESTING_RUN_NONHERMETIC_TESTS").is_err() {
-            println!("SKIPPED: running integration tests is not enabled");
-            return;
-        }
-
-        let chat = make_chat().await;
+        let server = FakeChatServer::new();
+        server.respond(SEARCH_PATH, CannedResponse::status(StatusCode::FORBIDDEN));
+        let chat = make_fake_chat(&server);
         let kt = make_kt(&chat);
 
         // This ACI belongs to account 18005550102
@@ -1887,12 +7325,9 @@ mod test {
 
     #[tokio::test]
     async fn search_for_account_that_isnt() {
-        if std::env::var("LIBSIGNAL_TESTING_RUN_NONHERMETIC_TESTS").is_err() {
-            println!("SKIPPED: running integration tests is not enabled");
-            return;
-        }
-
-        let chat = make_chat().await;
+        let server = FakeChatServer::new();
+        server.respond(SEARCH_PATH, CannedResponse::status(StatusCode::NOT_FOUND));
+        let chat = make_fake_chat(&server);
         let kt = make_kt(&chat);
 
         let aci = Aci::from(uuid::uuid!("00000000-0000-0000-0000-000000000000"));
@@ -1911,4 +7346,32 @@ mod test {
             .await;
         assert_matches!(result, Err(Error::RequestFailed(StatusCode::NOT_FOUND)));
     }
+
+    #[tokio::test]
+    #[test_case(Some(30) => matches Err(Error::RateLimited { retry_after: Some(delay) }) if delay == RetryDelay::from_secs(30); "with retry-after")]
+    #[test_case(None => matches Err(Error::RateLimited { retry_after: None }); "without retry-after")]
+    async fn rate_limited_search_keeps_retry_after(
+        retry_after_secs: Option<u64>,
+    ) -> Result<MaybePartial<SearchResult>> {
+        let server = FakeChatServer::new();
+        server.respond(
+            SEARCH_PATH,
+            match retry_after_secs {
+                Some(secs) => CannedResponse::retry_later(Duration::from_secs(secs)),
+                None => CannedResponse::status(StatusCode::TOO_MANY_REQUESTS),
+            },
+        );
+        let chat = make_fake_chat(&server);
+        let kt = make_kt(&chat);
+
+        kt.search(
+            &test_account::aci(),
+            &test_account::aci_identity_key(),
+            None,
+            None,
+            None,
+            &test_distinguished_tree(),
+        )
+        .await
+    }
 }
@@ -0,0 +1,175 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Synchronous wrappers for [`Kt`], for callers that don't otherwise use async
+//! code.
+
+use std::future::Future;
+use std::sync::OnceLock;
+
+use libsignal_core::{Aci, E164};
+use libsignal_keytrans::{AccountData, LastTreeHead};
+use libsignal_protocol::PublicKey;
+
+use super::{
+    DistinguishedResult, E164SearchKey, Kt, KtApi as _, MaybePartial, MonitorResult, SearchResult,
+    UsernameHash,
+};
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum Error {
+    /// Blocking key transparency calls can't be made from inside an async runtime
+    InsideAsyncRuntime,
+    /// Failed to start a runtime: {0}
+    RuntimeStartFailed(std::io::Error),
+    /// {0}
+    Kt(#[from] super::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Runs [`Kt`] operations to completion on the calling thread.
+///
+/// Each operation is driven by a current-thread runtime that's created the
+/// first time it's needed.
+///
+/// # Runtime requirements
+///
+/// `BlockingKt` only drives the key transparency operation itself, not the
+/// chat connection it sends requests over. The connection must have been
+/// created on a multi-threaded tokio runtime that keeps running for as long
+/// as the `BlockingKt` is in use, so that its worker threads can make progress
+/// while the calling thread is blocked. A connection created on a
+/// current-thread runtime is only driven while that runtime is itself blocked
+/// on something, which it can't be while the calling thread is blocked here.
+/// Calls over such a connection never complete.
+///
+/// None of the methods can be called from within a tokio runtime; they fail
+/// with [`Error::InsideAsyncRuntime`] rather than blocking the runtime's
+/// thread. For the same reason, a `BlockingKt` that has already been used
+/// shouldn't be dropped inside a runtime either.
+pub struct BlockingKt<'a> {
+    kt: Kt<'a>,
+    runtime: OnceLock<tokio::runtime::Runtime>,
+}
+
+impl<'a> BlockingKt<'a> {
+    pub fn new(kt: Kt<'a>) -> Self {
+        Self {
+            kt,
+            runtime: OnceLock::new(),
+        }
+    }
+
+    /// See [`KtApi::search`](super::KtApi::search).
+    pub fn search(
+        &self,
+        aci: &Aci,
+        aci_identity_key: &PublicKey,
+        e164: Option<E164SearchKey>,
+        username_hash: Option<UsernameHash<'_>>,
+        stored_account_data: Option<AccountData>,
+        distinguished_tree_head: &LastTreeHead,
+    ) -> Result<MaybePartial<SearchResult>> {
+        self.block_on(self.kt.search(
+            aci,
+            aci_identity_key,
+            e164,
+            username_hash,
+            stored_account_data,
+            distinguished_tree_head,
+        ))?
+        .map_err(Into::into)
+    }
+
+    /// See [`KtApi::distinguished`](super::KtApi::distinguished).
+    pub fn distinguished(
+        &self,
+        last_distinguished: Option<LastTreeHead>,
+    ) -> Result<DistinguishedResult> {
+        self.block_on(self.kt.distinguished(last_distinguished))?
+            .map_err(Into::into)
+    }
+
+    /// See [`KtApi::monitor`](super::KtApi::monitor).
+    pub fn monitor(
+        &self,
+        aci: &Aci,
+        e164: Option<E164>,
+        username_hash: Option<UsernameHash<'_>>,
+        account_data: AccountData,
+        last_distinguished_tree_head: &LastTreeHead,
+    ) -> Result<MonitorResult> {
+        self.block_on(self.kt.monitor(
+            aci,
+            e164,
+            username_hash,
+            account_data,
+            last_distinguished_tree_head,
+        ))?
+        .map_err(Into::into)
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> Result<F::Output> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(Error::InsideAsyncRuntime);
+        }
+        let runtime = match self.runtime.get() {
+            Some(runtime) => runtime,
+            None => {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(Error::RuntimeStartFailed)?;
+                // If another thread got there first, use its runtime instead.
+                self.runtime.get_or_init(|| runtime)
+            }
+        };
+        Ok(runtime.block_on(future))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use http::StatusCode;
+
+    use super::*;
+    use crate::chat::fake::server::FakeChatServer;
+    use crate::keytrans::test_support::{make_fake_chat, make_kt};
+
+    #[tokio::test]
+    async fn refuses_to_block_inside_runtime() {
+        let server = FakeChatServer::new();
+        let chat = make_fake_chat(&server);
+        let kt = BlockingKt::new(make_kt(&chat));
+
+        assert_matches!(kt.distinguished(None), Err(Error::InsideAsyncRuntime));
+        assert!(server.received_requests().is_empty());
+    }
+
+    #[test]
+    fn blocks_until_response() {
+        let chat_runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("can start runtime");
+        let server = FakeChatServer::new();
+        let chat = {
+            let _guard = chat_runtime.enter();
+            make_fake_chat(&server)
+        };
+        let kt = BlockingKt::new(make_kt(&chat));
+
+        assert_matches!(
+            kt.distinguished(None),
+            Err(Error::Kt(super::super::Error::RequestFailed(
+                StatusCode::NOT_FOUND
+            )))
+        );
+        assert_eq!(server.received_requests().len(), 1);
+    }
+}
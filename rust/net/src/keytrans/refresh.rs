@@ -0,0 +1,617 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Keeps the distinguished tree head up to date in the background.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use libsignal_keytrans::{LastTreeHead, LocalStateUpdate};
+use libsignal_net_infra::errors::RetryDelay;
+use rand::Rng;
+
+use super::{into_last_tree_head, DistinguishedResult, Error, KtApi, KtStateStore, Result};
+use crate::connect_state::OutageWatch;
+
+/// How often a [`DistinguishedRefresher`] fetches a new distinguished tree
+/// head.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RefreshSchedule {
+    /// The delay between refreshes.
+    pub interval: Duration,
+    /// The largest fraction, between 0 and 1, by which any delay is randomly
+    /// shortened.
+    pub jitter: f64,
+    /// When the server keeps rate limiting refreshes, the delay doubles until
+    /// it reaches this.
+    ///
+    /// A longer wait asked for by the server with `Retry-After` is still
+    /// honored.
+    pub max_backoff: Duration,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum InvalidRefreshSchedule {
+    /// refresh interval must be nonzero
+    ZeroInterval,
+    /// jitter must be between 0 and 1
+    JitterOutOfRange,
+}
+
+pub const SUGGESTED_REFRESH_SCHEDULE: RefreshSchedule = RefreshSchedule {
+    interval: Duration::from_secs(60 * 60),
+    jitter: 0.1,
+    max_backoff: Duration::from_secs(24 * 60 * 60),
+};
+
+impl RefreshSchedule {
+    pub fn validate(&self) -> std::result::Result<(), InvalidRefreshSchedule> {
+        if self.interval.is_zero() {
+            return Err(InvalidRefreshSchedule::ZeroInterval);
+        }
+        // Also rejects NaN.
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(InvalidRefreshSchedule::JitterOutOfRange);
+        }
+        Ok(())
+    }
+
+    /// The delay before the next refresh, after the last `rate_limited`
+    /// attempts in a row were rate limited, the last of which asked to wait
+    /// `retry_after`.
+    fn delay_after(
+        &self,
+        rate_limited: u32,
+        retry_after: Option<RetryDelay>,
+        rng: &mut impl Rng,
+    ) -> Duration {
+        let delay = match rate_limited {
+            0 => self.interval,
+            n => self
+                .interval
+                .checked_mul(1u32 << n.min(31))
+                .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+                .max(self.interval),
+        };
+        let delay = delay.mul_f64(1.0 - rng.gen_range(0.0..=self.jitter));
+        // Jitter shouldn't bring the next attempt any earlier than the server
+        // allows.
+        retry_after.map_or(delay, |retry_after| delay.max(retry_after.as_duration()))
+    }
+}
+
+/// Called with each distinguished tree head a [`DistinguishedRefresher`]
+/// fetches and verifies.
+pub type RefreshCallback = Arc<dyn Fn(&DistinguishedResult) + Send + Sync>;
+
+/// Where a [`DistinguishedRefresher`] keeps the latest distinguished tree head.
+pub enum RefreshTarget {
+    /// Each refresh is checked against the head in the store, and replaces
+    /// it.
+    Store(Arc<dyn KtStateStore>),
+    /// Each refresh is checked against the previous one (starting from
+    /// `last`), then handed to `on_refresh`.
+    Callback {
+        last: Option<LastTreeHead>,
+        on_refresh: RefreshCallback,
+    },
+}
+
+/// A background task that periodically fetches the distinguished tree head,
+/// verifies it against the previous one, and saves it to a [`RefreshTarget`].
+///
+/// Refreshes that fail are logged and skipped. If the server responds with
+/// `429 Too Many Requests`, the delay before the next attempt grows according
+/// to the [`RefreshSchedule`], and is never shorter than the response's
+/// `Retry-After`.
+///
+/// The task stops when the `DistinguishedRefresher` is dropped (or
+/// [stopped](Self::stop)), or when its runtime shuts down.
+#[derive(Debug)]
+pub struct DistinguishedRefresher {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl DistinguishedRefresher {
+    /// Spawns the refresh task on `runtime`.
+    ///
+    /// The first refresh happens one interval from now. While `outage` (from
+    /// [`ConnectState::outage_watch`](crate::connect_state::ConnectState::outage_watch))
+    /// reports that the network is unreachable, refreshes are put off until
+    /// the outage ends.
+    pub fn start<K>(
+        runtime: &tokio::runtime::Handle,
+        kt: K,
+        target: RefreshTarget,
+        schedule: RefreshSchedule,
+        outage: Option<OutageWatch>,
+    ) -> std::result::Result<Self, InvalidRefreshSchedule>
+    where
+        K: KtApi + Send + Sync + 'static,
+    {
+        schedule.validate()?;
+        Ok(Self {
+            task: runtime.spawn(run(kt, target, schedule, outage)),
+        })
+    }
+
+    /// Stops the task, abandoning any refresh in progress.
+    ///
+    /// Equivalent to dropping the `DistinguishedRefresher`.
+    pub fn stop(self) {}
+}
+
+impl Drop for DistinguishedRefresher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run(
+    kt: impl KtApi + Sync,
+    mut target: RefreshTarget,
+    schedule: RefreshSchedule,
+    mut outage: Option<OutageWatch>,
+) {
+    let mut rate_limited = 0u32;
+    let mut retry_after = None;
+    loop {
+        let delay = schedule.delay_after(rate_limited, retry_after, &mut rand::thread_rng());
+        tokio::time::sleep(delay).await;
+
+        if let Some(outage) = &mut outage {
+            wait_out_outage(outage).await;
+        }
+
+        (rate_limited, retry_after) = match target.refresh(&kt).await {
+            Ok(()) => (0, None),
+            Err(Error::RateLimited { retry_after }) => {
+                log::info!("distinguished tree head refresh was rate limited");
+                (rate_limited.saturating_add(1), retry_after)
+            }
+            Err(e) => {
+                log::warn!("failed to refresh distinguished tree head: {e}");
+                (0, None)
+            }
+        };
+    }
+}
+
+/// Returns once `outage` no longer reports an outage in progress.
+async fn wait_out_outage(outage: &mut OutageWatch) {
+    loop {
+        let Some(until) = *outage.borrow_and_update() else {
+            return;
+        };
+        if until <= tokio::time::Instant::now() {
+            return;
+        }
+        tokio::select! {
+            () = tokio::time::sleep_until(until) => return,
+            changed = outage.changed() => {
+                if changed.is_err() {
+                    // No more updates are coming, so the outage can only end
+                    // on schedule.
+                    tokio::time::sleep_until(until).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl RefreshTarget {
+    async fn refresh(&mut self, kt: &impl KtApi) -> Result<()> {
+        match self {
+            RefreshTarget::Store(store) => {
+                let last = store
+                    .distinguished_tree_head()
+                    .await
+                    .map_err(Error::StateLoadFailed)?
+                    .map(into_last_tree_head)
+                    .transpose()?;
+                let result = kt.distinguished(last).await?;
+                store
+                    .set_distinguished_tree_head(result.state_update.into_stored())
+                    .await
+                    .map_err(Error::StatePersistFailed)
+            }
+            RefreshTarget::Callback { last, on_refresh } => {
+                let result = kt.distinguished(last.clone()).await?;
+                on_refresh(&result);
+                let LocalStateUpdate {
+                    tree_head,
+                    tree_root,
+                    monitoring_data: _,
+                } = result.state_update;
+                *last = Some((tree_head, tree_root));
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use assert_matches::assert_matches;
+    use libsignal_core::{Aci, E164};
+    use libsignal_keytrans::{AccountData, StoredTreeHead, TreeHead};
+    use libsignal_protocol::PublicKey;
+    use test_case::test_case;
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::keytrans::test_support::InMemoryStateStore;
+    use crate::keytrans::{E164SearchKey, MaybePartial, MonitorResult, SearchResult, UsernameHash};
+
+    const NO_JITTER: RefreshSchedule = RefreshSchedule {
+        interval: Duration::from_secs(10),
+        jitter: 0.0,
+        max_backoff: Duration::from_secs(35),
+    };
+
+    /// Answers each `distinguished` request with the next of `responses`,
+    /// recording when it was made and the size of the tree head it was
+    /// checked against.
+    #[derive(Clone, Default)]
+    struct ScriptedKt {
+        responses: Arc<Mutex<VecDeque<Result<u64>>>>,
+        requests: Arc<Mutex<Vec<(tokio::time::Instant, Option<u64>)>>>,
+    }
+
+    impl ScriptedKt {
+        fn new(responses: impl IntoIterator<Item = Result<u64>>) -> Self {
+            Self {
+                responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+                ..Default::default()
+            }
+        }
+
+        fn requests(&self) -> Vec<(Duration, Option<u64>)> {
+            let requests = self.requests.lock().expect("not poisoned");
+            let Some((start, _)) = requests.first() else {
+                return vec![];
+            };
+            requests
+                .iter()
+                .map(|(at, size)| (*at - *start, *size))
+                .collect()
+        }
+    }
+
+    fn tree_head(tree_size: u64) -> LastTreeHead {
+        (
+            TreeHead {
+                tree_size,
+                ..Default::default()
+            },
+            [0; 32],
+        )
+    }
+
+    impl KtApi for ScriptedKt {
+        async fn search(
+            &self,
+            _aci: &Aci,
+            _aci_identity_key: &PublicKey,
+            _e164: Option<E164SearchKey>,
+            _username_hash: Option<UsernameHash<'_>>,
+            _stored_account_data: Option<AccountData>,
+            _distinguished_tree_head: &LastTreeHead,
+        ) -> Result<MaybePartial<SearchResult>> {
+            unreachable!()
+        }
+
+        async fn distinguished(&self, last: Option<LastTreeHead>) -> Result<DistinguishedResult> {
+            self.requests.lock().expect("not poisoned").push((
+                tokio::time::Instant::now(),
+                last.map(|(head, _)| head.tree_size),
+            ));
+            let response = self
+                .responses
+                .lock()
+                .expect("not poisoned")
+                .pop_front()
+                .unwrap_or(Err(Error::Internal("no more responses")));
+            let (tree_head, tree_root) = tree_head(response?);
+            Ok(DistinguishedResult {
+                state_update: LocalStateUpdate {
+                    tree_head,
+                    tree_root,
+                    monitoring_data: None,
+                },
+                auditor_lag: None,
+                auditor_timestamp: None,
+                proof_metrics: Default::default(),
+            })
+        }
+
+        async fn monitor(
+            &self,
+            _aci: &Aci,
+            _e164: Option<E164>,
+            _username_hash: Option<UsernameHash<'_>>,
+            _account_data: AccountData,
+            _last_distinguished_tree_head: &LastTreeHead,
+        ) -> Result<MonitorResult> {
+            unreachable!()
+        }
+    }
+
+    fn callback_target(last: u64) -> (RefreshTarget, Arc<Mutex<Vec<u64>>>) {
+        let refreshed = Arc::new(Mutex::new(vec![]));
+        let target = RefreshTarget::Callback {
+            last: Some(tree_head(last)),
+            on_refresh: Arc::new({
+                let refreshed = refreshed.clone();
+                move |result| {
+                    refreshed
+                        .lock()
+                        .expect("not poisoned")
+                        .push(result.state_update.tree_head.tree_size)
+                }
+            }),
+        };
+        (target, refreshed)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn each_refresh_is_checked_against_the_previous_one() {
+        let kt = ScriptedKt::new([Ok(2), Err(Error::Internal("oops")), Ok(3)]);
+        let (target, refreshed) = callback_target(1);
+
+        let refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            target,
+            NO_JITTER,
+            None,
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(35)).await;
+        refresher.stop();
+        tokio::time::sleep(Duration::from_secs(100)).await;
+
+        assert_eq!(
+            kt.requests(),
+            [
+                (Duration::ZERO, Some(1)),
+                (Duration::from_secs(10), Some(2)),
+                (Duration::from_secs(20), Some(2)),
+            ]
+        );
+        assert_eq!(*refreshed.lock().expect("not poisoned"), [2, 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn store_is_checked_and_updated_by_each_refresh() {
+        let kt = ScriptedKt::new([Ok(2), Ok(3)]);
+        let store = Arc::new(InMemoryStateStore {
+            distinguished: Mutex::new(Some(StoredTreeHead::from(tree_head(1)))),
+            ..Default::default()
+        });
+
+        let _refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            RefreshTarget::Store(store.clone()),
+            NO_JITTER,
+            None,
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(25)).await;
+
+        assert_eq!(
+            kt.requests(),
+            [
+                (Duration::ZERO, Some(1)),
+                (Duration::from_secs(10), Some(2)),
+            ]
+        );
+        let stored = store.distinguished.lock().expect("not poisoned").clone();
+        assert_eq!(stored, Some(StoredTreeHead::from(tree_head(3))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn store_write_failures_are_skipped() {
+        let kt = ScriptedKt::new([Ok(2), Ok(3)]);
+        let store = Arc::new(InMemoryStateStore {
+            fail_writes: true,
+            ..Default::default()
+        });
+
+        let _refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            RefreshTarget::Store(store.clone()),
+            NO_JITTER,
+            None,
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(25)).await;
+
+        assert_eq!(
+            kt.requests(),
+            [(Duration::ZERO, None), (Duration::from_secs(10), None)],
+            "keeps refreshing from nothing"
+        );
+        assert_eq!(*store.distinguished.lock().expect("not poisoned"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiting_backs_off() {
+        let rate_limited = || Err(Error::RateLimited { retry_after: None });
+        let kt = ScriptedKt::new([rate_limited(), rate_limited(), rate_limited(), Ok(2), Ok(3)]);
+        let (target, _refreshed) = callback_target(1);
+
+        let _refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            target,
+            NO_JITTER,
+            None,
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(10 + 20 + 35 + 35 + 10 + 1)).await;
+
+        let delays = kt
+            .requests()
+            .windows(2)
+            .map(|pair| pair[1].0 - pair[0].0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [20, 35, 35, 10].map(Duration::from_secs),
+            "doubles up to the maximum, then resets"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiting_honors_retry_after() {
+        let kt = ScriptedKt::new([
+            Err(Error::RateLimited {
+                retry_after: Some(RetryDelay::from_secs(100)),
+            }),
+            Err(Error::RateLimited {
+                retry_after: Some(RetryDelay::from_secs(1)),
+            }),
+            Ok(2),
+        ]);
+        let (target, _refreshed) = callback_target(1);
+
+        let _refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            target,
+            NO_JITTER,
+            None,
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(10 + 100 + 35 + 1)).await;
+
+        let delays = kt
+            .requests()
+            .windows(2)
+            .map(|pair| pair[1].0 - pair[0].0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [100, 35].map(Duration::from_secs),
+            "waits for whichever is longer"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_while_network_is_unreachable() {
+        let kt = ScriptedKt::new([Ok(2), Ok(3)]);
+        let (target, _refreshed) = callback_target(1);
+        let (outage_tx, outage_rx) = watch::channel(Some(
+            tokio::time::Instant::now() + Duration::from_secs(1000),
+        ));
+
+        let _refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            target,
+            NO_JITTER,
+            Some(outage_rx),
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert!(kt.requests().is_empty());
+
+        outage_tx.send_replace(None);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(kt.requests(), [(Duration::ZERO, Some(1))]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn resumes_when_outage_ends_on_schedule() {
+        let kt = ScriptedKt::new([Ok(2), Ok(3)]);
+        let (target, _refreshed) = callback_target(1);
+        let (_outage_tx, outage_rx) =
+            watch::channel(Some(tokio::time::Instant::now() + Duration::from_secs(15)));
+
+        let _refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            target,
+            NO_JITTER,
+            Some(outage_rx),
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(14)).await;
+        assert!(kt.requests().is_empty());
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(kt.requests(), [(Duration::ZERO, Some(1))]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn accepts_outage_watch_from_connect_state() {
+        let kt = ScriptedKt::new([Ok(2)]);
+        let (target, _refreshed) = callback_target(1);
+        let connect_state =
+            crate::connect_state::ConnectState::new(crate::connect_state::SUGGESTED_CONNECT_CONFIG);
+
+        let _refresher = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            kt.clone(),
+            target,
+            NO_JITTER,
+            Some(connect_state.read().await.outage_watch()),
+        )
+        .expect("valid schedule");
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(kt.requests(), [(Duration::ZERO, Some(1))]);
+    }
+
+    #[test_case(RefreshSchedule { interval: Duration::ZERO, ..NO_JITTER } => matches Err(InvalidRefreshSchedule::ZeroInterval); "zero interval")]
+    #[test_case(RefreshSchedule { jitter: f64::NAN, ..NO_JITTER } => matches Err(InvalidRefreshSchedule::JitterOutOfRange); "NaN jitter")]
+    #[test_case(RefreshSchedule { jitter: -0.1, ..NO_JITTER } => matches Err(InvalidRefreshSchedule::JitterOutOfRange); "negative jitter")]
+    #[test_case(RefreshSchedule { jitter: 1.5, ..NO_JITTER } => matches Err(InvalidRefreshSchedule::JitterOutOfRange); "jitter above 1")]
+    #[test_case(RefreshSchedule { jitter: 1.0, ..NO_JITTER } => matches Ok(()); "full jitter")]
+    #[test_case(SUGGESTED_REFRESH_SCHEDULE => matches Ok(()); "suggested")]
+    fn schedule_is_validated(
+        schedule: RefreshSchedule,
+    ) -> std::result::Result<(), InvalidRefreshSchedule> {
+        schedule.validate()
+    }
+
+    #[tokio::test]
+    async fn invalid_schedule_is_not_started() {
+        let (target, _refreshed) = callback_target(1);
+        let result = DistinguishedRefresher::start(
+            &tokio::runtime::Handle::current(),
+            ScriptedKt::default(),
+            target,
+            RefreshSchedule {
+                jitter: f64::NAN,
+                ..NO_JITTER
+            },
+            None,
+        );
+        assert_matches!(result, Err(InvalidRefreshSchedule::JitterOutOfRange));
+    }
+
+    #[test]
+    fn jitter_only_shortens_delays() {
+        let schedule = RefreshSchedule {
+            jitter: 0.5,
+            ..NO_JITTER
+        };
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let delay = schedule.delay_after(0, None, &mut rng);
+            assert!(
+                (Duration::from_secs(5)..=Duration::from_secs(10)).contains(&delay),
+                "{delay:?}"
+            );
+        }
+    }
+}
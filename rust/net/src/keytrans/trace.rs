@@ -0,0 +1,206 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! A record of each step of key transparency verification, for audit tooling.
+//!
+//! Attach a [`VerificationTrace`] with [`Config::with_verification_trace`],
+//! run searches or monitors, and then export the collected events with
+//! [`VerificationTrace::to_json`]. Search keys never appear in the trace;
+//! they are replaced by a hash salted with a value unique to the trace.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+
+#[cfg(doc)]
+use super::Config;
+use super::{Error, KtOperation, SearchKeyKind};
+
+/// Collects [`TraceEvent`]s from every operation of the [`Kt`](super::Kt)s
+/// it's attached to, in the order they happen.
+pub struct VerificationTrace {
+    salt: [u8; 32],
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+/// A search key, as it appears in a [`VerificationTrace`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TracedKey {
+    pub kind: SearchKeyKind,
+    /// Hex-encoded SHA-256 of the trace's salt followed by the search key.
+    pub hash: String,
+}
+
+/// A single step of verification.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// A response is about to be verified.
+    Started {
+        operation: KtOperation,
+        /// The size of the tree the response claims.
+        tree_size: Option<u64>,
+        /// The size of the tree the account data was last verified against.
+        last_tree_size: Option<u64>,
+        /// The size of the distinguished tree the response is checked against.
+        distinguished_tree_size: Option<u64>,
+        key_kinds: Vec<SearchKeyKind>,
+    },
+    /// A proof from the response is about to be checked.
+    ProofConsumed {
+        /// The key the proof is for, if it is specific to one.
+        key: Option<TracedKey>,
+        proof: TracedProof,
+    },
+    /// A proof checked out, producing this tree root.
+    RootComputed {
+        tree_size: u64,
+        /// Hex-encoded.
+        root: String,
+    },
+    /// The response was skipped because every proof in it had already been
+    /// verified.
+    AlreadyVerified,
+    /// The response was accepted or rejected.
+    Decided {
+        accepted: bool,
+        error: Option<String>,
+    },
+}
+
+/// The kind of proof in a [`TraceEvent::ProofConsumed`], and how big it was.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TracedProof {
+    /// Consistency with the tree head the account data was last verified
+    /// against.
+    LastConsistency {
+        hashes: usize,
+    },
+    /// Consistency with the distinguished tree head.
+    DistinguishedConsistency {
+        hashes: usize,
+    },
+    Search {
+        steps: usize,
+        inclusion_hashes: usize,
+    },
+    Monitor {
+        steps: usize,
+    },
+    /// Inclusion of every monitored key's latest entry.
+    MonitorInclusion {
+        hashes: usize,
+    },
+}
+
+impl VerificationTrace {
+    /// Creates an empty trace with a random salt.
+    pub fn new() -> Self {
+        Self::with_salt(rand::random())
+    }
+
+    /// Creates an empty trace that hashes search keys with `salt`.
+    ///
+    /// The same salt can be used for several traces so that their hashes can
+    /// be compared.
+    pub fn with_salt(salt: [u8; 32]) -> Self {
+        Self {
+            salt,
+            events: Default::default(),
+        }
+    }
+
+    /// The events recorded so far.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.lock().expect("not poisoned").clone()
+    }
+
+    /// The events recorded so far, as a JSON array.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&*self.events.lock().expect("not poisoned"))
+            .expect("events can always be serialized")
+    }
+
+    pub(super) fn record(&self, event: TraceEvent) {
+        self.events.lock().expect("not poisoned").push(event)
+    }
+
+    pub(super) fn key(&self, kind: SearchKeyKind, search_key: &[u8]) -> TracedKey {
+        let hash = Sha256::new()
+            .chain_update(self.salt)
+            .chain_update(search_key)
+            .finalize();
+        TracedKey {
+            kind,
+            hash: hex::encode(hash),
+        }
+    }
+}
+
+impl Default for VerificationTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for VerificationTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Leave out the salt, which would make the hashes reversible.
+        f.debug_struct("VerificationTrace")
+            .field("events", &self.events.lock().expect("not poisoned").len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TraceEvent {
+    /// The response was accepted if there's no `error`.
+    pub(super) fn decided(error: Option<&Error>) -> Self {
+        Self::Decided {
+            accepted: error.is_none(),
+            error: error.map(ToString::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keys_are_salted() {
+        let first = VerificationTrace::with_salt([1; 32]);
+        let second = VerificationTrace::with_salt([2; 32]);
+        let key = b"a1:search key";
+
+        assert_eq!(
+            first.key(SearchKeyKind::Aci, key),
+            first.key(SearchKeyKind::Aci, key)
+        );
+        assert_ne!(
+            first.key(SearchKeyKind::Aci, key).hash,
+            second.key(SearchKeyKind::Aci, key).hash
+        );
+    }
+
+    #[test]
+    fn json_format() {
+        let trace = VerificationTrace::with_salt([0; 32]);
+        trace.record(TraceEvent::ProofConsumed {
+            key: None,
+            proof: TracedProof::Monitor { steps: 3 },
+        });
+        trace.record(TraceEvent::Decided {
+            accepted: true,
+            error: None,
+        });
+        assert_eq!(
+            trace.to_json(),
+            r#"[{"event":"proof_consumed","key":null,"proof":{"kind":"monitor","steps":3}},{"event":"decided","accepted":true,"error":null}]"#
+        );
+    }
+}
@@ -8,10 +8,15 @@ pub mod cdsi;
 pub mod certs;
 pub mod chat;
 pub mod connect_state;
+pub mod connection_quality;
+pub mod data_usage;
 pub mod enclave;
 pub mod env;
 pub mod keytrans;
+pub mod metrics;
+pub mod network_events;
 pub mod proto;
+pub mod server_time;
 pub mod svr;
 pub mod ws;
 
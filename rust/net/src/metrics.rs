@@ -0,0 +1,220 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Connection and request metrics, reported through the [`metrics`] facade
+//! when the `metrics` feature is enabled.
+//!
+//! Without the feature every function here compiles down to nothing. With it,
+//! the embedding application is responsible for installing a recorder (such as
+//! a Prometheus exporter); until one is installed the metrics are discarded.
+//!
+//! Labels are deliberately limited to values from small fixed sets (the
+//! service, the kind of route, and a coarse error class) so that no
+//! user-identifying or high-cardinality data ends up in a metrics backend.
+//!
+//! [`metrics`]: https://docs.rs/metrics
+
+use crate::chat::{ConnectError, SendError};
+
+/// The remote service a connection or request is for.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, strum::IntoStaticStr, num_enum::TryFromPrimitive,
+)]
+#[strum(serialize_all = "snake_case")]
+#[repr(u8)]
+pub enum ServiceKind {
+    Chat = 0,
+    Cdsi = 1,
+    Svr = 2,
+}
+
+/// A coarse, low-cardinality classification of an error for use as a metric
+/// label.
+pub trait ErrorClass {
+    fn error_class(&self) -> &'static str;
+}
+
+impl ErrorClass for ConnectError {
+    fn error_class(&self) -> &'static str {
+        match self {
+            ConnectError::Timeout { .. } => "timeout",
+            ConnectError::AllAttemptsFailed { .. } => "network",
+            ConnectError::InvalidConnectionConfiguration => "configuration",
+            ConnectError::ProxyFailure(_) => "proxy",
+            ConnectError::WebSocket(_) => "websocket",
+            ConnectError::RetryLater { .. } | ConnectError::RateLimitChallenge(_) => "rate_limited",
+            ConnectError::AppExpired { .. } | ConnectError::DeviceDeregistered => "rejected",
+        }
+    }
+}
+
+impl ErrorClass for SendError {
+    fn error_class(&self) -> &'static str {
+        match self {
+            SendError::RequestTimedOut { .. } => "timeout",
+            SendError::Disconnected => "disconnected",
+            SendError::ConnectionIdleTimeout => "idle_timeout",
+            SendError::ConnectionLost { .. } => "connection_lost",
+            SendError::WebSocket(_) => "websocket",
+            SendError::IncomingDataInvalid => "protocol",
+            SendError::RequestHasInvalidHeader => "invalid_request",
+        }
+    }
+}
+
+impl ErrorClass for crate::enclave::Error {
+    fn error_class(&self) -> &'static str {
+        match self {
+            Self::WebSocketConnect(_) | Self::WebSocket(_) => "websocket",
+            Self::Protocol(_) => "protocol",
+            Self::AttestationError(_) => "attestation",
+            Self::ConnectionTimedOut => "timeout",
+        }
+    }
+}
+
+/// Measures the duration of a connection attempt or request.
+///
+/// Zero-sized when the `metrics` feature is disabled.
+#[derive(Debug)]
+pub struct Timer {
+    #[cfg(feature = "metrics")]
+    start: std::time::Instant,
+}
+
+impl Timer {
+    #[inline]
+    pub fn start() -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn elapsed_seconds(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+}
+
+/// Records the start of a connection attempt, returning a timer to pass to
+/// [`connect_succeeded`].
+#[inline]
+pub fn connect_attempted(service: ServiceKind) -> Timer {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(
+        "libsignal_net_connect_attempts_total",
+        "service" => <&'static str>::from(service),
+    )
+    .increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = service;
+    Timer::start()
+}
+
+/// Records a successful connection over a route of the given kind.
+#[inline]
+pub fn connect_succeeded(service: ServiceKind, route_kind: &'static str, timer: Timer) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(
+        "libsignal_net_connect_duration_seconds",
+        "service" => <&'static str>::from(service),
+        "route" => route_kind,
+    )
+    .record(timer.elapsed_seconds());
+    #[cfg(not(feature = "metrics"))]
+    let _ = (service, route_kind, timer);
+}
+
+/// Records a failed connection attempt.
+#[inline]
+pub fn connect_failed(service: ServiceKind, error: &impl ErrorClass) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(
+        "libsignal_net_connect_failures_total",
+        "service" => <&'static str>::from(service),
+        "error" => error.error_class(),
+    )
+    .increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (service, error);
+}
+
+/// Records a completed request along with the number of body bytes sent and
+/// received.
+#[inline]
+pub fn request_finished(
+    service: ServiceKind,
+    timer: Timer,
+    bytes_sent: usize,
+    bytes_received: usize,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let service = <&'static str>::from(service);
+        metrics::histogram!("libsignal_net_request_duration_seconds", "service" => service)
+            .record(timer.elapsed_seconds());
+        metrics::counter!("libsignal_net_bytes_sent_total", "service" => service)
+            .increment(bytes_sent as u64);
+        metrics::counter!("libsignal_net_bytes_received_total", "service" => service)
+            .increment(bytes_received as u64);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (service, timer, bytes_sent, bytes_received);
+}
+
+/// Records a request that failed without a response.
+#[inline]
+pub fn request_failed(service: ServiceKind, error: &impl ErrorClass) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(
+        "libsignal_net_request_failures_total",
+        "service" => <&'static str>::from(service),
+        "error" => error.error_class(),
+    )
+    .increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (service, error);
+}
+
+#[cfg(test)]
+mod test {
+    use libsignal_net_infra::errors::ProxyFailureKind;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(ServiceKind::Chat => "chat")]
+    #[test_case(ServiceKind::Cdsi => "cdsi")]
+    #[test_case(ServiceKind::Svr => "svr")]
+    fn service_kind_label(service: ServiceKind) -> &'static str {
+        service.into()
+    }
+
+    #[test_case(SendError::RequestTimedOut { correlation_id: None } => "timeout")]
+    #[test_case(SendError::Disconnected => "disconnected")]
+    #[test_case(SendError::ConnectionIdleTimeout => "idle_timeout")]
+    #[test_case(SendError::ConnectionLost { close_code: None } => "connection_lost")]
+    #[test_case(SendError::IncomingDataInvalid => "protocol")]
+    #[test_case(SendError::RequestHasInvalidHeader => "invalid_request")]
+    fn send_error_class(error: SendError) -> &'static str {
+        error.error_class()
+    }
+
+    #[test_case(ConnectError::InvalidConnectionConfiguration => "configuration")]
+    #[test_case(ConnectError::ProxyFailure(ProxyFailureKind::AuthFailed) => "proxy")]
+    #[test_case(ConnectError::AppExpired { minimum_version: None, upgrade_url: None } => "rejected")]
+    #[test_case(ConnectError::DeviceDeregistered => "rejected")]
+    fn connect_error_class(error: ConnectError) -> &'static str {
+        error.error_class()
+    }
+
+    #[test]
+    fn timer_is_free_without_feature() {
+        if cfg!(not(feature = "metrics")) {
+            assert_eq!(std::mem::size_of::<Timer>(), 0);
+        }
+    }
+}
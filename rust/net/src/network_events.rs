@@ -0,0 +1,177 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! A short history of connection attempts, for attaching to bug reports.
+//!
+//! Like the labels in [`crate::metrics`], events only hold values from small
+//! fixed sets and numbers, so an export never contains a hostname, an IP
+//! address, or anything else that identifies the user or the server.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::metrics::{ErrorClass, ServiceKind};
+
+/// The most recent connection attempts made through a
+/// [`ConnectState`](crate::connect_state::ConnectState), oldest first.
+///
+/// Once full, each new event replaces the oldest.
+#[derive(Debug)]
+pub struct NetworkEventLog {
+    capacity: usize,
+    events: Mutex<VecDeque<NetworkEvent>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NetworkEvent {
+    /// When the attempt finished, in milliseconds since the Unix epoch.
+    at_millis: u64,
+    service: &'static str,
+    /// Only known for successful attempts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    route: Option<&'static str>,
+    /// `"success"`, or the failure's [`ErrorClass`].
+    outcome: &'static str,
+    duration_millis: u64,
+}
+
+impl Default for NetworkEventLog {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl NetworkEventLog {
+    pub const DEFAULT_CAPACITY: usize = 100;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn connect_succeeded(
+        &self,
+        service: ServiceKind,
+        route_kind: &'static str,
+        duration: Duration,
+    ) {
+        self.push(NetworkEvent::new(
+            service,
+            Some(route_kind),
+            "success",
+            duration,
+        ))
+    }
+
+    pub fn connect_failed(
+        &self,
+        service: ServiceKind,
+        error: &impl ErrorClass,
+        duration: Duration,
+    ) {
+        self.push(NetworkEvent::new(
+            service,
+            None,
+            error.error_class(),
+            duration,
+        ))
+    }
+
+    /// The recorded events as a JSON array, oldest first.
+    pub fn export_json(&self) -> String {
+        let events = self.events.lock().expect("not poisoned");
+        serde_json::to_string(&*events).expect("can serialize")
+    }
+
+    fn push(&self, event: NetworkEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.events.lock().expect("not poisoned");
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+impl NetworkEvent {
+    fn new(
+        service: ServiceKind,
+        route: Option<&'static str>,
+        outcome: &'static str,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            at_millis: as_millis(
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default(),
+            ),
+            service: service.into(),
+            route,
+            outcome,
+            duration_millis: as_millis(duration),
+        }
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chat::SendError;
+
+    #[test]
+    fn oldest_events_are_dropped() {
+        let log = NetworkEventLog::new(2);
+        for millis in [1, 2, 3] {
+            log.connect_succeeded(ServiceKind::Chat, "direct", Duration::from_millis(millis));
+        }
+
+        let events = log.events.lock().expect("not poisoned");
+        let durations = events
+            .iter()
+            .map(|event| event.duration_millis)
+            .collect::<Vec<_>>();
+        assert_eq!(durations, [2, 3]);
+    }
+
+    #[test]
+    fn export_only_has_fixed_fields() {
+        let log = NetworkEventLog::default();
+        log.connect_succeeded(ServiceKind::Cdsi, "proxy", Duration::from_millis(150));
+        log.connect_failed(
+            ServiceKind::Chat,
+            &SendError::Disconnected,
+            Duration::from_secs(2),
+        );
+
+        let exported: serde_json::Value =
+            serde_json::from_str(&log.export_json()).expect("valid JSON");
+        let events = exported.as_array().expect("array");
+        assert_eq!(events.len(), 2);
+        for event in events {
+            assert!(event["atMillis"].as_u64().is_some());
+        }
+        assert_eq!(events[0]["service"], "cdsi");
+        assert_eq!(events[0]["route"], "proxy");
+        assert_eq!(events[0]["outcome"], "success");
+        assert_eq!(events[0]["durationMillis"], 150);
+        assert_eq!(events[1]["service"], "chat");
+        assert_eq!(events[1].get("route"), None);
+        assert_eq!(events[1]["outcome"], SendError::Disconnected.error_class());
+        assert_eq!(events[1]["durationMillis"], 2000);
+    }
+}
@@ -0,0 +1,332 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Estimating the server's idea of the current time.
+//!
+//! Some responses are only valid for a limited time, and checking that against
+//! the local clock fails on devices whose clocks are set wrong. The chat server
+//! puts its own timestamp on every response, so we can keep track of how far
+//! off the local clock is and correct for it.
+//!
+//! The server's timestamp is only as trustworthy as the server, though, so
+//! nothing uses the estimate to check responses by default. See
+//! [`BoundedServerClock`] for opting in.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use http::HeaderMap;
+
+use crate::env::TIMESTAMP_HEADER_NAME;
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The local system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that is stopped at a particular time.
+impl Clock for SystemTime {
+    fn now(&self) -> SystemTime {
+        *self
+    }
+}
+
+/// Tracks the offset between the local clock and the server's clock.
+///
+/// Each sample is blended into a running estimate, so that variation in
+/// network latency doesn't make the estimate jump around. If samples are very
+/// far from the current estimate, though, it's more likely that one of the
+/// clocks was changed (say, the user fixed their device's clock), so the
+/// estimate is reset instead. That takes several samples in a row that agree
+/// with each other; a single outlier is ignored.
+#[derive(Debug, Default)]
+pub struct ServerTimeEstimator {
+    state: Mutex<EstimatorState>,
+}
+
+#[derive(Debug, Default)]
+struct EstimatorState {
+    /// Server time minus local time, in milliseconds, if any samples have been
+    /// recorded.
+    offset_millis: Option<i64>,
+    /// Consecutive samples far from `offset_millis`: the first one's offset,
+    /// and how many there have been.
+    pending_jump: Option<(i64, usize)>,
+}
+
+impl ServerTimeEstimator {
+    /// How much weight a new sample gets relative to the existing estimate.
+    const SMOOTHING_DIVISOR: i64 = 8;
+
+    /// How far a sample can be from the current estimate before it's treated
+    /// as a possible clock change rather than blended in.
+    const RESET_THRESHOLD: Duration = Duration::from_secs(60);
+
+    /// How many samples in a row have to agree on a new offset before the
+    /// estimate is reset to it.
+    const RESET_AFTER_SAMPLES: usize = 3;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a sample from a chat response's headers, if they have a server
+    /// timestamp.
+    ///
+    /// `received_at` should be the local time when the response was received.
+    pub fn record_response(&self, headers: &HeaderMap, received_at: SystemTime) {
+        let Some(server_millis) = headers
+            .get(TIMESTAMP_HEADER_NAME)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+        else {
+            return;
+        };
+        self.record_sample(
+            SystemTime::UNIX_EPOCH + Duration::from_millis(server_millis),
+            received_at,
+        )
+    }
+
+    /// Records that the server's clock read `server_time` when the local clock
+    /// read `local_time`.
+    pub fn record_sample(&self, server_time: SystemTime, local_time: SystemTime) {
+        let sample = signed_millis_between(local_time, server_time);
+        let close = |a: i64, b: i64| a.abs_diff(b) <= Self::RESET_THRESHOLD.as_millis() as u64;
+        let mut guard = self.state.lock().expect("not poisoned");
+        let EstimatorState {
+            offset_millis,
+            pending_jump,
+        } = &mut *guard;
+        match *offset_millis {
+            Some(current) if close(current, sample) => {
+                *pending_jump = None;
+                *offset_millis = Some(current + (sample - current) / Self::SMOOTHING_DIVISOR);
+            }
+            Some(current) => {
+                let (first, count) = match *pending_jump {
+                    Some((first, count)) if close(first, sample) => (first, count + 1),
+                    _ => (sample, 1),
+                };
+                if count < Self::RESET_AFTER_SAMPLES {
+                    log::debug!(
+                        "server time offset sample of {sample}ms is far from the estimate of \
+                        {current}ms; ignoring it for now"
+                    );
+                    *pending_jump = Some((first, count));
+                    return;
+                }
+                log::info!(
+                    "server time offset jumped from {current}ms to {sample}ms; resetting estimate"
+                );
+                *pending_jump = None;
+                *offset_millis = Some(sample);
+            }
+            None => {
+                log::info!("server time offset is {sample}ms");
+                *offset_millis = Some(sample);
+            }
+        }
+    }
+
+    /// The estimated offset of the server's clock from the local clock, if any
+    /// samples have been recorded.
+    ///
+    /// Positive if the server's clock is ahead of the local clock.
+    pub fn offset_millis(&self) -> Option<i64> {
+        self.state.lock().expect("not poisoned").offset_millis
+    }
+
+    /// Converts a local time to the estimated server time.
+    ///
+    /// If no samples have been recorded, returns `local_time` unchanged.
+    pub fn estimate(&self, local_time: SystemTime) -> SystemTime {
+        self.estimate_within(local_time, Duration::MAX)
+    }
+
+    /// Like [`Self::estimate`], but never moves `local_time` by more than
+    /// `max_correction`.
+    pub fn estimate_within(&self, local_time: SystemTime, max_correction: Duration) -> SystemTime {
+        let offset = self.offset_millis().unwrap_or_default();
+        let magnitude = Duration::from_millis(offset.unsigned_abs()).min(max_correction);
+        if offset >= 0 {
+            local_time + magnitude
+        } else {
+            local_time - magnitude
+        }
+    }
+}
+
+/// A clock that corrects a local clock by a [`ServerTimeEstimator`], but only
+/// by a little.
+///
+/// The estimate comes from the server, so using it to check the server's own
+/// responses lets the server pick the time they're checked at. Limiting the
+/// correction to [`Self::MAX_CORRECTION`] still fixes small amounts of device
+/// clock drift, without letting the server make a response that's days out
+/// of date look current.
+#[derive(Clone, Debug)]
+pub struct BoundedServerClock<C = SystemClock> {
+    estimator: Arc<ServerTimeEstimator>,
+    local: C,
+}
+
+impl BoundedServerClock {
+    pub fn new(estimator: Arc<ServerTimeEstimator>) -> Self {
+        Self::with_local_clock(estimator, SystemClock)
+    }
+}
+
+impl<C> BoundedServerClock<C> {
+    /// The furthest the clock will ever be from `local`.
+    pub const MAX_CORRECTION: Duration = Duration::from_secs(5 * 60);
+
+    pub fn with_local_clock(estimator: Arc<ServerTimeEstimator>, local: C) -> Self {
+        Self { estimator, local }
+    }
+}
+
+impl<C: Clock> Clock for BoundedServerClock<C> {
+    fn now(&self) -> SystemTime {
+        self.estimator
+            .estimate_within(self.local.now(), Self::MAX_CORRECTION)
+    }
+}
+
+/// Returns `to - from` in milliseconds, which may be negative.
+fn signed_millis_between(from: SystemTime, to: SystemTime) -> i64 {
+    match to.duration_since(from) {
+        Ok(ahead) => ahead.as_millis().try_into().unwrap_or(i64::MAX),
+        Err(behind) => i64::try_from(behind.duration().as_millis())
+            .map(|millis| -millis)
+            .unwrap_or(i64::MIN),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::HeaderValue;
+    use test_case::test_case;
+
+    use super::*;
+
+    fn local_plus(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000) + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn no_samples_means_no_correction() {
+        let estimator = ServerTimeEstimator::new();
+        assert_eq!(estimator.offset_millis(), None);
+        assert_eq!(estimator.estimate(local_plus(0)), local_plus(0));
+    }
+
+    #[test_case(5_000; "server ahead")]
+    #[test_case(-5_000; "server behind")]
+    fn first_sample_is_used_directly(offset: i64) {
+        let estimator = ServerTimeEstimator::new();
+        let local = local_plus(10_000);
+        let server = local_plus(10_000u64.checked_add_signed(offset).unwrap());
+        estimator.record_sample(server, local);
+        assert_eq!(estimator.offset_millis(), Some(offset));
+        assert_eq!(estimator.estimate(local), server);
+    }
+
+    #[test]
+    fn later_samples_are_smoothed() {
+        let estimator = ServerTimeEstimator::new();
+        estimator.record_sample(local_plus(1_000), local_plus(0));
+        estimator.record_sample(local_plus(1_800), local_plus(0));
+        assert_eq!(estimator.offset_millis(), Some(1_100));
+    }
+
+    #[test]
+    fn large_jump_resets_estimate() {
+        let estimator = ServerTimeEstimator::new();
+        estimator.record_sample(local_plus(3_600_000), local_plus(0));
+        // The user fixed their clock.
+        estimator.record_sample(local_plus(3_600_200), local_plus(3_600_000));
+        estimator.record_sample(local_plus(3_601_200), local_plus(3_601_000));
+        assert_eq!(estimator.offset_millis(), Some(3_600_000));
+        estimator.record_sample(local_plus(3_602_300), local_plus(3_602_000));
+        assert_eq!(estimator.offset_millis(), Some(300));
+    }
+
+    #[test]
+    fn single_outlier_is_ignored() {
+        let estimator = ServerTimeEstimator::new();
+        estimator.record_sample(local_plus(1_000), local_plus(0));
+        estimator.record_sample(local_plus(86_400_000), local_plus(0));
+        assert_eq!(estimator.offset_millis(), Some(1_000));
+        // A sample close to the estimate again starts the count over.
+        estimator.record_sample(local_plus(1_000), local_plus(0));
+        estimator.record_sample(local_plus(86_400_000), local_plus(0));
+        estimator.record_sample(local_plus(86_400_000), local_plus(0));
+        assert_eq!(estimator.offset_millis(), Some(1_000));
+    }
+
+    #[test]
+    fn disagreeing_outliers_dont_reset_estimate() {
+        let estimator = ServerTimeEstimator::new();
+        estimator.record_sample(local_plus(1_000), local_plus(0));
+        for offset in [86_400_000, 2 * 86_400_000, 3 * 86_400_000] {
+            estimator.record_sample(local_plus(offset), local_plus(0));
+        }
+        assert_eq!(estimator.offset_millis(), Some(1_000));
+    }
+
+    #[test_case(3_600_000; "server far ahead")]
+    #[test_case(-3_600_000; "server far behind")]
+    #[test_case(2_000; "server slightly ahead")]
+    fn bounded_clock_limits_correction(offset: i64) {
+        let estimator = Arc::new(ServerTimeEstimator::new());
+        let local = local_plus(10_000_000);
+        estimator.record_sample(
+            local_plus(10_000_000u64.checked_add_signed(offset).unwrap()),
+            local,
+        );
+
+        let clock = BoundedServerClock::with_local_clock(estimator, local);
+        let max = BoundedServerClock::<SystemTime>::MAX_CORRECTION;
+        let corrected = clock.now();
+        let expected_correction = Duration::from_millis(offset.unsigned_abs()).min(max);
+        if offset >= 0 {
+            assert_eq!(corrected, local + expected_correction);
+        } else {
+            assert_eq!(corrected, local - expected_correction);
+        }
+    }
+
+    #[test]
+    fn samples_from_headers() {
+        let estimator = ServerTimeEstimator::new();
+        let received_at = local_plus(0);
+        let server_millis = received_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+            + 250;
+
+        estimator.record_response(&HeaderMap::new(), received_at);
+        assert_eq!(estimator.offset_millis(), None);
+
+        let headers = HeaderMap::from_iter([(
+            http::HeaderName::from_static(TIMESTAMP_HEADER_NAME),
+            HeaderValue::from_str(&server_millis.to_string()).unwrap(),
+        )]);
+        estimator.record_response(&headers, received_at);
+        assert_eq!(estimator.offset_millis(), Some(250));
+    }
+}
@@ -4,6 +4,7 @@
 //
 
 use std::marker::PhantomData;
+use std::time::Instant;
 
 use http::HeaderName;
 use libsignal_net_infra::dns::DnsResolver;
@@ -14,13 +15,15 @@ use crate::auth::Auth;
 use crate::connect_state::{ConnectState, RouteInfo, WebSocketTransportConnectorFactory};
 pub use crate::enclave::Error;
 use crate::enclave::{
-    ConnectionLabel, EnclaveKind, EndpointParams, IntoAttestedConnection, LabeledConnection,
-    NewHandshake,
+    AttestedMeasurement, ConnectionLabel, EnclaveKind, EndpointParams, IntoAttestedConnection,
+    LabeledConnection, NewHandshake,
 };
+use crate::metrics::ServiceKind;
 
 pub struct SvrConnection<Kind: EnclaveKind> {
     inner: AttestedConnection,
     remote_address: RouteInfo,
+    measurement: AttestedMeasurement,
     witness: PhantomData<Kind>,
 }
 
@@ -45,6 +48,9 @@ where
         params: &EndpointParams<'_, E>,
         auth: Auth,
     ) -> Result<Self, Error> {
+        let timer = crate::metrics::connect_attempted(ServiceKind::Svr);
+        let started = Instant::now();
+        let network_events = connect.read().await.network_events.clone();
         ConnectState::connect_attested_ws(
             connect,
             route_provider,
@@ -54,12 +60,35 @@ where
             (ws_config, crate::infra::ws::WithoutResponseHeaders::new()),
             format!("svr3:{}", std::any::type_name::<E>()).into(),
             params,
+            ServiceKind::Svr,
         )
         .await
-        .map(|(connection, info)| Self {
-            inner: connection,
-            remote_address: info,
-            witness: PhantomData,
+        .inspect(|(_connection, info)| {
+            crate::metrics::connect_succeeded(ServiceKind::Svr, info.route_kind(), timer);
+            network_events.connect_succeeded(
+                ServiceKind::Svr,
+                info.route_kind(),
+                started.elapsed(),
+            );
         })
+        .inspect_err(|e| {
+            crate::metrics::connect_failed(ServiceKind::Svr, e);
+            network_events.connect_failed(ServiceKind::Svr, e, started.elapsed());
+        })
+        .map(|(connection, info)| {
+            let measurement = AttestedMeasurement::from(&params.mr_enclave);
+            log::info!("svr3: attested {measurement} via {info}");
+            Self {
+                inner: connection,
+                remote_address: info,
+                measurement,
+                witness: PhantomData,
+            }
+        })
+    }
+
+    /// The enclave measurement this connection was attested against.
+    pub fn attested_measurement(&self) -> &AttestedMeasurement {
+        &self.measurement
     }
 }
@@ -8,7 +8,7 @@ use std::fmt::Display;
 use async_trait::async_trait;
 use http::HeaderName;
 use libsignal_net_infra::connection_manager::{ErrorClass, ErrorClassifier};
-use libsignal_net_infra::errors::{LogSafeDisplay, TransportConnectError};
+use libsignal_net_infra::errors::{LogSafeDisplay, ProxyFailureKind, TransportConnectError};
 use libsignal_net_infra::service::{CancellationToken, ServiceConnector};
 use libsignal_net_infra::ws::WebSocketConnectError;
 use libsignal_net_infra::{extract_retry_later, ConnectionParams};
@@ -70,6 +70,33 @@ impl WebSocketServiceConnectError {
         )
     }
 
+    /// Why the connection failed at the proxy, if it did.
+    pub fn proxy_failure(&self) -> Option<ProxyFailureKind> {
+        match self {
+            Self::Connect(
+                WebSocketConnectError::Transport(TransportConnectError::ProxyFailure(kind)),
+                _,
+            ) => Some(*kind),
+            Self::Connect(..) | Self::RejectedByServer { .. } => None,
+        }
+    }
+
+    /// Whether the attempt failed before anything answered on the other end,
+    /// like a TCP or TLS failure or a timeout.
+    ///
+    /// Any other failure, including a 5xx response, means the network is up
+    /// even if the service isn't.
+    pub fn is_transport_failure(&self) -> bool {
+        match self {
+            Self::Connect(
+                WebSocketConnectError::Transport(_) | WebSocketConnectError::Timeout,
+                _,
+            ) => true,
+            Self::Connect(WebSocketConnectError::WebSocketError(_), _)
+            | Self::RejectedByServer { .. } => false,
+        }
+    }
+
     pub fn invalid_proxy_configuration() -> Self {
         Self::Connect(
             WebSocketConnectError::Transport(TransportConnectError::InvalidConfiguration),
@@ -101,6 +128,52 @@ impl Display for WebSocketServiceConnectError {
     }
 }
 
+/// A challenge the server requires to be completed before further requests
+/// are accepted.
+///
+/// Sent by the server as a `428 Precondition Required` response whose body
+/// names the challenge token and the kinds of challenge the client may
+/// complete (e.g. `"recaptcha"` or `"pushChallenge"`). The solution should be
+/// submitted along with the token before connecting again.
+#[derive(Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct RateLimitChallenge {
+    pub token: String,
+    pub options: Vec<String>,
+}
+
+impl RateLimitChallenge {
+    /// Extracts a challenge from a server rejection, if it contains one.
+    ///
+    /// Returns `None` for any response that isn't a well-formed challenge,
+    /// in which case the caller should handle it like any other rejection.
+    pub fn from_response(response: &http::Response<Option<Vec<u8>>>) -> Option<Self> {
+        if response.status() != http::StatusCode::PRECONDITION_REQUIRED {
+            return None;
+        }
+        let body = response.body().as_deref()?;
+        serde_json::from_slice(body)
+            .inspect_err(|e| log::warn!("failed to parse rate limit challenge: {e}"))
+            .ok()
+    }
+}
+
+impl std::fmt::Debug for RateLimitChallenge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { token: _, options } = self;
+        f.debug_struct("RateLimitChallenge")
+            .field("token", &format_args!("[redacted]"))
+            .field("options", options)
+            .finish()
+    }
+}
+
+impl Display for RateLimitChallenge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't include the token; it's effectively a credential.
+        write!(f, "rate limit challenge with options {:?}", self.options)
+    }
+}
+
 /// [`ServiceConnector`] wrapper that transforms the connect error using
 /// [`WebSocketServiceConnectError::from_websocket_error`].
 #[derive(Clone, Debug)]
@@ -173,7 +246,7 @@ impl ErrorClassifier for WebSocketServiceConnectError {
 
         // Retry-After takes precedence over everything else.
         if let Some(retry_later) = extract_retry_later(response.headers()) {
-            return ErrorClass::RetryAt(*received_at + retry_later.duration());
+            return ErrorClass::RetryAt(retry_later.delay().retry_at(*received_at));
         }
 
         // If we're rejected based on the request (4xx), there's no point in retrying.
@@ -190,11 +263,53 @@ impl ErrorClassifier for WebSocketServiceConnectError {
 mod test {
     use assert_matches::assert_matches;
     use http::HeaderName;
-    use test_case::test_matrix;
+    use test_case::{test_case, test_matrix};
     use tokio::time::Instant;
 
     use super::*;
 
+    #[test]
+    fn rate_limit_challenge_from_response() {
+        let response = http::Response::builder()
+            .status(http::StatusCode::PRECONDITION_REQUIRED)
+            .body(Some(
+                br#"{"token":"abc","options":["recaptcha","pushChallenge"]}"#.to_vec(),
+            ))
+            .expect("valid");
+
+        assert_eq!(
+            RateLimitChallenge::from_response(&response),
+            Some(RateLimitChallenge {
+                token: "abc".to_owned(),
+                options: vec!["recaptcha".to_owned(), "pushChallenge".to_owned()],
+            })
+        );
+    }
+
+    #[test]
+    fn rate_limit_challenge_debug_omits_token() {
+        let challenge = RateLimitChallenge {
+            token: "secret-token".to_owned(),
+            options: vec!["recaptcha".to_owned()],
+        };
+        let debug = format!("{challenge:?}");
+        assert!(!debug.contains("secret-token"), "{debug}");
+        assert!(debug.contains("recaptcha"), "{debug}");
+    }
+
+    #[test_case(http::StatusCode::PRECONDITION_REQUIRED, Some("not json"); "malformed body")]
+    #[test_case(http::StatusCode::PRECONDITION_REQUIRED, Some(r#"{"options":[]}"#); "missing token")]
+    #[test_case(http::StatusCode::PRECONDITION_REQUIRED, None; "no body")]
+    #[test_case(http::StatusCode::TOO_MANY_REQUESTS, Some(r#"{"token":"abc","options":[]}"#); "wrong status")]
+    fn rate_limit_challenge_not_parsed(status: http::StatusCode, body: Option<&str>) {
+        let response = http::Response::builder()
+            .status(status)
+            .body(body.map(|b| b.as_bytes().to_vec()))
+            .expect("valid");
+
+        assert_eq!(RateLimitChallenge::from_response(&response), None);
+    }
+
     #[test_matrix([None, Some("x-pinky-promise")])]
     fn classify_errors(confirmation_header: Option<&'static str>) {
         let now = Instant::now();
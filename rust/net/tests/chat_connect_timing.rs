@@ -5,18 +5,20 @@
 
 use std::collections::HashMap;
 use std::future::Future;
+use std::sync::Arc;
 
 use assert_matches::assert_matches;
 use async_trait::async_trait;
 use futures_util::{StreamExt as _, TryFutureExt as _};
 use itertools::Itertools as _;
 use libsignal_net::chat;
-use libsignal_net::env::STAGING;
+use libsignal_net::env::{PROXY_CONFIG_F_STAGING, PROXY_CONFIG_G, STAGING};
 use libsignal_net::infra::errors::TransportConnectError;
 use libsignal_net_infra::dns::dns_lookup::{DnsLookup, DnsLookupRequest};
 use libsignal_net_infra::dns::lookup_result::LookupResult;
 use libsignal_net_infra::dns::{self, DnsResolver};
 use libsignal_net_infra::host::Host;
+use rand::rngs::mock::StepRng;
 use test_case::test_case;
 use tokio::time::{Duration, Instant};
 
@@ -46,7 +48,12 @@ async fn all_routes_connect_hangs_forever(expected_duration: Duration) {
     let (elapsed, outcome) = timed(deps.connect_chat().map_ok(|_| ())).await;
 
     assert_eq!(elapsed, expected_duration);
-    assert_matches!(outcome, Err(chat::ConnectError::Timeout));
+    assert_matches!(
+        outcome,
+        Err(chat::ConnectError::Timeout {
+            furthest_phase: chat::FailurePhase::Transport
+        })
+    );
 }
 
 #[test_case(Duration::from_millis(500))]
@@ -110,7 +117,7 @@ async fn transport_connects_but_websocket_never_responds(expected_duration: Dura
         incoming_streams.map(|(host, _stream)| host).collect().await;
 
     assert_eq!(elapsed, expected_duration);
-    assert_matches!(outcome, Err(chat::ConnectError::Timeout));
+    assert_matches!(outcome, Err(chat::ConnectError::Timeout { .. }));
 
     assert_eq!(
         &incoming_stream_hosts,
@@ -153,6 +160,22 @@ async fn connect_again_skips_timed_out_routes(
 async fn runs_one_tls_handshake_at_a_time() {
     let domain_config = STAGING.chat_domain_config;
     let (deps, incoming_streams) = FakeDeps::new(&domain_config);
+    // Always pick the first SNI for each front, so we know which hosts the
+    // fronted routes will connect to.
+    deps.set_route_randomness(StepRng::new(0, 0)).await;
+    let tcp_host = |hostname: &str| -> Option<Host<Arc<str>>> {
+        let ip = deps.static_ip_map()[hostname]
+            .iter()
+            .next()
+            .expect("has an address");
+        Some(Host::Ip(ip))
+    };
+    let [direct_host, first_front_host, second_front_host] = [
+        domain_config.connect.hostname,
+        PROXY_CONFIG_F_STAGING.sni_list[0],
+        PROXY_CONFIG_G.sni_list[0],
+    ]
+    .map(tcp_host);
 
     const TLS_HANDSHAKE_DELAY: Duration = Duration::from_secs(5);
     tokio::spawn(connect_websockets_on_incoming(incoming_streams));
@@ -197,15 +220,21 @@ async fn runs_one_tls_handshake_at_a_time() {
             // handshake is attempted. The other connections are abandoned when
             // the first TLS handshake completes, so we never see any TLS
             // handshake events for them.
-            ((TcpConnect(_), Start), Duration::ZERO),
+            ((TcpConnect(first), Start), Duration::ZERO),
             ((TcpConnect(_), End), Duration::ZERO),
             ((TlsHandshake(Host::Domain(first_sni)), Start), Duration::ZERO),
-            ((TcpConnect(_), Start), FIRST_DELAY),
+            ((TcpConnect(second), Start), FIRST_DELAY),
             ((TcpConnect(_), End), FIRST_DELAY),
-            ((TcpConnect(_), Start), SECOND_DELAY),
+            ((TcpConnect(third), Start), SECOND_DELAY),
             ((TcpConnect(_), End), SECOND_DELAY),
             ((TlsHandshake(_), End), TLS_HANDSHAKE_DELAY),
-        ] => assert_eq!(&**first_sni, STAGING.chat_domain_config.connect.hostname)
+        ] => {
+            assert_eq!(&**first_sni, STAGING.chat_domain_config.connect.hostname);
+            assert_eq!(
+                [first, second, third],
+                [&direct_host, &first_front_host, &second_front_host]
+            );
+        }
     );
     assert_eq!(timing, Duration::from_secs(5));
 }
@@ -229,7 +258,7 @@ async fn tcp_connects_but_tls_never_responds() {
     );
 
     let (timing, outcome) = timed(deps.connect_chat().map_ok(|_| ())).await;
-    assert_matches!(outcome, Err(chat::ConnectError::Timeout));
+    assert_matches!(outcome, Err(chat::ConnectError::Timeout { .. }));
     assert_eq!(timing, Duration::from_secs(60));
 
     use TransportConnectEvent::*;
@@ -315,7 +344,7 @@ async fn custom_dns_failure(lookup: impl DnsLookup + 'static, expected_duration:
     let (elapsed, outcome) = timed(deps.connect_chat().map_ok(|_| ())).await;
 
     assert_eq!(elapsed, expected_duration);
-    assert_matches!(outcome, Err(chat::ConnectError::AllAttemptsFailed));
+    assert_matches!(outcome, Err(chat::ConnectError::AllAttemptsFailed { .. }));
 }
 
 #[test_case(false, Duration::from_secs(60))]
@@ -344,6 +373,6 @@ async fn slow_dns(should_accept_connection: bool, expected_duration: Duration) {
     if should_accept_connection {
         outcome.expect("accepted")
     } else {
-        assert_matches!(outcome, Err(chat::ConnectError::Timeout));
+        assert_matches!(outcome, Err(chat::ConnectError::Timeout { .. }));
     }
 }
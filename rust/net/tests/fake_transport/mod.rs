@@ -16,7 +16,7 @@ use libsignal_net::connect_state::{
     ConnectState, DefaultConnectorFactory, DefaultTransportConnector, SUGGESTED_CONNECT_CONFIG,
 };
 use libsignal_net::env::{ConnectionConfig, DomainConfig, UserAgent};
-use libsignal_net::infra::connection_manager::MultiRouteConnectionManager;
+use libsignal_net::infra::connection_manager::{MultiRouteConnectionManager, RouteCooldowns};
 use libsignal_net::infra::dns::lookup_result::LookupResult;
 use libsignal_net::infra::dns::DnsResolver;
 use libsignal_net::infra::errors::TransportConnectError;
@@ -167,11 +167,13 @@ impl FakeDeps {
             &chat_domain_config.connect,
             &UserAgent::with_libsignal_version("libsignal test"),
             true,
-            &ObservableEvent::new(),
+            &RouteCooldowns::new(&ObservableEvent::new()),
         );
 
-        let connector_factory =
-            ReplacingConnectorFactory(transport_connector.clone(), DefaultConnectorFactory);
+        let connector_factory = ReplacingConnectorFactory(
+            transport_connector.clone(),
+            DefaultConnectorFactory::default(),
+        );
         let connect_state =
             ConnectState::new_with_transport_connector(SUGGESTED_CONNECT_CONFIG, connector_factory);
         let resolved_names = fake_ips_for_names(chat_domain_config);
@@ -193,6 +195,14 @@ impl FakeDeps {
         &self.resolved_names
     }
 
+    /// See [`ConnectState::set_route_randomness_for_testing`].
+    pub async fn set_route_randomness(&self, rng: impl rand::RngCore + Send + 'static) {
+        self.connect_state
+            .write()
+            .await
+            .set_route_randomness_for_testing(rng)
+    }
+
     pub async fn connect_chat(
         &self,
     ) -> Result<PendingChatConnection<impl AsyncDuplexStream>, chat::ConnectError> {
@@ -169,14 +169,14 @@ public class AuthenticatedChatConnection: NativeHandleOwner<
     /// - Throws: Other ``SignalError``s for other kinds of failures.
     public func send(_ request: Request) async throws -> Response {
         let internalRequest = try Request.InternalRequest(request)
-        let timeoutMillis = request.timeoutMillis
+        let deadlineMillis = request.deadlineMillis(from: Date())
         let rawResponse: SignalFfiChatResponse = try await self.tokioAsyncContext
             .invokeAsyncFunction { promise, tokioAsyncContext in
                 withNativeHandle { chatService in
                     internalRequest.withNativeHandle { request in
                         signal_authenticated_chat_connection_send(
                             promise, tokioAsyncContext.const(), chatService.const(),
-                            request.const(), timeoutMillis
+                            request.const(), deadlineMillis
                         )
                     }
                 }
@@ -292,14 +292,14 @@ public class UnauthenticatedChatConnection: NativeHandleOwner<
     /// - Throws: Other ``SignalError``s for other kinds of failures.
     public func send(_ request: Request) async throws -> Response {
         let internalRequest = try Request.InternalRequest(request)
-        let timeoutMillis = request.timeoutMillis
+        let deadlineMillis = request.deadlineMillis(from: Date())
         let rawResponse: SignalFfiChatResponse = try await self.tokioAsyncContext
             .invokeAsyncFunction { promise, tokioAsyncContext in
                 withNativeHandle { chatService in
                     internalRequest.withNativeHandle { request in
                         signal_unauthenticated_chat_connection_send(
                             promise, tokioAsyncContext.const(), chatService.const(),
-                            request.const(), timeoutMillis
+                            request.const(), deadlineMillis
                         )
                     }
                 }
@@ -13,6 +13,21 @@ public protocol ConnectionEventsListener<Service>: AnyObject {
     ///
     /// This includes both deliberate disconnects as well as unexpected socket closures.
     func connectionWasInterrupted(_ service: Service, error: Error?)
+
+    /// Called when the server sends a request that doesn't correspond to any of the other callbacks.
+    ///
+    /// `body` is empty if the request didn't have one. Respond to the server by calling
+    /// `sendResponse` with an HTTP status code.
+    ///
+    /// The default implementation of this method responds with 404 Not Found.
+    func connection(_ service: Service, didReceiveUnrecognizedRequest path: String, body: Data, sendResponse: @escaping (UInt16) throws -> Void)
+}
+
+extension ConnectionEventsListener {
+    public func connection(_: Service, didReceiveUnrecognizedRequest _: String, body _: Data, sendResponse: @escaping (UInt16) throws -> Void) {
+        // If the connection is already gone, there's no one left to respond to.
+        try? sendResponse(404)
+    }
 }
 
 public protocol ChatConnectionListener: ConnectionEventsListener<AuthenticatedChatConnection> {
@@ -51,13 +66,43 @@ private protocol ChatListenerConnection {
 
 extension AuthenticatedChatConnection: ChatListenerConnection {}
 
-internal class ChatListenerBridge {
-    private class AckHandleOwner: NativeHandleOwner<SignalMutPointerServerMessageAck> {
-        override class func destroyNativeHandle(_ handle: NonNull<SignalMutPointerServerMessageAck>) -> SignalFfiErrorRef? {
-            signal_server_message_ack_destroy(handle.pointer)
+private class AckHandleOwner: NativeHandleOwner<SignalMutPointerServerMessageAck> {
+    override class func destroyNativeHandle(_ handle: NonNull<SignalMutPointerServerMessageAck>) -> SignalFfiErrorRef? {
+        signal_server_message_ack_destroy(handle.pointer)
+    }
+}
+
+/// Forwards an unrecognized server request to `listener`, taking ownership of `path`, `body`, and
+/// `ackHandle`.
+private func forwardUnrecognizedRequest<Service: AnyObject>(
+    to listener: any ConnectionEventsListener<Service>,
+    from service: Service?,
+    path: UnsafePointer<CChar>?,
+    body: SignalOwnedBuffer,
+    ackHandle: OpaquePointer?
+) {
+    defer {
+        signal_free_string(path)
+        signal_free_buffer(body.base, body.length)
+    }
+
+    let ackHandleOwner = AckHandleOwner(owned: NonNull(ackHandle)!)
+    guard let service else {
+        return
+    }
+
+    listener.connection(
+        service,
+        didReceiveUnrecognizedRequest: String(cString: path!),
+        body: Data(bytes: body.base, count: body.length)
+    ) { status in
+        try ackHandleOwner.withNativeHandle { ackHandle in
+            try checkError(signal_server_message_ack_send_status(ackHandle.const(), status))
         }
     }
+}
 
+internal class ChatListenerBridge {
     internal weak var chatConnection: AuthenticatedChatConnection?
     private let chatListener: any ChatConnectionListener
 
@@ -126,6 +171,10 @@ internal class ChatListenerBridge {
 
             bridge.didReceiveAlerts(swiftAlerts)
         }
+        let receivedUnrecognizedRequest: SignalReceivedUnrecognizedRequest = { rawCtx, path, body, ackHandle in
+            let bridge = Unmanaged<ChatListenerBridge>.fromOpaque(rawCtx!).takeUnretainedValue()
+            forwardUnrecognizedRequest(to: bridge.chatListener, from: bridge.chatConnection, path: path, body: body, ackHandle: ackHandle)
+        }
         let connectionInterrupted: SignalConnectionInterrupted = { rawCtx, maybeError in
             let bridge = Unmanaged<ChatListenerBridge>.fromOpaque(rawCtx!).takeUnretainedValue()
             guard let chatConnection = bridge.chatConnection else {
@@ -140,6 +189,7 @@ internal class ChatListenerBridge {
             received_incoming_message: receivedIncomingMessage,
             received_queue_empty: receivedQueueEmpty,
             received_alerts: receivedAlerts,
+            received_unrecognized_request: receivedUnrecognizedRequest,
             connection_interrupted: connectionInterrupted,
             destroy: { rawCtx in
                 _ = Unmanaged<AnyObject>.fromOpaque(rawCtx!).takeRetainedValue()
@@ -201,6 +251,11 @@ internal final class UnauthConnectionEventsListenerBridge {
                 LoggerBridge.shared?.logger.log(level: .error, file: #fileID, line: #line, message: "unauth socket received \(alerts.lengths.length) alerts")
             }
         }
+        let receivedUnrecognizedRequest: SignalReceivedUnrecognizedRequest = { rawCtx, path, body, ackHandle in
+            let bridge = Unmanaged<UnauthConnectionEventsListenerBridge>.fromOpaque(rawCtx!)
+                .takeUnretainedValue()
+            forwardUnrecognizedRequest(to: bridge.chatListener, from: bridge.chatConnection, path: path, body: body, ackHandle: ackHandle)
+        }
         let connectionInterrupted: SignalConnectionInterrupted = { rawCtx, maybeError in
             let bridge = Unmanaged<UnauthConnectionEventsListenerBridge>.fromOpaque(rawCtx!)
                 .takeUnretainedValue()
@@ -219,6 +274,7 @@ internal final class UnauthConnectionEventsListenerBridge {
             received_incoming_message: receivedIncomingMessage,
             received_queue_empty: receivedQueueEmpty,
             received_alerts: receivedAlerts,
+            received_unrecognized_request: receivedUnrecognizedRequest,
             connection_interrupted: connectionInterrupted,
             destroy: { rawCtx in
                 _ = Unmanaged<AnyObject>.fromOpaque(rawCtx!).takeRetainedValue()
@@ -38,6 +38,12 @@ public struct ChatRequest: Equatable, Sendable {
         }
     }
 
+    /// The time by which the request must finish, if sent at `now`, in milliseconds since the epoch.
+    internal func deadlineMillis(from now: Date) -> UInt64 {
+        let nowMillis = UInt64(max(0, now.timeIntervalSince1970 * 1000))
+        return nowMillis + UInt64(self.timeoutMillis)
+    }
+
     // Exposed for testing
     internal class InternalRequest: NativeHandleOwner<SignalMutPointerHttpRequest> {
         convenience init(_ request: ChatRequest) throws {
@@ -57,12 +57,15 @@ public enum SignalError: Error {
     case networkProtocolError(String)
     case cdsiInvalidToken(String)
     case rateLimitedError(retryAfter: TimeInterval, message: String)
+    case rateLimitChallengeError(token: String, options: Set<String>, message: String)
     case svrDataMissing(String)
     case svrRestoreFailed(triesRemaining: UInt32, message: String)
     case svrRotationMachineTooManySteps(String)
     case chatServiceInactive(String)
-    case appExpired(String)
+    case connectionLost(closeCode: UInt16?, message: String)
+    case appExpired(minimumVersion: String?, upgradeUrl: String?, message: String)
     case deviceDeregistered(String)
+    case proxyFailure(String)
 
     case unknown(UInt32, String)
 }
@@ -205,6 +208,14 @@ internal func checkError(_ error: SignalFfiErrorRef?) throws {
             signal_error_get_retry_after_seconds(error, $0)
         }
         throw SignalError.rateLimitedError(retryAfter: TimeInterval(retryAfterSeconds), message: errStr)
+    case SignalErrorCodeRateLimitChallenge:
+        let token = try invokeFnReturningString {
+            signal_error_get_rate_limit_challenge_token(error, $0)
+        }
+        let options = try invokeFnReturningStringArray {
+            signal_error_get_rate_limit_challenge_options(error, $0)
+        }
+        throw SignalError.rateLimitChallengeError(token: token, options: Set(options), message: errStr)
     case SignalErrorCodeSvrDataMissing:
         throw SignalError.svrDataMissing(errStr)
     case SignalErrorCodeSvrRestoreFailed:
@@ -216,10 +227,23 @@ internal func checkError(_ error: SignalFfiErrorRef?) throws {
         throw SignalError.svrRotationMachineTooManySteps(errStr)
     case SignalErrorCodeChatServiceInactive:
         throw SignalError.chatServiceInactive(errStr)
+    case SignalErrorCodeConnectionLost:
+        let closeCode = try invokeFnReturningInteger {
+            signal_error_get_close_code(error, $0)
+        }
+        throw SignalError.connectionLost(closeCode: closeCode == 0 ? nil : closeCode, message: errStr)
     case SignalErrorCodeAppExpired:
-        throw SignalError.appExpired(errStr)
+        let minimumVersion = try invokeFnReturningOptionalString {
+            signal_error_get_app_expired_minimum_version(error, $0)
+        }
+        let upgradeUrl = try invokeFnReturningOptionalString {
+            signal_error_get_app_expired_upgrade_url(error, $0)
+        }
+        throw SignalError.appExpired(minimumVersion: minimumVersion, upgradeUrl: upgradeUrl, message: errStr)
     case SignalErrorCodeDeviceDeregistered:
         throw SignalError.deviceDeregistered(errStr)
+    case SignalErrorCodeProxyFailure:
+        throw SignalError.proxyFailure(errStr)
     case SignalErrorCodeBackupValidation:
         let unknownFields = try invokeFnReturningStringArray {
             signal_error_get_unknown_fields(error, $0)
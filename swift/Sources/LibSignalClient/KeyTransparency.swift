@@ -0,0 +1,86 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+import Foundation
+import SignalFfi
+
+/// The key transparency state kept for one account, in the serialized forms an app persists.
+public struct KeyTransparencyState: Equatable, Sendable {
+    /// A serialized `StoredAccountData` message.
+    public var accountData: Data
+    /// A serialized `StoredTreeHead` message.
+    public var lastDistinguishedTreeHead: Data
+
+    public init(accountData: Data, lastDistinguishedTreeHead: Data) {
+        self.accountData = accountData
+        self.lastDistinguishedTreeHead = lastDistinguishedTreeHead
+    }
+
+    /// Unpacks state produced by ``export()`` on another device.
+    ///
+    /// Throws ``SignalError/invalidArgument(_:)`` if `exported` is truncated, corrupted, or from an
+    /// unsupported version.
+    public init<Bytes: ContiguousBytes>(importing exported: Bytes) throws {
+        let state: ImportedKeyTransState = try exported.withUnsafeBorrowedBuffer { exported in
+            try invokeFnReturningNativeHandle {
+                signal_key_transparency_import_state($0, exported)
+            }
+        }
+        self = try state.withNativeHandle { state in
+            try Self(
+                accountData: invokeFnReturningData {
+                    signal_imported_key_trans_state_get_account_data($0, state.const())
+                },
+                lastDistinguishedTreeHead: invokeFnReturningData {
+                    signal_imported_key_trans_state_get_last_distinguished_tree_head($0, state.const())
+                }
+            )
+        }
+    }
+
+    /// Packs the state so that it can be moved to another device during device transfer.
+    ///
+    /// The result carries a checksum, so ``init(importing:)`` rejects an export that was cut short
+    /// or corrupted.
+    ///
+    /// Throws ``SignalError/invalidArgument(_:)`` if either part of the state is corrupted.
+    public func export() throws -> Data {
+        try self.accountData.withUnsafeBorrowedBuffer { accountData in
+            try self.lastDistinguishedTreeHead.withUnsafeBorrowedBuffer { lastDistinguishedTreeHead in
+                try invokeFnReturningData {
+                    signal_key_transparency_export_state($0, accountData, lastDistinguishedTreeHead)
+                }
+            }
+        }
+    }
+}
+
+private class ImportedKeyTransState: NativeHandleOwner<SignalMutPointerImportedKeyTransState> {
+    override class func destroyNativeHandle(_ handle: NonNull<SignalMutPointerImportedKeyTransState>) -> SignalFfiErrorRef? {
+        signal_imported_key_trans_state_destroy(handle.pointer)
+    }
+}
+
+extension SignalMutPointerImportedKeyTransState: SignalMutPointer {
+    public typealias ConstPointer = SignalConstPointerImportedKeyTransState
+
+    public init(untyped: OpaquePointer?) {
+        self.init(raw: untyped)
+    }
+
+    public func toOpaque() -> OpaquePointer? {
+        self.raw
+    }
+
+    public func const() -> Self.ConstPointer {
+        Self.ConstPointer(raw: self.raw)
+    }
+}
+
+extension SignalConstPointerImportedKeyTransState: SignalConstPointer {
+    public func toOpaque() -> OpaquePointer? {
+        self.raw
+    }
+}
@@ -22,6 +22,25 @@ public class Net {
         case production = 1
     }
 
+    /// The kind of network the device is using. See ``Net/setNetworkType(_:)``.
+    public enum NetworkType: UInt8, Sendable {
+        // This needs to be kept in sync with the Rust version of the enum.
+
+        case unknown = 0
+        case wifi = 1
+        case cellular = 2
+        case vpn = 3
+    }
+
+    /// A Signal service. See ``Net/setEndpointIpHints(for:addresses:ttl:)``.
+    public enum ServiceKind: UInt8, Sendable {
+        // This needs to be kept in sync with the Rust version of the enum.
+
+        case chat = 0
+        case cdsi = 1
+        case svr = 2
+    }
+
     /// The "scheme" for Signal TLS proxies. See ``Net/setProxy(scheme:host:port:username:password:)``.
     public static let signalTlsProxyScheme = "org.signal.tls"
 
@@ -102,6 +121,33 @@ public class Net {
         self.connectionManager.setCensorshipCircumventionEnabled(enabled)
     }
 
+    /// Tells libsignal what kind of network the device is using.
+    ///
+    /// This is used to adjust timeouts and route ordering for *new* connections. On a VPN,
+    /// domain-fronted routes are not tried when a direct route is available. It is not itself a
+    /// network change; call ``Net/networkDidChange()`` as well if the network changed.
+    public func setNetworkType(_ networkType: NetworkType) {
+        self.connectionManager.setNetworkType(networkType)
+    }
+
+    /// Adds `addresses` as candidates when connecting to `service` for the next `ttl`.
+    ///
+    /// This is for addresses the app learned about out-of-band, e.g. during a censorship event.
+    /// They're tried after the addresses found by resolving the service's hostname, and are used
+    /// on their own if resolution fails. Replaces any hints previously set for `service`. The TTL
+    /// is capped at one week.
+    ///
+    /// - Throws: ``SignalError/ioError(_:)`` if any of the addresses isn't a valid IPv4 or IPv6
+    ///   address.
+    public func setEndpointIpHints(for service: ServiceKind, addresses: [String], ttl: TimeInterval) throws {
+        try self.connectionManager.setEndpointIpHints(for: service, addresses: addresses, ttl: ttl)
+    }
+
+    /// Discards any addresses set by ``Net/setEndpointIpHints(for:addresses:ttl:)`` for `service`.
+    public func clearEndpointIpHints(for service: ServiceKind) {
+        self.connectionManager.clearEndpointIpHints(for: service)
+    }
+
     /// Notifies libsignal that the network has changed.
     ///
     /// This will lead to, e.g. caches being cleared and cooldowns being reset.
@@ -114,6 +160,16 @@ public class Net {
         }
     }
 
+    /// Like ``Net/networkDidChange()``, but takes effect even if the network was reported as
+    /// changed very recently.
+    ///
+    /// Meant for explicit user actions, like a "retry now" button.
+    public func forceNetworkChange() throws {
+        try self.connectionManager.withNativeHandle { connectionManager in
+            try checkError(signal_connection_manager_force_network_change(connectionManager.const()))
+        }
+    }
+
     /// Like ``cdsiLookup(auth:request:)`` but with the parameters to ``CdsiLookupRequest`` broken out.
     public func cdsiLookup(
         auth: Auth,
@@ -229,7 +285,7 @@ public class Net {
     ///   - password: The password to provide to the server.
     ///   - receiveStories: Indicates to the server whether it should send story updates on this connection.
     ///
-    /// - Throws: ``SignalError/appExpired(_:)`` if the current app version is too old (as judged by
+    /// - Throws: ``SignalError/appExpired(minimumVersion:upgradeUrl:message:)`` if the current app version is too old (as judged by
     ///   the server).
     /// - Throws: ``SignalError/rateLimitedError(_:, _:)`` if the server
     ///   response indicates the request should be tried again after some time.
@@ -252,7 +308,7 @@ public class Net {
     /// object can be used to send and receive messages after
     /// ``UnauthenticatedChatConnection/start(listener:)`` is called.
     ///
-    /// - Throws: ``SignalError/appExpired(_:)`` if the current app version is too old (as judged by
+    /// - Throws: ``SignalError/appExpired(minimumVersion:upgradeUrl:message:)`` if the current app version is too old (as judged by
     ///   the server).
     /// - Throws: ``SignalError/rateLimitedError(_:, _:)`` if the server
     ///   response indicates the request should be tried again after some time.
@@ -313,7 +369,7 @@ internal class ConnectionManager: NativeHandleOwner<SignalMutPointerConnectionMa
             let proxyConfig: ProxyConfig = try username.withCString { username in
                 try password.withCString { password in
                     try invokeFnReturningNativeHandle {
-                        signal_connection_proxy_config_new($0, scheme, host, port, username, password)
+                        signal_connection_proxy_config_new($0, scheme, host, port, username, password, "")
                     }
                 }
             }
@@ -347,6 +403,25 @@ internal class ConnectionManager: NativeHandleOwner<SignalMutPointerConnectionMa
         }
     }
 
+    internal func setNetworkType(_ networkType: Net.NetworkType) {
+        self.withNativeHandle {
+            failOnError(signal_connection_manager_set_network_type($0.const(), networkType.rawValue))
+        }
+    }
+
+    internal func setEndpointIpHints(for service: Net.ServiceKind, addresses: [String], ttl: TimeInterval) throws {
+        let ttlSeconds = UInt32(exactly: ttl.rounded(.up)) ?? (ttl > 0 ? UInt32.max : 0)
+        try self.withNativeHandle {
+            try checkError(signal_connection_manager_set_endpoint_ip_hints($0.const(), service.rawValue, addresses.joined(separator: ","), ttlSeconds))
+        }
+    }
+
+    internal func clearEndpointIpHints(for service: Net.ServiceKind) {
+        self.withNativeHandle {
+            failOnError(signal_connection_manager_clear_endpoint_ip_hints($0.const(), service.rawValue))
+        }
+    }
+
     override internal class func destroyNativeHandle(_ handle: NonNull<SignalMutPointerConnectionManager>) -> SignalFfiErrorRef? {
         signal_connection_manager_destroy(handle.pointer)
     }
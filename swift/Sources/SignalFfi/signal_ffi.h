@@ -205,11 +205,14 @@ typedef enum {
   SignalErrorCodeConnectionFailed = 148,
   SignalErrorCodeChatServiceInactive = 149,
   SignalErrorCodeRequestTimedOut = 150,
+  SignalErrorCodeRateLimitChallenge = 151,
+  SignalErrorCodeConnectionLost = 152,
   SignalErrorCodeSvrDataMissing = 160,
   SignalErrorCodeSvrRestoreFailed = 161,
   SignalErrorCodeSvrRotationMachineTooManySteps = 162,
   SignalErrorCodeAppExpired = 170,
   SignalErrorCodeDeviceDeregistered = 171,
+  SignalErrorCodeProxyFailure = 172,
   SignalErrorCodeBackupValidation = 180,
 } SignalErrorCode;
 
@@ -228,6 +231,12 @@ typedef struct SignalAuthenticatedChatConnection SignalAuthenticatedChatConnecti
 
 typedef struct SignalCdsiLookup SignalCdsiLookup;
 
+/**
+ * Lets app code follow the [`ConnectionState`] of a [`ConnectionManager`]'s authenticated chat
+ * connection.
+ */
+typedef struct SignalChatConnectionStateWatcher SignalChatConnectionStateWatcher;
+
 typedef struct SignalCiphertextMessage SignalCiphertextMessage;
 
 /**
@@ -235,8 +244,18 @@ typedef struct SignalCiphertextMessage SignalCiphertextMessage;
  */
 typedef struct SignalConnectionInfo SignalConnectionInfo;
 
+/**
+ * What [`ConnectionManager::reset_connect_state`] cleared.
+ */
+typedef struct SignalConnectStateResetSummary SignalConnectStateResetSummary;
+
 typedef struct SignalConnectionManager SignalConnectionManager;
 
+/**
+ * The state of a chat connection, as seen by whoever owns it.
+ */
+typedef struct SignalConnectionState SignalConnectionState;
+
 typedef struct SignalConnectionProxyConfig SignalConnectionProxyConfig;
 
 typedef struct SignalDecryptionErrorMessage SignalDecryptionErrorMessage;
@@ -247,6 +266,8 @@ typedef struct SignalHsmEnclaveClient SignalHsmEnclaveClient;
 
 typedef struct SignalHttpRequest SignalHttpRequest;
 
+typedef struct SignalImportedKeyTransState SignalImportedKeyTransState;
+
 typedef struct SignalIncrementalMac SignalIncrementalMac;
 
 typedef struct SignalKeyPair SignalKeyPair;
@@ -803,6 +824,14 @@ typedef struct {
   const SignalConnectionManager *raw;
 } SignalConstPointerConnectionManager;
 
+typedef struct {
+  SignalConnectStateResetSummary *raw;
+} SignalMutPointerConnectStateResetSummary;
+
+typedef struct {
+  const SignalConnectStateResetSummary *raw;
+} SignalConstPointerConnectStateResetSummary;
+
 typedef struct {
   SignalLookupRequest *raw;
 } SignalMutPointerLookupRequest;
@@ -907,6 +936,8 @@ typedef void (*SignalReceivedQueueEmpty)(void *ctx);
 
 typedef void (*SignalReceivedAlerts)(void *ctx, SignalStringArray alerts);
 
+typedef void (*SignalReceivedUnrecognizedRequest)(void *ctx, const char *path, SignalOwnedBuffer body, SignalServerMessageAck *ack);
+
 typedef void (*SignalConnectionInterrupted)(void *ctx, SignalFfiError *error);
 
 typedef void (*SignalDestroyChatListener)(void *ctx);
@@ -931,6 +962,7 @@ typedef struct {
   SignalReceivedIncomingMessage received_incoming_message;
   SignalReceivedQueueEmpty received_queue_empty;
   SignalReceivedAlerts received_alerts;
+  SignalReceivedUnrecognizedRequest received_unrecognized_request;
   SignalConnectionInterrupted connection_interrupted;
   SignalDestroyChatListener destroy;
 } SignalFfiChatListenerStruct;
@@ -939,6 +971,33 @@ typedef struct {
   const SignalFfiChatListenerStruct *raw;
 } SignalConstPointerFfiChatListenerStruct;
 
+typedef void (*SignalReportConnectProgress)(void *ctx, uint8_t stage, uint64_t at_millis);
+
+typedef void (*SignalDestroyChatConnectListener)(void *ctx);
+
+/**
+ * Callbacks for [`ChatConnectListener`].
+ *
+ * Stages are numbered as in [`bridged_connect_stage`].
+ *
+ * # Safety
+ *
+ * This type contains raw pointers. Code that constructs an instance of this type must ensure
+ * memory safety assuming that
+ * - the `connect_progress` function pointer field is called with `ctx` as an argument;
+ * - the `destroy` function pointer field is called with `ctx` as an argument;
+ * - no function pointer fields are called after `destroy` is called.
+ */
+typedef struct {
+  void *ctx;
+  SignalReportConnectProgress connect_progress;
+  SignalDestroyChatConnectListener destroy;
+} SignalFfiChatConnectListenerStruct;
+
+typedef struct {
+  const SignalFfiChatConnectListenerStruct *raw;
+} SignalConstPointerFfiChatConnectListenerStruct;
+
 typedef struct {
   uint16_t status;
   const char *message;
@@ -980,6 +1039,39 @@ typedef struct {
   SignalChatConnectionInfo *raw;
 } SignalMutPointerChatConnectionInfo;
 
+typedef struct {
+  SignalChatConnectionStateWatcher *raw;
+} SignalMutPointerChatConnectionStateWatcher;
+
+typedef SignalConnectionState SignalChatConnectionState;
+
+typedef struct {
+  SignalChatConnectionState *raw;
+} SignalMutPointerChatConnectionState;
+
+typedef struct {
+  const SignalChatConnectionStateWatcher *raw;
+} SignalConstPointerChatConnectionStateWatcher;
+
+/**
+ * A C callback used to report the results of Rust futures.
+ *
+ * cbindgen will produce independent C types like `SignalCPromisei32` and
+ * `SignalCPromiseProtocolAddress`.
+ *
+ * This derives Copy because it behaves like a C type; nevertheless, a promise should still only be
+ * completed once.
+ */
+typedef struct {
+  void (*complete)(SignalFfiError *error, const SignalMutPointerChatConnectionState *result, const void *context);
+  const void *context;
+  SignalCancellationId cancellation_id;
+} SignalCPromiseMutPointerChatConnectionState;
+
+typedef struct {
+  const SignalChatConnectionState *raw;
+} SignalConstPointerChatConnectionState;
+
 /**
  * A C callback used to report the results of Rust futures.
  *
@@ -1007,6 +1099,14 @@ typedef struct {
   const SignalServerMessageAck *raw;
 } SignalConstPointerServerMessageAck;
 
+typedef struct {
+  SignalImportedKeyTransState *raw;
+} SignalMutPointerImportedKeyTransState;
+
+typedef struct {
+  const SignalImportedKeyTransState *raw;
+} SignalConstPointerImportedKeyTransState;
+
 typedef struct {
   SignalTokioAsyncContext *raw;
 } SignalMutPointerTokioAsyncContext;
@@ -1099,10 +1199,34 @@ uint32_t signal_error_get_type(const SignalFfiError *err);
 
 SignalFfiError *signal_error_get_retry_after_seconds(const SignalFfiError *err, uint32_t *out);
 
+/**
+ * Gets the wall-clock time (in milliseconds since the Unix epoch) at which an
+ * operation that was rate-limited by the server can be retried.
+ *
+ * Unlike [`signal_error_get_retry_after_seconds`], this doesn't become stale
+ * as time passes before the error is handled.
+ */
+SignalFfiError *signal_error_get_retry_at_timestamp(const SignalFfiError *err, uint64_t *out);
+
 SignalFfiError *signal_error_get_tries_remaining(const SignalFfiError *err, uint32_t *out);
 
 SignalFfiError *signal_error_get_unknown_fields(const SignalFfiError *err, SignalStringArray *out);
 
+SignalFfiError *signal_error_get_rate_limit_challenge_token(const SignalFfiError *err, const char **out);
+
+SignalFfiError *signal_error_get_rate_limit_challenge_options(const SignalFfiError *err, SignalStringArray *out);
+
+SignalFfiError *signal_error_get_app_expired_minimum_version(const SignalFfiError *err, const char **out);
+
+SignalFfiError *signal_error_get_app_expired_upgrade_url(const SignalFfiError *err, const char **out);
+
+/**
+ * Writes the code from the server's close frame, or 0 if the connection ended without one.
+ *
+ * 0 is never a valid close code, so it can't be confused with a real one.
+ */
+SignalFfiError *signal_error_get_close_code(const SignalFfiError *err, uint16_t *out);
+
 void signal_error_free(SignalFfiError *err);
 
 SignalFfiError *signal_identitykeypair_deserialize(SignalMutPointerPrivateKey *private_key, SignalMutPointerPublicKey *public_key, SignalBorrowedBuffer input);
@@ -1859,7 +1983,7 @@ SignalFfiError *signal_connection_proxy_config_destroy(SignalMutPointerConnectio
 
 SignalFfiError *signal_connection_proxy_config_clone(SignalMutPointerConnectionProxyConfig *new_obj, SignalConstPointerConnectionProxyConfig obj);
 
-SignalFfiError *signal_connection_proxy_config_new(SignalMutPointerConnectionProxyConfig *out, const char *scheme, const char *host, int32_t port, const char *username, const char *password);
+SignalFfiError *signal_connection_proxy_config_new(SignalMutPointerConnectionProxyConfig *out, const char *scheme, const char *host, int32_t port, const char *username, const char *password, const char *connect_headers);
 
 SignalFfiError *signal_connection_manager_destroy(SignalMutPointerConnectionManager p);
 
@@ -1871,10 +1995,38 @@ SignalFfiError *signal_connection_manager_set_invalid_proxy(SignalConstPointerCo
 
 SignalFfiError *signal_connection_manager_clear_proxy(SignalConstPointerConnectionManager connection_manager);
 
+SignalFfiError *signal_connection_manager_set_local_bind_address(SignalConstPointerConnectionManager connection_manager, const char *address);
+
+SignalFfiError *signal_connection_manager_set_endpoint_ip_hints(SignalConstPointerConnectionManager connection_manager, uint8_t service, const char *addresses, uint32_t ttl_seconds);
+
+SignalFfiError *signal_connection_manager_clear_endpoint_ip_hints(SignalConstPointerConnectionManager connection_manager, uint8_t service);
+
 SignalFfiError *signal_connection_manager_set_censorship_circumvention_enabled(SignalConstPointerConnectionManager connection_manager, bool enabled);
 
+SignalFfiError *signal_connection_manager_set_network_type(SignalConstPointerConnectionManager connection_manager, uint8_t network_type);
+
 SignalFfiError *signal_connection_manager_on_network_change(SignalConstPointerConnectionManager connection_manager);
 
+SignalFfiError *signal_connection_manager_force_network_change(SignalConstPointerConnectionManager connection_manager);
+
+SignalFfiError *signal_connection_manager_chat_connection_quality(uint8_t *out, SignalConstPointerConnectionManager connection_manager);
+
+SignalFfiError *signal_connection_manager_set_connection_quality_thresholds(SignalConstPointerConnectionManager connection_manager, uint32_t good_below_millis, uint32_t bad_above_millis);
+
+SignalFfiError *signal_connect_state_reset_summary_destroy(SignalMutPointerConnectStateResetSummary p);
+
+SignalFfiError *signal_connection_manager_reset_connect_state(SignalMutPointerConnectStateResetSummary *out, SignalConstPointerConnectionManager connection_manager);
+
+SignalFfiError *signal_connection_manager_last_connect_state_reset(SignalMutPointerConnectStateResetSummary *out, SignalConstPointerConnectionManager connection_manager);
+
+SignalFfiError *signal_connection_manager_export_recent_network_events(const char **out, SignalConstPointerConnectionManager connection_manager);
+
+SignalFfiError *signal_connect_state_reset_summary_route_failures_cleared(uint32_t *out, SignalConstPointerConnectStateResetSummary summary);
+
+SignalFfiError *signal_connect_state_reset_summary_outage_cleared(bool *out, SignalConstPointerConnectStateResetSummary summary);
+
+SignalFfiError *signal_connect_state_reset_summary_dns_lookups_flushed(uint32_t *out, SignalConstPointerConnectStateResetSummary summary);
+
 SignalFfiError *signal_create_otp(const char **out, const char *username, SignalBorrowedBuffer secret);
 
 SignalFfiError *signal_create_otp_from_base64(const char **out, const char *username, const char *secret);
@@ -1887,6 +2039,8 @@ SignalFfiError *signal_lookup_request_add_e164(SignalConstPointerLookupRequest r
 
 SignalFfiError *signal_lookup_request_add_previous_e164(SignalConstPointerLookupRequest request, const char *e164);
 
+SignalFfiError *signal_lookup_request_add_removed_e164(SignalConstPointerLookupRequest request, const char *e164);
+
 SignalFfiError *signal_lookup_request_set_token(SignalConstPointerLookupRequest request, SignalBorrowedBuffer token);
 
 SignalFfiError *signal_lookup_request_add_aci_and_access_key(SignalConstPointerLookupRequest request, const SignalServiceIdFixedWidthBinaryBytes *aci, SignalBorrowedBuffer access_key);
@@ -1901,6 +2055,8 @@ SignalFfiError *signal_cdsi_lookup_token(SignalOwnedBuffer *out, SignalConstPoin
 
 SignalFfiError *signal_cdsi_lookup_complete(SignalCPromiseFfiCdsiLookupResponse *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerCdsiLookup lookup);
 
+SignalFfiError *signal_cdsi_lookup_next_batch(SignalCPromiseFfiCdsiLookupResponse *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerCdsiLookup lookup, uint32_t max_entries);
+
 SignalFfiError *signal_http_request_destroy(SignalMutPointerHttpRequest p);
 
 SignalFfiError *signal_unauthenticated_chat_connection_destroy(SignalMutPointerUnauthenticatedChatConnection p);
@@ -1921,9 +2077,11 @@ SignalFfiError *signal_chat_connection_info_description(const char **out, Signal
 
 SignalFfiError *signal_unauthenticated_chat_connection_connect(SignalCPromiseMutPointerUnauthenticatedChatConnection *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerConnectionManager connection_manager);
 
+SignalFfiError *signal_unauthenticated_chat_connection_connect_with_listener(SignalCPromiseMutPointerUnauthenticatedChatConnection *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerConnectionManager connection_manager, SignalConstPointerFfiChatConnectListenerStruct progress_listener);
+
 SignalFfiError *signal_unauthenticated_chat_connection_init_listener(SignalConstPointerUnauthenticatedChatConnection chat, SignalConstPointerFfiChatListenerStruct listener);
 
-SignalFfiError *signal_unauthenticated_chat_connection_send(SignalCPromiseFfiChatResponse *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerUnauthenticatedChatConnection chat, SignalConstPointerHttpRequest http_request, uint32_t timeout_millis);
+SignalFfiError *signal_unauthenticated_chat_connection_send(SignalCPromiseFfiChatResponse *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerUnauthenticatedChatConnection chat, SignalConstPointerHttpRequest http_request, uint64_t deadline);
 
 SignalFfiError *signal_unauthenticated_chat_connection_disconnect(SignalCPromisebool *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerUnauthenticatedChatConnection chat);
 
@@ -1933,18 +2091,54 @@ SignalFfiError *signal_authenticated_chat_connection_preconnect(SignalCPromisebo
 
 SignalFfiError *signal_authenticated_chat_connection_connect(SignalCPromiseMutPointerAuthenticatedChatConnection *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerConnectionManager connection_manager, const char *username, const char *password, bool receive_stories);
 
+SignalFfiError *signal_authenticated_chat_connection_connect_with_listener(SignalCPromiseMutPointerAuthenticatedChatConnection *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerConnectionManager connection_manager, const char *username, const char *password, bool receive_stories, SignalConstPointerFfiChatConnectListenerStruct progress_listener);
+
 SignalFfiError *signal_authenticated_chat_connection_init_listener(SignalConstPointerAuthenticatedChatConnection chat, SignalConstPointerFfiChatListenerStruct listener);
 
-SignalFfiError *signal_authenticated_chat_connection_send(SignalCPromiseFfiChatResponse *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerAuthenticatedChatConnection chat, SignalConstPointerHttpRequest http_request, uint32_t timeout_millis);
+SignalFfiError *signal_authenticated_chat_connection_send(SignalCPromiseFfiChatResponse *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerAuthenticatedChatConnection chat, SignalConstPointerHttpRequest http_request, uint64_t deadline);
 
 SignalFfiError *signal_authenticated_chat_connection_disconnect(SignalCPromisebool *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerAuthenticatedChatConnection chat);
 
+SignalFfiError *signal_authenticated_chat_connection_run_auto_reconnect(SignalCPromisebool *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerAuthenticatedChatConnection chat, SignalConstPointerConnectionManager connection_manager, const char *username, const char *password, bool receive_stories);
+
+SignalFfiError *signal_authenticated_chat_connection_stop_auto_reconnect(SignalConstPointerAuthenticatedChatConnection chat);
+
 SignalFfiError *signal_authenticated_chat_connection_info(SignalMutPointerChatConnectionInfo *out, SignalConstPointerAuthenticatedChatConnection chat);
 
+SignalFfiError *signal_chat_connection_state_watcher_destroy(SignalMutPointerChatConnectionStateWatcher p);
+
+SignalFfiError *signal_chat_connection_state_destroy(SignalMutPointerChatConnectionState p);
+
+SignalFfiError *signal_chat_connection_state_watcher_new(SignalMutPointerChatConnectionStateWatcher *out, SignalConstPointerConnectionManager connection_manager);
+
+SignalFfiError *signal_chat_connection_state_watcher_current(SignalMutPointerChatConnectionState *out, SignalConstPointerChatConnectionStateWatcher watcher);
+
+SignalFfiError *signal_chat_connection_state_watcher_next(SignalCPromiseMutPointerChatConnectionState *promise, SignalConstPointerTokioAsyncContext async_runtime, SignalConstPointerChatConnectionStateWatcher watcher);
+
+SignalFfiError *signal_chat_connection_state_kind(uint8_t *out, SignalConstPointerChatConnectionState state);
+
+SignalFfiError *signal_chat_connection_state_attempt(uint32_t *out, SignalConstPointerChatConnectionState state);
+
+SignalFfiError *signal_chat_connection_state_connected_since(uint64_t *out, SignalConstPointerChatConnectionState state);
+
+SignalFfiError *signal_chat_connection_state_disconnect_reason(uint8_t *out, SignalConstPointerChatConnectionState state);
+
 SignalFfiError *signal_server_message_ack_destroy(SignalMutPointerServerMessageAck p);
 
 SignalFfiError *signal_server_message_ack_send(SignalConstPointerServerMessageAck ack);
 
+SignalFfiError *signal_server_message_ack_send_status(SignalConstPointerServerMessageAck ack, uint16_t status);
+
+SignalFfiError *signal_key_transparency_export_state(SignalOwnedBuffer *out, SignalBorrowedBuffer account_data, SignalBorrowedBuffer last_distinguished_tree_head);
+
+SignalFfiError *signal_key_transparency_import_state(SignalMutPointerImportedKeyTransState *out, SignalBorrowedBuffer bytes);
+
+SignalFfiError *signal_imported_key_trans_state_destroy(SignalMutPointerImportedKeyTransState p);
+
+SignalFfiError *signal_imported_key_trans_state_get_account_data(SignalOwnedBuffer *out, SignalConstPointerImportedKeyTransState state);
+
+SignalFfiError *signal_imported_key_trans_state_get_last_distinguished_tree_head(SignalOwnedBuffer *out, SignalConstPointerImportedKeyTransState state);
+
 SignalFfiError *signal_tokio_async_context_destroy(SignalMutPointerTokioAsyncContext p);
 
 SignalFfiError *signal_tokio_async_context_new(SignalMutPointerTokioAsyncContext *out);
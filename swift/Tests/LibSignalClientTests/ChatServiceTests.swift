@@ -36,6 +36,8 @@ final class ChatServiceTests: TestCaseBase {
     private static let expectedMessage = "OK"
     private static let expectedContent = Data("content".utf8)
     private static let expectedHeaders = ["content-type": "application/octet-stream", "forwarded": "1.1.1.1"]
+    // Responses only carry allowlisted headers, so "forwarded" is dropped.
+    private static let expectedResponseHeaders = ["content-type": "application/octet-stream", "retry-after": "30"]
 
     func testConvertResponse() throws {
         do {
@@ -45,7 +47,7 @@ final class ChatServiceTests: TestCaseBase {
             let response = try ChatConnection.Response(consuming: rawResponse)
             XCTAssertEqual(Self.expectedStatus, response.status)
             XCTAssertEqual(Self.expectedMessage, response.message)
-            XCTAssertEqual(Self.expectedHeaders, response.headers)
+            XCTAssertEqual(Self.expectedResponseHeaders, response.headers)
             XCTAssert(response.body.isEmpty)
         }
 
@@ -56,7 +58,7 @@ final class ChatServiceTests: TestCaseBase {
             let response = try ChatConnection.Response(consuming: rawResponse)
             XCTAssertEqual(Self.expectedStatus, response.status)
             XCTAssertEqual(Self.expectedMessage, response.message)
-            XCTAssertEqual(Self.expectedHeaders, response.headers)
+            XCTAssertEqual(Self.expectedResponseHeaders, response.headers)
             XCTAssertEqual(Self.expectedContent, response.body)
         }
     }
@@ -68,10 +70,18 @@ final class ChatServiceTests: TestCaseBase {
         }
         do {
             try failWithError("AppExpired")
-        } catch SignalError.appExpired(_) {}
+        } catch SignalError.appExpired(let minimumVersion, let upgradeUrl, _) {
+            XCTAssertEqual(minimumVersion, "7.40.0")
+            XCTAssertEqual(upgradeUrl, "https://signal.org/install")
+        }
         do {
             try failWithError("DeviceDeregistered")
         } catch SignalError.deviceDeregistered(_) {}
+        do {
+            try failWithError("ProxyAuthFailed")
+        } catch SignalError.proxyFailure(let message) {
+            XCTAssertEqual(message, "Proxy connection failed: proxy rejected the provided credentials")
+        }
 
         do {
             try failWithError("WebSocketConnectionFailed")
@@ -91,6 +101,12 @@ final class ChatServiceTests: TestCaseBase {
         } catch SignalError.rateLimitedError(retryAfter: 42, let message) {
             XCTAssertEqual(message, "Rate limited; try again after 42s")
         }
+        do {
+            try failWithError("RateLimitChallenge")
+        } catch SignalError.rateLimitChallengeError(let token, let options, _) {
+            XCTAssertEqual(token, "fake token")
+            XCTAssertEqual(options, ["recaptcha"])
+        }
     }
 
     func testConvertSendError() throws {
@@ -101,6 +117,19 @@ final class ChatServiceTests: TestCaseBase {
         do {
             try failWithError("Disconnected")
         } catch SignalError.chatServiceInactive(_) {}
+        do {
+            try failWithError("ConnectionIdleTimeout")
+        } catch SignalError.chatServiceInactive(_) {}
+        do {
+            try failWithError("ConnectionLost")
+        } catch SignalError.connectionLost(let closeCode, _) {
+            XCTAssertEqual(closeCode, 4001)
+        }
+        do {
+            try failWithError("ConnectionLostWithoutCloseCode")
+        } catch SignalError.connectionLost(let closeCode, _) {
+            XCTAssertNil(closeCode)
+        }
 
         do {
             try failWithError("WebSocketConnectionReset")
@@ -133,6 +133,17 @@ final class NetTests: XCTestCase {
         // There's no feedback from this, we're just making sure it doesn't normally crash or throw.
         let net = Net(env: .staging, userAgent: userAgent)
         try net.networkDidChange()
+        try net.forceNetworkChange()
+    }
+
+    func testKeyTransparencyImportRejectsTruncatedState() {
+        XCTAssertThrowsError(try KeyTransparencyState(importing: Data())) { error in
+            guard case SignalError.invalidArgument(let message) = error else {
+                XCTFail("unexpected error: \(error)")
+                return
+            }
+            XCTAssert(message.contains("truncated"), message)
+        }
     }
 }
 